@@ -75,6 +75,54 @@ fn bench_filters(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_large_filters(c: &mut Criterion) {
+    let json: Value = serde_json::from_str(LARGE_JSON).unwrap();
+
+    let mut group = c.benchmark_group("large_filters");
+
+    let queries = [
+        ("literal", r#"$.items[?@.category == "books"]"#),
+        ("root_relative", "$.items[?@.price > $.items[0].price]"),
+        ("function", "$.items[?length(@.name) > 8]"),
+    ];
+
+    for (name, query_str) in queries {
+        let path = JsonPath::parse(query_str).unwrap();
+        group.bench_function(BenchmarkId::new("large", name), |b| {
+            b.iter(|| path.query(black_box(&json)))
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_root_queries(c: &mut Criterion) {
+    let items: Vec<Value> = (0..100_000)
+        .map(|i| serde_json::json!({"id": i, "price": i % 1000}))
+        .collect();
+    let json = serde_json::json!({
+        "config": {"limits": {"max_price": 500}, "tiers": [100, 500, 900]},
+        "items": items,
+    });
+
+    let mut group = c.benchmark_group("root_queries");
+    group.throughput(Throughput::Elements(100_000));
+
+    let queries = [
+        ("threshold", "$.items[?@.price < $.config.limits.max_price]"),
+        ("count", "$.items[?count($.config.tiers[?@ > 200]) == 2]"),
+    ];
+
+    for (name, query_str) in queries {
+        let path = JsonPath::parse(query_str).unwrap();
+        group.bench_function(BenchmarkId::new("100k", name), |b| {
+            b.iter(|| path.query(black_box(&json)))
+        });
+    }
+
+    group.finish();
+}
+
 fn bench_functions(c: &mut Criterion) {
     let json: Value = serde_json::from_str(SMALL_JSON).unwrap();
 
@@ -95,6 +143,33 @@ fn bench_functions(c: &mut Criterion) {
     group.finish();
 }
 
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+
+    let long = (0..200).fold(String::from("$"), |mut q, i| {
+        q.push_str(&format!(".member_{i}['quoted name {i}']"));
+        q
+    });
+    let queries = [
+        ("dotted", "$.store.book[0].author".to_string()),
+        ("quoted", "$['store']['book'][*]['title']".to_string()),
+        (
+            "filter",
+            r#"$.store.book[?@.price < 10 && @.category == "fiction"]"#.to_string(),
+        ),
+        ("long", long),
+    ];
+
+    for (name, query_str) in &queries {
+        group.throughput(Throughput::Bytes(query_str.len() as u64));
+        group.bench_with_input(BenchmarkId::new("query", name), query_str, |b, q| {
+            b.iter(|| JsonPath::parse(black_box(q)))
+        });
+    }
+
+    group.finish();
+}
+
 fn bench_by_json_size(c: &mut Criterion) {
     let small: Value = serde_json::from_str(SMALL_JSON).unwrap();
     let medium: Value = serde_json::from_str(MEDIUM_JSON).unwrap();
@@ -131,6 +206,8 @@ fn bench_descendant_chains(c: &mut Criterion) {
         ("single", "$..value"),
         ("double", "$..a..value"),
         ("triple", "$..a..a..value"),
+        ("index", "$..[0]"),
+        ("wildcard", "$..*"),
     ];
 
     for (name, query_str) in queries {
@@ -139,6 +216,18 @@ fn bench_descendant_chains(c: &mut Criterion) {
         });
     }
 
+    // Object-heavy document with many scalar leaves, which the traversal never visits
+    let large: Value = serde_json::from_str(LARGE_JSON).unwrap();
+    for (name, query_str) in [("index", "$..[0]"), ("name", "$..price")] {
+        let path = JsonPath::parse(query_str).unwrap();
+        group.bench_function(BenchmarkId::new("large", name), |b| {
+            b.iter(|| path.query(black_box(&large)))
+        });
+        group.bench_function(BenchmarkId::new("large_located", name), |b| {
+            b.iter(|| path.query_located(black_box(&large)))
+        });
+    }
+
     group.finish();
 }
 
@@ -212,7 +301,10 @@ criterion_group!(
     bench_basic_selectors,
     bench_advanced_selectors,
     bench_filters,
+    bench_large_filters,
+    bench_root_queries,
     bench_functions,
+    bench_parse,
     bench_by_json_size,
     bench_descendant_chains,
     bench_comparison,
@@ -0,0 +1,178 @@
+//! `--ascii-output`: non-ASCII characters of strings written as `\uXXXX` escapes
+
+use serde::Serialize;
+use serde_json::ser::Formatter;
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use std::io::{self, Write};
+
+/// How a character above U+FFFF is escaped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Astral {
+    /// `\ud83d\ude00`: the UTF-16 surrogate pair, as in JSON
+    SurrogatePair,
+    /// `\U0001f600`: YAML reads no surrogates, but has eight-digit escapes instead
+    Long,
+}
+
+/// `s` with every non-ASCII character escaped
+pub fn escape(s: &str, astral: Astral) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return Cow::Borrowed(s);
+    }
+    let mut escaped = String::with_capacity(s.len() * 2);
+    for c in s.chars() {
+        match (u32::from(c), astral) {
+            (0..0x80, _) => escaped.push(c),
+            (code @ 0x10000.., Astral::Long) => {
+                let _ = write!(escaped, "\\U{code:08x}");
+            }
+            _ => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    let _ = write!(escaped, "\\u{unit:04x}");
+                }
+            }
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Serialize `value` to `out` with `formatter`, escaping non-ASCII characters when `ascii`
+pub fn serialize<W: Write, T: Serialize + ?Sized, F: Formatter>(
+    out: W,
+    value: &T,
+    formatter: F,
+    ascii: bool,
+) -> io::Result<()> {
+    if ascii {
+        let formatter = AsciiFormatter {
+            inner: formatter,
+            astral: Astral::SurrogatePair,
+        };
+        value.serialize(&mut serde_json::Serializer::with_formatter(out, formatter))?;
+    } else {
+        value.serialize(&mut serde_json::Serializer::with_formatter(out, formatter))?;
+    }
+    Ok(())
+}
+
+/// Write `s` as a JSON string with every non-ASCII character escaped
+pub fn write_string<W: Write>(out: W, s: &str, astral: Astral) -> io::Result<()> {
+    let formatter = AsciiFormatter {
+        inner: serde_json::ser::CompactFormatter,
+        astral,
+    };
+    s.serialize(&mut serde_json::Serializer::with_formatter(out, formatter))?;
+    Ok(())
+}
+
+/// `inner` with the unescaped parts of strings escaped further
+struct AsciiFormatter<F> {
+    inner: F,
+    astral: Astral,
+}
+
+impl<F: Formatter> Formatter for AsciiFormatter<F> {
+    fn write_string_fragment<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        fragment: &str,
+    ) -> io::Result<()> {
+        writer.write_all(escape(fragment, self.astral).as_bytes())
+    }
+
+    fn begin_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_array(writer)
+    }
+
+    fn end_array<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array(writer)
+    }
+
+    fn begin_array_value<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_array_value(writer, first)
+    }
+
+    fn end_array_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_array_value(writer)
+    }
+
+    fn begin_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object(writer)
+    }
+
+    fn end_object<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object(writer)
+    }
+
+    fn begin_object_key<W: ?Sized + Write>(
+        &mut self,
+        writer: &mut W,
+        first: bool,
+    ) -> io::Result<()> {
+        self.inner.begin_object_key(writer, first)
+    }
+
+    fn end_object_key<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_key(writer)
+    }
+
+    fn begin_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.begin_object_value(writer)
+    }
+
+    fn end_object_value<W: ?Sized + Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        self.inner.end_object_value(writer)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::ser::{CompactFormatter, PrettyFormatter};
+    use serde_json::{Value, json};
+
+    fn to_string(value: &Value, formatter: impl Formatter) -> String {
+        let mut out = Vec::new();
+        serialize(&mut out, value, formatter, true).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("plain", Astral::SurrogatePair), "plain");
+        assert_eq!(escape("café", Astral::SurrogatePair), "caf\\u00e9");
+        assert_eq!(escape("東京", Astral::SurrogatePair), "\\u6771\\u4eac");
+        assert_eq!(escape("😀", Astral::SurrogatePair), "\\ud83d\\ude00");
+        assert_eq!(escape("😀", Astral::Long), "\\U0001f600");
+        assert_eq!(escape("\u{ffff}", Astral::Long), "\\uffff");
+    }
+
+    #[test]
+    fn test_serialize() {
+        let value = json!({"名前": "東京 🗼", "tab\t": ["é\n", 1]});
+        let compact = to_string(&value, CompactFormatter);
+        assert_eq!(
+            compact,
+            r#"{"tab\t":["\u00e9\n",1],"\u540d\u524d":"\u6771\u4eac \ud83d\uddfc"}"#
+        );
+        assert!(compact.is_ascii());
+        assert_eq!(serde_json::from_str::<Value>(&compact).unwrap(), value);
+        // Only the strings change, not the layout
+        let pretty = to_string(&value, PrettyFormatter::new());
+        assert!(pretty.is_ascii());
+        assert_eq!(serde_json::from_str::<Value>(&pretty).unwrap(), value);
+        assert_eq!(
+            pretty.lines().count(),
+            serde_json::to_string_pretty(&value)
+                .unwrap()
+                .lines()
+                .count()
+        );
+    }
+}
@@ -0,0 +1,304 @@
+//! Syntax-colored JSON output
+
+use crate::ascii;
+use serde_json::Value;
+use serde_json::ser::CompactFormatter;
+use std::io::{self, Write};
+
+/// Value of `--color`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(format!(
+                "invalid value '{s}' for '--color <WHEN>' (expected auto, always or never)"
+            )),
+        }
+    }
+
+    /// Whether output should be colored. `Auto` colors only a terminal, and only while
+    /// `NO_COLOR` is unset or empty.
+    pub fn enabled(self, is_terminal: bool, no_color: bool) -> bool {
+        match self {
+            Self::Auto => is_terminal && !no_color,
+            Self::Always => true,
+            Self::Never => false,
+        }
+    }
+}
+
+/// Escape sequences written before each kind of token, and `reset` after it
+pub struct Palette {
+    pub key: &'static str,
+    pub string: &'static str,
+    pub number: &'static str,
+    /// `null`, `true` and `false`
+    pub literal: &'static str,
+    /// Lines of `--diff`
+    pub removed: &'static str,
+    pub added: &'static str,
+    pub hunk: &'static str,
+    pub reset: &'static str,
+}
+
+impl Palette {
+    pub const ANSI: Self = Self {
+        key: "\x1b[1;34m",
+        string: "\x1b[32m",
+        number: "\x1b[36m",
+        literal: "\x1b[35m",
+        removed: "\x1b[31m",
+        added: "\x1b[32m",
+        hunk: "\x1b[36m",
+        reset: "\x1b[0m",
+    };
+}
+
+/// Serializes values with their keys and scalars wrapped in palette colors. With the colors
+/// removed the bytes match `serde_json::to_string`, or `to_string_pretty` for an indent of 2.
+pub struct ColorWriter<'p> {
+    palette: &'p Palette,
+    /// Spaces per level of pretty output, or `None` for compact output
+    indent: Option<usize>,
+    /// `--ascii-output`
+    ascii: bool,
+}
+
+impl<'p> ColorWriter<'p> {
+    pub fn new(palette: &'p Palette, indent: Option<usize>, ascii: bool) -> Self {
+        Self {
+            palette,
+            indent,
+            ascii,
+        }
+    }
+
+    pub fn write_value<W: Write>(&self, out: &mut W, value: &Value) -> io::Result<()> {
+        self.value(out, value, 0)
+    }
+
+    /// Write `items` as a JSON array
+    pub fn write_array<W: Write>(&self, out: &mut W, items: &[&Value]) -> io::Result<()> {
+        self.array(out, items.iter().copied(), items.len(), 0)
+    }
+
+    /// Write `entries` as a JSON object, keeping their order
+    pub fn write_entries<W: Write>(
+        &self,
+        out: &mut W,
+        entries: &[(String, Value)],
+    ) -> io::Result<()> {
+        let members = entries.iter().map(|(key, value)| (key, value));
+        self.object(out, members, entries.len(), 0)
+    }
+
+    fn value<W: Write>(&self, out: &mut W, value: &Value, depth: usize) -> io::Result<()> {
+        let palette = self.palette;
+        match value {
+            Value::Null => paint(out, palette.literal, palette.reset, "null"),
+            Value::Bool(b) => paint(out, palette.literal, palette.reset, b),
+            Value::Number(n) => paint(out, palette.number, palette.reset, n),
+            Value::String(s) => self.string(out, palette.string, s),
+            Value::Array(items) => self.array(out, items.iter(), items.len(), depth),
+            Value::Object(map) => self.object(out, map.iter(), map.len(), depth),
+        }
+    }
+
+    fn string<W: Write>(&self, out: &mut W, color: &str, s: &str) -> io::Result<()> {
+        out.write_all(color.as_bytes())?;
+        ascii::serialize(&mut *out, s, CompactFormatter, self.ascii)?;
+        out.write_all(self.palette.reset.as_bytes())
+    }
+
+    fn array<'v, W: Write>(
+        &self,
+        out: &mut W,
+        items: impl Iterator<Item = &'v Value>,
+        len: usize,
+        depth: usize,
+    ) -> io::Result<()> {
+        if len == 0 {
+            return out.write_all(b"[]");
+        }
+        out.write_all(b"[")?;
+        for (i, item) in items.enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            self.newline(out, depth + 1)?;
+            self.value(out, item, depth + 1)?;
+        }
+        self.newline(out, depth)?;
+        out.write_all(b"]")
+    }
+
+    fn object<'v, W: Write>(
+        &self,
+        out: &mut W,
+        members: impl Iterator<Item = (&'v String, &'v Value)>,
+        len: usize,
+        depth: usize,
+    ) -> io::Result<()> {
+        if len == 0 {
+            return out.write_all(b"{}");
+        }
+        out.write_all(b"{")?;
+        for (i, (key, value)) in members.enumerate() {
+            if i > 0 {
+                out.write_all(b",")?;
+            }
+            self.newline(out, depth + 1)?;
+            self.string(out, self.palette.key, key)?;
+            out.write_all(if self.indent.is_some() { b": " } else { b":" })?;
+            self.value(out, value, depth + 1)?;
+        }
+        self.newline(out, depth)?;
+        out.write_all(b"}")
+    }
+
+    fn newline<W: Write>(&self, out: &mut W, depth: usize) -> io::Result<()> {
+        if let Some(indent) = self.indent {
+            out.write_all(b"\n")?;
+            for _ in 0..depth * indent {
+                out.write_all(b" ")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn paint<W: Write>(
+    out: &mut W,
+    color: &str,
+    reset: &str,
+    token: impl std::fmt::Display,
+) -> io::Result<()> {
+    write!(out, "{color}{token}{reset}")
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const MARKERS: Palette = Palette {
+        key: "<k>",
+        string: "<s>",
+        number: "<n>",
+        literal: "<l>",
+        removed: "<->",
+        added: "<+>",
+        hunk: "<@>",
+        reset: "</>",
+    };
+
+    const PLAIN: Palette = Palette {
+        key: "",
+        string: "",
+        number: "",
+        literal: "",
+        removed: "",
+        added: "",
+        hunk: "",
+        reset: "",
+    };
+
+    fn render(palette: &Palette, indent: Option<usize>, value: &Value) -> String {
+        let mut out = Vec::new();
+        ColorWriter::new(palette, indent, false)
+            .write_value(&mut out, value)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_color_choice() {
+        use ColorChoice::*;
+        // (choice, is_terminal, no_color) -> enabled
+        let cases = [
+            (Auto, true, false, true),
+            (Auto, false, false, false),
+            (Auto, true, true, false),
+            (Auto, false, true, false),
+            (Always, false, false, true),
+            (Always, false, true, true),
+            (Never, true, false, false),
+        ];
+        for (choice, is_terminal, no_color, expected) in cases {
+            assert_eq!(
+                choice.enabled(is_terminal, no_color),
+                expected,
+                "{choice:?} terminal={is_terminal} no_color={no_color}"
+            );
+        }
+        assert_eq!(ColorChoice::parse("always"), Ok(Always));
+        assert!(ColorChoice::parse("yes").is_err());
+    }
+
+    #[test]
+    fn test_colored_snapshot() {
+        let value = json!({"a": [1, "x\n", null], "b": {"t": true}, "e": []});
+        assert_eq!(
+            render(&MARKERS, None, &value),
+            r#"{<k>"a"</>:[<n>1</>,<s>"x\n"</>,<l>null</>],<k>"b"</>:{<k>"t"</>:<l>true</>},<k>"e"</>:[]}"#
+        );
+        assert_eq!(
+            render(&MARKERS, Some(2), &value),
+            concat!(
+                "{\n",
+                "  <k>\"a\"</>: [\n",
+                "    <n>1</>,\n",
+                "    <s>\"x\\n\"</>,\n",
+                "    <l>null</>\n",
+                "  ],\n",
+                "  <k>\"b\"</>: {\n",
+                "    <k>\"t\"</>: <l>true</>\n",
+                "  },\n",
+                "  <k>\"e\"</>: []\n",
+                "}"
+            )
+        );
+    }
+
+    #[test]
+    fn test_layout_matches_serde_json() {
+        let value = json!({
+            "empty": {},
+            "nested": [[], [{"k\"ey": -1.5e-7}], {"x": [false, 0]}],
+            "unicode": "\u{1f}é"
+        });
+        assert_eq!(
+            render(&PLAIN, None, &value),
+            serde_json::to_string(&value).unwrap()
+        );
+        assert_eq!(
+            render(&PLAIN, Some(2), &value),
+            serde_json::to_string_pretty(&value).unwrap()
+        );
+
+        let items = [&value, &Value::Null];
+        let mut out = Vec::new();
+        ColorWriter::new(&PLAIN, Some(2), false)
+            .write_array(&mut out, &items)
+            .unwrap();
+        assert_eq!(out, serde_json::to_vec_pretty(&items).unwrap());
+
+        let mut out = Vec::new();
+        ColorWriter::new(&PLAIN, None, true)
+            .write_value(&mut out, &value)
+            .unwrap();
+        let mut expected = Vec::new();
+        ascii::serialize(&mut expected, &value, CompactFormatter, true).unwrap();
+        assert_eq!(out, expected);
+    }
+}
@@ -0,0 +1,267 @@
+//! `jpp completions SHELL`: completion scripts generated from the option table
+
+use crate::options::{OPTIONS, OptionSpec, SHELLS, ValueHint};
+use std::fmt::Write;
+
+/// Shell of `jpp completions`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+impl Shell {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "bash" => Ok(Self::Bash),
+            "zsh" => Ok(Self::Zsh),
+            "fish" => Ok(Self::Fish),
+            "powershell" => Ok(Self::Powershell),
+            _ => Err(format!(
+                "invalid shell '{s}' for 'completions' (expected {})",
+                SHELLS.join(", ")
+            )),
+        }
+    }
+}
+
+/// The completion script for `shell`
+pub fn script(shell: Shell) -> String {
+    match shell {
+        Shell::Bash => bash(),
+        Shell::Zsh => zsh(),
+        Shell::Fish => fish(),
+        Shell::Powershell => powershell(),
+    }
+}
+
+/// The short and long names of `option`
+fn names(option: &OptionSpec) -> Vec<String> {
+    let mut names: Vec<String> = option.short.map(|c| format!("-{c}")).into_iter().collect();
+    names.push(option.long.to_string());
+    names
+}
+
+/// Options taking a value, grouped by what completes it, in table order
+fn value_groups() -> Vec<(ValueHint, Vec<String>)> {
+    let mut groups: Vec<(ValueHint, Vec<String>)> = Vec::new();
+    for option in OPTIONS.iter().filter(|option| !option.values.is_empty()) {
+        match groups.iter_mut().find(|(hint, _)| *hint == option.hint) {
+            Some((_, group)) => group.extend(names(option)),
+            None => groups.push((option.hint, names(option))),
+        }
+    }
+    groups
+}
+
+fn bash() -> String {
+    let all: Vec<String> = OPTIONS.iter().flat_map(names).collect();
+    let mut cases = String::new();
+    for (hint, names) in value_groups() {
+        let reply = match hint {
+            ValueHint::Choice(words) => {
+                format!(
+                    "COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))",
+                    words.join(" ")
+                )
+            }
+            ValueHint::File => "COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+            // Free text: offer nothing rather than file names
+            ValueHint::Text => "COMPREPLY=()".to_string(),
+        };
+        let _ = writeln!(
+            cases,
+            "        {})\n            {reply}\n            return ;;",
+            names.join("|")
+        );
+    }
+    format!(
+        r#"# bash completion for jpp; load it with: source <(jpp completions bash)
+_jpp() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}" prev="${{COMP_WORDS[COMP_CWORD-1]}}"
+    if [[ $COMP_CWORD -eq 2 && $prev == completions ]]; then
+        COMPREPLY=($(compgen -W "{shells}" -- "$cur"))
+        return
+    fi
+    case "$prev" in
+{cases}    esac
+    if [[ $cur == -* ]]; then
+        COMPREPLY=($(compgen -W "{all}" -- "$cur"))
+    else
+        COMPREPLY=($(compgen -f -- "$cur"))
+    fi
+}}
+complete -o filenames -F _jpp jpp
+"#,
+        shells = SHELLS.join(" "),
+        all = all.join(" "),
+    )
+}
+
+fn zsh() -> String {
+    let mut specs = String::new();
+    for option in OPTIONS {
+        let description = option
+            .summary()
+            .replace('\\', "\\\\")
+            .replace('[', "\\[")
+            .replace(']', "\\]")
+            .replace('\'', "'\\''");
+        let mut values = String::new();
+        for value in option.values {
+            let action = match option.hint {
+                ValueHint::Choice(words) => format!("({})", words.join(" ")),
+                ValueHint::File => "_files".to_string(),
+                ValueHint::Text => " ".to_string(),
+            };
+            let _ = write!(values, ":{}:{action}", value.to_lowercase());
+        }
+        for name in names(option) {
+            let _ = writeln!(specs, "    '{name}[{description}]{values}' \\");
+        }
+    }
+    format!(
+        r#"#compdef jpp
+# zsh completion for jpp; save it as _jpp in a directory of $fpath
+
+_jpp() {{
+  if [[ $CURRENT -eq 3 && $words[2] == completions ]]; then
+    _values shell {shells}
+    return
+  fi
+  _arguments -s \
+{specs}    '*:file:_files'
+}}
+
+_jpp "$@"
+"#,
+        shells = SHELLS.join(" "),
+    )
+}
+
+fn fish() -> String {
+    let mut script =
+        String::from("# fish completion for jpp; load it with: jpp completions fish | source\n");
+    let _ = writeln!(
+        script,
+        "complete -c jpp -n __fish_use_subcommand -a completions -d 'Print a shell completion script'"
+    );
+    let _ = writeln!(
+        script,
+        "complete -c jpp -n '__fish_seen_subcommand_from completions' -x -a '{}'",
+        SHELLS.join(" ")
+    );
+    for option in OPTIONS {
+        let mut line = String::from("complete -c jpp");
+        if let Some(short) = option.short {
+            let _ = write!(line, " -s {short}");
+        }
+        let _ = write!(line, " -l {}", &option.long[2..]);
+        if !option.values.is_empty() {
+            match option.hint {
+                ValueHint::Choice(words) => {
+                    let _ = write!(line, " -x -a '{}'", words.join(" "));
+                }
+                ValueHint::File => line.push_str(" -r -F"),
+                ValueHint::Text => line.push_str(" -x"),
+            }
+        }
+        let description = option.summary().replace('\\', "\\\\").replace('\'', "\\'");
+        let _ = writeln!(script, "{line} -d '{description}'");
+    }
+    script
+}
+
+fn powershell() -> String {
+    let quote = |s: &str| format!("'{}'", s.replace('\'', "''"));
+    let mut options = String::new();
+    for option in OPTIONS {
+        for name in names(option) {
+            let _ = writeln!(
+                options,
+                "        @({}, {})",
+                quote(&name),
+                quote(&option.summary())
+            );
+        }
+    }
+    let mut choices = format!(
+        "        'completions' = @({})\n",
+        SHELLS
+            .iter()
+            .map(|s| quote(s))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    for (hint, names) in value_groups() {
+        if let ValueHint::Choice(words) = hint {
+            let words: Vec<String> = words.iter().map(|s| quote(s)).collect();
+            for name in names {
+                let _ = writeln!(
+                    choices,
+                    "        {} = @({})",
+                    quote(&name),
+                    words.join(", ")
+                );
+            }
+        }
+    }
+    format!(
+        r#"# PowerShell completion for jpp; load it with:
+# jpp completions powershell | Out-String | Invoke-Expression
+Register-ArgumentCompleter -Native -CommandName jpp -ScriptBlock {{
+    param($wordToComplete, $commandAst, $cursorPosition)
+    $words = @($commandAst.CommandElements | ForEach-Object {{ $_.ToString() }})
+    $previous = if ($wordToComplete) {{ $words[-2] }} else {{ $words[-1] }}
+    $choices = @{{
+{choices}    }}
+    $options = @(
+{options}    )
+    if ($choices.ContainsKey($previous)) {{
+        $choices[$previous] | Where-Object {{ $_ -like "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)
+        }}
+    }} elseif ($wordToComplete -like '-*') {{
+        $options | Where-Object {{ $_[0] -like "$wordToComplete*" }} | ForEach-Object {{
+            [System.Management.Automation.CompletionResult]::new($_[0], $_[0], 'ParameterName', $_[1])
+        }}
+    }}
+}}
+"#
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts() {
+        let bash = script(Shell::Bash);
+        assert!(bash.contains("--output)\n"));
+        assert!(bash.contains("compgen -W \"json ndjson json-seq yaml yaml-docs table markdown\""));
+        assert!(bash.contains("-f|--query-file|-o|--output-file)"));
+
+        let zsh = script(Shell::Zsh);
+        assert!(zsh.contains(
+            "'--color[Color output: auto (default), always or never]:when:(auto always never)' \\\n"
+        ));
+        assert!(
+            zsh.contains("'--arg[Define $NAME as a string for the queries]:name: :value: ' \\\n")
+        );
+
+        let fish = script(Shell::Fish);
+        assert!(fish.contains("complete -c jpp -s o -l output-file -r -F -d"));
+        assert!(fish.contains("complete -c jpp -l error-format -x -a 'human json' -d"));
+
+        let powershell = script(Shell::Powershell);
+        assert!(powershell.contains("'--explain-format' = @('human', 'json')"));
+        assert!(powershell.contains("@('-V', 'Show version')"));
+
+        assert_eq!(Shell::parse("powershell").unwrap(), Shell::Powershell);
+        assert!(Shell::parse("tcsh").is_err());
+    }
+}
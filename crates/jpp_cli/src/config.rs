@@ -0,0 +1,161 @@
+//! Defaults for common flags from `JPP_*` environment variables
+
+use crate::OutputFormat;
+use crate::color::ColorChoice;
+
+/// Largest value of `--indent`
+pub const MAX_INDENT: usize = 7;
+
+/// Value of `--indent` or `JPP_INDENT`
+pub fn parse_indent(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&indent| indent <= MAX_INDENT)
+        .ok_or_else(|| {
+            format!("invalid value '{value}' for '--indent <N>' (expected 0 to {MAX_INDENT})")
+        })
+}
+
+/// Value of `JPP_SORT_KEYS`
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value {
+        "1" | "true" | "yes" => Ok(true),
+        "" | "0" | "false" | "no" => Ok(false),
+        _ => Err(format!(
+            "invalid value '{value}' (expected 1, true, yes, 0, false or no)"
+        )),
+    }
+}
+
+/// Output settings with an environment default. Unset fields keep the built-in defaults.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Config {
+    /// `--indent`, or `JPP_INDENT`
+    pub indent: Option<usize>,
+    /// `--color`, or `JPP_COLOR`
+    pub color: Option<ColorChoice>,
+    /// `--output`, or `JPP_OUTPUT`
+    pub output: Option<OutputFormat>,
+    /// `--sort-keys`, or `JPP_SORT_KEYS`
+    pub sort_keys: Option<bool>,
+}
+
+impl Config {
+    /// `flags`, the settings given on the command line, with each one left unset taken from
+    /// its variable in `env`. A flag always overrides its variable. Invalid variables are
+    /// ignored, with a warning for each.
+    pub fn from_env_and_args(
+        env: impl Fn(&str) -> Option<String>,
+        flags: Config,
+    ) -> (Self, Vec<String>) {
+        let mut warnings = Vec::new();
+        let config = Config {
+            indent: flags
+                .indent
+                .or_else(|| var(&env, "JPP_INDENT", parse_indent, &mut warnings)),
+            color: flags
+                .color
+                .or_else(|| var(&env, "JPP_COLOR", ColorChoice::parse, &mut warnings)),
+            output: flags
+                .output
+                .or_else(|| var(&env, "JPP_OUTPUT", OutputFormat::parse, &mut warnings)),
+            sort_keys: flags
+                .sort_keys
+                .or_else(|| var(&env, "JPP_SORT_KEYS", parse_switch, &mut warnings)),
+        };
+        (config, warnings)
+    }
+}
+
+/// Value of the variable `name` in `env`, if set and valid
+fn var<T>(
+    env: &impl Fn(&str) -> Option<String>,
+    name: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+    warnings: &mut Vec<String>,
+) -> Option<T> {
+    let value = env(name)?;
+    parse(&value)
+        .map_err(|e| warnings.push(format!("ignoring {name}: {e}")))
+        .ok()
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn env(vars: &'static [(&'static str, &'static str)]) -> impl Fn(&str) -> Option<String> {
+        |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_precedence() {
+        let vars = env(&[
+            ("JPP_INDENT", "4"),
+            ("JPP_COLOR", "never"),
+            ("JPP_OUTPUT", "yaml"),
+            ("JPP_SORT_KEYS", "1"),
+        ]);
+        let from_env = Config {
+            indent: Some(4),
+            color: Some(ColorChoice::Never),
+            output: Some(OutputFormat::Yaml),
+            sort_keys: Some(true),
+        };
+        let flags = Config {
+            indent: Some(1),
+            color: Some(ColorChoice::Always),
+            output: Some(OutputFormat::Ndjson),
+            sort_keys: Some(true),
+        };
+
+        // Environment only, flags only, and both
+        assert_eq!(
+            Config::from_env_and_args(&vars, Config::default()),
+            (from_env, vec![])
+        );
+        assert_eq!(Config::from_env_and_args(env(&[]), flags), (flags, vec![]));
+        assert_eq!(Config::from_env_and_args(&vars, flags), (flags, vec![]));
+        let partial = Config {
+            color: Some(ColorChoice::Auto),
+            ..Config::default()
+        };
+        assert_eq!(
+            Config::from_env_and_args(&vars, partial).0,
+            Config {
+                color: Some(ColorChoice::Auto),
+                ..from_env
+            }
+        );
+    }
+
+    #[test]
+    fn test_invalid_variables() {
+        let vars = env(&[
+            ("JPP_INDENT", "9"),
+            ("JPP_COLOR", "blue"),
+            ("JPP_SORT_KEYS", ""),
+        ]);
+        let (config, warnings) = Config::from_env_and_args(vars, Config::default());
+        assert_eq!(
+            config,
+            Config {
+                sort_keys: Some(false),
+                ..Config::default()
+            }
+        );
+        assert_eq!(
+            warnings,
+            [
+                "ignoring JPP_INDENT: invalid value '9' for '--indent <N>' (expected 0 to 7)",
+                "ignoring JPP_COLOR: invalid value 'blue' for '--color <WHEN>' (expected auto, always or never)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Errors of a run as written to stderr: a `jpp:` message, or with `--error-format json` one
+//! JSON object per error
+
+use crate::validate::{ErrorFormat, write_caret};
+use jpp_core::ParseErrorKind;
+use serde_json::json;
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Class of a [`Diagnostic`], the `kind` of its JSON object
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    /// Invalid command-line arguments
+    Usage,
+    /// A query that does not parse
+    QueryParse,
+    /// A query using an undefined `--arg` variable
+    UndefinedVariable,
+    /// An input that cannot be read or parsed
+    Input,
+    /// Output that cannot be written
+    Output,
+    /// `--max-input-bytes`, `--max-results`, `--max-depth` or `--timeout`
+    Limit,
+    /// Any other failure
+    Other,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Usage => "usage_error",
+            Self::QueryParse => "query_parse_error",
+            Self::UndefinedVariable => "undefined_variable",
+            Self::Input => "input_error",
+            Self::Output => "output_error",
+            Self::Limit => "limit_exceeded",
+            Self::Other => "error",
+        }
+    }
+}
+
+/// Name of a syntax error category in JSON reports, e.g. `unexpected_token`
+pub fn parse_error_name(kind: ParseErrorKind) -> &'static str {
+    match kind {
+        ParseErrorKind::InvalidWhitespace => "invalid_whitespace",
+        ParseErrorKind::MissingRoot => "missing_root",
+        ParseErrorKind::UnexpectedCharacter => "unexpected_character",
+        ParseErrorKind::UnexpectedToken => "unexpected_token",
+        ParseErrorKind::UnexpectedEndOfInput => "unexpected_end_of_input",
+        ParseErrorKind::UnclosedBracket => "unclosed_bracket",
+        ParseErrorKind::UnclosedParen => "unclosed_paren",
+        ParseErrorKind::UnterminatedString => "unterminated_string",
+        ParseErrorKind::InvalidEscape => "invalid_escape",
+        ParseErrorKind::ControlCharacter => "control_character",
+        ParseErrorKind::InvalidNumber => "invalid_number",
+        ParseErrorKind::InvalidIndex => "invalid_index",
+        ParseErrorKind::IndexOutOfRange => "index_out_of_range",
+        ParseErrorKind::LiteralOnlyFilter => "literal_only_filter",
+        ParseErrorKind::LiteralLogicalOperand => "literal_logical_operand",
+        ParseErrorKind::NonSingularComparison => "non_singular_comparison",
+        ParseErrorKind::FunctionResultType => "function_result_type",
+        ParseErrorKind::ArithmeticType => "arithmetic_type",
+        ParseErrorKind::FunctionArity => "function_arity",
+        ParseErrorKind::FunctionArgumentType => "function_argument_type",
+        ParseErrorKind::UnknownFunction => "unknown_function",
+        ParseErrorKind::InvalidRegex => "invalid_regex",
+        ParseErrorKind::LimitExceeded => "limit_exceeded",
+    }
+}
+
+/// An error, with what is known of where it happened
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub kind: Kind,
+    pub message: String,
+    /// The query in error, after variable substitution
+    pub query: Option<String>,
+    /// Characters of `query` in error
+    pub span: Option<Range<usize>>,
+    /// File the error is about: an input, or the `-f` file of the query
+    pub file: Option<String>,
+    /// Exit status of the run
+    pub exit_code: u8,
+}
+
+impl Diagnostic {
+    pub fn new(kind: Kind, message: impl Into<String>, exit_code: u8) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            query: None,
+            span: None,
+            file: None,
+            exit_code,
+        }
+    }
+
+    pub fn with_file(mut self, file: Option<&str>) -> Self {
+        self.file = file.map(str::to_string);
+        self
+    }
+
+    /// Write the error to `out`. Human messages show the query with carets under the span.
+    pub fn write(&self, out: &mut impl Write, format: ErrorFormat) -> io::Result<()> {
+        match format {
+            ErrorFormat::Human => {
+                writeln!(out, "jpp: {}", self.message)?;
+                match (&self.query, &self.span) {
+                    (Some(query), Some(span)) => write_caret(out, query, span),
+                    _ => Ok(()),
+                }
+            }
+            ErrorFormat::Json => {
+                let object = json!({
+                    "kind": self.kind.name(),
+                    "message": self.message,
+                    "position": self.span.as_ref().map(|span| span.start),
+                    "query": self.query,
+                    "file": self.file,
+                    "exit_code": self.exit_code,
+                });
+                serde_json::to_writer(&mut *out, &object)?;
+                writeln!(out)
+            }
+        }
+    }
+
+    /// Write the error to stderr; failing that, there is nowhere left to report it
+    pub fn report(&self, format: ErrorFormat) {
+        let _ = self.write(&mut io::stderr().lock(), format);
+    }
+}
+
+/// A bare message is an error of [`Kind::Other`]
+impl From<String> for Diagnostic {
+    fn from(message: String) -> Self {
+        Self::new(Kind::Other, message, crate::EXIT_ERROR)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_write() {
+        let diagnostic = Diagnostic {
+            query: Some("$.a[?".to_string()),
+            span: Some(5..5),
+            ..Diagnostic::new(Kind::QueryParse, "bad query", 2)
+        };
+        let mut out = Vec::new();
+        diagnostic.write(&mut out, ErrorFormat::Human).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "jpp: bad query\n  $.a[?\n       ^\n"
+        );
+
+        let mut out = Vec::new();
+        diagnostic.write(&mut out, ErrorFormat::Json).unwrap();
+        let object: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(
+            object,
+            json!({
+                "kind": "query_parse_error",
+                "message": "bad query",
+                "position": 5,
+                "query": "$.a[?",
+                "file": null,
+                "exit_code": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_error_name() {
+        assert_eq!(
+            parse_error_name(ParseErrorKind::MissingRoot),
+            "missing_root"
+        );
+        assert_eq!(
+            parse_error_name(ParseErrorKind::UnexpectedEndOfInput),
+            "unexpected_end_of_input"
+        );
+    }
+}
@@ -0,0 +1,370 @@
+//! `--diff`: what changed between the results of the queries against two inputs
+
+use crate::color::Palette;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, Write};
+
+/// Value of `--diff-format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// A unified diff of the output for each input
+    Text,
+    /// The results added, removed and changed, by location
+    Json,
+}
+
+impl DiffFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid value '{s}' for '--diff-format <FORMAT>' (expected text or json)"
+            )),
+        }
+    }
+}
+
+/// Lines kept around each change of a unified diff
+const CONTEXT: usize = 3;
+/// Edits past which lines between the first and the last change are all shown as removed then
+/// added, instead of searching further for the fewest edits
+const MAX_EDITS: isize = 2000;
+
+/// What becomes of each line of a line diff
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Change {
+    Same,
+    Removed,
+    Added,
+}
+
+/// The changes turning the lines `old` into `new`, in order
+fn changes(old: &[&str], new: &[&str]) -> Vec<Change> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+
+    let mut changes = vec![Change::Same; prefix];
+    match fewest_changes(old, new) {
+        Some(middle) => changes.extend(middle),
+        None => {
+            changes.extend(old.iter().map(|_| Change::Removed));
+            changes.extend(new.iter().map(|_| Change::Added));
+        }
+    }
+    changes.extend((0..suffix).map(|_| Change::Same));
+    changes
+}
+
+/// Myers' O(ND) search for the fewest changes, or `None` past [`MAX_EDITS`]. A path through
+/// the grid of lines moves right for a removed line, down for an added one and diagonally
+/// for a line both keep; `trace[d]` holds the furthest x reached with `d` edits on each
+/// diagonal `k = x - y` from `-d` to `d`, or -1.
+fn fewest_changes(old: &[&str], new: &[&str]) -> Option<Vec<Change>> {
+    let (n, m) = (old.len() as isize, new.len() as isize);
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    for d in 0..=(n + m).min(MAX_EDITS) {
+        let mut furthest = vec![-1; 2 * d as usize + 1];
+        for k in (-d..=d).step_by(2) {
+            let Some((_, mut x)) = snake_start(trace.last(), d, k, n, m) else {
+                continue;
+            };
+            while x < n && x - k < m && old[x as usize] == new[(x - k) as usize] {
+                x += 1;
+            }
+            furthest[(k + d) as usize] = x;
+            if x == n && x - k == m {
+                trace.push(furthest);
+                return Some(backtrack(&trace, n, m));
+            }
+        }
+        trace.push(furthest);
+    }
+    None
+}
+
+/// Where the path with `d` edits on diagonal `k` goes on diagonally, after an added line from
+/// diagonal `k + 1` or a removed one from `k - 1`, whichever gets further inside the grid.
+/// Returns that diagonal and x, given the furthest x with `d - 1` edits in `previous`.
+fn snake_start(
+    previous: Option<&Vec<isize>>,
+    d: isize,
+    k: isize,
+    n: isize,
+    m: isize,
+) -> Option<(isize, isize)> {
+    let Some(previous) = previous else {
+        // No edits: the start of the grid
+        return Some((0, 0));
+    };
+    let furthest = |k: isize| {
+        (k.abs() < d)
+            .then(|| previous[(k + d - 1) as usize])
+            .filter(|&x| x >= 0)
+    };
+    let added = furthest(k + 1).filter(|&x| x - k <= m).map(|x| (k + 1, x));
+    let removed = furthest(k - 1)
+        .map(|x| x + 1)
+        .filter(|&x| x <= n)
+        .map(|x| (k - 1, x));
+    match (added, removed) {
+        (Some(added), Some(removed)) if removed.1 > added.1 => Some(removed),
+        (added, removed) => added.or(removed),
+    }
+}
+
+/// The changes along the path found by [`fewest_changes`], from its end back to the start
+fn backtrack(trace: &[Vec<isize>], n: isize, m: isize) -> Vec<Change> {
+    let mut changes = Vec::new();
+    let (mut x, mut y) = (n, m);
+    for d in (1..trace.len() as isize).rev() {
+        let previous = &trace[d as usize - 1];
+        let k = x - y;
+        let Some((from, start)) = snake_start(Some(previous), d, k, n, m) else {
+            break;
+        };
+        changes.extend((start..x).map(|_| Change::Same));
+        changes.push(if from == k + 1 {
+            Change::Added
+        } else {
+            Change::Removed
+        });
+        x = previous[(from + d - 1) as usize];
+        y = x - from;
+    }
+    changes.extend((0..x).map(|_| Change::Same));
+    changes.reverse();
+    changes
+}
+
+/// `start,count` of a hunk header, where an empty range starts at the line before it
+fn range(start: usize, count: usize) -> String {
+    match count {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    }
+}
+
+/// Write a unified diff of the lines of `old` and `new`, labeled `old_label` and `new_label`,
+/// or nothing when they are the same. Returns whether they differ.
+pub fn write_unified(
+    out: &mut impl Write,
+    (old_label, old): (&str, &str),
+    (new_label, new): (&str, &str),
+    palette: Option<&Palette>,
+) -> io::Result<bool> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let changes = changes(&old, &new);
+    // Lines of `old` and `new` before each change
+    let mut before = Vec::with_capacity(changes.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for change in &changes {
+        before.push((i, j));
+        match change {
+            Change::Same => (i, j) = (i + 1, j + 1),
+            Change::Removed => i += 1,
+            Change::Added => j += 1,
+        }
+    }
+    before.push((i, j));
+
+    let edited: Vec<usize> = (0..changes.len())
+        .filter(|&i| changes[i] != Change::Same)
+        .collect();
+    if edited.is_empty() {
+        return Ok(false);
+    }
+    writeln!(out, "--- {old_label}")?;
+    writeln!(out, "+++ {new_label}")?;
+    // Changes closer than twice the context share a hunk
+    let mut hunks: Vec<(usize, usize)> = Vec::new();
+    for &i in &edited {
+        match hunks.last_mut() {
+            Some((_, end)) if i <= *end + 2 * CONTEXT => *end = i + 1,
+            _ => hunks.push((i, i + 1)),
+        }
+    }
+    for (start, end) in hunks {
+        let start = start.saturating_sub(CONTEXT);
+        let end = (end + CONTEXT).min(changes.len());
+        let ((old_start, new_start), (old_end, new_end)) = (before[start], before[end]);
+        let header = format!(
+            "@@ -{} +{} @@",
+            range(old_start, old_end - old_start),
+            range(new_start, new_end - new_start)
+        );
+        write_line(out, palette, |p| p.hunk, &header)?;
+        for (change, &(i, j)) in changes[start..end].iter().zip(&before[start..end]) {
+            match change {
+                Change::Same => writeln!(out, " {}", old[i])?,
+                Change::Removed => {
+                    write_line(out, palette, |p| p.removed, &format!("-{}", old[i]))?
+                }
+                Change::Added => write_line(out, palette, |p| p.added, &format!("+{}", new[j]))?,
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Write `line` in the color of the palette that `color` picks, if any
+fn write_line(
+    out: &mut impl Write,
+    palette: Option<&Palette>,
+    color: fn(&Palette) -> &'static str,
+    line: &str,
+) -> io::Result<()> {
+    match palette {
+        Some(palette) => writeln!(out, "{}{line}{}", color(palette), palette.reset),
+        None => writeln!(out, "{line}"),
+    }
+}
+
+/// The `--diff-format json` report on the results located in `old` and `new`, and whether
+/// they differ: the results at locations only `new` has, those at locations only `old` has,
+/// and those at the same location with another value. Each list follows the order of its input.
+pub fn report(old: &[(String, &Value)], new: &[(String, &Value)]) -> (Value, bool) {
+    let new_values: HashMap<&str, &Value> = new
+        .iter()
+        .map(|(location, value)| (location.as_str(), *value))
+        .collect();
+    let old_locations: HashSet<&str> = old.iter().map(|(location, _)| location.as_str()).collect();
+    let mut removed = Vec::new();
+    let mut changed = Vec::new();
+    for (location, value) in old {
+        match new_values.get(location.as_str()) {
+            None => removed.push(json!({"path": location, "value": value})),
+            Some(new) if new != value => {
+                changed.push(json!({"path": location, "old": value, "new": new}));
+            }
+            Some(_) => {}
+        }
+    }
+    let added: Vec<Value> = new
+        .iter()
+        .filter(|(location, _)| !old_locations.contains(location.as_str()))
+        .map(|(location, value)| json!({"path": location, "value": value}))
+        .collect();
+    let differ = !(added.is_empty() && removed.is_empty() && changed.is_empty());
+    (
+        json!({"added": added, "removed": removed, "changed": changed}),
+        differ,
+    )
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn unified(old: &str, new: &str) -> String {
+        let mut out = Vec::new();
+        let differ = write_unified(&mut out, ("a", old), ("b", new), None).unwrap();
+        assert_eq!(differ, old != new);
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_changes() {
+        use Change::{Added, Removed, Same};
+        let lines = |s: &'static str| s.split(' ').collect::<Vec<_>>();
+        assert_eq!(
+            changes(&lines("a b c a b b a"), &lines("c b a b a c")),
+            [
+                Removed, Removed, Same, Added, Same, Same, Removed, Same, Added
+            ]
+        );
+        assert_eq!(changes(&lines("a b"), &lines("a b")), [Same, Same]);
+        assert_eq!(changes(&[], &lines("a")), [Added]);
+        assert_eq!(changes(&lines("a b c"), &lines("x y")).len(), 5);
+
+        // Each change script turns one into the other with the fewest edits, n + m - 2 LCS
+        let words = [
+            "a b a c b",
+            "b b c a",
+            "c a b",
+            "a",
+            "a a a b",
+            "b a b c c a b",
+        ];
+        for old in words.map(lines) {
+            for new in words.map(lines) {
+                let changes = changes(&old, &new);
+                let (mut i, mut j, mut kept) = (0, 0, Vec::new());
+                for change in &changes {
+                    match change {
+                        Same => {
+                            assert_eq!(old[i], new[j]);
+                            kept.push(old[i]);
+                            (i, j) = (i + 1, j + 1);
+                        }
+                        Removed => i += 1,
+                        Added => j += 1,
+                    }
+                }
+                assert_eq!((i, j), (old.len(), new.len()));
+                let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+                for i in 0..old.len() {
+                    for j in 0..new.len() {
+                        lcs[i + 1][j + 1] = if old[i] == new[j] {
+                            lcs[i][j] + 1
+                        } else {
+                            lcs[i][j + 1].max(lcs[i + 1][j])
+                        };
+                    }
+                }
+                assert_eq!(kept.len(), lcs[old.len()][new.len()], "{old:?} {new:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_unified() {
+        assert_eq!(unified("a\nb\n", "a\nb\n"), "");
+        let old: String = (1..=20).map(|i| format!("{i}\n")).collect();
+        let new: String = (1..=21)
+            .filter(|&i| i != 12)
+            .map(|i| match i {
+                2 => "two\n".to_string(),
+                i => format!("{i}\n"),
+            })
+            .collect();
+        assert_eq!(
+            unified(&old, &new),
+            "--- a\n+++ b\n\
+             @@ -1,5 +1,5 @@\n 1\n-2\n+two\n 3\n 4\n 5\n\
+             @@ -9,7 +9,6 @@\n 9\n 10\n 11\n-12\n 13\n 14\n 15\n\
+             @@ -18,3 +17,4 @@\n 18\n 19\n 20\n+21\n"
+        );
+        assert_eq!(unified("", "a\n"), "--- a\n+++ b\n@@ -0,0 +1 @@\n+a\n");
+    }
+
+    #[test]
+    fn test_report() {
+        let (one, two, three) = (json!(1), json!(2), json!(3));
+        let old = [("$[0]".to_string(), &one), ("$[1]".to_string(), &two)];
+        let new = [("$[1]".to_string(), &three), ("$[2]".to_string(), &one)];
+        assert_eq!(
+            report(&old, &new),
+            (
+                json!({
+                    "added": [{"path": "$[2]", "value": 1}],
+                    "removed": [{"path": "$[0]", "value": 1}],
+                    "changed": [{"path": "$[1]", "old": 2, "new": 3}],
+                }),
+                true
+            )
+        );
+        assert!(!report(&old, &old).1);
+    }
+}
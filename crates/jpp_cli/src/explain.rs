@@ -0,0 +1,175 @@
+//! `--explain`: a readable breakdown of a parsed query
+//!
+//! Each segment is listed with its selectors, and filter expressions are drawn as a tree, one
+//! node per line, children indented under their operator. Paths inside filters are expanded
+//! the same way when they hold a filter of their own.
+
+use jpp_core::JsonPath;
+use jpp_core::ast::{Expr, LogicalOp, Segment, Selector};
+use std::fmt::Arguments;
+use std::io::{self, Write};
+
+/// Write the breakdown of `path`, starting with the query in normalized bracket notation
+pub fn write_explanation(out: &mut impl Write, path: &JsonPath) -> io::Result<()> {
+    writeln!(out, "{path}")?;
+    if path.segments.is_empty() {
+        return line(out, 0, format_args!("the root node, with no segments"));
+    }
+    segments(out, &path.segments, 0)
+}
+
+fn line(out: &mut impl Write, depth: usize, text: Arguments) -> io::Result<()> {
+    writeln!(out, "{:width$}{text}", "", width = depth * 2)
+}
+
+fn segments(out: &mut impl Write, segments: &[Segment], depth: usize) -> io::Result<()> {
+    for (i, segment) in segments.iter().enumerate() {
+        let kind = match segment {
+            Segment::Child(_) => "child",
+            Segment::Descendant(_) => "descendant",
+        };
+        line(
+            out,
+            depth,
+            format_args!("segment {}: {kind} {segment}", i + 1),
+        )?;
+        for selector in segment.selectors() {
+            self::selector(out, selector, depth + 1)?;
+        }
+    }
+    Ok(())
+}
+
+fn selector(out: &mut impl Write, selector: &Selector, depth: usize) -> io::Result<()> {
+    match selector {
+        Selector::Name(_) => line(out, depth, format_args!("name {selector}")),
+        Selector::Index(_) => line(out, depth, format_args!("index {selector}")),
+        Selector::Wildcard => line(out, depth, format_args!("wildcard *")),
+        Selector::Slice { start, end, step } => {
+            let part = |name: &str, value: &Option<i64>| match value {
+                Some(value) => format!("{name} {value}"),
+                None => format!("default {name}"),
+            };
+            line(
+                out,
+                depth,
+                format_args!(
+                    "slice {selector} ({}, {}, {})",
+                    part("start", start),
+                    part("end", end),
+                    part("step", step)
+                ),
+            )
+        }
+        Selector::Filter(expr) => {
+            line(out, depth, format_args!("filter {selector}"))?;
+            self::expr(out, expr, depth + 1, true)
+        }
+    }
+}
+
+/// Write `expr` and its operands. `test` tells whether it is in a test position, where a query
+/// checks that a node exists rather than producing a value.
+fn expr(out: &mut impl Write, expr: &Expr, depth: usize, test: bool) -> io::Result<()> {
+    match expr {
+        Expr::CurrentNode => line(out, depth, format_args!("current node @")),
+        Expr::RootNode => line(out, depth, format_args!("root node $")),
+        Expr::Path { segments, .. } => {
+            let kind = if test { "exists" } else { "query" };
+            line(out, depth, format_args!("{kind} {expr}"))?;
+            let nested = segments
+                .iter()
+                .flat_map(Segment::selectors)
+                .any(|selector| matches!(selector, Selector::Filter(_)));
+            if nested {
+                self::segments(out, segments, depth + 1)?;
+            }
+            Ok(())
+        }
+        Expr::Literal(literal) => line(out, depth, format_args!("literal {}", literal.literal)),
+        Expr::Comparison { left, op, right } => {
+            line(out, depth, format_args!("comparison {op}"))?;
+            self::expr(out, left, depth + 1, false)?;
+            self::expr(out, right, depth + 1, false)
+        }
+        Expr::Logical { op, .. } => {
+            let name = match op {
+                LogicalOp::And => "and",
+                LogicalOp::Or => "or",
+            };
+            line(out, depth, format_args!("{name} {op}"))?;
+            // `a && b && c` nests to the left; list the operands side by side
+            let mut operands = Vec::new();
+            flatten(expr, *op, &mut operands);
+            operands
+                .into_iter()
+                .try_for_each(|operand| self::expr(out, operand, depth + 1, true))
+        }
+        Expr::Not(inner) => {
+            line(out, depth, format_args!("not !"))?;
+            self::expr(out, inner, depth + 1, true)
+        }
+        Expr::Arithmetic { left, op, right } => {
+            line(out, depth, format_args!("arithmetic {op}"))?;
+            self::expr(out, left, depth + 1, false)?;
+            self::expr(out, right, depth + 1, false)
+        }
+        Expr::FunctionCall { function, args, .. } => {
+            line(out, depth, format_args!("function {}()", function.name()))?;
+            args.iter()
+                .try_for_each(|arg| self::expr(out, arg, depth + 1, false))
+        }
+    }
+}
+
+/// Operands of a chain of `op`
+fn flatten<'a>(expr: &'a Expr, op: LogicalOp, operands: &mut Vec<&'a Expr>) {
+    match expr {
+        Expr::Logical {
+            left,
+            op: inner,
+            right,
+        } if *inner == op => {
+            flatten(left, op, operands);
+            flatten(right, op, operands);
+        }
+        operand => operands.push(operand),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn explain(query: &str) -> String {
+        let mut out = Vec::new();
+        write_explanation(&mut out, &JsonPath::parse(query).unwrap()).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_explain() {
+        assert_eq!(explain("$"), "$\nthe root node, with no segments\n");
+        assert_eq!(
+            explain("$..a[*, ::-1][?@.b || !@.c || @.d == $.e]"),
+            concat!(
+                "$..['a'][*, ::-1][?@['b'] || !@['c'] || @['d'] == $['e']]\n",
+                "segment 1: descendant ..['a']\n",
+                "  name 'a'\n",
+                "segment 2: child [*, ::-1]\n",
+                "  wildcard *\n",
+                "  slice ::-1 (default start, default end, step -1)\n",
+                "segment 3: child [?@['b'] || !@['c'] || @['d'] == $['e']]\n",
+                "  filter ?@['b'] || !@['c'] || @['d'] == $['e']\n",
+                "    or ||\n",
+                "      exists @['b']\n",
+                "      not !\n",
+                "        exists @['c']\n",
+                "      comparison ==\n",
+                "        query @['d']\n",
+                "        query $['e']\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! `--follow`: read a file as it grows, like `tail -f`
+
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// How long to wait before looking again at a file with nothing new in it
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Reads a file without ever reaching its end: at the end it waits for more to be appended.
+/// A file truncated in place is read again from its start, and a file replaced by another,
+/// as log rotation does, is reopened.
+pub struct Follow {
+    path: PathBuf,
+    file: File,
+    /// Bytes of `file` read so far
+    position: u64,
+}
+
+impl Follow {
+    /// Open the file at `path`, at its end unless `from_start`
+    pub fn open(path: &str, from_start: bool) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let position = if from_start {
+            0
+        } else {
+            file.seek(SeekFrom::End(0))?
+        };
+        Ok(Self {
+            path: PathBuf::from(path),
+            file,
+            position,
+        })
+    }
+
+    /// Go back to the start of a truncated file, or open the file now at `path` if it was
+    /// replaced. Returns whether there is anything new to read.
+    fn rotate(&mut self) -> io::Result<bool> {
+        // Between the rename of the old file and the creation of the new one, there is none
+        let Ok(current) = fs::metadata(&self.path) else {
+            return Ok(false);
+        };
+        if !same_file(&self.file.metadata()?, &current) {
+            if let Ok(file) = File::open(&self.path) {
+                self.file = file;
+                self.position = 0;
+                return Ok(true);
+            }
+            return Ok(false);
+        }
+        if current.len() < self.position {
+            self.position = self.file.seek(SeekFrom::Start(0))?;
+            return Ok(true);
+        }
+        Ok(current.len() > self.position)
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    (a.dev(), a.ino()) == (b.dev(), b.ino())
+}
+
+/// Without inode numbers, only truncation is noticed
+#[cfg(not(unix))]
+fn same_file(_: &Metadata, _: &Metadata) -> bool {
+    true
+}
+
+impl Read for Follow {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let read = self.file.read(buf)?;
+            if read > 0 {
+                self.position += read as u64;
+                return Ok(read);
+            }
+            if !self.rotate()? {
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_rotation() {
+        let dir = std::env::temp_dir().join(format!("jpp-follow-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        fs::write(&path, "old\n").unwrap();
+        let mut follow = Follow::open(path.to_str().unwrap(), false).unwrap();
+        let mut read = |len: usize| {
+            let mut buf = vec![0; len];
+            follow.read_exact(&mut buf).unwrap();
+            String::from_utf8(buf).unwrap()
+        };
+
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"new\n")
+            .unwrap();
+        assert_eq!(read(4), "new\n");
+        // Truncated in place, then replaced
+        fs::write(&path, "a\n").unwrap();
+        assert_eq!(read(2), "a\n");
+        fs::write(dir.join("app.log.1"), "b\n").unwrap();
+        fs::rename(dir.join("app.log.1"), &path).unwrap();
+        assert_eq!(read(2), "b\n");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,470 @@
+//! Inputs fetched from `http://` and `https://` URLs, with ureq over rustls
+//!
+//! Requests are plain GETs. Gzip content encoding is decoded, and up to five redirects are
+//! followed here rather than by ureq, so that `--header` values are dropped once a redirect
+//! leaves the scheme, host and port of the URL given.
+
+#[cfg(feature = "http")]
+use std::io;
+use std::io::BufRead;
+use std::time::Duration;
+
+/// Redirects followed before giving up
+#[cfg(feature = "http")]
+const MAX_REDIRECTS: usize = 5;
+
+/// Default of `--http-timeout`
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Whether the FILE argument `file` is a URL rather than a path
+pub fn is_url(file: &str) -> bool {
+    split_scheme(file).is_some()
+}
+
+/// Whether `url` starts with `https://` rather than `http://`, in any case, and the rest of it
+fn split_scheme(url: &str) -> Option<(bool, &str)> {
+    let (scheme, rest) = url.split_once("://")?;
+    if scheme.eq_ignore_ascii_case("http") {
+        Some((false, rest))
+    } else if scheme.eq_ignore_ascii_case("https") {
+        Some((true, rest))
+    } else {
+        None
+    }
+}
+
+/// Whether a `Location` value starts with a scheme (RFC 3986 section 3.1), making it absolute
+#[cfg(feature = "http")]
+fn has_scheme(location: &str) -> bool {
+    let Some(end) = location.find([':', '/', '?', '#']) else {
+        return false;
+    };
+    let scheme = &location[..end];
+    location[end..].starts_with(':')
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'))
+}
+
+/// Value of `--header`: `Name: value`
+pub fn parse_header(value: &str) -> Result<(String, String), String> {
+    match value.split_once(':') {
+        Some((name, header))
+            if !name.is_empty()
+                && name.bytes().all(|b| b.is_ascii_graphic())
+                && !value.contains(['\r', '\n']) =>
+        {
+            Ok((name.to_string(), header.trim().to_string()))
+        }
+        _ => Err(format!(
+            "invalid value '{value}' for '--header <HEADER>' (expected 'Name: value')"
+        )),
+    }
+}
+
+/// How URL inputs are requested
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Client {
+    /// `--header`: extra request headers
+    pub headers: Vec<(String, String)>,
+    /// `--http-timeout`: for connecting, sending the request and receiving the response headers
+    pub timeout: Duration,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self {
+            headers: Vec::new(),
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl Client {
+    /// GET `url` and return its body, decoded. A status other than 2xx is an error.
+    pub fn get(&self, url: &str) -> Result<Box<dyn BufRead>, String> {
+        let first = Url::parse(url)?;
+        let agent = self.agent();
+        let mut location = first.clone();
+        for _ in 0..=MAX_REDIRECTS {
+            let mut request = agent.get(location.to_string());
+            // `--header` values such as credentials are only sent where the URL points
+            if location.same_origin(&first) {
+                for (name, value) in &self.headers {
+                    request = request.header(name, value);
+                }
+            }
+            let response = request
+                .call()
+                .map_err(|e| format!("error fetching '{url}': {e}"))?;
+            let status = response.status();
+            match status.as_u16() {
+                200..=299 => {
+                    return Ok(Box::new(io::BufReader::new(
+                        response.into_body().into_reader(),
+                    )));
+                }
+                301 | 302 | 303 | 307 | 308 => {
+                    let Some(target) = response
+                        .headers()
+                        .get("location")
+                        .and_then(|target| target.to_str().ok())
+                    else {
+                        return Err(format!(
+                            "error fetching '{url}': HTTP {} without a Location",
+                            status.as_u16()
+                        ));
+                    };
+                    location = location.join(target)?;
+                }
+                code => {
+                    return Err(format!(
+                        "error fetching '{url}': HTTP {code} {}",
+                        status.canonical_reason().unwrap_or_default()
+                    ));
+                }
+            }
+        }
+        Err(format!(
+            "error fetching '{url}': more than {MAX_REDIRECTS} redirects"
+        ))
+    }
+
+    /// Redirects and error statuses are left to `get`
+    fn agent(&self) -> ureq::Agent {
+        let timeout = Some(self.timeout);
+        ureq::Agent::config_builder()
+            .http_status_as_error(false)
+            .max_redirects(0)
+            // Redirects are the only repeated requests
+            .max_idle_connections(0)
+            .user_agent(concat!("jpp/", env!("CARGO_PKG_VERSION")))
+            .accept("application/json, */*")
+            .timeout_resolve(timeout)
+            .timeout_connect(timeout)
+            .timeout_send_request(timeout)
+            .timeout_recv_response(timeout)
+            .build()
+            .into()
+    }
+}
+
+/// An `http://` or `https://` URL, split to follow redirects
+#[cfg(feature = "http")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Url {
+    https: bool,
+    host: String,
+    port: u16,
+    /// Path and query, starting with `/`
+    target: String,
+}
+
+#[cfg(feature = "http")]
+impl Url {
+    fn parse(url: &str) -> Result<Self, String> {
+        let (https, rest) = split_scheme(url)
+            .ok_or_else(|| format!("cannot fetch '{url}': not an http:// or https:// URL"))?;
+        let default_port = if https { 443 } else { 80 };
+        // The fragment stays in the browser
+        let rest = rest.split_once('#').map_or(rest, |(rest, _)| rest);
+        let (authority, target) = match rest.find(['/', '?']) {
+            Some(start) if rest[start..].starts_with('?') => {
+                (&rest[..start], format!("/{}", &rest[start..]))
+            }
+            Some(start) => (&rest[..start], rest[start..].to_string()),
+            None => (rest, "/".to_string()),
+        };
+        let invalid = || format!("cannot fetch '{url}': invalid host");
+        if authority.contains('@') {
+            return Err(format!(
+                "cannot fetch '{url}': credentials in URLs are not supported; use --header 'Authorization: ...'"
+            ));
+        }
+        let (host, port) = match authority.rsplit_once(':') {
+            // An IPv6 address without a port
+            Some((_, port)) if port.ends_with(']') => (authority, default_port),
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (authority, default_port),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            https,
+            host: host.to_ascii_lowercase(),
+            port,
+            target,
+        })
+    }
+
+    /// Host, and port unless it is the default for the scheme
+    fn authority(&self) -> String {
+        let host = if self.host.contains(':') {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        };
+        match (self.https, self.port) {
+            (false, 80) | (true, 443) => host,
+            (_, port) => format!("{host}:{port}"),
+        }
+    }
+
+    /// Whether both URLs have the same scheme, host and port
+    fn same_origin(&self, other: &Self) -> bool {
+        (self.https, &self.host, self.port) == (other.https, &other.host, other.port)
+    }
+
+    /// The URL a `Location` header points to from this one
+    fn join(&self, location: &str) -> Result<Self, String> {
+        if has_scheme(location) {
+            return Self::parse(location);
+        }
+        if location.starts_with("//") {
+            // Network-path reference: another host with the current scheme
+            let scheme = if self.https { "https:" } else { "http:" };
+            return Self::parse(&format!("{scheme}{location}"));
+        }
+        let location = location
+            .split_once('#')
+            .map_or(location, |(location, _)| location);
+        let target = if location.starts_with('/') {
+            location.to_string()
+        } else if location.starts_with('?') {
+            let path = self.target.split('?').next().unwrap_or("/");
+            format!("{path}{location}")
+        } else {
+            // Relative to the directory of the current path
+            let path = self.target.split('?').next().unwrap_or("/");
+            let dir = path.rsplit_once('/').map_or("", |(dir, _)| dir);
+            format!("{dir}/{location}")
+        };
+        Ok(Self {
+            target,
+            ..self.clone()
+        })
+    }
+}
+
+#[cfg(feature = "http")]
+impl std::fmt::Display for Url {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let scheme = if self.https { "https" } else { "http" };
+        write!(f, "{scheme}://{}{}", self.authority(), self.target)
+    }
+}
+
+#[cfg(not(feature = "http"))]
+impl Client {
+    pub fn get(&self, url: &str) -> Result<Box<dyn BufRead>, String> {
+        Err(format!(
+            "cannot fetch '{url}': jpp was built without the 'http' feature"
+        ))
+    }
+}
+
+#[cfg(all(test, feature = "http"))]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serve each of `responses` to one connection in turn, and return the base URL and the
+    /// requests received, in lowercase
+    fn serve(responses: Vec<Vec<u8>>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let mut requests = Vec::new();
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0; 1024];
+                while !request.ends_with(b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                requests.push(String::from_utf8(request).unwrap().to_ascii_lowercase());
+                stream.write_all(&response).unwrap();
+            }
+            requests
+        });
+        (base, server)
+    }
+
+    fn response(head: &str, body: &[u8]) -> Vec<u8> {
+        let mut response = format!("HTTP/1.1 {head}\r\n\r\n").into_bytes();
+        response.extend_from_slice(body);
+        response
+    }
+
+    fn body(client: &Client, url: &str) -> Result<String, String> {
+        let mut body = String::new();
+        client.get(url)?.read_to_string(&mut body).unwrap();
+        Ok(body)
+    }
+
+    #[test]
+    fn test_get() {
+        let (base, server) = serve(vec![
+            response("200 OK\r\nContent-Length: 8", b"{\"a\": 1}trailing"),
+            response(
+                "200 OK\r\nTransfer-Encoding: chunked",
+                b"4;ext=1\r\n{\"b\"\r\n5\r\n: [2]\r\n1\r\n}\r\n0\r\nX-Trailer: 1\r\n\r\n",
+            ),
+            response("200 OK", b"[\"until closed\"]"),
+        ]);
+        let client = Client {
+            headers: vec![("Authorization".to_string(), "Bearer t0ken".to_string())],
+            ..Client::default()
+        };
+        assert_eq!(
+            body(&client, &format!("{base}/a?x=1#top")).unwrap(),
+            "{\"a\": 1}"
+        );
+        assert_eq!(body(&client, &base).unwrap(), "{\"b\": [2]}");
+        assert_eq!(body(&client, &base).unwrap(), "[\"until closed\"]");
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].starts_with("get /a?x=1 http/1.1\r\n"));
+        assert!(requests[0].contains("\r\nhost: 127.0.0.1:"));
+        assert!(requests[0].contains("\r\nauthorization: bearer t0ken\r\n"));
+        assert!(requests[0].contains("\r\nuser-agent: jpp/"));
+        assert!(requests[1].starts_with("get / http/1.1\r\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "gzip")]
+    fn test_gzip() {
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(b"{\"gzip\": true}").unwrap();
+        let gzip = gzip.finish().unwrap();
+        let (base, server) = serve(vec![response(
+            &format!(
+                "200 OK\r\nContent-Encoding: gzip\r\nContent-Length: {}",
+                gzip.len()
+            ),
+            &gzip,
+        )]);
+        assert_eq!(body(&Client::default(), &base).unwrap(), "{\"gzip\": true}");
+        assert!(server.join().unwrap()[0].contains("\r\naccept-encoding: gzip\r\n"));
+    }
+
+    #[test]
+    fn test_errors_and_redirects() {
+        let (base, server) = serve(vec![
+            response("404 Not Found\r\nContent-Length: 0", b""),
+            response(
+                "302 Found\r\nLocation: /moved/here\r\nContent-Length: 0",
+                b"",
+            ),
+            response("301 Moved Permanently\r\nLocation: there", b""),
+            response("200 OK\r\nContent-Length: 4", b"true"),
+        ]);
+        let client = Client {
+            headers: vec![("X-Api-Key".to_string(), "abc".to_string())],
+            ..Client::default()
+        };
+        assert_eq!(
+            body(&client, &format!("{base}/missing")),
+            Err(format!(
+                "error fetching '{base}/missing': HTTP 404 Not Found"
+            ))
+        );
+        assert_eq!(body(&client, &format!("{base}/old")).unwrap(), "true");
+        let requests = server.join().unwrap();
+        assert!(requests[2].starts_with("get /moved/here http/1.1\r\n"));
+        assert!(requests[3].starts_with("get /moved/there http/1.1\r\n"));
+        // The same host and port keep the headers
+        assert!(requests[3].contains("\r\nx-api-key: abc\r\n"));
+
+        // Nothing listens on the port of a closed listener
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        assert!(
+            body(&client, &format!("http://127.0.0.1:{port}/"))
+                .unwrap_err()
+                .starts_with(&format!("error fetching 'http://127.0.0.1:{port}/': "))
+        );
+    }
+
+    #[test]
+    fn test_cross_origin_redirect() {
+        let (other, other_server) = serve(vec![response("200 OK\r\nContent-Length: 2", b"[]")]);
+        let (base, server) = serve(vec![response(
+            &format!("302 Found\r\nLocation: {other}/data\r\nContent-Length: 0"),
+            b"",
+        )]);
+        let client = Client {
+            headers: vec![
+                ("Authorization".to_string(), "Bearer t0ken".to_string()),
+                ("X-Api-Key".to_string(), "abc".to_string()),
+            ],
+            ..Client::default()
+        };
+        assert_eq!(body(&client, &format!("{base}/data")).unwrap(), "[]");
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains("\r\nauthorization: bearer t0ken\r\n"));
+        // Another port on the same host is another origin
+        let requests = other_server.join().unwrap();
+        assert!(requests[0].starts_with("get /data http/1.1\r\n"));
+        assert!(!requests[0].contains("authorization"));
+        assert!(!requests[0].contains("x-api-key"));
+    }
+
+    #[test]
+    fn test_url() {
+        let url = Url::parse("http://[::1]:8080/a/b?q=1").unwrap();
+        assert_eq!((url.host.as_str(), url.port), ("::1", 8080));
+        assert_eq!(url.to_string(), "http://[::1]:8080/a/b?q=1");
+        assert_eq!(Url::parse("http://example.com?q").unwrap().target, "/?q");
+        assert_eq!(
+            Url::parse("http://Example.com").unwrap().to_string(),
+            "http://example.com/"
+        );
+        let https = Url::parse("https://example.com/a#top").unwrap();
+        assert_eq!(
+            (https.port, https.to_string().as_str()),
+            (443, "https://example.com/a")
+        );
+        assert!(https.same_origin(&https.join("/b").unwrap()));
+        assert!(!https.same_origin(&Url::parse("http://example.com/a").unwrap()));
+        assert!(!https.same_origin(&Url::parse("https://example.com:8443/a").unwrap()));
+        assert!(!https.same_origin(&Url::parse("https://api.example.com/a").unwrap()));
+        assert_eq!(url.join("c").unwrap().target, "/a/c");
+        assert_eq!(url.join("?p=2#x").unwrap().target, "/a/b?p=2");
+        // A URL in the query doesn't make the reference absolute
+        assert_eq!(
+            url.join("/login?next=https://x").unwrap().to_string(),
+            "http://[::1]:8080/login?next=https://x"
+        );
+        // Network-path references keep the scheme and change the host
+        let cdn = https.join("//cdn.example.com/data").unwrap();
+        assert_eq!(cdn.to_string(), "https://cdn.example.com/data");
+        assert!(!https.same_origin(&cdn));
+        assert_eq!(
+            url.join("HTTPS://Example.com:8443/x").unwrap().to_string(),
+            "https://example.com:8443/x"
+        );
+        assert!(url.join("ftp://example.com/").is_err());
+        assert!(is_url("HTTP://example.com/") && !is_url("data/http://x"));
+        assert!(Url::parse("http://user:pw@example.com/").is_err());
+        assert!(Url::parse("http://:80/").is_err());
+        assert!(Url::parse("ftp://example.com/").is_err());
+        assert_eq!(
+            parse_header("X-Api-Key:  abc "),
+            Ok(("X-Api-Key".to_string(), "abc".to_string()))
+        );
+        assert!(parse_header("no colon").is_err());
+        assert!(parse_header("X: a\r\nInjected: 1").is_err());
+    }
+}
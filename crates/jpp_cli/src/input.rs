@@ -0,0 +1,1287 @@
+//! Reading input documents
+
+use crate::follow::Follow;
+use crate::http::Client;
+use crate::jsonc;
+#[cfg(feature = "toml")]
+use crate::limits::Nested;
+use crate::limits::{DepthScan, LimitExceeded, Nesting};
+use serde::de::{self, Deserializer, SeqAccess, Visitor};
+use serde_json::Value;
+use std::cell::Cell;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read};
+use std::time::{Duration, Instant};
+
+/// Where a document comes from
+#[derive(Clone, Copy)]
+pub enum Input<'a> {
+    Stdin,
+    File(&'a str),
+    /// An `http://` or `https://` URL, fetched with the client
+    Url(&'a str, &'a Client),
+    /// `--follow`: a file read as it grows, from its start or its end
+    Follow {
+        path: &'a str,
+        from_start: bool,
+    },
+    /// `--null-input`: a `null` document, without reading anything
+    Null,
+}
+
+impl<'a> Input<'a> {
+    /// Name shown by `--with-filename`
+    pub fn label(self) -> &'a str {
+        match self {
+            Input::Stdin => "(standard input)",
+            Input::File(path) | Input::Url(path, _) | Input::Follow { path, .. } => path,
+            Input::Null => "(null input)",
+        }
+    }
+
+    /// Path of a file input, or the URL of a fetched one
+    pub fn path(self) -> Option<&'a str> {
+        match self {
+            Input::File(path) | Input::Url(path, _) | Input::Follow { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+
+    /// Open the input, decompressing it on the fly when it is gzip or zstd compressed, and
+    /// adding its reads to `meter` when given. With `nesting`, the reads also count the nesting
+    /// of its documents for `--max-depth`.
+    fn open(
+        self,
+        meter: Option<&'a ReadMeter>,
+        nesting: Option<Nesting>,
+    ) -> Result<Box<dyn BufRead + 'a>, String> {
+        let mut reader: Box<dyn BufRead + 'a> = match self {
+            Input::Stdin => Box::new(io::stdin().lock()),
+            Input::File(path) => match File::open(path) {
+                Ok(file) => Box::new(BufReader::new(file)),
+                Err(e) => return Err(self.read_error(&e)),
+            },
+            Input::Url(url, client) => client.get(url)?,
+            Input::Follow { path, from_start } => match Follow::open(path, from_start) {
+                Ok(follow) => Box::new(BufReader::new(follow)),
+                Err(e) => return Err(self.read_error(&e)),
+            },
+            Input::Null => return Ok(Box::new(io::empty())),
+        };
+        let compression = match self {
+            // Nothing may have been appended yet to look at
+            Input::Follow { .. } => None,
+            _ => match self.name().and_then(Compression::from_extension) {
+                Some(compression) => Some(compression),
+                None => {
+                    Compression::from_magic(reader.fill_buf().map_err(|e| self.read_error(&e))?)
+                }
+            },
+        };
+        let reader = match compression {
+            Some(compression) => self.decompress(reader, compression)?,
+            None => reader,
+        };
+        Ok(match meter {
+            Some(meter) => Box::new(BufReader::new(Metered {
+                inner: reader,
+                meter,
+                scan: meter
+                    .max_depth
+                    .zip(nesting)
+                    .map(|(max, nesting)| DepthScan::new(max, nesting)),
+            })),
+            None => reader,
+        })
+    }
+
+    fn decompress(
+        self,
+        reader: Box<dyn BufRead + 'a>,
+        compression: Compression,
+    ) -> Result<Box<dyn BufRead + 'a>, String> {
+        match compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(BufReader::new(
+                flate2::bufread::MultiGzDecoder::new(reader),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => match ruzstd::decoding::StreamingDecoder::new(reader) {
+                Ok(decoder) => Ok(Box::new(BufReader::new(decoder))),
+                Err(e) => Err(self.read_error(&io::Error::other(e))),
+            },
+            #[allow(unreachable_patterns)]
+            _ => {
+                drop(reader);
+                let name = compression.name();
+                Err(format!(
+                    "cannot read {name}-compressed input{}: jpp was built without the '{name}' feature",
+                    self.location()
+                ))
+            }
+        }
+    }
+
+    fn read_error(self, e: &io::Error) -> String {
+        match self {
+            Input::File(path) | Input::Follow { path, .. } => {
+                format!("error reading file '{path}': {e}")
+            }
+            Input::Url(url, _) => format!("error reading '{url}': {e}"),
+            _ => format!("error reading stdin: {e}"),
+        }
+    }
+
+    /// File name with the extensions that tell the format and compression: the path of a
+    /// file, or the path of a URL without its query and fragment
+    fn name(self) -> Option<&'a str> {
+        match self {
+            Input::File(path) => Some(path),
+            Input::Url(url, _) => url.split(['?', '#']).next(),
+            _ => None,
+        }
+    }
+
+    /// Format of a file or URL named `*.yaml`, `*.yml` or `*.toml`, also when compressed, and
+    /// JSON otherwise
+    pub fn detect_format(self) -> InputFormat {
+        let Some(path) = self.name() else {
+            return InputFormat::Json;
+        };
+        let path = match Compression::from_extension(path) {
+            Some(_) => path.rsplit_once('.').map_or(path, |(stem, _)| stem),
+            None => path,
+        };
+        match path.rsplit_once('.') {
+            Some((_, "yaml" | "yml")) => InputFormat::Yaml,
+            Some((_, "toml")) => InputFormat::Toml,
+            Some((_, "jsonc")) => InputFormat::Jsonc,
+            _ => InputFormat::Json,
+        }
+    }
+
+    /// Name of the compression of a file, recognized by extension or magic bytes, or `None` for
+    /// plain input
+    pub fn compression_name(self) -> Result<Option<&'static str>, String> {
+        let Input::File(path) = self else {
+            return Ok(None);
+        };
+        if let Some(compression) = Compression::from_extension(path) {
+            return Ok(Some(compression.name()));
+        }
+        let mut header = Vec::new();
+        File::open(path)
+            .and_then(|file| file.take(4).read_to_end(&mut header))
+            .map_err(|e| self.read_error(&e))?;
+        Ok(Compression::from_magic(&header).map(Compression::name))
+    }
+
+    /// Where parse errors happened, for error messages: ` in 'path'` for files and URLs
+    fn location(self) -> String {
+        match self {
+            Input::File(path) | Input::Url(path, _) | Input::Follow { path, .. } => {
+                format!(" in '{path}'")
+            }
+            _ => String::new(),
+        }
+    }
+}
+
+/// Where standard input is connected, behind a trait so that tests can stand in for a terminal
+pub trait Console {
+    fn stdin_is_terminal(&self) -> bool;
+}
+
+/// The standard input of the process
+pub struct ProcessConsole;
+
+impl Console for ProcessConsole {
+    fn stdin_is_terminal(&self) -> bool {
+        io::stdin().is_terminal()
+    }
+}
+
+/// Compression format of an input
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Compression {
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    fn from_extension(path: &str) -> Option<Self> {
+        let (_, extension) = path.rsplit_once('.')?;
+        match extension {
+            "gz" => Some(Self::Gzip),
+            "zst" | "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    /// Recognize the magic bytes at the start of `header`, for stdin and files without a
+    /// compression extension. No JSON document starts with either.
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&[0x1f, 0x8b]) {
+            Some(Self::Gzip)
+        } else if header.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Some(Self::Zstd)
+        } else {
+            None
+        }
+    }
+
+    /// Name of the format, which is also the name of the cargo feature that reads it
+    fn name(self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+/// Bytes read from inputs and the time spent reading them, decompression included
+#[derive(Clone, Copy, Default)]
+pub struct ReadStats {
+    pub bytes: u64,
+    pub time: Duration,
+}
+
+/// Counts the bytes read from an input and the time spent reading them, and stops reading
+/// past `--max-input-bytes` or at a document nested deeper than `--max-depth`
+#[derive(Default)]
+pub struct ReadMeter {
+    pub stats: Cell<ReadStats>,
+    pub max_bytes: Option<u64>,
+    pub max_depth: Option<usize>,
+    /// Whether a document of the input nests deeper than `max_depth`
+    pub too_deep: Cell<bool>,
+}
+
+impl ReadMeter {
+    /// The limit that stopped reading, if any
+    pub fn exceeded(&self) -> Option<LimitExceeded> {
+        if self.too_deep.get() {
+            return self.max_depth.map(LimitExceeded::Depth);
+        }
+        self.max_bytes
+            .filter(|&max| self.stats.get().bytes > max)
+            .map(LimitExceeded::InputBytes)
+    }
+
+    /// Start over for the next input
+    pub fn reset(&self) {
+        self.stats.take();
+        self.too_deep.set(false);
+    }
+}
+
+/// Adds the reads of `inner` to `meter`, and follows their nesting with `scan`. It sits under a
+/// `BufReader`, so it times whole buffer fills rather than every small read of a parser.
+struct Metered<'m, R> {
+    inner: R,
+    meter: &'m ReadMeter,
+    scan: Option<DepthScan>,
+}
+
+impl<R: Read> Read for Metered<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let start = Instant::now();
+        let read = self.inner.read(buf);
+        let mut stats = self.meter.stats.get();
+        stats.time += start.elapsed();
+        if let Ok(n) = read {
+            stats.bytes += n as u64;
+            if let Some(scan) = &mut self.scan
+                && scan.scan(&buf[..n]).is_err()
+            {
+                self.meter.too_deep.set(true);
+            }
+        }
+        self.meter.stats.set(stats);
+        if let Some(limit) = self.meter.exceeded() {
+            return Err(io::Error::other(limit));
+        }
+        read
+    }
+}
+
+/// Value of `--input-format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputFormat {
+    /// Each input is one document
+    Json,
+    /// Each input is one document, which may have comments and trailing commas
+    Jsonc,
+    /// Each non-empty line of an input is a document
+    Ndjson,
+    /// Each document of a YAML stream is a document
+    Yaml,
+    /// Each input is one TOML document
+    Toml,
+    /// `--raw-input`: each line of an input is a string document
+    Raw,
+    /// Each record of an RFC 7464 JSON text sequence is a document
+    JsonSeq,
+}
+
+impl InputFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "json" => Ok(Self::Json),
+            "jsonc" => Ok(Self::Jsonc),
+            "ndjson" => Ok(Self::Ndjson),
+            "yaml" => Ok(Self::Yaml),
+            "toml" => Ok(Self::Toml),
+            "raw" => Ok(Self::Raw),
+            "json-seq" => Ok(Self::JsonSeq),
+            _ => Err(format!(
+                "invalid value '{s}' for '--input-format <FORMAT>' (expected json, jsonc, ndjson, yaml, toml, raw or json-seq)"
+            )),
+        }
+    }
+
+    /// Name of the format in messages
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Json => "JSON",
+            Self::Jsonc => "JSONC",
+            Self::Ndjson => "NDJSON",
+            Self::Yaml => "YAML",
+            Self::Toml => "TOML",
+            Self::Raw => "raw text",
+            Self::JsonSeq => "JSON text sequence",
+        }
+    }
+}
+
+/// Read and parse a whole input as one document
+pub fn load_document(input: Input, meter: Option<&ReadMeter>) -> Result<Value, String> {
+    if let Input::Null = input {
+        return Ok(Value::Null);
+    }
+    let text = read_text(input, meter, Some(Nesting::Json))?;
+    serde_json::from_str(&text).map_err(|e| {
+        // Several documents back to back, as some tools print them
+        let hint = if e.to_string().starts_with("trailing characters") {
+            "; use --multi to read several concatenated documents"
+        } else {
+            ""
+        };
+        format!("error parsing JSON{}: {e}{hint}", input.location())
+    })
+}
+
+/// Read and parse a whole input as one JSONC document, with comments and trailing commas
+pub fn load_jsonc_document(input: Input, meter: Option<&ReadMeter>) -> Result<Value, String> {
+    if let Input::Null = input {
+        return Ok(Value::Null);
+    }
+    let parse_error = |e: String| format!("error parsing JSONC{}: {e}", input.location());
+    let text =
+        jsonc::strip(&read_text(input, meter, Some(Nesting::Jsonc))?).map_err(parse_error)?;
+    serde_json::from_str(&text).map_err(|e| parse_error(e.to_string()))
+}
+
+fn read_text(
+    input: Input,
+    meter: Option<&ReadMeter>,
+    nesting: Option<Nesting>,
+) -> Result<String, String> {
+    let mut text = String::new();
+    input
+        .open(meter, nesting)?
+        .read_to_string(&mut text)
+        .map_err(|e| input.read_error(&e))?;
+    Ok(text)
+}
+
+/// Read and parse every document of a YAML stream, in order
+///
+/// Aliases and merge keys are resolved, tags are dropped, and non-string keys become their
+/// JSON text, so each document is projected onto JSON.
+#[cfg(feature = "yaml")]
+pub fn load_yaml_documents(input: Input, meter: Option<&ReadMeter>) -> Result<Vec<Value>, String> {
+    if let Input::Null = input {
+        return Ok(vec![Value::Null]);
+    }
+    let text = read_text(input, meter, None)?;
+    parse_yaml_documents(&text, meter)
+        .map_err(|e| format!("error parsing YAML{}: {e}", input.location()))
+}
+
+#[cfg(not(feature = "yaml"))]
+pub fn load_yaml_documents(input: Input, _: Option<&ReadMeter>) -> Result<Vec<Value>, String> {
+    Err(format!(
+        "cannot read YAML input{}: jpp was built without the 'yaml' feature",
+        input.location()
+    ))
+}
+
+/// Arrays and objects a YAML document may nest, as many as serde_json allows in JSON, so
+/// that its values can be dropped and written without overflowing the stack
+#[cfg(feature = "yaml")]
+const YAML_RECURSION_LIMIT: usize = 128;
+
+/// Nodes that aliases may copy in a YAML stream, against alias bombs that grow exponentially
+#[cfg(feature = "yaml")]
+const YAML_ALIAS_LIMIT: usize = 1 << 20;
+
+/// Parse a YAML stream into one value per document, stopping at a document nested deeper than
+/// the `--max-depth` of `meter`
+///
+/// Values are built from the parser events on a stack rather than through a YAML tree, so no
+/// nesting turns into recursion.
+#[cfg(feature = "yaml")]
+pub fn parse_yaml_documents(text: &str, meter: Option<&ReadMeter>) -> Result<Vec<Value>, String> {
+    use yaml_rust2::{Event, ScanError};
+    let max_depth = meter.and_then(|meter| meter.max_depth);
+    let mut parser = yaml_rust2::parser::Parser::new_from_str(text);
+    let mut builder = YamlBuilder::default();
+    let mut started = 0;
+    loop {
+        let (event, mark) = parser.next_token().map_err(|e| e.to_string())?;
+        let error = |info: &str| ScanError::new(mark, info).to_string();
+        match event {
+            Event::StreamEnd => return Ok(builder.documents),
+            Event::DocumentStart => started = builder.documents.len(),
+            // An empty document
+            Event::DocumentEnd if builder.documents.len() == started => {
+                builder.documents.push(Value::Null);
+            }
+            Event::SequenceStart(anchor, _) | Event::MappingStart(anchor, _) => {
+                let depth = builder.open.len() + 1;
+                if let Some(max) = max_depth
+                    && depth > max
+                {
+                    return Err(too_deep(meter, &LimitExceeded::Depth(max)));
+                }
+                if depth > YAML_RECURSION_LIMIT {
+                    return Err(error("recursion limit exceeded"));
+                }
+                let collection = match event {
+                    Event::SequenceStart(..) => Collection::Sequence(Vec::new()),
+                    _ => Collection::Mapping {
+                        object: serde_json::Map::new(),
+                        key: None,
+                        merged: Vec::new(),
+                    },
+                };
+                builder.open.push(OpenNode {
+                    anchor,
+                    nodes: 1,
+                    collection,
+                });
+            }
+            Event::SequenceEnd | Event::MappingEnd => {
+                if let Some(node) = builder.open.pop() {
+                    builder.insert(node.anchor, node.nodes, node.collection.close())?;
+                }
+            }
+            Event::Scalar(value, style, anchor, tag) => {
+                builder.insert(anchor, 1, yaml_scalar(value, style, tag))?;
+            }
+            Event::Alias(id) => {
+                let (value, nodes) = builder.anchors.get(&id).cloned().unwrap_or_default();
+                builder.aliased += nodes;
+                if builder.aliased > YAML_ALIAS_LIMIT {
+                    return Err(error("aliases copy too many nodes"));
+                }
+                builder.insert(0, nodes, value)?;
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Note on `meter` that a document converted to JSON nests past `limit`, which ends the run
+#[cfg(any(feature = "yaml", feature = "toml"))]
+fn too_deep(meter: Option<&ReadMeter>, limit: &LimitExceeded) -> String {
+    if let Some(meter) = meter {
+        meter.too_deep.set(true);
+    }
+    limit.to_string()
+}
+
+/// Documents of a YAML stream as they are built from parser events
+#[cfg(feature = "yaml")]
+#[derive(Default)]
+struct YamlBuilder {
+    documents: Vec<Value>,
+    /// Arrays and objects being built, innermost last
+    open: Vec<OpenNode>,
+    /// Values by anchor, with their number of nodes
+    anchors: std::collections::HashMap<usize, (Value, usize)>,
+    /// Nodes copied by aliases so far
+    aliased: usize,
+}
+
+#[cfg(feature = "yaml")]
+impl YamlBuilder {
+    /// Add a finished value of `nodes` nodes to the collection being built, or as a document
+    fn insert(&mut self, anchor: usize, nodes: usize, value: Value) -> Result<(), String> {
+        // Anchor ids start from 1
+        if anchor > 0 {
+            self.anchors.insert(anchor, (value.clone(), nodes));
+        }
+        let Some(parent) = self.open.last_mut() else {
+            self.documents.push(value);
+            return Ok(());
+        };
+        parent.nodes += nodes;
+        match &mut parent.collection {
+            Collection::Sequence(items) => items.push(value),
+            Collection::Mapping {
+                object,
+                key,
+                merged,
+            } => match key.take() {
+                None => *key = Some(value),
+                // A merge key adds the entries of one mapping, or of a list of mappings with
+                // the earlier ones winning, that the mapping does not set itself
+                Some(Value::String(key))
+                    if key == "<<"
+                        && (value.is_object()
+                            || value
+                                .as_array()
+                                .is_some_and(|items| items.iter().all(Value::is_object))) =>
+                {
+                    merged.push(value);
+                }
+                Some(key) => {
+                    let key = match key {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    };
+                    if object.contains_key(&key) {
+                        return Err(format!("duplicate key {key:?} in a mapping"));
+                    }
+                    object.insert(key, value);
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+/// An array or object being built, with the anchor it goes by
+#[cfg(feature = "yaml")]
+struct OpenNode {
+    anchor: usize,
+    /// Nodes in the collection so far, itself included
+    nodes: usize,
+    collection: Collection,
+}
+
+#[cfg(feature = "yaml")]
+enum Collection {
+    Sequence(Vec<Value>),
+    Mapping {
+        object: serde_json::Map<String, Value>,
+        /// Key read, waiting for its value
+        key: Option<Value>,
+        /// Values of merge keys, merged once the mapping has all its own entries
+        merged: Vec<Value>,
+    },
+}
+
+#[cfg(feature = "yaml")]
+impl Collection {
+    fn close(self) -> Value {
+        match self {
+            Collection::Sequence(items) => Value::Array(items),
+            Collection::Mapping {
+                mut object, merged, ..
+            } => {
+                for source in merged {
+                    let sources = match source {
+                        Value::Array(items) => items,
+                        object => vec![object],
+                    };
+                    for entries in sources {
+                        if let Value::Object(entries) = entries {
+                            for (key, value) in entries {
+                                object.entry(key).or_insert(value);
+                            }
+                        }
+                    }
+                }
+                Value::Object(object)
+            }
+        }
+    }
+}
+
+/// A scalar as JSON: quoted scalars and those with a tag outside the core schema are strings,
+/// and plain ones are resolved by the core schema
+#[cfg(feature = "yaml")]
+fn yaml_scalar(
+    value: String,
+    style: yaml_rust2::scanner::TScalarStyle,
+    tag: Option<yaml_rust2::parser::Tag>,
+) -> Value {
+    use yaml_rust2::Yaml;
+    use yaml_rust2::scanner::TScalarStyle;
+    let yaml = match tag {
+        _ if style != TScalarStyle::Plain => Yaml::String(value),
+        Some(tag) if tag.handle == "tag:yaml.org,2002:" => match tag.suffix.as_str() {
+            "bool" | "int" | "float" | "null" => Yaml::from_str(&value),
+            _ => Yaml::String(value),
+        },
+        Some(_) => Yaml::String(value),
+        None => Yaml::from_str(&value),
+    };
+    match yaml {
+        Yaml::Boolean(b) => Value::Bool(b),
+        Yaml::Integer(i) => Value::from(i),
+        // Integers past `i64` are read as floats
+        Yaml::Real(text) => text.parse::<u64>().map_or_else(
+            // `.nan` and `.inf` have no JSON form
+            |_| {
+                Yaml::Real(text)
+                    .as_f64()
+                    .and_then(serde_json::Number::from_f64)
+                    .map_or(Value::Null, Value::Number)
+            },
+            Value::from,
+        ),
+        Yaml::String(s) => Value::String(s),
+        _ => Value::Null,
+    }
+}
+
+/// Read and parse a whole input as one TOML document
+///
+/// Datetimes become their TOML text, and integers and floats stay distinct (`1` and `1.0`).
+#[cfg(feature = "toml")]
+pub fn load_toml_document(input: Input, meter: Option<&ReadMeter>) -> Result<Value, String> {
+    if let Input::Null = input {
+        return Ok(Value::Null);
+    }
+    let table: toml::Table =
+        read_text(input, meter, None)?
+            .parse()
+            .map_err(|e: toml::de::Error| {
+                // The message ends with a caret diagram and a newline
+                let message = e.to_string();
+                format!(
+                    "error parsing TOML{}: {}",
+                    input.location(),
+                    message.trim_end()
+                )
+            })?;
+    toml_to_json(
+        toml::Value::Table(table),
+        Nested::top(meter.and_then(|m| m.max_depth)),
+    )
+    .map_err(|limit| too_deep(meter, &limit))
+}
+
+#[cfg(not(feature = "toml"))]
+pub fn load_toml_document(input: Input, _: Option<&ReadMeter>) -> Result<Value, String> {
+    Err(format!(
+        "cannot read TOML input{}: jpp was built without the 'toml' feature",
+        input.location()
+    ))
+}
+
+/// `value` as JSON, where `nested` is the level of the array or table holding it
+#[cfg(feature = "toml")]
+fn toml_to_json(value: toml::Value, nested: Nested) -> Result<Value, LimitExceeded> {
+    Ok(match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        // `nan` and `inf` have no JSON form
+        toml::Value::Float(f) => serde_json::Number::from_f64(f).map_or(Value::Null, Value::Number),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(datetime) => Value::String(datetime.to_string()),
+        toml::Value::Array(items) => {
+            let inner = nested.enter()?;
+            Value::Array(
+                items
+                    .into_iter()
+                    .map(|item| toml_to_json(item, inner))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+        toml::Value::Table(table) => {
+            let inner = nested.enter()?;
+            Value::Object(
+                table
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, toml_to_json(value, inner)?)))
+                    .collect::<Result<_, _>>()?,
+            )
+        }
+    })
+}
+
+/// `--stream`: pass each element of the top-level array of `input` to `element`, holding only
+/// one element in memory at a time
+///
+/// An error from `element`, such as failing to write output, stops the stream and is the
+/// outer error. Reading and parsing errors are the inner one.
+pub fn for_each_array_element(
+    input: Input,
+    meter: Option<&ReadMeter>,
+    element: impl FnMut(Value) -> io::Result<()>,
+) -> io::Result<Result<(), String>> {
+    let mut reader = match input.open(meter, Some(Nesting::Elements)) {
+        Ok(reader) => reader,
+        Err(e) => return Ok(Err(e)),
+    };
+    match starts_with_array(&mut reader) {
+        Ok(true) => {}
+        Ok(false) => {
+            return Ok(Err(format!(
+                "--stream needs a top-level array{}; query other documents without --stream, or use --ndjson for one document per line",
+                input.location()
+            )));
+        }
+        Err(e) => return Ok(Err(input.read_error(&e))),
+    }
+
+    let mut failure = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let streamed = deserializer
+        .deserialize_seq(Elements {
+            element,
+            failure: &mut failure,
+        })
+        .and_then(|()| deserializer.end());
+    match (failure, streamed) {
+        (Some(e), _) => Err(e),
+        (None, Ok(())) => Ok(Ok(())),
+        (None, Err(e)) => Ok(Err(format!("error parsing JSON{}: {e}", input.location()))),
+    }
+}
+
+/// Skip leading whitespace and tell whether the next byte opens an array
+fn starts_with_array(reader: &mut impl BufRead) -> io::Result<bool> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(false);
+        }
+        match buf.iter().position(|b| !b.is_ascii_whitespace()) {
+            Some(start) => {
+                let is_array = buf[start] == b'[';
+                reader.consume(start);
+                return Ok(is_array);
+            }
+            None => {
+                let len = buf.len();
+                reader.consume(len);
+            }
+        }
+    }
+}
+
+/// Visits the top-level array, deserializing and handing over one element at a time
+struct Elements<'f, F> {
+    element: F,
+    /// Error returned by `element`, which ends the visit
+    failure: &'f mut Option<io::Error>,
+}
+
+impl<'de, F: FnMut(Value) -> io::Result<()>> Visitor<'de> for Elements<'_, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a top-level array")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(value) = seq.next_element()? {
+            if let Err(e) = (self.element)(value) {
+                *self.failure = Some(e);
+                return Err(de::Error::custom("stopped"));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Documents of an NDJSON input, or the lines of a raw input as strings, with their 1-based
+/// line numbers
+///
+/// Only the current line is held in memory. A line that is not valid JSON, or not valid UTF-8
+/// for raw input, yields an error and reading goes on; a read error yields an error and ends
+/// the input. Blank NDJSON lines are skipped, while blank raw lines are empty strings.
+pub struct LineDocuments<'a> {
+    input: Input<'a>,
+    reader: Box<dyn BufRead + 'a>,
+    /// Whether lines are strings rather than JSON
+    raw: bool,
+    line: Vec<u8>,
+    number: usize,
+}
+
+impl<'a> LineDocuments<'a> {
+    pub fn open(
+        input: Input<'a>,
+        format: InputFormat,
+        meter: Option<&'a ReadMeter>,
+    ) -> Result<Self, String> {
+        Ok(Self {
+            input,
+            reader: input.open(
+                meter,
+                (format != InputFormat::Raw).then_some(Nesting::Lines),
+            )?,
+            raw: format == InputFormat::Raw,
+            line: Vec::new(),
+            number: 0,
+        })
+    }
+
+    /// The current line as a string, without its line ending
+    fn raw_line(&self) -> Result<Value, String> {
+        let line = self.line.strip_suffix(b"\n").unwrap_or(&self.line);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        String::from_utf8(line.to_vec())
+            .map(Value::String)
+            .map_err(|e| {
+                format!(
+                    "invalid UTF-8{} on line {}: {}",
+                    self.input.location(),
+                    self.number,
+                    e.utf8_error()
+                )
+            })
+    }
+}
+
+impl Iterator for LineDocuments<'_> {
+    type Item = Result<(usize, Value), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.line.clear();
+            match self.reader.read_until(b'\n', &mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => self.number += 1,
+                Err(e) => {
+                    self.reader = Box::new(io::empty());
+                    return Some(Err(self.input.read_error(&e)));
+                }
+            }
+            let number = self.number;
+            if self.raw {
+                return Some(self.raw_line().map(|document| (number, document)));
+            }
+            if self.line.trim_ascii().is_empty() {
+                continue;
+            }
+            return Some(
+                serde_json::from_slice(&self.line)
+                    .map(|document| (number, document))
+                    .map_err(|e| {
+                        format!(
+                            "error parsing JSON{} on line {number}: {e}",
+                            self.input.location()
+                        )
+                    }),
+            );
+        }
+    }
+}
+
+/// `--multi`: documents of an input holding several JSON values back to back, separated by
+/// whitespace or nothing at all
+///
+/// Only the current document is held in memory. A document that does not parse yields an
+/// error giving its byte offset and ends the input, since there is no telling where the next
+/// one starts.
+pub struct ConcatenatedDocuments<'a> {
+    input: Input<'a>,
+    documents:
+        serde_json::StreamDeserializer<'a, serde_json::de::IoRead<Box<dyn BufRead + 'a>>, Value>,
+}
+
+impl<'a> ConcatenatedDocuments<'a> {
+    pub fn open(input: Input<'a>, meter: Option<&'a ReadMeter>) -> Result<Self, String> {
+        Ok(Self {
+            input,
+            documents: serde_json::Deserializer::from_reader(
+                input.open(meter, Some(Nesting::Json))?,
+            )
+            .into_iter(),
+        })
+    }
+}
+
+impl Iterator for ConcatenatedDocuments<'_> {
+    type Item = Result<Value, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // The end of the last document, where the next one starts after any whitespace
+        let offset = self.documents.byte_offset();
+        Some(self.documents.next()?.map_err(|e| {
+            if e.is_io() {
+                self.input.read_error(&io::Error::from(e))
+            } else {
+                format!(
+                    "error parsing JSON{} in the document after byte {offset}: {e}",
+                    self.input.location()
+                )
+            }
+        }))
+    }
+}
+
+/// The byte starting each record of a JSON text sequence
+pub const RECORD_SEPARATOR: u8 = 0x1e;
+
+/// Documents of an RFC 7464 JSON text sequence
+///
+/// Only the current record is held in memory. As the RFC suggests, a record that is not valid
+/// JSON, or a number, `true`, `false` or `null` without the whitespace that shows it was not
+/// truncated, is skipped and counted in `skipped`; reading resumes at the next separator. A
+/// read error yields an error and ends the input.
+pub struct SeqDocuments<'a> {
+    input: Input<'a>,
+    reader: Box<dyn BufRead + 'a>,
+    record: Vec<u8>,
+    /// Invalid records skipped so far
+    pub skipped: usize,
+}
+
+impl<'a> SeqDocuments<'a> {
+    pub fn open(input: Input<'a>, meter: Option<&'a ReadMeter>) -> Result<Self, String> {
+        Ok(Self {
+            input,
+            reader: input.open(meter, Some(Nesting::Records))?,
+            record: Vec::new(),
+            skipped: 0,
+        })
+    }
+}
+
+impl Iterator for SeqDocuments<'_> {
+    type Item = Result<Value, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.record.clear();
+            match self.reader.read_until(RECORD_SEPARATOR, &mut self.record) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => {
+                    self.reader = Box::new(io::empty());
+                    return Some(Err(self.input.read_error(&e)));
+                }
+            }
+            let record = self
+                .record
+                .strip_suffix(&[RECORD_SEPARATOR])
+                .unwrap_or(&self.record);
+            // Empty records, such as before the first separator, are not documents
+            if record.trim_ascii().is_empty() {
+                continue;
+            }
+            let truncated = !record.last().is_some_and(u8::is_ascii_whitespace);
+            match serde_json::from_slice(record) {
+                Ok(Value::Number(_) | Value::Bool(_) | Value::Null) if truncated => {}
+                Ok(json) => return Some(Ok(json)),
+                Err(_) => {}
+            }
+            self.skipped += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn documents(text: &'static str) -> Vec<Result<(usize, Value), String>> {
+        lines(text.as_bytes(), false)
+    }
+
+    fn lines(bytes: &'static [u8], raw: bool) -> Vec<Result<(usize, Value), String>> {
+        LineDocuments {
+            input: Input::Stdin,
+            reader: Box::new(bytes),
+            raw,
+            line: Vec::new(),
+            number: 0,
+        }
+        .collect()
+    }
+
+    #[test]
+    fn test_starts_with_array() {
+        for (text, expected) in [
+            ("[1]", true),
+            (" \n\t [", true),
+            ("{}", false),
+            ("  1", false),
+            ("", false),
+            ("   ", false),
+        ] {
+            let mut reader = text.as_bytes();
+            assert_eq!(
+                starts_with_array(&mut reader).unwrap(),
+                expected,
+                "{text:?}"
+            );
+            if expected {
+                assert!(reader.starts_with(b"["));
+            }
+        }
+    }
+
+    #[test]
+    fn test_compression_detection() {
+        assert_eq!(
+            Compression::from_extension("data.json.gz"),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_extension("dir.v2/data.zst"),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_extension("data.json"), None);
+        assert_eq!(Compression::from_extension("gz"), None);
+
+        assert_eq!(
+            Compression::from_magic(&[0x1f, 0x8b, 8]),
+            Some(Compression::Gzip)
+        );
+        assert_eq!(
+            Compression::from_magic(&[0x28, 0xb5, 0x2f, 0xfd]),
+            Some(Compression::Zstd)
+        );
+        assert_eq!(Compression::from_magic(b"[1]"), None);
+        assert_eq!(Compression::from_magic(b""), None);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        for (path, expected) in [
+            ("deploy.yaml", InputFormat::Yaml),
+            ("ci/config.yml", InputFormat::Yaml),
+            ("deploy.yaml.gz", InputFormat::Yaml),
+            ("Cargo.toml", InputFormat::Toml),
+            ("data.json", InputFormat::Json),
+            (".vscode/settings.jsonc", InputFormat::Jsonc),
+            ("yaml", InputFormat::Json),
+            ("data.json.zst", InputFormat::Json),
+        ] {
+            assert_eq!(Input::File(path).detect_format(), expected, "{path}");
+        }
+        assert_eq!(Input::Stdin.detect_format(), InputFormat::Json);
+    }
+
+    #[test]
+    #[cfg(feature = "yaml")]
+    fn test_parse_yaml_documents() {
+        let yaml = "
+base: &base {image: nginx, replicas: 1}
+web:
+  <<: *base
+  replicas: 3
+at: 2024-01-02T03:04:05Z
+tagged: !Secret token
+keys: {1: one, true: yes, null: nothing}
+floats: [1.5, .nan, -.inf]
+big: 18446744073709551615
+---
+list: [&a {x: 1}, &b {x: 2, y: 2}, {<<: [*a, *b], z: 3}]
+---
+";
+        assert_eq!(
+            parse_yaml_documents(yaml, None).unwrap(),
+            [
+                json!({
+                    "base": {"image": "nginx", "replicas": 1},
+                    "web": {"image": "nginx", "replicas": 3},
+                    "at": "2024-01-02T03:04:05Z",
+                    "tagged": "token",
+                    "keys": {"1": "one", "true": "yes", "null": "nothing"},
+                    "floats": [1.5, null, null],
+                    "big": 18446744073709551615u64
+                }),
+                json!({"list": [{"x": 1}, {"x": 2, "y": 2}, {"x": 1, "y": 2, "z": 3}]}),
+                Value::Null
+            ]
+        );
+
+        // The list of mappings is one level too deep
+        let meter = ReadMeter {
+            max_depth: Some(2),
+            ..ReadMeter::default()
+        };
+        assert!(parse_yaml_documents(yaml, Some(&meter)).is_err());
+        assert_eq!(meter.exceeded(), Some(LimitExceeded::Depth(2)));
+
+        // Deep block nesting, duplicate keys and alias bombs fail without using the stack
+        let deep: String = (0..10_000)
+            .map(|i| format!("{}-\n", " ".repeat(i)))
+            .collect();
+        let error = parse_yaml_documents(&deep, None).unwrap_err();
+        assert!(error.starts_with("recursion limit exceeded"), "{error}");
+        let error = parse_yaml_documents("a: 1\na: 2\n", None).unwrap_err();
+        assert_eq!(error, "duplicate key \"a\" in a mapping");
+        let mut bomb = "a: &a [x, x, x, x, x, x, x, x]\n".to_string();
+        for (anchor, alias) in ["b", "c", "d", "e", "f", "g", "h"]
+            .iter()
+            .zip(["a", "b", "c", "d", "e", "f", "g"])
+        {
+            bomb += &format!(
+                "{anchor}: &{anchor} [{}]\n",
+                vec![format!("*{alias}"); 8].join(", ")
+            );
+        }
+        let error = parse_yaml_documents(&bomb, None).unwrap_err();
+        assert!(error.starts_with("aliases copy too many nodes"), "{error}");
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_toml_to_json() {
+        let table: toml::Table = "
+int = 1
+float = 1.0
+special = [nan, inf]
+date = 1979-05-27
+stamp = 1979-05-27T07:32:00-08:00
+[[bin]]
+name = 'a'
+"
+        .parse()
+        .unwrap();
+        let json = toml_to_json(toml::Value::Table(table.clone()), Nested::top(None)).unwrap();
+        assert_eq!(
+            json,
+            json!({
+                "int": 1,
+                "float": 1.0,
+                "special": [null, null],
+                "date": "1979-05-27",
+                "stamp": "1979-05-27T07:32:00-08:00",
+                "bin": [{"name": "a"}]
+            })
+        );
+        assert_eq!(json.to_string().matches("1.0").count(), 1);
+        // `[[bin]]` is a table in an array in the top table
+        let value = toml::Value::Table(table);
+        assert!(toml_to_json(value.clone(), Nested::top(Some(3))).is_ok());
+        assert_eq!(
+            toml_to_json(value, Nested::top(Some(2))),
+            Err(LimitExceeded::Depth(2))
+        );
+    }
+
+    #[test]
+    fn test_ndjson_documents() {
+        assert_eq!(
+            documents("{\"a\": 1}\n\n  \n[2]\r\n\"x\""),
+            [
+                Ok((1, json!({"a": 1}))),
+                Ok((4, json!([2]))),
+                Ok((5, json!("x")))
+            ]
+        );
+        assert!(documents("").is_empty());
+
+        let results = documents("1\n{\n3\n");
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0], Ok((1, json!(1))));
+        assert!(
+            results[1]
+                .as_ref()
+                .unwrap_err()
+                .starts_with("error parsing JSON on line 2: ")
+        );
+        assert_eq!(results[2], Ok((3, json!(3))));
+    }
+
+    #[test]
+    fn test_raw_lines() {
+        assert_eq!(
+            lines(b"a \"quoted\" line\r\n\nC:\\dir\n\tlast", true),
+            [
+                Ok((1, json!("a \"quoted\" line"))),
+                Ok((2, json!(""))),
+                Ok((3, json!("C:\\dir"))),
+                Ok((4, json!("\tlast")))
+            ]
+        );
+        assert!(lines(b"", true).is_empty());
+        assert_eq!(lines(b"x\n", true), [Ok((1, json!("x")))]);
+
+        let results = lines(b"ok\n\xff\xfe\nok\n", true);
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[1],
+            Err(
+                "invalid UTF-8 on line 2: invalid utf-8 sequence of 1 bytes from index 0"
+                    .to_string()
+            )
+        );
+        assert_eq!(results[2], Ok((3, json!("ok"))));
+    }
+
+    #[test]
+    fn test_seq_documents() {
+        let mut documents = SeqDocuments {
+            input: Input::Stdin,
+            reader: Box::new(
+                &b"\x1e{\"a\": 1}\n\x1e\x1e[2\n\x1e 3\n\x1e\"s\"\x1e4\x1etrue\n\x1e{\"b\"\n:null}"
+                    [..],
+            ),
+            record: Vec::new(),
+            skipped: 0,
+        };
+        let values: Vec<Value> = documents.by_ref().map(Result::unwrap).collect();
+        assert_eq!(
+            values,
+            [
+                json!({"a": 1}),
+                json!(3),
+                json!("s"),
+                json!(true),
+                json!({"b": null})
+            ]
+        );
+        // `[2` does not parse, and `4` may have been cut short
+        assert_eq!(documents.skipped, 2);
+    }
+
+    #[test]
+    fn test_concatenated_documents() {
+        let documents = |text: &'static str| -> Vec<Result<Value, String>> {
+            ConcatenatedDocuments {
+                input: Input::Stdin,
+                documents: serde_json::Deserializer::from_reader(
+                    Box::new(text.as_bytes()) as Box<dyn BufRead>
+                )
+                .into_iter(),
+            }
+            .collect()
+        };
+        assert_eq!(
+            documents("{\"a\": 1}{\"b\": 2}\n [3] \"x\"4 5\n"),
+            [
+                Ok(json!({"a": 1})),
+                Ok(json!({"b": 2})),
+                Ok(json!([3])),
+                Ok(json!("x")),
+                Ok(json!(4)),
+                Ok(json!(5))
+            ]
+        );
+        assert!(documents(" \n").is_empty());
+        assert_eq!(
+            documents("{}\n garbage {}"),
+            [
+                Ok(json!({})),
+                Err("error parsing JSON in the document after byte 2: expected value at line 2 column 2".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,134 @@
+//! `--input-format jsonc`: JSON with comments and trailing commas, as in `tsconfig.json`
+
+/// `text` with its `//` and `/* */` comments and the commas before a `]` or `}` blanked out
+///
+/// Every removed byte but a line break becomes a space, so the JSON parser reports errors at
+/// the line and column of the original text. Comment markers inside strings are kept.
+pub fn strip(text: &str) -> Result<String, String> {
+    let mut bytes = text.as_bytes().to_vec();
+    // Index of a comma after a value, not yet followed by anything but whitespace and comments
+    let mut comma = None;
+    let mut after_value = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => {
+                (comma, after_value) = (None, true);
+                i += 1;
+                while i < bytes.len() && bytes[i] != b'"' {
+                    i += if bytes[i] == b'\\' { 2 } else { 1 };
+                }
+                i += 1;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'/') => {
+                let end = bytes[i..]
+                    .iter()
+                    .position(|&b| b == b'\n')
+                    .map_or(bytes.len(), |n| i + n);
+                blank(&mut bytes[i..end]);
+                i = end;
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                let Some(n) = bytes[i + 2..].windows(2).position(|w| w == b"*/") else {
+                    let (line, column) = position(text, i);
+                    return Err(format!(
+                        "unterminated comment at line {line} column {column}"
+                    ));
+                };
+                let end = i + 2 + n + 2;
+                blank(&mut bytes[i..end]);
+                i = end;
+            }
+            b',' => {
+                comma = after_value.then_some(i);
+                after_value = false;
+                i += 1;
+            }
+            b']' | b'}' => {
+                if let Some(comma) = comma.take() {
+                    bytes[comma] = b' ';
+                }
+                after_value = true;
+                i += 1;
+            }
+            b'[' | b'{' | b':' => {
+                (comma, after_value) = (None, false);
+                i += 1;
+            }
+            b if b.is_ascii_whitespace() => i += 1,
+            _ => {
+                (comma, after_value) = (None, true);
+                i += 1;
+            }
+        }
+    }
+    // Only ASCII bytes outside strings were replaced, with ASCII spaces
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// Replace `bytes` with spaces, keeping line breaks
+fn blank(bytes: &mut [u8]) {
+    for b in bytes.iter_mut().filter(|b| !matches!(b, b'\n' | b'\r')) {
+        *b = b' ';
+    }
+}
+
+/// Line and column of byte `offset` of `text`, counted from 1 as the JSON parser does
+fn position(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text.as_bytes()[..offset];
+    let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+    let column = before.iter().rev().take_while(|&&b| b != b'\n').count() + 1;
+    (line, column)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn parse(text: &str) -> Value {
+        serde_json::from_str(&strip(text).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_strip() {
+        let text = r#"{
+  // Line comment with "quotes" and a trailing ,
+  "url": "http://example.com/*not a comment*/", /* block
+     comment */ "path": "a\"//b",
+  "list": [1, 2, /* three */],
+  "nested": {"a": [], "b": {},},
+}"#;
+        assert_eq!(
+            parse(text),
+            json!({
+                "url": "http://example.com/*not a comment*/",
+                "path": "a\"//b",
+                "list": [1, 2],
+                "nested": {"a": [], "b": {}}
+            })
+        );
+        let stripped = strip(text).unwrap();
+        assert_eq!(stripped.len(), text.len());
+        assert_eq!(stripped.lines().count(), text.lines().count());
+        assert_eq!(parse("[1, // 一つ目\n 2,\n]"), json!([1, 2]));
+        assert_eq!(parse("\"trailing, ]\""), json!("trailing, ]"));
+    }
+
+    #[test]
+    fn test_errors() {
+        // Commas that are not trailing are left for the parser to reject
+        let e = serde_json::from_str::<Value>(&strip("[1,,]").unwrap()).unwrap_err();
+        assert_eq!((e.line(), e.column()), (1, 4));
+        assert_eq!(strip("[,]").unwrap(), "[,]");
+        // Positions are those of the original text
+        let e =
+            serde_json::from_str::<Value>(&strip("{\n  /* x */ \"a\": x\n}").unwrap()).unwrap_err();
+        assert_eq!((e.line(), e.column()), (2, 16));
+        assert_eq!(
+            strip("[1]\n  /* open").unwrap_err(),
+            "unterminated comment at line 2 column 3"
+        );
+    }
+}
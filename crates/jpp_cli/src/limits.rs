@@ -0,0 +1,337 @@
+//! `--max-input-bytes`, `--max-results`, `--max-depth` and `--timeout`: limits for running
+//! over untrusted input. Exceeding one aborts the run.
+
+use jpp_core::{BudgetLimit, EvalError, EvalOptions};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// The limits of a run; the default sets none
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Bytes read from each input, after decompression
+    pub max_input_bytes: Option<u64>,
+    /// Results of each query against each document
+    pub max_results: Option<usize>,
+    /// Arrays and objects nested in each document
+    pub max_depth: Option<usize>,
+    /// Time spent evaluating queries over the whole run
+    pub timeout: Option<Duration>,
+}
+
+/// Value of `--max-input-bytes`, `--max-results` or `--max-depth`
+pub fn parse_count<T: FromStr>(usage: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| {
+        format!("invalid value '{value}' for '{usage}' (expected a non-negative integer)")
+    })
+}
+
+/// Value of `--timeout` or `--http-timeout`
+pub fn parse_timeout(usage: &str, value: &str) -> Result<Duration, String> {
+    value
+        .parse::<f64>()
+        .ok()
+        .filter(|secs| *secs > 0.0)
+        .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+        .ok_or_else(|| {
+            format!("invalid value '{value}' for '{usage}' (expected a positive number of seconds)")
+        })
+}
+
+/// The limit that aborted a run
+#[derive(Debug, PartialEq, Eq)]
+pub enum LimitExceeded {
+    InputBytes(u64),
+    Results(usize),
+    Depth(usize),
+    Timeout(Duration),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InputBytes(max) => write!(f, "input is larger than --max-input-bytes {max}"),
+            Self::Results(max) => write!(f, "a query selected more than --max-results {max}"),
+            Self::Depth(max) => write!(f, "document nesting is deeper than --max-depth {max}"),
+            Self::Timeout(timeout) => write!(
+                f,
+                "evaluation took longer than --timeout {}",
+                timeout.as_secs_f64()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// How the bytes of an input nest documents, for [`DepthScan`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Nesting {
+    /// JSON documents, one or several in a row
+    Json,
+    /// JSON with `//` and `/* */` comments
+    Jsonc,
+    /// The elements of a top-level array, each a document of `--stream`
+    Elements,
+    /// One JSON document per line
+    Lines,
+    /// JSON text sequence records, each starting with a record separator
+    Records,
+}
+
+/// `--max-depth` over the bytes of an input as they are read, so that reading stops at the
+/// first array or object too deep instead of after the document is parsed
+#[derive(Debug)]
+pub struct DepthScan {
+    max: usize,
+    nesting: Nesting,
+    /// Arrays and objects open at this point
+    depth: usize,
+    state: ScanState,
+}
+
+/// Where a [`DepthScan`] is in the syntax
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ScanState {
+    /// Between tokens, or in a number or a literal
+    Value,
+    String,
+    /// After a backslash in a string
+    Escape,
+    /// After a `/` that may start a comment
+    Slash,
+    LineComment,
+    BlockComment,
+    /// After a `*` in a block comment
+    BlockStar,
+}
+
+impl DepthScan {
+    pub fn new(max: usize, nesting: Nesting) -> Self {
+        Self {
+            max,
+            nesting,
+            depth: 0,
+            state: ScanState::Value,
+        }
+    }
+
+    /// Follow the next bytes of the input, failing at an array or object nested deeper than
+    /// `max` in its document
+    pub fn scan(&mut self, bytes: &[u8]) -> Result<(), LimitExceeded> {
+        // The top-level array of `--stream` is not part of any document
+        let max = match self.nesting {
+            Nesting::Elements => self.max.saturating_add(1),
+            _ => self.max,
+        };
+        for &b in bytes {
+            // Not a comment after all, which the parser reports
+            if self.state == ScanState::Slash && !matches!(b, b'/' | b'*') {
+                self.state = ScanState::Value;
+            }
+            self.state = match (self.state, b) {
+                // A document ends here whatever came before, even if it did not parse
+                (_, b'\n') if self.nesting == Nesting::Lines => {
+                    self.depth = 0;
+                    ScanState::Value
+                }
+                (_, crate::input::RECORD_SEPARATOR) if self.nesting == Nesting::Records => {
+                    self.depth = 0;
+                    ScanState::Value
+                }
+                (ScanState::Value, b'[' | b'{') => {
+                    self.depth += 1;
+                    if self.depth > max {
+                        return Err(LimitExceeded::Depth(self.max));
+                    }
+                    ScanState::Value
+                }
+                (ScanState::Value, b']' | b'}') => {
+                    self.depth = self.depth.saturating_sub(1);
+                    ScanState::Value
+                }
+                (ScanState::Value, b'"') | (ScanState::Escape, _) => ScanState::String,
+                (ScanState::Value, b'/') if self.nesting == Nesting::Jsonc => ScanState::Slash,
+                (ScanState::String, b'\\') => ScanState::Escape,
+                (ScanState::String, b'"') => ScanState::Value,
+                (ScanState::Slash, b'/') => ScanState::LineComment,
+                (ScanState::Slash, b'*') => ScanState::BlockComment,
+                (ScanState::LineComment, b'\n') => ScanState::Value,
+                (ScanState::BlockComment | ScanState::BlockStar, b'*') => ScanState::BlockStar,
+                (ScanState::BlockStar, b'/') => ScanState::Value,
+                (ScanState::BlockStar, _) => ScanState::BlockComment,
+                (state, _) => state,
+            };
+        }
+        Ok(())
+    }
+}
+
+/// `--max-depth` for a document converted to JSON from TOML, which has no JSON bytes to scan:
+/// the level of the array or table being converted
+#[cfg(feature = "toml")]
+#[derive(Clone, Copy, Debug)]
+pub struct Nested {
+    max: Option<usize>,
+    level: usize,
+}
+
+#[cfg(feature = "toml")]
+impl Nested {
+    /// The top of a document, under `max` levels
+    pub fn top(max: Option<usize>) -> Self {
+        Self { max, level: 0 }
+    }
+
+    /// One level down, into an array or object
+    pub fn enter(self) -> Result<Self, LimitExceeded> {
+        let level = self.level + 1;
+        match self.max {
+            Some(max) if level > max => Err(LimitExceeded::Depth(max)),
+            max => Ok(Self { max, level }),
+        }
+    }
+}
+
+/// What is left of the evaluation limits during a run
+pub struct Budget {
+    max_results: Option<usize>,
+    timeout: Option<Duration>,
+    /// Evaluation time left before `timeout`
+    left: Option<Duration>,
+}
+
+impl Budget {
+    pub fn new(limits: &Limits) -> Self {
+        Self {
+            max_results: limits.max_results,
+            timeout: limits.timeout,
+            left: limits.timeout,
+        }
+    }
+
+    /// Run one evaluation under the core options for the limits, charging its time to
+    /// `--timeout`
+    pub fn eval<T>(
+        &mut self,
+        eval: impl FnOnce(&EvalOptions) -> Result<T, EvalError>,
+    ) -> Result<T, LimitExceeded> {
+        let start = Instant::now();
+        let options = EvalOptions {
+            max_results: self.max_results,
+            deadline: self.left.map(|left| start + left),
+            ..EvalOptions::default()
+        };
+        let result = eval(&options);
+        if let Some(left) = &mut self.left {
+            *left = left.saturating_sub(start.elapsed());
+        }
+        result.map_err(|EvalError::BudgetExceeded { limit, .. }| match limit {
+            BudgetLimit::MaxResults => LimitExceeded::Results(self.max_results.unwrap_or(0)),
+            // No node limit is set, so the deadline is the only other one
+            BudgetLimit::Deadline | BudgetLimit::MaxNodes => {
+                LimitExceeded::Timeout(self.timeout.unwrap_or_default())
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use jpp_core::JsonPath;
+    use serde_json::json;
+
+    /// Whether scanning `text` in one go, then byte by byte, stays within `max`
+    fn within(max: usize, nesting: Nesting, text: &str) -> bool {
+        let whole = DepthScan::new(max, nesting).scan(text.as_bytes()).is_ok();
+        let mut scan = DepthScan::new(max, nesting);
+        let split = text.bytes().all(|b| scan.scan(&[b]).is_ok());
+        assert_eq!(whole, split, "{text:?}");
+        whole
+    }
+
+    #[test]
+    fn test_depth_scan() {
+        let json = r#"{"a": [1, {"b": []}], "c": "[[[{{"}"#;
+        assert!(within(4, Nesting::Json, json));
+        assert!(!within(3, Nesting::Json, json));
+        assert!(within(0, Nesting::Json, "1 \"[\\\"[\""));
+        assert!(!within(0, Nesting::Json, "[]"));
+        assert_eq!(
+            DepthScan::new(1, Nesting::Json).scan(b"[[]]"),
+            Err(LimitExceeded::Depth(1))
+        );
+        // Documents in a row are each as deep as their own nesting
+        assert!(within(1, Nesting::Json, "[] {} [1]"));
+
+        // Comments may hold brackets; a lone slash is not a comment
+        assert!(within(1, Nesting::Jsonc, "// [[\n[/* ]]{{ */ 1] /"));
+        assert!(!within(1, Nesting::Jsonc, "/* */ [[1]] // x"));
+        assert!(!within(1, Nesting::Json, "// [[\n"));
+        assert!(!within(1, Nesting::Jsonc, "[/[]]"));
+
+        // The array of `--stream` does not count; a line or a record that does not parse
+        // leaves no nesting behind
+        assert!(within(1, Nesting::Elements, "[[1], [2]]"));
+        assert!(!within(1, Nesting::Elements, "[[[1]]]"));
+        assert!(within(1, Nesting::Lines, "[\n[1]\n"));
+        assert!(within(1, Nesting::Records, "\x1e[\x1e[1]\n"));
+        assert!(!within(1, Nesting::Json, "[\n[1]\n"));
+    }
+
+    #[test]
+    #[cfg(feature = "toml")]
+    fn test_nested() {
+        let top = Nested::top(Some(1));
+        assert!(top.enter().is_ok());
+        assert_eq!(
+            top.enter().unwrap().enter().unwrap_err(),
+            LimitExceeded::Depth(1)
+        );
+        assert!(Nested::top(None).enter().unwrap().enter().is_ok());
+    }
+
+    #[test]
+    fn test_budget() {
+        let json = json!([1, 2, 3]);
+        let path = JsonPath::parse("$[*]").unwrap();
+        let mut budget = Budget::new(&Limits {
+            max_results: Some(2),
+            ..Limits::default()
+        });
+        assert_eq!(
+            budget.eval(|options| path.query_with_options(&json, options)),
+            Err(LimitExceeded::Results(2))
+        );
+        let mut budget = Budget::new(&Limits {
+            max_results: Some(3),
+            timeout: Some(Duration::from_secs(60)),
+            ..Limits::default()
+        });
+        assert_eq!(
+            budget
+                .eval(|options| path.query_with_options(&json, options))
+                .unwrap()
+                .len(),
+            3
+        );
+        assert!(budget.left.unwrap() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_count::<u64>("--max-depth <N>", "7"), Ok(7));
+        assert!(parse_count::<u64>("--max-depth <N>", "-1").is_err());
+        assert_eq!(
+            parse_timeout("--timeout <SECS>", "0.5"),
+            Ok(Duration::from_millis(500))
+        );
+        assert_eq!(
+            parse_timeout("--http-timeout <SECS>", "0"),
+            Err("invalid value '0' for '--http-timeout <SECS>' (expected a positive number of seconds)".to_string())
+        );
+        assert!(parse_timeout("--timeout <SECS>", "inf").is_err());
+    }
+}
@@ -1,24 +1,103 @@
-use serde_json::Value;
+mod ascii;
+mod color;
+mod completions;
+mod config;
+mod diagnostic;
+mod diff;
+mod explain;
+mod follow;
+mod http;
+mod input;
+mod jsonc;
+mod limits;
+mod options;
+mod output;
+mod parallel;
+mod preview;
+mod sort;
+mod table;
+mod template;
+mod timing;
+mod validate;
+mod variables;
+mod walk;
+mod watch;
+mod yaml;
+
+use ascii::Astral;
+use color::{ColorChoice, ColorWriter, Palette};
+use completions::Shell;
+use config::Config;
+use diagnostic::{Diagnostic, Kind};
+use diff::DiffFormat;
+use input::{
+    ConcatenatedDocuments, Console, Input, InputFormat, LineDocuments, ProcessConsole,
+    RECORD_SEPARATOR, ReadMeter, SeqDocuments, for_each_array_element, load_document,
+    load_jsonc_document, load_toml_document, load_yaml_documents,
+};
+use jpp_core::{EvalError, EvalOptions, JsonPath, NormalizedPath};
+use limits::{Budget, LimitExceeded, Limits};
+use output::{AtomicFile, Output};
+use preview::Preview;
+use serde::Serialize;
+use serde_json::ser::{CompactFormatter, PrettyFormatter};
+use serde_json::{Map, Value, json};
+use sort::Sort;
+use std::collections::HashSet;
 use std::env;
+use std::fmt;
 use std::fs;
-use std::io::{self, Read};
+use std::io::{self, IsTerminal, Write};
+use std::mem;
+use std::ops::ControlFlow;
+use std::path::Path;
 use std::process::ExitCode;
+use std::time::{Duration, Instant};
+use table::Style;
+use template::Template;
+use timing::{TimeFormat, Timing};
+use validate::{Checked, ErrorFormat};
+use variables::Variables;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Exit status under `--exit-status` when the query matched nothing
+const EXIT_NO_MATCH: u8 = 1;
+/// Exit status of `--validate` when a query is invalid
+const EXIT_INVALID: u8 = 1;
+/// Exit status of `--diff` when the results differ
+const EXIT_DIFFERENT: u8 = 1;
+/// Exit status for usage, query, input and output errors, kept apart from [`EXIT_NO_MATCH`]
+const EXIT_ERROR: u8 = 2;
+/// Exit status when `--max-input-bytes`, `--max-results`, `--max-depth` or `--timeout` stops
+/// the run
+const EXIT_LIMIT: u8 = 3;
+
 fn print_help() {
+    let options = options::help();
     println!(
         "jpp {VERSION} - JSONPath processor (RFC 9535)
 
-Usage: jpp [OPTIONS] <QUERY> [FILE]
+Usage: jpp [OPTIONS] <QUERY> [FILE]...
+       jpp [OPTIONS] (--query <QUERY> | -f <QUERY_FILE>)... [FILE]...
+       jpp --validate [QUERY]... [-f <QUERY_FILE>]...
+       jpp --explain [QUERY]... [-f <QUERY_FILE>]...
+       jpp completions <SHELL>
 
 Arguments:
   <QUERY>    JSONPath query (RFC 9535 format)
-  [FILE]     Input JSON file (reads from stdin if omitted)
+  [FILE]...  Input files or http(s):// URLs (reads from stdin if omitted; `-` is stdin).
+             Arguments after `--` are never options
+  <SHELL>    Shell of the completion script: bash, zsh, fish or powershell
 
 Options:
-  -h, --help     Show this help message
-  -V, --version  Show version"
+{options}
+Exit status:
+  0  Success (with -e: at least one match; with --diff: the same results)
+  1  No match (only with -e), an invalid query (only with --validate), or different
+     results (only with --diff)
+  2  Usage, query, input or output error
+  3  A limit was exceeded: --max-input-bytes, --max-results, --max-depth or --timeout"
     );
 }
 
@@ -29,99 +108,2492 @@ fn print_version() {
 enum ParsedArgs {
     Help,
     Version,
-    Query { query: String, file: Option<String> },
+    Query(Box<QueryArgs>),
+    Validate(ValidateArgs),
+    Explain(ExplainArgs),
+    /// `jpp completions SHELL`
+    Completions(Shell),
 }
 
-fn parse_args() -> Result<ParsedArgs, String> {
-    let args: Vec<String> = env::args().skip(1).collect();
+/// Value of `--output`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// All results as one JSON array
+    Json,
+    /// One compact JSON value per line
+    Ndjson,
+    /// RFC 7464 JSON text sequence: each result as a record separator, compact JSON and a
+    /// newline
+    JsonSeq,
+    /// All results as one YAML sequence document
+    Yaml,
+    /// Each result as its own YAML document
+    YamlDocs,
+    /// The results as the rows of an aligned text table
+    Table,
+    /// The results as the rows of a Markdown table
+    Markdown,
+}
+
+impl OutputFormat {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "json-seq" => Ok(Self::JsonSeq),
+            "yaml" => Ok(Self::Yaml),
+            "yaml-docs" => Ok(Self::YamlDocs),
+            "table" => Ok(Self::Table),
+            "markdown" => Ok(Self::Markdown),
+            _ => Err(format!(
+                "invalid value '{s}' for '--output <FORMAT>' (expected json, ndjson, json-seq, yaml, yaml-docs, table or markdown)"
+            )),
+        }
+    }
+
+    /// How `--output table` and `--output markdown` draw tables
+    fn table_style(self) -> Option<Style> {
+        match self {
+            Self::Table => Some(Style::Text),
+            Self::Markdown => Some(Style::Markdown),
+            _ => None,
+        }
+    }
+}
+
+/// What ends or separates the records of raw output
+#[derive(Debug, PartialEq, Eq)]
+enum Delimiter {
+    /// `-r`: a newline after each record
+    Newline,
+    /// `-0`: a NUL byte after each record
+    Nul,
+    /// `--join`: the separator between records, and nothing after the last
+    Join(String),
+}
+
+/// Which inputs `--files-with-matches` or `--files-without-match` names
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ListFiles {
+    WithMatches,
+    WithoutMatch,
+}
+
+/// Change made to the matches of each document
+enum Edit {
+    /// `--delete`
+    Delete,
+    /// `--set` or `--set-raw`: replace each match with the value
+    Set(Value),
+}
+
+fn set_edit(edit: &mut Option<Edit>, new: Edit) -> Result<(), String> {
+    if edit.replace(new).is_some() {
+        return Err("only one of --delete, --set and --set-raw can be given".to_string());
+    }
+    Ok(())
+}
+
+/// `-0` or `--join`, replacing the newlines of `-r`
+fn set_delimiter(raw_output: &mut Option<Delimiter>, new: Delimiter) -> Result<(), String> {
+    match raw_output.replace(new) {
+        None | Some(Delimiter::Newline) => Ok(()),
+        Some(_) => Err("only one of --join and --null-output can be given".to_string()),
+    }
+}
+
+fn set_list_files(list_files: &mut Option<ListFiles>, new: ListFiles) -> Result<(), String> {
+    match list_files.replace(new) {
+        Some(old) if old != new => Err(
+            "--files-with-matches and --files-without-match cannot be used together".to_string(),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn parse_set_value(value: &str) -> Result<Edit, String> {
+    serde_json::from_str(value).map(Edit::Set).map_err(|e| {
+        format!("invalid JSON for '--set <JSON>': {e}; use --set-raw for a plain string")
+    })
+}
+
+/// A query with the `--label` naming its results
+struct QuerySpec {
+    query: String,
+    label: Option<String>,
+    /// `-f` file the query was read from
+    file: Option<String>,
+}
+
+impl QuerySpec {
+    fn label(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.query)
+    }
+}
+
+impl From<String> for QuerySpec {
+    fn from(query: String) -> Self {
+        Self {
+            query,
+            label: None,
+            file: None,
+        }
+    }
+}
+
+/// `--validate`
+struct ValidateArgs {
+    /// Queries from arguments and files, or empty to read a list from stdin
+    queries: Vec<QuerySpec>,
+    variables: Variables,
+    error_format: ErrorFormat,
+}
+
+/// Value of `--explain-format`
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ExplainFormat {
+    Human,
+    /// The serialized syntax tree
+    Json,
+}
+
+impl ExplainFormat {
+    fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid value '{s}' for '--explain-format <FORMAT>' (expected human or json)"
+            )),
+        }
+    }
+}
+
+/// `--explain`
+struct ExplainArgs {
+    /// At least one query
+    queries: Vec<QuerySpec>,
+    variables: Variables,
+    format: ExplainFormat,
+}
+
+struct QueryArgs {
+    /// At least one query
+    queries: Vec<QuerySpec>,
+    /// Print an object keyed by label for each input, for several or labeled queries
+    keyed: bool,
+    /// `--arg` and `--argjson`, substituted into the queries before parsing
+    variables: Variables,
+    files: Vec<String>,
+    /// `--with-filename` / `--no-filename`, or `None` to label only when there are several files
+    with_filename: Option<bool>,
+    /// `--recursive`: directories in `files` stand for the files under them
+    recursive: bool,
+    /// `--include`: globs of the file names `--recursive` reads
+    include: Vec<String>,
+    /// `--files-with-matches` or `--files-without-match`: print input names instead of results
+    list_files: Option<ListFiles>,
+    /// `--jobs`: inputs read at once when there are several
+    jobs: usize,
+    /// `--header` and `--http-timeout`, for URLs in `files`
+    http: http::Client,
+    /// `--follow`: read the one file as it grows, from its start with `--from-start`
+    follow: bool,
+    from_start: bool,
+    null_input: bool,
+    /// `--input-format`, or `None` to choose by file extension
+    input_format: Option<InputFormat>,
+    slurp: bool,
+    stream: bool,
+    /// `--multi`: JSON inputs hold several documents back to back
+    multi: bool,
+    with_line_numbers: bool,
+    exit_status: bool,
+    /// `--diff` or `--diff-format`: compare the results for the two `files`
+    diff: Option<DiffFormat>,
+    limit: Option<usize>,
+    /// `--sort` or `--sort-by`, applied before `limit`
+    sort: Option<Sort>,
+    count: bool,
+    total: bool,
+    /// `--paths`, or `--pointer` without `--entries`: print the location of each result
+    paths: bool,
+    entries: bool,
+    /// `--pointer`: locations as JSON Pointers rather than normalized paths
+    pointer: bool,
+    /// `--delete` or `--set`: print each document with its matches changed instead of the
+    /// matches
+    edit: Option<Edit>,
+    /// `-q` given once or more: 1 prints nothing on stdout but edited documents, and stops at
+    /// the first match; 2 also keeps errors and warnings off stderr
+    quiet: u8,
+    in_place: bool,
+    /// `--backup`: suffix of the copies `--in-place` keeps of the original files
+    backup: Option<String>,
+    /// `-o`: file replacing stdout
+    output_file: Option<String>,
+    output: OutputFormat,
+    /// `--max-col-width`: columns of each cell of a table
+    max_col_width: Option<usize>,
+    /// `-c`, or an indent of 0
+    compact: bool,
+    /// `--indent`: spaces per level of pretty JSON output
+    indent: usize,
+    /// `-r`, `-0` or `--join`: strings as their contents and other values as compact JSON
+    raw_output: Option<Delimiter>,
+    /// `-a`: non-ASCII characters of strings escaped
+    ascii_output: bool,
+    /// `--format`: a line of text for each result
+    template: Option<Template>,
+    /// `--max-output-depth` and `--truncate-strings`
+    preview: Option<Preview>,
+    sort_keys: bool,
+    color: ColorChoice,
+    limits: Limits,
+    error_format: ErrorFormat,
+    /// `--time`: report timings on stderr in this format
+    time: Option<TimeFormat>,
+    /// `--watch`: run again whenever one of `files` changes
+    watch: bool,
+    /// `--clear`: clear the screen instead of printing a separator before each rerun
+    clear: bool,
+    /// Invalid `JPP_*` variables, ignored
+    warnings: Vec<String>,
+}
 
+impl QueryArgs {
+    /// Indent of pretty JSON output, or `None` for compact output
+    fn pretty(&self) -> Option<usize> {
+        (!self.compact).then_some(self.indent)
+    }
+
+    /// Whether results are written as a copy changed by `--sort-keys` or a preview
+    fn reshapes(&self) -> bool {
+        self.sort_keys || self.preview.is_some()
+    }
+
+    /// Copy of `value` as it is written, with its keys sorted for `--sort-keys` and cut short
+    /// for `--max-output-depth` and `--truncate-strings`
+    fn shaped(&self, value: &Value) -> Value {
+        let sorted;
+        let value = if self.sort_keys {
+            sorted = sorted_keys(value);
+            &sorted
+        } else {
+            value
+        };
+        match &self.preview {
+            Some(preview) => preview.apply(value),
+            None => value.clone(),
+        }
+    }
+}
+
+fn parse_limit(value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| {
+        format!("invalid value '{value}' for '--limit <N>' (expected a non-negative integer)")
+    })
+}
+
+/// Read a query saved in a file: `#` comment lines are dropped, and so is the single trailing
+/// newline that editors add and that the RFC's no-trailing-whitespace rule would reject
+fn read_query_file(path: &str) -> Result<String, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("error reading query file '{path}': {e}"))?;
+    let text = match text.strip_suffix('\n') {
+        Some(text) => text.strip_suffix('\r').unwrap_or(text),
+        None => &text,
+    };
+    Ok(text
+        .split('\n')
+        .filter(|line| !line.trim_start().starts_with('#'))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// `-f`: a query read from `path`
+fn query_file_spec(path: &str) -> Result<QuerySpec, String> {
+    Ok(QuerySpec {
+        file: Some(path.to_string()),
+        ..QuerySpec::from(read_query_file(path)?)
+    })
+}
+
+fn set_label(queries: &mut [QuerySpec], label: &str) -> Result<(), String> {
+    let spec = queries
+        .last_mut()
+        .ok_or("--label must follow --query or --query-file")?;
+    spec.label = Some(label.to_string());
+    Ok(())
+}
+
+const USAGE_HINT: &str =
+    "Usage: jpp [OPTIONS] <QUERY> [FILE]...\n\nFor more information, try '--help'";
+
+/// Parse the command line, with defaults for some flags from `env`, the environment
+fn parse_args(args: &[String], env: impl Fn(&str) -> Option<String>) -> Result<ParsedArgs, String> {
     if args.is_empty() {
-        return Err("missing required argument: <QUERY>\n\nUsage: jpp [OPTIONS] <QUERY> [FILE]\n\nFor more information, try '--help'".to_string());
+        return Err(format!(
+            "missing required argument: <QUERY>\n\n{USAGE_HINT}"
+        ));
+    }
+    if args[0] == "completions" {
+        return match &args[1..] {
+            [shell] => Ok(ParsedArgs::Completions(Shell::parse(shell)?)),
+            _ => Err(format!(
+                "expected one shell after 'completions' ({})",
+                options::SHELLS.join(", ")
+            )),
+        };
     }
 
     let mut positional = Vec::new();
+    let mut queries: Vec<QuerySpec> = Vec::new();
+    let mut variables = Variables::default();
+    let mut with_filename = None;
+    let mut recursive = false;
+    let mut include = Vec::new();
+    let mut list_files = None;
+    let mut jobs = None;
+    let mut http = http::Client::default();
+    let mut follow = false;
+    let mut from_start = false;
+    let mut null_input = false;
+    let mut input_format = None;
+    let mut slurp = false;
+    let mut stream = false;
+    let mut multi = false;
+    let mut with_line_numbers = false;
+    let mut exit_status = false;
+    let mut diff = None;
+    let mut limit = None;
+    let mut sort = None;
+    let mut count = false;
+    let mut total = false;
+    let mut paths = false;
+    let mut pointer = false;
+    let mut entries = false;
+    let mut edit = None;
+    let mut quiet = 0;
+    let mut in_place = false;
+    let mut backup = None;
+    let mut output_file = None;
+    let mut max_col_width = None;
+    // Flags with a default from the environment
+    let mut flags = Config::default();
+    let mut compact = false;
+    let mut ascii_output = false;
+    let mut raw_output = None;
+    let mut template = None;
+    let mut format_missing = None;
+    let mut preview = Preview::default();
+    let mut validate = false;
+    let mut explain = None;
+    let mut error_format = ErrorFormat::Human;
+    let mut limits = Limits::default();
+    let mut time = None;
+    let mut watch = false;
+    let mut clear = false;
 
-    for arg in &args {
-        match arg.as_str() {
-            "-h" | "--help" => return Ok(ParsedArgs::Help),
-            "-V" | "--version" => return Ok(ParsedArgs::Version),
-            s if s.starts_with('-') => {
-                return Err(format!(
-                    "unknown option: {s}\n\nUsage: jpp [OPTIONS] <QUERY> [FILE]\n\nFor more information, try '--help'"
-                ));
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--" {
+            // Everything after `--` is a query or a file, even when it starts with a dash
+            positional.extend(iter.by_ref().cloned());
+            break;
+        }
+        // The one bundle of short options, like `grep -qq`
+        if arg == "-qq" {
+            quiet = 2;
+            continue;
+        }
+        // `-` is a file: standard input
+        if !arg.starts_with('-') || arg == "-" {
+            positional.push(arg.clone());
+            continue;
+        }
+        let Some((option, mut inline)) = options::lookup(arg) else {
+            return Err(format!("unknown option: {arg}\n\n{USAGE_HINT}"));
+        };
+        if inline.is_some() && option.values.len() != 1 {
+            return Err(match option.values {
+                [] => format!("option '{}' does not take a value", option.long),
+                _ => format!(
+                    "'{}' takes its values as separate arguments",
+                    option.usage()
+                ),
+            });
+        }
+        let mut value = || {
+            inline
+                .take()
+                .or_else(|| iter.next().map(String::as_str))
+                .ok_or_else(|| format!("missing value for '{}'", option.usage()))
+        };
+        match option.long {
+            "--help" => return Ok(ParsedArgs::Help),
+            "--version" => return Ok(ParsedArgs::Version),
+            "--output" => flags.output = Some(OutputFormat::parse(value()?)?),
+            "--input-format" => input_format = Some(InputFormat::parse(value()?)?),
+            "--ndjson" => input_format = Some(InputFormat::Ndjson),
+            "--raw-input" => input_format = Some(InputFormat::Raw),
+            "--slurp" => slurp = true,
+            "--stream" => stream = true,
+            "--multi" => multi = true,
+            "--with-line-numbers" => with_line_numbers = true,
+            "--color" => flags.color = Some(ColorChoice::parse(value()?)?),
+            "--query" => queries.push(QuerySpec::from(value()?.to_string())),
+            "--query-file" => queries.push(query_file_spec(value()?)?),
+            "--label" => set_label(&mut queries, value()?)?,
+            "--arg" => {
+                let (name, value) = (value()?, value()?);
+                variables.define("--arg", name, &Value::String(value.to_string()))?;
+            }
+            "--argjson" => {
+                let (name, value) = (value()?, value()?);
+                let value: Value = serde_json::from_str(value)
+                    .map_err(|e| format!("invalid JSON for '--argjson' variable '{name}': {e}"))?;
+                variables.define("--argjson", name, &value)?;
+            }
+            "--null-input" => null_input = true,
+            "--exit-status" => exit_status = true,
+            "--diff" => {
+                diff.get_or_insert(DiffFormat::Text);
+            }
+            "--diff-format" => diff = Some(DiffFormat::parse(value()?)?),
+            "--validate" => validate = true,
+            "--explain" => {
+                explain.get_or_insert(ExplainFormat::Human);
             }
-            _ => positional.push(arg.clone()),
+            "--explain-format" => explain = Some(ExplainFormat::parse(value()?)?),
+            "--error-format" => error_format = ErrorFormat::parse(value()?)?,
+            "--time" => {
+                time.get_or_insert(TimeFormat::Text);
+            }
+            "--time-format" => time = Some(TimeFormat::parse(value()?)?),
+            "--max-input-bytes" => {
+                limits.max_input_bytes = Some(limits::parse_count(&option.usage(), value()?)?);
+            }
+            "--max-results" => {
+                limits.max_results = Some(limits::parse_count(&option.usage(), value()?)?);
+            }
+            "--max-depth" => {
+                limits.max_depth = Some(limits::parse_count(&option.usage(), value()?)?);
+            }
+            "--timeout" => {
+                limits.timeout = Some(limits::parse_timeout(&option.usage(), value()?)?);
+            }
+            "--watch" => watch = true,
+            "--clear" => clear = true,
+            "--limit" => limit = Some(parse_limit(value()?)?),
+            "--first" => limit = Some(1),
+            "--sort" => {
+                sort.get_or_insert(Sort::Values);
+            }
+            "--sort-by" => sort = Some(Sort::by(value()?)?),
+            "--count" => count = true,
+            "--total" => total = true,
+            "--paths" => paths = true,
+            "--pointer" => pointer = true,
+            "--entries" => entries = true,
+            "--delete" => set_edit(&mut edit, Edit::Delete)?,
+            "--set" | "--replace" => set_edit(&mut edit, parse_set_value(value()?)?)?,
+            "--set-raw" => {
+                set_edit(&mut edit, Edit::Set(Value::String(value()?.to_string())))?;
+            }
+            "--quiet" => quiet = (quiet + 1).min(2),
+            "--in-place" => in_place = true,
+            "--backup" => backup = Some(value()?.to_string()),
+            "--output-file" => output_file = Some(value()?.to_string()),
+            "--lines" => flags.output = Some(OutputFormat::Ndjson),
+            "--max-col-width" => max_col_width = Some(table::parse_max_width(value()?)?),
+            "--compact" => compact = true,
+            "--indent" => flags.indent = Some(config::parse_indent(value()?)?),
+            "--raw-output" => {
+                raw_output.get_or_insert(Delimiter::Newline);
+            }
+            "--ascii-output" => ascii_output = true,
+            "--format" => template = Some(value()?.to_string()),
+            "--format-missing" => format_missing = Some(value()?.to_string()),
+            "--max-output-depth" => preview.max_depth = Some(preview::parse_depth(value()?)?),
+            "--truncate-strings" => preview.max_chars = Some(preview::parse_chars(value()?)?),
+            "--null-output" => set_delimiter(&mut raw_output, Delimiter::Nul)?,
+            "--join" => set_delimiter(&mut raw_output, Delimiter::Join(value()?.to_string()))?,
+            "--sort-keys" => flags.sort_keys = Some(true),
+            "--with-filename" => with_filename = Some(true),
+            "--no-filename" => with_filename = Some(false),
+            "--recursive" => recursive = true,
+            "--include" => include.push(value()?.to_string()),
+            "--files-with-matches" => set_list_files(&mut list_files, ListFiles::WithMatches)?,
+            "--files-without-match" => set_list_files(&mut list_files, ListFiles::WithoutMatch)?,
+            "--jobs" => jobs = Some(parallel::parse_jobs(value()?)?),
+            "--header" => http.headers.push(http::parse_header(value()?)?),
+            "--http-timeout" => {
+                http.timeout = limits::parse_timeout(&option.usage(), value()?)?;
+            }
+            "--follow" => follow = true,
+            "--from-start" => from_start = true,
+            long => return Err(format!("option '{long}' is not implemented")),
         }
     }
 
-    match positional.len() {
-        0 => Err("missing required argument: <QUERY>\n\nUsage: jpp [OPTIONS] <QUERY> [FILE]\n\nFor more information, try '--help'".to_string()),
-        1 => Ok(ParsedArgs::Query {
-            query: positional.into_iter().next().unwrap_or_default(),
-            file: None,
+    if (validate || explain.is_some()) && time.is_some() {
+        return Err("--time measures queries run over input and cannot be used with --validate or --explain".to_string());
+    }
+    if validate || explain.is_some() {
+        // Nothing is read but the queries, so every argument is one
+        queries.extend(positional.into_iter().map(QuerySpec::from));
+        return match explain {
+            _ if validate && explain.is_some() => {
+                Err("--validate and --explain cannot be used together".to_string())
+            }
+            Some(_) if queries.is_empty() => Err("missing required argument: <QUERY>\n\nUsage: jpp --explain [QUERY]... [-f <QUERY_FILE>]...".to_string()),
+            Some(format) => Ok(ParsedArgs::Explain(ExplainArgs {
+                queries,
+                variables,
+                format,
+            })),
+            None => Ok(ParsedArgs::Validate(ValidateArgs {
+                queries,
+                variables,
+                error_format,
+            })),
+        };
+    }
+
+    let mut positional = positional.into_iter();
+    if queries.is_empty() {
+        let query = positional
+            .next()
+            .ok_or_else(|| format!("missing required argument: <QUERY>\n\n{USAGE_HINT}"))?;
+        queries.push(QuerySpec::from(query));
+    } else if positional
+        .as_slice()
+        .first()
+        .is_some_and(|arg| arg.trim_start().starts_with('$') && !Path::new(arg).exists())
+    {
+        // Every query starts with `$`, possibly after whitespace, unlike the file names that
+        // follow --query options but for files that are really named so
+        return Err(
+            "a query cannot be given both with --query/--query-file and as an argument".to_string(),
+        );
+    }
+    let keyed = queries.len() > 1 || queries.iter().any(|spec| spec.label.is_some());
+    let mut labels = HashSet::new();
+    if let Some(spec) = queries.iter().find(|spec| !labels.insert(spec.label())) {
+        return Err(format!("duplicate query label '{}'", spec.label()));
+    }
+    if total && queries.len() > 1 {
+        return Err("--total cannot be used with several queries".to_string());
+    }
+    if paths && pointer {
+        return Err("--paths and --pointer cannot be used together".to_string());
+    }
+    // Without --entries, --pointer prints the locations the way --paths does
+    let paths = paths || (pointer && !entries);
+    if let Some(edit) = &edit
+        && (count || total || paths || entries || limit.is_some() || sort.is_some())
+    {
+        let option = match edit {
+            Edit::Delete => "--delete",
+            Edit::Set(_) => "--set",
+        };
+        return Err(format!(
+            "{option} prints whole documents and cannot be used with --count, --total, --paths, --pointer, --entries, --limit or --sort"
+        ));
+    }
+    if matches!(raw_output, Some(Delimiter::Nul | Delimiter::Join(_)))
+        && (count || total || paths || keyed)
+    {
+        return Err(
+            "--join and --null-output write raw results and cannot be used with --count, --total, --paths, --pointer or several queries"
+                .to_string(),
+        );
+    }
+    let template = match template {
+        Some(_)
+            if raw_output.is_some()
+                || flags.output.is_some()
+                || count
+                || total
+                || paths
+                || entries
+                || edit.is_some()
+                || keyed =>
+        {
+            return Err(
+                "--format writes a line for each result and cannot be used with -r, --null-output, --join, --output, --count, --total, --paths, --pointer, --entries, --delete, --set or several queries"
+                    .to_string(),
+            );
+        }
+        Some(template) => Some(Template::parse(
+            &template,
+            format_missing.unwrap_or_default(),
+        )?),
+        None if format_missing.is_some() => {
+            return Err("--format-missing needs --format".to_string());
+        }
+        None => None,
+    };
+    let preview = (preview != Preview::default()).then_some(preview);
+    if preview.is_some() && (in_place || template.is_some()) {
+        return Err(
+            "--max-output-depth and --truncate-strings shorten JSON results for reading and cannot be used with --in-place or --format"
+                .to_string(),
+        );
+    }
+    if sort.is_some() && (count || total) {
+        return Err("--sort orders results and cannot be used with --count or --total".to_string());
+    }
+    let files: Vec<String> = positional.collect();
+    if null_input && !files.is_empty() {
+        return Err("--null-input cannot be used with FILE arguments".to_string());
+    }
+    if stream && (null_input || slurp || input_format.is_some_and(|f| f != InputFormat::Json)) {
+        return Err(
+            "--stream reads JSON input and cannot be used with --null-input, --slurp or another --input-format"
+                .to_string(),
+        );
+    }
+    if multi && (null_input || stream || input_format.is_some_and(|f| f != InputFormat::Json)) {
+        return Err(
+            "--multi reads JSON input and cannot be used with --null-input, --stream or another --input-format"
+                .to_string(),
+        );
+    }
+    if in_place {
+        if edit.is_none() {
+            return Err(
+                "--in-place writes back the documents edited by --delete or --set, so it needs one of them"
+                    .to_string(),
+            );
+        }
+        if files.is_empty() {
+            return Err("--in-place needs FILE arguments to edit".to_string());
+        }
+        if files.iter().any(|file| file == "-") {
+            return Err("--in-place cannot write back to standard input '-'".to_string());
+        }
+        if let Some(url) = files.iter().find(|file| http::is_url(file)) {
+            return Err(format!("--in-place cannot write back to the URL '{url}'"));
+        }
+        if output_file.is_some()
+            || stream
+            || slurp
+            || with_line_numbers
+            || with_filename == Some(true)
+        {
+            return Err(
+                "--in-place cannot be used with --output-file, --stream, --slurp, --with-line-numbers or --with-filename"
+                    .to_string(),
+            );
+        }
+    }
+    if list_files.is_some()
+        && (count || paths || entries || edit.is_some() || sort.is_some() || raw_output.is_some())
+    {
+        return Err(
+            "--files-with-matches and --files-without-match print file names and cannot be used with --count, --total, --paths, --pointer, --entries, --delete, --set, --sort or raw output"
+                .to_string(),
+        );
+    }
+    if !include.is_empty() && !recursive {
+        return Err("--include needs --recursive".to_string());
+    }
+    if recursive && (null_input || files.is_empty()) {
+        return Err(
+            "--recursive needs FILE arguments and cannot be used with --null-input".to_string(),
+        );
+    }
+    if follow {
+        if files.len() != 1 || files[0] == "-" || http::is_url(&files[0]) {
+            return Err("--follow needs one FILE to follow".to_string());
+        }
+        if !matches!(input_format, Some(InputFormat::Ndjson | InputFormat::Raw)) {
+            return Err("--follow reads lines; add --ndjson or --raw-input".to_string());
+        }
+        if slurp || count || total || in_place || output_file.is_some() || watch || recursive {
+            return Err(
+                "--follow never reaches the end of its input and cannot be used with --slurp, --count, --total, --in-place, --output-file, --watch or --recursive"
+                    .to_string(),
+            );
+        }
+    } else if from_start {
+        return Err("--from-start needs --follow".to_string());
+    }
+    if let Some(format) = diff {
+        if files.len() != 2 {
+            return Err(
+                "--diff compares the results for two inputs; give two FILE arguments".to_string(),
+            );
+        }
+        if recursive
+            || follow
+            || watch
+            || in_place
+            || output_file.is_some()
+            || quiet > 0
+            || total
+            || list_files.is_some()
+        {
+            return Err(
+                "--diff prints the differences and cannot be used with --recursive, --follow, --watch, --in-place, --output-file, --quiet, --total, --files-with-matches or --files-without-match"
+                    .to_string(),
+            );
+        }
+        // With --pointer alone, `paths` is set to print the locations
+        if format == DiffFormat::Json
+            && (keyed
+                || count
+                || (paths && !pointer)
+                || entries
+                || edit.is_some()
+                || template.is_some())
+        {
+            return Err(
+                "--diff-format json compares results by location and cannot be used with several queries, --count, --paths, --entries, --delete, --set or --format"
+                    .to_string(),
+            );
+        }
+    }
+    if quiet > 0 && edit.is_none() && output_file.is_some() {
+        return Err("--quiet prints no results and cannot be used with --output-file".to_string());
+    }
+    if watch {
+        if files.is_empty() || files.iter().any(|file| file == "-") {
+            return Err(
+                "--watch needs FILE arguments to watch; standard input cannot be watched"
+                    .to_string(),
+            );
+        }
+        if let Some(url) = files.iter().find(|file| http::is_url(file)) {
+            return Err(format!("--watch cannot watch the URL '{url}'"));
+        }
+        if in_place || output_file.is_some() || time.is_some() {
+            return Err(
+                "--watch cannot be used with --in-place, --output-file or --time".to_string(),
+            );
+        }
+        // Only the directories themselves would be watched, not the files under them
+        if recursive {
+            return Err("--watch cannot be used with --recursive".to_string());
+        }
+    } else if clear {
+        return Err("--clear needs --watch".to_string());
+    }
+    match &backup {
+        Some(_) if !in_place => return Err("--backup needs --in-place".to_string()),
+        Some(suffix) if suffix.is_empty() => {
+            return Err("--backup needs a non-empty suffix".to_string());
+        }
+        _ => {}
+    }
+    let (config, warnings) = Config::from_env_and_args(env, flags);
+    let indent = config.indent.unwrap_or(2);
+    // Results of line-delimited or streamed input are line-delimited unless asked otherwise
+    let output = config.output.unwrap_or(match input_format {
+        Some(InputFormat::JsonSeq) => OutputFormat::JsonSeq,
+        Some(InputFormat::Ndjson | InputFormat::Raw) => OutputFormat::Ndjson,
+        _ if stream => OutputFormat::Ndjson,
+        _ => OutputFormat::Json,
+    });
+    if max_col_width.is_some() && output.table_style().is_none() {
+        return Err("--max-col-width needs --output table or --output markdown".to_string());
+    }
+    Ok(ParsedArgs::Query(Box::new(QueryArgs {
+        queries,
+        keyed,
+        variables,
+        files,
+        with_filename,
+        recursive,
+        include: if include.is_empty() {
+            vec![walk::DEFAULT_INCLUDE.to_string()]
+        } else {
+            include
+        },
+        list_files,
+        jobs: jobs.unwrap_or_else(parallel::default_jobs),
+        http,
+        follow,
+        from_start,
+        null_input,
+        input_format,
+        slurp,
+        stream,
+        multi,
+        with_line_numbers,
+        exit_status,
+        diff,
+        limit,
+        sort,
+        // `--total` sums counts, so it implies `--count`
+        count: count || total,
+        total,
+        paths,
+        entries,
+        pointer,
+        edit,
+        quiet,
+        in_place,
+        backup,
+        output_file,
+        output,
+        max_col_width,
+        compact: compact || indent == 0,
+        indent,
+        raw_output,
+        ascii_output,
+        template,
+        preview,
+        sort_keys: config.sort_keys.unwrap_or(false),
+        color: config.color.unwrap_or(ColorChoice::Auto),
+        limits,
+        error_format,
+        time,
+        watch,
+        clear,
+        warnings,
+    })))
+}
+
+/// Copy of `value` with the members of every object inserted in lexicographic key order, so the
+/// output is sorted whether or not the map type preserves insertion order
+fn sorted_keys(value: &Value) -> Value {
+    match value {
+        Value::Array(items) => Value::Array(items.iter().map(sorted_keys).collect()),
+        Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+            Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| (key.clone(), sorted_keys(value)))
+                    .collect(),
+            )
+        }
+        other => other.clone(),
+    }
+}
+
+/// Write `value` as JSON indented by `indent` spaces per level, or compact for `None`
+fn write_json<T: Serialize + ?Sized>(
+    out: &mut impl Write,
+    value: &T,
+    indent: Option<usize>,
+    ascii: bool,
+) -> io::Result<()> {
+    match indent {
+        Some(indent) => {
+            let indent = " ".repeat(indent);
+            let formatter = PrettyFormatter::with_indent(indent.as_bytes());
+            ascii::serialize(out, value, formatter, ascii)
+        }
+        None => ascii::serialize(out, value, CompactFormatter, ascii),
+    }
+}
+
+/// Write `value` as compact JSON, colored when a palette is given
+fn write_compact(
+    out: &mut impl Write,
+    value: &Value,
+    palette: Option<&Palette>,
+    ascii: bool,
+) -> io::Result<()> {
+    match palette {
+        Some(palette) => ColorWriter::new(palette, None, ascii).write_value(out, value),
+        None => write_json(out, value, None, ascii),
+    }
+}
+
+/// Write each result as a record ended or separated by `delimiter`: strings as their raw
+/// contents, other values as compact JSON. A string containing newlines is written verbatim
+/// and spans several lines, which `-0` makes unambiguous. `joined` tells whether `--join` has
+/// written a record already, so the next one needs a separator.
+fn write_raw(
+    out: &mut impl Write,
+    results: &[&Value],
+    palette: Option<&Palette>,
+    delimiter: &Delimiter,
+    joined: &mut bool,
+    ascii: bool,
+) -> io::Result<()> {
+    for result in results {
+        if let Delimiter::Join(separator) = delimiter
+            && mem::replace(joined, true)
+        {
+            out.write_all(separator.as_bytes())?;
+        }
+        match result {
+            Value::String(s) if ascii => {
+                out.write_all(ascii::escape(s, Astral::SurrogatePair).as_bytes())?
+            }
+            Value::String(s) => out.write_all(s.as_bytes())?,
+            other => write_compact(out, other, palette, ascii)?,
+        }
+        match delimiter {
+            Delimiter::Newline => out.write_all(b"\n")?,
+            Delimiter::Nul => out.write_all(b"\0")?,
+            Delimiter::Join(_) => {}
+        }
+    }
+    Ok(())
+}
+
+/// Write each result as one line of compact JSON. String escaping keeps embedded newlines
+/// inside their record, so line count always equals result count.
+fn write_ndjson(
+    out: &mut impl Write,
+    results: &[&Value],
+    palette: Option<&Palette>,
+    ascii: bool,
+) -> io::Result<()> {
+    for result in results {
+        write_compact(out, result, palette, ascii)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Write each result as a record of a JSON text sequence: the record separator, compact JSON
+/// and a newline
+fn write_json_seq(
+    out: &mut impl Write,
+    results: &[&Value],
+    palette: Option<&Palette>,
+    ascii: bool,
+) -> io::Result<()> {
+    for result in results {
+        out.write_all(&[RECORD_SEPARATOR])?;
+        write_compact(out, result, palette, ascii)?;
+        out.write_all(b"\n")?;
+    }
+    Ok(())
+}
+
+/// Write `results` in the format selected by `args`
+fn write_results(
+    out: &mut impl Write,
+    args: &QueryArgs,
+    results: &[&Value],
+    palette: Option<&Palette>,
+    joined: &mut bool,
+) -> io::Result<()> {
+    let ascii = args.ascii_output;
+    if let Some(delimiter) = &args.raw_output {
+        return write_raw(out, results, palette, delimiter, joined, ascii);
+    }
+    if let Some(template) = &args.template {
+        return results
+            .iter()
+            .try_for_each(|result| template.write_line(out, result, ascii));
+    }
+    match args.output {
+        OutputFormat::Ndjson => write_ndjson(out, results, palette, ascii),
+        OutputFormat::JsonSeq => write_json_seq(out, results, palette, ascii),
+        OutputFormat::Yaml => yaml::write_sequence(out, results, ascii),
+        OutputFormat::YamlDocs => results
+            .iter()
+            .try_for_each(|result| yaml::write_document(out, result, ascii)),
+        OutputFormat::Table | OutputFormat::Markdown => {
+            let style = args.output.table_style().unwrap_or(Style::Text);
+            table::write_table(out, results, style, args.max_col_width, ascii)
+        }
+        OutputFormat::Json => {
+            match palette {
+                Some(palette) => {
+                    ColorWriter::new(palette, args.pretty(), ascii).write_array(out, results)?
+                }
+                None => write_json(out, results, args.pretty(), ascii)?,
+            }
+            out.write_all(b"\n")
+        }
+    }
+}
+
+/// Write one whole document, such as the result of `--delete`, in the format selected by `args`
+fn write_document(
+    out: &mut impl Write,
+    args: &QueryArgs,
+    document: &Value,
+    palette: Option<&Palette>,
+    joined: &mut bool,
+) -> io::Result<()> {
+    let ascii = args.ascii_output;
+    if let Some(delimiter) = &args.raw_output {
+        return write_raw(out, &[document], palette, delimiter, joined, ascii);
+    }
+    match args.output {
+        OutputFormat::Ndjson => write_ndjson(out, &[document], palette, ascii),
+        OutputFormat::JsonSeq => write_json_seq(out, &[document], palette, ascii),
+        OutputFormat::Yaml | OutputFormat::YamlDocs => yaml::write_document(out, document, ascii),
+        OutputFormat::Table | OutputFormat::Markdown => {
+            let style = args.output.table_style().unwrap_or(Style::Text);
+            table::write_table(out, &[document], style, args.max_col_width, ascii)
+        }
+        OutputFormat::Json => {
+            match palette {
+                Some(palette) => {
+                    ColorWriter::new(palette, args.pretty(), ascii).write_value(out, document)?
+                }
+                None => write_json(out, document, args.pretty(), ascii)?,
+            }
+            out.write_all(b"\n")
+        }
+    }
+}
+
+/// Run `write` on `out`, prefixed with `label` at the start of every line when given
+fn write_labeled<W: Write>(
+    out: &mut W,
+    label: Option<&str>,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    match label {
+        Some(label) => write(&mut PrefixWriter::new(out, label.as_bytes())),
+        None => write(out),
+    }
+}
+
+/// Writes `prefix` at the start of every line, the way grep labels matches with a file name
+struct PrefixWriter<'p, W: Write> {
+    inner: W,
+    prefix: &'p [u8],
+    at_line_start: bool,
+}
+
+impl<'p, W: Write> PrefixWriter<'p, W> {
+    fn new(inner: W, prefix: &'p [u8]) -> Self {
+        Self {
+            inner,
+            prefix,
+            at_line_start: true,
+        }
+    }
+}
+
+impl<W: Write> Write for PrefixWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                self.inner.write_all(self.prefix)?;
+            }
+            self.inner.write_all(line)?;
+            self.at_line_start = line.ends_with(b"\n");
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// `options` for a query keeping only its first `limit` results: more results than
+/// `--max-results` are fine when `limit` is lower
+fn options_within(options: &EvalOptions, limit: Option<usize>) -> EvalOptions {
+    EvalOptions {
+        max_results: options
+            .max_results
+            .filter(|&max| limit.is_none_or(|limit| limit > max)),
+        ..*options
+    }
+}
+
+/// Results of `path`, in `sort` order when given, stopping after `limit` when given
+fn query<'a>(
+    path: &JsonPath,
+    json: &'a Value,
+    limit: Option<usize>,
+    sort: Option<&Sort>,
+    options: &EvalOptions,
+) -> Result<Vec<&'a Value>, EvalError> {
+    if let Some(sort) = sort {
+        // The first `limit` results in order are only known once all are sorted
+        let mut results = path.query_with_options(json, options)?;
+        sort.sort(&mut results, |result| result);
+        if let Some(limit) = limit {
+            results.truncate(limit);
+        }
+        return Ok(results);
+    }
+    if *options == EvalOptions::default() {
+        return Ok(match limit {
+            Some(limit) => path.query_limit(json, limit),
+            None => path.query(json),
+        });
+    }
+    let mut results = path.query_with_options(json, &options_within(options, limit))?;
+    if let Some(limit) = limit {
+        results.truncate(limit);
+    }
+    Ok(results)
+}
+
+fn count_results(
+    path: &JsonPath,
+    json: &Value,
+    limit: Option<usize>,
+    options: &EvalOptions,
+) -> Result<usize, EvalError> {
+    if *options == EvalOptions::default() && limit.is_none() {
+        return Ok(path.count(json));
+    }
+    query(path, json, limit, None, options).map(|results| results.len())
+}
+
+/// Results of `path` with their locations, in `sort` order and the first `limit` when given
+fn query_located<'a>(
+    path: &JsonPath,
+    json: &'a Value,
+    limit: Option<usize>,
+    sort: Option<&Sort>,
+    options: &EvalOptions,
+) -> Result<Vec<(NormalizedPath, &'a Value)>, EvalError> {
+    let within = match sort {
+        Some(_) => *options,
+        None => options_within(options, limit),
+    };
+    let mut located = path.query_located_with_options(json, &within)?;
+    if let Some(sort) = sort {
+        sort.sort(&mut located, |(_, value)| value);
+    }
+    if let Some(limit) = limit {
+        located.truncate(limit);
+    }
+    Ok(located)
+}
+
+/// Location of a result as printed by `--paths`, `--pointer` and `--entries`
+fn location(args: &QueryArgs, location: &NormalizedPath) -> String {
+    if args.pointer {
+        location.to_json_pointer()
+    } else {
+        location.to_string()
+    }
+}
+
+/// `--entries` object for one result, with a `pointer` rather than a `path` under `--pointer`
+fn entry(args: &QueryArgs, location: &NormalizedPath, value: &Value) -> Value {
+    let key = if args.pointer { "pointer" } else { "path" };
+    json!({key: self::location(args, location), "value": value})
+}
+
+/// Result of each query against `json`, labeled: the count, the paths, the entries or the
+/// values, as selected by `args`
+fn keyed_results(
+    args: &QueryArgs,
+    paths: &[JsonPath],
+    json: &Value,
+    options: &EvalOptions,
+) -> Result<Vec<(String, Value)>, EvalError> {
+    args.queries
+        .iter()
+        .zip(paths)
+        .map(|(spec, path)| {
+            let result = if args.count {
+                Value::from(count_results(path, json, args.limit, options)?)
+            } else if args.paths {
+                query_located(path, json, args.limit, args.sort.as_ref(), options)?
+                    .into_iter()
+                    .map(|(location, _)| Value::String(self::location(args, &location)))
+                    .collect()
+            } else if args.entries {
+                query_located(path, json, args.limit, args.sort.as_ref(), options)?
+                    .into_iter()
+                    .map(|(location, value)| entry(args, &location, value))
+                    .collect()
+            } else {
+                query(path, json, args.limit, args.sort.as_ref(), options)?
+                    .into_iter()
+                    .cloned()
+                    .collect()
+            };
+            let result = if args.reshapes() {
+                args.shaped(&result)
+            } else {
+                result
+            };
+            Ok((spec.label().to_string(), result))
+        })
+        .collect()
+}
+
+/// Number of matches in a result of [`keyed_results`]: the count or the length of the list
+fn result_matches(result: &Value) -> usize {
+    match result {
+        Value::Array(items) => items.len(),
+        count => count.as_u64().map_or(0, |count| count as usize),
+    }
+}
+
+/// Write labeled results as one object, or as one single-member object per line for NDJSON
+/// and per record for JSON text sequences, in the order of the queries
+fn write_keyed(
+    out: &mut impl Write,
+    args: &QueryArgs,
+    keyed: Vec<(String, Value)>,
+    palette: Option<&Palette>,
+) -> io::Result<()> {
+    let ascii = args.ascii_output;
+    match args.output {
+        OutputFormat::Ndjson | OutputFormat::JsonSeq => {
+            keyed.into_iter().try_for_each(|(label, result)| {
+                let object = Value::Object(Map::from_iter([(label, result)]));
+                if args.output == OutputFormat::JsonSeq {
+                    write_json_seq(out, &[&object], palette, ascii)
+                } else {
+                    write_ndjson(out, &[&object], palette, ascii)
+                }
+            })
+        }
+        OutputFormat::Yaml => yaml::write_entries(out, &keyed, ascii),
+        OutputFormat::YamlDocs => keyed.into_iter().try_for_each(|(label, result)| {
+            let object = Value::Object(Map::from_iter([(label, result)]));
+            yaml::write_document(out, &object, ascii)
         }),
-        2 => {
-            let mut iter = positional.into_iter();
-            Ok(ParsedArgs::Query {
-                query: iter.next().unwrap_or_default(),
-                file: iter.next(),
+        // One row, with a column for each query
+        OutputFormat::Table | OutputFormat::Markdown => {
+            let style = args.output.table_style().unwrap_or(Style::Text);
+            table::write_entries(out, &keyed, style, args.max_col_width, ascii)
+        }
+        OutputFormat::Json => {
+            match palette {
+                Some(palette) => {
+                    ColorWriter::new(palette, args.pretty(), ascii).write_entries(out, &keyed)?
+                }
+                None => write_json(out, &Entries(&keyed), args.pretty(), ascii)?,
+            }
+            out.write_all(b"\n")
+        }
+    }
+}
+
+/// Labeled results serialized as a JSON object in query order, which a [`Map`] would sort
+struct Entries<'a>(&'a [(String, Value)]);
+
+impl Serialize for Entries<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.0.iter().map(|(label, result)| (label, result)))
+    }
+}
+
+/// Read an input whose format holds exactly one document
+fn load_single_document(
+    input: Input,
+    format: InputFormat,
+    meter: Option<&ReadMeter>,
+) -> Result<Value, String> {
+    match format {
+        InputFormat::Toml => load_toml_document(input, meter),
+        InputFormat::Jsonc => load_jsonc_document(input, meter),
+        _ => load_document(input, meter),
+    }
+}
+
+/// Ends reading an input at its first match, after which nothing changes the outcome: the
+/// whole run for `--quiet`, the input for `--files-with-matches`
+#[derive(Debug)]
+struct FirstMatch;
+
+impl fmt::Display for FirstMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("stopped at the first match")
+    }
+}
+
+impl std::error::Error for FirstMatch {}
+
+/// Evaluates the queries against each input document and writes the results
+struct Runner<'a, W: Write> {
+    args: &'a QueryArgs,
+    /// Parsed `args.queries`
+    paths: &'a [JsonPath],
+    palette: Option<&'a Palette>,
+    out: W,
+    /// Whether any query matched any document so far
+    matched: bool,
+    /// Whether any input or document could not be read or parsed
+    failed: bool,
+    /// Results counted so far by `--count`, which writes counts per input rather than per
+    /// document
+    total: usize,
+    /// Matches deleted or replaced so far by `--delete` or `--set`
+    edited: usize,
+    /// `--time` measurements so far
+    timing: Option<Timing>,
+    /// Reads of the current input, metered for `--time` and `--max-input-bytes`
+    meter: &'a ReadMeter,
+    /// `--max-results` and `--timeout`
+    budget: Budget,
+    /// Whether `--join` has written a record
+    joined: bool,
+    /// Errors and warnings of a `--jobs` worker, kept for stderr until the results of the
+    /// inputs before are out; `None` writes them right away
+    messages: Option<Vec<u8>>,
+    /// `--diff-format json`: the documents read, kept to be queried once both inputs are
+    documents: Option<Vec<Value>>,
+}
+
+/// What a `--jobs` worker made of one input, for [`Runner::merge`]
+struct Worked {
+    out: Vec<u8>,
+    messages: Vec<u8>,
+    result: io::Result<()>,
+    matched: bool,
+    failed: bool,
+    total: usize,
+    edited: usize,
+}
+
+impl<'a, W: Write> Runner<'a, W> {
+    fn new(
+        args: &'a QueryArgs,
+        paths: &'a [JsonPath],
+        palette: Option<&'a Palette>,
+        out: W,
+        meter: &'a ReadMeter,
+    ) -> Self {
+        Self {
+            args,
+            paths,
+            palette,
+            out,
+            matched: false,
+            failed: false,
+            total: 0,
+            edited: 0,
+            timing: None,
+            meter,
+            budget: Budget::new(&args.limits),
+            joined: false,
+            messages: None,
+            documents: None,
+        }
+    }
+
+    /// Write an error or warning to stderr, or keep it with the other messages of a worker
+    fn message(&mut self, write: impl FnOnce(&mut dyn Write) -> io::Result<()>) {
+        // Failing to write to stderr, there is nowhere left to report it
+        let _ = match &mut self.messages {
+            Some(messages) => write(messages),
+            None => write(&mut io::stderr().lock()),
+        };
+    }
+
+    /// Report an input that cannot be read, parsed or written back as an error about `file`;
+    /// it is skipped and the run fails
+    fn report(&mut self, kind: Kind, file: Option<&str>, error: &str) -> io::Result<()> {
+        self.failed = true;
+        self.out.flush()?;
+        // Reading stopped at `--max-input-bytes` or `--max-depth`, which aborts the run with
+        // its own message
+        if self.meter.exceeded().is_none() && self.args.quiet < 2 {
+            let format = self.args.error_format;
+            let diagnostic = Diagnostic::new(kind, error, EXIT_ERROR).with_file(file);
+            self.message(|mut out| diagnostic.write(&mut out, format));
+        }
+        Ok(())
+    }
+
+    /// Read one input, labeled with its name when `with_filename`
+    fn run_input(&mut self, input: Input, with_filename: bool) -> io::Result<()> {
+        let label = with_filename.then(|| format!("{}:", input.label()));
+        self.input(input, label.as_deref())
+    }
+
+    /// Finish with `input` after reading it to `result`, `failed` telling whether an earlier
+    /// input failed: end the run at a limit, with its exit status, or stop reading inputs at
+    /// the first match of `--quiet`. Lists the input for `--files-with-matches` and
+    /// `--files-without-match`.
+    fn finish_input(
+        &mut self,
+        input: Input,
+        result: io::Result<()>,
+        failed: bool,
+    ) -> Result<ControlFlow<Option<ExitCode>>, Diagnostic> {
+        let args = self.args;
+        if let Err(e) = &result
+            && let Some(limit) = e.get_ref().and_then(|e| e.downcast_ref::<LimitExceeded>())
+        {
+            // Neither `--output-file` nor `--in-place` replaces a file with partial output
+            self.out.flush().map_err(write_error)?;
+            if args.quiet < 2 {
+                Diagnostic::new(Kind::Limit, limit.to_string(), EXIT_LIMIT)
+                    .with_file(input.path())
+                    .report(args.error_format);
+            }
+            return Ok(ControlFlow::Break(Some(ExitCode::from(EXIT_LIMIT))));
+        }
+        let first_match =
+            matches!(&result, Err(e) if e.get_ref().is_some_and(|e| e.is::<FirstMatch>()));
+        if first_match && args.quiet > 0 {
+            self.failed |= failed;
+            return Ok(ControlFlow::Break(None));
+        }
+        if !first_match {
+            result.map_err(write_error)?;
+        }
+        let listed = match args.list_files {
+            Some(ListFiles::WithMatches) => first_match,
+            // An input that could not be read is not known to lack matches
+            Some(ListFiles::WithoutMatch) => !first_match && !self.failed,
+            None => false,
+        };
+        if listed {
+            writeln!(self.out, "{}", input.label()).map_err(write_error)?;
+        }
+        self.failed |= failed;
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Evaluate the queries against every document of `input` and write the results, each
+    /// output line prefixed with `label` when given
+    fn input(&mut self, input: Input, label: Option<&str>) -> io::Result<()> {
+        let meter = self.meter;
+        if self.timing.is_none() && meter.max_bytes.is_none() && meter.max_depth.is_none() {
+            return self.input_documents(input, label, None);
+        }
+        meter.reset();
+        let documents = self
+            .timing
+            .as_ref()
+            .map_or(Duration::ZERO, |timing| timing.documents);
+        let start = Instant::now();
+        let result = self.input_documents(input, label, Some(meter));
+        if let Some(timing) = &mut self.timing {
+            let read = meter.stats.get();
+            timing.input_bytes += read.bytes;
+            timing.read += read.time;
+            // The time spent outside of the documents went into reading and parsing them
+            timing.parse += start
+                .elapsed()
+                .saturating_sub(timing.documents - documents)
+                .saturating_sub(read.time);
+        }
+        if let Some(limit) = meter.exceeded() {
+            return Err(io::Error::other(limit));
+        }
+        result
+    }
+
+    fn input_documents(
+        &mut self,
+        input: Input,
+        label: Option<&str>,
+        meter: Option<&ReadMeter>,
+    ) -> io::Result<()> {
+        let args = self.args;
+        let counted = self.total;
+        let format = args.input_format.unwrap_or_else(|| input.detect_format());
+
+        match format {
+            InputFormat::Jsonc | InputFormat::Yaml | InputFormat::Toml if args.stream => {
+                return self.report(
+                    Kind::Input,
+                    input.path(),
+                    &format!(
+                        "--stream needs JSON input, not {} in '{}'",
+                        format.name(),
+                        input.label()
+                    ),
+                );
+            }
+            InputFormat::Json if args.stream => {
+                if let Err(e) =
+                    for_each_array_element(input, meter, |json| self.document(json, label))?
+                {
+                    self.report(Kind::Input, input.path(), &e)?;
+                }
+            }
+            InputFormat::Json if args.multi => {
+                let documents = match ConcatenatedDocuments::open(input, meter) {
+                    Ok(documents) => documents,
+                    Err(e) => return self.report(Kind::Input, input.path(), &e),
+                };
+                if args.slurp {
+                    match documents.collect() {
+                        Ok(documents) => self.document(Value::Array(documents), label)?,
+                        Err(e) => return self.report(Kind::Input, input.path(), &e),
+                    }
+                } else {
+                    // A document that does not parse is the last one
+                    for document in documents {
+                        match document {
+                            Ok(json) => self.document(json, label)?,
+                            Err(e) => self.report(Kind::Input, input.path(), &e)?,
+                        }
+                    }
+                }
+            }
+            InputFormat::Json | InputFormat::Jsonc | InputFormat::Toml => {
+                match load_single_document(input, format, meter) {
+                    Ok(json) if args.slurp => self.document(Value::Array(vec![json]), label)?,
+                    Ok(json) => self.document(json, label)?,
+                    Err(e) => return self.report(Kind::Input, input.path(), &e),
+                }
+            }
+            InputFormat::Yaml => match load_yaml_documents(input, meter) {
+                Ok(documents) if args.slurp => self.document(Value::Array(documents), label)?,
+                Ok(documents) => {
+                    for json in documents {
+                        self.document(json, label)?;
+                    }
+                }
+                Err(e) => return self.report(Kind::Input, input.path(), &e),
+            },
+            InputFormat::Ndjson | InputFormat::Raw if args.slurp => {
+                // A bad line fails the whole input, since the array would silently lose an
+                // element
+                let documents: Result<Vec<Value>, String> =
+                    LineDocuments::open(input, format, meter).and_then(|documents| {
+                        documents
+                            .map(|document| document.map(|(_, json)| json))
+                            .collect()
+                    });
+                match documents {
+                    Ok(documents) => self.document(Value::Array(documents), label)?,
+                    Err(e) => return self.report(Kind::Input, input.path(), &e),
+                }
+            }
+            InputFormat::Ndjson | InputFormat::Raw => {
+                let documents = match LineDocuments::open(input, format, meter) {
+                    Ok(documents) => documents,
+                    Err(e) => return self.report(Kind::Input, input.path(), &e),
+                };
+                for document in documents {
+                    match document {
+                        Ok((number, json)) => {
+                            let numbered = args
+                                .with_line_numbers
+                                .then(|| format!("{}{number}:", label.unwrap_or_default()));
+                            self.document(json, numbered.as_deref().or(label))?;
+                            // Each match is seen as soon as its line is appended
+                            if args.follow {
+                                self.out.flush()?;
+                            }
+                        }
+                        Err(e) => self.report(Kind::Input, input.path(), &e)?,
+                    }
+                }
+            }
+            InputFormat::JsonSeq => {
+                let mut documents = match SeqDocuments::open(input, meter) {
+                    Ok(documents) => documents,
+                    Err(e) => return self.report(Kind::Input, input.path(), &e),
+                };
+                if args.slurp {
+                    match documents.by_ref().collect() {
+                        Ok(documents) => self.document(Value::Array(documents), label)?,
+                        Err(e) => return self.report(Kind::Input, input.path(), &e),
+                    }
+                } else {
+                    for document in documents.by_ref() {
+                        match document {
+                            Ok(json) => self.document(json, label)?,
+                            Err(e) => self.report(Kind::Input, input.path(), &e)?,
+                        }
+                    }
+                }
+                // Invalid records are skipped rather than failing the input, as RFC 7464
+                // suggests
+                if documents.skipped > 0 {
+                    self.out.flush()?;
+                    self.message(|out| {
+                        writeln!(
+                            out,
+                            "jpp: warning: skipped {} invalid JSON text sequence record{} in '{}'",
+                            documents.skipped,
+                            if documents.skipped == 1 { "" } else { "s" },
+                            input.label()
+                        )
+                    });
+                }
+            }
+        }
+
+        if args.count && !args.keyed && !args.total && args.quiet == 0 {
+            let count = self.total - counted;
+            write_labeled(&mut self.out, label, |out| writeln!(out, "{count}"))?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate the queries against `json` and write the results, each output line prefixed
+    /// with `label` when given
+    fn document(&mut self, json: Value, label: Option<&str>) -> io::Result<()> {
+        if let Some(documents) = &mut self.documents {
+            documents.push(json);
+            return Ok(());
+        }
+        if self.timing.is_none() {
+            return self.evaluate(json, label);
+        }
+        let start = Instant::now();
+        let result = self.evaluate(json, label);
+        if let Some(timing) = &mut self.timing {
+            timing.documents += start.elapsed();
+        }
+        result
+    }
+
+    /// Note that `--exit-status` has `matches` more results, and `--time` too
+    fn matched(&mut self, matches: usize) {
+        self.matched |= matches > 0;
+        if let Some(timing) = &mut self.timing {
+            timing.matches += matches;
+        }
+    }
+
+    fn evaluate(&mut self, json: Value, label: Option<&str>) -> io::Result<()> {
+        let args = self.args;
+        let palette = self.palette;
+
+        if let Some(edit) = &args.edit {
+            let mut json = json;
+            let edited = Timing::eval(&mut self.timing, || {
+                self.budget.eval(|options| {
+                    let mut edited = 0;
+                    for path in self.paths {
+                        // The edits have no limits of their own, so the query runs within
+                        // them first
+                        if *options != EvalOptions::default() {
+                            path.query_with_options(&json, options)?;
+                        }
+                        edited += match edit {
+                            Edit::Delete => path.delete(&mut json),
+                            Edit::Set(value) => path.set(&mut json, value),
+                        };
+                    }
+                    Ok(edited)
+                })
             })
+            .map_err(io::Error::other)?;
+            self.matched(edited);
+            self.edited += edited;
+            let json = if args.reshapes() {
+                args.shaped(&json)
+            } else {
+                json
+            };
+            return write_labeled(&mut self.out, label, |mut out| {
+                write_document(&mut out, args, &json, palette, &mut self.joined)
+            });
         }
-        _ => Err("too many arguments\n\nUsage: jpp [OPTIONS] <QUERY> [FILE]\n\nFor more information, try '--help'".to_string()),
+        let json = &json;
+
+        if args.quiet > 0 || args.list_files.is_some() {
+            // Whether anything matches is all there is to know, so one match is enough
+            let matches = Timing::eval(&mut self.timing, || {
+                self.budget.eval(|options| {
+                    self.paths
+                        .iter()
+                        .map(|path| count_results(path, json, Some(1), options))
+                        .sum::<Result<usize, _>>()
+                })
+            })
+            .map_err(io::Error::other)?;
+            self.matched(matches);
+            return if matches > 0 {
+                Err(io::Error::other(FirstMatch))
+            } else {
+                Ok(())
+            };
+        }
+
+        if args.keyed {
+            let keyed = Timing::eval(&mut self.timing, || {
+                self.budget
+                    .eval(|options| keyed_results(args, self.paths, json, options))
+            })
+            .map_err(io::Error::other)?;
+            self.matched(keyed.iter().map(|(_, result)| result_matches(result)).sum());
+            return write_labeled(&mut self.out, label, |mut out| {
+                write_keyed(&mut out, args, keyed, palette)
+            });
+        }
+        let path = &self.paths[0];
+
+        if args.count {
+            let count = Timing::eval(&mut self.timing, || {
+                self.budget
+                    .eval(|options| count_results(path, json, args.limit, options))
+            })
+            .map_err(io::Error::other)?;
+            self.matched(count);
+            self.total += count;
+            return Ok(());
+        }
+
+        if args.paths {
+            let located = Timing::eval(&mut self.timing, || {
+                self.budget.eval(|options| {
+                    query_located(path, json, args.limit, args.sort.as_ref(), options)
+                })
+            })
+            .map_err(io::Error::other)?;
+            self.matched(located.len());
+            return write_labeled(&mut self.out, label, |out| {
+                located.iter().try_for_each(|(location, _)| {
+                    writeln!(out, "{}", self::location(args, location))
+                })
+            });
+        }
+
+        let entries: Vec<Value>;
+        let results = if args.entries {
+            entries = Timing::eval(&mut self.timing, || {
+                self.budget.eval(|options| {
+                    query_located(path, json, args.limit, args.sort.as_ref(), options)
+                })
+            })
+            .map_err(io::Error::other)?
+            .into_iter()
+            .map(|(location, value)| entry(args, &location, value))
+            .collect();
+            entries.iter().collect()
+        } else {
+            Timing::eval(&mut self.timing, || {
+                self.budget
+                    .eval(|options| query(path, json, args.limit, args.sort.as_ref(), options))
+            })
+            .map_err(io::Error::other)?
+        };
+        self.matched(results.len());
+        let shaped: Vec<Value>;
+        let results = if args.reshapes() {
+            shaped = results.iter().map(|v| args.shaped(v)).collect();
+            shaped.iter().collect()
+        } else {
+            results
+        };
+
+        write_labeled(&mut self.out, label, |mut out| {
+            write_results(&mut out, args, &results, palette, &mut self.joined)
+        })
     }
 }
 
-fn read_input(file: Option<&str>) -> Result<String, String> {
-    match file {
-        Some(path) => {
-            fs::read_to_string(path).map_err(|e| format!("error reading file '{path}': {e}"))
+impl Runner<'_, Output> {
+    /// Take in what a `--jobs` worker made of an input, as if it had been read here: write
+    /// its output and messages and add up its counts. Returns the result of reading it.
+    fn merge(&mut self, worked: Worked) -> io::Result<()> {
+        self.out.write_all(&worked.out)?;
+        if !worked.messages.is_empty() {
+            self.out.flush()?;
+            let _ = io::stderr().write_all(&worked.messages);
         }
-        None => {
-            let mut buffer = String::new();
-            io::stdin()
-                .read_to_string(&mut buffer)
-                .map_err(|e| format!("error reading stdin: {e}"))?;
-            Ok(buffer)
+        self.matched |= worked.matched;
+        self.failed |= worked.failed;
+        self.total += worked.total;
+        self.edited += worked.edited;
+        worked.result
+    }
+
+    /// `--in-place`: write the edited documents of the file at `path` to a temporary file,
+    /// then rename it over `path`. On any error the file is left as it was.
+    fn in_place(&mut self, path: &str) -> io::Result<()> {
+        let args = self.args;
+        let input = Input::File(path);
+        let format = args.input_format.unwrap_or_else(|| input.detect_format());
+        let writes_format = matches!(
+            (format, args.output),
+            (InputFormat::Json, OutputFormat::Json)
+                | (InputFormat::Ndjson, OutputFormat::Ndjson)
+                | (InputFormat::JsonSeq, OutputFormat::JsonSeq)
+                | (
+                    InputFormat::Yaml,
+                    OutputFormat::Yaml | OutputFormat::YamlDocs
+                )
+        );
+        if !writes_format || args.raw_output.is_some() {
+            let hint = match format {
+                InputFormat::Yaml => "; add --output yaml",
+                InputFormat::Ndjson if args.output != OutputFormat::Ndjson => {
+                    "; add --output ndjson"
+                }
+                _ => "",
+            };
+            return self.report(Kind::Usage, Some(path), &format!(
+                "--in-place cannot write {} back to '{path}' in the selected output format{hint}",
+                format.name()
+            ));
+        }
+        match input.compression_name() {
+            Ok(None) => {}
+            Ok(Some(name)) => {
+                return self.report(
+                    Kind::Usage,
+                    Some(path),
+                    &format!("--in-place cannot write {name}-compressed '{path}' back"),
+                );
+            }
+            Err(e) => return self.report(Kind::Input, Some(path), &e),
+        }
+        let file = match AtomicFile::create(path) {
+            Ok(file) => file,
+            Err(e) => {
+                return self.report(
+                    Kind::Output,
+                    Some(path),
+                    &format!("cannot write '{path}': {e}"),
+                );
+            }
+        };
+
+        let previous_out = mem::replace(&mut self.out, Output::File(file));
+        let previous_failed = mem::replace(&mut self.failed, false);
+        let written = self.input(input, None);
+        let out = mem::replace(&mut self.out, previous_out);
+        let failed = self.failed;
+        self.failed |= previous_failed;
+        written?;
+        let Some(file) = out.into_file() else {
+            return Ok(());
+        };
+        if failed {
+            if args.error_format == ErrorFormat::Human {
+                eprintln!("jpp: left '{path}' unchanged");
+            }
+            return Ok(());
+        }
+
+        if let Some(suffix) = &args.backup
+            && let Err(e) = fs::copy(path, format!("{path}{suffix}"))
+        {
+            return self.report(
+                Kind::Output,
+                Some(path),
+                &format!("cannot back up '{path}' to '{path}{suffix}': {e}"),
+            );
+        }
+        if let Err(e) = file.commit() {
+            return self.report(
+                Kind::Output,
+                Some(path),
+                &format!("cannot replace '{path}': {e}"),
+            );
+        }
+        Ok(())
+    }
+}
+
+fn write_error(e: io::Error) -> Diagnostic {
+    Diagnostic::new(
+        Kind::Output,
+        format!("error writing output: {e}"),
+        EXIT_ERROR,
+    )
+}
+
+/// `--validate`: report on each query without reading any input
+fn run_validate(args: &ValidateArgs) -> Result<ExitCode, Diagnostic> {
+    let list;
+    let checked: Vec<Checked> = if args.queries.is_empty() {
+        list = io::read_to_string(io::stdin()).map_err(|e| {
+            Diagnostic::new(Kind::Input, format!("error reading stdin: {e}"), EXIT_ERROR)
+        })?;
+        list.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+            .map(|(i, line)| Checked {
+                query: line,
+                file: None,
+                line: Some(i + 1),
+            })
+            .collect()
+    } else {
+        args.queries
+            .iter()
+            .map(|spec| Checked {
+                query: &spec.query,
+                file: spec.file.as_deref(),
+                line: None,
+            })
+            .collect()
+    };
+    // A single query argument needs no name
+    let named = checked.len() > 1 || checked.iter().any(|c| c.file.is_some() || c.line.is_some());
+
+    let mut out = Output::stdout();
+    let mut valid = true;
+    for checked in &checked {
+        valid &= validate::report(&mut out, args.error_format, &args.variables, checked, named)
+            .map_err(write_error)?;
+    }
+    out.flush().map_err(write_error)?;
+    Ok(if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(EXIT_INVALID)
+    })
+}
+
+/// Parse each query of `queries`, substituting `variables`
+fn parse_queries(
+    queries: &[QuerySpec],
+    variables: &Variables,
+) -> Result<Vec<JsonPath>, Diagnostic> {
+    queries
+        .iter()
+        .map(|spec| {
+            let query = variables.substitute(&spec.query).map_err(|e| Diagnostic {
+                query: Some(spec.query.clone()),
+                file: spec.file.clone(),
+                ..Diagnostic::new(Kind::UndefinedVariable, e, EXIT_ERROR)
+            })?;
+            JsonPath::parse(&query).map_err(|e| {
+                let message = if queries.len() > 1 {
+                    format!("error parsing JSONPath query '{}': {e}", spec.label())
+                } else {
+                    format!("error parsing JSONPath query: {e}")
+                };
+                Diagnostic {
+                    span: e.parse_error().map(|e| e.span.clone()),
+                    query: Some(query),
+                    file: spec.file.clone(),
+                    ..Diagnostic::new(Kind::QueryParse, message, EXIT_ERROR)
+                }
+            })
+        })
+        .collect()
+}
+
+/// `--explain`: describe each query, separated by blank lines
+fn run_explain(args: &ExplainArgs) -> Result<ExitCode, Diagnostic> {
+    let paths = parse_queries(&args.queries, &args.variables)?;
+    let mut out = Output::stdout();
+    for (i, path) in paths.iter().enumerate() {
+        match args.format {
+            ExplainFormat::Human => {
+                if i > 0 {
+                    writeln!(out).map_err(write_error)?;
+                }
+                explain::write_explanation(&mut out, path).map_err(write_error)?;
+            }
+            ExplainFormat::Json => {
+                serde_json::to_writer_pretty(&mut out, path).map_err(|e| write_error(e.into()))?;
+                writeln!(out).map_err(write_error)?;
+            }
         }
     }
+    out.flush().map_err(write_error)?;
+    Ok(ExitCode::SUCCESS)
 }
 
-fn run() -> Result<(), String> {
-    let args = parse_args()?;
+fn run(args: &[String]) -> Result<ExitCode, Diagnostic> {
+    let args = parse_args(args, |name| env::var(name).ok())
+        .map_err(|e| Diagnostic::new(Kind::Usage, e, EXIT_ERROR))?;
 
     match args {
         ParsedArgs::Help => {
             print_help();
-            Ok(())
+            Ok(ExitCode::SUCCESS)
         }
         ParsedArgs::Version => {
             print_version();
-            Ok(())
+            Ok(ExitCode::SUCCESS)
         }
-        ParsedArgs::Query { query, file } => {
-            let input = read_input(file.as_deref())?;
+        ParsedArgs::Completions(shell) => {
+            print!("{}", completions::script(shell));
+            Ok(ExitCode::SUCCESS)
+        }
+        ParsedArgs::Validate(args) => run_validate(&args),
+        ParsedArgs::Explain(args) => run_explain(&args),
+        ParsedArgs::Query(args) if args.quiet >= 2 => {
+            // `-qq`: the exit status tells it all
+            Ok(run_queries(&args).unwrap_or_else(|e| ExitCode::from(e.exit_code)))
+        }
+        ParsedArgs::Query(args) => {
+            for warning in &args.warnings {
+                eprintln!("jpp: warning: {warning}");
+            }
+            run_queries(&args)
+        }
+    }
+}
 
-            let json: Value =
-                serde_json::from_str(&input).map_err(|e| format!("error parsing JSON: {e}"))?;
+/// Parse the queries of `args` and run them over the inputs, again after each change under
+/// `--watch`
+fn run_queries(args: &QueryArgs) -> Result<ExitCode, Diagnostic> {
+    let start = Instant::now();
+    let paths = parse_queries(&args.queries, &args.variables)?;
+    let query_parse = start.elapsed();
+    if matches!(args.edit, Some(Edit::Delete)) && paths.iter().any(|path| path.segments.is_empty())
+    {
+        return Err(Diagnostic::new(
+            Kind::Usage,
+            "--delete cannot remove the root node '$'; select the nodes to remove",
+            EXIT_ERROR,
+        ));
+    }
 
-            let results = jpp_core::query(&query, &json)
-                .map_err(|e| format!("error parsing JSONPath query: {e}"))?;
+    if let Some(format) = args.diff {
+        return run_diff(args, &paths, format);
+    }
+    if args.watch {
+        watch::watch(&args.files, args.clear, || {
+            run_query(args, &paths, query_parse).map(drop)
+        })?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    run_query(args, &paths, query_parse)
+}
 
-            let output = serde_json::to_string_pretty(&results)
-                .map_err(|e| format!("error serializing output: {e}"))?;
+/// A hint for whoever runs jpp on a terminal without piping input or naming a file, instead
+/// of waiting silently for input
+fn stdin_hint(args: &QueryArgs, console: &impl Console) -> Option<&'static str> {
+    (args.files.is_empty() && !args.null_input && console.stdin_is_terminal())
+        .then_some("reading from stdin; pipe JSON or pass a file \u{2014} Ctrl-D to end")
+}
 
-            println!("{output}");
-            Ok(())
+/// The input that the FILE argument `file` stands for
+fn input<'a>(args: &'a QueryArgs, file: &'a str) -> Input<'a> {
+    match file {
+        "-" => Input::Stdin,
+        path if args.follow => Input::Follow {
+            path,
+            from_start: args.from_start,
+        },
+        url if http::is_url(url) => Input::Url(url, &args.http),
+        file => Input::File(file),
+    }
+}
+
+/// Whether to color the output of `args` on stdout
+fn stdout_palette(args: &QueryArgs) -> Option<&'static Palette> {
+    // Files are never a terminal; `--in-place` writes only plain documents
+    let is_terminal = args.output_file.is_none() && io::stdout().is_terminal();
+    let no_color = env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    (!args.in_place && args.color.enabled(is_terminal, no_color)).then_some(&Palette::ANSI)
+}
+
+/// `--diff`: read both inputs, then compare what the queries print for each, or with
+/// `--diff-format json` the results of the query by location
+fn run_diff(
+    args: &QueryArgs,
+    paths: &[JsonPath],
+    format: DiffFormat,
+) -> Result<ExitCode, Diagnostic> {
+    let meter = ReadMeter {
+        max_bytes: args.limits.max_input_bytes,
+        max_depth: args.limits.max_depth,
+        ..ReadMeter::default()
+    };
+    let mut runner = Runner {
+        documents: (format == DiffFormat::Json).then(Vec::new),
+        ..Runner::new(args, paths, None, Vec::new(), &meter)
+    };
+    let mut read = Vec::new();
+    for file in &args.files {
+        let input = input(args, file);
+        let result = runner.input(input, None);
+        if let ControlFlow::Break(Some(exit)) = runner.finish_input(input, result, false)? {
+            return Ok(exit);
         }
+        let output = mem::take(&mut runner.out);
+        let documents = runner.documents.as_mut().map(mem::take).unwrap_or_default();
+        read.push((input, output, documents));
+    }
+    // Neither input can be compared without the other
+    if runner.failed {
+        return Ok(ExitCode::from(EXIT_ERROR));
     }
+    let [(old, old_output, _), (new, new_output, _)] = &read[..] else {
+        return Err(Diagnostic::new(
+            Kind::Usage,
+            "--diff needs two inputs",
+            EXIT_ERROR,
+        ));
+    };
+
+    let palette = stdout_palette(args);
+    let mut out = Output::stdout();
+    let differ = match format {
+        DiffFormat::Text => diff::write_unified(
+            &mut out,
+            (old.label(), &String::from_utf8_lossy(old_output)),
+            (new.label(), &String::from_utf8_lossy(new_output)),
+            palette,
+        )
+        .map_err(write_error)?,
+        DiffFormat::Json => {
+            let mut locate = |i: usize| {
+                let (input, _, documents) = &read[i];
+                let [json] = &documents[..] else {
+                    return Err(Diagnostic::new(
+                        Kind::Input,
+                        format!(
+                            "--diff-format json compares one document from each input, not {} from '{}'; add --slurp to compare them as one array",
+                            documents.len(),
+                            input.label()
+                        ),
+                        EXIT_ERROR,
+                    )
+                    .with_file(input.path()));
+                };
+                let results = runner
+                    .budget
+                    .eval(|options| {
+                        query_located(&paths[0], json, args.limit, args.sort.as_ref(), options)
+                    })
+                    .map_err(|limit| {
+                        Diagnostic::new(Kind::Limit, limit.to_string(), EXIT_LIMIT)
+                            .with_file(input.path())
+                    })?;
+                Ok(results
+                    .into_iter()
+                    .map(|(location, value)| (self::location(args, &location), value))
+                    .collect::<Vec<_>>())
+            };
+            let (old, new) = (locate(0)?, locate(1)?);
+            let (report, differ) = diff::report(&old, &new);
+            let report = if args.reshapes() {
+                args.shaped(&report)
+            } else {
+                report
+            };
+            write_document(&mut out, args, &report, palette, &mut false).map_err(write_error)?;
+            differ
+        }
+    };
+    out.flush().map_err(write_error)?;
+    Ok(if differ {
+        ExitCode::from(EXIT_DIFFERENT)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Run the parsed queries over the inputs of `args`
+fn run_query(
+    args: &QueryArgs,
+    paths: &[JsonPath],
+    query_parse: Duration,
+) -> Result<ExitCode, Diagnostic> {
+    let timing = args.time.map(|_| Timing {
+        query_parse,
+        ..Timing::default()
+    });
+    let out = match &args.output_file {
+        Some(path) => Output::File(AtomicFile::create(path).map_err(|e| {
+            Diagnostic::new(
+                Kind::Output,
+                format!("cannot write '{path}': {e}"),
+                EXIT_ERROR,
+            )
+            .with_file(Some(path))
+        })?),
+        None => Output::stdout(),
+    };
+    let palette = stdout_palette(args);
+    let meter = ReadMeter {
+        max_bytes: args.limits.max_input_bytes,
+        max_depth: args.limits.max_depth,
+        ..ReadMeter::default()
+    };
+    let mut runner = Runner {
+        timing,
+        ..Runner::new(args, paths, palette, out, &meter)
+    };
+
+    let files = if args.recursive {
+        let (files, errors) = walk::expand(&args.files, &args.include);
+        for (path, error) in errors {
+            runner
+                .report(Kind::Input, Some(&path), &error)
+                .map_err(write_error)?;
+        }
+        files
+    } else {
+        args.files.clone()
+    };
+    let inputs: Vec<Input> = if args.null_input {
+        vec![Input::Null]
+    } else if files.is_empty() && !args.recursive {
+        vec![Input::Stdin]
+    } else {
+        files.iter().map(|file| input(args, file)).collect()
+    };
+    if let Some(hint) = stdin_hint(args, &ProcessConsole)
+        && args.quiet < 2
+    {
+        eprintln!("jpp: {hint}");
+    }
+    let with_filename = args
+        .with_filename
+        .unwrap_or(inputs.len() > 1 || args.recursive);
+
+    // Each input is read whole before the results of the next are written, so only
+    // these need the inputs read one after another: `--in-place` writes the files back,
+    // `--join` separates records across inputs, and `--time` and `--timeout` take the time of
+    // the whole run
+    let parallel = args.jobs > 1
+        && inputs.len() > 1
+        && !args.in_place
+        && !matches!(args.raw_output, Some(Delimiter::Join(_)))
+        && args.time.is_none()
+        && args.limits.timeout.is_none();
+    let mut flow = ControlFlow::Continue(());
+    if parallel {
+        let work = |&input: &Input| {
+            let meter = ReadMeter {
+                max_bytes: args.limits.max_input_bytes,
+                max_depth: args.limits.max_depth,
+                ..ReadMeter::default()
+            };
+            let mut worker = Runner {
+                messages: Some(Vec::new()),
+                ..Runner::new(args, paths, palette, Vec::new(), &meter)
+            };
+            let result = worker.run_input(input, with_filename);
+            Worked {
+                out: worker.out,
+                messages: worker.messages.unwrap_or_default(),
+                result,
+                matched: worker.matched,
+                failed: worker.failed,
+                total: worker.total,
+                edited: worker.edited,
+            }
+        };
+        parallel::in_order(&inputs, args.jobs, work, |done| {
+            for (&input, worked) in inputs.iter().zip(done) {
+                let failed = mem::take(&mut runner.failed);
+                let result = runner.merge(worked);
+                flow = runner.finish_input(input, result, failed)?;
+                if flow.is_break() {
+                    break;
+                }
+            }
+            Ok::<_, Diagnostic>(())
+        })?;
+    } else {
+        for &input in &inputs {
+            let failed = mem::take(&mut runner.failed);
+            let result = match input {
+                Input::File(path) if args.in_place => runner.in_place(path),
+                _ => runner.run_input(input, with_filename),
+            };
+            flow = runner.finish_input(input, result, failed)?;
+            if flow.is_break() {
+                break;
+            }
+        }
+    }
+    if let ControlFlow::Break(Some(exit)) = flow {
+        return Ok(exit);
+    }
+    if args.total && args.quiet == 0 {
+        writeln!(runner.out, "{}", runner.total).map_err(write_error)?;
+    }
+    runner.out.flush().map_err(write_error)?;
+    if let Some(file) = runner.out.into_file() {
+        // Keep the previous contents rather than replacing them with partial output
+        if runner.failed {
+            if args.error_format == ErrorFormat::Human && args.quiet < 2 {
+                eprintln!("jpp: left '{}' unchanged", file.path().display());
+            }
+        } else {
+            let path = file.path().display().to_string();
+            file.commit().map_err(|e| {
+                Diagnostic::new(
+                    Kind::Output,
+                    format!("cannot replace '{path}': {e}"),
+                    EXIT_ERROR,
+                )
+                .with_file(Some(&path))
+            })?;
+        }
+    }
+    if let Some(Edit::Set(_)) = args.edit
+        && args.quiet == 0
+    {
+        let matches = if runner.edited == 1 {
+            "match"
+        } else {
+            "matches"
+        };
+        eprintln!("jpp: replaced {} {matches}", runner.edited);
+    }
+    if let (Some(timing), Some(format)) = (&runner.timing, args.time) {
+        timing
+            .write(&mut io::stderr().lock(), format)
+            .map_err(|e| {
+                Diagnostic::new(
+                    Kind::Output,
+                    format!("error writing timings: {e}"),
+                    EXIT_ERROR,
+                )
+            })?;
+    }
+
+    Ok(if runner.failed {
+        ExitCode::from(EXIT_ERROR)
+    } else if args.exit_status && !runner.matched {
+        ExitCode::from(EXIT_NO_MATCH)
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// The `--error-format` among `args`, found without parsing them, since an error in the
+/// arguments is reported in it too
+fn error_format(args: &[String]) -> ErrorFormat {
+    let mut format = ErrorFormat::Human;
+    let mut iter = args.iter().take_while(|arg| *arg != "--");
+    while let Some(arg) = iter.next() {
+        if let Some((option, inline)) = options::lookup(arg)
+            && option.long == "--error-format"
+            && let Some(Ok(value)) = inline
+                .or_else(|| iter.next().map(String::as_str))
+                .map(ErrorFormat::parse)
+        {
+            format = value;
+        }
+    }
+    format
 }
 
 fn main() -> ExitCode {
-    match run() {
-        Ok(()) => ExitCode::SUCCESS,
+    let args: Vec<String> = env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
         Err(e) => {
-            eprintln!("jpp: {e}");
-            ExitCode::FAILURE
+            e.report(error_format(&args));
+            ExitCode::from(e.exit_code)
         }
     }
 }
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use options::{
+        COLOR_CHOICES, INPUT_FORMATS, OPTIONS, OUTPUT_FORMATS, REPORT_FORMATS, TIME_FORMATS,
+    };
+
+    fn parse(args: &[&str]) -> Result<ParsedArgs, String> {
+        parse_args(
+            &args.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+            |_| None,
+        )
+    }
+
+    fn query_args(args: &[&str]) -> Box<QueryArgs> {
+        match parse(args) {
+            Ok(ParsedArgs::Query(args)) => Some(args),
+            _ => None,
+        }
+        .unwrap()
+    }
+
+    #[test]
+    fn test_options_complete() {
+        let bash = completions::script(Shell::Bash);
+        let words: HashSet<&str> = bash
+            .split(|c: char| c.is_whitespace() || matches!(c, '"' | '|' | ')'))
+            .collect();
+        for option in OPTIONS {
+            let short = option.short.map(|c| format!("-{c}"));
+            for name in short.iter().map(String::as_str).chain([option.long]) {
+                // The values may be invalid; the option only has to be recognized
+                let mut args = vec![name];
+                args.extend(option.values.iter().map(|_| "1"));
+                args.push("$");
+                if let Err(e) = parse(&args) {
+                    assert!(
+                        !e.starts_with("unknown option") && !e.ends_with("is not implemented"),
+                        "{name}: {e}"
+                    );
+                }
+                assert!(
+                    words.contains(name),
+                    "{name} is missing from the bash script"
+                );
+            }
+        }
+        assert!(
+            parse(&["--nope", "$"])
+                .err()
+                .unwrap()
+                .starts_with("unknown option: --nope")
+        );
+    }
+
+    #[test]
+    fn test_choices() {
+        for format in OUTPUT_FORMATS {
+            OutputFormat::parse(format).unwrap();
+        }
+        for format in INPUT_FORMATS {
+            InputFormat::parse(format).unwrap();
+        }
+        for choice in COLOR_CHOICES {
+            ColorChoice::parse(choice).unwrap();
+        }
+        for format in REPORT_FORMATS {
+            ErrorFormat::parse(format).unwrap();
+            ExplainFormat::parse(format).unwrap();
+        }
+        for format in TIME_FORMATS {
+            TimeFormat::parse(format).unwrap();
+        }
+        for shell in options::SHELLS {
+            assert!(matches!(
+                parse(&["completions", shell]),
+                Ok(ParsedArgs::Completions(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_dash_arguments() {
+        let args = query_args(&["-c", "--", "$", "-weird.json", "--count"]);
+        assert_eq!(args.queries[0].query, "$");
+        assert_eq!(args.files, ["-weird.json", "--count"]);
+        assert!(args.compact && !args.count);
+
+        let args = query_args(&["$", "-", "a.json"]);
+        assert_eq!(args.files, ["-", "a.json"]);
+    }
+
+    struct FakeConsole(bool);
+
+    impl Console for FakeConsole {
+        fn stdin_is_terminal(&self) -> bool {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_stdin_hint() {
+        let hint =
+            |args: &[&str], terminal: bool| stdin_hint(&query_args(args), &FakeConsole(terminal));
+        assert_eq!(
+            hint(&["$"], true),
+            Some("reading from stdin; pipe JSON or pass a file \u{2014} Ctrl-D to end")
+        );
+        assert_eq!(hint(&["$"], false), None);
+        // Naming stdin, a file or no input at all needs no hint
+        assert_eq!(hint(&["$", "-"], true), None);
+        assert_eq!(hint(&["$", "a.json"], true), None);
+        assert_eq!(hint(&["-n", "$"], true), None);
+    }
+
+    #[test]
+    fn test_inline_values() {
+        assert!(
+            matches!(parse(&["--limit=2", "$"]), Ok(ParsedArgs::Query(args)) if args.limit == Some(2))
+        );
+        assert_eq!(
+            parse(&["--count=1", "$"]).err().unwrap(),
+            "option '--count' does not take a value"
+        );
+        assert_eq!(
+            parse(&["--replace"]).err().unwrap(),
+            "missing value for '--replace <JSON>'"
+        );
+    }
+}
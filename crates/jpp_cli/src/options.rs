@@ -0,0 +1,605 @@
+//! The command-line options: one table drives argument parsing, `--help` and the shell
+//! completion scripts
+
+/// What a shell can complete for the value of an option
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ValueHint {
+    /// Free text such as a query, or no value at all
+    Text,
+    /// A file path
+    File,
+    /// One of a fixed set of words
+    Choice(&'static [&'static str]),
+}
+
+/// A command-line option
+pub struct OptionSpec {
+    pub short: Option<char>,
+    /// Long name, with the leading `--`
+    pub long: &'static str,
+    /// Names of the values the option takes, e.g. `["FORMAT"]`
+    pub values: &'static [&'static str],
+    pub hint: ValueHint,
+    /// Help text; each line after the first continues the description
+    pub help: &'static str,
+}
+
+impl OptionSpec {
+    const fn flag(short: Option<char>, long: &'static str, help: &'static str) -> Self {
+        Self {
+            short,
+            long,
+            values: &[],
+            hint: ValueHint::Text,
+            help,
+        }
+    }
+
+    const fn value(
+        short: Option<char>,
+        long: &'static str,
+        value: &'static [&'static str],
+        hint: ValueHint,
+        help: &'static str,
+    ) -> Self {
+        Self {
+            short,
+            long,
+            values: value,
+            hint,
+            help,
+        }
+    }
+
+    /// The long name with its values, e.g. `--output <FORMAT>`
+    pub fn usage(&self) -> String {
+        let mut usage = self.long.to_string();
+        for value in self.values {
+            usage.push_str(&format!(" <{value}>"));
+        }
+        usage
+    }
+
+    /// The first sentence of the help text on one line, for completion menus
+    pub fn summary(&self) -> String {
+        let help = self.help.replace('\n', " ");
+        // Examples are too long for a menu
+        let end = [". ", ", e.g. "]
+            .iter()
+            .filter_map(|stop| help.find(stop))
+            .min()
+            .unwrap_or(help.len());
+        help[..end].to_string()
+    }
+}
+
+/// Values of `--output`
+pub const OUTPUT_FORMATS: &[&str] = &[
+    "json",
+    "ndjson",
+    "json-seq",
+    "yaml",
+    "yaml-docs",
+    "table",
+    "markdown",
+];
+/// Values of `--input-format`
+pub const INPUT_FORMATS: &[&str] = &["json", "jsonc", "ndjson", "yaml", "toml", "raw", "json-seq"];
+/// Values of `--color`
+pub const COLOR_CHOICES: &[&str] = &["auto", "always", "never"];
+/// Values of `--error-format` and `--explain-format`
+pub const REPORT_FORMATS: &[&str] = &["human", "json"];
+/// Values of `--diff-format`
+pub const DIFF_FORMATS: &[&str] = &["text", "json"];
+/// Values of `--time-format`
+pub const TIME_FORMATS: &[&str] = &["text", "json"];
+/// Shells of `jpp completions`
+pub const SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+use ValueHint::{Choice, File, Text};
+
+/// Every option, in `--help` order
+pub const OPTIONS: &[OptionSpec] = &[
+    OptionSpec::value(
+        None,
+        "--query",
+        &["QUERY"],
+        Text,
+        "Add a query; repeat to run several queries over each input. With --query\n\
+         or -f, every argument is an input file; one starting with $ is an error\n\
+         unless a file of that name exists",
+    ),
+    OptionSpec::value(
+        Some('f'),
+        "--query-file",
+        &["QUERY_FILE"],
+        File,
+        "Add a query read from a file. Lines starting with # are skipped and\n\
+         one trailing newline is ignored",
+    ),
+    OptionSpec::value(
+        None,
+        "--label",
+        &["NAME"],
+        Text,
+        "Name the results of the preceding query (defaults to the query)",
+    ),
+    OptionSpec::value(
+        None,
+        "--arg",
+        &["NAME", "VALUE"],
+        Text,
+        "Define $NAME as a string for the queries, e.g.\n\
+         jpp --arg s active '$.items[?@.status == $s]'",
+    ),
+    OptionSpec::value(
+        None,
+        "--argjson",
+        &["NAME", "JSON"],
+        Text,
+        "Define $NAME as a JSON string, number, boolean or null",
+    ),
+    OptionSpec::value(
+        None,
+        "--input-format",
+        &["FORMAT"],
+        Choice(INPUT_FORMATS),
+        "Input format: json, jsonc (comments and trailing commas), ndjson (one\n\
+         document per line), yaml, toml, raw (each line a string) or json-seq\n\
+         (RFC 7464 records). Defaults by file extension (.jsonc, .yaml, .yml,\n\
+         .toml), else json",
+    ),
+    OptionSpec::flag(
+        None,
+        "--ndjson",
+        "Same as --input-format ndjson; output defaults to NDJSON",
+    ),
+    OptionSpec::flag(
+        Some('R'),
+        "--raw-input",
+        "Same as --input-format raw: each line is a JSON string, and with --slurp\n\
+         the lines of each input are one array; output defaults to NDJSON",
+    ),
+    OptionSpec::flag(
+        None,
+        "--stream",
+        "Read a top-level array one element at a time, running the query on\n\
+         each element as its own document ($ is the element); output\n\
+         defaults to NDJSON",
+    ),
+    OptionSpec::flag(
+        None,
+        "--multi",
+        "Read several JSON documents back to back in each input, separated by\n\
+         whitespace or nothing, running the query on each",
+    ),
+    OptionSpec::flag(
+        None,
+        "--follow",
+        "With --ndjson or -R, keep reading the one FILE as lines are appended,\n\
+         like tail -f, starting at its end. A truncated or replaced file is\n\
+         read again from its start. Ctrl-C ends it",
+    ),
+    OptionSpec::flag(
+        None,
+        "--from-start",
+        "With --follow, read the lines already in the file first",
+    ),
+    OptionSpec::flag(
+        None,
+        "--with-line-numbers",
+        "Prefix NDJSON input results with their 1-based input line number",
+    ),
+    OptionSpec::flag(
+        Some('n'),
+        "--null-input",
+        "Use `null` as the input document instead of reading any input",
+    ),
+    OptionSpec::flag(
+        Some('s'),
+        "--slurp",
+        "Collect the documents of each input (YAML documents, NDJSON lines)\n\
+         into one array and query that array",
+    ),
+    OptionSpec::value(
+        None,
+        "--output",
+        &["FORMAT"],
+        Choice(OUTPUT_FORMATS),
+        "Output format: json, ndjson, json-seq (RFC 7464), yaml (one sequence),\n\
+         yaml-docs (one document per result), or table or markdown (a row per\n\
+         result, a column per key). Default json, or the input format for\n\
+         NDJSON and json-seq input",
+    ),
+    OptionSpec::value(
+        None,
+        "--max-col-width",
+        &["N"],
+        Text,
+        "Cut table and markdown cells to N columns, ending with …",
+    ),
+    OptionSpec::flag(
+        Some('l'),
+        "--lines",
+        "Same as --output ndjson: one compact JSON result per line",
+    ),
+    OptionSpec::value(
+        None,
+        "--limit",
+        &["N"],
+        Text,
+        "Stop after the first N results of each input",
+    ),
+    OptionSpec::flag(None, "--first", "Same as --limit 1"),
+    OptionSpec::flag(
+        None,
+        "--sort",
+        "Sort the results before --limit: null, booleans, numbers, strings, arrays,\nthen objects",
+    ),
+    OptionSpec::value(
+        None,
+        "--sort-by",
+        &["QUERY"],
+        Text,
+        "Sort the results by the first result of QUERY against each, e.g. @.price;\nresults without one sort last",
+    ),
+    OptionSpec::flag(
+        None,
+        "--count",
+        "Print only the number of results of each input",
+    ),
+    OptionSpec::flag(
+        None,
+        "--total",
+        "With --count, print one sum over all inputs",
+    ),
+    OptionSpec::flag(
+        None,
+        "--paths",
+        "Print the normalized path of each result instead of its value",
+    ),
+    OptionSpec::flag(
+        None,
+        "--pointer",
+        "Print the JSON Pointer (RFC 6901) of each result instead of its value;\n\
+         with --entries, give each entry a pointer instead of a path",
+    ),
+    OptionSpec::flag(
+        None,
+        "--entries",
+        "Print a {path, value} object for each result",
+    ),
+    OptionSpec::flag(
+        None,
+        "--delete",
+        "Print each input document with every match removed",
+    ),
+    OptionSpec::value(
+        None,
+        "--set",
+        &["JSON"],
+        Text,
+        "Print each input document with every match replaced by the JSON\n\
+         value, and report the number of replacements on stderr",
+    ),
+    OptionSpec::value(None, "--replace", &["JSON"], Text, "Same as --set"),
+    OptionSpec::value(
+        None,
+        "--set-raw",
+        &["STRING"],
+        Text,
+        "Same as --set with a string value",
+    ),
+    OptionSpec::flag(
+        Some('q'),
+        "--quiet",
+        "Print nothing on stdout and stop at the first match, for use with -e.\n\
+         With --delete or --set, only skip the report of replacements. -qq\n\
+         also keeps errors and warnings off stderr",
+    ),
+    OptionSpec::flag(
+        Some('i'),
+        "--in-place",
+        "Write the result of --delete or --set back to each FILE instead of\n\
+         printing it. A file is replaced only when it was read and written\n\
+         completely, and keeps its permissions",
+    ),
+    OptionSpec::value(
+        None,
+        "--backup",
+        &["SUFFIX"],
+        Text,
+        "With --in-place, keep a copy of each original file as FILE<SUFFIX>",
+    ),
+    OptionSpec::value(
+        Some('o'),
+        "--output-file",
+        &["FILE"],
+        File,
+        "Write the output to FILE, replacing it only if every input was read",
+    ),
+    OptionSpec::flag(
+        Some('c'),
+        "--compact",
+        "Print the result array on a single line",
+    ),
+    OptionSpec::value(
+        None,
+        "--indent",
+        &["N"],
+        Text,
+        "Indent JSON output by N spaces per level, 0 to 7 (default 2; 0 is\n\
+         the same as --compact)",
+    ),
+    OptionSpec::flag(
+        Some('r'),
+        "--raw-output",
+        "Print string results without quotes and other results as compact\n\
+         JSON, one result per line instead of a JSON array",
+    ),
+    OptionSpec::flag(
+        Some('0'),
+        "--null-output",
+        "Like -r, but end each result with a NUL byte instead of a newline,\nfor xargs -0",
+    ),
+    OptionSpec::value(
+        None,
+        "--join",
+        &["SEP"],
+        Text,
+        "Like -r, but separate the results with SEP, with none after the last",
+    ),
+    OptionSpec::value(
+        None,
+        "--format",
+        &["TEMPLATE"],
+        Text,
+        "Print a line of TEMPLATE for each result, with each {@...} replaced by\n\
+         what that relative query matches in the result: strings as their\n\
+         contents, other values as compact JSON. {{ and }} are literal braces",
+    ),
+    OptionSpec::value(
+        None,
+        "--format-missing",
+        &["TEXT"],
+        Text,
+        "Print TEXT for a --format placeholder that matches nothing (default empty)",
+    ),
+    OptionSpec::flag(
+        Some('a'),
+        "--ascii-output",
+        "Escape the non-ASCII characters of strings as \\uXXXX in every output\n\
+         format, also with -r",
+    ),
+    OptionSpec::flag(Some('S'), "--sort-keys", "Sort object keys in the output"),
+    OptionSpec::value(
+        None,
+        "--max-output-depth",
+        &["N"],
+        Text,
+        "Write arrays and objects only N levels deep, and deeper ones as a string\n\
+         such as \"[… 42 items]\" or \"{… 3 keys}\". Evaluation is unaffected",
+    ),
+    OptionSpec::value(
+        None,
+        "--truncate-strings",
+        &["N"],
+        Text,
+        "Write strings longer than N characters as their first N characters,\n\
+         then \"…\" and their length. Evaluation is unaffected",
+    ),
+    OptionSpec::flag(
+        None,
+        "--with-filename",
+        "Prefix each output line with its file name (default with several files)",
+    ),
+    OptionSpec::flag(
+        None,
+        "--no-filename",
+        "Never prefix output lines with file names",
+    ),
+    OptionSpec::value(
+        None,
+        "--header",
+        &["HEADER"],
+        Text,
+        "Send 'Name: value' with the requests for URL inputs, but not to another\n\
+         host after a redirect; repeat for several. URLs need the 'http' feature",
+    ),
+    OptionSpec::value(
+        None,
+        "--http-timeout",
+        &["SECS"],
+        Text,
+        "Give up on a URL input after SECS seconds connecting or waiting for the\n\
+         response headers (default 30)",
+    ),
+    OptionSpec::flag(
+        Some('d'),
+        "--recursive",
+        "Read the files under each directory FILE, at any depth, whose names\n\
+         match --include. Output lines are prefixed with file names",
+    ),
+    OptionSpec::value(
+        None,
+        "--include",
+        &["GLOB"],
+        Text,
+        "With --recursive, read the files whose names match GLOB, where * is\n\
+         any characters and ? one; repeat for several (default *.json)",
+    ),
+    OptionSpec::flag(
+        Some('m'),
+        "--files-with-matches",
+        "Print only the name of each input with a match, reading it no further",
+    ),
+    OptionSpec::flag(
+        Some('L'),
+        "--files-without-match",
+        "Print only the name of each input without any match",
+    ),
+    OptionSpec::value(
+        None,
+        "--jobs",
+        &["N"],
+        Text,
+        "Read up to N inputs at once when there are several (default one per\n\
+         CPU). Results still follow the order of the inputs",
+    ),
+    OptionSpec::value(
+        None,
+        "--color",
+        &["WHEN"],
+        Choice(COLOR_CHOICES),
+        "Color output: auto (default), always or never. auto colors only a\n\
+         terminal and honors NO_COLOR",
+    ),
+    OptionSpec::flag(
+        Some('e'),
+        "--exit-status",
+        "Exit with 1 if the query matched nothing",
+    ),
+    OptionSpec::flag(
+        None,
+        "--diff",
+        "Compare the results for two FILEs: print a unified diff of the output\n\
+         for each and exit with 1 if they differ, 0 if not",
+    ),
+    OptionSpec::value(
+        None,
+        "--diff-format",
+        &["FORMAT"],
+        Choice(DIFF_FORMATS),
+        "Format of --diff: text (default) or json, the results added, removed\n\
+         and changed by location",
+    ),
+    OptionSpec::flag(
+        None,
+        "--validate",
+        "Check the queries without reading input: every argument is a\n\
+         query, and without any, each line of stdin is one. Prints ok or the\n\
+         error for each and exits with 1 if any is invalid",
+    ),
+    OptionSpec::value(
+        None,
+        "--error-format",
+        &["FORMAT"],
+        Choice(REPORT_FORMATS),
+        "Format of errors and --validate reports: human (default) or json.\n\
+         json writes each error as one object on stderr, with its kind,\n\
+         message, position, query, file and exit_code, and one report\n\
+         object per query for --validate",
+    ),
+    OptionSpec::flag(
+        None,
+        "--explain",
+        "Print the structure of the queries without reading input: segments,\n\
+         selectors and filter expressions as a tree. Every argument is a query",
+    ),
+    OptionSpec::value(
+        None,
+        "--explain-format",
+        &["FORMAT"],
+        Choice(REPORT_FORMATS),
+        "Format of --explain: human (default) or json (the syntax tree)",
+    ),
+    OptionSpec::value(
+        None,
+        "--max-input-bytes",
+        &["N"],
+        Text,
+        "Stop reading an input past N bytes, after decompression",
+    ),
+    OptionSpec::value(
+        None,
+        "--max-results",
+        &["N"],
+        Text,
+        "Stop when a query selects more than N nodes of a document",
+    ),
+    OptionSpec::value(
+        None,
+        "--max-depth",
+        &["N"],
+        Text,
+        "Stop at a document nesting more than N arrays and objects",
+    ),
+    OptionSpec::value(
+        None,
+        "--timeout",
+        &["SECS"],
+        Text,
+        "Stop when evaluating the queries takes more than SECS seconds in\n\
+         total. Exceeding any of these limits exits with 3",
+    ),
+    OptionSpec::flag(
+        None,
+        "--watch",
+        "Run again whenever a FILE changes, printing a separator before the\n\
+         new results, until Ctrl-C. Needs the 'watch' feature",
+    ),
+    OptionSpec::flag(
+        None,
+        "--clear",
+        "With --watch, clear the screen instead of printing a separator",
+    ),
+    OptionSpec::flag(
+        None,
+        "--time",
+        "Report on stderr, after the run, the input bytes and the time spent\n\
+         reading input, parsing it, parsing the queries and evaluating them,\n\
+         the number of matches and the peak memory use, one key=value per line",
+    ),
+    OptionSpec::value(
+        None,
+        "--time-format",
+        &["FORMAT"],
+        Choice(TIME_FORMATS),
+        "Format of --time: text (default) or json (one object)",
+    ),
+    OptionSpec::flag(Some('h'), "--help", "Show this help message"),
+    OptionSpec::flag(Some('V'), "--version", "Show version"),
+];
+
+/// The option `arg` names, with the value attached by `--long=VALUE` if any. `None` when
+/// `arg` is not an option of the table.
+pub fn lookup(arg: &str) -> Option<(&'static OptionSpec, Option<&str>)> {
+    if let Some(long) = arg.strip_prefix("--") {
+        let (name, value) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (long, None),
+        };
+        let option = OPTIONS.iter().find(|option| &option.long[2..] == name)?;
+        return Some((option, value));
+    }
+    let mut chars = arg.strip_prefix('-')?.chars();
+    let (Some(short), None) = (chars.next(), chars.next()) else {
+        return None;
+    };
+    let option = OPTIONS.iter().find(|option| option.short == Some(short))?;
+    Some((option, None))
+}
+
+/// Column where the descriptions of `--help` start
+const HELP_COLUMN: usize = 25;
+
+/// The `Options:` section of `--help`
+pub fn help() -> String {
+    let mut help = String::new();
+    for option in OPTIONS {
+        let names = match option.short {
+            Some(short) => format!("  -{short}, {}", option.usage()),
+            None => format!("      {}", option.usage()),
+        };
+        help.push_str(&names);
+        if names.len() < HELP_COLUMN {
+            help.push_str(&" ".repeat(HELP_COLUMN - names.len()));
+        } else {
+            help.push('\n');
+            help.push_str(&" ".repeat(HELP_COLUMN));
+        }
+        help.push_str(&option.help.replace('\n', &format!("\n{:HELP_COLUMN$}", "")));
+        help.push('\n');
+    }
+    help
+}
@@ -0,0 +1,204 @@
+//! Writing output to stdout or atomically replaced files
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, StdoutLock, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+
+/// Where results are written
+pub enum Output {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    /// `-o FILE` or `--in-place`
+    File(AtomicFile),
+}
+
+impl Output {
+    pub fn stdout() -> Self {
+        Self::Stdout(BufWriter::new(io::stdout().lock()))
+    }
+
+    /// The file being written, if any
+    pub fn into_file(self) -> Option<AtomicFile> {
+        match self {
+            Self::Stdout(_) => None,
+            Self::File(file) => Some(file),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Stdout(out) => out.write(buf),
+            Self::File(file) => file.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Stdout(out) => out.flush(),
+            Self::File(file) => file.flush(),
+        }
+    }
+}
+
+/// Writes a temporary file next to `path` and renames it over `path` on [`commit`], so the
+/// target is either untouched or completely written, never truncated. Dropping it without
+/// committing removes the temporary file.
+///
+/// [`commit`]: AtomicFile::commit
+pub struct AtomicFile {
+    path: PathBuf,
+    temp: PathBuf,
+    file: BufWriter<File>,
+    committed: bool,
+}
+
+impl AtomicFile {
+    /// Start replacing `path`. The permissions of an existing file carry over.
+    pub fn create(path: &str) -> io::Result<Self> {
+        let path = Path::new(path);
+        let name = path.file_name().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "the path does not name a file")
+        })?;
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let permissions = fs::metadata(path)
+            .ok()
+            .map(|metadata| metadata.permissions());
+
+        let mut attempt = 0;
+        let (temp, file) = loop {
+            let temp = dir.join(format!(
+                ".{}.jpp-{}-{attempt}.tmp",
+                name.to_string_lossy(),
+                process::id()
+            ));
+            match OpenOptions::new().write(true).create_new(true).open(&temp) {
+                Ok(file) => break (temp, file),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists && attempt < 100 => attempt += 1,
+                Err(e) => return Err(e),
+            }
+        };
+        let atomic = Self {
+            path: path.to_path_buf(),
+            temp,
+            file: BufWriter::new(file),
+            committed: false,
+        };
+        if let Some(permissions) = permissions {
+            atomic.file.get_ref().set_permissions(permissions)?;
+        }
+        Ok(atomic)
+    }
+
+    /// The file being replaced
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Flush the temporary file to disk and rename it over the target
+    pub fn commit(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        self.file.get_ref().sync_all()?;
+        fs::rename(&self.temp, &self.path)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.committed {
+            // Nothing to report from a destructor; a leftover file is only clutter
+            let _ = fs::remove_file(&self.temp);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory under the system temporary directory
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("jpp-{name}-{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_commit_replaces_target() {
+        let dir = scratch_dir("commit");
+        let target = dir.join("out.json");
+        fs::write(&target, "old").unwrap();
+
+        let mut file = AtomicFile::create(target.to_str().unwrap()).unwrap();
+        file.write_all(b"new").unwrap();
+        // Until the commit, the target keeps its old contents
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(entries(&dir).len(), 2);
+        file.commit().unwrap();
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(entries(&dir), ["out.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_drop_discards() {
+        let dir = scratch_dir("discard");
+        let target = dir.join("out.json");
+        fs::write(&target, "old").unwrap();
+
+        let mut file = AtomicFile::create(target.to_str().unwrap()).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+
+        assert_eq!(fs::read_to_string(&target).unwrap(), "old");
+        assert_eq!(entries(&dir), ["out.json"]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_keeps_permissions() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = scratch_dir("permissions");
+        let target = dir.join("out.json");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o640)).unwrap();
+
+        AtomicFile::create(target.to_str().unwrap())
+            .unwrap()
+            .commit()
+            .unwrap();
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,113 @@
+//! `--jobs`: reading several inputs at once, with the results kept in input order
+
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+/// Value of `--jobs`
+pub fn parse_jobs(value: &str) -> Result<usize, String> {
+    value.parse().ok().filter(|&jobs| jobs > 0).ok_or_else(|| {
+        format!("invalid value '{value}' for '--jobs <N>' (expected a positive integer)")
+    })
+}
+
+/// Jobs without `--jobs`: one per CPU
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, NonZeroUsize::get)
+}
+
+/// Run `work` on each of `items` on up to `jobs` threads, and hand `consume` the results in the
+/// order of `items`. Once `consume` returns, the items not started yet are skipped.
+pub fn in_order<T: Sync, R: Send, X>(
+    items: &[T],
+    jobs: usize,
+    work: impl Fn(&T) -> R + Sync,
+    consume: impl FnOnce(&mut dyn Iterator<Item = R>) -> X,
+) -> X {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        let (next, work) = (&next, &work);
+        for _ in 0..jobs.min(items.len()) {
+            let sender = sender.clone();
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+                    // Nobody is waiting for the results any more
+                    if sender.send((index, work(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        consume(&mut InOrder {
+            receiver,
+            pending: BTreeMap::new(),
+            next: 0,
+        })
+    })
+}
+
+/// Results of [`in_order`], held back until those of all earlier items are out
+struct InOrder<R> {
+    receiver: Receiver<(usize, R)>,
+    /// Results that arrived early, by index
+    pending: BTreeMap<usize, R>,
+    /// Index of the next result
+    next: usize,
+}
+
+impl<R> Iterator for InOrder<R> {
+    type Item = R;
+
+    fn next(&mut self) -> Option<R> {
+        loop {
+            if let Some(result) = self.pending.remove(&self.next) {
+                self.next += 1;
+                return Some(result);
+            }
+            let (index, result) = self.receiver.recv().ok()?;
+            self.pending.insert(index, result);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_in_order() {
+        let items: Vec<u64> = (0..50).collect();
+        // Early items take longest, so they finish last
+        let work = |&item: &u64| {
+            thread::sleep(Duration::from_millis(50 - item));
+            item * 2
+        };
+        let results = in_order(&items, 8, work, |results| results.collect::<Vec<_>>());
+        assert_eq!(
+            results,
+            items.iter().map(|item| item * 2).collect::<Vec<_>>()
+        );
+        let first = in_order(&items, 8, work, |results| {
+            results.take(3).collect::<Vec<_>>()
+        });
+        assert_eq!(first, [0, 2, 4]);
+    }
+
+    #[test]
+    fn test_parse_jobs() {
+        assert_eq!(parse_jobs("4"), Ok(4));
+        assert!(parse_jobs("0").is_err());
+        assert!(parse_jobs("-1").is_err());
+        assert!(default_jobs() >= 1);
+    }
+}
@@ -0,0 +1,147 @@
+//! `--max-output-depth` and `--truncate-strings`: results cut short for reading, after evaluation
+
+use serde_json::Value;
+
+/// How far results are written
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Preview {
+    /// `--max-output-depth`: levels of arrays and objects written out
+    pub max_depth: Option<usize>,
+    /// `--truncate-strings`: characters kept of a longer string
+    pub max_chars: Option<usize>,
+}
+
+impl Preview {
+    /// Copy of `value` with the arrays and objects below `max_depth` levels replaced by a string
+    /// such as `[… 42 items]` or `{… 3 keys}`, and the strings over `max_chars` characters cut
+    /// to that many, followed by `…` and their length. Object keys are kept whole.
+    pub fn apply(&self, value: &Value) -> Value {
+        self.node(value, 0)
+    }
+
+    fn node(&self, value: &Value, depth: usize) -> Value {
+        let hidden = self.max_depth.is_some_and(|max| depth >= max);
+        match value {
+            Value::Array(items) if hidden && !items.is_empty() => {
+                Value::String(format!("[… {}]", count(items.len(), "item", "items")))
+            }
+            Value::Object(map) if hidden && !map.is_empty() => {
+                Value::String(format!("{{… {}}}", count(map.len(), "key", "keys")))
+            }
+            Value::Array(items) => Value::Array(
+                items
+                    .iter()
+                    .map(|item| self.node(item, depth + 1))
+                    .collect(),
+            ),
+            Value::Object(map) => Value::Object(
+                map.iter()
+                    .map(|(key, value)| (key.clone(), self.node(value, depth + 1)))
+                    .collect(),
+            ),
+            Value::String(s) => match self.max_chars {
+                Some(max) => Value::String(truncate(s, max)),
+                None => value.clone(),
+            },
+            other => other.clone(),
+        }
+    }
+}
+
+/// Value of `--max-output-depth`
+pub fn parse_depth(value: &str) -> Result<usize, String> {
+    value.parse().map_err(|_| {
+        format!(
+            "invalid value '{value}' for '--max-output-depth <N>' (expected a non-negative integer)"
+        )
+    })
+}
+
+/// Value of `--truncate-strings`
+pub fn parse_chars(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&chars| chars > 0)
+        .ok_or_else(|| {
+            format!(
+                "invalid value '{value}' for '--truncate-strings <N>' (expected a positive integer)"
+            )
+        })
+}
+
+fn count(n: usize, one: &str, many: &str) -> String {
+    format!("{n} {}", if n == 1 { one } else { many })
+}
+
+/// `s` cut to `max` characters with `…` and its length in characters, or whole if it fits
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((end, _)) => format!("{}… ({} chars)", &s[..end], s.chars().count()),
+        None => s.to_string(),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_depth() {
+        let value = json!({
+            "name": "root",
+            "list": [1, [2, 3], {"a": {"b": 1}}],
+            "one": {"k": []},
+            "empty": [],
+            "nested": {"x": {}, "y": [[]]}
+        });
+        let preview = |max_depth| {
+            Preview {
+                max_depth: Some(max_depth),
+                max_chars: None,
+            }
+            .apply(&value)
+        };
+        assert_eq!(preview(0), json!("{… 5 keys}"));
+        assert_eq!(
+            preview(1),
+            json!({
+                "name": "root",
+                "list": "[… 3 items]",
+                "one": "{… 1 key}",
+                "empty": [],
+                "nested": "{… 2 keys}"
+            })
+        );
+        assert_eq!(
+            preview(2),
+            json!({
+                "name": "root",
+                "list": [1, "[… 2 items]", "{… 1 key}"],
+                "one": {"k": []},
+                "empty": [],
+                "nested": {"x": {}, "y": "[… 1 item]"}
+            })
+        );
+        assert_eq!(preview(4), value);
+        assert_eq!(Preview::default().apply(&value), value);
+    }
+
+    #[test]
+    fn test_truncate() {
+        let preview = Preview {
+            max_depth: None,
+            max_chars: Some(5),
+        };
+        assert_eq!(
+            preview.apply(
+                &json!({"long key name": ["abcdefgh", "short", "日本語のテキスト", 123456789]})
+            ),
+            json!({"long key name": ["abcde… (8 chars)", "short", "日本語のテ… (8 chars)", 123456789]})
+        );
+        assert!(parse_chars("0").is_err());
+        assert_eq!(parse_depth("0"), Ok(0));
+    }
+}
@@ -0,0 +1,157 @@
+//! `--sort` and `--sort-by`: results in a total order over JSON values
+//!
+//! Values of different types order as null < false < true < numbers < strings < arrays <
+//! objects. Numbers compare numerically and strings by code point. Arrays compare element by
+//! element, a prefix first; objects compare their members in key order, key then value.
+
+use jpp_core::JsonPath;
+use serde_json::{Map, Number, Value};
+use std::cmp::Ordering;
+
+/// Order of the results of each query against each document
+pub enum Sort {
+    /// `--sort`: by the results themselves
+    Values,
+    /// `--sort-by`: by the first result of the query against each result; results it selects
+    /// nothing from sort last
+    By(JsonPath),
+}
+
+impl Sort {
+    /// Value of `--sort-by`: a query relative to each result, starting with `@` or `$`
+    pub fn by(query: &str) -> Result<Self, String> {
+        let root = match query.strip_prefix('@') {
+            Some(rest) => format!("${rest}"),
+            None => query.to_string(),
+        };
+        JsonPath::parse(&root)
+            .map(Self::By)
+            .map_err(|e| format!("invalid query '{query}' for '--sort-by <QUERY>': {e}"))
+    }
+
+    /// Stable sort of `items`, each standing for the result `value(item)`
+    pub fn sort<'a, T>(&self, items: &mut Vec<T>, value: impl Fn(&T) -> &'a Value) {
+        let key = |result: &'a Value| match self {
+            Self::Values => Some(result),
+            Self::By(path) => path.query_limit(result, 1).into_iter().next(),
+        };
+        let mut keyed: Vec<_> = items
+            .drain(..)
+            .map(|item| (key(value(&item)), item))
+            .collect();
+        keyed.sort_by(|(a, _), (b, _)| match (a, b) {
+            (Some(a), Some(b)) => compare(a, b),
+            (a, b) => b.is_some().cmp(&a.is_some()),
+        });
+        items.extend(keyed.into_iter().map(|(_, item)| item));
+    }
+}
+
+/// Rank of the type of `value` in the order
+fn rank(value: &Value) -> u8 {
+    match value {
+        Value::Null => 0,
+        Value::Bool(_) => 1,
+        Value::Number(_) => 2,
+        Value::String(_) => 3,
+        Value::Array(_) => 4,
+        Value::Object(_) => 5,
+    }
+}
+
+/// The total order of `--sort`
+pub fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a, b) {
+        (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+        (Value::Number(a), Value::Number(b)) => compare_numbers(a, b),
+        (Value::String(a), Value::String(b)) => a.cmp(b),
+        (Value::Array(a), Value::Array(b)) => a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| compare(a, b))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Value::Object(a), Value::Object(b)) => compare_objects(a, b),
+        _ => rank(a).cmp(&rank(b)),
+    }
+}
+
+/// Integers compare exactly; anything else as floating point
+fn compare_numbers(a: &Number, b: &Number) -> Ordering {
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        return a.cmp(&b);
+    }
+    if let (Some(a), Some(b)) = (a.as_u64(), b.as_u64()) {
+        return a.cmp(&b);
+    }
+    let (a, b) = (a.as_f64().unwrap_or(0.0), b.as_f64().unwrap_or(0.0));
+    a.total_cmp(&b)
+}
+
+fn sorted_members(map: &Map<String, Value>) -> Vec<(&String, &Value)> {
+    let mut members: Vec<_> = map.iter().collect();
+    members.sort_unstable_by(|a, b| a.0.cmp(b.0));
+    members
+}
+
+fn compare_objects(a: &Map<String, Value>, b: &Map<String, Value>) -> Ordering {
+    let (a, b) = (sorted_members(a), sorted_members(b));
+    a.iter()
+        .zip(&b)
+        .map(|((a_key, a), (b_key, b))| a_key.cmp(b_key).then_with(|| compare(a, b)))
+        .find(|order| order.is_ne())
+        .unwrap_or_else(|| a.len().cmp(&b.len()))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sorted(sort: &Sort, values: Value) -> Value {
+        let Value::Array(values) = values else {
+            return values;
+        };
+        let mut results: Vec<&Value> = values.iter().collect();
+        sort.sort(&mut results, |result| result);
+        Value::Array(results.into_iter().cloned().collect())
+    }
+
+    #[test]
+    fn test_mixed_types() {
+        assert_eq!(
+            sorted(
+                &Sort::Values,
+                json!([{"a": 1}, [2], "b", 10, true, null, [1, 2], 2.5, "a", false, {}, -3, [1]])
+            ),
+            json!([null, false, true, -3, 2.5, 10, "a", "b", [1], [1, 2], [2], {}, {"a": 1}])
+        );
+        assert_eq!(
+            compare(&json!(18446744073709551615u64), &json!(-1)),
+            Ordering::Greater
+        );
+        assert_eq!(
+            compare(&json!({"a": 1, "b": 2}), &json!({"b": 2, "a": 1})),
+            Ordering::Equal
+        );
+        assert_eq!(
+            compare(&json!({"a": 2}), &json!({"a": 1, "b": 0})),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_sort_by() {
+        let sort = Sort::by("@.price").unwrap();
+        assert_eq!(
+            sorted(
+                &sort,
+                json!([{"id": 1, "price": 9}, {"id": 2}, {"id": 3, "price": 5}, 7, {"id": 4, "price": 5}])
+            ),
+            json!([{"id": 3, "price": 5}, {"id": 4, "price": 5}, {"id": 1, "price": 9}, {"id": 2}, 7])
+        );
+        assert!(Sort::by("$.price").is_ok());
+        assert!(Sort::by("price").is_err());
+    }
+}
@@ -0,0 +1,264 @@
+//! `--output table` and `--output markdown`: results as the rows of a table
+
+use crate::ascii::{self, Astral};
+use jpp_core::display_width;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::io::{self, Write};
+
+/// Header of the one column of results that are not all objects
+const VALUE_COLUMN: &str = "value";
+
+/// How a table is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// Columns aligned with spaces, under a line of dashes
+    Text,
+    /// A GitHub-flavored Markdown table
+    Markdown,
+}
+
+/// Value of `--max-col-width`
+pub fn parse_max_width(value: &str) -> Result<usize, String> {
+    value
+        .parse()
+        .ok()
+        .filter(|&width| width > 0)
+        .ok_or_else(|| {
+            format!(
+                "invalid value '{value}' for '--max-col-width <N>' (expected a positive integer)"
+            )
+        })
+}
+
+/// Write `results` as a table with a row for each: when they are all objects, a column for
+/// each of their keys in order of first appearance, with an empty cell where one lacks the
+/// key; otherwise a single column of the results. Strings show their contents and other
+/// values compact JSON, cut to `max_width` columns with `…`. No results write nothing. With
+/// `ascii`, cells escape their non-ASCII characters.
+pub fn write_table(
+    out: &mut impl Write,
+    results: &[&Value],
+    style: Style,
+    max_width: Option<usize>,
+    ascii: bool,
+) -> io::Result<()> {
+    if results.is_empty() {
+        return Ok(());
+    }
+    let objects: Option<Vec<_>> = results.iter().map(|result| result.as_object()).collect();
+    let (header, rows): (Vec<String>, Vec<Vec<String>>) = match objects {
+        Some(objects) => {
+            let mut seen = HashSet::new();
+            let keys: Vec<&String> = objects
+                .iter()
+                .flat_map(|object| object.keys())
+                .filter(|key| seen.insert(*key))
+                .collect();
+            let rows = objects
+                .iter()
+                .map(|object| {
+                    keys.iter()
+                        .map(|key| object.get(*key).map(cell).unwrap_or_default())
+                        .collect()
+                })
+                .collect();
+            (keys.into_iter().cloned().collect(), rows)
+        }
+        None => (
+            vec![VALUE_COLUMN.to_string()],
+            results.iter().map(|result| vec![cell(result)]).collect(),
+        ),
+    };
+    write_cells(out, &header, &rows, style, max_width, ascii)
+}
+
+/// Write `entries` as a table of one row, with a column for each in order
+pub fn write_entries(
+    out: &mut impl Write,
+    entries: &[(String, Value)],
+    style: Style,
+    max_width: Option<usize>,
+    ascii: bool,
+) -> io::Result<()> {
+    let (header, row): (Vec<String>, Vec<String>) = entries
+        .iter()
+        .map(|(key, value)| (key.clone(), cell(value)))
+        .unzip();
+    write_cells(out, &header, &[row], style, max_width, ascii)
+}
+
+/// Write the header and rows of cell texts, fitted to `max_width` and aligned
+fn write_cells(
+    out: &mut impl Write,
+    header: &[String],
+    rows: &[Vec<String>],
+    style: Style,
+    max_width: Option<usize>,
+    ascii: bool,
+) -> io::Result<()> {
+    let fit = |text: &str| {
+        let text = if ascii {
+            ascii::escape(text, Astral::SurrogatePair)
+        } else {
+            text.into()
+        };
+        escape(&truncate(&text, max_width), style)
+    };
+    let header: Vec<String> = header.iter().map(|name| fit(name)).collect();
+    let rows: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|text| fit(text)).collect())
+        .collect();
+
+    // Markdown needs three dashes under each header
+    let least = match style {
+        Style::Text => 0,
+        Style::Markdown => 3,
+    };
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| display_width(&row[i]))
+                .chain([display_width(&header[i]), least])
+                .max()
+                .unwrap_or(least)
+        })
+        .collect();
+    let rule: Vec<String> = widths.iter().map(|&width| "-".repeat(width)).collect();
+    for row in [&header, &rule].into_iter().chain(&rows) {
+        write_row(out, row, &widths, style)?;
+    }
+    Ok(())
+}
+
+/// Text of the cell of `value`
+fn cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// `text` cut to `max_width` columns, ending with `…` where it was cut
+fn truncate(text: &str, max_width: Option<usize>) -> String {
+    let Some(max_width) = max_width.filter(|&max| display_width(text) > max) else {
+        return text.to_string();
+    };
+    let mut cut = String::new();
+    let mut width = 0;
+    for c in text.chars() {
+        width += display_width(c.encode_utf8(&mut [0; 4]));
+        if width >= max_width {
+            break;
+        }
+        cut.push(c);
+    }
+    cut.push('…');
+    cut
+}
+
+/// `text` kept on one line of the table: control characters escaped, and for Markdown, `|`
+/// escaped and line breaks as `<br>`
+fn escape(text: &str, style: Style) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match (c, style) {
+            ('|', Style::Markdown) => escaped.push_str("\\|"),
+            ('\n', Style::Markdown) => escaped.push_str("<br>"),
+            (c, _) if c.is_control() => escaped.extend(c.escape_default()),
+            (c, _) => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn write_row(
+    out: &mut impl Write,
+    cells: &[String],
+    widths: &[usize],
+    style: Style,
+) -> io::Result<()> {
+    let padded = cells
+        .iter()
+        .zip(widths)
+        .map(|(cell, &width)| format!("{cell}{}", " ".repeat(width - display_width(cell))));
+    match style {
+        Style::Text => {
+            let line = padded.collect::<Vec<_>>().join("  ");
+            writeln!(out, "{}", line.trim_end())
+        }
+        Style::Markdown => writeln!(out, "| {} |", padded.collect::<Vec<_>>().join(" | ")),
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn table(results: Value, style: Style, max_width: Option<usize>) -> String {
+        table_with(results, style, max_width, false)
+    }
+
+    fn table_with(results: Value, style: Style, max_width: Option<usize>, ascii: bool) -> String {
+        let Value::Array(results) = results else {
+            return String::new();
+        };
+        let results: Vec<&Value> = results.iter().collect();
+        let mut out = Vec::new();
+        write_table(&mut out, &results, style, max_width, ascii).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_text() {
+        let results = json!([
+            {"name": "東京", "pop": 14, "tags": ["capital"]},
+            {"name": "Osaka", "pop": 2.7},
+            {"name": "a\tb", "pop": null, "note": "x"}
+        ]);
+        assert_eq!(
+            table(results, Style::Text, None),
+            "name   pop   tags         note\n\
+             -----  ----  -----------  ----\n\
+             東京   14    [\"capital\"]\n\
+             Osaka  2.7\n\
+             a\\tb   null               x\n"
+        );
+        assert_eq!(
+            table(json!([1, "two", {"a": 3}]), Style::Text, None),
+            "value\n-------\n1\ntwo\n{\"a\":3}\n"
+        );
+        assert_eq!(table(json!([]), Style::Text, None), "");
+        assert_eq!(
+            table_with(json!([{"都市": "東京"}]), Style::Text, None, true),
+            "\\u90fd\\u5e02\n------------\n\\u6771\\u4eac\n"
+        );
+    }
+
+    #[test]
+    fn test_markdown() {
+        assert_eq!(
+            table(
+                json!([{"cmd": "a | b", "out": "one\ntwo"}, {"cmd": "日本語のテキスト"}]),
+                Style::Markdown,
+                Some(8)
+            ),
+            "| cmd     | out        |\n\
+             | ------- | ---------- |\n\
+             | a \\| b  | one<br>two |\n\
+             | 日本語… |            |\n"
+        );
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("abcdef", Some(4)), "abc…");
+        assert_eq!(truncate("abcd", Some(4)), "abcd");
+        assert_eq!(truncate("日本語", Some(4)), "日…");
+        assert_eq!(truncate("abc", Some(1)), "…");
+        assert!(parse_max_width("0").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+//! `--format`: a line of text for each result, with `{@...}` placeholders filled in from it
+
+use crate::ascii::{self, Astral};
+use jpp_core::JsonPath;
+use serde_json::Value;
+use serde_json::ser::CompactFormatter;
+use std::io::{self, Write};
+use std::mem;
+
+/// A parsed `--format` template
+#[derive(Debug)]
+pub struct Template {
+    parts: Vec<Part>,
+    /// `--format-missing`: text for a placeholder that matches nothing
+    missing: String,
+}
+
+#[derive(Debug)]
+enum Part {
+    Text(String),
+    /// A relative query, run against the result with `@` as its root
+    Placeholder(JsonPath),
+}
+
+impl Template {
+    /// Parse `template`, where `{{` and `}}` stand for literal braces and every other `{...}`
+    /// holds a relative query such as `@.title`
+    pub fn parse(template: &str, missing: String) -> Result<Self, String> {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let column = template[..template.len() - rest.len() + i].chars().count() + 1;
+            let after = &rest[i + 1..];
+            if rest[i..].starts_with("{{") || rest[i..].starts_with("}}") {
+                text.push_str(&rest[i..=i]);
+                rest = &after[1..];
+                continue;
+            }
+            if rest[i..].starts_with('}') {
+                return Err(format!(
+                    "unmatched '}}' in --format at column {column}; write '}}}}' for a literal brace"
+                ));
+            }
+            let Some(end) = placeholder_end(after) else {
+                return Err(format!(
+                    "unclosed '{{' in --format at column {column}; write '{{{{' for a literal brace"
+                ));
+            };
+            parts.push(Part::Text(mem::take(&mut text)));
+            parts.push(Part::Placeholder(placeholder(&after[..end])?));
+            rest = &after[end + 1..];
+        }
+        text.push_str(rest);
+        parts.push(Part::Text(text));
+        parts.retain(|part| !matches!(part, Part::Text(text) if text.is_empty()));
+        Ok(Self { parts, missing })
+    }
+
+    /// Write the line of `result`: strings as their contents, other values as compact JSON,
+    /// and several matches of one placeholder as a compact JSON array. With `ascii`, the
+    /// whole line escapes its non-ASCII characters.
+    pub fn write_line(&self, out: &mut impl Write, result: &Value, ascii: bool) -> io::Result<()> {
+        for part in &self.parts {
+            match part {
+                Part::Text(text) => write_text(out, text, ascii)?,
+                Part::Placeholder(path) => match path.query(result).as_slice() {
+                    [] => write_text(out, &self.missing, ascii)?,
+                    [Value::String(s)] => write_text(out, s, ascii)?,
+                    [value] => ascii::serialize(&mut *out, value, CompactFormatter, ascii)?,
+                    values => ascii::serialize(&mut *out, values, CompactFormatter, ascii)?,
+                },
+            }
+        }
+        out.write_all(b"\n")
+    }
+}
+
+fn write_text(out: &mut impl Write, text: &str, ascii: bool) -> io::Result<()> {
+    if ascii {
+        out.write_all(ascii::escape(text, Astral::SurrogatePair).as_bytes())
+    } else {
+        out.write_all(text.as_bytes())
+    }
+}
+
+/// Length of the query of a placeholder that starts `text`, up to its closing `}`. Braces
+/// inside the quoted strings of the query do not close it.
+fn placeholder_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '}') => return Some(i),
+            (None, _) => {}
+        }
+    }
+    None
+}
+
+/// Parse the query of `{query}`, which starts at the result with `@`
+fn placeholder(query: &str) -> Result<JsonPath, String> {
+    let trimmed = query.trim();
+    let Some(relative) = trimmed.strip_prefix('@') else {
+        return Err(format!(
+            "invalid placeholder '{{{query}}}' in --format: expected a relative query starting with '@', such as {{@.name}}"
+        ));
+    };
+    JsonPath::parse(&format!("${relative}"))
+        .map_err(|e| format!("invalid placeholder '{{{query}}}' in --format: {e}"))
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(template: &str, result: &Value) -> String {
+        let mut out = Vec::new();
+        Template::parse(template, "-".to_string())
+            .unwrap()
+            .write_line(&mut out, result, false)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let book = json!({
+            "title": "Sayings",
+            "price": 8.95,
+            "author": {"name": "Nigel Rees"},
+            "tags": ["a", "b"],
+            "odd}key": true
+        });
+        assert_eq!(
+            render("{@.title} by {@.author.name}: {@.price}", &book),
+            "Sayings by Nigel Rees: 8.95\n"
+        );
+        assert_eq!(
+            render("{{{@.tags[0]}}} {@.tags} {@.tags[*]} {@.author}", &book),
+            "{a} [\"a\",\"b\"] [\"a\",\"b\"] {\"name\":\"Nigel Rees\"}\n"
+        );
+        assert_eq!(render("{ @['odd}key'] } {@.isbn}", &book), "true -\n");
+        assert_eq!(render("{@}", &json!("whole")), "whole\n");
+        assert_eq!(render("plain", &json!(null)), "plain\n");
+
+        let mut out = Vec::new();
+        Template::parse("€ {@}", String::new())
+            .unwrap()
+            .write_line(&mut out, &json!(["東京"]), true)
+            .unwrap();
+        assert_eq!(out, b"\\u20ac [\"\\u6771\\u4eac\"]\n");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |template| Template::parse(template, String::new()).unwrap_err();
+        assert_eq!(
+            error("é } b"),
+            "unmatched '}' in --format at column 3; write '}}' for a literal brace"
+        );
+        assert_eq!(
+            error("{@.a"),
+            "unclosed '{' in --format at column 1; write '{{' for a literal brace"
+        );
+        assert!(error("{title}").contains("starting with '@'"));
+        assert!(error("{$.title}").contains("starting with '@'"));
+        assert!(error("{@.a[}").starts_with("invalid placeholder '{@.a[}' in --format: "));
+    }
+}
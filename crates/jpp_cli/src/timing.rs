@@ -0,0 +1,147 @@
+//! `--time`: where the time of a run went, reported on stderr
+
+use serde_json::json;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+/// Value of `--time-format`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeFormat {
+    /// One `key=value` line per measurement
+    Text,
+    /// One JSON object
+    Json,
+}
+
+impl TimeFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid value '{s}' for '--time-format <FORMAT>' (expected text or json)"
+            )),
+        }
+    }
+}
+
+/// Measurements summed over all inputs
+#[derive(Default)]
+pub struct Timing {
+    /// Bytes read from the inputs, after decompression
+    pub input_bytes: u64,
+    /// Reading and decompressing the inputs
+    pub read: Duration,
+    /// Parsing the input documents
+    pub parse: Duration,
+    /// Parsing the queries
+    pub query_parse: Duration,
+    /// Evaluating the queries, edits included
+    pub eval: Duration,
+    /// Results of all queries: nodes, counted nodes or edited matches
+    pub matches: usize,
+    /// Evaluating the queries and writing the results, which is kept out of `parse`; not
+    /// reported
+    pub documents: Duration,
+}
+
+impl Timing {
+    /// Run `eval`, adding its duration to the evaluation time
+    pub fn eval<T>(timing: &mut Option<Self>, eval: impl FnOnce() -> T) -> T {
+        let Some(timing) = timing else {
+            return eval();
+        };
+        let start = Instant::now();
+        let result = eval();
+        timing.eval += start.elapsed();
+        result
+    }
+
+    pub fn write(&self, out: &mut impl Write, format: TimeFormat) -> io::Result<()> {
+        let ms = |duration: Duration| (duration.as_secs_f64() * 1e6).round() / 1e3;
+        let peak_rss = peak_rss_bytes();
+        match format {
+            TimeFormat::Text => {
+                writeln!(out, "input_bytes={}", self.input_bytes)?;
+                writeln!(out, "read_ms={:.3}", ms(self.read))?;
+                writeln!(out, "parse_ms={:.3}", ms(self.parse))?;
+                writeln!(out, "query_parse_ms={:.3}", ms(self.query_parse))?;
+                writeln!(out, "eval_ms={:.3}", ms(self.eval))?;
+                writeln!(out, "matches={}", self.matches)?;
+                if let Some(peak_rss) = peak_rss {
+                    writeln!(out, "peak_rss_bytes={peak_rss}")?;
+                }
+                Ok(())
+            }
+            TimeFormat::Json => {
+                let object = json!({
+                    "input_bytes": self.input_bytes,
+                    "read_ms": ms(self.read),
+                    "parse_ms": ms(self.parse),
+                    "query_parse_ms": ms(self.query_parse),
+                    "eval_ms": ms(self.eval),
+                    "matches": self.matches,
+                    "peak_rss_bytes": peak_rss,
+                });
+                serde_json::to_writer(&mut *out, &object)?;
+                writeln!(out)
+            }
+        }
+    }
+}
+
+/// Peak resident set size of the process, where the system reports it cheaply
+#[cfg(target_os = "linux")]
+fn peak_rss_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let kib = status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))?
+        .trim()
+        .strip_suffix("kB")?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    Some(kib * 1024)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peak_rss_bytes() -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    #[test]
+    fn test_write() {
+        let timing = Timing {
+            input_bytes: 42,
+            read: Duration::from_micros(1500),
+            eval: Duration::from_nanos(2_000_400),
+            matches: 3,
+            ..Timing::default()
+        };
+        let mut out = Vec::new();
+        timing.write(&mut out, TimeFormat::Text).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with(concat!(
+            "input_bytes=42\n",
+            "read_ms=1.500\n",
+            "parse_ms=0.000\n",
+            "query_parse_ms=0.000\n",
+            "eval_ms=2.000\n",
+            "matches=3\n",
+        )));
+
+        let mut out = Vec::new();
+        timing.write(&mut out, TimeFormat::Json).unwrap();
+        let object: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(object["read_ms"], 1.5);
+        assert_eq!(object["matches"], 3);
+        assert!(object.get("peak_rss_bytes").is_some());
+    }
+}
@@ -0,0 +1,189 @@
+//! `--validate`: parse queries without reading input and report each one
+
+use crate::diagnostic::{Kind, parse_error_name};
+use crate::variables::Variables;
+use jpp_core::parser::Parser;
+use jpp_core::{ParseError, render_span};
+use serde_json::{Value, json};
+use std::io::{self, Write};
+use std::ops::Range;
+
+/// Value of `--error-format`
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// Messages with the query and a caret under the error
+    Human,
+    /// One JSON object per report
+    Json,
+}
+
+impl ErrorFormat {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "invalid value '{s}' for '--error-format <FORMAT>' (expected human or json)"
+            )),
+        }
+    }
+}
+
+/// A query to validate and where it came from
+pub struct Checked<'a> {
+    pub query: &'a str,
+    /// `-f` file the query was read from
+    pub file: Option<&'a str>,
+    /// Line of standard input the query was read from, in list mode
+    pub line: Option<usize>,
+}
+
+impl Checked<'_> {
+    /// Name that prefixes human-readable reports
+    fn name(&self) -> String {
+        match (self.file, self.line) {
+            (Some(file), _) => file.to_string(),
+            (None, Some(line)) => format!("(standard input):{line}"),
+            (None, None) => self.query.to_string(),
+        }
+    }
+}
+
+/// Why a query is invalid
+enum Failure {
+    /// A syntax error in `query`, the query after variable substitution
+    Parse { query: String, error: ParseError },
+    /// An undefined `--arg` variable
+    Variable(String),
+}
+
+fn check(variables: &Variables, query: &str) -> Result<(), Failure> {
+    let query = variables.substitute(query).map_err(Failure::Variable)?;
+    match Parser::parse(&query) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(Failure::Parse { query, error }),
+    }
+}
+
+/// Validate `checked` and write a report for it, prefixed with its name when `named`.
+/// Returns whether the query is valid.
+pub fn report(
+    out: &mut impl Write,
+    format: ErrorFormat,
+    variables: &Variables,
+    checked: &Checked,
+    named: bool,
+) -> io::Result<bool> {
+    let result = check(variables, checked.query);
+    let valid = result.is_ok();
+    match format {
+        ErrorFormat::Json => {
+            let mut object = json!({
+                "query": checked.query,
+                "file": checked.file,
+                "line": checked.line,
+                "valid": valid,
+            });
+            match &result {
+                Ok(()) => {}
+                Err(Failure::Parse { error: e, .. }) => {
+                    object["kind"] = Value::from(parse_error_name(e.kind));
+                    object["message"] = Value::from(e.message.as_str());
+                    object["position"] = Value::from(e.span.start);
+                    object["end"] = Value::from(e.span.end);
+                }
+                Err(Failure::Variable(message)) => {
+                    object["kind"] = Value::from(Kind::UndefinedVariable.name());
+                    object["message"] = Value::from(message.as_str());
+                }
+            }
+            serde_json::to_writer(&mut *out, &object)?;
+            out.write_all(b"\n")?;
+        }
+        ErrorFormat::Human => {
+            if named {
+                write!(out, "{}: ", checked.name())?;
+            }
+            match &result {
+                Ok(()) => writeln!(out, "ok")?,
+                Err(Failure::Parse { query, error }) => {
+                    writeln!(out, "error: {error}")?;
+                    write_caret(out, query, &error.span)?;
+                }
+                Err(Failure::Variable(message)) => writeln!(out, "error: {message}")?,
+            }
+        }
+    }
+    Ok(valid)
+}
+
+/// Write the line of `query` holding the error, with a caret under the start of `span` and
+/// a squiggle under the rest, cut to fit the terminal
+pub fn write_caret(out: &mut impl Write, query: &str, span: &Range<usize>) -> io::Result<()> {
+    let [line, marker] = render_span(query, span, terminal_width().saturating_sub(2));
+    writeln!(out, "  {line}")?;
+    writeln!(out, "  {marker}")
+}
+
+/// Columns of the terminal, from `COLUMNS`, else 80
+fn terminal_width() -> usize {
+    std::env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse().ok())
+        .filter(|&columns| columns > 0)
+        .unwrap_or(80)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn render(format: ErrorFormat, query: &str) -> (String, bool) {
+        let mut out = Vec::new();
+        let checked = Checked {
+            query,
+            file: None,
+            line: None,
+        };
+        let valid = report(&mut out, format, &Variables::default(), &checked, false).unwrap();
+        (String::from_utf8(out).unwrap(), valid)
+    }
+
+    #[test]
+    fn test_caret() {
+        assert_eq!(
+            render(ErrorFormat::Human, "$.a[0]"),
+            ("ok\n".to_string(), true)
+        );
+        assert_eq!(
+            render(ErrorFormat::Human, "$.a[?@.x >]").0,
+            concat!(
+                "error: at position 10, unexpected token in expression: BracketClose\n",
+                "  $.a[?@.x >]\n",
+                "            ^\n",
+            )
+        );
+        // At the end of the input, and on the second line of a multi-line query
+        assert!(
+            render(ErrorFormat::Human, "$.a[")
+                .0
+                .ends_with("  $.a[\n      ^\n")
+        );
+        assert!(
+            render(ErrorFormat::Human, "$[?@.a == 1 &&\n  @.b ==]")
+                .0
+                .ends_with("    @.b ==]\n          ^\n")
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let (report, valid) = render(ErrorFormat::Json, "$.a[?@.x >]");
+        assert!(!valid);
+        let report: Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(report["kind"], "unexpected_token");
+        assert_eq!(report["position"], 10);
+        assert_eq!(report["file"], Value::Null);
+    }
+}
@@ -0,0 +1,150 @@
+//! `--arg` and `--argjson` variables, substituted into queries as literals
+
+use jpp_core::ast::Literal;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Variable names mapped to the query text of their values
+#[derive(Default)]
+pub struct Variables(HashMap<String, String>);
+
+impl Variables {
+    /// Define `$name` as `value`, replacing an earlier definition. Only scalars can be
+    /// defined, since JSONPath has no array or object literals.
+    pub fn define(&mut self, option: &str, name: &str, value: &Value) -> Result<(), String> {
+        if !is_name(name) {
+            return Err(format!(
+                "invalid variable name '{name}' for '{option}' (expected letters, digits and _, not starting with a digit)"
+            ));
+        }
+        let literal = match value {
+            Value::Null => Literal::Null,
+            Value::Bool(b) => Literal::Bool(*b),
+            Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+                (Some(n), _) => Literal::Integer(n.into()),
+                (_, Some(n)) => Literal::Integer(n.into()),
+                _ => Literal::Number(n.as_f64().unwrap_or_default()),
+            },
+            Value::String(s) => Literal::String(s.clone()),
+            Value::Array(_) | Value::Object(_) => {
+                return Err(format!(
+                    "invalid value for '{option}' variable '{name}': JSONPath has no array or object literals"
+                ));
+            }
+        };
+        self.0.insert(name.to_string(), literal.to_string());
+        Ok(())
+    }
+
+    /// Replace each `$name` outside string literals in `query` with the literal of its value.
+    /// RFC 9535 never allows a name right after `$`, so `$name` cannot mean the root.
+    pub fn substitute(&self, query: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(query.len());
+        let mut quote = None;
+        let mut escaped = false;
+        let mut chars = query.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            if let Some(q) = quote {
+                out.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+                continue;
+            }
+            match c {
+                '\'' | '"' => {
+                    quote = Some(c);
+                    out.push(c);
+                }
+                '$' if chars
+                    .peek()
+                    .is_some_and(|&(_, next)| next.is_ascii_alphabetic() || next == '_') =>
+                {
+                    let start = i + 1;
+                    let mut end = start;
+                    while let Some(&(j, next)) = chars.peek() {
+                        if !(next.is_ascii_alphanumeric() || next == '_') {
+                            break;
+                        }
+                        end = j + next.len_utf8();
+                        chars.next();
+                    }
+                    let name = &query[start..end];
+                    let literal = self.0.get(name).ok_or_else(|| {
+                        format!("undefined variable '${name}' in query; define it with --arg or --argjson")
+                    })?;
+                    out.push_str(literal);
+                }
+                _ => out.push(c),
+            }
+        }
+        Ok(out)
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    name.chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_substitute() {
+        let mut variables = Variables::default();
+        variables.define("--arg", "s", &json!("it's")).unwrap();
+        variables.define("--argjson", "n", &json!(-2.5)).unwrap();
+        variables.define("--argjson", "i", &json!(3)).unwrap();
+        variables.define("--argjson", "b_2", &json!(true)).unwrap();
+        variables.define("--argjson", "z", &json!(null)).unwrap();
+
+        assert_eq!(
+            variables
+                .substitute("$[?@.a == $s && @.b < $n || @.c == $b_2 && @.d != $z]")
+                .unwrap(),
+            r"$[?@.a == 'it\'s' && @.b < -2.5 || @.c == true && @.d != null]"
+        );
+        // Selectors take literals too, and the root is left alone
+        assert_eq!(variables.substitute("$[$i][$s]").unwrap(), r"$[3]['it\'s']");
+        assert_eq!(
+            variables.substitute("$[?@ == $.x]").unwrap(),
+            "$[?@ == $.x]"
+        );
+        // Nothing inside string literals changes, escaped quotes included
+        assert_eq!(
+            variables.substitute(r#"$['$s', "\"$s", '\'$n']"#).unwrap(),
+            r#"$['$s', "\"$s", '\'$n']"#
+        );
+
+        assert_eq!(
+            variables.substitute("$[?@.a == $missing]").unwrap_err(),
+            "undefined variable '$missing' in query; define it with --arg or --argjson"
+        );
+    }
+
+    #[test]
+    fn test_define() {
+        let mut variables = Variables::default();
+        assert!(variables.define("--arg", "1x", &json!("")).is_err());
+        assert!(variables.define("--arg", "a-b", &json!("")).is_err());
+        assert!(variables.define("--arg", "", &json!("")).is_err());
+        assert_eq!(
+            variables.define("--argjson", "a", &json!([1])).unwrap_err(),
+            "invalid value for '--argjson' variable 'a': JSONPath has no array or object literals"
+        );
+        // A later definition wins
+        variables.define("--arg", "a", &json!("x")).unwrap();
+        variables.define("--argjson", "a", &json!(1)).unwrap();
+        assert_eq!(variables.substitute("$[$a]").unwrap(), "$[1]");
+    }
+}
@@ -0,0 +1,124 @@
+//! `--recursive`: the files under directory arguments
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Default of `--include`
+pub const DEFAULT_INCLUDE: &str = "*.json";
+
+/// The files to read for the FILE arguments `files`. Each directory is replaced by the files
+/// under it whose name matches one of the `include` globs, in name order; other arguments
+/// are kept as they are. Directories that cannot be read come back as errors, each with the
+/// path it is about.
+pub fn expand(files: &[String], include: &[String]) -> (Vec<String>, Vec<(String, String)>) {
+    let mut found = Vec::new();
+    let mut errors = Vec::new();
+    for file in files {
+        if file != "-" && fs::metadata(file).is_ok_and(|metadata| metadata.is_dir()) {
+            walk(Path::new(file), include, &mut found, &mut errors);
+        } else {
+            found.push(file.clone());
+        }
+    }
+    (found, errors)
+}
+
+fn walk(
+    dir: &Path,
+    include: &[String],
+    found: &mut Vec<String>,
+    errors: &mut Vec<(String, String)>,
+) {
+    let label = dir.display().to_string();
+    let mut entries = match fs::read_dir(dir).and_then(Iterator::collect::<io::Result<Vec<_>>>) {
+        Ok(entries) => entries,
+        Err(e) => {
+            let message = format!("error reading directory '{label}': {e}");
+            return errors.push((label, message));
+        }
+    };
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        let path = entry.path();
+        // Links to directories are not followed, so a link cannot make a cycle
+        if entry.file_type().is_ok_and(|kind| kind.is_dir()) {
+            walk(&path, include, found, errors);
+        } else if entry
+            .file_name()
+            .to_str()
+            .is_some_and(|name| include.iter().any(|glob| glob_matches(glob, name)))
+        {
+            match path.to_str() {
+                Some(path) => found.push(path.to_string()),
+                None => {
+                    let label = path.display().to_string();
+                    let message = format!("skipping '{label}': the path is not UTF-8");
+                    errors.push((label, message));
+                }
+            }
+        }
+    }
+}
+
+/// Whether the file name `name` matches `glob`, in which `*` stands for any characters and
+/// `?` for one
+pub fn glob_matches(glob: &str, name: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut g, mut n) = (0, 0);
+    // After the last `*`: where the glob goes on, and where the `*` stopped in the name
+    let mut star = None;
+    while n < name.len() {
+        match glob.get(g) {
+            Some('*') => {
+                g += 1;
+                star = Some((g, n));
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                g += 1;
+                n += 1;
+            }
+            // Let the last `*` take one more character
+            _ => match star {
+                Some((after, stopped)) => {
+                    g = after;
+                    n = stopped + 1;
+                    star = Some((after, n));
+                }
+                None => return false,
+            },
+        }
+    }
+    glob[g..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        for (glob, name) in [
+            ("*.json", "a.json"),
+            ("*.json", ".json"),
+            ("*", "anything"),
+            ("data-?.json", "data-1.json"),
+            ("*-*.json", "a-b-c.json"),
+            ("*.ndjson", "x.ndjson"),
+            ("**a", "banana"),
+        ] {
+            assert!(glob_matches(glob, name), "{glob} {name}");
+        }
+        for (glob, name) in [
+            ("*.json", "a.json.bak"),
+            ("*.json", "a.jsonl"),
+            ("data-?.json", "data-10.json"),
+            ("a*b", "abc"),
+            ("", "a"),
+        ] {
+            assert!(!glob_matches(glob, name), "{glob} {name}");
+        }
+    }
+}
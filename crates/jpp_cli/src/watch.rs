@@ -0,0 +1,117 @@
+//! `--watch`: run the queries again whenever an input file changes
+
+/// How long the files must stay unchanged before they are read again, so that an editor's
+/// save is one change
+#[cfg(feature = "watch")]
+const DEBOUNCE: std::time::Duration = std::time::Duration::from_millis(200);
+
+#[cfg(feature = "watch")]
+enum Event {
+    Changed(notify::Result<notify::Event>),
+    Interrupted,
+}
+
+/// Call `run` once, then again after each change to one of `files`, until Ctrl-C. Before each
+/// rerun the screen is cleared when `clear`, else a separator naming the changed files is
+/// printed. An error from `run` ends the watch.
+///
+/// The directories of the files are watched rather than the files, since editors often save
+/// by writing a new file and renaming it over the old one.
+#[cfg(feature = "watch")]
+pub fn watch<E: From<String>>(
+    files: &[String],
+    clear: bool,
+    mut run: impl FnMut() -> Result<(), E>,
+) -> Result<(), E> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use std::io::{self, Write};
+    use std::path::{Path, PathBuf};
+    use std::sync::mpsc::{self, RecvTimeoutError};
+
+    let (sender, receiver) = mpsc::channel();
+    let interrupt = sender.clone();
+    ctrlc::set_handler(move || {
+        let _ = interrupt.send(Event::Interrupted);
+    })
+    .map_err(|e| format!("cannot handle Ctrl-C: {e}"))?;
+    let mut watcher = notify::recommended_watcher(move |event| {
+        let _ = sender.send(Event::Changed(event));
+    })
+    .map_err(|e| format!("cannot watch files: {e}"))?;
+
+    // Paths as the watcher reports them, and the file arguments they stand for
+    let mut targets: Vec<(PathBuf, &str)> = Vec::new();
+    for file in files {
+        let path = Path::new(file);
+        let (Some(name), Some(dir)) = (path.file_name(), path.parent()) else {
+            return Err(format!("cannot watch '{file}': not a file").into());
+        };
+        let dir = if dir.as_os_str().is_empty() {
+            Path::new(".")
+        } else {
+            dir
+        };
+        let dir = dir
+            .canonicalize()
+            .map_err(|e| format!("cannot watch '{file}': {e}"))?;
+        if !targets
+            .iter()
+            .any(|(target, _)| target.parent() == Some(&dir))
+        {
+            watcher
+                .watch(&dir, RecursiveMode::NonRecursive)
+                .map_err(|e| format!("cannot watch '{file}': {e}"))?;
+        }
+        targets.push((dir.join(name), file));
+    }
+
+    run()?;
+    // Files changed since the last run, waiting for the changes to settle
+    let mut changed: Vec<&str> = Vec::new();
+    loop {
+        let event = if changed.is_empty() {
+            receiver.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            receiver.recv_timeout(DEBOUNCE)
+        };
+        match event {
+            Ok(Event::Interrupted) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            // Reading the files makes access events of its own
+            Ok(Event::Changed(Ok(event))) if matches!(event.kind, EventKind::Access(_)) => {}
+            Ok(Event::Changed(Ok(event))) => {
+                for (target, file) in &targets {
+                    if event.paths.contains(target) && !changed.contains(file) {
+                        changed.push(file);
+                    }
+                }
+            }
+            Ok(Event::Changed(Err(e))) => eprintln!("jpp: error watching files: {e}"),
+            Err(RecvTimeoutError::Timeout) => {
+                let mut stdout = io::stdout().lock();
+                if clear {
+                    write!(stdout, "\x1b[2J\x1b[H")
+                } else {
+                    writeln!(stdout, "==> {} changed <==", changed.join(", "))
+                }
+                .and_then(|()| stdout.flush())
+                .map_err(|e| format!("error writing output: {e}"))?;
+                drop(stdout);
+                changed.clear();
+                run()?;
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "watch"))]
+pub fn watch<E: From<String>>(
+    _: &[String],
+    _: bool,
+    _: impl FnMut() -> Result<(), E>,
+) -> Result<(), E> {
+    Err(
+        "cannot use --watch: jpp was built without the 'watch' feature"
+            .to_string()
+            .into(),
+    )
+}
@@ -0,0 +1,317 @@
+//! YAML output
+//!
+//! YAML 1.2 writers leave strings such as `no`, `on` or `2024-01-01` unquoted, which YAML 1.1
+//! readers still turn into booleans and timestamps. This writer quotes every
+//! string that is not plainly a word, so the output reads back as the same values under
+//! either version.
+//!
+//! Every document starts with a `---` marker, so the output of several documents, inputs or
+//! NDJSON records in a row is still a valid YAML stream.
+
+use crate::ascii::{self, Astral};
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Words that some YAML version reads as a boolean or null, compared case-insensitively
+const RESERVED_WORDS: &[&str] = &["y", "n", "yes", "no", "on", "off", "true", "false", "null"];
+
+/// Write `value` as a block-style YAML document, ending with a newline. With `ascii`, strings
+/// escape their non-ASCII characters.
+pub fn write_document<W: Write>(out: &mut W, value: &Value, ascii: bool) -> io::Result<()> {
+    out.write_all(b"---\n")?;
+    node(out, value, 0, ascii)
+}
+
+/// Write `value` starting at the current column, which is `indent`
+fn node<W: Write>(out: &mut W, value: &Value, indent: usize, ascii: bool) -> io::Result<()> {
+    match value {
+        Value::Array(items) if !items.is_empty() => {
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    write_indent(out, indent)?;
+                }
+                out.write_all(b"- ")?;
+                node(out, item, indent + 2, ascii)?;
+            }
+            Ok(())
+        }
+        Value::Object(map) if !map.is_empty() => mapping(out, map.iter(), indent, ascii),
+        scalar_value => scalar(out, scalar_value, ascii),
+    }
+}
+
+/// Write the members of a non-empty mapping starting at the current column, which is `indent`
+fn mapping<'v, W: Write>(
+    out: &mut W,
+    members: impl Iterator<Item = (&'v String, &'v Value)>,
+    indent: usize,
+    ascii: bool,
+) -> io::Result<()> {
+    for (i, (key, value)) in members.enumerate() {
+        if i > 0 {
+            write_indent(out, indent)?;
+        }
+        string(out, key, ascii)?;
+        if is_block(value) {
+            out.write_all(b":\n")?;
+            write_indent(out, indent + 2)?;
+            node(out, value, indent + 2, ascii)?;
+        } else {
+            out.write_all(b": ")?;
+            scalar(out, value, ascii)?;
+        }
+    }
+    Ok(())
+}
+
+/// Write `entries` as a YAML mapping document, keeping their order
+pub fn write_entries<W: Write>(
+    out: &mut W,
+    entries: &[(String, Value)],
+    ascii: bool,
+) -> io::Result<()> {
+    out.write_all(b"---\n")?;
+    if entries.is_empty() {
+        return out.write_all(b"{}\n");
+    }
+    mapping(
+        out,
+        entries.iter().map(|(key, value)| (key, value)),
+        0,
+        ascii,
+    )
+}
+
+/// Whether `value` is written as an indented block rather than on the line of its key
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Array(items) => !items.is_empty(),
+        Value::Object(map) => !map.is_empty(),
+        _ => false,
+    }
+}
+
+/// Write `items` as a YAML sequence document
+pub fn write_sequence<W: Write>(out: &mut W, items: &[&Value], ascii: bool) -> io::Result<()> {
+    out.write_all(b"---\n")?;
+    if items.is_empty() {
+        return out.write_all(b"[]\n");
+    }
+    for item in items {
+        out.write_all(b"- ")?;
+        node(out, item, 2, ascii)?;
+    }
+    Ok(())
+}
+
+/// Write a scalar or an empty collection in flow style, then a newline
+fn scalar<W: Write>(out: &mut W, value: &Value, ascii: bool) -> io::Result<()> {
+    match value {
+        Value::String(s) => string(out, s, ascii)?,
+        Value::Array(_) => out.write_all(b"[]")?,
+        Value::Object(_) => out.write_all(b"{}")?,
+        other => write!(out, "{other}")?,
+    }
+    out.write_all(b"\n")
+}
+
+/// Write `s` plain when it can only be read back as that string, and double-quoted otherwise.
+/// JSON string escapes are valid in YAML double-quoted scalars, but for surrogate pairs.
+fn string<W: Write>(out: &mut W, s: &str, ascii: bool) -> io::Result<()> {
+    if is_plain_safe(s) {
+        out.write_all(s.as_bytes())
+    } else if ascii {
+        ascii::write_string(out, s, Astral::Long)
+    } else {
+        serde_json::to_writer(&mut *out, s).map_err(io::Error::from)
+    }
+}
+
+/// Whether `s` reads back as itself without quotes: it starts with a letter, so it cannot be a
+/// number or a date, holds no indicator that could start a comment or a mapping, and is not a
+/// reserved word
+fn is_plain_safe(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    let Some(&first) = bytes.first() else {
+        return false;
+    };
+    first.is_ascii_alphabetic()
+        && !s.ends_with(' ')
+        && bytes.iter().enumerate().all(|(i, &b)| match b {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'_' | b'-' | b'.' | b'/' | b'@' | b'+' => {
+                true
+            }
+            // `a b` is fine, but `a #b` starts a comment
+            b' ' => bytes.get(i + 1) != Some(&b'#'),
+            // `image:1.0` is fine, but `a: b` is a mapping
+            b':' => bytes.get(i + 1).is_some_and(|&next| next != b' '),
+            _ => false,
+        })
+        && !RESERVED_WORDS
+            .iter()
+            .any(|word| s.eq_ignore_ascii_case(word))
+}
+
+fn write_indent<W: Write>(out: &mut W, indent: usize) -> io::Result<()> {
+    for _ in 0..indent {
+        out.write_all(b" ")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn render(value: &Value) -> String {
+        render_with(value, false)
+    }
+
+    fn render_with(value: &Value, ascii: bool) -> String {
+        let mut out = Vec::new();
+        write_document(&mut out, value, ascii).unwrap();
+        String::from_utf8(out)
+            .unwrap()
+            .strip_prefix("---\n")
+            .unwrap()
+            .to_string()
+    }
+
+    /// Check that a YAML reader turns `rendered` back into `value`
+    #[cfg(feature = "yaml")]
+    fn assert_reads_back(rendered: &str, value: &Value) {
+        let back = crate::input::parse_yaml_documents(rendered, None).unwrap();
+        assert_eq!(back, std::slice::from_ref(value), "{rendered:?}");
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    fn assert_reads_back(_: &str, _: &Value) {}
+
+    #[test]
+    fn test_layout() {
+        let value = json!({
+            "name": "web",
+            "ports": [80, 443],
+            "spec": {"containers": [{"image": "nginx:1.27", "args": []}], "meta": {}},
+            "matrix": [[1, 2], []],
+            "none": null,
+            "ratio": 1.0
+        });
+        assert_eq!(
+            render(&value),
+            concat!(
+                "matrix:\n",
+                "  - - 1\n",
+                "    - 2\n",
+                "  - []\n",
+                "name: web\n",
+                "none: null\n",
+                "ports:\n",
+                "  - 80\n",
+                "  - 443\n",
+                "ratio: 1.0\n",
+                "spec:\n",
+                "  containers:\n",
+                "    - args: []\n",
+                "      image: nginx:1.27\n",
+                "  meta: {}\n",
+            )
+        );
+        assert_eq!(render(&json!("x")), "x\n");
+        assert_eq!(render(&json!([])), "[]\n");
+
+        let items = [&value["ports"], &value["none"]];
+        let mut out = Vec::new();
+        write_sequence(&mut out, &items, false).unwrap();
+        assert_eq!(out, b"---\n- - 80\n  - 443\n- null\n");
+        let mut out = Vec::new();
+        write_sequence(&mut out, &[], false).unwrap();
+        assert_eq!(out, b"---\n[]\n");
+    }
+
+    #[test]
+    fn test_ascii() {
+        let value = json!({"名前": ["東京 🗼", "plain"]});
+        let rendered = render_with(&value, true);
+        assert_eq!(
+            rendered,
+            "\"\\u540d\\u524d\":\n  - \"\\u6771\\u4eac \\U0001f5fc\"\n  - plain\n"
+        );
+        assert_reads_back(&rendered, &value);
+    }
+
+    #[test]
+    fn test_quoting() {
+        for s in [
+            "no",
+            "No",
+            "NO",
+            "yes",
+            "on",
+            "off",
+            "y",
+            "n",
+            "true",
+            "False",
+            "null",
+            "~",
+            "1.0",
+            "007",
+            "0x1F",
+            "1e3",
+            ".inf",
+            "+1",
+            "-1",
+            "2024-01-01",
+            "12:30",
+            "",
+            " a",
+            "a ",
+            "a: b",
+            "a:",
+            "a #b",
+            "- x",
+            "#c",
+            "&a",
+            "*a",
+            "!t",
+            "[x]",
+            "{x}",
+            "'q'",
+            "\"q\"",
+            "multi\nline",
+            "tab\there",
+            "%x",
+            "@x",
+            "`x`",
+            "é",
+        ] {
+            let rendered = render(&json!(s));
+            assert!(rendered.starts_with('"'), "{s:?} rendered as {rendered:?}");
+            assert_reads_back(&rendered, &json!(s));
+        }
+        for s in [
+            "web",
+            "nginx:1.27",
+            "a b",
+            "user@example.com",
+            "v1.2.3",
+            "no-op",
+        ] {
+            assert_eq!(render(&json!(s)), format!("{s}\n"));
+        }
+        assert_eq!(render(&json!({"on": "off"})), "\"on\": \"off\"\n");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let value = json!({
+            "a": [1, -2.5, true, null, "no", {"b": ["", "x\ny"]}],
+            "yes": {"nested": [[], {}]},
+            "unicode": "日本"
+        });
+        assert_reads_back(&render(&value), &value);
+    }
+}
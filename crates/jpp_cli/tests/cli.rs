@@ -0,0 +1,3164 @@
+//! Runs the `jpp` binary and checks its exact output
+
+#![allow(clippy::unwrap_used)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const FIXTURE: &str = "store.json";
+
+/// Run `jpp` with `args` in the fixtures directory, feeding `stdin` to it
+fn jpp(args: &[&str], stdin: impl AsRef<[u8]>) -> Output {
+    jpp_env(&[], args, stdin)
+}
+
+/// [`jpp`] with the environment variables `vars` set
+fn jpp_env(vars: &[(&str, &str)], args: &[&str], stdin: impl AsRef<[u8]>) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jpp"))
+        .args(args)
+        .env_remove("COLUMNS")
+        .envs(vars.iter().copied())
+        .current_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The process may exit without reading its input
+    if let Err(e) = child.stdin.take().unwrap().write_all(stdin.as_ref()) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe);
+    }
+    child.wait_with_output().unwrap()
+}
+
+/// Stdout of a successful run on the fixture file
+fn stdout(args: &[&str]) -> String {
+    let mut args = args.to_vec();
+    args.push(FIXTURE);
+    let output = jpp(&args, "");
+    assert!(output.status.success(), "{args:?}: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn test_pretty_output_by_default() {
+    assert_eq!(
+        stdout(&["$.store.book[?@.price < 10].tags"]),
+        "[\n  [\n    \"old\"\n  ],\n  [\n    \"sea\",\n    \"whale\"\n  ]\n]\n"
+    );
+    assert_eq!(stdout(&["$.missing"]), "[]\n");
+}
+
+#[test]
+fn test_compact_output() {
+    for flag in ["-c", "--compact"] {
+        assert_eq!(
+            stdout(&[flag, "$.store.book[?@.price < 10].tags"]),
+            "[[\"old\"],[\"sea\",\"whale\"]]\n"
+        );
+    }
+    assert_eq!(
+        stdout(&["$.store.book[1]", "-c"]),
+        "[{\"price\":12.99,\"tags\":[],\"title\":\"Sword\\nof Honour\"}]\n"
+    );
+}
+
+#[test]
+fn test_raw_output() {
+    assert_eq!(
+        stdout(&["-r", "$.store.book[*].title"]),
+        "Sayings\nSword\nof Honour\nMoby Dick\n"
+    );
+    // Non-strings are compact JSON, with or without -c
+    assert_eq!(stdout(&["-r", "$.store.book[*].price"]), "8.95\n12.99\n8\n");
+    assert_eq!(
+        stdout(&["-r", "$.store.book[2].tags"]),
+        "[\"sea\",\"whale\"]\n"
+    );
+    assert_eq!(
+        stdout(&["-c", "--raw-output", "$.store['name', 'book'][0]"]),
+        "{\"price\":8.95,\"tags\":[\"old\"],\"title\":\"Sayings\"}\n"
+    );
+}
+
+#[test]
+fn test_reads_stdin() {
+    let output = jpp(&["-c", "$.a[*]"], r#"{"a": [1, "x"]}"#);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"[1,\"x\"]\n");
+}
+
+#[test]
+fn test_errors_go_to_stderr() {
+    let output = jpp(&["$["], "{}");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error parsing JSONPath query")
+    );
+}
+
+#[test]
+fn test_ndjson_output() {
+    for args in [
+        &["--output", "ndjson"][..],
+        &["--output=ndjson"],
+        &["-l"],
+        &["--lines", "-c"],
+    ] {
+        let mut args = args.to_vec();
+        args.push("$.store.book[*]['title', 'tags']");
+        assert_eq!(
+            stdout(&args),
+            concat!(
+                "\"Sayings\"\n",
+                "[\"old\"]\n",
+                "\"Sword\\nof Honour\"\n",
+                "[]\n",
+                "\"Moby Dick\"\n",
+                "[\"sea\",\"whale\"]\n",
+            ),
+            "{args:?}"
+        );
+    }
+    // Embedded newlines stay escaped, so there is exactly one line per match
+    let output = stdout(&["-l", "$..title"]);
+    assert_eq!(output.lines().count(), 3);
+    assert_eq!(stdout(&["-l", "$.missing"]), "");
+}
+
+#[test]
+fn test_invalid_output_format() {
+    for args in [&["--output", "xml", "$"][..], &["$", "--output"]] {
+        let output = jpp(args, "{}");
+        assert!(!output.status.success());
+        assert!(
+            String::from_utf8(output.stderr)
+                .unwrap()
+                .contains("--output <FORMAT>")
+        );
+    }
+}
+
+#[test]
+fn test_yaml_output() {
+    assert_eq!(
+        stdout(&["--output", "yaml", "$.store.book[0]"]),
+        concat!(
+            "---\n",
+            "- price: 8.95\n",
+            "  tags:\n",
+            "    - old\n",
+            "  title: Sayings\n",
+        )
+    );
+    assert_eq!(
+        stdout(&["--output=yaml-docs", "$.store.book[1:].tags"]),
+        "---\n[]\n---\n- sea\n- whale\n"
+    );
+    assert_eq!(stdout(&["--output", "yaml", "$.missing"]), "---\n[]\n");
+    // Every document is marked, so several in a row stay a valid stream
+    #[cfg(feature = "yaml")]
+    assert_eq!(
+        jpp(&["--output", "yaml", "$..image", "manifest.yaml"], "").stdout,
+        b"---\n- web:1.4\n- envoy:1.30\n- web:1.4\n---\n- busybox:1.36\n"
+    );
+    assert_eq!(stdout(&["--output", "yaml-docs", "$.missing"]), "");
+
+    // Strings that YAML readers would take for booleans, numbers or dates are quoted
+    let output = jpp(
+        &["--output", "yaml", "$.*"],
+        r#"{"a": "no", "b": "1.0", "c": "007", "d": "2024-01-01", "e": "On", "f": 1.0}"#,
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "---\n- \"no\"\n- \"1.0\"\n- \"007\"\n- \"2024-01-01\"\n- \"On\"\n- 1.0\n"
+    );
+
+    // Labeled results are one mapping, or one document per query
+    assert_eq!(
+        stdout(&[
+            "--output",
+            "yaml-docs",
+            "--query",
+            "$.store.name",
+            "--label",
+            "yes",
+            "--query",
+            "$.store.book[0].tags",
+        ]),
+        concat!(
+            "---\n",
+            "\"yes\":\n",
+            "  - \"Corner \\\"Books\\\"\"\n",
+            "---\n",
+            "\"$.store.book[0].tags\":\n",
+            "  - - old\n",
+        )
+    );
+    let output = jpp(
+        &["--output", "yaml", "-S", "$"],
+        r#"{"z": {"b": 1, "a": [true, null]}}"#,
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "---\n- z:\n    a:\n      - true\n      - null\n    b: 1\n"
+    );
+}
+
+#[test]
+fn test_table_output() {
+    let run = |args: &[&str]| {
+        let output = jpp(&[args, &["cities.json"]].concat(), "");
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    // A column per key, aligned by display width; São Paulo has no population or note
+    assert_eq!(
+        run(&["--output", "table", "$[*]"]),
+        "country  name       population  note\n\
+         -------  ---------  ----------  ------------------------------------------\n\
+         日本     東京       37400068\n\
+         India    Delhi      28514000    capital territory, National Capital Region\n\
+         Brasil   São Paulo\n"
+    );
+    assert_eq!(
+        run(&["--output", "markdown", "--max-col-width", "20", "$[*]"]),
+        "| country | name      | population | note                 |\n\
+         | ------- | --------- | ---------- | -------------------- |\n\
+         | 日本    | 東京      | 37400068   |                      |\n\
+         | India   | Delhi     | 28514000   | capital territory, … |\n\
+         | Brasil  | São Paulo |            |                      |\n"
+    );
+    assert_eq!(
+        run(&["--output", "table", "$[*].population"]),
+        "value\n--------\n37400068\n28514000\n"
+    );
+
+    let output = jpp(&["--max-col-width", "20", "$", "cities.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        output.stderr,
+        b"jpp: --max-col-width needs --output table or --output markdown\n"
+    );
+}
+
+#[test]
+fn test_ascii_output() {
+    let run = |args: &[&str]| {
+        let output = jpp(args, r#"{"city": "東京", "emoji": "🗼 ok", "tags": ["é"]}"#);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let compact = run(&["-a", "-c", "$"]);
+    assert_eq!(
+        compact,
+        "[{\"city\":\"\\u6771\\u4eac\",\"emoji\":\"\\ud83d\\uddfc ok\",\"tags\":[\"\\u00e9\"]}]\n"
+    );
+    // The escapes read back as the same strings
+    let back: serde_json::Value = serde_json::from_str(&compact).unwrap();
+    assert_eq!(back[0]["emoji"], "🗼 ok");
+    assert_eq!(
+        run(&["--ascii-output", "$.city"]),
+        "[\n  \"\\u6771\\u4eac\"\n]\n"
+    );
+    // -r still escapes, though it writes no quotes
+    assert_eq!(
+        run(&["-a", "-r", "$..*"]),
+        "\\u6771\\u4eac\n\\ud83d\\uddfc ok\n[\"\\u00e9\"]\n\\u00e9\n"
+    );
+    assert_eq!(
+        run(&["-a", "--output", "ndjson", "--color", "always", "$.tags"]),
+        "[\x1b[32m\"\\u00e9\"\x1b[0m]\n"
+    );
+    // YAML has no surrogate pairs, so characters above U+FFFF take eight digits
+    assert_eq!(
+        run(&["-a", "--output", "yaml", "$.emoji"]),
+        "---\n- \"\\U0001f5fc ok\"\n"
+    );
+    assert_eq!(
+        run(&["-a", "--output", "table", "$.city"]),
+        "value\n------------\n\\u6771\\u4eac\n"
+    );
+}
+
+#[test]
+fn test_preview() {
+    let run = |args: &[&str]| {
+        let output = jpp(&[args, &["deep.json"]].concat(), "");
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // Each result keeps its top two levels; the counts are those of the hidden content
+    assert_eq!(
+        run(&["--max-output-depth", "2", "--truncate-strings", "20", "$"]),
+        r#"[
+  {
+    "config": {
+      "features": "[… 4 items]",
+      "retry": "{… 2 keys}"
+    },
+    "description": "Handles carts, payme… (71 chars)",
+    "replicas": [
+      "{… 3 keys}",
+      "{… 3 keys}",
+      "{… 3 keys}"
+    ],
+    "service": "checkout"
+  }
+]
+"#
+    );
+    assert_eq!(
+        run(&["--max-output-depth", "1", "-l", "$.replicas[*]"]),
+        concat!(
+            r#"{"hosts":"[… 3 items]","labels":"{… 2 keys}","zone":"eu-west-1a"}"#,
+            "\n",
+            r#"{"hosts":"[… 1 item]","labels":{},"zone":"eu-west-1b"}"#,
+            "\n",
+            r#"{"hosts":[],"labels":"{… 1 key}","zone":"eu-west-1c"}"#,
+            "\n"
+        )
+    );
+    assert_eq!(
+        run(&["--max-output-depth", "0", "-r", "$.config.retry.backoff.*"]),
+        "0.5\n[… 2 items]\n30\n"
+    );
+    // Filters still see the whole document
+    assert_eq!(
+        run(&[
+            "--truncate-strings",
+            "4",
+            "-r",
+            "$.config.features[?length(@) > 10]"
+        ]),
+        "spli… (14 chars)\nrece… (17 chars)\n"
+    );
+    // Without the options the output is unchanged
+    assert_eq!(
+        run(&["-c", "$.config.retry"]),
+        "[{\"attempts\":3,\"backoff\":{\"initial\":0.5,\"jitter\":[0.1,0.2],\"max\":30}}]\n"
+    );
+
+    let output = jpp(&["--truncate-strings", "0", "$", "deep.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: invalid value '0' for '--truncate-strings <N>' (expected a positive integer)\n"
+    );
+    let output = jpp(
+        &[
+            "--max-output-depth",
+            "1",
+            "--format",
+            "{@}",
+            "$",
+            "deep.json",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --max-output-depth and --truncate-strings shorten JSON results for reading and cannot be used with --in-place or --format\n"
+    );
+}
+
+#[test]
+fn test_color() {
+    // Pipes are never colored under the default `auto`
+    assert!(!stdout(&["$..price"]).contains('\x1b'));
+    assert!(!stdout(&["--color", "never", "$..price"]).contains('\x1b'));
+
+    assert_eq!(
+        stdout(&[
+            "--color",
+            "always",
+            "-c",
+            "$.store.book[2]['price', 'tags']"
+        ]),
+        "[\x1b[36m8\x1b[0m,[\x1b[32m\"sea\"\x1b[0m,\x1b[32m\"whale\"\x1b[0m]]\n"
+    );
+    assert_eq!(
+        stdout(&["--color=always", "$.store.book[0].tags"]),
+        "[\n  [\n    \x1b[32m\"old\"\x1b[0m\n  ]\n]\n"
+    );
+    assert_eq!(
+        stdout(&["--color=always", "-l", "$.store.book[?@.price == 8]"]),
+        concat!(
+            "{\x1b[1;34m\"price\"\x1b[0m:\x1b[36m8\x1b[0m,",
+            "\x1b[1;34m\"tags\"\x1b[0m:[\x1b[32m\"sea\"\x1b[0m,\x1b[32m\"whale\"\x1b[0m],",
+            "\x1b[1;34m\"title\"\x1b[0m:\x1b[32m\"Moby Dick\"\x1b[0m}\n"
+        )
+    );
+    // Raw strings stay uncolored
+    assert_eq!(
+        stdout(&["--color=always", "-r", "$.store.book[0]['title', 'price']"]),
+        "Sayings\n\x1b[36m8.95\x1b[0m\n"
+    );
+}
+
+#[test]
+fn test_sort_keys() {
+    let permutations = [
+        r#"{"b": {"y": 1, "x": [{"d": 0, "c": {"f": 1, "e": 2}}]}, "a": null}"#,
+        r#"{"a": null, "b": {"x": [{"c": {"e": 2, "f": 1}, "d": 0}], "y": 1}}"#,
+    ];
+    for (mode, expected) in [
+        (
+            &["-S"][..],
+            concat!(
+                "[\n  {\n    \"a\": null,\n    \"b\": {\n      \"x\": [\n        {\n",
+                "          \"c\": {\n            \"e\": 2,\n            \"f\": 1\n          },\n",
+                "          \"d\": 0\n        }\n      ],\n      \"y\": 1\n    }\n  }\n]\n"
+            ),
+        ),
+        (
+            &["--sort-keys", "-c"],
+            "[{\"a\":null,\"b\":{\"x\":[{\"c\":{\"e\":2,\"f\":1},\"d\":0}],\"y\":1}}]\n",
+        ),
+        (
+            &["-S", "-l"],
+            "{\"a\":null,\"b\":{\"x\":[{\"c\":{\"e\":2,\"f\":1},\"d\":0}],\"y\":1}}\n",
+        ),
+    ] {
+        for input in permutations {
+            let mut args = mode.to_vec();
+            args.push("$");
+            let output = jpp(&args, input);
+            assert!(output.status.success());
+            assert_eq!(
+                String::from_utf8(output.stdout).unwrap(),
+                expected,
+                "{args:?}"
+            );
+        }
+    }
+}
+
+#[test]
+fn test_multiple_files() {
+    let files = ["a.json", "malformed.json", "b.json"];
+    let run = |options: &[&str], query: &str| {
+        let mut args = options.to_vec();
+        args.push(query);
+        args.extend(files);
+        let output = jpp(&args, "");
+        assert!(!output.status.success(), "{args:?}");
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    let (stdout, stderr) = run(&["-c"], "$.version");
+    assert_eq!(stdout, "a.json:[\"1.0.0\"]\nb.json:[\"2.1.0\"]\n");
+    assert!(stderr.starts_with("jpp: error parsing JSON in 'malformed.json': "));
+    assert_eq!(stderr.lines().count(), 1);
+
+    let (stdout, _) = run(&[], "$.tags");
+    assert_eq!(
+        stdout,
+        "a.json:[]\nb.json:[\nb.json:  [\nb.json:    \"x\",\nb.json:    \"y\"\nb.json:  ]\nb.json:]\n"
+    );
+
+    let (stdout, _) = run(&["-r", "--no-filename"], "$.name");
+    assert_eq!(stdout, "a\nb\n");
+
+    let output = jpp(&["-r", "$.name", "missing.json", "a.json"], "");
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, b"a.json:a\n");
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error reading file 'missing.json': ")
+    );
+}
+
+#[test]
+fn test_with_filename() {
+    let output = jpp(&["-l", "--with-filename", "$.name", "a.json"], "");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"a.json:\"a\"\n");
+
+    let output = jpp(&["--with-filename", "-c", "$.name"], r#"{"name": 1}"#);
+    assert_eq!(output.stdout, b"(standard input):[1]\n");
+}
+
+#[test]
+fn test_null_input() {
+    // stdin is never read, so input that is not JSON makes no difference
+    for args in [&["-n", "-c", "$"][..], &["--null-input", "-c", "$"]] {
+        let output = jpp(args, "not json");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, b"[null]\n");
+    }
+    assert_eq!(jpp(&["-n", "-c", "$.a"], "").stdout, b"[]\n");
+
+    let output = jpp(&["-n", "$", "a.json"], "");
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        output.stderr,
+        b"jpp: --null-input cannot be used with FILE arguments\n"
+    );
+}
+
+#[test]
+fn test_exit_status() {
+    // (arguments after the mode flags, stdin, status without -e, status with -e)
+    let cases: [(&[&str], &str, i32, i32); 7] = [
+        (&["$.a"], r#"{"a": 1}"#, 0, 0),
+        (&["$.a"], r#"{"a": null}"#, 0, 0),
+        (&["$.b"], r#"{"a": 1}"#, 0, 1),
+        (&["$.a"], "{", 2, 2),
+        (&["$["], "{}", 2, 2),
+        (&["$", "missing.json"], "", 2, 2),
+        (&["--bogus", "$"], "{}", 2, 2),
+    ];
+    for (args, stdin, plain, with_e) in cases {
+        assert_eq!(
+            jpp(args, stdin).status.code(),
+            Some(plain),
+            "{args:?} {stdin}"
+        );
+        for flag in ["-e", "--exit-status"] {
+            let mut flagged = vec![flag];
+            flagged.extend(args);
+            assert_eq!(
+                jpp(&flagged, stdin).status.code(),
+                Some(with_e),
+                "{flagged:?} {stdin}"
+            );
+        }
+    }
+
+    // Any match across several files counts, and a failed file is still an error
+    assert_eq!(
+        jpp(&["-e", "$.tags", "a.json", "b.json"], "").status.code(),
+        Some(0)
+    );
+    assert_eq!(
+        jpp(&["-e", "$.nope", "a.json", "b.json"], "").status.code(),
+        Some(1)
+    );
+    assert_eq!(
+        jpp(&["-e", "$.tags", "b.json", "malformed.json"], "")
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_limit() {
+    assert_eq!(
+        stdout(&["--limit", "2", "-c", "$..title"]),
+        "[\"Sayings\",\"Sword\\nof Honour\"]\n"
+    );
+    assert_eq!(stdout(&["--limit=0", "-c", "$..title"]), "[]\n");
+    assert_eq!(
+        stdout(&["--limit", "10", "-r", "$..price"]),
+        "8.95\n12.99\n8\n"
+    );
+    for args in [
+        &["--first", "-l", "$..*"][..],
+        &["--limit", "1", "-l", "$..*"],
+    ] {
+        assert_eq!(
+            stdout(args),
+            "{\"book\":[{\"price\":8.95,\"tags\":[\"old\"],\"title\":\"Sayings\"},{\"price\":12.99,\"tags\":[],\"title\":\"Sword\\nof Honour\"},{\"price\":8,\"tags\":[\"sea\",\"whale\"],\"title\":\"Moby Dick\"}],\"name\":\"Corner \\\"Books\\\"\"}\n"
+        );
+    }
+
+    // A limited but non-empty result still counts as a match
+    assert_eq!(
+        jpp(&["-e", "--first", "$..tags", "store.json"], "")
+            .status
+            .code(),
+        Some(0)
+    );
+    assert_eq!(
+        jpp(&["-e", "--limit", "0", "$..tags", "store.json"], "")
+            .status
+            .code(),
+        Some(1)
+    );
+
+    // The limit applies to each input
+    assert_eq!(
+        jpp(&["--first", "-r", "$.*", "a.json", "b.json"], "").stdout,
+        b"a.json:a\nb.json:b\n"
+    );
+
+    let output = jpp(&["--limit", "-1", "$"], "{}");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("--limit <N>")
+    );
+}
+
+#[test]
+fn test_raw_input() {
+    const TEXT: &str = "say \"hi\"\r\nC:\\tmp\\new\n\nERROR: disk full\n";
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // Quotes and backslashes are escaped into the strings; a blank line is an empty string
+    assert_eq!(
+        run(&["-R", "$"], TEXT),
+        "\"say \\\"hi\\\"\"\n\"C:\\\\tmp\\\\new\"\n\"\"\n\"ERROR: disk full\"\n"
+    );
+    assert_eq!(
+        run(
+            &["--raw-input", "--slurp", "-c", "--output", "json", "$"],
+            TEXT
+        ),
+        "[[\"say \\\"hi\\\"\",\"C:\\\\tmp\\\\new\",\"\",\"ERROR: disk full\"]]\n"
+    );
+    assert_eq!(
+        run(&["-R", "-s", "-r", "$[?search(@, \"ERROR|hi\")]"], TEXT),
+        "say \"hi\"\nERROR: disk full\n"
+    );
+    assert_eq!(
+        run(
+            &["--input-format=raw", "--with-line-numbers", "-r", "$"],
+            "a\nx\n"
+        ),
+        "1:a\n2:x\n"
+    );
+
+    // An invalid line is reported and skipped, or fails the whole input with --slurp
+    let output = jpp(&["-R", "$"], b"ok\n\xff\nend\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "\"ok\"\n\"end\"\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: invalid UTF-8 on line 2: invalid utf-8 sequence of 1 bytes from index 0\n"
+    );
+    let output = jpp(&["-R", "-s", "$"], b"ok\n\xff\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_multi() {
+    const TWO: &str = "{\"kind\": \"Pod\", \"n\": 1}{\"kind\": \"Service\", \"n\": 2}\n";
+    let output = jpp(&["-c", "$.kind"], TWO);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: error parsing JSON: trailing characters at line 1 column 24; use --multi to read several concatenated documents\n"
+    );
+
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        run(&["--multi", "-c", "$.kind"], TWO),
+        "[\"Pod\"]\n[\"Service\"]\n"
+    );
+    assert_eq!(
+        run(&["--multi", "-l", "$"], "1 2\n\n[3]\"4\""),
+        "1\n2\n[3]\n\"4\"\n"
+    );
+    assert_eq!(run(&["--multi", "--slurp", "-c", "$[*].n"], TWO), "[1,2]\n");
+    assert_eq!(run(&["--multi", "--count", "$.n"], TWO), "2\n");
+
+    // Garbage between documents ends the input after the documents before it
+    let output = jpp(&["--multi", "-c", "$.n"], "{\"n\": 1}\n} {\"n\": 2}");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[1]\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: error parsing JSON in the document after byte 8: expected value at line 2 column 1\n"
+    );
+
+    let output = jpp(&["--multi", "--stream", "$"], "[]");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_json_seq() {
+    let output = jpp(&["--output", "json-seq", "$.store.book[*]", FIXTURE], "");
+    assert!(output.status.success(), "{output:?}");
+    let records = output.stdout;
+    assert!(records.starts_with(b"\x1e{\"price\":8.95,"));
+    assert_eq!(records.iter().filter(|&&b| b == 0x1e).count(), 3);
+    assert!(records.ends_with(b"}\n"));
+
+    // Round trip: the records read back as the same documents, written as records again
+    let output = jpp(&["--input-format", "json-seq", "$.price"], &records);
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"\x1e8.95\n\x1e12.99\n\x1e8\n");
+    let output = jpp(
+        &[
+            "--input-format=json-seq",
+            "--slurp",
+            "-c",
+            "--output",
+            "json",
+            "$[*].title",
+        ],
+        &records,
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[\"Sayings\",\"Sword\\nof Honour\",\"Moby Dick\"]\n"
+    );
+    let output = jpp(&["--input-format", "json-seq", "$"], &records);
+    assert_eq!(output.stdout, records);
+
+    // Invalid and possibly truncated records are skipped with a warning, and the run succeeds
+    let output = jpp(
+        &["--input-format", "json-seq", "-l", "$"],
+        b"\x1e{\"a\":1}\n\x1e{\"a\":\x1e42\x1e\x1e\"ok\"\n",
+    );
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "{\"a\":1}\n\"ok\"\n"
+    );
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: warning: skipped 2 invalid JSON text sequence records in '(standard input)'\n"
+    );
+}
+
+#[test]
+fn test_join_and_null_output() {
+    const DOC: &str = r#"["a\nb", 1, {"x": "y"}, ""]"#;
+    let bytes = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        output.stdout
+    };
+    assert_eq!(
+        bytes(&["-0", "$[*]"], DOC),
+        b"a\nb\x001\x00{\"x\":\"y\"}\x00\x00"
+    );
+    assert_eq!(
+        bytes(&["--null-output", "-r", "$[*]"], DOC),
+        b"a\nb\x001\x00{\"x\":\"y\"}\x00\x00"
+    );
+    assert_eq!(
+        bytes(&["--join", ", ", "$[*]"], DOC),
+        b"a\nb, 1, {\"x\":\"y\"}, "
+    );
+    assert_eq!(bytes(&["--join=|", "$[0]"], DOC), b"a\nb");
+    assert_eq!(bytes(&["--join", "|", "$[9]"], DOC), b"");
+    // The separator goes between the results of every document, never after the last
+    assert_eq!(
+        bytes(&["--join", ",", "--ndjson", "$[*]"], "[1, 2]\n[]\n[3]\n"),
+        b"1,2,3"
+    );
+
+    for args in [
+        &["--join", ",", "-0", "$"][..],
+        &["-0", "--join", ",", "$"],
+        &["-0", "--count", "$"],
+        &["--join", ",", "--paths", "$"],
+        &["-0", "--query", "$", "--query", "$.a"],
+    ] {
+        let output = jpp(args, "{}");
+        assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+    }
+}
+
+#[test]
+fn test_format() {
+    // One line per result; strings print their contents and other values compact JSON
+    assert_eq!(
+        stdout(&[
+            "--format",
+            "{@.title}: {@.price} {{{@.tags}}} first={@.tags[0]}",
+            "$.store.book[*]"
+        ]),
+        "Sayings: 8.95 {[\"old\"]} first=old\n\
+         Sword\nof Honour: 12.99 {[]} first=\n\
+         Moby Dick: 8 {[\"sea\",\"whale\"]} first=sea\n"
+    );
+    // Placeholders hold any relative query, with braces inside its strings
+    assert_eq!(
+        stdout(&[
+            "--format",
+            "{@.book[?@.price < 9].title} {@['na}me']} {@}",
+            "--format-missing",
+            "-",
+            "$.store"
+        ]),
+        "[\"Sayings\",\"Moby Dick\"] - {\"book\":[{\"price\":8.95,\"tags\":[\"old\"],\"title\":\"Sayings\"},{\"price\":12.99,\"tags\":[],\"title\":\"Sword\\nof Honour\"},{\"price\":8,\"tags\":[\"sea\",\"whale\"],\"title\":\"Moby Dick\"}],\"name\":\"Corner \\\"Books\\\"\"}\n"
+    );
+    assert_eq!(
+        stdout(&[
+            "--format",
+            "{@.isbn}|{@.title}",
+            "--format-missing",
+            "n/a",
+            "$.store.book[0]"
+        ]),
+        "n/a|Sayings\n"
+    );
+    let output = jpp(
+        &["-a", "--format", "{@.city} 🗼", "$"],
+        r#"{"city": "東京"}"#,
+    );
+    assert_eq!(output.stdout, b"\\u6771\\u4eac \\ud83d\\uddfc\n");
+
+    for (args, message) in [
+        (
+            &["--format", "{@.title", "$"][..],
+            "jpp: unclosed '{' in --format at column 1; write '{{' for a literal brace\n",
+        ),
+        (
+            &["--format", "a}", "$"],
+            "jpp: unmatched '}' in --format at column 2; write '}}' for a literal brace\n",
+        ),
+        (
+            &["--format", "{$.title}", "$"],
+            "jpp: invalid placeholder '{$.title}' in --format: expected a relative query starting with '@', such as {@.name}\n",
+        ),
+        (
+            &["--format-missing", "-", "$"],
+            "jpp: --format-missing needs --format\n",
+        ),
+        (
+            &["--format", "{@}", "--count", "$"],
+            "jpp: --format writes a line for each result and cannot be used with -r, --null-output, --join, --output, --count, --total, --paths, --pointer, --entries, --delete, --set or several queries\n",
+        ),
+    ] {
+        let output = jpp(&[args, &[FIXTURE]].concat(), "");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert_eq!(String::from_utf8(output.stderr).unwrap(), message);
+    }
+}
+
+#[test]
+fn test_sort() {
+    assert_eq!(stdout(&["--sort", "-r", "$..price"]), "8\n8.95\n12.99\n");
+    // Sorted first, then limited
+    assert_eq!(stdout(&["--sort", "--first", "-r", "$..price"]), "8\n");
+    assert_eq!(
+        stdout(&["--sort-by", "@.price", "-r", "$..book[*].title"]),
+        "Sayings\nSword\nof Honour\nMoby Dick\n"
+    );
+    assert_eq!(
+        stdout(&[
+            "--sort-by",
+            "@.price",
+            "--limit",
+            "2",
+            "--paths",
+            "$..book[*]"
+        ]),
+        "$['store']['book'][2]\n$['store']['book'][0]\n"
+    );
+
+    // Mixed types in the documented order; results without a key sort last, in input order
+    let sorted = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        sorted(
+            &["--sort", "-c", "$[*]"],
+            r#"[{"a": 1}, "b", [1], 2, true, null, "a", -1, false, [0, 5], {}]"#
+        ),
+        "[null,false,true,-1,2,\"a\",\"b\",[0,5],[1],{},{\"a\":1}]\n"
+    );
+    assert_eq!(
+        sorted(
+            &["--sort-by", "@.n", "-c", "$[*]"],
+            r#"[{"id": 1}, {"id": 2, "n": 3}, {"id": 3, "n": "x"}, {"id": 4}, {"id": 5, "n": 1}]"#
+        ),
+        r#"[{"id":5,"n":1},{"id":2,"n":3},{"id":3,"n":"x"},{"id":1},{"id":4}]"#.to_string() + "\n"
+    );
+
+    let output = jpp(&["--sort", "--count", "$..price", FIXTURE], "");
+    assert_eq!(output.status.code(), Some(2));
+    let output = jpp(&["--sort-by", "price", "$..book[*]", FIXTURE], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn test_count() {
+    assert_eq!(stdout(&["--count", "$..title"]), "3\n");
+    assert_eq!(stdout(&["--count", "$..nope"]), "0\n");
+    // Format options don't affect the count
+    assert_eq!(
+        stdout(&["--count", "-c", "-r", "$.store.book[0, 0]"]),
+        "2\n"
+    );
+    assert_eq!(stdout(&["--count", "--limit", "2", "$..title"]), "2\n");
+
+    let output = jpp(&["--count", "$.tags[*]", "a.json", "b.json"], "");
+    assert_eq!(output.stdout, b"a.json:0\nb.json:2\n");
+    let output = jpp(&["--total", "$.*", "a.json", "b.json"], "");
+    assert_eq!(output.stdout, b"5\n");
+    let output = jpp(
+        &[
+            "--count",
+            "--total",
+            "$.*",
+            "a.json",
+            "malformed.json",
+            "b.json",
+        ],
+        "",
+    );
+    assert_eq!(output.stdout, b"5\n");
+    assert_eq!(output.status.code(), Some(2));
+
+    for (query, status) in [("$..title", 0), ("$..nope", 1)] {
+        let output = jpp(&["-e", "--count", query, "store.json"], "");
+        assert_eq!(output.status.code(), Some(status), "{query}");
+    }
+}
+
+#[test]
+fn test_paths() {
+    assert_eq!(
+        stdout(&["--paths", "$..tags[*]"]),
+        "$['store']['book'][0]['tags'][0]\n$['store']['book'][2]['tags'][0]\n$['store']['book'][2]['tags'][1]\n"
+    );
+    assert_eq!(stdout(&["--paths", "$"]), "$\n");
+    assert_eq!(
+        stdout(&["--paths", "--first", "$..price"]),
+        "$['store']['book'][0]['price']\n"
+    );
+    assert_eq!(stdout(&["--paths", "$.nope"]), "");
+
+    // Odd member names are escaped so each path is itself a valid singular query
+    let document = r#"{"it's": {"a\\b": [{"\u0001\"\n": true}]}}"#;
+    let output = jpp(&["--paths", "$..*"], document);
+    let printed = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(
+        printed,
+        concat!(
+            "$['it\\'s']\n",
+            "$['it\\'s']['a\\\\b']\n",
+            "$['it\\'s']['a\\\\b'][0]\n",
+            "$['it\\'s']['a\\\\b'][0]['\\u0001\"\\n']\n",
+        )
+    );
+    for location in printed.lines() {
+        let output = jpp(&["--paths", location], document);
+        assert!(output.status.success(), "{location}");
+        assert_eq!(output.stdout, format!("{location}\n").as_bytes());
+    }
+
+    let output = jpp(&["--paths", "$.tags[1]", "a.json", "b.json"], "");
+    assert_eq!(output.stdout, b"b.json:$['tags'][1]\n");
+}
+
+#[test]
+fn test_recursive() {
+    let run = |args: &[&str]| {
+        let output = jpp(args, "");
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    const BROKEN: &str = "jpp: error parsing JSON in 'tree/api/broken.json': EOF while parsing a value at line 2 column 0\n";
+
+    // Every *.json file under the directory, in name order, prefixed with its name; the
+    // invalid one is reported and skipped
+    assert_eq!(
+        run(&["--recursive", "-r", "$..deprecated", "tree"]),
+        (
+            Some(2),
+            "tree/api/v1/users.json:true\ntree/ui/theme.json:use accent\n".to_string(),
+            BROKEN.to_string()
+        )
+    );
+    assert_eq!(
+        run(&[
+            "--recursive",
+            "--files-with-matches",
+            "$..deprecated",
+            "tree"
+        ]),
+        (
+            Some(2),
+            "tree/api/v1/users.json\ntree/ui/theme.json\n".to_string(),
+            BROKEN.to_string()
+        )
+    );
+    assert_eq!(
+        run(&["-d", "-m", "$..deprecated", "tree"]),
+        run(&[
+            "--recursive",
+            "--files-with-matches",
+            "$..deprecated",
+            "tree"
+        ])
+    );
+    // An input that cannot be read is listed by neither
+    assert_eq!(
+        run(&["-d", "-L", "$..deprecated", "tree", "missing.json"]).1,
+        "tree/api/v1/orders.json\n"
+    );
+    #[cfg(feature = "yaml")]
+    assert_eq!(
+        run(&[
+            "--recursive",
+            "--include",
+            "*.yaml",
+            "--include",
+            "user?.json",
+            "--files-with-matches",
+            "-e",
+            "$..deprecated",
+            "tree",
+        ]),
+        (
+            Some(0),
+            "tree/api/v1/users.json\ntree/ui/theme.yaml\n".to_string(),
+            String::new()
+        )
+    );
+    assert_eq!(
+        run(&["--recursive", "--include", "*.toml", "-e", "$", "tree"]),
+        (Some(1), String::new(), String::new())
+    );
+
+    // Without --recursive, any input can be listed
+    assert_eq!(
+        run(&["-L", "$.tags", "a.json", "b.json", "malformed.json"]).1,
+        "a.json\n"
+    );
+    for (args, error) in [
+        (
+            &["--include", "*.json", "$", "tree"][..],
+            "--include needs --recursive",
+        ),
+        (
+            &["--recursive", "$"],
+            "--recursive needs FILE arguments and cannot be used with --null-input",
+        ),
+        (
+            &["--files-with-matches", "-L", "$", "a.json"],
+            "--files-with-matches and --files-without-match cannot be used together",
+        ),
+        (
+            &["-L", "--count", "$", "a.json"],
+            "--files-with-matches and --files-without-match print file names and cannot be used with --count, --total, --paths, --pointer, --entries, --delete, --set, --sort or raw output",
+        ),
+    ] {
+        assert_eq!(
+            run(args),
+            (Some(2), String::new(), format!("jpp: {error}\n"))
+        );
+    }
+}
+
+#[test]
+fn test_jobs() {
+    // Many small files, with a large one every so often to finish after those following it
+    let dir = scratch_dir("jobs");
+    let mut files = Vec::new();
+    for i in 0..300 {
+        let path = dir.join(format!("{i:03}.json"));
+        let items = if i % 25 == 0 { 20_000 } else { 3 };
+        let json = if i == 150 {
+            "{\"id\": ".to_string()
+        } else {
+            format!(
+                "{{\"id\": {i}, \"items\": [{}]}}",
+                vec![i.to_string(); items].join(",")
+            )
+        };
+        fs::write(&path, json).unwrap();
+        files.push(path.to_str().unwrap().to_string());
+    }
+    let run = |args: &[&str]| {
+        let mut args = args.to_vec();
+        args.extend(files.iter().map(String::as_str));
+        let output = jpp(&args, "");
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+
+    // Results in the order of the files, and the bad file skipped without stopping the others
+    let (code, out, err) = run(&["--jobs", "8", "-r", "--no-filename", "$.id"]);
+    let ids: Vec<String> = (0..300)
+        .filter(|&i| i != 150)
+        .map(|i| i.to_string())
+        .collect();
+    assert_eq!((code, out), (Some(2), format!("{}\n", ids.join("\n"))));
+    assert_eq!(
+        err,
+        format!(
+            "jpp: error parsing JSON in '{}': EOF while parsing a value at line 1 column 7\n",
+            files[150]
+        )
+    );
+    for args in [
+        &["-r", "$.id"][..],
+        &["--count", "$.items[*]"],
+        &["-L", "$[?@ == 42]"],
+        &["--error-format", "json", "-c", "$.items[-1]"],
+    ] {
+        let parallel = run(&[&["--jobs", "8"], args].concat());
+        assert_eq!(
+            parallel,
+            run(&[&["--jobs", "1"], args].concat()),
+            "{args:?}"
+        );
+        assert_eq!(parallel, run(args), "{args:?}");
+    }
+    assert_eq!(
+        run(&["--jobs", "4", "-q", "-e", "$[?@ == 7]"]),
+        (Some(0), String::new(), String::new())
+    );
+    assert_eq!(
+        jpp(&["--jobs", "0", "$", "a.json"], "").stderr,
+        b"jpp: invalid value '0' for '--jobs <N>' (expected a positive integer)\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+#[cfg(feature = "yaml")]
+fn test_diff() {
+    let run = |args: &[&str]| {
+        let output = jpp(args, "");
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let diff = |args: &[&str]| run(&[args, &["diff/old.yaml", "diff/new.yaml"]].concat());
+
+    assert_eq!(
+        diff(&["--diff", "$..image"]),
+        (
+            Some(1),
+            "--- diff/old.yaml\n+++ diff/new.yaml\n@@ -1,5 +1,5 @@\n [\n\
+             -  \"redis:7.0\",\n-  \"nginx:1.24\",\n+  \"rabbitmq:3.12\",\n+  \"nginx:1.25\",\n   \"app/worker:3.1\"\n ]\n"
+                .to_string(),
+            String::new()
+        )
+    );
+    assert_eq!(
+        diff(&["--diff-format", "json", "-c", "$..image"]),
+        (
+            Some(1),
+            "{\"added\":[{\"path\":\"$['services']['queue']['image']\",\"value\":\"rabbitmq:3.12\"}],\
+             \"changed\":[{\"new\":\"nginx:1.25\",\"old\":\"nginx:1.24\",\"path\":\"$['services']['web']['image']\"}],\
+             \"removed\":[{\"path\":\"$['services']['cache']['image']\",\"value\":\"redis:7.0\"}]}\n"
+                .to_string(),
+            String::new()
+        )
+    );
+    // The same results: nothing printed, or empty lists
+    assert_eq!(
+        diff(&["--diff", "$.services.worker"]),
+        (Some(0), String::new(), String::new())
+    );
+    assert_eq!(
+        diff(&["--diff-format", "json", "-c", "$.services.worker.image"]).1,
+        "{\"added\":[],\"changed\":[],\"removed\":[]}\n"
+    );
+
+    // Order matters: the same ports in another order differ, unless sorted
+    assert_eq!(
+        diff(&["--diff", "-r", "$..ports[*]"]),
+        (
+            Some(1),
+            "--- diff/old.yaml\n+++ diff/new.yaml\n@@ -1,2 +1,2 @@\n-80\n 443\n+80\n".to_string(),
+            String::new()
+        )
+    );
+    assert_eq!(diff(&["--diff", "--sort", "$..ports[*]"]).0, Some(0));
+    assert_eq!(
+        diff(&["--diff-format", "json", "--pointer", "-c", "$..ports[*]"]).1,
+        "{\"added\":[],\"changed\":[{\"new\":443,\"old\":80,\"path\":\"/services/web/ports/0\"},\
+         {\"new\":80,\"old\":443,\"path\":\"/services/web/ports/1\"}],\"removed\":[]}\n"
+    );
+
+    assert_eq!(
+        run(&["--diff", "$", "a.json", "malformed.json"]),
+        (
+            Some(2),
+            String::new(),
+            "jpp: error parsing JSON in 'malformed.json': EOF while parsing a value at line 2 column 0\n"
+                .to_string()
+        )
+    );
+    assert_eq!(
+        run(&["--diff-format", "json", "$.kind", "manifest.yaml", "a.json"]).2,
+        "jpp: --diff-format json compares one document from each input, not 2 from 'manifest.yaml'; add --slurp to compare them as one array\n"
+    );
+    for (args, error) in [
+        (
+            &["--diff", "$", "a.json"][..],
+            "--diff compares the results for two inputs; give two FILE arguments",
+        ),
+        (
+            &["--diff", "-q", "$", "a.json", "b.json"],
+            "--diff prints the differences and cannot be used with --recursive, --follow, --watch, --in-place, --output-file, --quiet, --total, --files-with-matches or --files-without-match",
+        ),
+        (
+            &["--diff-format", "json", "--count", "$", "a.json", "b.json"],
+            "--diff-format json compares results by location and cannot be used with several queries, --count, --paths, --entries, --delete, --set or --format",
+        ),
+    ] {
+        assert_eq!(
+            run(args),
+            (Some(2), String::new(), format!("jpp: {error}\n"))
+        );
+    }
+}
+
+#[test]
+fn test_quiet() {
+    let quiet = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.stdout.is_empty(), "{args:?}: {output:?}");
+        (
+            output.status.code(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    assert_eq!(
+        quiet(&["-q", "-e", "$..price", FIXTURE], ""),
+        (Some(0), String::new())
+    );
+    assert_eq!(
+        quiet(&["-q", "-e", "$..isbn", FIXTURE], ""),
+        (Some(1), String::new())
+    );
+    assert_eq!(
+        quiet(&["--quiet", "$..isbn", FIXTURE], ""),
+        (Some(0), String::new())
+    );
+    // Counts are not printed at all
+    assert_eq!(
+        quiet(&["-q", "-e", "--count", "$..price", FIXTURE, FIXTURE], ""),
+        (Some(0), String::new())
+    );
+    assert_eq!(
+        quiet(&["-q", "--total", "--query", "$..price"], "{}"),
+        (Some(0), String::new())
+    );
+
+    // The run stops at the first match, before the invalid line after it
+    assert_eq!(
+        quiet(
+            &["-q", "-e", "--ndjson", "$.a"],
+            "{}\n{\"a\": 1}\nnot json\n"
+        ),
+        (Some(0), String::new())
+    );
+    assert_eq!(
+        quiet(
+            &["-q", "-e", "--ndjson", "$.a"],
+            "{}\nnot json\n{\"a\": 1}\n"
+        ),
+        (
+            Some(2),
+            "jpp: error parsing JSON on line 2: expected ident at line 1 column 2\n".to_string()
+        )
+    );
+    let (code, stderr) = quiet(&["-q", "-e", "$", "missing.json"], "");
+    assert_eq!(code, Some(2));
+    assert!(stderr.starts_with("jpp: error reading file 'missing.json': "));
+    // --time still reports on stderr
+    let (code, stderr) = quiet(&["-q", "--time", "$..price", FIXTURE], "");
+    assert_eq!(code, Some(0));
+    assert!(stderr.contains("matches=1\n"), "{stderr}");
+
+    // -qq keeps errors off stderr too
+    for args in [&["-qq", "-e", "$", "missing.json"][..], &["-q", "-q", "$["]] {
+        assert_eq!(quiet(args, ""), (Some(2), String::new()));
+    }
+    assert_eq!(quiet(&["-qq", "-e", "$.a"], "{}"), (Some(1), String::new()));
+}
+
+#[test]
+fn test_pointer() {
+    assert_eq!(
+        stdout(&["--pointer", "$.store.book[?@.price < 10].price"]),
+        "/store/book/0/price\n/store/book/2/price\n"
+    );
+
+    const DOC: &str = r#"{"a/b": {"~c": [true]}, "": {"x~/": 1}}"#;
+    let run = |args: &[&str]| {
+        let output = jpp(args, DOC);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(run(&["--pointer", "$['a/b']['~c'][0]"]), "/a~1b/~0c/0\n");
+    assert_eq!(run(&["--pointer", "$['']['x~/']"]), "//x~0~1\n");
+    // The root is the empty pointer
+    assert_eq!(run(&["--pointer", "$"]), "\n");
+    assert_eq!(
+        run(&["--pointer", "--entries", "-c", "$['a/b']..*"]),
+        r#"[{"pointer":"/a~1b/~0c","value":[true]},{"pointer":"/a~1b/~0c/0","value":true}]"#
+            .to_string()
+            + "\n"
+    );
+    assert_eq!(
+        run(&["--pointer", "-c", "--query", "$['']", "--query", "$.none"]),
+        "{\"$['']\":[\"/\"],\"$.none\":[]}\n"
+    );
+
+    let output = jpp(&["--pointer", "--paths", "$"], DOC);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --paths and --pointer cannot be used together\n"
+    );
+}
+
+#[test]
+fn test_entries() {
+    assert_eq!(
+        stdout(&["--entries", "-l", "$..book[?@.price < 9].price"]),
+        concat!(
+            "{\"path\":\"$['store']['book'][0]['price']\",\"value\":8.95}\n",
+            "{\"path\":\"$['store']['book'][2]['price']\",\"value\":8}\n",
+        )
+    );
+    assert_eq!(
+        stdout(&["--entries", "$.store.book[1].tags"]),
+        "[\n  {\n    \"path\": \"$['store']['book'][1]['tags']\",\n    \"value\": []\n  }\n]\n"
+    );
+
+    // `value` is exactly the matched JSON
+    let document = r#"{"a": [1.5, -0, 1e2, "é\n", {"z": null, "b": [true]}]}"#;
+    let input: serde_json::Value = serde_json::from_str(document).unwrap();
+    let output = String::from_utf8(jpp(&["--entries", "-l", "$.a[*]"], document).stdout).unwrap();
+    assert_eq!(output.lines().count(), 5);
+    for (line, expected) in output.lines().zip(0..) {
+        let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+        let object = entry.as_object().unwrap();
+        assert_eq!(object.keys().collect::<Vec<_>>(), ["path", "value"]);
+        assert_eq!(entry["path"], format!("$['a'][{expected}]"));
+        assert_eq!(entry["value"], input["a"][expected]);
+    }
+}
+
+#[test]
+fn test_delete() {
+    let expected = |name: &str| {
+        std::fs::read_to_string(format!(
+            "{}/tests/fixtures/{name}",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    };
+    let run = |args: &[&str]| {
+        let output = jpp(args, "");
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        run(&["--delete", "$..credentials", "config.json"]),
+        expected("config.redacted.json")
+    );
+    // Indices shift as elements go; several queries delete in turn
+    assert_eq!(
+        run(&[
+            "--delete",
+            "--query",
+            "$.features[?search(@, '^legacy')]",
+            "--query",
+            "$.upstreams[2, 0]",
+            "config.json",
+        ]),
+        expected("config.pruned.json")
+    );
+    // No match prints the input unchanged, and formatting flags apply to the document
+    assert_eq!(
+        run(&["--delete", "-c", "$.missing", "a.json"]),
+        "{\"name\":\"a\",\"version\":\"1.0.0\"}\n"
+    );
+    assert_eq!(
+        run(&["--delete", "--output", "yaml", "$.tags[0]", "b.json"]),
+        "---\nname: b\ntags:\n  - \"y\"\nversion: \"2.1.0\"\n"
+    );
+    assert_eq!(
+        jpp(
+            &["--delete", "--ndjson", "$.msg"],
+            "{\"msg\": 1, \"a\": 2}\n[3]\n"
+        )
+        .stdout,
+        b"{\"a\":2}\n[3]\n"
+    );
+    assert_eq!(
+        jpp(&["--delete", "-e", "$.missing", "a.json"], "")
+            .status
+            .code(),
+        Some(1)
+    );
+
+    let output = jpp(&["--delete", "$", "a.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --delete cannot remove the root node '$'; select the nodes to remove\n"
+    );
+    assert_eq!(
+        jpp(&["--delete", "--count", "$.a", "a.json"], "")
+            .status
+            .code(),
+        Some(2)
+    );
+}
+
+#[test]
+fn test_set() {
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let document =
+        r#"{"token": 1, "a": [{"token": 2}, [3, {"token": 4}]], "b": {"c": {"token": 5}}}"#;
+    // Every nesting level: top-level member, array elements, nested objects
+    assert_eq!(
+        run(&["--set", "\"REDACTED\"", "-c", "$..token"], document),
+        (
+            concat!(
+                r#"{"a":[{"token":"REDACTED"},[3,{"token":"REDACTED"}]],"#,
+                r#""b":{"c":{"token":"REDACTED"}},"token":"REDACTED"}"#,
+                "\n"
+            )
+            .to_string(),
+            "jpp: replaced 4 matches\n".to_string()
+        )
+    );
+    assert_eq!(
+        run(&["--set", "[]", "-c", "$.a[1][0]"], document).0,
+        r#"{"a":[{"token":2},[[],{"token":4}]],"b":{"c":{"token":5}},"token":1}"#.to_string()
+            + "\n"
+    );
+    // A match inside another match is replaced with it
+    assert_eq!(
+        run(&["--replace={\"x\": null}", "-c", "$.b..*"], document),
+        (
+            r#"{"a":[{"token":2},[3,{"token":4}]],"b":{"c":{"x":null}},"token":1}"#.to_string()
+                + "\n",
+            "jpp: replaced 1 match\n".to_string()
+        )
+    );
+    assert_eq!(
+        run(
+            &["-q", "--set-raw", "no", "-c", "$.items[*]"],
+            r#"{"items": [1, 2]}"#
+        ),
+        ("{\"items\":[\"no\",\"no\"]}\n".to_string(), String::new())
+    );
+    assert_eq!(
+        run(&["--set", "7", "-c", "$"], "[1]"),
+        ("7\n".to_string(), "jpp: replaced 1 match\n".to_string())
+    );
+
+    let output = jpp(&["--set", "1", "-e", "$.missing"], "{}");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"{}\n");
+    assert_eq!(output.stderr, b"jpp: replaced 0 matches\n");
+
+    // The value is checked before any input is read
+    let output = jpp(&["--set", "REDACTED", "$.a", "missing.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: invalid JSON for '--set <JSON>': ")
+    );
+    for args in [
+        &["--set", "1", "--delete", "$.a"][..],
+        &["--set", "1", "--paths", "$.a"],
+    ] {
+        assert_eq!(jpp(args, "{}").status.code(), Some(2), "{args:?}");
+    }
+}
+
+/// A fresh scratch directory under the system temporary directory
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("jpp-cli-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir(&dir).unwrap();
+    dir
+}
+
+/// Sorted file names in `dir`, to check that no temporary file is left behind
+fn dir_entries(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    names.sort();
+    names
+}
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(format!(
+        "{}/tests/fixtures/{name}",
+        env!("CARGO_MANIFEST_DIR")
+    ))
+    .unwrap()
+}
+
+#[test]
+fn test_in_place() {
+    let dir = scratch_dir("in-place");
+    let config = dir.join("config.json");
+    let bad = dir.join("bad.json");
+    fs::write(&config, fixture("config.json")).unwrap();
+    fs::write(&bad, "{\"credentials\": 1,").unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&config, fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    let output = jpp(
+        &[
+            "--delete",
+            "$..credentials",
+            "-i",
+            "--backup",
+            ".orig",
+            config.to_str().unwrap(),
+            bad.to_str().unwrap(),
+        ],
+        "",
+    );
+    // The good file is edited even though the other one fails
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.ends_with(&format!("jpp: left '{}' unchanged\n", bad.display())),
+        "{stderr}"
+    );
+    assert_eq!(
+        fs::read_to_string(&config).unwrap(),
+        fixture("config.redacted.json")
+    );
+    assert_eq!(
+        fs::read_to_string(dir.join("config.json.orig")).unwrap(),
+        fixture("config.json")
+    );
+    assert_eq!(fs::read_to_string(&bad).unwrap(), "{\"credentials\": 1,");
+    assert_eq!(
+        dir_entries(&dir),
+        ["bad.json", "config.json", "config.json.orig"]
+    );
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&config).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    // YAML stays YAML when asked to
+    #[cfg(feature = "yaml")]
+    {
+        let manifest = dir.join("manifest.yaml");
+        fs::write(&manifest, "a: 1\nb: 2\n---\na: 3\n").unwrap();
+        let output = jpp(
+            &[
+                "--set",
+                "0",
+                "-q",
+                "--in-place",
+                "--output",
+                "yaml-docs",
+                "$.a",
+                manifest.to_str().unwrap(),
+            ],
+            "",
+        );
+        assert!(output.status.success(), "{output:?}");
+        assert_eq!(
+            fs::read_to_string(&manifest).unwrap(),
+            "---\na: 0\nb: 2\n---\na: 0\n"
+        );
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_in_place_errors() {
+    let stderr = |args: &[&str]| {
+        let output = jpp(args, "{}");
+        assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+        assert_eq!(output.stdout, b"");
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert_eq!(
+        stderr(&["-i", "--delete", "$.a"]),
+        "jpp: --in-place needs FILE arguments to edit\n"
+    );
+    assert_eq!(
+        stderr(&["-i", "$.a", "config.json"]),
+        "jpp: --in-place writes back the documents edited by --delete or --set, so it needs one of them\n"
+    );
+    assert_eq!(
+        stderr(&["--backup", ".bak", "--delete", "$.a", "config.json"]),
+        "jpp: --backup needs --in-place\n"
+    );
+    assert_eq!(
+        stderr(&["-i", "--delete", "$.a", "--stream", "config.json"]),
+        "jpp: --in-place cannot be used with --output-file, --stream, --slurp, --with-line-numbers or --with-filename\n"
+    );
+    // Files that cannot be written back as they were read are left alone
+    assert_eq!(
+        stderr(&["-i", "--delete", "$.kind", "manifest.yaml"]),
+        "jpp: --in-place cannot write YAML back to 'manifest.yaml' in the selected output format; add --output yaml\n"
+    );
+    assert_eq!(
+        stderr(&["-i", "--delete", "$.a", "--output", "yaml", "config.json"]),
+        "jpp: --in-place cannot write JSON back to 'config.json' in the selected output format\n"
+    );
+    assert_eq!(
+        stderr(&["-i", "--delete", "$.a", "store.json.gz"]),
+        "jpp: --in-place cannot write gzip-compressed 'store.json.gz' back\n"
+    );
+}
+
+#[test]
+fn test_output_file() {
+    let dir = scratch_dir("output-file");
+    let out = dir.join("out.json");
+    let out_arg = out.to_str().unwrap();
+
+    let output = jpp(&["-c", "$.service", "-o", out_arg, "config.json"], "");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, b"");
+    assert_eq!(fs::read_to_string(&out).unwrap(), "[\"billing\"]\n");
+
+    // A failed input leaves the previous output in place, and no temporary file behind
+    let output = jpp(
+        &[
+            "-c",
+            "$.service",
+            &format!("--output-file={out_arg}"),
+            "config.json",
+            "malformed.json",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.ends_with(&format!("jpp: left '{out_arg}' unchanged\n")),
+        "{stderr}"
+    );
+    assert_eq!(fs::read_to_string(&out).unwrap(), "[\"billing\"]\n");
+    assert_eq!(dir_entries(&dir), ["out.json"]);
+
+    // Color goes to a file only when forced
+    jpp(&["$.service", "-o", out_arg, "config.json"], "");
+    assert!(!fs::read_to_string(&out).unwrap().contains('\x1b'));
+    jpp(
+        &["--color=always", "$.service", "-o", out_arg, "config.json"],
+        "",
+    );
+    assert!(fs::read_to_string(&out).unwrap().contains('\x1b'));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_query_file() {
+    // One trailing newline is stripped
+    for args in [
+        &["-f", "tags.jsonpath"][..],
+        &["--query-file", "tags.jsonpath"],
+        &["--query-file=tags.jsonpath"],
+    ] {
+        assert_eq!(
+            stdout(&[args, &["-c"]].concat()),
+            "[\"old\",\"sea\",\"whale\"]\n"
+        );
+    }
+    // Comment lines are skipped and the remaining lines form one query
+    assert_eq!(
+        stdout(&["-f", "cheap_titles.jsonpath", "-r"]),
+        "Sayings\nMoby Dick\n"
+    );
+    // With a query file, every argument is an input file
+    let output = jpp(&["-f", "tags.jsonpath", "-c", "a.json", "b.json"], "");
+    assert_eq!(output.stdout, b"a.json:[]\nb.json:[\"x\",\"y\"]\n");
+
+    let output = jpp(&["-f", "missing.jsonpath"], "{}");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error reading query file 'missing.jsonpath': ")
+    );
+
+    let output = jpp(&["-f", "tags.jsonpath", "$.a", "a.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        output.stderr,
+        b"jpp: a query cannot be given both with --query/--query-file and as an argument\n"
+    );
+}
+
+#[test]
+fn test_validate() {
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        (
+            output.status.code(),
+            String::from_utf8(output.stdout).unwrap(),
+        )
+    };
+    // No input is read, even when stdin has some
+    assert_eq!(
+        run(&["--validate", "$.a[?@.x > 1]"], "not json"),
+        (Some(0), "ok\n".to_string())
+    );
+    assert_eq!(
+        run(&["--validate", "$.a[?@.x >]"], ""),
+        (
+            Some(1),
+            concat!(
+                "error: at position 10, unexpected token in expression: BracketClose\n",
+                "  $.a[?@.x >]\n",
+                "            ^\n"
+            )
+            .to_string()
+        )
+    );
+    // Several query files are each reported under their name
+    assert_eq!(
+        run(
+            &[
+                "--validate",
+                "-f",
+                "tags.jsonpath",
+                "-f",
+                "missing_operand.jsonpath",
+                "--query-file=cheap_titles.jsonpath",
+            ],
+            ""
+        ),
+        (
+            Some(1),
+            concat!(
+                "tags.jsonpath: ok\n",
+                "missing_operand.jsonpath: error: at position 24, unexpected token in expression: BracketClose\n",
+                "  $.store.book[?@.price < ]\n",
+                "                          ^\n",
+                "cheap_titles.jsonpath: ok\n"
+            )
+            .to_string()
+        )
+    );
+    // Without query arguments, each line of stdin is a query
+    assert_eq!(
+        run(&["--validate"], "# saved queries\n$.a\n\n$.b[\n"),
+        (
+            Some(1),
+            concat!(
+                "(standard input):2: ok\n",
+                "(standard input):4: error: at position 4, unexpected end of input in selector\n",
+                "  $.b[\n",
+                "      ^\n"
+            )
+            .to_string()
+        )
+    );
+    assert_eq!(
+        run(&["--validate", "--arg", "n", "1", "$[?@ == $n]", "$[$m]"], ""),
+        (
+            Some(1),
+            concat!(
+                "$[?@ == $n]: ok\n",
+                "$[$m]: error: undefined variable '$m' in query; define it with --arg or --argjson\n"
+            )
+            .to_string()
+        )
+    );
+
+    let (code, stdout) = run(
+        &[
+            "--validate",
+            "--error-format",
+            "json",
+            "-f",
+            "tags.jsonpath",
+            "-f",
+            "missing_operand.jsonpath",
+        ],
+        "",
+    );
+    assert_eq!(code, Some(1));
+    let reports: Vec<serde_json::Value> = stdout
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(
+        reports,
+        [
+            serde_json::json!({
+                "query": "$..tags[*]",
+                "file": "tags.jsonpath",
+                "line": null,
+                "valid": true
+            }),
+            serde_json::json!({
+                "query": "$.store.book[?@.price < ]",
+                "file": "missing_operand.jsonpath",
+                "line": null,
+                "valid": false,
+                "kind": "unexpected_token",
+                "message": "unexpected token in expression: BracketClose",
+                "position": 24,
+                "end": 25
+            })
+        ]
+    );
+}
+
+#[test]
+fn test_explain() {
+    const QUERY: &str = r#"$.store.book[0:2, -1, ?@.price < 10 && count(@.reviews[?length(@.text) > 100 && match(@.lang, "en|de")]) >= 2].title"#;
+    // No input is read, even when stdin has some
+    let output = jpp(&["--explain", QUERY], "not json");
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        fs::read_to_string(format!(
+            "{}/tests/fixtures/reviews.explain.txt",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    );
+
+    // Several queries are separated by a blank line
+    let output = jpp(&["--explain", "-f", "tags.jsonpath", "$.a"], "");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        concat!(
+            "$..['tags'][*]\n",
+            "segment 1: descendant ..['tags']\n",
+            "  name 'tags'\n",
+            "segment 2: child [*]\n",
+            "  wildcard *\n",
+            "\n",
+            "$['a']\n",
+            "segment 1: child ['a']\n",
+            "  name 'a'\n",
+        )
+    );
+
+    let output = jpp(
+        &[
+            "--explain-format",
+            "json",
+            "$[?match(@.id, $re)]",
+            "--arg",
+            "re",
+            "a.*",
+        ],
+        "",
+    );
+    assert!(output.status.success(), "{output:?}");
+    let tree: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        tree,
+        serde_json::json!({"segments": [{"Child": [{"Filter": {"FunctionCall": {
+            "function": "match",
+            "args": [
+                {"Path": {"start": "CurrentNode", "segments": [{"Child": [{"Name": "id"}]}]}},
+                {"Literal": {"String": "a.*"}}
+            ]
+        }}}]}]})
+    );
+
+    let output = jpp(&["--explain", "$[?@.a =]"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error parsing JSONPath query: ")
+    );
+}
+
+#[test]
+fn test_multiple_queries() {
+    assert_eq!(
+        stdout(&["--query", "$.store.name", "--query", "$.store.missing"]),
+        "{\n  \"$.store.name\": [\n    \"Corner \\\"Books\\\"\"\n  ],\n  \"$.store.missing\": []\n}\n"
+    );
+    assert_eq!(
+        stdout(&[
+            "-c",
+            "--query",
+            "$..price",
+            "--label",
+            "prices",
+            "-f",
+            "tags.jsonpath",
+            "--label=tags",
+            "--query=$.nope",
+            "--label",
+            "nothing",
+        ]),
+        "{\"prices\":[8.95,12.99,8],\"tags\":[\"old\",\"sea\",\"whale\"],\"nothing\":[]}\n"
+    );
+    // Every format keeps the order of the queries, not of the labels
+    let args = [
+        "--count",
+        "--query=$..price",
+        "--label=z",
+        "--query=$..title",
+        "--label=a",
+    ];
+    for (format, expected) in [
+        ("json", "{\"z\":3,\"a\":3}\n"),
+        ("ndjson", "{\"z\":3}\n{\"a\":3}\n"),
+        ("yaml", "---\nz: 3\na: 3\n"),
+        ("table", "z  a\n-  -\n3  3\n"),
+        ("markdown", "| z   | a   |\n| --- | --- |\n| 3   | 3   |\n"),
+    ] {
+        let output = stdout(&[&["-c", "--output", format][..], &args].concat());
+        assert_eq!(output, expected, "{format}");
+    }
+    let colored = stdout(&[&["-c", "--color", "always"][..], &args].concat());
+    assert!(colored.find("\"z\"").unwrap() < colored.find("\"a\"").unwrap());
+    // NDJSON prints one object per query, in the order given
+    assert_eq!(
+        stdout(&["-l", "--query=$.nope", "--query", "$..book[0].title"]),
+        "{\"$.nope\":[]}\n{\"$..book[0].title\":[\"Sayings\"]}\n"
+    );
+    // A single labeled query is keyed too, and modes apply per query
+    assert_eq!(
+        stdout(&["-c", "--count", "--query", "$..title", "--label", "n"]),
+        "{\"n\":3}\n"
+    );
+    assert_eq!(
+        stdout(&[
+            "-c",
+            "--paths",
+            "--query",
+            "$.store.book[1].title",
+            "--query",
+            "$.x"
+        ]),
+        "{\"$.store.book[1].title\":[\"$['store']['book'][1]['title']\"],\"$.x\":[]}\n"
+    );
+
+    let output = jpp(
+        &[
+            "-c", "--query", "$.name", "--query", "$.tags", "a.json", "b.json",
+        ],
+        "",
+    );
+    assert_eq!(
+        output.stdout,
+        b"a.json:{\"$.name\":[\"a\"],\"$.tags\":[]}\nb.json:{\"$.name\":[\"b\"],\"$.tags\":[[\"x\",\"y\"]]}\n"
+    );
+
+    for (args, status) in [
+        (
+            &["-e", "--query", "$.nope", "--query", "$.name", "a.json"][..],
+            0,
+        ),
+        (
+            &["-e", "--query", "$.nope", "--query", "$.none", "a.json"],
+            1,
+        ),
+    ] {
+        assert_eq!(jpp(args, "").status.code(), Some(status), "{args:?}");
+    }
+}
+
+#[test]
+fn test_query_option_with_dollar_file() {
+    let dir = scratch_dir("dollar-file");
+    fs::write(dir.join("$data.json"), r#"{"a": 1}"#).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_jpp"))
+        .args(["-c", "--query", "$.a", "$data.json"])
+        .current_dir(&dir)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap();
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[1]\n");
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_multiple_query_errors() {
+    for (args, message) in [
+        (
+            &["--label", "x", "--query", "$"][..],
+            "--label must follow --query or --query-file",
+        ),
+        (
+            &[
+                "--query", "$.a", "--label", "x", "--query", "$.b", "--label", "x",
+            ],
+            "duplicate query label 'x'",
+        ),
+        (
+            &["--query", "$.a", "--query", "$.a"],
+            "duplicate query label '$.a'",
+        ),
+        (
+            &["--query", "$.a", "$.b"],
+            "a query cannot be given both with --query/--query-file and as an argument",
+        ),
+        (
+            &["--query", "$.a", " $.b"],
+            "a query cannot be given both with --query/--query-file and as an argument",
+        ),
+        (
+            &["--total", "--query", "$.a", "--query", "$.b"],
+            "--total cannot be used with several queries",
+        ),
+    ] {
+        let output = jpp(args, "{}");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("jpp: {message}\n")
+        );
+    }
+
+    let output = jpp(&["--query", "$.a", "--query", "$[", "--label", "bad"], "{}");
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error parsing JSONPath query 'bad': ")
+    );
+}
+
+#[test]
+fn test_variables() {
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let users = r#"[
+        {"id": 1, "name": "O'Brien", "active": true},
+        {"id": 2, "name": "say \"hi\"", "active": false},
+        {"id": 3, "name": "back\\slash", "active": true},
+        {"id": 4, "name": "Zoë 日本", "active": null}
+    ]"#;
+    // Values are literals, whatever quotes or escapes they hold
+    for (name, id) in [
+        ("O'Brien", "[1]"),
+        ("say \"hi\"", "[2]"),
+        ("back\\slash", "[3]"),
+        ("Zoë 日本", "[4]"),
+        ("' || true || '", "[]"),
+    ] {
+        assert_eq!(
+            run(&["-c", "--arg", "n", name, "$[?@.name == $n].id"], users),
+            format!("{id}\n"),
+            "{name:?}"
+        );
+    }
+    assert_eq!(
+        run(
+            &[
+                "-c",
+                "--argjson",
+                "min",
+                "2",
+                "--argjson",
+                "on",
+                "true",
+                "$[?@.id >= $min && @.active == $on].id",
+            ],
+            users
+        ),
+        "[3]\n"
+    );
+    // In selectors, and defined after the query
+    assert_eq!(
+        run(&["-c", "$[$i].name", "--argjson", "i", "-1"], users),
+        "[\"Zoë 日本\"]\n"
+    );
+    // `$name` inside a string literal is a member name, not a variable
+    assert_eq!(
+        run(
+            &["-c", "--arg", "a", "x", "$['$a']"],
+            r#"{"$a": 1, "x": 2}"#
+        ),
+        "[1]\n"
+    );
+
+    for (args, message) in [
+        (
+            &["$[?@.name == $who]"][..],
+            "undefined variable '$who' in query; define it with --arg or --argjson",
+        ),
+        (
+            &["--arg", "1st", "x", "$"],
+            "invalid variable name '1st' for '--arg' (expected letters, digits and _, not starting with a digit)",
+        ),
+        (&["--arg", "x"], "missing value for '--arg <NAME> <VALUE>'"),
+        (
+            &["--argjson", "x", "{\"a\": 1}", "$"],
+            "invalid value for '--argjson' variable 'x': JSONPath has no array or object literals",
+        ),
+        (
+            &["--argjson", "x", "active", "$"],
+            "invalid JSON for '--argjson' variable 'x': expected value at line 1 column 1",
+        ),
+    ] {
+        let output = jpp(args, "{}");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("jpp: {message}\n")
+        );
+    }
+}
+
+#[test]
+fn test_ndjson_input() {
+    let run = |args: &[&str]| {
+        let output = jpp(args, "");
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // Lines without matches print nothing, and results are NDJSON by default
+    for flag in ["--ndjson", "--input-format=ndjson"] {
+        assert_eq!(
+            run(&[flag, "$.level", "app.log"]),
+            "\"info\"\n\"error\"\n\"warn\"\n"
+        );
+    }
+    assert_eq!(
+        run(&["--ndjson", "--with-line-numbers", "$.msg", "app.log"]),
+        "1:\"start\"\n2:\"disk\\nfull\"\n4:\"no level\"\n"
+    );
+    assert_eq!(
+        run(&[
+            "--ndjson",
+            "--with-line-numbers",
+            "-r",
+            "$.tags[*]",
+            "app.log"
+        ]),
+        "5:a\n5:b\n"
+    );
+    assert_eq!(
+        run(&["--ndjson", "--output", "json", "-c", "$.tags", "app.log"]),
+        "[]\n[]\n[]\n[[\"a\",\"b\"]]\n"
+    );
+    assert_eq!(run(&["--ndjson", "--count", "$.level", "app.log"]), "3\n");
+
+    let output = jpp(&["--ndjson", "$.level"], "{\"level\": 1}\n\n{\"level\": 2}");
+    assert_eq!(output.stdout, b"1\n2\n");
+}
+
+#[test]
+fn test_ndjson_input_errors() {
+    // Bad lines are reported and skipped; the other files and lines are still queried
+    let output = jpp(
+        &[
+            "--ndjson",
+            "--with-line-numbers",
+            "$.level",
+            "bad.log",
+            "app.log",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        concat!(
+            "bad.log:1:\"error\"\n",
+            "bad.log:3:\"info\"\n",
+            "app.log:1:\"info\"\n",
+            "app.log:2:\"error\"\n",
+            "app.log:5:\"warn\"\n",
+        )
+    );
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("jpp: error parsing JSON in 'bad.log' on line 2: "));
+    assert_eq!(stderr.lines().count(), 1);
+
+    let output = jpp(&["--input-format", "xml", "$"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+#[cfg(feature = "yaml")]
+fn test_yaml_input() {
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // Each document of the stream gets its own results; aliases are resolved
+    assert_eq!(
+        run(&["-c", "$..image", "manifest.yaml"], ""),
+        "[\"web:1.4\",\"envoy:1.30\",\"web:1.4\"]\n[\"busybox:1.36\"]\n"
+    );
+    assert_eq!(
+        run(&["-s", "-c", "$..image", "manifest.yaml"], ""),
+        "[\"web:1.4\",\"envoy:1.30\",\"web:1.4\",\"busybox:1.36\"]\n"
+    );
+    assert_eq!(
+        run(&["--slurp", "-r", "$[*].kind", "manifest.yaml"], ""),
+        "Deployment\nCronJob\n"
+    );
+    // Timestamps are strings
+    assert_eq!(
+        run(&["-r", "$.metadata.created", "manifest.yaml"], ""),
+        "2024-05-01T10:00:00Z\n"
+    );
+    // Stdin has no extension to detect
+    assert_eq!(
+        run(
+            &["--input-format", "yaml", "-c", "$.a"],
+            "a: [1, {b: true}]"
+        ),
+        "[[1,{\"b\":true}]]\n"
+    );
+    assert_eq!(
+        run(&["-c", "$.version", "a.json", "manifest.yaml"], ""),
+        "a.json:[\"1.0.0\"]\nmanifest.yaml:[]\nmanifest.yaml:[]\n"
+    );
+
+    let output = jpp(&["--input-format=yaml", "$"], "a: [1,\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error parsing YAML: ")
+    );
+    let output = jpp(&["--stream", "$", "manifest.yaml"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --stream needs JSON input, not YAML in 'manifest.yaml'\n"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "yaml"))]
+fn test_yaml_input_without_feature() {
+    let output = jpp(&["$", "manifest.yaml"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: cannot read YAML input in 'manifest.yaml': jpp was built without the 'yaml' feature\n"
+    );
+}
+
+#[test]
+fn test_jsonc_input() {
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // Comment markers inside strings are kept, and trailing commas are dropped
+    assert_eq!(
+        run(&["-c", "$.compilerOptions.paths", "tsconfig.jsonc"], ""),
+        "[{\"//not-a-comment\":[\"src/*.ts\"],\"@app/*\":[\"src/app/*\"]}]\n"
+    );
+    assert_eq!(
+        run(&["-r", "$.include[*]", "tsconfig.jsonc"], ""),
+        "src/**/*.ts\ntests\n"
+    );
+    assert_eq!(
+        run(
+            &["--input-format", "jsonc", "-c", "$.a"],
+            "{\"a\": [1, /* 2, */ 3,], // done\n}"
+        ),
+        "[[1,3]]\n"
+    );
+
+    // Errors point into the original text
+    let output = jpp(
+        &["--input-format=jsonc", "$"],
+        "{\n  // \"a\": 1\n  \"b\": 2 /* x */ \"c\": 3\n}",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: error parsing JSONC: expected `,` or `}` at line 3 column 18\n"
+    );
+    let output = jpp(&["--input-format=jsonc", "$"], "[1] /* open");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: error parsing JSONC: unterminated comment at line 1 column 5\n"
+    );
+    let output = jpp(&["--stream", "$", "tsconfig.jsonc"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --stream needs JSON input, not JSONC in 'tsconfig.jsonc'\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "toml")]
+fn test_toml_input() {
+    let run = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        run(&["-c", "$.dependencies.*", "crate.toml"], ""),
+        concat!(
+            r#"[{"optional":true,"version":"1"},"#,
+            r#"{"features":["serde"],"path":"../jpp_core"},"1"]"#,
+            "\n"
+        )
+    );
+    // Arrays of tables are arrays of objects
+    assert_eq!(
+        run(
+            &["-r", "$.bin[?@['required-features']].name", "crate.toml"],
+            ""
+        ),
+        "jpp-bench\n"
+    );
+    assert_eq!(
+        run(&["-r", "$.bin[*].path", "crate.toml"], ""),
+        "src/main.rs\nsrc/bench.rs\n"
+    );
+    // Datetimes are strings; floats and integers stay distinct
+    assert_eq!(
+        run(&["-c", "$.package.metadata.release.*", "crate.toml"], ""),
+        "[1.0,\"2025-03-01T12:00:00Z\",30]\n"
+    );
+    assert_eq!(
+        run(
+            &["--input-format", "toml", "-c", "$.a.b"],
+            "[a]\nb = [1, 2.5]"
+        ),
+        "[[1,2.5]]\n"
+    );
+
+    let output = jpp(&["--input-format=toml", "$"], "a = \n");
+    assert_eq!(output.status.code(), Some(2));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("jpp: error parsing TOML: "), "{stderr}");
+    assert!(!stderr.ends_with("\n\n"), "{stderr}");
+}
+
+#[test]
+#[cfg(not(feature = "toml"))]
+fn test_toml_input_without_feature() {
+    let output = jpp(&["$", "crate.toml"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: cannot read TOML input in 'crate.toml': jpp was built without the 'toml' feature\n"
+    );
+}
+
+#[test]
+fn test_slurp() {
+    assert_eq!(
+        jpp(
+            &["--ndjson", "-s", "-c", "$[*].level"],
+            "{\"level\": 1}\n{\"level\": 2}\n"
+        )
+        .stdout,
+        b"1\n2\n"
+    );
+    assert_eq!(
+        stdout(&["-s", "-c", "$[0].store.name"]),
+        "[\"Corner \\\"Books\\\"\"]\n"
+    );
+
+    // A bad line fails the whole input rather than dropping an element
+    let output = jpp(&["--ndjson", "-s", "$", "bad.log"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"");
+    assert_eq!(jpp(&["--stream", "-s", "$"], "[]").status.code(), Some(2));
+}
+
+#[test]
+fn test_stream() {
+    let document = r#" [{"a": 1, "b": [true]}, {"a": "x\ny"}, 3, {"b": []}] "#;
+    let run = |args: &[&str]| {
+        let output = jpp(args, document);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    // `$` is each element in turn, and results are NDJSON by default
+    assert_eq!(run(&["--stream", "$.a"]), "1\n\"x\\ny\"\n");
+    assert_eq!(
+        run(&["--stream", "$"]),
+        "{\"a\":1,\"b\":[true]}\n{\"a\":\"x\\ny\"}\n3\n{\"b\":[]}\n"
+    );
+    assert_eq!(run(&["--stream", "--paths", "$.b"]), "$['b']\n$['b']\n");
+    assert_eq!(run(&["--stream", "--count", "$.*"]), "4\n");
+    assert_eq!(
+        run(&["--stream", "-c", "--output", "json", "$.b"]),
+        "[[true]]\n[]\n[]\n[[]]\n"
+    );
+    assert_eq!(jpp(&["--stream", "$"], "[]").stdout, b"");
+
+    // Elements before a syntax error are still queried
+    let output = jpp(&["--stream", "$.a"], r#"[{"a": 1}, {"a": 2}, {"a": "#);
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(output.stdout, b"1\n2\n");
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: error parsing JSON: ")
+    );
+
+    let output = jpp(&["--stream", "$", "a.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --stream needs a top-level array in 'a.json'; query other documents without --stream, or use --ndjson for one document per line\n"
+    );
+    for args in [&["--stream", "-n", "$"][..], &["--stream", "--ndjson", "$"]] {
+        assert_eq!(jpp(args, "[]").status.code(), Some(2), "{args:?}");
+    }
+}
+
+#[test]
+#[cfg(all(feature = "gzip", feature = "zstd"))]
+fn test_compressed_input() {
+    let run = |args: &[&str], stdin: &[u8]| {
+        let output = jpp(args, stdin);
+        assert!(output.status.success(), "{args:?}: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let expected = stdout(&["-c", "$..title"]);
+    for file in ["store.json.gz", "store.json.zst"] {
+        assert_eq!(run(&["-c", "$..title", file], b""), expected, "{file}");
+    }
+    // Stdin is recognized by its magic bytes
+    let gzipped = std::fs::read(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/store.json.gz"
+    ))
+    .unwrap();
+    assert_eq!(run(&["-c", "$..title"], &gzipped), expected);
+
+    // Decompression composes with the streaming input modes
+    assert_eq!(
+        run(&["--ndjson", "$.level", "app.log.gz"], b""),
+        "\"info\"\n\"error\"\n\"warn\"\n"
+    );
+    assert_eq!(
+        run(&["--stream", "-c", "$.a", "items.json.zst"], b""),
+        "1\n[2,3]\n"
+    );
+
+    for file in ["truncated.json.gz", "truncated.json.zst"] {
+        let output = jpp(&["$", file], "");
+        assert_eq!(output.status.code(), Some(2), "{file}");
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.starts_with(&format!("jpp: error reading file '{file}': ")),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+#[cfg(not(feature = "gzip"))]
+fn test_compressed_input_without_feature() {
+    let output = jpp(&["$", "store.json.gz"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: cannot read gzip-compressed input in 'store.json.gz': jpp was built without the 'gzip' feature\n"
+    );
+}
+
+/// Streams a generated array of several hundred megabytes; run with `--ignored`
+#[test]
+#[ignore]
+fn test_stream_large_document() {
+    const ELEMENTS: usize = 2_000_000;
+    let path = std::env::temp_dir().join(format!("jpp-stream-{}.json", std::process::id()));
+    {
+        let mut file = std::io::BufWriter::new(std::fs::File::create(&path).unwrap());
+        file.write_all(b"[").unwrap();
+        for i in 0..ELEMENTS {
+            if i > 0 {
+                file.write_all(b",\n").unwrap();
+            }
+            write!(
+                file,
+                r#"{{"id": {i}, "name": "user-{i}", "tags": ["a", "b", "c"], "padding": "{}"}}"#,
+                "x".repeat(100)
+            )
+            .unwrap();
+        }
+        file.write_all(b"]").unwrap();
+    }
+
+    let output = jpp(
+        &[
+            "--stream",
+            "--count",
+            "$.tags[?@ == 'b']",
+            path.to_str().unwrap(),
+        ],
+        "",
+    );
+    std::fs::remove_file(&path).unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(output.stdout, format!("{ELEMENTS}\n").as_bytes());
+}
+
+#[test]
+fn test_completions() {
+    for shell in ["bash", "zsh", "fish", "powershell"] {
+        let output = jpp(&["completions", shell], "");
+        assert!(output.status.success(), "{shell}: {output:?}");
+        let script = String::from_utf8(output.stdout).unwrap();
+        for option in ["query-file", "output", "explain-format", "yaml-docs"] {
+            assert!(script.contains(option), "{shell}: {option}");
+        }
+    }
+
+    // The bash script parses and completes option values
+    let bash = jpp(&["completions", "bash"], "").stdout;
+    let complete = |line: &str| {
+        let output = Command::new("bash")
+            .arg("-c")
+            .arg(format!(
+                "source /dev/stdin; {line}; _jpp; echo \"${{COMPREPLY[*]}}\""
+            ))
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .and_then(|mut child| {
+                child.stdin.take().unwrap().write_all(&bash)?;
+                child.wait_with_output()
+            })
+            .unwrap();
+        assert!(output.status.success(), "{output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    assert_eq!(
+        complete("COMP_WORDS=(jpp --color a); COMP_CWORD=2"),
+        "auto always\n"
+    );
+    assert_eq!(
+        complete("COMP_WORDS=(jpp --with); COMP_CWORD=1"),
+        "--with-line-numbers --with-filename\n"
+    );
+
+    let output = jpp(&["completions", "tcsh"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: invalid shell 'tcsh' for 'completions' (expected bash, zsh, fish, powershell)\n"
+    );
+}
+
+#[test]
+fn test_time() {
+    const QUERY: &str = "$.store.book[?@.price < 10].title";
+    let plain = jpp(&[QUERY, FIXTURE], "");
+    let timed = jpp(&["--time", QUERY, FIXTURE], "");
+    assert!(timed.status.success(), "{timed:?}");
+    assert_eq!(timed.stdout, plain.stdout);
+    let stderr = String::from_utf8(timed.stderr).unwrap();
+    let keys: Vec<&str> = stderr
+        .lines()
+        .map(|line| line.split_once('=').unwrap().0)
+        .collect();
+    assert_eq!(
+        keys[..6],
+        [
+            "input_bytes",
+            "read_ms",
+            "parse_ms",
+            "query_parse_ms",
+            "eval_ms",
+            "matches"
+        ]
+    );
+    let size = fixture(FIXTURE).len();
+    assert!(stderr.starts_with(&format!("input_bytes={size}\n")));
+    assert!(stderr.contains("\nmatches=2\n"));
+
+    // Edits count their matches, and the JSON summary is one object
+    let output = jpp(
+        &["--time-format=json", "--delete", "$..price"],
+        "[{\"price\":1},{\"price\":2}]",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[\n  {},\n  {}\n]\n"
+    );
+    let summary: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    assert_eq!(summary["matches"], 2);
+    assert_eq!(summary["input_bytes"], 25);
+}
+
+#[test]
+fn test_limits() {
+    let failure = |args: &[&str], stdin: &str| {
+        let output = jpp(args, stdin);
+        assert_eq!(output.status.code(), Some(3), "{args:?}: {output:?}");
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert_eq!(
+        failure(&["--max-input-bytes", "10", "$", FIXTURE], ""),
+        "jpp: input is larger than --max-input-bytes 10\n"
+    );
+    assert_eq!(
+        failure(&["--max-input-bytes=4", "--ndjson", "$"], "[1]\n[2]\n"),
+        "jpp: input is larger than --max-input-bytes 4\n"
+    );
+    assert_eq!(
+        failure(&["--max-results", "1", "$..price", FIXTURE], ""),
+        "jpp: a query selected more than --max-results 1\n"
+    );
+    assert_eq!(
+        failure(&["--max-results", "0", "--count", "$[*]"], "[1]"),
+        "jpp: a query selected more than --max-results 0\n"
+    );
+    assert_eq!(
+        failure(&["--max-depth", "2", "$", FIXTURE], ""),
+        "jpp: document nesting is deeper than --max-depth 2\n"
+    );
+    // Reading stops at the first level too deep, before the parser meets its own limit
+    assert_eq!(
+        failure(&["--max-depth", "10", "$"], &"[".repeat(1_000_000)),
+        "jpp: document nesting is deeper than --max-depth 10\n"
+    );
+    assert_eq!(
+        failure(&["--max-depth=1", "--ndjson", "-c", "$"], "[1]\n[[2]]\n"),
+        "jpp: document nesting is deeper than --max-depth 1\n"
+    );
+    assert_eq!(
+        jpp(&["--max-depth", "1", "--stream", "-c", "$"], "[[1], [2]]").stdout,
+        b"[1]\n[2]\n"
+    );
+    let items = format!("[{}]", vec!["[1, [2, {\"a\": 3}]]"; 50_000].join(","));
+    assert_eq!(
+        failure(&["--timeout", "0.000001", "$..*"], &items),
+        "jpp: evaluation took longer than --timeout 0.000001\n"
+    );
+    // Edits are checked too, and leave the file alone
+    let dir = scratch_dir("limits");
+    let file = dir.join("data.json");
+    fs::write(&file, "[1, 2]").unwrap();
+    let path = file.to_str().unwrap();
+    failure(&["--max-results", "1", "--delete", "-i", "$[*]", path], "");
+    assert_eq!(fs::read_to_string(&file).unwrap(), "[1, 2]");
+
+    // Runs within the limits are unaffected
+    const QUERY: &str = "$.store.book[?@.price < 10].title";
+    let plain = jpp(&[QUERY, FIXTURE], "");
+    let size = fixture(FIXTURE).len().to_string();
+    let limited = jpp(
+        &[
+            "--max-input-bytes",
+            &size,
+            "--max-results",
+            "2",
+            "--max-depth",
+            "5",
+            "--timeout",
+            "60",
+            QUERY,
+            FIXTURE,
+        ],
+        "",
+    );
+    assert!(limited.status.success(), "{limited:?}");
+    assert_eq!(limited.stdout, plain.stdout);
+    assert_eq!(
+        stdout(&["--max-results", "1", "--limit", "1", "$..price"]),
+        stdout(&["--limit", "1", "$..price"])
+    );
+    assert_eq!(
+        stdout(&["--max-results", "4", "--paths", "$..price"]),
+        stdout(&["--paths", "$..price"])
+    );
+
+    let output = jpp(&["--timeout", "0", "$"], "1");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: invalid value '0' for '--timeout <SECS>' (expected a positive number of seconds)\n"
+    );
+}
+
+#[test]
+fn test_error_format_json() {
+    let error = |args: &[&str]| {
+        let output = jpp(args, "{}");
+        assert_eq!(output.status.code(), Some(2), "{args:?}: {output:?}");
+        let object: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+        object
+    };
+    let object = error(&["--error-format", "json", "$.store[?@.price <"]);
+    assert_eq!(object["kind"], "query_parse_error");
+    assert_eq!(object["position"], 18);
+    assert_eq!(object["query"], "$.store[?@.price <");
+    assert_eq!(object["file"], serde_json::Value::Null);
+    assert_eq!(object["exit_code"], 2);
+    assert!(
+        object["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("error parsing JSONPath query: ")
+    );
+
+    let object = error(&["--error-format=json", "$", "missing.json"]);
+    assert_eq!(object["kind"], "input_error");
+    assert_eq!(object["file"], "missing.json");
+    assert_eq!(object["position"], serde_json::Value::Null);
+    assert!(
+        object["message"]
+            .as_str()
+            .unwrap()
+            .starts_with("error reading file 'missing.json': ")
+    );
+
+    // Argument errors use the format too, and human errors show where a query is wrong
+    assert_eq!(
+        error(&["--error-format", "json", "--limit", "x", "$"])["kind"],
+        "usage_error"
+    );
+    let output = jpp(&["$.store[?@.price <"], "{}");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: error parsing JSONPath query: parse error: at position 18, unexpected end of input in expression\n  $.store[?@.price <\n                    ^\n"
+    );
+}
+
+#[test]
+fn test_query_error_caret() {
+    let stderr = |vars: &[(&str, &str)], args: &[&str]| {
+        let output = jpp_env(vars, args, "{}");
+        assert_eq!(output.status.code(), Some(2));
+        String::from_utf8(output.stderr).unwrap()
+    };
+    // A squiggle under the rest of the token
+    assert_eq!(
+        stderr(&[], &["$.items[?size(@) > 1]"]),
+        concat!(
+            "jpp: error parsing JSONPath query: parse error: at position 9, unknown function 'size'\n",
+            "  $.items[?size(@) > 1]\n",
+            "           ^~~~\n",
+        )
+    );
+    // Wide characters take two columns
+    assert_eq!(
+        stderr(&[], &["$['日本語'].価格[?@ >]"]),
+        concat!(
+            "jpp: error parsing JSONPath query: parse error: at position 16, unexpected token in expression: BracketClose\n",
+            "  $['日本語'].価格[?@ >]\n",
+            "                       ^\n",
+        )
+    );
+    // Long queries are cut to a window around the error that fits COLUMNS
+    let query = format!("$.config{}[?@.enabled == tru]", ".section".repeat(12));
+    assert_eq!(
+        stderr(&[("COLUMNS", "40")], &[&query]),
+        concat!(
+            "jpp: error parsing JSONPath query: parse error: at position 119, unexpected identifier 'tru' in expression\n",
+            "  …on.section.section[?@.enabled == tru]\n",
+            "                                    ^~~\n",
+        )
+    );
+    assert_eq!(
+        stderr(&[], &[&query])
+            .lines()
+            .nth(1)
+            .unwrap()
+            .chars()
+            .count(),
+        80
+    );
+
+    // JSON errors carry the position and not the rendering
+    let object: serde_json::Value = serde_json::from_str(&stderr(
+        &[("COLUMNS", "40")],
+        &["--error-format", "json", &query],
+    ))
+    .unwrap();
+    assert_eq!(object["position"], 119);
+    assert_eq!(object["query"], query.as_str());
+}
+
+#[test]
+fn test_env_defaults() {
+    const DOC: &str = "{\"b\": [1], \"a\": null}";
+    let run = |vars: &[(&str, &str)], args: &[&str]| {
+        let output = jpp_env(vars, args, DOC);
+        assert!(output.status.success(), "{output:?}");
+        (
+            String::from_utf8(output.stdout).unwrap(),
+            String::from_utf8(output.stderr).unwrap(),
+        )
+    };
+    let vars = [
+        ("JPP_INDENT", "4"),
+        ("JPP_SORT_KEYS", "true"),
+        ("JPP_COLOR", "never"),
+    ];
+    let indented =
+        "[\n    {\n        \"a\": null,\n        \"b\": [\n            1\n        ]\n    }\n]\n";
+
+    // Environment only, flags only, and flags over the environment
+    assert_eq!(run(&vars, &["$"]).0, indented);
+    assert_eq!(run(&[], &["--indent", "4", "-S", "$"]).0, indented);
+    assert_eq!(
+        run(&vars, &["--indent", "1", "$"]).0,
+        "[\n {\n  \"a\": null,\n  \"b\": [\n   1\n  ]\n }\n]\n"
+    );
+    assert_eq!(
+        run(&[("JPP_OUTPUT", "yaml")], &["--output", "ndjson", "$.b"]).0,
+        "[1]\n"
+    );
+    assert_eq!(run(&[("JPP_OUTPUT", "ndjson")], &["$.b"]).0, "[1]\n");
+    assert_eq!(
+        run(
+            &[("JPP_COLOR", "always")],
+            &["--color", "never", "-c", "$.b"]
+        )
+        .0,
+        "[[1]]\n"
+    );
+
+    // An invalid variable is ignored with a warning
+    assert_eq!(
+        run(&[("JPP_INDENT", "wide")], &["-c", "$.b"]),
+        (
+            "[[1]]\n".to_string(),
+            "jpp: warning: ignoring JPP_INDENT: invalid value 'wide' for '--indent <N>' (expected 0 to 7)\n"
+                .to_string()
+        )
+    );
+}
+
+#[test]
+fn test_watch_errors() {
+    for (args, error) in [
+        (
+            &["--watch", "$"][..],
+            "--watch needs FILE arguments to watch; standard input cannot be watched",
+        ),
+        (
+            &["--watch", "-o", "out.json", "$", FIXTURE],
+            "--watch cannot be used with --in-place, --output-file or --time",
+        ),
+        (&["--clear", "$", FIXTURE], "--clear needs --watch"),
+    ] {
+        let output = jpp(args, "");
+        assert_eq!(output.status.code(), Some(2), "{args:?}");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("jpp: {error}\n")
+        );
+    }
+}
+
+#[test]
+#[cfg(feature = "http")]
+fn test_url_input() {
+    use std::io::Read;
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    let store =
+        fs::read(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/store.json")).unwrap();
+    let server = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for (head, body) in [
+            ("200 OK", store),
+            ("200 OK", b"name: jpp\n".to_vec()),
+            ("503 Service Unavailable", b"{}".to_vec()),
+        ] {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buf = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let n = stream.read(&mut buf).unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            requests.push(String::from_utf8(request).unwrap().to_ascii_lowercase());
+            let head = format!("HTTP/1.1 {head}\r\nContent-Length: {}\r\n\r\n", body.len());
+            stream.write_all(head.as_bytes()).unwrap();
+            stream.write_all(&body).unwrap();
+        }
+        requests
+    });
+
+    let output = jpp(
+        &[
+            "-c",
+            "--header",
+            "Authorization: Bearer t0ken",
+            "$..price",
+            &format!("{base}/store.json?page=1"),
+        ],
+        "",
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "[8.95,12.99,8]\n"
+    );
+    // The format follows the extension of the URL path
+    #[cfg(feature = "yaml")]
+    {
+        let output = jpp(&["-r", "$.name", &format!("{base}/app.yaml")], "");
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "jpp\n");
+    }
+    let output = jpp(&["$", &format!("{base}/down.json")], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("jpp: error fetching '{base}/down.json': HTTP 503 Service Unavailable\n")
+    );
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("get /store.json?page=1 http/1.1\r\n"));
+    assert!(requests[0].contains("\r\nauthorization: bearer t0ken\r\n"));
+    assert!(!requests[1].contains("authorization"));
+
+    let output = jpp(&["-i", "--delete", "$.a", &format!("{base}/a.json")], "");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("jpp: --in-place cannot write back to the URL '{base}/a.json'\n")
+    );
+}
+
+#[test]
+#[cfg(not(feature = "http"))]
+fn test_url_without_feature() {
+    let output = jpp(&["$", "http://127.0.0.1:9/a.json"], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: cannot fetch 'http://127.0.0.1:9/a.json': jpp was built without the 'http' feature\n"
+    );
+}
+
+#[test]
+#[cfg(not(feature = "watch"))]
+fn test_watch_without_feature() {
+    let output = jpp(&["--watch", "$", FIXTURE], "");
+    assert_eq!(output.status.code(), Some(2));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: cannot use --watch: jpp was built without the 'watch' feature\n"
+    );
+}
+
+/// Read from `reader` until the output so far ends with `expected`
+fn read_until(reader: &mut impl std::io::Read, output: &mut String, expected: &str) {
+    let mut buf = [0; 256];
+    while !output.ends_with(expected) {
+        let n = reader.read(&mut buf).unwrap();
+        assert!(n > 0, "output ended before {expected:?}: {output:?}");
+        output.push_str(std::str::from_utf8(&buf[..n]).unwrap());
+    }
+}
+
+#[test]
+#[cfg(feature = "watch")]
+fn test_watch() {
+    let dir = scratch_dir("watch");
+    let config = dir.join("config.json");
+    fs::write(&config, r#"{"servers":[{"port":1}]}"#).unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jpp"))
+        .args(["-c", "--watch", "$.servers[*].port", "config.json"])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut stderr = child.stderr.take().unwrap();
+    let mut output = String::new();
+    let mut errors = String::new();
+    read_until(&mut stdout, &mut output, "[1]\n");
+
+    // A half-written file is reported, and watching goes on
+    fs::write(&config, r#"{"servers":["#).unwrap();
+    read_until(&mut stderr, &mut errors, "\n");
+    assert!(
+        errors.starts_with("jpp: error parsing JSON in 'config.json': "),
+        "{errors}"
+    );
+    // Editors write a new file and rename it over the old one
+    fs::write(dir.join("config.json.tmp"), r#"{"servers":[{"port":2}]}"#).unwrap();
+    fs::rename(dir.join("config.json.tmp"), &config).unwrap();
+    read_until(&mut stdout, &mut output, "[2]\n");
+
+    let killed = Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+    assert!(killed.success());
+    let status = child.wait().unwrap();
+    assert!(status.success(), "{status:?}");
+    assert_eq!(
+        output,
+        "[1]\n==> config.json changed <==\n==> config.json changed <==\n[2]\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_follow() {
+    let dir = scratch_dir("follow");
+    let log = dir.join("app.log");
+    let append = |text: &str| {
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&log)
+            .unwrap()
+            .write_all(text.as_bytes())
+            .unwrap();
+    };
+    fs::write(&log, "{\"level\": \"info\"}\n{\"level\": \"error\"}\n").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_jpp"))
+        .args([
+            "--ndjson",
+            "--follow",
+            "--from-start",
+            "--with-line-numbers",
+            "-r",
+            "$[?@ == 'error']",
+            "app.log",
+        ])
+        .current_dir(&dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdout = child.stdout.take().unwrap();
+    let mut output = String::new();
+
+    // Each match shows up as soon as its line is in the file, while jpp keeps running
+    read_until(&mut stdout, &mut output, "2:error\n");
+    // A line is read once it is complete
+    append("{\"level\": \"er");
+    append("ror\", \"n\": 3}\n");
+    read_until(&mut stdout, &mut output, "3:error\n");
+    // A rotated log is read from the start of the new file
+    fs::write(dir.join("app.log.new"), "{\"level\": \"error\"}\n").unwrap();
+    fs::rename(dir.join("app.log.new"), &log).unwrap();
+    read_until(&mut stdout, &mut output, "4:error\n");
+    assert_eq!(output, "2:error\n3:error\n4:error\n");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    fs::remove_dir_all(&dir).unwrap();
+
+    // -q ends at the first match, here one already in the file
+    let output = jpp(
+        &[
+            "--ndjson",
+            "--follow",
+            "--from-start",
+            "-q",
+            "-e",
+            "$[?@ == 'error']",
+            "app.log",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+    for (args, error) in [
+        (
+            &["--follow", "$", "app.log"][..],
+            "--follow reads lines; add --ndjson or --raw-input",
+        ),
+        (
+            &["--ndjson", "--follow", "$", "a.json", "b.json"],
+            "--follow needs one FILE to follow",
+        ),
+        (
+            &["--from-start", "$", "a.json"],
+            "--from-start needs --follow",
+        ),
+    ] {
+        let output = jpp(args, "");
+        assert_eq!(
+            String::from_utf8(output.stderr).unwrap(),
+            format!("jpp: {error}\n")
+        );
+    }
+}
+
+#[test]
+fn test_dash_arguments() {
+    // `-` reads stdin, here between two files
+    let output = jpp(&["-c", "$.a", "-", "--no-filename"], r#"{"a":1}"#);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[1]\n");
+    let output = jpp(
+        &["-c", "$.store.book[1].price", FIXTURE, "-"],
+        r#"{"store":{}}"#,
+    );
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "store.json:[12.99]\n(standard input):[]\n"
+    );
+
+    // After `--`, names starting with a dash are files
+    let dir = scratch_dir("dash");
+    fs::write(dir.join("-weird.json"), r#"{"a":2}"#).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_jpp"))
+        .args(["-c", "$.a", "--", "-weird.json"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "[2]\n");
+    let output = Command::new(env!("CARGO_BIN_EXE_jpp"))
+        .args(["-c", "$.a", "-weird.json"])
+        .current_dir(&dir)
+        .output()
+        .unwrap();
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .starts_with("jpp: unknown option: -weird.json\n")
+    );
+    fs::remove_dir_all(&dir).unwrap();
+
+    let output = jpp(&["-i", "--delete", "$.a", "-"], "{}");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "jpp: --in-place cannot write back to standard input '-'\n"
+    );
+}
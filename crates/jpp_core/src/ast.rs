@@ -1,15 +1,37 @@
 //! AST definitions for JSONPath queries (RFC 9535)
 
+pub mod visit;
+
+use crate::function::Function;
+use crate::path::write_escaped_name;
+use regex::Regex;
 use serde_json::Value;
+use std::fmt;
+use std::mem::{self, Discriminant};
+use std::ops::Range;
+use std::sync::Arc;
 
 /// A complete JSONPath query
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct JsonPath {
     pub segments: Vec<Segment>,
+    /// Where the parsed query wrote each selector and expression, in the order of
+    /// [`rendered_spans`]. Empty for a path built with [`JsonPath::new`].
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub(crate) source_spans: Vec<(NodeKind, Range<usize>)>,
+}
+
+/// Equal when the segments are, wherever each query was parsed from
+impl PartialEq for JsonPath {
+    fn eq(&self, other: &Self) -> bool {
+        self.segments == other.segments
+    }
 }
 
 /// A segment in a JSONPath query
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Segment {
     /// Child segment (single dot: `.key` or `[selector]`)
     Child(Vec<Selector>),
@@ -19,6 +41,7 @@ pub enum Segment {
 
 /// A selector within a segment
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Selector {
     /// Name selector: `.key` or `['key']`
     Name(String),
@@ -38,6 +61,7 @@ pub enum Selector {
 
 /// An expression in a filter
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Expr {
     /// Current node reference: `@`
     CurrentNode,
@@ -66,12 +90,33 @@ pub enum Expr {
     },
     /// Logical NOT expression: `!@.archived`
     Not(Box<Expr>),
-    /// Function call: `length(@.items)`
-    FunctionCall { name: String, args: Vec<Expr> },
+    /// Arithmetic on numbers: `@.price * @.qty`, only parsed with
+    /// [`ParseOptions::arithmetic`](crate::ParseOptions)
+    Arithmetic {
+        left: Box<Expr>,
+        op: ArithOp,
+        right: Box<Expr>,
+    },
+    /// Function call: `length(@.items)`, resolved against the registry at parse time
+    FunctionCall {
+        /// Serialized as the function name
+        #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_function_name"))]
+        function: Arc<Function>,
+        args: Vec<Expr>,
+        /// The pattern of a built-in `match()`/`search()` call, compiled at parse time when it
+        /// is a valid string literal
+        #[cfg_attr(feature = "serde", serde(skip))]
+        regex: Option<CompiledRegex>,
+        /// Whether a built-in `match()`/`search()` accepts only I-Regexp patterns, see
+        /// [`ParseOptions::strict_iregexp`](crate::ParseOptions::strict_iregexp)
+        #[cfg_attr(feature = "serde", serde(skip))]
+        strict_regex: bool,
+    },
 }
 
 /// Comparison operators
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum CompOp {
     /// Equal: `==`
     Eq,
@@ -89,6 +134,7 @@ pub enum CompOp {
 
 /// Logical operators
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum LogicalOp {
     /// Logical AND: `&&`
     And,
@@ -96,14 +142,31 @@ pub enum LogicalOp {
     Or,
 }
 
+/// Arithmetic operators
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum ArithOp {
+    /// Addition: `+`
+    Add,
+    /// Subtraction: `-`
+    Sub,
+    /// Multiplication: `*`
+    Mul,
+    /// Division: `/`
+    Div,
+}
+
 /// Literal values in expressions
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Literal {
     /// Null value
     Null,
     /// Boolean value
     Bool(bool),
-    /// Number (integer or floating-point)
+    /// Integer, exact within the i64 and u64 ranges and compared as an f64 outside them
+    Integer(i128),
+    /// Floating-point number, or an integer outside the i64 and u64 ranges
     Number(f64),
     /// String value
     String(String),
@@ -127,6 +190,11 @@ impl CachedLiteral {
         let cached_value = match &literal {
             Literal::Null => Value::Null,
             Literal::Bool(b) => Value::Bool(*b),
+            Literal::Integer(n) => i64::try_from(*n)
+                .map(Value::from)
+                .or_else(|_| u64::try_from(*n).map(Value::from))
+                // Beyond both, like the lexer's Number for such literals
+                .unwrap_or_else(|_| Value::from(*n as f64)),
             Literal::Number(n) => serde_json::Number::from_f64(*n)
                 .map(Value::Number)
                 .unwrap_or(Value::Null),
@@ -139,6 +207,41 @@ impl CachedLiteral {
     }
 }
 
+/// A `match()`/`search()` pattern after the I-Regexp transform (and anchoring, for `match()`),
+/// compiled once so evaluation doesn't go through the regex cache
+#[derive(Debug, Clone)]
+pub struct CompiledRegex(pub(crate) Regex);
+
+impl CompiledRegex {
+    /// The regex source actually compiled
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+// Compiled regexes compare by source, like CachedLiteral compares by literal
+impl PartialEq for CompiledRegex {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+#[cfg(feature = "serde")]
+fn serialize_function_name<S: serde::Serializer>(
+    function: &Arc<Function>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(function.name())
+}
+
+// Serialized as the literal alone, since cached_value is derived from it
+#[cfg(feature = "serde")]
+impl serde::Serialize for CachedLiteral {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serde::Serialize::serialize(&self.literal, serializer)
+    }
+}
+
 // PartialEq compares only the literal, ignoring cached_value
 // (cached_value is deterministically derived from literal)
 impl PartialEq for CachedLiteral {
@@ -149,6 +252,630 @@ impl PartialEq for CachedLiteral {
 
 impl JsonPath {
     pub fn new(segments: Vec<Segment>) -> Self {
-        Self { segments }
+        Self {
+            segments,
+            source_spans: Vec::new(),
+        }
+    }
+
+    /// A normal form of the query, suitable as a cache key
+    ///
+    /// Builds on [`Display`](fmt::Display) (bracket notation, single-quoted names, filter
+    /// literals as parsed values, fixed spacing) and additionally drops slice parts equal to
+    /// their defaults, so `$.a[0:3:1]` and `$['a'][:3]` give the same string. The result
+    /// parses back to a query with the same canonical string.
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::JsonPath;
+    ///
+    /// let a = JsonPath::parse("$.a[0:2:1][?@.b==1.0]").unwrap();
+    /// let b = JsonPath::parse(r#"$["a"][:2][?(@['b'] == 1)]"#).unwrap();
+    /// assert_eq!(a.canonical_string(), "$['a'][:2][?@['b'] == 1]");
+    /// assert_eq!(a.canonical_string(), b.canonical_string());
+    /// ```
+    pub fn canonical_string(&self) -> String {
+        let mut path = self.clone();
+        visit::walk_mut(&mut path, &mut SliceDefaults);
+        path.to_string()
+    }
+}
+
+/// Removes slice parts that equal their defaults: a step of 1, and a start of 0 for a
+/// positive step
+struct SliceDefaults;
+
+impl visit::VisitorMut for SliceDefaults {
+    fn visit_selector_mut(&mut self, selector: &mut Selector) {
+        if let Selector::Slice { start, step, .. } = selector {
+            if *step == Some(1) {
+                *step = None;
+            }
+            if step.is_none() && *start == Some(0) {
+                *start = None;
+            }
+        }
+        visit::walk_selector_mut(self, selector);
+    }
+}
+
+impl Segment {
+    /// Selectors of the segment
+    pub fn selectors(&self) -> &[Selector] {
+        match self {
+            Segment::Child(selectors) | Segment::Descendant(selectors) => selectors,
+        }
+    }
+}
+
+/// True if any filter in `segments` contains an absolute (`$`) query
+pub(crate) fn segments_reference_root(segments: &[Segment]) -> bool {
+    let mut references = visit::RootReferences::default();
+    for segment in segments {
+        visit::Visitor::visit_segment(&mut references, segment);
+    }
+    references.found
+}
+
+// Display renders queries in bracket notation, e.g. `$['a'][0]..[*][?@.b > 1]`.
+// The output re-parses to an equal AST.
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        self.segments
+            .iter()
+            .try_for_each(|segment| write!(f, "{segment}"))
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Segment::Descendant(_) = self {
+            f.write_str("..")?;
+        }
+        f.write_str("[")?;
+        for (i, selector) in self.selectors().iter().enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            write!(f, "{selector}")?;
+        }
+        f.write_str("]")
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selector::Name(name) => {
+                f.write_str("'")?;
+                write_escaped_name(f, name)?;
+                f.write_str("'")
+            }
+            Selector::Index(i) => write!(f, "{i}"),
+            Selector::Wildcard => f.write_str("*"),
+            Selector::Slice { start, end, step } => {
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                f.write_str(":")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                if let Some(step) = step {
+                    write!(f, ":{step}")?;
+                }
+                Ok(())
+            }
+            Selector::Filter(expr) => write!(f, "?{expr}"),
+        }
+    }
+}
+
+/// Binding strength of an expression, used to decide where parentheses are needed
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Logical {
+            op: LogicalOp::Or, ..
+        } => 1,
+        Expr::Logical {
+            op: LogicalOp::And, ..
+        } => 2,
+        Expr::Comparison { .. } => 3,
+        Expr::Arithmetic {
+            op: ArithOp::Add | ArithOp::Sub,
+            ..
+        } => 4,
+        Expr::Arithmetic { .. } => 5,
+        _ => 6,
+    }
+}
+
+/// Write `expr`, parenthesized if it binds looser than `min_precedence`
+fn write_operand(f: &mut fmt::Formatter<'_>, expr: &Expr, min_precedence: u8) -> fmt::Result {
+    if precedence(expr) < min_precedence {
+        write!(f, "({expr})")
+    } else {
+        write!(f, "{expr}")
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::CurrentNode => f.write_str("@"),
+            Expr::RootNode => f.write_str("$"),
+            Expr::Path { start, segments } => {
+                write!(f, "{start}")?;
+                segments
+                    .iter()
+                    .try_for_each(|segment| write!(f, "{segment}"))
+            }
+            Expr::Literal(literal) => write!(f, "{}", literal.literal),
+            Expr::Comparison { left, op, right } => {
+                write_operand(f, left, 4)?;
+                write!(f, " {op} ")?;
+                write_operand(f, right, 4)
+            }
+            Expr::Logical { left, op, right } => {
+                let prec = precedence(self);
+                write_operand(f, left, prec)?;
+                write!(f, " {op} ")?;
+                write_operand(f, right, prec + 1)
+            }
+            Expr::Not(inner) => {
+                f.write_str("!")?;
+                write_operand(f, inner, 6)
+            }
+            Expr::Arithmetic { left, op, right } => {
+                let prec = precedence(self);
+                write_operand(f, left, prec)?;
+                write!(f, " {op} ")?;
+                write_operand(f, right, prec + 1)
+            }
+            Expr::FunctionCall { function, args, .. } => {
+                write!(f, "{}(", function.name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+impl fmt::Display for CompOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CompOp::Eq => "==",
+            CompOp::Ne => "!=",
+            CompOp::Lt => "<",
+            CompOp::Gt => ">",
+            CompOp::Le => "<=",
+            CompOp::Ge => ">=",
+        })
+    }
+}
+
+impl fmt::Display for ArithOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArithOp::Add => "+",
+            ArithOp::Sub => "-",
+            ArithOp::Mul => "*",
+            ArithOp::Div => "/",
+        })
+    }
+}
+
+impl fmt::Display for LogicalOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LogicalOp::And => "&&",
+            LogicalOp::Or => "||",
+        })
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Null => f.write_str("null"),
+            Literal::Bool(b) => write!(f, "{b}"),
+            Literal::Integer(n) => write!(f, "{n}"),
+            Literal::Number(n) => write!(f, "{n}"),
+            Literal::String(s) => {
+                f.write_str("'")?;
+                write_escaped_name(f, s)?;
+                f.write_str("'")
+            }
+        }
+    }
+}
+
+/// A selector or expression located in the [`Display`](fmt::Display) output of a query
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RenderedNode<'a> {
+    Selector(&'a Selector),
+    Expr(&'a Expr),
+}
+
+/// Variant of a [`RenderedNode`], recorded by the parser to check that
+/// [`JsonPath::source_spans`] still describes the segments
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NodeKind {
+    Selector(Discriminant<Selector>),
+    Expr(Discriminant<Expr>),
+}
+
+impl NodeKind {
+    pub(crate) fn selector(selector: &Selector) -> Self {
+        Self::Selector(mem::discriminant(selector))
+    }
+
+    pub(crate) fn expr(expr: &Expr) -> Self {
+        Self::Expr(mem::discriminant(expr))
+    }
+}
+
+impl From<RenderedNode<'_>> for NodeKind {
+    fn from(node: RenderedNode<'_>) -> Self {
+        match node {
+            RenderedNode::Selector(selector) => Self::selector(selector),
+            RenderedNode::Expr(expr) => Self::expr(expr),
+        }
+    }
+}
+
+/// Character spans of every selector and expression in `path.to_string()`, including
+/// those in filter sub-paths. Mirrors the `Display` impls above.
+pub(crate) fn rendered_spans(path: &JsonPath) -> Vec<(RenderedNode<'_>, Range<usize>)> {
+    let mut spans = Vec::new();
+    segment_spans(&path.segments, 1, &mut spans);
+    spans
+}
+
+/// Character spans of the nodes of `rendered` in the query `path` was parsed from, or `None`
+/// if `path` was not parsed or its segments were changed since
+pub(crate) fn source_spans<'a>(
+    path: &'a JsonPath,
+    rendered: &[(RenderedNode<'_>, Range<usize>)],
+) -> Option<Vec<&'a Range<usize>>> {
+    let recorded = &path.source_spans;
+    let matches = recorded.len() == rendered.len()
+        && recorded
+            .iter()
+            .zip(rendered)
+            .all(|((kind, _), (node, _))| *kind == NodeKind::from(*node));
+    matches.then(|| recorded.iter().map(|(_, span)| span).collect())
+}
+
+fn rendered_len(node: &impl fmt::Display) -> usize {
+    node.to_string().chars().count()
+}
+
+fn segment_spans<'a>(
+    segments: &'a [Segment],
+    mut pos: usize,
+    spans: &mut Vec<(RenderedNode<'a>, Range<usize>)>,
+) {
+    for segment in segments {
+        if let Segment::Descendant(_) = segment {
+            pos += 2;
+        }
+        pos += 1;
+        for (i, selector) in segment.selectors().iter().enumerate() {
+            if i > 0 {
+                pos += 2;
+            }
+            let len = rendered_len(selector);
+            spans.push((RenderedNode::Selector(selector), pos..pos + len));
+            if let Selector::Filter(expr) = selector {
+                expr_spans(expr, pos + 1, spans);
+            }
+            pos += len;
+        }
+        pos += 1;
+    }
+}
+
+fn expr_spans<'a>(expr: &'a Expr, start: usize, spans: &mut Vec<(RenderedNode<'a>, Range<usize>)>) {
+    let len = rendered_len(expr);
+    spans.push((RenderedNode::Expr(expr), start..start + len));
+
+    // Start of an operand written by `write_operand` at `pos`; returns the position after it
+    let operand = |operand: &'a Expr, pos: usize, min_precedence: u8, spans: &mut Vec<_>| {
+        let paren = usize::from(precedence(operand) < min_precedence);
+        expr_spans(operand, pos + paren, spans);
+        pos + rendered_len(operand) + 2 * paren
+    };
+    match expr {
+        Expr::Path {
+            start: node,
+            segments,
+        } => {
+            segment_spans(segments, start + rendered_len(node.as_ref()), spans);
+        }
+        Expr::Comparison { left, op, right } => {
+            let pos = operand(left, start, 4, spans) + rendered_len(op) + 2;
+            operand(right, pos, 4, spans);
+        }
+        Expr::Logical { left, op, right } => {
+            let prec = precedence(expr);
+            let pos = operand(left, start, prec, spans) + rendered_len(op) + 2;
+            operand(right, pos, prec + 1, spans);
+        }
+        Expr::Arithmetic { left, op, right } => {
+            let prec = precedence(expr);
+            let pos = operand(left, start, prec, spans) + rendered_len(op) + 2;
+            operand(right, pos, prec + 1, spans);
+        }
+        Expr::Not(inner) => {
+            operand(inner, start + 1, 6, spans);
+        }
+        Expr::FunctionCall { function, args, .. } => {
+            let mut pos = start + function.name().chars().count() + 1;
+            for arg in args {
+                expr_spans(arg, pos, spans);
+                pos += rendered_len(arg) + 2;
+            }
+        }
+        Expr::CurrentNode | Expr::RootNode | Expr::Literal(_) => {}
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serialize() {
+        use serde_json::json;
+
+        let path = JsonPath::parse("$..a[1:, ?length(@.b) > 2]").unwrap();
+        assert_eq!(
+            serde_json::to_value(&path).unwrap(),
+            json!({"segments": [{"Descendant": [{"Name": "a"}]}, {"Child": [
+                {"Slice": {"start": 1, "end": null, "step": null}},
+                {"Filter": {"Comparison": {
+                    "left": {"FunctionCall": {
+                        "function": "length",
+                        "args": [{"Path": {
+                            "start": "CurrentNode",
+                            "segments": [{"Child": [{"Name": "b"}]}]
+                        }}]
+                    }},
+                    "op": "Gt",
+                    "right": {"Literal": {"Integer": 2}}
+                }}}
+            ]}]})
+        );
+    }
+
+    #[test]
+    fn test_cached_literal_beyond_u64() {
+        let literal = |n: i128| CachedLiteral::new(Literal::Integer(n)).cached_value;
+        assert_eq!(literal(i64::MIN.into()), Value::from(i64::MIN));
+        assert_eq!(literal(u64::MAX.into()), Value::from(u64::MAX));
+        // Built through the API rather than parsed, so not already a Number
+        let negated = literal(-i128::from(u64::MAX));
+        assert_ne!(negated, Value::Null);
+        assert_eq!(negated.as_f64(), Some(-(u64::MAX as f64)));
+        assert_eq!(literal(i128::MAX).as_f64(), Some(i128::MAX as f64));
+    }
+
+    #[test]
+    fn test_rendered_spans() {
+        let path = JsonPath::parse("$..a[0, ?(@.b || @.c) && !match(@.d, 'x')]").unwrap();
+        let rendered = path.to_string();
+        let chars: Vec<char> = rendered.chars().collect();
+        let texts: Vec<String> = rendered_spans(&path)
+            .into_iter()
+            .map(|(_, span)| chars[span].iter().collect())
+            .collect();
+        assert_eq!(
+            texts,
+            [
+                "'a'",
+                "0",
+                "?(@['b'] || @['c']) && !match(@['d'], 'x')",
+                "(@['b'] || @['c']) && !match(@['d'], 'x')",
+                "@['b'] || @['c']",
+                "@['b']",
+                "'b'",
+                "@['c']",
+                "'c'",
+                "!match(@['d'], 'x')",
+                "match(@['d'], 'x')",
+                "@['d']",
+                "'d'",
+                "'x'",
+            ]
+        );
+    }
+
+    /// Source text of each node of `path`, parsed from `source`
+    fn source_texts(path: &JsonPath, source: &str) -> Option<Vec<String>> {
+        let chars: Vec<char> = source.chars().collect();
+        let spans = source_spans(path, &rendered_spans(path))?;
+        Some(
+            spans
+                .into_iter()
+                .map(|span| chars[span.clone()].iter().collect())
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_source_spans() {
+        let source = "$..a[0,?( @.b||@[\"c\"] ) && !match(@.d,'x')]";
+        let path = JsonPath::parse(source).unwrap();
+        assert_eq!(
+            source_texts(&path, source).unwrap(),
+            [
+                "a",
+                "0",
+                "?( @.b||@[\"c\"] ) && !match(@.d,'x')",
+                "( @.b||@[\"c\"] ) && !match(@.d,'x')",
+                "@.b||@[\"c\"]",
+                "@.b",
+                "b",
+                "@[\"c\"]",
+                "\"c\"",
+                "!match(@.d,'x')",
+                "match(@.d,'x')",
+                "@.d",
+                "d",
+                "'x'",
+            ]
+        );
+
+        assert_eq!(
+            source_texts(&JsonPath::new(path.segments.clone()), source),
+            None
+        );
+        assert_eq!(source_texts(&path.clone().optimize(), source), None);
+        let mut edited = path;
+        edited.segments[0] = Segment::Descendant(vec![Selector::Wildcard]);
+        assert_eq!(source_texts(&edited, source), None);
+    }
+
+    #[cfg(feature = "arithmetic")]
+    #[test]
+    fn test_source_spans_arithmetic() {
+        let options = crate::ParseOptions {
+            arithmetic: true,
+            ..Default::default()
+        };
+        let source = "$[?@.a-1 - @.b == -1.5*-2 + 3]";
+        let (path, _) = JsonPath::parse_with_options(source, &options).unwrap();
+        assert_eq!(
+            source_texts(&path, source).unwrap(),
+            [
+                "?@.a-1 - @.b == -1.5*-2 + 3",
+                "@.a-1 - @.b == -1.5*-2 + 3",
+                "@.a-1 - @.b",
+                "@.a-1",
+                "@.a",
+                "a",
+                "1",
+                "@.b",
+                "b",
+                "-1.5*-2 + 3",
+                "-1.5*-2",
+                "-1.5",
+                "-2",
+                "3",
+            ]
+        );
+    }
+
+    #[cfg(feature = "extended-functions")]
+    #[test]
+    fn test_source_spans_parent_query() {
+        let source = "$..x[?parent().a==1]";
+        let path =
+            JsonPath::parse_with_registry(source, &crate::FunctionRegistry::extended()).unwrap();
+        assert_eq!(
+            source_texts(&path, source).unwrap(),
+            [
+                "x",
+                "?parent().a==1",
+                "parent().a==1",
+                "parent().a",
+                "a",
+                "1"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_canonical_string_equates_variants() {
+        let groups: &[&[&str]] = &[
+            &["$.a", "$['a']", "$[\"a\"]"],
+            &["$.a.b[0]", "$['a'][\"b\"][0]", "$.a['b'][0]"],
+            &["$[1:3]", "$[1:3:1]"],
+            &["$[:3]", "$[0:3]", "$[0:3:1]", "$[:3:1]"],
+            &["$[::-1]", "$[::-1]"],
+            &["$..*", "$..[*]"],
+            &[
+                "$[?@.a == 1]",
+                "$[?(@.a==1)]",
+                "$[?@['a'] == 1.0]",
+                "$[?@.a == 1e0]",
+            ],
+            &["$[?@.a == 'x']", "$[?@.a == \"x\"]", "$[?@.a == '\\u0078']"],
+            &["$[?@[0:2] && @.a]", "$[?(@[:2:1]) && (@.a)]"],
+        ];
+        for group in groups {
+            let canonical: Vec<String> = group
+                .iter()
+                .map(|q| JsonPath::parse(q).unwrap().canonical_string())
+                .collect();
+            assert!(
+                canonical.iter().all(|c| *c == canonical[0]),
+                "group {group:?}: {canonical:?}"
+            );
+            let reparsed = JsonPath::parse(&canonical[0]).unwrap();
+            assert_eq!(reparsed.canonical_string(), canonical[0]);
+        }
+    }
+
+    #[test]
+    fn test_canonical_string_keeps_distinct_queries_apart() {
+        for (a, b) in [
+            ("$[1:]", "$[1::-1]"),
+            ("$[0::-1]", "$[::-1]"),
+            ("$[:]", "$[*]"),
+            ("$['a', 'a']", "$['a']"),
+            ("$[?@.a == 1]", "$[?@.a == '1']"),
+        ] {
+            assert_ne!(
+                JsonPath::parse(a).unwrap().canonical_string(),
+                JsonPath::parse(b).unwrap().canonical_string()
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips() {
+        let queries = [
+            "$",
+            "$.a['b c'][0][-1]",
+            "$..*",
+            "$[1:2, :, ::-1, 1::2]",
+            "$['it\\'s'][\"\\n\"]",
+            "$[?@.a && (@.b || !@.c)]",
+            "$[?(@.a || @.b) && @.c]",
+            "$[?!(@.a == 1)]",
+            "$[?@.price < 10.5 && @.name != null]",
+            "$[?length(@.tags) >= 2 && match(@.id, 'a.*')]",
+            "$[?@[?@.x == $.y]]",
+            "$[?count(@..*) > 1]",
+        ];
+        for query in queries {
+            let path = JsonPath::parse(query).unwrap();
+            let rendered = path.to_string();
+            assert_eq!(
+                JsonPath::parse(&rendered).unwrap(),
+                path,
+                "{query} -> {rendered}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_bracket_notation() {
+        let path = JsonPath::parse("$.store..book[0, 'x'][?@.price < 10 || @.free]").unwrap();
+        assert_eq!(
+            path.to_string(),
+            "$['store']..['book'][0, 'x'][?@['price'] < 10 || @['free']]"
+        );
     }
 }
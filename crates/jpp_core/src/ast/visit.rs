@@ -0,0 +1,271 @@
+//! Traversal of query ASTs
+//!
+//! Implement [`Visitor`] (or [`VisitorMut`] to rewrite nodes) and override the methods for
+//! the nodes of interest. Each default method recurses into the children through the
+//! matching `walk_*` function, so an override that still wants the children visited calls
+//! that function itself. Traversal reaches every node, including the segments of filter
+//! sub-paths such as `@.a[?$.b]`.
+
+use super::{Expr, JsonPath, Segment, Selector};
+
+/// Read-only AST visitor
+pub trait Visitor {
+    fn visit_segment(&mut self, segment: &Segment) {
+        walk_segment(self, segment);
+    }
+
+    fn visit_selector(&mut self, selector: &Selector) {
+        walk_selector(self, selector);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+}
+
+/// Visit every segment of `path` in query order
+///
+/// # Example
+/// ```
+/// use jpp_core::JsonPath;
+/// use jpp_core::ast::Selector;
+/// use jpp_core::ast::visit::{Visitor, walk, walk_selector};
+///
+/// #[derive(Default)]
+/// struct Names(Vec<String>);
+///
+/// impl Visitor for Names {
+///     fn visit_selector(&mut self, selector: &Selector) {
+///         if let Selector::Name(name) = selector {
+///             self.0.push(name.clone());
+///         }
+///         walk_selector(self, selector);
+///     }
+/// }
+///
+/// let path = JsonPath::parse("$.store.book[?@.price < $.limit].title").unwrap();
+/// let mut names = Names::default();
+/// walk(&path, &mut names);
+/// assert_eq!(names.0, ["store", "book", "price", "limit", "title"]);
+/// ```
+pub fn walk<V: Visitor + ?Sized>(path: &JsonPath, visitor: &mut V) {
+    for segment in &path.segments {
+        visitor.visit_segment(segment);
+    }
+}
+
+/// Visit the selectors of `segment`
+pub fn walk_segment<V: Visitor + ?Sized>(visitor: &mut V, segment: &Segment) {
+    for selector in segment.selectors() {
+        visitor.visit_selector(selector);
+    }
+}
+
+/// Visit the expression of a filter selector
+pub fn walk_selector<V: Visitor + ?Sized>(visitor: &mut V, selector: &Selector) {
+    if let Selector::Filter(expr) = selector {
+        visitor.visit_expr(expr);
+    }
+}
+
+/// Visit the sub-expressions and path segments of `expr`
+pub fn walk_expr<V: Visitor + ?Sized>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        Expr::Path { start, segments } => {
+            visitor.visit_expr(start);
+            for segment in segments {
+                visitor.visit_segment(segment);
+            }
+        }
+        Expr::Comparison { left, right, .. }
+        | Expr::Logical { left, right, .. }
+        | Expr::Arithmetic { left, right, .. } => {
+            visitor.visit_expr(left);
+            visitor.visit_expr(right);
+        }
+        Expr::Not(inner) => visitor.visit_expr(inner),
+        Expr::FunctionCall { args, .. } => {
+            for arg in args {
+                visitor.visit_expr(arg);
+            }
+        }
+        Expr::CurrentNode | Expr::RootNode | Expr::Literal(_) => {}
+    }
+}
+
+/// AST visitor that may rewrite nodes in place
+pub trait VisitorMut {
+    fn visit_segment_mut(&mut self, segment: &mut Segment) {
+        walk_segment_mut(self, segment);
+    }
+
+    fn visit_selector_mut(&mut self, selector: &mut Selector) {
+        walk_selector_mut(self, selector);
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+    }
+}
+
+/// Visit every segment of `path` in query order, allowing rewrites
+///
+/// Rewrites are not re-validated, so a visitor must keep the AST well-formed (for example,
+/// comparison operands singular) for evaluation and [`Display`](std::fmt::Display) to stay
+/// meaningful.
+pub fn walk_mut<V: VisitorMut + ?Sized>(path: &mut JsonPath, visitor: &mut V) {
+    for segment in &mut path.segments {
+        visitor.visit_segment_mut(segment);
+    }
+}
+
+/// Visit the selectors of `segment`, allowing rewrites
+pub fn walk_segment_mut<V: VisitorMut + ?Sized>(visitor: &mut V, segment: &mut Segment) {
+    match segment {
+        Segment::Child(selectors) | Segment::Descendant(selectors) => {
+            for selector in selectors {
+                visitor.visit_selector_mut(selector);
+            }
+        }
+    }
+}
+
+/// Visit the expression of a filter selector, allowing rewrites
+pub fn walk_selector_mut<V: VisitorMut + ?Sized>(visitor: &mut V, selector: &mut Selector) {
+    if let Selector::Filter(expr) = selector {
+        visitor.visit_expr_mut(expr);
+    }
+}
+
+/// Visit the sub-expressions and path segments of `expr`, allowing rewrites
+pub fn walk_expr_mut<V: VisitorMut + ?Sized>(visitor: &mut V, expr: &mut Expr) {
+    match expr {
+        Expr::Path { start, segments } => {
+            visitor.visit_expr_mut(start);
+            for segment in segments {
+                visitor.visit_segment_mut(segment);
+            }
+        }
+        Expr::Comparison { left, right, .. }
+        | Expr::Logical { left, right, .. }
+        | Expr::Arithmetic { left, right, .. } => {
+            visitor.visit_expr_mut(left);
+            visitor.visit_expr_mut(right);
+        }
+        Expr::Not(inner) => visitor.visit_expr_mut(inner),
+        Expr::FunctionCall { args, .. } => {
+            for arg in args {
+                visitor.visit_expr_mut(arg);
+            }
+        }
+        Expr::CurrentNode | Expr::RootNode | Expr::Literal(_) => {}
+    }
+}
+
+/// Detects absolute (`$`) queries inside filters
+#[derive(Default)]
+pub(crate) struct RootReferences {
+    pub(crate) found: bool,
+}
+
+impl Visitor for RootReferences {
+    fn visit_expr(&mut self, expr: &Expr) {
+        if matches!(expr, Expr::RootNode) {
+            self.found = true;
+        } else if !self.found {
+            walk_expr(self, expr);
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::ast::{CachedLiteral, Literal};
+
+    #[derive(Default)]
+    struct Counter {
+        segments: usize,
+        descendants: usize,
+        selectors: usize,
+        exprs: usize,
+    }
+
+    impl Visitor for Counter {
+        fn visit_segment(&mut self, segment: &Segment) {
+            self.segments += 1;
+            if let Segment::Descendant(_) = segment {
+                self.descendants += 1;
+            }
+            walk_segment(self, segment);
+        }
+
+        fn visit_selector(&mut self, selector: &Selector) {
+            self.selectors += 1;
+            walk_selector(self, selector);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            self.exprs += 1;
+            walk_expr(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_walk_reaches_filter_sub_paths() {
+        let path = JsonPath::parse("$.a[?@..b[?$.c] && length(@.d) > 1]").unwrap();
+        let mut counter = Counter::default();
+        walk(&path, &mut counter);
+        // .a and [?...], ..b and [?$.c] of @..b[?$.c], .c of $.c, .d of @.d
+        assert_eq!(counter.segments, 6);
+        assert_eq!(counter.descendants, 1);
+        // 'a', filter, 'b', filter, 'c', 'd'
+        assert_eq!(counter.selectors, 6);
+        // &&, @..b[..], @, $.c, $, >, length(), @.d, @, 1
+        assert_eq!(counter.exprs, 10);
+    }
+
+    #[test]
+    fn test_root_references() {
+        for (query, expected) in [
+            ("$.a[?@.b]", false),
+            ("$.a[?@.b == $.c]", true),
+            ("$[?@[?$]]", true),
+            ("$[?count(@.*) == 1]", false),
+            ("$[?!@.a[?value($.b) == 1]]", true),
+        ] {
+            let path = JsonPath::parse(query).unwrap();
+            let mut references = RootReferences::default();
+            walk(&path, &mut references);
+            assert_eq!(references.found, expected, "query {query}");
+        }
+    }
+
+    struct NegateNumbers;
+
+    impl VisitorMut for NegateNumbers {
+        fn visit_selector_mut(&mut self, selector: &mut Selector) {
+            if let Selector::Index(i) = selector {
+                *i = -*i;
+            }
+            walk_selector_mut(self, selector);
+        }
+
+        fn visit_expr_mut(&mut self, expr: &mut Expr) {
+            if let Expr::Literal(cached) = expr
+                && let Literal::Integer(n) = cached.literal
+            {
+                *cached = CachedLiteral::new(Literal::Integer(-n));
+            }
+            walk_expr_mut(self, expr);
+        }
+    }
+
+    #[test]
+    fn test_walk_mut_rewrites() {
+        let mut path = JsonPath::parse("$[1][?@[2] > 3]").unwrap();
+        walk_mut(&mut path, &mut NegateNumbers);
+        assert_eq!(path, JsonPath::parse("$[-1][?@[-2] > -3]").unwrap());
+    }
+}
@@ -0,0 +1,673 @@
+//! Completion context: what may be typed at a cursor in a partially typed query
+//!
+//! The text before the cursor is tokenized and walked with a small pushdown automaton that
+//! mirrors the parser's grammar. Paths are tracked as query text so that, at a name position,
+//! the complete part of the path can be evaluated against a document for candidate keys.
+
+use crate::ast::JsonPath;
+use crate::error::ParseErrorKind;
+use crate::function::FunctionRegistry;
+use crate::lexer::{Lexer, Token, TokenKind};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::ops::Range;
+
+/// Kind of input that may follow the cursor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Expected {
+    /// The root identifier `$`
+    Root,
+    /// A segment: `.name`, `..name` or `[...]`
+    Segment,
+    /// A member name or `*` after `.`, or after `..` where `[` may also follow
+    MemberName,
+    /// A selector inside brackets: a quoted name, an index, a slice, `*` or a `?` filter
+    Selector,
+    /// The rest of a quoted member name inside brackets
+    QuotedName,
+    /// The rest of a string literal in a filter expression
+    StringLiteral,
+    /// A filter operand: `@`, `$`, a literal, a function call, `(` or `!`
+    Operand,
+    /// A comparison operator, `&&` or `||`
+    Operator,
+    /// `,` or `]` to continue or close a bracketed selection
+    ClosingBracket,
+    /// `)` to close a group, or `,` or `)` after a function argument
+    ClosingParen,
+}
+
+/// What may be typed at a cursor position, see [`complete`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct CompletionContext {
+    /// Input accepted at the cursor; empty if the text before it cannot start a valid query
+    pub expected: Vec<Expected>,
+    /// Partial name, keyword or string content right before the cursor
+    pub prefix: String,
+    /// Character range of `prefix`, which a chosen completion replaces
+    pub replace: Range<usize>,
+    /// Quote character when the cursor is inside a string literal
+    pub quote: Option<char>,
+    /// Member names starting with `prefix`, sorted; only with a document and at name positions
+    pub names: Vec<String>,
+    /// Function names starting with `prefix`, sorted; only at operand positions
+    pub functions: Vec<String>,
+}
+
+/// Describe what may be typed at character `offset` of `input`
+///
+/// Only the text before `offset` is considered. At member name positions (after `.` or `..`,
+/// inside brackets, and in quoted names) the complete part of the path is evaluated against
+/// `doc` to list the member names that exist there. Names are returned unescaped: use
+/// [`needs_bracket_notation`] and [`escape_name`] to insert them.
+///
+/// Inside a filter, `@` stands for every child of the nodes the filter is applied to, so
+/// names after `@.` are the members of those children.
+///
+/// [`needs_bracket_notation`]: crate::needs_bracket_notation
+/// [`escape_name`]: crate::escape_name
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use jpp_core::{complete, Expected};
+///
+/// let doc = json!({"store": {"book": [{"title": "A", "price": 8}]}});
+/// let context = complete("$.store.book[?@.pr", 18, Some(&doc));
+/// assert_eq!(context.expected, vec![Expected::MemberName]);
+/// assert_eq!(context.prefix, "pr");
+/// assert_eq!(context.replace, 16..18);
+/// assert_eq!(context.names, vec!["price"]);
+/// ```
+pub fn complete(input: &str, offset: usize, doc: Option<&Value>) -> CompletionContext {
+    let chars: Vec<char> = input.chars().take(offset).collect();
+    let offset = chars.len();
+    let mut context = CompletionContext {
+        expected: Vec::new(),
+        prefix: String::new(),
+        replace: offset..offset,
+        quote: None,
+        names: Vec::new(),
+        functions: Vec::new(),
+    };
+
+    // An unterminated string means the cursor is inside it: walk the tokens before its quote
+    let before: String = chars.iter().collect();
+    let (tokens, quote_at) = match Lexer::new(&before).tokenize() {
+        Ok(tokens) => (tokens, None),
+        Err(err) if err.kind == ParseErrorKind::UnterminatedString => {
+            let Some(opening) = err.opening else {
+                return context;
+            };
+            let head = before
+                .char_indices()
+                .nth(opening.start)
+                .map_or(before.as_str(), |(byte, _)| &before[..byte]);
+            match Lexer::new(head).tokenize() {
+                Ok(tokens) => (tokens, Some(opening.start)),
+                Err(_) => return context,
+            }
+        }
+        Err(_) => return context,
+    };
+
+    let mut walker = Walker {
+        chars: &chars,
+        frames: Vec::new(),
+        state: State::Start,
+        path: None,
+    };
+    let (last, rest) = match tokens.split_last() {
+        Some((last, rest)) if quote_at.is_none() && last.end == offset && is_word(&last.kind) => {
+            (Some(last), rest)
+        }
+        _ => (None, tokens.as_slice()),
+    };
+    if rest
+        .iter()
+        .try_for_each(|token| walker.step(token))
+        .is_none()
+    {
+        return context;
+    }
+    // A word at the cursor is the prefix being completed where a name is expected
+    if let Some(last) = last {
+        if walker.state == State::Operand || walker.after_dot(last) {
+            context.prefix = chars[last.span()].iter().collect();
+            context.replace = last.span();
+        } else if walker.step(last).is_none() {
+            return context;
+        }
+    }
+
+    if let Some(open) = quote_at {
+        let quote = chars[open];
+        let raw: String = chars[open + 1..].iter().collect();
+        context.quote = Some(quote);
+        context.replace = open + 1..offset;
+        context.prefix = unescape(&raw, quote).unwrap_or(raw);
+        match walker.state {
+            State::Selector => {
+                context.expected = vec![Expected::QuotedName];
+                context.names = walker.selection_names(doc, &context.prefix);
+            }
+            State::Operand => context.expected = vec![Expected::StringLiteral],
+            _ => {}
+        }
+        return context;
+    }
+
+    match walker.state {
+        State::Start => context.expected = vec![Expected::Root],
+        State::Segment if walker.in_expression() => {
+            context.expected = vec![Expected::Segment, Expected::Operator, walker.closing()];
+        }
+        State::Segment => context.expected = vec![Expected::Segment],
+        State::AfterDot { dot, descendant } => {
+            context.expected = vec![Expected::MemberName];
+            if let (Some(doc), Some(query)) = (doc, walker.path_query(dot)) {
+                context.names = member_names(doc, &query, descendant, &context.prefix);
+            }
+        }
+        State::Selector => {
+            context.expected = vec![Expected::Selector];
+            context.names = walker.selection_names(doc, &context.prefix);
+        }
+        State::AfterSelector => context.expected = vec![Expected::ClosingBracket],
+        State::Operand => {
+            context.expected = vec![Expected::Operand];
+            let mut functions: Vec<String> = FunctionRegistry::builtins()
+                .names()
+                .filter(|name| name.starts_with(&context.prefix))
+                .map(str::to_string)
+                .collect();
+            functions.sort();
+            context.functions = functions;
+        }
+        State::AfterOperand => {
+            context.expected = vec![Expected::Operator, walker.closing()];
+        }
+        // Whitespace between a function name and `(` is not allowed
+        State::FunctionName { .. } => {}
+    }
+    context
+}
+
+/// Tokens that may be a partially typed name, function name or keyword
+fn is_word(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Ident(_) | TokenKind::True | TokenKind::False | TokenKind::Null
+    )
+}
+
+/// Decode the escapes in the content of an unterminated string, if they are complete
+fn unescape(raw: &str, quote: char) -> Option<String> {
+    let literal = format!("{quote}{raw}{quote}");
+    let tokens = Lexer::new(&literal).tokenize().ok()?;
+    match tokens.as_slice() {
+        [
+            Token {
+                kind: TokenKind::String(s),
+                ..
+            },
+        ] => Some(s.to_string()),
+        _ => None,
+    }
+}
+
+/// Sorted member names starting with `prefix` of the objects selected by `query`, or of
+/// those objects and all their descendants
+fn member_names(doc: &Value, query: &str, descendant: bool, prefix: &str) -> Vec<String> {
+    let Ok(path) = JsonPath::parse(query) else {
+        return Vec::new();
+    };
+    let mut names = BTreeSet::new();
+    for node in path.query(doc) {
+        collect_names(node, descendant, &mut names);
+    }
+    names
+        .into_iter()
+        .filter(|name| name.starts_with(prefix))
+        .map(str::to_string)
+        .collect()
+}
+
+fn collect_names<'a>(node: &'a Value, descendant: bool, names: &mut BTreeSet<&'a str>) {
+    match node {
+        Value::Object(map) => {
+            names.extend(map.keys().map(String::as_str));
+            if descendant {
+                for child in map.values() {
+                    collect_names(child, true, names);
+                }
+            }
+        }
+        Value::Array(arr) if descendant => {
+            for child in arr {
+                collect_names(child, true, names);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Grammatical position after the tokens walked so far
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    /// Nothing yet, expecting `$`
+    Start,
+    /// After a path root or a complete segment
+    Segment,
+    /// After the `.` or `..` at character `dot`
+    AfterDot { dot: usize, descendant: bool },
+    /// After `[` or `,` in a bracketed selection
+    Selector,
+    /// After a selector or part of a slice
+    AfterSelector,
+    /// Expecting a filter operand
+    Operand,
+    /// After a literal, a group or a function call
+    AfterOperand,
+    /// After a function name ending at character `end`, expecting `(`
+    FunctionName { end: usize },
+}
+
+/// Path being written: its root as an absolute query, and where its segments start
+#[derive(Debug, Clone)]
+struct PathStart {
+    root: String,
+    start: usize,
+}
+
+/// Enclosing construct of the walker
+#[derive(Debug)]
+enum Frame {
+    /// Bracketed selection of `path`, applied to the nodes of `input` (or their descendants)
+    Selection {
+        path: PathStart,
+        input: String,
+        descendant: bool,
+    },
+    /// Filter expression, where `@` stands for the nodes of `current`
+    Filter { current: String },
+    /// Parenthesized group
+    Group,
+    /// Function arguments
+    Args,
+}
+
+struct Walker<'a> {
+    chars: &'a [char],
+    frames: Vec<Frame>,
+    state: State,
+    /// Path being extended by segments, if any
+    path: Option<PathStart>,
+}
+
+impl Walker<'_> {
+    /// Advance over one token; None if it cannot appear here
+    fn step(&mut self, token: &Token) -> Option<()> {
+        use TokenKind as T;
+        self.state = match (self.state, &token.kind) {
+            (State::Start, T::Root) => {
+                self.path = Some(PathStart {
+                    root: "$".to_string(),
+                    start: token.end,
+                });
+                State::Segment
+            }
+            (State::Segment, T::Dot | T::DotDot) => State::AfterDot {
+                dot: token.position,
+                descendant: token.kind == T::DotDot,
+            },
+            (State::Segment, T::BracketOpen) => self.open_selection(token.position, false)?,
+            (
+                State::AfterDot {
+                    dot,
+                    descendant: true,
+                },
+                T::BracketOpen,
+            ) => self.open_selection(dot, true)?,
+            (State::AfterDot { .. }, T::Ident(_) | T::True | T::False | T::Null | T::Wildcard)
+                if self.after_dot(token) =>
+            {
+                State::Segment
+            }
+            (
+                State::Selector,
+                T::String(_) | T::Wildcard | T::Integer(_) | T::Number(..) | T::Colon,
+            ) => State::AfterSelector,
+            (State::AfterSelector, T::Integer(_) | T::Number(..) | T::Colon) => {
+                State::AfterSelector
+            }
+            (State::AfterSelector, T::Comma) => State::Selector,
+            (State::AfterSelector, T::BracketClose) => self.close_selection()?,
+            (State::Selector, T::Question) => {
+                let Some(Frame::Selection {
+                    input, descendant, ..
+                }) = self.frames.last()
+                else {
+                    return None;
+                };
+                let children = if *descendant { "..[*]" } else { "[*]" };
+                let current = format!("{input}{children}");
+                self.frames.push(Frame::Filter { current });
+                State::Operand
+            }
+            (State::Operand, T::At) => {
+                let root = self.frames.iter().rev().find_map(|frame| match frame {
+                    Frame::Filter { current } => Some(current.clone()),
+                    _ => None,
+                })?;
+                self.path = Some(PathStart {
+                    root,
+                    start: token.end,
+                });
+                State::Segment
+            }
+            (State::Operand, T::Root) => {
+                self.path = Some(PathStart {
+                    root: "$".to_string(),
+                    start: token.end,
+                });
+                State::Segment
+            }
+            (
+                State::Operand,
+                T::String(_) | T::Integer(_) | T::Number(..) | T::True | T::False | T::Null,
+            ) => State::AfterOperand,
+            (State::Operand, T::Not) => State::Operand,
+            (State::Operand, T::ParenOpen) => {
+                self.frames.push(Frame::Group);
+                State::Operand
+            }
+            (State::Operand, T::Ident(_)) => State::FunctionName { end: token.end },
+            (State::FunctionName { end }, T::ParenOpen) if token.position == end => {
+                self.frames.push(Frame::Args);
+                State::Operand
+            }
+            (State::Segment | State::AfterOperand, kind) if self.in_expression() => {
+                self.path = None;
+                match (kind, self.frames.last()) {
+                    (
+                        T::Equal
+                        | T::NotEqual
+                        | T::LessThan
+                        | T::LessEq
+                        | T::GreaterThan
+                        | T::GreaterEq
+                        | T::And
+                        | T::Or,
+                        _,
+                    ) => State::Operand,
+                    (T::ParenClose, Some(Frame::Group | Frame::Args)) => {
+                        self.frames.pop();
+                        State::AfterOperand
+                    }
+                    (T::Comma, Some(Frame::Args)) => State::Operand,
+                    (T::Comma, Some(Frame::Filter { .. })) => {
+                        self.frames.pop();
+                        State::Selector
+                    }
+                    (T::BracketClose, Some(Frame::Filter { .. })) => {
+                        self.frames.pop();
+                        self.close_selection()?
+                    }
+                    _ => return None,
+                }
+            }
+            _ => return None,
+        };
+        Some(())
+    }
+
+    /// Whether `token` directly follows a `.` or `..`, as no whitespace is allowed there
+    fn after_dot(&self, token: &Token) -> bool {
+        match self.state {
+            State::AfterDot { dot, descendant } => {
+                token.position == dot + if descendant { 2 } else { 1 }
+            }
+            _ => false,
+        }
+    }
+
+    /// Enter a bracketed selection applied to the path up to character `input_end`
+    fn open_selection(&mut self, input_end: usize, descendant: bool) -> Option<State> {
+        let input = self.path_query(input_end)?;
+        let path = self.path.take()?;
+        self.frames.push(Frame::Selection {
+            path,
+            input,
+            descendant,
+        });
+        Some(State::Selector)
+    }
+
+    /// Leave a bracketed selection, resuming its path
+    fn close_selection(&mut self) -> Option<State> {
+        let Some(Frame::Selection { path, .. }) = self.frames.pop() else {
+            return None;
+        };
+        self.path = Some(path);
+        Some(State::Segment)
+    }
+
+    /// Absolute query for the current path up to character `end`
+    fn path_query(&self, end: usize) -> Option<String> {
+        let path = self.path.as_ref()?;
+        let segments: String = self.chars[path.start..end].iter().collect();
+        Some(format!("{}{}", path.root, segments.trim_end()))
+    }
+
+    /// Member names for the innermost bracketed selection
+    fn selection_names(&self, doc: Option<&Value>, prefix: &str) -> Vec<String> {
+        match (doc, self.frames.last()) {
+            (
+                Some(doc),
+                Some(Frame::Selection {
+                    input, descendant, ..
+                }),
+            ) => member_names(doc, input, *descendant, prefix),
+            _ => Vec::new(),
+        }
+    }
+
+    fn in_expression(&self) -> bool {
+        matches!(
+            self.frames.last(),
+            Some(Frame::Filter { .. } | Frame::Group | Frame::Args)
+        )
+    }
+
+    /// Closing delimiter of the innermost expression construct
+    fn closing(&self) -> Expected {
+        match self.frames.last() {
+            Some(Frame::Group | Frame::Args) => Expected::ClosingParen,
+            _ => Expected::ClosingBracket,
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc() -> Value {
+        json!({
+            "store": {
+                "book": [
+                    {"title": "A", "price": 8, "author": {"name": "X", "born": 1900}},
+                    {"title": "B", "price": 12, "isbn": "y"}
+                ],
+                "bicycle": {"price": 19, "color": "red"}
+            },
+            "stock": 3
+        })
+    }
+
+    fn at_end(input: &str) -> CompletionContext {
+        complete(input, input.chars().count(), Some(&doc()))
+    }
+
+    #[test]
+    fn test_root_and_segments() {
+        assert_eq!(at_end("").expected, vec![Expected::Root]);
+        assert_eq!(at_end("$").expected, vec![Expected::Segment]);
+        assert_eq!(at_end("$.store.book[0]").expected, vec![Expected::Segment]);
+        assert_eq!(
+            at_end("$.store.book[0").expected,
+            vec![Expected::ClosingBracket]
+        );
+        assert_eq!(at_end("$[1:").expected, vec![Expected::ClosingBracket]);
+    }
+
+    #[test]
+    fn test_member_names_after_dot() {
+        let context = at_end("$.st");
+        assert_eq!(context.expected, vec![Expected::MemberName]);
+        assert_eq!(context.prefix, "st");
+        assert_eq!(context.replace, 2..4);
+        assert_eq!(context.names, vec!["stock", "store"]);
+
+        assert_eq!(at_end("$.store.").names, vec!["bicycle", "book"]);
+        assert_eq!(
+            at_end("$.store.book[*].").names,
+            vec!["author", "isbn", "price", "title"]
+        );
+        assert_eq!(
+            at_end("$.store.book[?@.price > 10].").names,
+            vec!["isbn", "price", "title"]
+        );
+        assert!(at_end("$.nothing.").names.is_empty());
+        assert!(complete("$.store.", 8, None).names.is_empty());
+    }
+
+    #[test]
+    fn test_member_names_after_descendant() {
+        let context = at_end("$..");
+        assert_eq!(context.expected, vec![Expected::MemberName]);
+        assert_eq!(
+            context.names,
+            vec![
+                "author", "bicycle", "book", "born", "color", "isbn", "name", "price", "stock",
+                "store", "title"
+            ]
+        );
+        assert_eq!(at_end("$.store..b").names, vec!["bicycle", "book", "born"]);
+        assert_eq!(
+            at_end("$.store..['b").names,
+            vec!["bicycle", "book", "born"]
+        );
+    }
+
+    #[test]
+    fn test_names_in_brackets() {
+        let context = at_end("$.store[");
+        assert_eq!(context.expected, vec![Expected::Selector]);
+        assert_eq!(context.names, vec!["bicycle", "book"]);
+
+        let context = at_end("$.store['book', \"bi");
+        assert_eq!(context.expected, vec![Expected::QuotedName]);
+        assert_eq!(context.quote, Some('"'));
+        assert_eq!(context.prefix, "bi");
+        assert_eq!(context.replace, 17..19);
+        assert_eq!(context.names, vec!["bicycle"]);
+
+        // Escapes in the typed part are decoded
+        assert_eq!(at_end("$['\\u0073t").prefix, "st");
+        assert_eq!(at_end("$['\\u0073t").names, vec!["stock", "store"]);
+        assert_eq!(at_end("$['a\\").prefix, "a\\");
+    }
+
+    #[test]
+    fn test_names_in_filters() {
+        let context = at_end("$.store.book[?@.pr");
+        assert_eq!(context.expected, vec![Expected::MemberName]);
+        assert_eq!(context.names, vec!["price"]);
+
+        assert_eq!(
+            at_end("$.store.book[?@.author.").names,
+            vec!["born", "name"]
+        );
+        assert_eq!(at_end("$.store.book[?@.price < 10 && @.").names.len(), 4);
+        assert_eq!(at_end("$.store.book[?length(@.ti").names, vec!["title"]);
+        assert_eq!(at_end("$.store[?@[?@.ti").names, vec!["title"]);
+        assert_eq!(at_end("$.store..[?@.co").names, vec!["color"]);
+        assert_eq!(at_end("$[?$.store.b").names, vec!["bicycle", "book"]);
+        assert_eq!(at_end("$.store.book[?@['ti").names, vec!["title"]);
+    }
+
+    #[test]
+    fn test_expression_positions() {
+        let context = at_end("$.store.book[?le");
+        assert_eq!(context.expected, vec![Expected::Operand]);
+        assert_eq!(context.prefix, "le");
+        assert_eq!(context.functions, vec!["length"]);
+        assert_eq!(at_end("$[?").functions.len(), 5);
+
+        assert_eq!(
+            at_end("$[?@.price ").expected,
+            vec![
+                Expected::Segment,
+                Expected::Operator,
+                Expected::ClosingBracket
+            ]
+        );
+        assert_eq!(
+            at_end("$[?@.a == 1").expected,
+            vec![Expected::Operator, Expected::ClosingBracket]
+        );
+        assert_eq!(
+            at_end("$[?length(@.a ").expected,
+            vec![
+                Expected::Segment,
+                Expected::Operator,
+                Expected::ClosingParen
+            ]
+        );
+        assert_eq!(
+            at_end("$[?(@.a ").expected,
+            vec![
+                Expected::Segment,
+                Expected::Operator,
+                Expected::ClosingParen
+            ]
+        );
+        assert_eq!(at_end("$[?!").expected, vec![Expected::Operand]);
+        assert_eq!(at_end("$[?@.a, ").expected, vec![Expected::Selector]);
+        assert_eq!(at_end("$[?@.a][").expected, vec![Expected::Selector]);
+
+        let context = at_end("$[?@.title == 'A");
+        assert_eq!(context.expected, vec![Expected::StringLiteral]);
+        assert_eq!(context.prefix, "A");
+        assert!(context.names.is_empty());
+    }
+
+    #[test]
+    fn test_cursor_before_end() {
+        let context = complete("$.store.book", 4, Some(&doc()));
+        assert_eq!(context.prefix, "st");
+        assert_eq!(context.names, vec!["stock", "store"]);
+        assert_eq!(complete("$.a", 100, None).replace, 2..3);
+    }
+
+    #[test]
+    fn test_invalid_prefix() {
+        for input in [
+            "$.a b",
+            "$[?@.a = ",
+            "a",
+            "$[?@.a]]",
+            "$[?length (",
+            "$... ",
+            "$. a",
+        ] {
+            assert!(at_end(input).expected.is_empty(), "input {input}");
+        }
+    }
+}
@@ -0,0 +1,194 @@
+//! Query complexity metrics for gating untrusted queries
+
+use crate::ast::{Expr, JsonPath, Segment, Selector};
+
+/// Structural metrics of a parsed query.
+///
+/// All counts include segments and selectors inside filter sub-paths (`@.a.b`, `$.x`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Complexity {
+    /// Total number of segments
+    pub segments: usize,
+    /// Number of descendant (`..`) segments
+    pub descendant_segments: usize,
+    /// Largest number of selectors in a single segment
+    pub max_selectors_per_segment: usize,
+    /// Number of filter selectors
+    pub filters: usize,
+    /// Deepest filter nesting (0 = no filters, 1 = `[?...]`, 2 = `[?@[?...]]`, ...)
+    pub max_filter_depth: usize,
+    /// Number of function calls
+    pub function_calls: usize,
+    /// Number of regex function calls (`match`, `search`)
+    pub regex_calls: usize,
+}
+
+/// Upper bounds checked by [`Complexity::exceeds`]. `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ComplexityLimits {
+    pub max_segments: Option<usize>,
+    pub max_descendant_segments: Option<usize>,
+    pub max_selectors_per_segment: Option<usize>,
+    pub max_filters: Option<usize>,
+    pub max_filter_depth: Option<usize>,
+    pub max_function_calls: Option<usize>,
+    pub max_regex_calls: Option<usize>,
+}
+
+impl Complexity {
+    /// Compute the metrics of a query
+    pub fn of(path: &JsonPath) -> Self {
+        let mut complexity = Self::default();
+        complexity.visit_segments(&path.segments, 0);
+        complexity
+    }
+
+    /// True if any metric is above its limit
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::{ComplexityLimits, JsonPath};
+    ///
+    /// // At most one `..` and no nested filters
+    /// let limits = ComplexityLimits {
+    ///     max_descendant_segments: Some(1),
+    ///     max_filter_depth: Some(1),
+    ///     ..Default::default()
+    /// };
+    /// let ok = JsonPath::parse("$..book[?@.price < 10]").unwrap();
+    /// let nested = JsonPath::parse("$[?@[?@.a]]").unwrap();
+    /// assert!(!ok.complexity().exceeds(&limits));
+    /// assert!(nested.complexity().exceeds(&limits));
+    /// ```
+    pub fn exceeds(&self, limits: &ComplexityLimits) -> bool {
+        let over = |value: usize, limit: Option<usize>| limit.is_some_and(|max| value > max);
+        over(self.segments, limits.max_segments)
+            || over(self.descendant_segments, limits.max_descendant_segments)
+            || over(
+                self.max_selectors_per_segment,
+                limits.max_selectors_per_segment,
+            )
+            || over(self.filters, limits.max_filters)
+            || over(self.max_filter_depth, limits.max_filter_depth)
+            || over(self.function_calls, limits.max_function_calls)
+            || over(self.regex_calls, limits.max_regex_calls)
+    }
+
+    fn visit_segments(&mut self, segments: &[Segment], filter_depth: usize) {
+        for segment in segments {
+            let selectors = match segment {
+                Segment::Child(selectors) => selectors,
+                Segment::Descendant(selectors) => {
+                    self.descendant_segments += 1;
+                    selectors
+                }
+            };
+            self.segments += 1;
+            self.max_selectors_per_segment = self.max_selectors_per_segment.max(selectors.len());
+
+            for selector in selectors {
+                if let Selector::Filter(expr) = selector {
+                    let depth = filter_depth + 1;
+                    self.filters += 1;
+                    self.max_filter_depth = self.max_filter_depth.max(depth);
+                    self.visit_expr(expr, depth);
+                }
+            }
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr, filter_depth: usize) {
+        match expr {
+            Expr::Path { segments, .. } => self.visit_segments(segments, filter_depth),
+            Expr::Comparison { left, right, .. }
+            | Expr::Logical { left, right, .. }
+            | Expr::Arithmetic { left, right, .. } => {
+                self.visit_expr(left, filter_depth);
+                self.visit_expr(right, filter_depth);
+            }
+            Expr::Not(inner) => self.visit_expr(inner, filter_depth),
+            Expr::FunctionCall { function, args, .. } => {
+                self.function_calls += 1;
+                if matches!(function.name(), "match" | "search" | "imatch" | "isearch") {
+                    self.regex_calls += 1;
+                }
+                for arg in args {
+                    self.visit_expr(arg, filter_depth);
+                }
+            }
+            Expr::CurrentNode | Expr::RootNode | Expr::Literal(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn complexity(query: &str) -> Complexity {
+        JsonPath::parse(query).unwrap().complexity()
+    }
+
+    #[test]
+    fn test_root_only() {
+        assert_eq!(complexity("$"), Complexity::default());
+    }
+
+    #[test]
+    fn test_segments_and_selectors() {
+        let c = complexity("$.a..b[0,1,2]..*");
+        assert_eq!(c.segments, 4);
+        assert_eq!(c.descendant_segments, 2);
+        assert_eq!(c.max_selectors_per_segment, 3);
+        assert_eq!(c.filters, 0);
+        assert_eq!(c.max_filter_depth, 0);
+    }
+
+    #[test]
+    fn test_filter_sub_paths_are_counted() {
+        // 1 top-level segment + @.a.b (2) + $..c[0] (2)
+        let c = complexity("$[?@.a.b && $..c[0]]");
+        assert_eq!(c.segments, 5);
+        assert_eq!(c.descendant_segments, 1);
+        assert_eq!(c.filters, 1);
+        assert_eq!(c.max_filter_depth, 1);
+    }
+
+    #[test]
+    fn test_nested_filter_depth() {
+        let c = complexity("$[?@[?@[?@.x]]][?@.y]");
+        assert_eq!(c.filters, 4);
+        assert_eq!(c.max_filter_depth, 3);
+    }
+
+    #[test]
+    fn test_function_calls() {
+        let c =
+            complexity(r#"$[?match(@.a, "x") && search(@.b, "y") && length(@.c) > count(@.*)]"#);
+        assert_eq!(c.function_calls, 4);
+        assert_eq!(c.regex_calls, 2);
+    }
+
+    #[test]
+    fn test_exceeds() {
+        let c = complexity("$..a..b[?match(@, 'x')]");
+        assert!(!c.exceeds(&ComplexityLimits::default()));
+        let limits = ComplexityLimits {
+            max_descendant_segments: Some(2),
+            max_regex_calls: Some(1),
+            ..Default::default()
+        };
+        assert!(!c.exceeds(&limits));
+        let limits = ComplexityLimits {
+            max_descendant_segments: Some(1),
+            ..Default::default()
+        };
+        assert!(c.exceeds(&limits));
+        let limits = ComplexityLimits {
+            max_regex_calls: Some(0),
+            ..Default::default()
+        };
+        assert!(c.exceeds(&limits));
+    }
+}
@@ -0,0 +1,378 @@
+//! Syntax errors reported by the lexer and parser, and evaluation errors
+
+use std::ops::Range;
+
+/// Category of a syntax error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// Leading or trailing whitespace, or whitespace after `.`/`..` or before `(`
+    InvalidWhitespace,
+    /// The query does not start with `$`
+    MissingRoot,
+    /// A character that cannot start any token
+    UnexpectedCharacter,
+    /// A token that is not valid at this position
+    UnexpectedToken,
+    /// The input ended in the middle of a construct
+    UnexpectedEndOfInput,
+    /// A `[` without a matching `]`
+    UnclosedBracket,
+    /// A `(` without a matching `)`
+    UnclosedParen,
+    /// A string literal without a closing quote
+    UnterminatedString,
+    /// An invalid escape sequence in a string literal
+    InvalidEscape,
+    /// An unescaped control character in a string literal
+    ControlCharacter,
+    /// A malformed number literal
+    InvalidNumber,
+    /// An index or slice bound that is not an integer, or is `-0`
+    InvalidIndex,
+    /// An index or slice bound outside the I-JSON range `-(2^53-1)..=2^53-1`
+    IndexOutOfRange,
+    /// A filter consisting only of a literal, e.g. `[?1]`
+    LiteralOnlyFilter,
+    /// A literal used as operand of `&&` or `||`
+    LiteralLogicalOperand,
+    /// A comparison operand that can produce more than one node
+    NonSingularComparison,
+    /// A function result used where its type is not allowed, e.g. `[?length(@)]` or
+    /// `match(@, 'a') == true`
+    FunctionResultType,
+    /// Arithmetic used as a test instead of being compared, e.g. `[?@.a + 1]`, or an
+    /// arithmetic operand that is not a singular value, e.g. `@.* + 1`
+    ArithmeticType,
+    /// A function called with the wrong number of arguments
+    FunctionArity,
+    /// A function argument of the wrong type
+    FunctionArgumentType,
+    /// A call to a function that is not defined
+    UnknownFunction,
+    /// A `match()`/`search()` pattern literal that is not a valid regular expression, with
+    /// [`ParseOptions::reject_invalid_regex`](crate::ParseOptions::reject_invalid_regex)
+    InvalidRegex,
+    /// The query exceeds a limit set in [`ParseOptions`](crate::ParseOptions)
+    LimitExceeded,
+}
+
+/// Syntax error with the character range of the offending input
+///
+/// Spans count characters, not bytes, so positions read naturally for non-ASCII queries.
+/// Use [`ParseError::byte_span`] to slice the input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub message: String,
+    /// Character offsets of the offending token(s); empty at the end of input
+    pub span: Range<usize>,
+    /// Character offsets of the unclosed `[`, `(` or quote, for unclosed delimiter errors
+    pub opening: Option<Range<usize>>,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, message: impl Into<String>, span: Range<usize>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            span,
+            opening: None,
+        }
+    }
+
+    /// Attach the span of the opening delimiter that was never closed
+    pub fn with_opening(mut self, opening: Range<usize>) -> Self {
+        self.opening = Some(opening);
+        self
+    }
+
+    /// Character offset where the error starts
+    pub fn position(&self) -> usize {
+        self.span.start
+    }
+
+    /// Byte range of `span` in `input`, the query that was parsed, for slicing and
+    /// highlighting; offsets past the end map to `input.len()`
+    pub fn byte_span(&self, input: &str) -> Range<usize> {
+        byte_range(input, &self.span)
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at position {}, {}", self.span.start, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Deviation from RFC 9535 accepted by a lenient parse, see [`ParseOptions`]
+///
+/// [`ParseOptions`]: crate::ParseOptions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseWarning {
+    pub message: String,
+    /// Character offsets of the relaxed input
+    pub span: Range<usize>,
+}
+
+impl ParseWarning {
+    /// Byte range of `span` in `input`, see [`ParseError::byte_span`]
+    pub fn byte_span(&self, input: &str) -> Range<usize> {
+        byte_range(input, &self.span)
+    }
+}
+
+impl std::fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "at position {}, {}", self.span.start, self.message)
+    }
+}
+
+/// Error from [`FunctionRegistry::register`](crate::FunctionRegistry::register): a name that
+/// a query can't call
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidFunctionName(pub String);
+
+impl std::fmt::Display for InvalidFunctionName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid function name '{}': expected a lowercase letter followed by lowercase letters, digits or '_'",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidFunctionName {}
+
+/// Error from evaluating a query with [`EvalOptions`](crate::EvalOptions)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EvalError {
+    /// Evaluation was stopped by a limit after `visited` node visits
+    BudgetExceeded { visited: usize, limit: BudgetLimit },
+}
+
+/// The [`EvalOptions`](crate::EvalOptions) limit that stopped an evaluation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BudgetLimit {
+    /// [`EvalOptions::max_nodes`](crate::EvalOptions::max_nodes)
+    MaxNodes,
+    /// [`EvalOptions::deadline`](crate::EvalOptions::deadline)
+    Deadline,
+    /// [`EvalOptions::max_results`](crate::EvalOptions::max_results)
+    MaxResults,
+}
+
+impl std::fmt::Display for EvalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EvalError::BudgetExceeded { visited, limit } => {
+                let limit = match limit {
+                    BudgetLimit::MaxNodes => "node limit",
+                    BudgetLimit::Deadline => "deadline",
+                    BudgetLimit::MaxResults => "result limit",
+                };
+                write!(
+                    f,
+                    "evaluation exceeded its {limit} after visiting {visited} nodes"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// The two lines that show `span` of `query` under an error message: the line of `query`
+/// where the span starts, and a marker line with `^` under the start of the span and `~`
+/// under the rest of it
+///
+/// Columns count display width, so that the marker lines up under wide characters such as
+/// CJK, which take two columns. A line wider than `width` columns is cut to a window around
+/// the span, with `…` in place of what was cut.
+///
+/// ```
+/// use jpp_core::parser::Parser;
+/// use jpp_core::render_span;
+///
+/// let query = "$[?foo(@.a)]";
+/// let error = Parser::parse(query).unwrap_err();
+/// let [line, marker] = render_span(query, &error.span, 80);
+/// assert_eq!(format!("{line}\n{marker}"), "$[?foo(@.a)]\n   ^~~");
+/// ```
+pub fn render_span(query: &str, span: &Range<usize>, width: usize) -> [String; 2] {
+    // The line where the span starts, and where it starts in that line
+    let mut start = span.start;
+    let mut lines = query.split('\n').peekable();
+    let mut line = "";
+    while let Some(next) = lines.next() {
+        line = next;
+        let len = next.chars().count();
+        if start <= len || lines.peek().is_none() {
+            break;
+        }
+        start -= len + 1;
+    }
+    let chars: Vec<char> = line
+        .chars()
+        .map(|c| if c == '\t' { ' ' } else { c })
+        .collect();
+    let len = chars.len();
+    let start = start.min(len);
+    let end = (start + span.len()).min(len);
+    // Column of each character boundary
+    let mut columns = vec![0];
+    for &c in &chars {
+        columns.push(columns[columns.len() - 1] + char_width(c));
+    }
+
+    // A marker at the end of the line takes a column past it
+    let at_end = usize::from(start == len);
+    let (mut first, mut last) = (0, len);
+    if columns[len] + at_end > width {
+        let width = width.max(MIN_WIDTH);
+        // At least a third of the window goes to what precedes the span, and all of what
+        // the rest of the line leaves
+        let before = (width / 3).max(width.saturating_sub(columns[len] + at_end - columns[start]));
+        if columns[start] > before {
+            first = (0..start)
+                .find(|&i| columns[start] - columns[i] < before)
+                .unwrap_or(start);
+        }
+        let cut = usize::from(first > 0);
+        if cut + columns[len] - columns[first] + at_end > width {
+            last = (start + 1..len)
+                .rev()
+                .find(|&i| cut + columns[i] - columns[first] < width)
+                .unwrap_or(len.min(start + 1));
+        }
+    }
+
+    let mut shown = String::new();
+    if first > 0 {
+        shown.push('…');
+    }
+    shown.extend(&chars[first..last]);
+    if last < len {
+        shown.push('…');
+    }
+    let indent = usize::from(first > 0) + columns[start] - columns[first];
+    let marked = columns[end.min(last)].saturating_sub(columns[start]);
+    let marker = format!(
+        "{}^{}",
+        " ".repeat(indent),
+        "~".repeat(marked.saturating_sub(1))
+    );
+    [shown, marker]
+}
+
+/// Narrowest window [`render_span`] cuts a long line to
+const MIN_WIDTH: usize = 20;
+
+/// Columns `text` takes in a terminal, counted as [`render_span`] counts them, for aligning
+/// text that holds wide characters
+///
+/// ```
+/// assert_eq!(jpp_core::display_width("$['日本語']"), 11);
+/// ```
+pub fn display_width(text: &str) -> usize {
+    text.chars().map(char_width).sum()
+}
+
+/// Columns `c` takes in a terminal: none for combining marks and zero-width characters, two
+/// for East Asian wide and fullwidth characters and most emoji, else one
+fn char_width(c: char) -> usize {
+    match u32::from(c) {
+        0x0300..=0x036F
+        | 0x1AB0..=0x1AFF
+        | 0x1DC0..=0x1DFF
+        | 0x200B..=0x200F
+        | 0x20D0..=0x20FF
+        | 0xFE00..=0xFE0F
+        | 0xFE20..=0xFE2F => 0,
+        0x1100..=0x115F
+        | 0x2E80..=0x303E
+        | 0x3041..=0x33FF
+        | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF
+        | 0xA000..=0xA4CF
+        | 0xAC00..=0xD7A3
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F
+        | 0x1F900..=0x1F9FF
+        | 0x20000..=0x3FFFD => 2,
+        _ => 1,
+    }
+}
+
+/// Byte range in `input` of the character range `span`
+fn byte_range(input: &str, span: &Range<usize>) -> Range<usize> {
+    let offset = |chars: usize| {
+        input
+            .char_indices()
+            .nth(chars)
+            .map_or(input.len(), |(byte, _)| byte)
+    };
+    offset(span.start)..offset(span.end)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn render(query: &str, span: Range<usize>, width: usize) -> String {
+        render_span(query, &span, width).join("\n")
+    }
+
+    #[test]
+    fn test_render_span() {
+        assert_eq!(
+            render("$.a[?@.x >]", 10..11, 80),
+            "$.a[?@.x >]\n          ^"
+        );
+        assert_eq!(render("$.a[", 4..4, 80), "$.a[\n    ^");
+        assert_eq!(render("$[?foo(@)]", 3..6, 80), "$[?foo(@)]\n   ^~~");
+        // The line of a multi-line query where the span starts
+        assert_eq!(
+            render("$[?@.a == 1 &&\n  @.b ==]", 23..24, 80),
+            "  @.b ==]\n        ^"
+        );
+    }
+
+    #[test]
+    fn test_wide_characters() {
+        assert_eq!(
+            render("$['日本語'].x[?", 12..12, 80),
+            "$['日本語'].x[?\n               ^"
+        );
+        assert_eq!(render("$['日本語']x", 3..6, 80), "$['日本語']x\n   ^~~~~~");
+        assert_eq!(
+            render("$['e\u{301}'] x", 8..9, 80),
+            "$['e\u{301}'] x\n       ^"
+        );
+        assert_eq!(
+            render("$[?@.a ==\t]", 10..11, 80),
+            "$[?@.a == ]\n          ^"
+        );
+        assert_eq!(display_width("e\u{301}日本"), 5);
+    }
+
+    #[test]
+    fn test_window() {
+        let query = format!("$.a{}[?@.x == ]", ".b".repeat(30));
+        assert_eq!(
+            render(&query, 72..73, 40),
+            format!("…{}[?@.x == ]\n{}^", "b.".repeat(14) + "b", " ".repeat(39))
+        );
+        // Cut on both sides, with the window never narrower than MIN_WIDTH
+        let query = format!("$.a{}[?@.x ==]{}", ".b".repeat(30), ".c".repeat(30));
+        assert_eq!(render(&query, 71..72, 10), "….x ==].c.c.c.c.c.c…\n      ^");
+        let [line, _] = render_span(&query, &(71..72), 30);
+        assert_eq!(line.chars().count(), 30);
+    }
+}
@@ -1,106 +1,276 @@
 //! Evaluator for JSONPath queries
 
-use crate::ast::{CompOp, Expr, JsonPath, Literal, LogicalOp, Segment, Selector};
-use regex::Regex;
-use serde_json::Value;
+use crate::ast::{ArithOp, CompOp, CompiledRegex, Expr, JsonPath, LogicalOp, Segment, Selector};
+use crate::error::{BudgetLimit, EvalError};
+use crate::function::{CustomFn, ExprValue, Function, FunctionType};
+use crate::iregexp;
+use crate::path::{NormalizedPath, PathElement};
+use regex::{Regex, RegexBuilder};
+use serde_json::{Number, Value};
 use smallvec::{SmallVec, smallvec};
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::sync::LazyLock;
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::ControlFlow;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
 
-// Static Bool/Null values for zero-copy filter evaluation.
-// These avoid allocating new Value instances for comparison results and common literals.
-static TRUE_VAL: LazyLock<Value> = LazyLock::new(|| Value::Bool(true));
-static FALSE_VAL: LazyLock<Value> = LazyLock::new(|| Value::Bool(false));
-static NULL_VAL: LazyLock<Value> = LazyLock::new(|| Value::Null);
+/// Result of an emit callback that keeps the walk going
+const CONTINUE: ControlFlow<()> = ControlFlow::Continue(());
 
 /// A list of JSON value references, optimized for the common case of 1 element.
 /// Uses stack allocation for up to 1 element, heap allocation for more.
 type NodeList<'a> = SmallVec<[&'a Value; 1]>;
 
+/// Default number of compiled patterns kept per thread
+pub const DEFAULT_REGEX_CACHE_CAPACITY: usize = 256;
+
+static REGEX_CACHE_CAPACITY: AtomicUsize = AtomicUsize::new(DEFAULT_REGEX_CACHE_CAPACITY);
+
 // Thread-local cache for compiled regex patterns.
 // Regex compilation is expensive (~10μs+), but the compiled Regex is cheap to clone (Arc-based).
 // This cache dramatically improves performance for queries like $[?match(@.name, "pattern")]
 // executed against large arrays - pattern is compiled once instead of per element.
 thread_local! {
-    static REGEX_CACHE: RefCell<HashMap<String, Regex>> = RefCell::new(HashMap::new());
+    static REGEX_CACHE: RefCell<RegexCache> = RefCell::new(RegexCache::default());
+}
+
+/// LRU cache of compiled patterns, keyed by the user pattern as written so hits skip the
+/// I-Regexp transform. Anchored (match) and unanchored (search) regexes are compiled
+/// separately, as are strict I-Regexp and case-insensitive ones, so each mode has its own
+/// map. Invalid patterns are cached as `None`.
+#[derive(Default)]
+struct RegexCache {
+    entries: [HashMap<String, (Option<Regex>, u64)>; 8],
+    /// Mode and pattern of each entry by the tick of its last use, least recent first
+    recency: BTreeMap<u64, (usize, String)>,
+    tick: u64,
+    /// Lookups answered from the cache, for [`EvalStats`]
+    hits: usize,
+    /// Patterns compiled on a miss, for [`EvalStats`]
+    compiles: usize,
+}
+
+impl RegexCache {
+    fn len(&self) -> usize {
+        self.recency.len()
+    }
+
+    fn get_or_compile(
+        &mut self,
+        pattern: &str,
+        full_match: bool,
+        strict: bool,
+        case_insensitive: bool,
+        capacity: usize,
+    ) -> Option<Regex> {
+        // The capacity may have been lowered since the last lookup
+        self.trim(capacity);
+        self.tick += 1;
+        let mode =
+            usize::from(full_match) | usize::from(strict) << 1 | usize::from(case_insensitive) << 2;
+        if let Some((re, used)) = self.entries[mode].get_mut(pattern) {
+            if let Some(entry) = self.recency.remove(used) {
+                self.recency.insert(self.tick, entry);
+            }
+            *used = self.tick;
+            self.hits += 1;
+            return re.clone();
+        }
+
+        self.compiles += 1;
+        let re = compile_iregexp(pattern, full_match, strict, case_insensitive);
+        if capacity == 0 {
+            return re;
+        }
+        self.trim(capacity - 1);
+        self.entries[mode].insert(pattern.to_string(), (re.clone(), self.tick));
+        self.recency.insert(self.tick, (mode, pattern.to_string()));
+        re
+    }
+
+    /// Evict the least recently used entries until at most `capacity` are left
+    fn trim(&mut self, capacity: usize) {
+        while self.len() > capacity {
+            let Some((_, (mode, key))) = self.recency.pop_first() else {
+                break;
+            };
+            self.entries[mode].remove(&key);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.iter_mut().for_each(HashMap::clear);
+        self.recency.clear();
+    }
+}
+
+/// Compile a match()/search() pattern after the I-Regexp transform. With `strict`, patterns
+/// outside the I-Regexp grammar don't compile. `case_insensitive` sets the regex flag, which
+/// applies simple case folding, instead of rewriting the pattern.
+pub(crate) fn compile_iregexp(
+    pattern: &str,
+    full_match: bool,
+    strict: bool,
+    case_insensitive: bool,
+) -> Option<Regex> {
+    let transformed = if strict {
+        iregexp::translate(pattern).ok()?
+    } else {
+        transform_pattern_for_iregexp(pattern)
+    };
+    let final_pattern = if full_match {
+        format!("^(?:{})$", transformed)
+    } else {
+        transformed
+    };
+    RegexBuilder::new(&final_pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+        .ok()
 }
 
 /// Get a cached regex or compile and cache a new one.
 /// Returns None if the pattern is invalid.
-fn get_or_compile_regex(pattern: &str) -> Option<Regex> {
+fn get_or_compile_regex(
+    pattern: &str,
+    full_match: bool,
+    strict: bool,
+    case_insensitive: bool,
+) -> Option<Regex> {
+    let capacity = REGEX_CACHE_CAPACITY.load(Ordering::Relaxed);
     REGEX_CACHE.with(|cache| {
-        let mut cache = cache.borrow_mut();
-        if let Some(re) = cache.get(pattern) {
-            return Some(re.clone());
-        }
-        match Regex::new(pattern) {
-            Ok(re) => {
-                cache.insert(pattern.to_string(), re.clone());
-                Some(re)
-            }
-            Err(_) => None,
-        }
+        cache
+            .borrow_mut()
+            .get_or_compile(pattern, full_match, strict, case_insensitive, capacity)
     })
 }
 
+/// Drop all compiled patterns cached by match()/search() on the current thread
+pub fn clear_regex_cache() {
+    REGEX_CACHE.with(|cache| cache.borrow_mut().clear());
+}
+
+/// Set how many compiled patterns each thread keeps; `0` disables caching.
+///
+/// Applies to every thread. The current thread's cache is trimmed right away; other threads
+/// evict their least recently used entries on their next match()/search() lookup.
+pub fn set_regex_cache_capacity(capacity: usize) {
+    REGEX_CACHE_CAPACITY.store(capacity, Ordering::Relaxed);
+    REGEX_CACHE.with(|cache| cache.borrow_mut().trim(capacity));
+}
+
+/// Current per-thread capacity of the regex cache
+pub fn regex_cache_capacity() -> usize {
+    REGEX_CACHE_CAPACITY.load(Ordering::Relaxed)
+}
+
+/// True if a match()/search() pattern compiles, i.e. the function can ever return true
+pub(crate) fn regex_compiles(pattern: &str) -> bool {
+    get_or_compile_regex(pattern, false, false, false).is_some()
+}
+
 /// Transform regex pattern for I-Regexp compliance (RFC 9535).
 /// Per RFC 9535, `.` should NOT match \r (U+000D) in addition to \n which Rust already excludes.
 /// Note: Unlike ECMAScript, I-Regexp's `.` DOES match \u2028 and \u2029.
+///
+/// Only `.` outside bracket classes is rewritten, following the regex crate's class syntax:
+/// escapes are copied as pairs, classes nest (`[a[b]]`), `[:alpha:]` inside a class is not a
+/// nested class, and `]` right after `[` or `[^` is a literal member.
 fn transform_pattern_for_iregexp(pattern: &str) -> String {
     let mut result = String::with_capacity(pattern.len() * 2);
-    let mut chars = pattern.chars();
-    let mut in_char_class = false;
+    let mut chars = pattern.chars().peekable();
+    // Number of open bracket classes, counting nested ones
+    let mut class_depth = 0usize;
+    // Set right after `[` or `[^`, where `]` is a literal
+    let mut class_start = false;
 
     while let Some(c) = chars.next() {
-        if c == '\\' {
-            // Escaped character - pass through as-is
-            result.push(c);
-            if let Some(next) = chars.next() {
-                result.push(next);
+        let at_class_start = std::mem::take(&mut class_start);
+        match c {
+            '\\' => {
+                // Escaped character - pass through as-is
+                result.push(c);
+                if let Some(next) = chars.next() {
+                    result.push(next);
+                }
+            }
+            '[' => {
+                result.push(c);
+                let ascii_class = (class_depth > 0)
+                    .then(|| ascii_class_len(chars.clone()))
+                    .flatten();
+                if let Some(len) = ascii_class {
+                    result.extend(chars.by_ref().take(len));
+                } else {
+                    class_depth += 1;
+                    if let Some(caret) = chars.next_if_eq(&'^') {
+                        result.push(caret);
+                    }
+                    class_start = true;
+                }
+            }
+            ']' if class_depth > 0 && !at_class_start => {
+                class_depth -= 1;
+                result.push(c);
             }
-            continue;
-        }
-
-        if c == '[' && !in_char_class {
-            in_char_class = true;
-            result.push(c);
-        } else if c == ']' && in_char_class {
-            in_char_class = false;
-            result.push(c);
-        } else if c == '.' && !in_char_class {
             // Replace unescaped . outside character class with I-Regexp compliant class
             // Excludes: \n (U+000A - already excluded by Rust), \r (U+000D)
-            result.push_str("[^\\r\\n]");
-        } else {
-            result.push(c);
+            '.' if class_depth == 0 => result.push_str("[^\\r\\n]"),
+            _ => result.push(c),
         }
     }
 
     result
 }
 
+/// Length of an ASCII class like `:alpha:]` or `:^digit:]` following a `[` inside a class
+fn ascii_class_len(mut chars: impl Iterator<Item = char>) -> Option<usize> {
+    if chars.next() != Some(':') {
+        return None;
+    }
+    let mut len = 1;
+    for c in chars.by_ref() {
+        len += 1;
+        match c {
+            '^' if len == 2 => {}
+            'a'..='z' => {}
+            ':' => break,
+            _ => return None,
+        }
+    }
+    (chars.next() == Some(']')).then_some(len + 1)
+}
+
 /// Result of evaluating an expression (uses references to avoid cloning)
+///
+/// The variants mirror the RFC 9535 function types: `Value`/`OwnedValue`/`Nothing` are
+/// ValueType, the node lists are NodesType, and `Logical` is LogicalType.
 #[derive(Debug)]
 enum ExprResult<'a> {
     /// A reference to an existing JSON value
     Value(&'a Value),
-    /// An owned JSON value (for literals, computed booleans)
+    /// An owned JSON value (computed function results)
     OwnedValue(Value),
     /// Multiple values from a path query (references)
     NodeList(NodeList<'a>),
-    /// No result (missing property, failed comparison, etc.)
+    /// Multiple owned values (NodesType results of user-defined functions)
+    OwnedNodeList(Vec<Value>),
+    /// No result (missing property, function argument of the wrong kind, etc.)
     Nothing,
+    /// A test result: comparisons, `&&`, `||`, `!` and match()/search()
+    Logical(bool),
 }
 
 impl<'a> ExprResult<'a> {
-    /// Check if the result is truthy per RFC 9535 rules
+    /// Truth value in a test expression: a logical result as is, anything else an existence
+    /// test. RFC 9535 gives values no truthiness, so `0` and `""` exist like any value.
     #[inline]
     fn is_truthy(&self) -> bool {
         match self {
+            ExprResult::Logical(b) => *b,
             ExprResult::NodeList(list) => !list.is_empty(),
-            ExprResult::Value(v) => value_is_truthy(v),
-            ExprResult::OwnedValue(v) => value_is_truthy(v),
+            ExprResult::OwnedNodeList(list) => !list.is_empty(),
+            ExprResult::Value(_) | ExprResult::OwnedValue(_) => true,
             ExprResult::Nothing => false,
         }
     }
@@ -112,139 +282,710 @@ impl<'a> ExprResult<'a> {
         match self {
             ExprResult::Value(_) | ExprResult::OwnedValue(_) => true,
             ExprResult::NodeList(list) => list.len() <= 1,
-            ExprResult::Nothing => true,
+            ExprResult::OwnedNodeList(list) => list.len() <= 1,
+            ExprResult::Nothing | ExprResult::Logical(_) => true,
         }
     }
 
-    /// Convert to a single value for comparison (takes first if NodeList)
+    /// The nodes of a nodelist, or the value as a single node
+    #[cfg(feature = "extended-functions")]
+    fn nodes(&self) -> impl Iterator<Item = &Value> {
+        let (borrowed, owned): (&[&Value], &[Value]) = match self {
+            ExprResult::Value(v) => (std::slice::from_ref(v), &[]),
+            ExprResult::OwnedValue(v) => (&[], std::slice::from_ref(v)),
+            ExprResult::NodeList(list) => (list, &[]),
+            ExprResult::OwnedNodeList(list) => (&[], list),
+            ExprResult::Nothing | ExprResult::Logical(_) => (&[], &[]),
+        };
+        borrowed.iter().copied().chain(owned)
+    }
+
+    /// Convert to a single value for comparison (takes first if NodeList). Logical results
+    /// are not values.
     #[inline]
     fn to_value(&self) -> Option<&Value> {
         match self {
             ExprResult::Value(v) => Some(v),
             ExprResult::OwnedValue(v) => Some(v),
             ExprResult::NodeList(list) => list.first().copied(),
-            ExprResult::Nothing => None,
+            ExprResult::OwnedNodeList(list) => list.first(),
+            ExprResult::Nothing | ExprResult::Logical(_) => None,
         }
     }
 }
 
-/// Check if a JSON value is truthy
-#[inline]
-fn value_is_truthy(v: &Value) -> bool {
-    match v {
-        Value::Null => false,
-        Value::Bool(b) => *b,
-        Value::Number(n) => n.as_f64().is_some_and(|f| f != 0.0),
-        Value::String(s) => !s.is_empty(),
-        Value::Array(arr) => !arr.is_empty(),
-        Value::Object(obj) => !obj.is_empty(),
+/// Evaluate a JSONPath query against a JSON value
+pub fn evaluate<'a>(path: &JsonPath, root: &'a Value) -> Vec<&'a Value> {
+    let mut results = Vec::new();
+    let _ = for_each_node(
+        &path.segments,
+        &mut (),
+        root,
+        root,
+        &Unmetered,
+        &mut |_, node| {
+            results.push(node);
+            CONTINUE
+        },
+    );
+    results
+}
+
+/// Evaluate a JSONPath query, stopping as soon as `limit` nodes have been selected
+///
+/// The result is the first `limit` nodes of [`evaluate`]; the rest of the document is not
+/// walked.
+pub fn evaluate_limit<'a>(path: &JsonPath, root: &'a Value, limit: usize) -> Vec<&'a Value> {
+    let mut results = Vec::new();
+    if limit == 0 {
+        return results;
+    }
+    let _ = for_each_node(
+        &path.segments,
+        &mut (),
+        root,
+        root,
+        &Unmetered,
+        &mut |_, node| {
+            results.push(node);
+            if results.len() < limit {
+                CONTINUE
+            } else {
+                ControlFlow::Break(())
+            }
+        },
+    );
+    results
+}
+
+/// Number of nodes a JSONPath query selects, counted without collecting them
+pub fn count(path: &JsonPath, root: &Value) -> usize {
+    let mut count = 0;
+    let _ = for_each_node(
+        &path.segments,
+        &mut (),
+        root,
+        root,
+        &Unmetered,
+        &mut |_, _| {
+            count += 1;
+            CONTINUE
+        },
+    );
+    count
+}
+
+/// Limits on the work done by [`evaluate_with_options`]; the default sets none
+///
+/// A node counts as visited each time a segment takes it as input, a descendant segment
+/// walks through it, a filter tests it, or it is added to the result. Nodes visited by
+/// queries inside filter expressions are not counted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalOptions {
+    /// Maximum number of node visits
+    pub max_nodes: Option<usize>,
+    /// Instant after which evaluation stops. The clock is read every
+    /// [`DEADLINE_CHECK_INTERVAL`] visits.
+    pub deadline: Option<Instant>,
+    /// Maximum number of nodes in the result, duplicates included
+    pub max_results: Option<usize>,
+}
+
+/// Number of node visits between two reads of the clock for [`EvalOptions::deadline`]
+pub const DEADLINE_CHECK_INTERVAL: usize = 256;
+
+/// Evaluate a JSONPath query within the limits of `options`
+///
+/// Fails with [`EvalError::BudgetExceeded`] instead of returning partial results. Without
+/// limits this is [`evaluate`], with no counting overhead.
+pub fn evaluate_with_options<'a>(
+    path: &JsonPath,
+    root: &'a Value,
+    options: &EvalOptions,
+) -> Result<Vec<&'a Value>, EvalError> {
+    if *options == EvalOptions::default() {
+        return Ok(evaluate(path, root));
+    }
+    let mut results = Vec::new();
+    for_each_budgeted(path, &mut (), root, options, |_, node| results.push(node))?;
+    Ok(results)
+}
+
+/// [`evaluate_located`] within the limits of `options`, failing like
+/// [`evaluate_with_options`]
+pub fn evaluate_located_with_options<'a>(
+    path: &JsonPath,
+    root: &'a Value,
+    options: &EvalOptions,
+) -> Result<Vec<(NormalizedPath, &'a Value)>, EvalError> {
+    if *options == EvalOptions::default() {
+        return Ok(evaluate_located(path, root));
+    }
+    let mut results = Vec::new();
+    let mut location = NormalizedPath::root();
+    for_each_budgeted(path, &mut location, root, options, |location, node| {
+        results.push((location.clone(), node));
+    })?;
+    Ok(results)
+}
+
+/// Stream the results of `path` to `emit` under a [`Budget`] for `options`
+fn for_each_budgeted<'a, L: Locator>(
+    path: &JsonPath,
+    location: &mut L,
+    root: &'a Value,
+    options: &EvalOptions,
+    mut emit: impl FnMut(&mut L, &'a Value),
+) -> Result<(), EvalError> {
+    let budget = Budget::new(options);
+    let max_results = options.max_results.unwrap_or(usize::MAX);
+    let mut results = 0;
+    let _ = for_each_node(
+        &path.segments,
+        location,
+        root,
+        root,
+        &budget,
+        &mut |location, node| {
+            results += 1;
+            if results > max_results {
+                return budget.exceed(BudgetLimit::MaxResults);
+            }
+            emit(location, node);
+            CONTINUE
+        },
+    );
+    match budget.exceeded.get() {
+        Some(limit) => Err(EvalError::BudgetExceeded {
+            visited: budget.visited.get(),
+            limit,
+        }),
+        None => Ok(()),
     }
 }
 
-/// Evaluate a JSONPath query against a JSON value
-pub fn evaluate<'a>(path: &JsonPath, root: &'a Value) -> Vec<&'a Value> {
-    let mut current: NodeList<'a> = smallvec![root];
+/// Counters reported by [`evaluate_with_stats`]
+///
+/// Like [`EvalOptions::max_nodes`], node counts leave out queries inside filter expressions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EvalStats {
+    /// Nodes taken as input by a segment, plus the result nodes: `$.a.b` visits `$`, `a`
+    /// and `b`
+    pub nodes_visited: usize,
+    /// Nodes in the result
+    pub nodes_matched: usize,
+    /// Nodes walked through by descendant segments
+    pub descendant_visits: usize,
+    /// Filter expressions evaluated, one per candidate node
+    pub filter_evaluations: usize,
+    /// Patterns compiled by `match()`/`search()` during evaluation
+    pub regex_compilations: usize,
+    /// `match()`/`search()` patterns found in the regex cache
+    pub regex_cache_hits: usize,
+    /// Wall-clock time of the evaluation
+    pub elapsed: Duration,
+}
 
-    for segment in &path.segments {
-        current = evaluate_segment(segment, &current, root);
+/// Evaluate a JSONPath query, counting the work done
+///
+/// Only this function pays for the counting; [`evaluate`] is unaffected.
+pub fn evaluate_with_stats<'a>(path: &JsonPath, root: &'a Value) -> (Vec<&'a Value>, EvalStats) {
+    let regex_counts = || {
+        REGEX_CACHE.with(|cache| {
+            let cache = cache.borrow();
+            (cache.compiles, cache.hits)
+        })
+    };
+    let start = Instant::now();
+    let (compiles_before, hits_before) = regex_counts();
+    let meter = StatsMeter::default();
+    let mut results = Vec::new();
+    let _ = for_each_node(
+        &path.segments,
+        &mut (),
+        root,
+        root,
+        &meter,
+        &mut |_, node| {
+            results.push(node);
+            CONTINUE
+        },
+    );
+    let (compiles, hits) = regex_counts();
+    let stats = EvalStats {
+        nodes_visited: meter.visited.get(),
+        nodes_matched: results.len(),
+        descendant_visits: meter.descendant_visits.get(),
+        filter_evaluations: meter.filter_evaluations.get(),
+        regex_compilations: compiles - compiles_before,
+        regex_cache_hits: hits - hits_before,
+        elapsed: start.elapsed(),
+    };
+    (results, stats)
+}
+
+/// Counts node visits during evaluation. `Break` from any method stops the walk.
+///
+/// Evaluation is generic over the meter, so [`Unmetered`] compiles to no code at all.
+pub(crate) trait Meter {
+    /// A node taken as input by a segment, or a result node
+    fn visit(&self) -> ControlFlow<()>;
+
+    /// A node walked through by a descendant segment
+    #[inline(always)]
+    fn descend(&self) -> ControlFlow<()> {
+        self.visit()
     }
 
-    current.into_vec()
+    /// A filter candidate about to be tested
+    #[inline(always)]
+    fn test_filter(&self) -> ControlFlow<()> {
+        self.visit()
+    }
 }
 
-#[inline]
-fn evaluate_segment<'a>(segment: &Segment, nodes: &[&'a Value], root: &'a Value) -> NodeList<'a> {
-    match segment {
-        Segment::Child(selectors) => {
-            let mut results: NodeList<'a> = SmallVec::new();
-            for node in nodes {
-                for selector in selectors {
-                    results.extend(evaluate_selector(selector, node, root));
-                }
-            }
-            results
+/// Meter of unlimited evaluation
+pub(crate) struct Unmetered;
+
+impl Meter for Unmetered {
+    #[inline(always)]
+    fn visit(&self) -> ControlFlow<()> {
+        CONTINUE
+    }
+}
+
+/// Meter enforcing [`EvalOptions`]; shared by reference through nested callbacks
+struct Budget {
+    max_nodes: usize,
+    deadline: Option<Instant>,
+    visited: Cell<usize>,
+    exceeded: Cell<Option<BudgetLimit>>,
+}
+
+impl Budget {
+    fn new(options: &EvalOptions) -> Self {
+        Self {
+            max_nodes: options.max_nodes.unwrap_or(usize::MAX),
+            deadline: options.deadline,
+            visited: Cell::new(0),
+            exceeded: Cell::new(None),
+        }
+    }
+
+    fn exceed(&self, limit: BudgetLimit) -> ControlFlow<()> {
+        self.exceeded.set(Some(limit));
+        ControlFlow::Break(())
+    }
+}
+
+/// Meter of [`evaluate_with_stats`]
+#[derive(Default)]
+struct StatsMeter {
+    visited: Cell<usize>,
+    descendant_visits: Cell<usize>,
+    filter_evaluations: Cell<usize>,
+}
+
+impl Meter for StatsMeter {
+    #[inline]
+    fn visit(&self) -> ControlFlow<()> {
+        self.visited.set(self.visited.get() + 1);
+        CONTINUE
+    }
+
+    #[inline]
+    fn descend(&self) -> ControlFlow<()> {
+        self.descendant_visits.set(self.descendant_visits.get() + 1);
+        CONTINUE
+    }
+
+    #[inline]
+    fn test_filter(&self) -> ControlFlow<()> {
+        self.filter_evaluations
+            .set(self.filter_evaluations.get() + 1);
+        CONTINUE
+    }
+}
+
+impl Meter for Budget {
+    #[inline]
+    fn visit(&self) -> ControlFlow<()> {
+        let visited = self.visited.get() + 1;
+        self.visited.set(visited);
+        if visited > self.max_nodes {
+            return self.exceed(BudgetLimit::MaxNodes);
+        }
+        if visited.is_multiple_of(DEADLINE_CHECK_INTERVAL)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+        {
+            return self.exceed(BudgetLimit::Deadline);
         }
+        CONTINUE
+    }
+}
+
+/// Evaluate a JSONPath query, returning each match together with its normalized path.
+/// Results are in the same order as [`evaluate`].
+pub fn evaluate_located<'a>(path: &JsonPath, root: &'a Value) -> Vec<(NormalizedPath, &'a Value)> {
+    let mut results = Vec::new();
+    let mut location = NormalizedPath::root();
+    let _ = for_each_node(
+        &path.segments,
+        &mut location,
+        root,
+        root,
+        &Unmetered,
+        &mut |location, node| {
+            results.push((location.clone(), node));
+            CONTINUE
+        },
+    );
+    results
+}
+
+/// Stream the nodes that `segments` select from `node` to `emit`.
+///
+/// Evaluation is depth first: each node a segment selects runs through the remaining
+/// segments before the segment moves on. Concatenation is associative, so this is exactly the
+/// RFC 9535 order of segment-by-segment evaluation, without intermediate node lists.
+/// Returning `Break` from `emit` or `meter` stops the walk.
+///
+/// Top-level queries and queries inside filters share this implementation; `location`
+/// tracks the path of `node` for located queries and is `()` otherwise.
+fn for_each_node<'a, L: Locator, M: Meter>(
+    segments: &[Segment],
+    location: &mut L,
+    node: &'a Value,
+    root: &'a Value,
+    meter: &M,
+    emit: &mut dyn FnMut(&mut L, &'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    meter.visit()?;
+    let Some((segment, rest)) = segments.split_first() else {
+        return emit(location, node);
+    };
+    let mut next = |location: &mut L, child: &'a Value| {
+        for_each_node(rest, location, child, root, meter, emit)
+    };
+    match segment {
+        Segment::Child(selectors) => selectors.iter().try_for_each(|selector| {
+            for_each_selected(selector, location, node, root, meter, &mut next)
+        }),
         Segment::Descendant(selectors) => {
-            let mut results: NodeList<'a> = SmallVec::new();
-            for node in nodes {
-                // Inline traversal: evaluate selectors during DFS, avoiding intermediate Vec
-                evaluate_descendant_inline(selectors, node, root, &mut results);
-            }
-            results
+            for_each_descendant(selectors, location, node, root, meter, &mut next)
+        }
+    }
+}
+
+/// Path of the current node during evaluation: a [`NormalizedPath`] for located queries, or
+/// `()` when locations aren't needed and tracking compiles to nothing
+pub(crate) trait Locator {
+    fn enter(&mut self, key: &ChildKey<'_>);
+    fn leave(&mut self);
+}
+
+impl Locator for () {
+    #[inline(always)]
+    fn enter(&mut self, _key: &ChildKey<'_>) {}
+
+    #[inline(always)]
+    fn leave(&mut self) {}
+}
+
+impl Locator for NormalizedPath {
+    #[inline]
+    fn enter(&mut self, key: &ChildKey<'_>) {
+        self.push(key.to_element());
+    }
+
+    #[inline]
+    fn leave(&mut self) {
+        self.pop();
+    }
+}
+
+/// Evaluate one selector against a node, collecting the selected nodes
+pub(crate) fn evaluate_selector<'a>(
+    selector: &Selector,
+    node: &'a Value,
+    root: &'a Value,
+) -> NodeList<'a> {
+    let mut results = SmallVec::new();
+    let _ = for_each_selected(
+        selector,
+        &mut (),
+        node,
+        root,
+        &Unmetered,
+        &mut |_, child| {
+            results.push(child);
+            CONTINUE
+        },
+    );
+    results
+}
+
+/// Stream the nodes one selector selects from `node`, in document order. `location` is the
+/// path of `node`, and is the path of each emitted node while `emit` runs.
+fn for_each_selected<'a, L: Locator, M: Meter>(
+    selector: &Selector,
+    location: &mut L,
+    node: &'a Value,
+    root: &'a Value,
+    meter: &M,
+    emit: &mut dyn FnMut(&mut L, &'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let mut index = |location: &mut L, i: usize, child: &'a Value| {
+        emit_child(location, &ChildKey::Index(i), child, emit)
+    };
+    match (selector, node) {
+        (Selector::Name(name), Value::Object(map)) => match map.get_key_value(name) {
+            Some((key, child)) => emit_child(location, &ChildKey::Name(key), child, emit),
+            None => CONTINUE,
+        },
+        (Selector::Index(idx), Value::Array(arr)) => match normalize_index(*idx, arr.len()) {
+            Some(i) if i < arr.len() => index(location, i, &arr[i]),
+            _ => CONTINUE,
+        },
+        (Selector::Wildcard, Value::Array(arr)) => arr
+            .iter()
+            .enumerate()
+            .try_for_each(|(i, child)| index(location, i, child)),
+        (Selector::Wildcard, Value::Object(map)) => map
+            .iter()
+            .try_for_each(|(k, child)| emit_child(location, &ChildKey::Name(k), child, emit)),
+        (Selector::Slice { start, end, step }, Value::Array(arr)) => {
+            for_each_slice_index(arr.len(), *start, *end, *step, |i| {
+                index(location, i, &arr[i])
+            })
         }
+        (Selector::Filter(expr), node) => match Children::of(node) {
+            Some(mut children) => {
+                let roots = RootQueries::of(expr);
+                children.try_for_each(|(key, child)| {
+                    meter.test_filter()?;
+                    if candidate_matches(expr, node, key, child, root, &roots) {
+                        emit_child(location, &key, child, emit)
+                    } else {
+                        CONTINUE
+                    }
+                })
+            }
+            None => CONTINUE,
+        },
+        _ => CONTINUE,
     }
 }
 
+/// Emit `child` with `key` entered into `location` for the duration of the call
 #[inline]
-fn evaluate_selector<'a>(selector: &Selector, node: &'a Value, root: &'a Value) -> NodeList<'a> {
-    match selector {
-        Selector::Name(name) => {
-            if let Value::Object(map) = node {
-                map.get(name).into_iter().collect()
-            } else {
-                SmallVec::new()
+fn emit_child<'a, L: Locator>(
+    location: &mut L,
+    key: &ChildKey<'_>,
+    child: &'a Value,
+    emit: &mut dyn FnMut(&mut L, &'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    location.enter(key);
+    let flow = emit(location, child);
+    location.leave();
+    flow
+}
+
+/// Test a filter expression against a single candidate node
+pub fn filter_matches(expr: &Expr, current: &Value, root: &Value) -> bool {
+    is_truthy(expr, Current::unkeyed(current), root)
+}
+
+/// Test a filter expression against the child of `parent` reached by `key`, as a filter
+/// selector does. `roots` holds the `$` queries of `expr` across the candidates of one node.
+pub(crate) fn candidate_matches<'a>(
+    expr: &'a Expr,
+    parent: &'a Value,
+    key: ChildKey<'a>,
+    candidate: &'a Value,
+    root: &'a Value,
+    roots: &'a RootQueries<'a>,
+) -> bool {
+    let current = Current {
+        value: candidate,
+        key: Some(key),
+        parent: Some(parent),
+        roots: &roots.queries,
+    };
+    is_truthy(expr, current, root)
+}
+
+/// The `$`-rooted queries of a filter expression. They select the same nodes for every
+/// candidate, so each runs once, when a candidate first needs it, for one application of the
+/// filter. Nested filters are applied once per node they select from and keep their own.
+pub(crate) struct RootQueries<'a> {
+    queries: Vec<RootQuery<'a>>,
+}
+
+/// A `$...` query of a filter and its nodes once evaluated
+struct RootQuery<'a> {
+    segments: &'a [Segment],
+    nodes: OnceCell<Vec<&'a Value>>,
+}
+
+impl<'a> RootQueries<'a> {
+    pub(crate) fn of(expr: &'a Expr) -> Self {
+        let mut queries = Vec::new();
+        collect_root_queries(expr, &mut queries);
+        Self { queries }
+    }
+}
+
+fn collect_root_queries<'a>(expr: &'a Expr, queries: &mut Vec<RootQuery<'a>>) {
+    match expr {
+        // `$.name` is a single lookup already
+        Expr::Path { start, segments } if matches!(**start, Expr::RootNode) => {
+            if !is_single_name(segments) {
+                queries.push(RootQuery {
+                    segments,
+                    nodes: OnceCell::new(),
+                });
             }
         }
-        Selector::Index(idx) => {
-            if let Value::Array(arr) = node {
-                let index = normalize_index(*idx, arr.len());
-                index.and_then(|i| arr.get(i)).into_iter().collect()
-            } else {
-                SmallVec::new()
-            }
+        Expr::Path { start, .. } => collect_root_queries(start, queries),
+        Expr::Comparison { left, right, .. }
+        | Expr::Logical { left, right, .. }
+        | Expr::Arithmetic { left, right, .. } => {
+            collect_root_queries(left, queries);
+            collect_root_queries(right, queries);
         }
-        Selector::Wildcard => match node {
-            Value::Array(arr) => arr.iter().collect(),
-            Value::Object(map) => map.values().collect(),
-            _ => SmallVec::new(),
-        },
-        Selector::Slice { start, end, step } => {
-            if let Value::Array(arr) = node {
-                evaluate_slice(arr, *start, *end, *step)
-            } else {
-                SmallVec::new()
+        Expr::Not(inner) => collect_root_queries(inner, queries),
+        Expr::FunctionCall { args, .. } => {
+            for arg in args {
+                collect_root_queries(arg, queries);
             }
         }
-        Selector::Filter(expr) => evaluate_filter(expr, node, root),
+        Expr::CurrentNode | Expr::RootNode | Expr::Literal(_) => {}
     }
 }
 
-/// Evaluate a filter expression against a node
-#[inline]
-fn evaluate_filter<'a>(expr: &Expr, node: &'a Value, root: &'a Value) -> NodeList<'a> {
-    match node {
-        Value::Array(arr) => arr
+/// The node `@` refers to, with the name or index it was reached by and the node containing
+/// it when it is a filter candidate
+#[derive(Clone, Copy)]
+struct Current<'a> {
+    value: &'a Value,
+    #[cfg_attr(not(feature = "extended-functions"), allow(dead_code))]
+    key: Option<ChildKey<'a>>,
+    #[cfg_attr(not(feature = "extended-functions"), allow(dead_code))]
+    parent: Option<&'a Value>,
+    /// The `$` queries of the filter shared by its candidates
+    roots: &'a [RootQuery<'a>],
+}
+
+impl<'a> Current<'a> {
+    /// A node that was not reached as a filter candidate, such as a standalone filter's input
+    fn unkeyed(value: &'a Value) -> Self {
+        Self {
+            value,
+            key: None,
+            parent: None,
+            roots: &[],
+        }
+    }
+
+    /// Nodes of the `$` query with `segments` when it is one of the filter's, evaluated on
+    /// first use
+    fn root_query(self, segments: &[Segment], root: &'a Value) -> Option<&'a [&'a Value]> {
+        let query = self
+            .roots
             .iter()
-            .filter(|elem| {
-                let result = evaluate_expr(expr, elem, root);
-                result.is_truthy()
-            })
-            .collect(),
-        Value::Object(map) => map
-            .values()
-            .filter(|elem| {
-                let result = evaluate_expr(expr, elem, root);
-                result.is_truthy()
+            .find(|query| std::ptr::eq(query.segments, segments))?;
+        let nodes = query
+            .nodes
+            .get_or_init(|| evaluate_path_segments(query.segments, root, root).into_vec());
+        Some(nodes)
+    }
+}
+
+/// Truthiness of an expression in logical context. Existence tests stop at the first node
+/// instead of collecting the node list.
+#[inline]
+fn is_truthy<'a>(expr: &'a Expr, current: Current<'a>, root: &'a Value) -> bool {
+    match expr {
+        // `@.name` has an allocation-free fast path in `evaluate_expr`
+        Expr::Path { start, segments } if !is_single_name(segments) => {
+            for_each_path_node(start, segments, current, root, &mut |_| {
+                ControlFlow::Break(())
             })
-            .collect(),
-        _ => SmallVec::new(),
+            .is_break()
+        }
+        _ => evaluate_expr(expr, current, root).is_truthy(),
     }
 }
 
+/// True for the common `@.name` filter query shape
+#[inline]
+fn is_single_name(segments: &[Segment]) -> bool {
+    matches!(segments, [Segment::Child(selectors)] if matches!(selectors.as_slice(), [Selector::Name(_)]))
+}
+
+/// The node a filter query starts from: `@`, `$`, or the single node of a NodesType function
+/// call such as `parent()`
+fn path_start<'a>(start: &'a Expr, current: Current<'a>, root: &'a Value) -> Option<&'a Value> {
+    match start {
+        Expr::CurrentNode => Some(current.value),
+        Expr::RootNode => Some(root),
+        Expr::FunctionCall { .. } => match evaluate_expr(start, current, root) {
+            ExprResult::NodeList(nodes) if nodes.len() == 1 => Some(nodes[0]),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Stream the nodes of a filter query (`@...`, `$...` or `parent()...`) without collecting them
+fn for_each_path_node<'a>(
+    start: &'a Expr,
+    segments: &[Segment],
+    current: Current<'a>,
+    root: &'a Value,
+    emit: &mut dyn FnMut(&'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    if let Expr::RootNode = start
+        && let Some(nodes) = current.root_query(segments, root)
+    {
+        return nodes.iter().try_for_each(|&node| emit(node));
+    }
+    let Some(start) = path_start(start, current, root) else {
+        return CONTINUE;
+    };
+    for_each_node(
+        segments,
+        &mut (),
+        start,
+        root,
+        &Unmetered,
+        &mut |_, node| emit(node),
+    )
+}
+
 /// Evaluate an expression in filter context
 #[inline]
-fn evaluate_expr<'a>(expr: &Expr, current: &'a Value, root: &'a Value) -> ExprResult<'a> {
+fn evaluate_expr<'a>(expr: &'a Expr, current: Current<'a>, root: &'a Value) -> ExprResult<'a> {
     match expr {
         // RFC 9535: Bare @ in filter expression is an existence test.
         // Return as NodeList so is_truthy() checks existence, not value truthiness.
         // This ensures $[?@] includes null values (they exist, even if not truthy).
-        Expr::CurrentNode => ExprResult::NodeList(smallvec![current]),
+        Expr::CurrentNode => ExprResult::NodeList(smallvec![current.value]),
         Expr::RootNode => ExprResult::Value(root),
         Expr::Path { start, segments } => {
-            let start_value = match start.as_ref() {
-                Expr::CurrentNode => current,
-                Expr::RootNode => root,
-                _ => return ExprResult::Nothing,
+            if let Expr::RootNode = **start
+                && let Some(nodes) = current.root_query(segments, root)
+            {
+                return match nodes {
+                    [] => ExprResult::Nothing,
+                    nodes => ExprResult::NodeList(nodes.iter().copied().collect()),
+                };
+            }
+            let Some(start_value) = path_start(start, current, root) else {
+                return ExprResult::Nothing;
             };
 
             // Fast path: single property access (@.name or $.name)
@@ -273,69 +1014,35 @@ fn evaluate_expr<'a>(expr: &Expr, current: &'a Value, root: &'a Value) -> ExprRe
                 ExprResult::NodeList(results)
             }
         }
-        // Use static references for Bool/Null to avoid cloning, cached value for others
-        Expr::Literal(cached) => match &cached.literal {
-            Literal::Bool(true) => ExprResult::Value(&TRUE_VAL),
-            Literal::Bool(false) => ExprResult::Value(&FALSE_VAL),
-            Literal::Null => ExprResult::Value(&NULL_VAL),
-            // Numbers and strings still need the cached value (clone is unavoidable)
-            _ => ExprResult::OwnedValue(cached.cached_value.clone()),
-        },
+        // Literals borrow their cached value from the AST
+        Expr::Literal(cached) => ExprResult::Value(&cached.cached_value),
         Expr::Comparison { left, op, right } => {
-            // Optimize: directly reference cached_value for literals to avoid cloning
-            let left_result = match left.as_ref() {
-                Expr::Literal(cached) => ExprResult::Value(&cached.cached_value),
-                _ => evaluate_expr(left, current, root),
-            };
-            let right_result = match right.as_ref() {
-                Expr::Literal(cached) => ExprResult::Value(&cached.cached_value),
-                _ => evaluate_expr(right, current, root),
-            };
-            let result = compare_values(&left_result, *op, &right_result);
-            if result {
-                ExprResult::Value(&TRUE_VAL)
-            } else {
-                ExprResult::Value(&FALSE_VAL)
-            }
-        }
-        Expr::Logical { left, op, right } => {
             let left_result = evaluate_expr(left, current, root);
-            match op {
-                LogicalOp::And => {
-                    if !left_result.is_truthy() {
-                        ExprResult::Value(&FALSE_VAL)
-                    } else {
-                        let right_result = evaluate_expr(right, current, root);
-                        if right_result.is_truthy() {
-                            ExprResult::Value(&TRUE_VAL)
-                        } else {
-                            ExprResult::Value(&FALSE_VAL)
-                        }
-                    }
-                }
-                LogicalOp::Or => {
-                    if left_result.is_truthy() {
-                        ExprResult::Value(&TRUE_VAL)
-                    } else {
-                        let right_result = evaluate_expr(right, current, root);
-                        if right_result.is_truthy() {
-                            ExprResult::Value(&TRUE_VAL)
-                        } else {
-                            ExprResult::Value(&FALSE_VAL)
-                        }
-                    }
-                }
-            }
+            let right_result = evaluate_expr(right, current, root);
+            ExprResult::Logical(compare_values(&left_result, *op, &right_result))
         }
-        Expr::Not(inner) => {
-            let inner_result = evaluate_expr(inner, current, root);
-            if inner_result.is_truthy() {
-                ExprResult::Value(&FALSE_VAL)
-            } else {
-                ExprResult::Value(&TRUE_VAL)
+        Expr::Logical { left, op, right } => ExprResult::Logical(match op {
+            LogicalOp::And => is_truthy(left, current, root) && is_truthy(right, current, root),
+            LogicalOp::Or => is_truthy(left, current, root) || is_truthy(right, current, root),
+        }),
+        Expr::Not(inner) => ExprResult::Logical(!is_truthy(inner, current, root)),
+        Expr::Arithmetic { left, op, right } => {
+            let left = evaluate_expr(left, current, root);
+            let right = evaluate_expr(right, current, root);
+            match left.to_value().zip(right.to_value()) {
+                Some((left, right)) => match arithmetic(left, *op, right) {
+                    Some(value) => ExprResult::OwnedValue(value),
+                    None => ExprResult::Nothing,
+                },
+                None => ExprResult::Nothing,
             }
         }
-        Expr::FunctionCall { name, args } => evaluate_function(name, args, current, root),
+        Expr::FunctionCall {
+            function,
+            args,
+            regex,
+            strict_regex,
+        } => evaluate_function(function, args, regex.as_ref(), *strict_regex, current, root),
     }
 }
 
@@ -346,33 +1053,211 @@ fn evaluate_path_segments<'a>(
     start: &'a Value,
     root: &'a Value,
 ) -> NodeList<'a> {
-    let mut current: NodeList<'a> = smallvec![start];
-    for segment in segments {
-        current = evaluate_segment(segment, &current, root);
-    }
-    current
+    let mut results = SmallVec::new();
+    let _ = for_each_node(
+        segments,
+        &mut (),
+        start,
+        root,
+        &Unmetered,
+        &mut |_, node| {
+            results.push(node);
+            CONTINUE
+        },
+    );
+    results
 }
 
-/// Evaluate a built-in function call
+/// Evaluate a function call: built-ins natively, user-defined functions via their closure
 #[inline]
 fn evaluate_function<'a>(
-    name: &str,
-    args: &[Expr],
-    current: &'a Value,
+    function: &Function,
+    args: &'a [Expr],
+    regex: Option<&CompiledRegex>,
+    strict_regex: bool,
+    current: Current<'a>,
     root: &'a Value,
 ) -> ExprResult<'a> {
-    match name {
+    if let Some(eval) = &function.eval {
+        return evaluate_custom_function(eval.as_ref(), function.params(), args, current, root);
+    }
+    match function.name() {
         "length" => fn_length(args, current, root),
         "count" => fn_count(args, current, root),
         "value" => fn_value(args, current, root),
-        "match" => fn_match(args, current, root),
-        "search" => fn_search(args, current, root),
+        "match" => regex_function(args, regex, strict_regex, current, root, true, false),
+        "search" => regex_function(args, regex, strict_regex, current, root, false, false),
+        #[cfg(feature = "extended-functions")]
+        name @ ("min" | "max" | "sum" | "avg") => fn_aggregate(name, args, current, root),
+        #[cfg(feature = "extended-functions")]
+        "key" => fn_key(current),
+        #[cfg(feature = "extended-functions")]
+        "imatch" => regex_function(args, regex, strict_regex, current, root, true, true),
+        #[cfg(feature = "extended-functions")]
+        "isearch" => regex_function(args, regex, strict_regex, current, root, false, true),
+        #[cfg(feature = "extended-functions")]
+        "parent" => fn_parent(current),
+        #[cfg(feature = "extended-functions")]
+        "type" => fn_type(args, current, root),
+        #[cfg(feature = "extended-functions")]
+        "contains" => fn_contains(args, current, root),
+        #[cfg(feature = "extended-functions")]
+        name @ ("to_number" | "to_string") => fn_convert(name, args, current, root),
+        #[cfg(feature = "extended-functions")]
+        name @ ("starts_with" | "ends_with") => fn_affix(name, args, current, root),
         _ => ExprResult::Nothing, // Unknown function
     }
 }
 
+/// Extension key(): the name or index under which the filter candidate was reached, Nothing
+/// outside a filter selector
+#[cfg(feature = "extended-functions")]
+fn fn_key<'a>(current: Current<'a>) -> ExprResult<'a> {
+    match current.key {
+        Some(ChildKey::Name(name)) => ExprResult::OwnedValue(Value::String(name.clone())),
+        Some(ChildKey::Index(i)) => ExprResult::OwnedValue(Value::from(i)),
+        None => ExprResult::Nothing,
+    }
+}
+
+/// Extension parent(): the node containing the filter candidate, Nothing outside a filter
+/// selector
+#[cfg(feature = "extended-functions")]
+fn fn_parent<'a>(current: Current<'a>) -> ExprResult<'a> {
+    match current.parent {
+        Some(parent) => ExprResult::NodeList(smallvec![parent]),
+        None => ExprResult::Nothing,
+    }
+}
+
+/// Extension type(): the JSON type name of a ValueType argument, Nothing for Nothing
+#[cfg(feature = "extended-functions")]
+fn fn_type<'a>(args: &'a [Expr], current: Current<'a>, root: &'a Value) -> ExprResult<'a> {
+    let [arg] = args else {
+        return ExprResult::Nothing;
+    };
+    match evaluate_expr(arg, current, root).to_value() {
+        Some(value) => ExprResult::OwnedValue(Value::from(crate::extensions::type_name(value))),
+        None => ExprResult::Nothing,
+    }
+}
+
+/// Extensions to_number() and to_string(): a ValueType argument converted, or Nothing
+#[cfg(feature = "extended-functions")]
+fn fn_convert<'a>(
+    name: &str,
+    args: &'a [Expr],
+    current: Current<'a>,
+    root: &'a Value,
+) -> ExprResult<'a> {
+    let [arg] = args else {
+        return ExprResult::Nothing;
+    };
+    let arg = evaluate_expr(arg, current, root);
+    let converted = match (name, arg.to_value()) {
+        ("to_number", Some(value)) => crate::extensions::to_number(value),
+        ("to_string", Some(value)) => crate::extensions::to_string(value),
+        _ => None,
+    };
+    match converted {
+        Some(value) => ExprResult::OwnedValue(value),
+        None => ExprResult::Nothing,
+    }
+}
+
+/// Extension contains(): whether an array argument has an element equal to the second argument
+#[cfg(feature = "extended-functions")]
+fn fn_contains<'a>(args: &'a [Expr], current: Current<'a>, root: &'a Value) -> ExprResult<'a> {
+    let [haystack, needle] = args else {
+        return ExprResult::Logical(false);
+    };
+    let haystack = evaluate_expr(haystack, current, root);
+    let needle = evaluate_expr(needle, current, root);
+    ExprResult::Logical(match (haystack.to_value(), needle.to_value()) {
+        (Some(haystack), Some(needle)) => crate::extensions::contains(haystack, needle),
+        _ => false,
+    })
+}
+
+/// Extensions starts_with() and ends_with(): literal prefix and suffix tests on strings
+#[cfg(feature = "extended-functions")]
+fn fn_affix<'a>(
+    name: &str,
+    args: &'a [Expr],
+    current: Current<'a>,
+    root: &'a Value,
+) -> ExprResult<'a> {
+    let [string, affix] = args else {
+        return ExprResult::Logical(false);
+    };
+    let string = evaluate_expr(string, current, root);
+    let affix = evaluate_expr(affix, current, root);
+    ExprResult::Logical(match (string.to_value(), affix.to_value()) {
+        (Some(Value::String(string)), Some(Value::String(affix))) => {
+            crate::extensions::has_affix(name, string, affix)
+        }
+        _ => false,
+    })
+}
+
+/// Extension aggregations over a NodesType argument, see [`crate::extensions`]
+#[cfg(feature = "extended-functions")]
+fn fn_aggregate<'a>(
+    name: &str,
+    args: &'a [Expr],
+    current: Current<'a>,
+    root: &'a Value,
+) -> ExprResult<'a> {
+    let [arg] = args else {
+        return ExprResult::Nothing;
+    };
+    let nodes = evaluate_expr(arg, current, root);
+    match crate::extensions::aggregate(name, nodes.nodes()) {
+        Some(value) => ExprResult::OwnedValue(value),
+        None => ExprResult::Nothing,
+    }
+}
+
+/// Call a user-defined function with its arguments converted to the declared parameter types
+fn evaluate_custom_function<'a>(
+    eval: &CustomFn,
+    params: &[FunctionType],
+    args: &'a [Expr],
+    current: Current<'a>,
+    root: &'a Value,
+) -> ExprResult<'a> {
+    let values: Vec<ExprValue> = params
+        .iter()
+        .zip(args)
+        .map(|(param, arg)| {
+            let result = evaluate_expr(arg, current, root);
+            match param {
+                FunctionType::Value => match result.to_value() {
+                    Some(v) => ExprValue::Value(v.clone()),
+                    None => ExprValue::Nothing,
+                },
+                FunctionType::Nodes => ExprValue::Nodes(match result {
+                    ExprResult::NodeList(list) => list.into_iter().cloned().collect(),
+                    ExprResult::OwnedNodeList(list) => list,
+                    ExprResult::Value(v) => vec![v.clone()],
+                    ExprResult::OwnedValue(v) => vec![v],
+                    ExprResult::Nothing | ExprResult::Logical(_) => Vec::new(),
+                }),
+                FunctionType::Logical => ExprValue::Logical(result.is_truthy()),
+            }
+        })
+        .collect();
+
+    match eval(&values) {
+        ExprValue::Value(v) => ExprResult::OwnedValue(v),
+        ExprValue::Nothing => ExprResult::Nothing,
+        ExprValue::Nodes(list) => ExprResult::OwnedNodeList(list),
+        ExprValue::Logical(b) => ExprResult::Logical(b),
+    }
+}
+
 /// RFC 9535 length() function: returns length of string, array, or object
-fn fn_length<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResult<'a> {
+fn fn_length<'a>(args: &'a [Expr], current: Current<'a>, root: &'a Value) -> ExprResult<'a> {
     if args.len() != 1 {
         return ExprResult::Nothing;
     }
@@ -390,22 +1275,33 @@ fn fn_length<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResu
 }
 
 /// RFC 9535 count() function: returns count of nodes in a nodelist
-fn fn_count<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResult<'a> {
+fn fn_count<'a>(args: &'a [Expr], current: Current<'a>, root: &'a Value) -> ExprResult<'a> {
     if args.len() != 1 {
         return ExprResult::Nothing;
     }
 
+    // Count query results as they stream instead of collecting them
+    if let Expr::Path { start, segments } = &args[0] {
+        let mut count = 0usize;
+        let _ = for_each_path_node(start, segments, current, root, &mut |_| {
+            count += 1;
+            CONTINUE
+        });
+        return ExprResult::OwnedValue(Value::Number(count.into()));
+    }
+
     let arg = evaluate_expr(&args[0], current, root);
     let count = match &arg {
         ExprResult::NodeList(list) => list.len(),
+        ExprResult::OwnedNodeList(list) => list.len(),
         ExprResult::Value(_) | ExprResult::OwnedValue(_) => 1,
-        ExprResult::Nothing => 0,
+        ExprResult::Nothing | ExprResult::Logical(_) => 0,
     };
     ExprResult::OwnedValue(Value::Number(count.into()))
 }
 
 /// RFC 9535 value() function: returns the value if exactly one node, Nothing otherwise
-fn fn_value<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResult<'a> {
+fn fn_value<'a>(args: &'a [Expr], current: Current<'a>, root: &'a Value) -> ExprResult<'a> {
     if args.len() != 1 {
         return ExprResult::Nothing;
     }
@@ -415,61 +1311,65 @@ fn fn_value<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResul
         ExprResult::Value(v) => ExprResult::Value(v),
         ExprResult::OwnedValue(v) => ExprResult::OwnedValue(v),
         ExprResult::NodeList(list) if list.len() == 1 => ExprResult::Value(list[0]),
+        ExprResult::OwnedNodeList(mut list) if list.len() == 1 => {
+            ExprResult::OwnedValue(list.swap_remove(0))
+        }
         _ => ExprResult::Nothing,
     }
 }
 
 /// Helper for regex matching with I-Regexp transformation
 /// `full_match`: true = match() (anchored), false = search() (unanchored)
-fn regex_string_match(string: &str, pattern: &str, full_match: bool) -> bool {
-    let transformed = transform_pattern_for_iregexp(pattern);
-    let final_pattern = if full_match {
-        format!("^(?:{})$", transformed)
-    } else {
-        transformed
-    };
-    get_or_compile_regex(&final_pattern).is_some_and(|re| re.is_match(string))
-}
-
-/// RFC 9535 match() function: returns true if string matches regex (full match)
-fn fn_match<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResult<'a> {
-    regex_function(args, current, root, true)
-}
-
-/// RFC 9535 search() function: returns true if regex pattern found anywhere in string
-fn fn_search<'a>(args: &[Expr], current: &'a Value, root: &'a Value) -> ExprResult<'a> {
-    regex_function(args, current, root, false)
+fn regex_string_match(
+    string: &str,
+    pattern: &str,
+    full_match: bool,
+    strict: bool,
+    case_insensitive: bool,
+) -> bool {
+    get_or_compile_regex(pattern, full_match, strict, case_insensitive)
+        .is_some_and(|re| re.is_match(string))
 }
 
-/// Common implementation for match() and search() functions
+/// RFC 9535 match() (`full_match`, anchored) and search() functions, and their
+/// `case_insensitive` extensions imatch() and isearch(), using the pattern compiled at parse
+/// time when there is one. With `strict`, dynamic patterns that aren't I-Regexp are false.
 fn regex_function<'a>(
-    args: &[Expr],
-    current: &'a Value,
+    args: &'a [Expr],
+    regex: Option<&CompiledRegex>,
+    strict: bool,
+    current: Current<'a>,
     root: &'a Value,
     full_match: bool,
+    case_insensitive: bool,
 ) -> ExprResult<'a> {
     if args.len() != 2 {
-        return ExprResult::Nothing;
+        return ExprResult::Logical(false);
     }
 
     let string_arg = evaluate_expr(&args[0], current, root);
-    let pattern_arg = evaluate_expr(&args[1], current, root);
-
     let string = match string_arg.to_value() {
         Some(Value::String(s)) => s.as_str(),
-        _ => return ExprResult::Value(&FALSE_VAL),
+        _ => return ExprResult::Logical(false),
     };
+    if let Some(regex) = regex {
+        return ExprResult::Logical(regex.0.is_match(string));
+    }
+
+    let pattern_arg = evaluate_expr(&args[1], current, root);
 
     let pattern = match pattern_arg.to_value() {
         Some(Value::String(p)) => p.as_str(),
-        _ => return ExprResult::Value(&FALSE_VAL),
+        _ => return ExprResult::Logical(false),
     };
 
-    if regex_string_match(string, pattern, full_match) {
-        ExprResult::Value(&TRUE_VAL)
-    } else {
-        ExprResult::Value(&FALSE_VAL)
-    }
+    ExprResult::Logical(regex_string_match(
+        string,
+        pattern,
+        full_match,
+        strict,
+        case_insensitive,
+    ))
 }
 
 /// Compare two expression results with the given operator
@@ -486,20 +1386,79 @@ fn compare_values(left: &ExprResult<'_>, op: CompOp, right: &ExprResult<'_>) ->
 
     match (left_val, right_val) {
         (Some(l), Some(r)) => compare_json_values(l, op, r),
-        // Both sides are Nothing (absent) - equal in being absent
-        (None, None) => matches!(op, CompOp::Eq),
+        // Both sides are Nothing (absent) - equal in being absent, and `<=`/`>=` include `==`
+        (None, None) => matches!(op, CompOp::Eq | CompOp::Le | CompOp::Ge),
         // One side is Nothing, one has a value - not equal
         _ => matches!(op, CompOp::Ne),
     }
 }
 
+/// The value of an integral JSON number, which may be outside the i64 range
+///
+/// Integral floats are included so that `2^53 + 1` and `2^53` as a float compare unequal.
+/// Every f64 beyond 2^53 is integral, so the f64 comparison only sees values it can order.
+#[inline]
+fn exact_integer(n: &serde_json::Number) -> Option<i128> {
+    n.as_i64()
+        .map(i128::from)
+        .or_else(|| n.as_u64().map(i128::from))
+        .or_else(|| {
+            n.as_f64()
+                .filter(|f| f.fract() == 0.0 && f.abs() < 2f64.powi(126))
+                .map(|f| f as i128)
+        })
+}
+
+/// Apply `+ - * /` to two numbers. Integers stay integers while the result is exact and fits
+/// in `i64`, anything else is computed in `f64`. None (Nothing) for non-numbers, division by
+/// zero, and results that aren't finite.
+fn arithmetic(left: &Value, op: ArithOp, right: &Value) -> Option<Value> {
+    let (Value::Number(a), Value::Number(b)) = (left, right) else {
+        return None;
+    };
+    if let (Some(a), Some(b)) = (a.as_i64(), b.as_i64()) {
+        let exact = match op {
+            ArithOp::Add => a.checked_add(b),
+            ArithOp::Sub => a.checked_sub(b),
+            ArithOp::Mul => a.checked_mul(b),
+            ArithOp::Div if b == 0 => return None,
+            ArithOp::Div => a
+                .checked_rem(b)
+                .filter(|&rem| rem == 0)
+                .and_then(|_| a.checked_div(b)),
+        };
+        if let Some(n) = exact {
+            return Some(Value::from(n));
+        }
+    }
+    let (a, b) = (a.as_f64()?, b.as_f64()?);
+    let result = match op {
+        ArithOp::Add => a + b,
+        ArithOp::Sub => a - b,
+        ArithOp::Mul => a * b,
+        ArithOp::Div if b == 0.0 => return None,
+        ArithOp::Div => a / b,
+    };
+    Number::from_f64(result).map(Value::Number)
+}
+
 /// Compare two JSON values with the given operator.
 /// Uses single-pass extraction for numeric comparisons to avoid redundant as_f64() calls.
 #[inline]
-fn compare_json_values(left: &Value, op: CompOp, right: &Value) -> bool {
+pub(crate) fn compare_json_values(left: &Value, op: CompOp, right: &Value) -> bool {
     match (left, right) {
-        // Numbers: single-pass comparison with all operators
+        // Numbers: integers compare exactly, beyond the 2^53 precision of f64
         (Value::Number(l), Value::Number(r)) => {
+            if let (Some(l), Some(r)) = (exact_integer(l), exact_integer(r)) {
+                return match op {
+                    CompOp::Eq => l == r,
+                    CompOp::Ne => l != r,
+                    CompOp::Lt => l < r,
+                    CompOp::Gt => l > r,
+                    CompOp::Le => l <= r,
+                    CompOp::Ge => l >= r,
+                };
+            }
             match (l.as_f64(), r.as_f64()) {
                 (Some(lf), Some(rf)) => match op {
                     CompOp::Eq => lf == rf,
@@ -528,27 +1487,49 @@ fn compare_json_values(left: &Value, op: CompOp, right: &Value) -> bool {
             CompOp::Eq => l == r,
             CompOp::Ne => l != r,
             CompOp::Le | CompOp::Ge => l == r, // Le/Ge true only if equal
-            CompOp::Lt | CompOp::Gt => false,  // No ordering for bools
-        },
-        // Arrays: equality and Le/Ge (when equal)
-        (Value::Array(l), Value::Array(r)) => match op {
-            CompOp::Eq => l == r,
-            CompOp::Ne => l != r,
-            CompOp::Le | CompOp::Ge => l == r, // Le/Ge true only if equal
-            CompOp::Lt | CompOp::Gt => false,  // No ordering for arrays
-        },
-        // Objects: equality and Le/Ge (when equal)
-        (Value::Object(l), Value::Object(r)) => match op {
-            CompOp::Eq => l == r,
-            CompOp::Ne => l != r,
-            CompOp::Le | CompOp::Ge => l == r, // Le/Ge true only if equal
-            CompOp::Lt | CompOp::Gt => false,  // No ordering for objects
+            CompOp::Lt | CompOp::Gt => false,  // No ordering for bools
+        },
+        // Arrays and objects: equality and Le/Ge (when equal), no ordering
+        (Value::Array(_), Value::Array(_)) | (Value::Object(_), Value::Object(_)) => match op {
+            CompOp::Eq | CompOp::Le | CompOp::Ge => values_equal(left, right),
+            CompOp::Ne => !values_equal(left, right),
+            CompOp::Lt | CompOp::Gt => false,
         },
         // Different types: never equal
         _ => matches!(op, CompOp::Ne),
     }
 }
 
+/// Deep equality with numbers compared by value, so `[1] == [1.0]`, unlike `Value`'s `==`.
+/// Iterative, so deeply nested values can't overflow the stack.
+pub(crate) fn values_equal(left: &Value, right: &Value) -> bool {
+    let mut pending: SmallVec<[(&Value, &Value); WALK_STACK]> = smallvec![(left, right)];
+    while let Some((left, right)) = pending.pop() {
+        let equal = match (left, right) {
+            (Value::Number(_), Value::Number(_)) => compare_json_values(left, CompOp::Eq, right),
+            (Value::Array(l), Value::Array(r)) => {
+                pending.extend(l.iter().zip(r));
+                l.len() == r.len()
+            }
+            (Value::Object(l), Value::Object(r)) => {
+                l.len() == r.len()
+                    && l.iter().all(|(k, a)| match r.get(k) {
+                        Some(b) => {
+                            pending.push((a, b));
+                            true
+                        }
+                        None => false,
+                    })
+            }
+            _ => left == right,
+        };
+        if !equal {
+            return false;
+        }
+    }
+    true
+}
+
 fn normalize_index(idx: i64, len: usize) -> Option<usize> {
     let len_i64 = len as i64;
     if idx >= 0 {
@@ -564,17 +1545,72 @@ fn normalize_index(idx: i64, len: usize) -> Option<usize> {
     }
 }
 
-fn evaluate_slice(
-    arr: &[Value],
+/// Call `f` with each index a slice selects, in slice order, until it breaks
+fn for_each_slice_index(
+    len: usize,
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    mut f: impl FnMut(usize) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    let Some((start, end, step)) = slice_bounds(len, start, end, step) else {
+        return CONTINUE;
+    };
+
+    if step > 0 {
+        let mut i = start;
+        while i < end {
+            if i >= 0 && (i as usize) < len {
+                f(i as usize)?;
+            }
+            i += step;
+        }
+    } else {
+        let mut i = start;
+        while i > end {
+            if i >= 0 && (i as usize) < len {
+                f(i as usize)?;
+            }
+            i += step;
+        }
+    }
+    CONTINUE
+}
+
+/// True if a slice over an array of length `len` selects `index`
+pub(crate) fn slice_selects(
+    len: usize,
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    index: usize,
+) -> bool {
+    let Some((start, end, step)) = slice_bounds(len, start, end, step) else {
+        return false;
+    };
+    if index >= len {
+        return false;
+    }
+    let i = index as i64;
+    if step > 0 {
+        start <= i && i < end && (i - start) % step == 0
+    } else {
+        end < i && i <= start && (start - i) % step == 0
+    }
+}
+
+/// Normalized `(start, end, step)` of a slice, or None if the step is 0
+fn slice_bounds(
+    len: usize,
     start: Option<i64>,
     end: Option<i64>,
     step: Option<i64>,
-) -> NodeList<'_> {
-    let len = arr.len() as i64;
+) -> Option<(i64, i64, i64)> {
+    let len = len as i64;
     let step = step.unwrap_or(1);
 
     if step == 0 {
-        return SmallVec::new();
+        return None;
     }
 
     let (start, end) = if step > 0 {
@@ -591,28 +1627,7 @@ fn evaluate_slice(
             .unwrap_or(-1);
         (start.min(len - 1), end.max(-1))
     };
-
-    let mut results: NodeList<'_> = SmallVec::new();
-
-    if step > 0 {
-        let mut i = start;
-        while i < end {
-            if i >= 0 && (i as usize) < arr.len() {
-                results.push(&arr[i as usize]);
-            }
-            i += step;
-        }
-    } else {
-        let mut i = start;
-        while i > end {
-            if i >= 0 && (i as usize) < arr.len() {
-                results.push(&arr[i as usize]);
-            }
-            i += step;
-        }
-    }
-
-    results
+    Some((start, end, step))
 }
 
 fn normalize_slice_bound(bound: i64, len: i64) -> i64 {
@@ -634,32 +1649,107 @@ fn normalize_slice_bound_for_negative_step(bound: i64, len: i64) -> i64 {
     }
 }
 
-/// Evaluate descendant selectors inline during DFS traversal.
-/// This avoids the intermediate Vec allocation of collect_descendants.
-fn evaluate_descendant_inline<'a>(
+/// Stream the nodes descendant selectors select from `node` and everything below it, depth
+/// first. Scalars are never visited: no selector selects anything from them.
+fn for_each_descendant<'a, L: Locator, M: Meter>(
     selectors: &[Selector],
+    location: &mut L,
     node: &'a Value,
     root: &'a Value,
-    results: &mut NodeList<'a>,
-) {
-    // Evaluate selectors on current node
-    for selector in selectors {
-        results.extend(evaluate_selector(selector, node, root));
-    }
-
-    // Recurse into children
-    match node {
-        Value::Array(arr) => {
-            for child in arr {
-                evaluate_descendant_inline(selectors, child, root, results);
+    meter: &M,
+    emit: &mut dyn FnMut(&mut L, &'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    walk_descendants(node, location, true, &mut |location, node| {
+        meter.descend()?;
+        selectors
+            .iter()
+            .try_for_each(|selector| for_each_selected(selector, location, node, root, meter, emit))
+    })
+}
+
+/// Inline capacity of the explicit traversal stack; deeper documents spill to the heap
+const WALK_STACK: usize = 16;
+
+/// Call `visit` for `node` and each node below it in document order (pre-order), optionally
+/// skipping scalars below `node`. `location` is kept in step with the walk.
+///
+/// Uses an explicit stack rather than recursion, so arbitrarily deep documents can't
+/// overflow the call stack.
+pub(crate) fn walk_descendants<'a, L: Locator>(
+    node: &'a Value,
+    location: &mut L,
+    containers_only: bool,
+    visit: &mut dyn FnMut(&mut L, &'a Value) -> ControlFlow<()>,
+) -> ControlFlow<()> {
+    visit(location, node)?;
+    let mut stack: SmallVec<[Children<'a>; WALK_STACK]> = Children::of(node).into_iter().collect();
+    while let Some(children) = stack.last_mut() {
+        match children.find(|(_, child)| !containers_only || is_container(child)) {
+            Some((key, child)) => {
+                location.enter(&key);
+                visit(location, child)?;
+                match Children::of(child) {
+                    Some(grandchildren) => stack.push(grandchildren),
+                    None => location.leave(),
+                }
             }
-        }
-        Value::Object(map) => {
-            for child in map.values() {
-                evaluate_descendant_inline(selectors, child, root, results);
+            None => {
+                stack.pop();
+                if !stack.is_empty() {
+                    location.leave();
+                }
             }
         }
-        _ => {}
+    }
+    CONTINUE
+}
+
+/// True for arrays and objects, the only nodes selectors can select from
+#[inline]
+fn is_container(node: &Value) -> bool {
+    matches!(node, Value::Array(_) | Value::Object(_))
+}
+
+/// Iterator over the members of an array or object
+pub(crate) enum Children<'a> {
+    Array(std::iter::Enumerate<std::slice::Iter<'a, Value>>),
+    Object(serde_json::map::Iter<'a>),
+}
+
+impl<'a> Children<'a> {
+    pub(crate) fn of(node: &'a Value) -> Option<Self> {
+        match node {
+            Value::Array(arr) => Some(Children::Array(arr.iter().enumerate())),
+            Value::Object(map) => Some(Children::Object(map.iter())),
+            _ => None,
+        }
+    }
+}
+
+/// Position of a member within its parent
+#[derive(Clone, Copy)]
+pub(crate) enum ChildKey<'a> {
+    Index(usize),
+    Name(&'a String),
+}
+
+impl ChildKey<'_> {
+    fn to_element(self) -> PathElement {
+        match self {
+            ChildKey::Index(i) => PathElement::Index(i),
+            ChildKey::Name(name) => PathElement::Name(name.clone()),
+        }
+    }
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = (ChildKey<'a>, &'a Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Children::Array(iter) => iter.next().map(|(i, v)| (ChildKey::Index(i), v)),
+            Children::Object(iter) => iter.next().map(|(k, v)| (ChildKey::Name(k), v)),
+        }
     }
 }
 
@@ -675,6 +1765,623 @@ mod tests {
         evaluate(&parsed, json).into_iter().cloned().collect()
     }
 
+    #[test]
+    fn test_transform_only_rewrites_dots_outside_classes() {
+        let dot = "[^\\r\\n]";
+        let cases = [
+            ("a.b", format!("a{dot}b")),
+            (r"\.", r"\.".to_string()),
+            ("[.]", "[.]".to_string()),
+            (r"[\]a.].", format!(r"[\]a.]{dot}")),
+            (r"\[.\]", format!(r"\[{dot}\]")),
+            ("[]a.].", format!("[]a.]{dot}")),
+            ("[^]a.].", format!("[^]a.]{dot}")),
+            ("[a[b].].", format!("[a[b].]{dot}")),
+            ("[^a[^b].].", format!("[^a[^b].]{dot}")),
+            ("[[:alpha:].].", format!("[[:alpha:].]{dot}")),
+            ("[[:^digit:].].", format!("[[:^digit:].]{dot}")),
+            ("[[:a].].", format!("[[:a].]{dot}")),
+            (r"[\\].", format!(r"[\\]{dot}")),
+        ];
+        for (pattern, expected) in cases {
+            assert_eq!(
+                transform_pattern_for_iregexp(pattern),
+                expected,
+                "{pattern}"
+            );
+            assert!(Regex::new(&expected).is_ok(), "{expected}");
+        }
+    }
+
+    #[test]
+    fn test_match_and_search_with_bracket_classes() {
+        let json = json!(["]", ".", "a", "\r", "x]y", "[.", "\\"]);
+        let cases = [
+            (r#"$[?match(@, '[\\].]')]"#, vec![json!("]"), json!(".")]),
+            (r#"$[?match(@, '[]a]')]"#, vec![json!("]"), json!("a")]),
+            (r#"$[?match(@, '[^]a.]')]"#, vec![json!("\r"), json!("\\")]),
+            (r#"$[?match(@, '\\[.')]"#, vec![json!("[.")]),
+            (r#"$[?match(@, '[a[.]]')]"#, vec![json!("."), json!("a")]),
+            (r#"$[?search(@, 'x.y')]"#, vec![json!("x]y")]),
+            (r#"$[?search(@, '[\\]].')]"#, vec![json!("x]y")]),
+            (
+                r#"$[?match(@, '.')]"#,
+                vec![json!("]"), json!("."), json!("a"), json!("\\")],
+            ),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(query(path, &json), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_regex_cache_drops_least_recently_used() {
+        let mut cache = RegexCache::default();
+        cache.get_or_compile("a", true, false, false, 2);
+        cache.get_or_compile("b", false, false, false, 2);
+        cache.get_or_compile("a", true, false, false, 2);
+        cache.get_or_compile("c", true, false, false, 2);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.entries[1].contains_key("a"));
+        assert!(cache.entries[1].contains_key("c"));
+        assert!(!cache.entries[0].contains_key("b"));
+
+        // Same pattern in both modes takes two slots
+        cache.get_or_compile("c", false, false, false, 2);
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.entries[1].contains_key("a"));
+
+        // Case-insensitive patterns don't collide with case-sensitive ones
+        let insensitive = cache.get_or_compile("c", false, false, true, 2);
+        assert!(insensitive.unwrap().is_match("C"));
+        assert!(cache.entries[4].contains_key("c"));
+        let sensitive = cache.get_or_compile("c", false, false, false, 2);
+        assert!(!sensitive.unwrap().is_match("C"));
+    }
+
+    #[test]
+    fn test_regex_cache_matches_uncached() {
+        let patterns = ["a.c", "[.]", "b", "(", "^x|y$", "\\d+"];
+        let inputs = ["abc", "a\rc", ".", "xyz", "12", "b"];
+        let mut cache = RegexCache::default();
+        for _ in 0..3 {
+            for pattern in patterns {
+                for full_match in [true, false] {
+                    let cached = cache.get_or_compile(pattern, full_match, false, false, 3);
+                    let uncached = compile_iregexp(pattern, full_match, false, false);
+                    assert_eq!(cached.is_some(), uncached.is_some(), "{pattern}");
+                    for input in inputs {
+                        assert_eq!(
+                            cached.as_ref().map(|re| re.is_match(input)),
+                            uncached.as_ref().map(|re| re.is_match(input)),
+                            "{pattern} on {input:?}"
+                        );
+                    }
+                }
+            }
+        }
+        assert_eq!(cache.len(), 3);
+
+        // Capacity 0 compiles every time and keeps nothing
+        let mut disabled = RegexCache::default();
+        for _ in 0..2 {
+            let re = disabled.get_or_compile("b", true, false, false, 0);
+            assert!(re.is_some_and(|re| re.is_match("b") && !re.is_match("xbz")));
+        }
+        assert_eq!(
+            (disabled.len(), disabled.compiles, disabled.hits),
+            (0, 2, 0)
+        );
+    }
+
+    #[test]
+    fn test_regex_cache_lowered_capacity() {
+        let mut cache = RegexCache::default();
+        for pattern in ["a", "b", "c"] {
+            cache.get_or_compile(pattern, true, false, false, 3);
+        }
+        cache.get_or_compile("a", true, false, false, 3);
+        assert_eq!((cache.len(), cache.hits), (3, 1));
+
+        // Lowering the capacity evicts before the lookup, least recently used first
+        cache.get_or_compile("a", true, false, false, 2);
+        assert_eq!((cache.len(), cache.hits), (2, 2));
+        assert!(!cache.entries[1].contains_key("b"));
+
+        // What set_regex_cache_capacity(0) does on the current thread
+        cache.trim(0);
+        assert_eq!(cache.len(), 0);
+        // Earlier patterns are compiled again, and nothing new is kept
+        for pattern in ["a", "c", "d"] {
+            assert!(
+                cache
+                    .get_or_compile(pattern, true, false, false, 0)
+                    .is_some()
+            );
+        }
+        assert_eq!((cache.len(), cache.hits, cache.compiles), (0, 2, 6));
+    }
+
+    #[test]
+    fn test_literal_and_dynamic_patterns_agree() {
+        let json = json!([
+            {"s": "abc", "p": "a.c"},
+            {"s": "a\rc", "p": "a.c"},
+            {"s": "xabcx", "p": "a.c"},
+            {"s": "abc", "p": "a("},
+        ]);
+        for function in ["match", "search"] {
+            for pattern in ["a.c", "a("] {
+                let literal = format!("$[?{function}(@.s, '{pattern}') && @.p == '{pattern}']");
+                let dynamic = format!("$[?{function}(@.s, @.p) && @.p == '{pattern}']");
+                assert_eq!(query(&literal, &json), query(&dynamic, &json), "{literal}");
+            }
+        }
+        assert_eq!(query("$[?search(@.s, 'a.c')]", &json).len(), 3);
+    }
+
+    #[test]
+    fn test_strict_iregexp_patterns() {
+        let json = json!([
+            {"s": "12", "p": "\\d+"},
+            {"s": "^a", "p": "^a"},
+            {"s": "a", "p": "^a"},
+        ]);
+        let strict = |q: &str| {
+            let options = crate::ParseOptions {
+                strict_iregexp: true,
+                ..crate::ParseOptions::default()
+            };
+            let (path, _) = Parser::parse_with_options(q, &options).unwrap();
+            evaluate(&path, &json)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        // Non-I-Regexp patterns from the document are false in strict mode
+        assert_eq!(
+            query("$[?match(@.s, @.p)]", &json),
+            vec![json[0].clone(), json[2].clone()]
+        );
+        assert_eq!(strict("$[?match(@.s, @.p)]"), vec![json[1].clone()]);
+        // '^' is an ordinary character in I-Regexp
+        assert_eq!(strict("$[?search(@.s, '^a')]"), vec![json[1].clone()]);
+        assert_eq!(query("$[?search(@.s, '^a')]", &json), vec![json[2].clone()]);
+    }
+
+    #[test]
+    fn test_large_integer_ids_compare_exactly() {
+        let json = json!([
+            {"id": 9007199254740992_u64},
+            {"id": 9007199254740993_u64},
+            {"id": 18446744073709551615_u64},
+            {"id": -9223372036854775807_i64},
+            {"id": 9007199254740993.0}
+        ]);
+        assert_eq!(
+            query("$[?@.id == 9007199254740993]", &json),
+            vec![json!({"id": 9007199254740993_u64})]
+        );
+        assert_eq!(
+            query("$[?@.id == 18446744073709551615]", &json),
+            vec![json!({"id": 18446744073709551615_u64})]
+        );
+        assert_eq!(query("$[?@.id > 9007199254740992]", &json).len(), 2);
+        assert_eq!(query("$[?@.id != 9007199254740992]", &json).len(), 3);
+        assert_eq!(
+            query("$[?@.id < -9223372036854775806]", &json),
+            vec![json!({"id": -9223372036854775807_i64})]
+        );
+        // Integral floats compare exactly with integers
+        assert_eq!(query("$[?@.id == 9007199254740992.0]", &json).len(), 2);
+        assert_eq!(query("$[?@.id == 9007199254740993.0]", &json).len(), 2);
+        assert_eq!(query("$[?@[0] == 1]", &json!([[1.0], [1], [2]])).len(), 2);
+        // The float document value is 2^53 after parsing
+        assert_eq!(query("$[?@.id >= 9007199254740993]", &json).len(), 2);
+        assert_eq!(query("$[?@.id < 9007199254740993]", &json).len(), 3);
+        assert_eq!(query("$[?1 == 1.0]", &json).len(), 5);
+    }
+
+    fn budget_document() -> Value {
+        let items: Vec<Value> = (0..200)
+            .map(|i| json!({"id": i, "tags": ["a", i]}))
+            .collect();
+        json!({"items": items, "meta": {"count": 200}})
+    }
+
+    #[test]
+    fn test_node_budget_fails_only_when_exceeded() {
+        let json = budget_document();
+        for query in [
+            "$..*",
+            "$..id",
+            "$.items[?@.id > 100].tags[1]",
+            "$.items[*]",
+        ] {
+            let path = Parser::parse(query).unwrap();
+            let within = |max_nodes| {
+                let options = EvalOptions {
+                    max_nodes: Some(max_nodes),
+                    ..EvalOptions::default()
+                };
+                evaluate_with_options(&path, &json, &options)
+            };
+            let needed = (0..).find(|&max| within(max).is_ok()).unwrap();
+            assert_eq!(within(needed).unwrap(), evaluate(&path, &json), "{query}");
+            assert_eq!(
+                within(usize::MAX).unwrap(),
+                evaluate(&path, &json),
+                "{query}"
+            );
+            assert_eq!(
+                within(needed - 1),
+                Err(EvalError::BudgetExceeded {
+                    visited: needed,
+                    limit: BudgetLimit::MaxNodes
+                }),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_deadline_stops_evaluation() {
+        let json = budget_document();
+        let path = Parser::parse("$..*").unwrap();
+        let passed = EvalOptions {
+            deadline: Some(Instant::now()),
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            evaluate_with_options(&path, &json, &passed),
+            Err(EvalError::BudgetExceeded {
+                visited: DEADLINE_CHECK_INTERVAL,
+                limit: BudgetLimit::Deadline
+            })
+        );
+        let ahead = EvalOptions {
+            deadline: Some(Instant::now() + std::time::Duration::from_secs(3600)),
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            evaluate_with_options(&path, &json, &ahead).unwrap(),
+            evaluate(&path, &json)
+        );
+        assert_eq!(
+            evaluate_with_options(&path, &json, &EvalOptions::default()).unwrap(),
+            evaluate(&path, &json)
+        );
+    }
+
+    #[test]
+    fn test_result_limit() {
+        let json = budget_document();
+        let path = Parser::parse("$.items[*].id").unwrap();
+        let within = |max_results| EvalOptions {
+            max_results: Some(max_results),
+            ..EvalOptions::default()
+        };
+        assert_eq!(
+            evaluate_with_options(&path, &json, &within(200)).unwrap(),
+            evaluate(&path, &json)
+        );
+        assert_eq!(
+            evaluate_located_with_options(&path, &json, &within(200)).unwrap(),
+            evaluate_located(&path, &json)
+        );
+        assert!(matches!(
+            evaluate_with_options(&path, &json, &within(199)),
+            Err(EvalError::BudgetExceeded {
+                limit: BudgetLimit::MaxResults,
+                ..
+            })
+        ));
+        assert!(matches!(
+            evaluate_located_with_options(&path, &json, &within(0)),
+            Err(EvalError::BudgetExceeded {
+                limit: BudgetLimit::MaxResults,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_evaluate_limit() {
+        let json = json!({"a": [1, {"b": [2, 3]}], "c": {"b": 4}});
+        for query in ["$..*", "$..b", "$.a[*]", "$[?@.b]", "$.missing"] {
+            let path = Parser::parse(query).unwrap();
+            let all = evaluate(&path, &json);
+            for limit in 0..=all.len() + 1 {
+                let expected = &all[..limit.min(all.len())];
+                assert_eq!(
+                    evaluate_limit(&path, &json, limit),
+                    expected,
+                    "{query} {limit}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_count() {
+        let json = json!({"a": [1, {"b": [2, 3]}], "c": {"b": 4}});
+        for query in ["$", "$..*", "$..b", "$.a[0, 0, 1]", "$[?@.b]", "$.missing"] {
+            let path = Parser::parse(query).unwrap();
+            assert_eq!(count(&path, &json), evaluate(&path, &json).len(), "{query}");
+        }
+    }
+
+    #[test]
+    fn test_stats_count_work() {
+        let json = json!({
+            "a": {"b": 1, "c": [2, 3]},
+            "items": [{"s": "ab", "p": "a."}, {"s": "ac", "p": "a."}, {"s": "b", "p": "a."}]
+        });
+        let stats = |query: &str| {
+            let path = Parser::parse(query).unwrap();
+            let (results, stats) = evaluate_with_stats(&path, &json);
+            assert_eq!(results, evaluate(&path, &json), "{query}");
+            assert_eq!(stats.nodes_matched, results.len(), "{query}");
+            // (visited, matched, descendant visits, filter evaluations)
+            (
+                stats.nodes_visited,
+                stats.nodes_matched,
+                stats.descendant_visits,
+                stats.filter_evaluations,
+            )
+        };
+        assert_eq!(stats("$.a.b"), (3, 1, 0, 0));
+        assert_eq!(stats("$.a.missing"), (2, 0, 0, 0));
+        assert_eq!(stats("$.a.c[*]"), (5, 2, 0, 0));
+        // Walks $, a, c, items and the three item objects
+        assert_eq!(stats("$..b"), (2, 1, 7, 0));
+        assert_eq!(stats("$.items[?@.s == 'b']"), (3, 1, 0, 3));
+
+        clear_regex_cache();
+        let path = Parser::parse("$.items[?match(@.s, @.p)]").unwrap();
+        let (results, stats) = evaluate_with_stats(&path, &json);
+        assert_eq!(results.len(), 2);
+        assert_eq!((stats.regex_compilations, stats.regex_cache_hits), (1, 2));
+        // Literal patterns are compiled by the parser
+        let path = Parser::parse("$.items[?match(@.s, 'a.')]").unwrap();
+        let (_, stats) = evaluate_with_stats(&path, &json);
+        assert_eq!((stats.regex_compilations, stats.regex_cache_hits), (0, 0));
+    }
+
+    #[test]
+    fn test_deep_documents_do_not_overflow_the_stack() {
+        const DEPTH: usize = 100_000;
+        // `{"a": [{"a": [... {"leaf": true} ...]}]}`
+        fn deep() -> Value {
+            (0..DEPTH).fold(json!({"leaf": true}), |inner, _| {
+                // Not `json!`, which would serialize `inner` recursively
+                Value::Object(
+                    [("a".to_string(), Value::Array(vec![inner]))]
+                        .into_iter()
+                        .collect(),
+                )
+            })
+        }
+        // serde_json drops values recursively, so take them apart by hand
+        fn dismantle(value: Value) {
+            let mut stack = vec![value];
+            while let Some(value) = stack.pop() {
+                match value {
+                    Value::Array(arr) => stack.extend(arr),
+                    Value::Object(map) => stack.extend(map.into_iter().map(|(_, v)| v)),
+                    _ => {}
+                }
+            }
+        }
+
+        let json = Value::Object(
+            [("l".to_string(), deep()), ("r".to_string(), deep())]
+                .into_iter()
+                .collect(),
+        );
+        for (query, count) in [
+            ("$..leaf", 2),
+            ("$..[?@.leaf]", 2),
+            ("$..*[?@ == true]", 2),
+            ("$[?$.l == $.r]", 2),
+        ] {
+            let path = Parser::parse(query).unwrap();
+            assert_eq!(evaluate(&path, &json).len(), count, "{query}");
+            assert_eq!(evaluate_located(&path, &json).len(), count, "{query}");
+        }
+        let trace = Parser::parse("$..leaf").unwrap().query_trace(&json);
+        assert_eq!(trace.result_count, 2);
+        dismantle(json);
+    }
+
+    #[test]
+    fn test_filter_subpaths_agree_with_top_level() {
+        let json = json!({
+            "a": [1, {"a": [2, 3], "x": true}, [4, [5]]],
+            "b": {"a": {"x": 6}, "c": null},
+            "x": "top"
+        });
+        let suffixes = [
+            ".a",
+            "['a', 'b', 'a']",
+            ".a[1]",
+            ".a[-1]",
+            ".missing",
+            "[*]",
+            ".a[*]",
+            ".a[1:]",
+            ".a[::-1]",
+            ".a[5:0:-2]",
+            ".a[?@ > 0]",
+            "[?@.x]",
+            "..a",
+            "..*",
+            "..[0]",
+            "..[?@.x]",
+            ".a..[1:]",
+            ".b[*]..x",
+        ];
+        for suffix in suffixes {
+            let top = Parser::parse(&format!("${suffix}")).unwrap();
+            let expected = evaluate(&top, &json);
+            let located: Vec<&Value> = evaluate_located(&top, &json)
+                .into_iter()
+                .map(|(_, v)| v)
+                .collect();
+            assert_eq!(located, expected, "located ${suffix}");
+
+            let filter = Parser::parse(&format!("$[?@{suffix}]")).unwrap();
+            let Segment::Child(selectors) = &filter.segments[0] else {
+                unreachable!()
+            };
+            let Selector::Filter(expr) = &selectors[0] else {
+                unreachable!()
+            };
+            let in_filter = match evaluate_expr(expr, Current::unkeyed(&json), &json) {
+                ExprResult::NodeList(list) => list.to_vec(),
+                _ => Vec::new(),
+            };
+            assert_eq!(in_filter, expected, "@{suffix}");
+        }
+    }
+
+    #[test]
+    fn test_descendants_match_naive_walk() {
+        /// Every node in document order, the way RFC 9535 defines descendants
+        fn walk<'a>(node: &'a Value, out: &mut Vec<&'a Value>) {
+            out.push(node);
+            match node {
+                Value::Array(arr) => arr.iter().for_each(|c| walk(c, out)),
+                Value::Object(map) => map.values().for_each(|c| walk(c, out)),
+                _ => {}
+            }
+        }
+
+        let json = json!({
+            "a": [1, {"a": [2, "x"], "b": null}, [[3]]],
+            "b": {"a": {"a": 4}, "c": []},
+            "c": "leaf"
+        });
+        let mut nodes = Vec::new();
+        walk(&json, &mut nodes);
+        for selectors in ["a", "*", "[0]", "[-1]", "[1:]", "[?@ == 2]", "['a', 0]"] {
+            let descendant = Parser::parse(&format!("$..{selectors}")).unwrap();
+            let (Segment::Descendant(selectors) | Segment::Child(selectors)) =
+                &descendant.segments[0];
+            let expected: Vec<&Value> = nodes
+                .iter()
+                .flat_map(|node| {
+                    selectors
+                        .iter()
+                        .flat_map(|s| evaluate_selector(s, node, &json))
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            assert_eq!(evaluate(&descendant, &json), expected, "{descendant}");
+            let located: Vec<&Value> = evaluate_located(&descendant, &json)
+                .into_iter()
+                .map(|(_, v)| v)
+                .collect();
+            assert_eq!(located, expected, "{descendant}");
+        }
+    }
+
+    #[test]
+    fn test_truthiness_is_logical_or_existence() {
+        let (zero, empty) = (json!(0), json!(""));
+        assert!(ExprResult::Value(&zero).is_truthy());
+        assert!(ExprResult::Value(&empty).is_truthy());
+        assert!(ExprResult::OwnedValue(json!(false)).is_truthy());
+        assert!(ExprResult::NodeList(smallvec![&zero]).is_truthy());
+        assert!(!ExprResult::NodeList(SmallVec::new()).is_truthy());
+        assert!(!ExprResult::Nothing.is_truthy());
+        assert!(ExprResult::Logical(true).is_truthy());
+        assert!(!ExprResult::Logical(false).is_truthy());
+
+        let json = json!({"s": "abc", "n": 0});
+        let logical = |filter: &str| {
+            let expr = Parser::parse_filter(filter).unwrap();
+            match evaluate_expr(&expr, Current::unkeyed(&json), &json) {
+                ExprResult::Logical(b) => Some(b),
+                _ => None,
+            }
+        };
+        assert_eq!(logical("@.n == 0"), Some(true));
+        assert_eq!(logical("@.n && @.s"), Some(true));
+        assert_eq!(logical("@.n || @.missing"), Some(true));
+        assert_eq!(logical("!@.n"), Some(false));
+        assert_eq!(logical("match(@.s, 'a.c')"), Some(true));
+        assert_eq!(logical("search(@.n, 'a')"), Some(false));
+        assert_eq!(logical("@.n"), None);
+    }
+
+    #[cfg(feature = "extended-functions")]
+    #[test]
+    fn test_key_without_filter_candidate_is_nothing() {
+        let json = json!({"a": 1});
+        assert!(matches!(
+            fn_key(Current::unkeyed(&json)),
+            ExprResult::Nothing
+        ));
+        let key = String::from("a");
+        let current = Current {
+            value: &json["a"],
+            key: Some(ChildKey::Name(&key)),
+            parent: Some(&json),
+            roots: &[],
+        };
+        assert_eq!(fn_key(current).to_value(), Some(&json!("a")));
+        assert!(matches!(
+            fn_parent(Current::unkeyed(&json)),
+            ExprResult::Nothing
+        ));
+        assert!(matches!(
+            fn_parent(current),
+            ExprResult::NodeList(nodes) if nodes.as_slice() == [&json]
+        ));
+    }
+
+    #[test]
+    fn test_nothing_comparison_truth_table() {
+        let json = json!([{"a": 1}]);
+        // (operator, Nothing vs Nothing, Nothing vs value, value vs Nothing)
+        let table = [
+            ("==", true, false, false),
+            ("!=", false, true, true),
+            ("<", false, false, false),
+            ("<=", true, false, false),
+            (">", false, false, false),
+            (">=", true, false, false),
+        ];
+        for (op, both, left, right) in table {
+            for (query_str, expected) in [
+                (format!("$[?@.x {op} @.y]"), both),
+                (format!("$[?@.x {op} @.a]"), left),
+                (format!("$[?@.a {op} @.x]"), right),
+                (format!("$[?@.x {op} 1]"), left),
+            ] {
+                assert_eq!(query(&query_str, &json).len() == 1, expected, "{query_str}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_structured_values_compare_numbers_by_value() {
+        let json = json!({
+            "a": [1, {"b": 2.0}],
+            "b": [1.0, {"b": 2}],
+            "c": [9007199254740992_u64],
+            "d": [9007199254740993_u64]
+        });
+        assert_eq!(query("$[?@ == $.a]", &json).len(), 2);
+        assert_eq!(query("$[?@ != $.a]", &json).len(), 2);
+        assert_eq!(
+            query("$[?@ >= $.c]", &json),
+            vec![json!([9007199254740992_u64])]
+        );
+    }
+
     #[test]
     fn test_root_only() {
         let json = json!({"foo": "bar"});
@@ -1297,6 +3004,40 @@ mod tests {
         assert_eq!(results[2], json!({"a": 1, "b": 2}));
     }
 
+    // ========== Located Evaluation Tests ==========
+
+    fn query_paths(path: &str, json: &Value) -> Vec<String> {
+        let parsed = Parser::parse(path).unwrap();
+        evaluate_located(&parsed, json)
+            .into_iter()
+            .map(|(location, _)| location.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_located_child_paths() {
+        let json = json!({"a": [10, 20, 30]});
+        assert_eq!(query_paths("$", &json), vec!["$"]);
+        assert_eq!(query_paths("$.a[-1]", &json), vec!["$['a'][2]"]);
+        assert_eq!(
+            query_paths("$.a[::-2]", &json),
+            vec!["$['a'][2]", "$['a'][0]"]
+        );
+        assert_eq!(
+            query_paths("$.a[?@ > 15]", &json),
+            vec!["$['a'][1]", "$['a'][2]"]
+        );
+    }
+
+    #[test]
+    fn test_located_descendant_paths() {
+        let json = json!({"x": {"y": 1}, "z": [{"y": 2}]});
+        assert_eq!(
+            query_paths("$..y", &json),
+            vec!["$['x']['y']", "$['z'][0]['y']"]
+        );
+    }
+
     #[test]
     fn test_mixed_selectors() {
         // $[0,'a'] - mix of index and property (only index applies to array)
@@ -1306,4 +3047,135 @@ mod tests {
         assert_eq!(results.len(), 1);
         assert_eq!(results[0], json!("first"));
     }
+
+    #[cfg(feature = "arithmetic")]
+    #[test]
+    fn test_arithmetic() {
+        let json = json!({
+            "items": [
+                {"id": 1, "price": 20, "qty": 6},
+                {"id": 2, "price": 2.5, "qty": 40},
+                {"id": 3, "price": 9.99, "qty": 10},
+                {"id": 4, "price": "free", "qty": 1000}
+            ],
+            "metrics": [{"used": 95, "total": 100}, {"used": 5, "total": 0}]
+        });
+        let options = crate::ParseOptions {
+            arithmetic: true,
+            ..Default::default()
+        };
+        let ids = |query: &str| -> Vec<Value> {
+            let (path, _) = Parser::parse_with_options(query, &options).unwrap();
+            evaluate(&path, &json)
+                .into_iter()
+                .map(|v| v["id"].clone())
+                .collect()
+        };
+        assert_eq!(ids("$.items[?@.price * @.qty > 100]"), [json!(1)]);
+        assert_eq!(ids("$.items[?@.price * @.qty == 100]"), [json!(2)]);
+        let path = "$.metrics[?@.used / @.total > 0.9]";
+        let (path, _) = Parser::parse_with_options(path, &options).unwrap();
+        assert_eq!(evaluate(&path, &json), [&json["metrics"][0]]);
+        // Non-numbers and division by zero are Nothing
+        assert_eq!(ids("$.items[?@.price + 1 == @.missing]"), [json!(4)]);
+        assert_eq!(ids("$.items[?@.missing - 1 == @.missing]").len(), 4);
+        assert_eq!(ids("$.items[?@.qty / 0 == @.missing]").len(), 4);
+        assert!(ids("$.items[?@.qty / 0 != @.missing]").is_empty());
+
+        // Precedence, associativity and mixed integer/float results
+        for (filter, holds) in [
+            ("2 + 3 * 4 == 14", true),
+            ("(2 + 3) * 4 == 20", true),
+            ("1 - 2 - 3 == -4", true),
+            ("24 / 4 / 2 == 3", true),
+            ("7 / 2 == 3.5", true),
+            ("6 / 3 == 2", true),
+            ("0.1 + 0.2 == 0.30000000000000004", true),
+            ("1 + 0.5 == 1.5", true),
+            ("-7 / 2 == -3.5", true),
+            ("2 * -3 == -6", true),
+        ] {
+            let query = format!("$.items[?{filter}]");
+            assert_eq!(ids(&query).len() == 4, holds, "{filter}");
+        }
+    }
+
+    #[test]
+    fn test_arithmetic_results() {
+        let op = |a: Value, op, b: Value| arithmetic(&a, op, &b);
+        assert_eq!(op(json!(2), ArithOp::Add, json!(3)), Some(json!(5)));
+        assert_eq!(op(json!(2), ArithOp::Mul, json!(1.5)), Some(json!(3.0)));
+        assert_eq!(op(json!(6), ArithOp::Div, json!(4)), Some(json!(1.5)));
+        assert_eq!(op(json!(-6), ArithOp::Div, json!(3)), Some(json!(-2)));
+        // Integers overflowing i64 continue in f64
+        assert_eq!(
+            op(json!(i64::MAX), ArithOp::Add, json!(1)),
+            Some(json!(-(i64::MIN as f64)))
+        );
+        assert_eq!(
+            op(json!(i64::MIN), ArithOp::Div, json!(-1)),
+            Some(json!(-(i64::MIN as f64)))
+        );
+        assert_eq!(
+            op(json!(u64::MAX), ArithOp::Sub, json!(1)),
+            Some(json!(1.8446744073709552e19))
+        );
+        assert_eq!(op(json!(1), ArithOp::Div, json!(0)), None);
+        assert_eq!(op(json!(1.5), ArithOp::Div, json!(0.0)), None);
+        assert_eq!(op(json!(1e308), ArithOp::Mul, json!(10)), None);
+        assert_eq!(op(json!("1"), ArithOp::Add, json!(1)), None);
+        assert_eq!(op(json!(null), ArithOp::Sub, json!(1)), None);
+    }
+
+    #[test]
+    fn test_root_queries_run_once_per_filter() {
+        use crate::function::FunctionRegistry;
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut registry = FunctionRegistry::new();
+        let counter = Arc::clone(&calls);
+        registry
+            .register(
+                "seen",
+                &[FunctionType::Value],
+                FunctionType::Logical,
+                move |_| {
+                    counter.fetch_add(1, Ordering::Relaxed);
+                    ExprValue::Logical(true)
+                },
+            )
+            .unwrap();
+        let json = json!({
+            "config": {"limits": [{"max": 10}, {"max": 20}], "off": null},
+            "items": [
+                {"n": 1, "tags": [1, 3]},
+                {"n": 2, "tags": [3]},
+                {"n": 3, "tags": []}
+            ]
+        });
+        let query = |path: &str| {
+            calls.store(0, Ordering::Relaxed);
+            let results = JsonPath::parse_with_registry(path, &registry)
+                .unwrap()
+                .query(&json)
+                .into_iter()
+                .cloned()
+                .collect::<Vec<_>>();
+            (results, calls.load(Ordering::Relaxed))
+        };
+
+        // The nested filter of the `$` query runs over the two limits once, not once per item
+        let (results, calls) = query("$.items[?count($.config.limits[?seen(@)]) > @.n].n");
+        assert_eq!((results, calls), (vec![json!(1)], 2));
+        // Existence tests, function arguments and comparisons read the same cached nodes
+        let (results, _) = query("$.items[?$.config.off && count($.config.limits[*]) == 2].n");
+        assert_eq!(results, vec![json!(1), json!(2), json!(3)]);
+        let (results, _) = query("$.items[?$.config.missing.max].n");
+        assert!(results.is_empty());
+        // A nested filter applied to each item's tags keeps its own cache, filled only when a
+        // tag is tested
+        let (results, calls) = query("$.items[*].tags[?@ > count($.config.limits[?seen(@)])]");
+        assert_eq!((results, calls), (vec![json!(3), json!(3)], 4));
+    }
 }
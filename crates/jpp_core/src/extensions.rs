@@ -0,0 +1,658 @@
+//! Non-RFC filter functions, see [`FunctionRegistry::extended`]
+//!
+//! [`FunctionRegistry::extended`]: crate::FunctionRegistry::extended
+
+use crate::ast::CompOp;
+use crate::eval::{compare_json_values, values_equal};
+use crate::function::FunctionType;
+use serde_json::{Number, Value};
+
+/// Name, parameter types and result type of each extension function
+pub(crate) const SIGNATURES: &[(&str, &[FunctionType], FunctionType)] = &[
+    ("min", &[FunctionType::Nodes], FunctionType::Value),
+    ("max", &[FunctionType::Nodes], FunctionType::Value),
+    ("sum", &[FunctionType::Nodes], FunctionType::Value),
+    ("avg", &[FunctionType::Nodes], FunctionType::Value),
+    ("key", &[], FunctionType::Value),
+    (
+        "imatch",
+        &[FunctionType::Value, FunctionType::Value],
+        FunctionType::Logical,
+    ),
+    (
+        "isearch",
+        &[FunctionType::Value, FunctionType::Value],
+        FunctionType::Logical,
+    ),
+    ("parent", &[], FunctionType::Nodes),
+    ("type", &[FunctionType::Value], FunctionType::Value),
+    ("to_number", &[FunctionType::Value], FunctionType::Value),
+    ("to_string", &[FunctionType::Value], FunctionType::Value),
+    (
+        "contains",
+        &[FunctionType::Value, FunctionType::Value],
+        FunctionType::Logical,
+    ),
+    (
+        "starts_with",
+        &[FunctionType::Value, FunctionType::Value],
+        FunctionType::Logical,
+    ),
+    (
+        "ends_with",
+        &[FunctionType::Value, FunctionType::Value],
+        FunctionType::Logical,
+    ),
+];
+
+/// Evaluate the aggregation `name` over a nodelist; None for Nothing
+pub(crate) fn aggregate<'v>(name: &str, nodes: impl Iterator<Item = &'v Value>) -> Option<Value> {
+    let numbers = nodes.filter(|node| node.is_number());
+    match name {
+        "min" => numbers.reduce(|a, b| pick(a, CompOp::Lt, b)).cloned(),
+        "max" => numbers.reduce(|a, b| pick(a, CompOp::Gt, b)).cloned(),
+        "sum" => sum(numbers),
+        "avg" => avg(numbers),
+        _ => None,
+    }
+}
+
+/// JSON type name reported by type()
+pub(crate) fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Numbers as they are, and strings holding exactly a JSON number (no surrounding whitespace)
+/// as that number; None (Nothing) for anything else
+pub(crate) fn to_number(value: &Value) -> Option<Value> {
+    match value {
+        Value::Number(_) => Some(value.clone()),
+        Value::String(s) => s.parse::<Number>().ok().map(Value::Number),
+        _ => None,
+    }
+}
+
+/// Strings as they are, and numbers and booleans as their JSON text; None (Nothing) for
+/// anything else
+pub(crate) fn to_string(value: &Value) -> Option<Value> {
+    match value {
+        Value::String(_) => Some(value.clone()),
+        Value::Number(n) => Some(Value::String(n.to_string())),
+        Value::Bool(b) => Some(Value::String(b.to_string())),
+        _ => None,
+    }
+}
+
+/// Whether `haystack` is an array with an element equal to `needle`; false for other values
+pub(crate) fn contains(haystack: &Value, needle: &Value) -> bool {
+    match haystack {
+        Value::Array(elements) => elements.iter().any(|element| values_equal(element, needle)),
+        _ => false,
+    }
+}
+
+/// Literal prefix (`starts_with`) or suffix (`ends_with`) test
+pub(crate) fn has_affix(name: &str, string: &str, affix: &str) -> bool {
+    match name {
+        "starts_with" => string.starts_with(affix),
+        "ends_with" => string.ends_with(affix),
+        _ => false,
+    }
+}
+
+/// `b` if it compares `op` to `a`, else `a`: the first of equal extremes wins
+fn pick<'v>(a: &'v Value, op: CompOp, b: &'v Value) -> &'v Value {
+    if compare_json_values(b, op, a) { b } else { a }
+}
+
+/// Integer sum while every number is an integer and the total fits, float sum otherwise
+fn sum<'v>(numbers: impl Iterator<Item = &'v Value>) -> Option<Value> {
+    let mut int = Some(0i64);
+    let mut float = 0.0;
+    let mut any = false;
+    for n in numbers {
+        any = true;
+        int = int.zip(n.as_i64()).and_then(|(a, b)| a.checked_add(b));
+        float += n.as_f64().unwrap_or_default();
+    }
+    match int {
+        _ if !any => None,
+        Some(total) => Some(Value::from(total)),
+        None => Number::from_f64(float).map(Value::Number),
+    }
+}
+
+/// Arithmetic mean as a float
+fn avg<'v>(numbers: impl Iterator<Item = &'v Value>) -> Option<Value> {
+    let (count, total) = numbers.fold((0usize, 0.0), |(count, total), n| {
+        (count + 1, total + n.as_f64().unwrap_or_default())
+    });
+    if count == 0 {
+        return None;
+    }
+    Number::from_f64(total / count as f64).map(Value::Number)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::{FunctionRegistry, JsonPath, ParseErrorKind};
+    use serde_json::json;
+
+    #[test]
+    fn test_aggregations_skip_non_numbers() {
+        let nodes = [
+            json!(3),
+            json!(1.5),
+            json!("9"),
+            json!(null),
+            json!(2),
+            json!([100]),
+        ];
+        for (name, expected) in [
+            ("min", json!(1.5)),
+            ("max", json!(3)),
+            ("sum", json!(6.5)),
+            ("avg", json!(6.5 / 3.0)),
+        ] {
+            assert_eq!(aggregate(name, nodes.iter()), Some(expected), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_aggregations_without_numbers_are_nothing() {
+        for name in ["min", "max", "sum", "avg"] {
+            assert_eq!(aggregate(name, [].iter()), None, "{name}");
+            assert_eq!(
+                aggregate(name, [json!("1"), json!(true)].iter()),
+                None,
+                "{name}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_sum_stays_integral_until_overflow() {
+        let sum = aggregate("sum", [json!(2), json!(3)].iter()).unwrap();
+        assert!(sum.is_i64());
+        assert_eq!(sum, json!(5));
+        assert_eq!(
+            aggregate("sum", [json!(i64::MAX), json!(1)].iter()),
+            Some(json!(i64::MAX as f64 + 1.0))
+        );
+        assert_eq!(
+            aggregate("max", [json!(1), json!(1.0)].iter()).unwrap(),
+            json!(1)
+        );
+    }
+
+    #[test]
+    fn test_aggregations_in_queries() {
+        let json = json!({
+            "orders": [
+                {"id": "a", "total": 10, "items": [{"qty": 1}, {"qty": 2}]},
+                {"id": "b", "total": 40, "items": [{"qty": 5}, {"qty": 3}]},
+                {"id": "c", "total": 25, "items": []}
+            ]
+        });
+        let ids = |query: &str| -> Vec<Value> {
+            let path = JsonPath::parse_with_registry(query, &FunctionRegistry::extended()).unwrap();
+            path.query(&json)
+                .into_iter()
+                .map(|o| o["id"].clone())
+                .collect()
+        };
+        assert_eq!(
+            ids("$.orders[?@.total > avg($.orders[*].total)]"),
+            [json!("b")]
+        );
+        assert_eq!(
+            ids("$.orders[?@.total == max($.orders[*].total)]"),
+            [json!("b")]
+        );
+        assert_eq!(ids("$.orders[?@.total == min($..total)]"), [json!("a")]);
+        assert_eq!(ids("$.orders[?sum(@.items[*].qty) > 5]"), [json!("b")]);
+        // No items: Nothing, which equals only Nothing
+        assert_eq!(
+            ids("$.orders[?sum(@.items[*].qty) == @.missing]"),
+            [json!("c")]
+        );
+    }
+
+    #[test]
+    fn test_extensions_need_the_extended_registry() {
+        for query in ["$[?min(@.*) == 1]", "$[?avg(@.*) > 1]"] {
+            let err = JsonPath::parse(query).unwrap_err();
+            assert_eq!(
+                err.parse_error().unwrap().kind,
+                ParseErrorKind::UnknownFunction
+            );
+            assert!(JsonPath::parse_with_registry(query, &FunctionRegistry::extended()).is_ok());
+        }
+        let registry = FunctionRegistry::extended();
+        for (query, kind) in [
+            ("$[?sum(@.*)]", ParseErrorKind::FunctionResultType),
+            ("$[?max(1) == 1]", ParseErrorKind::FunctionArgumentType),
+            ("$[?min(@.a, @.b) == 1]", ParseErrorKind::FunctionArity),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_key_of_filter_candidates() {
+        let json = json!({
+            "config": {"ssl_cert": "a.pem", "port": 443, "ssl_key": "a.key"},
+            "list": ["x", "y", "z"],
+            "nested": {"a": {"a": 1, "b": 2}}
+        });
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &FunctionRegistry::extended()).unwrap();
+            let located = path.query_located(&json);
+            let trace = path.query_trace(&json);
+            assert_eq!(trace.result_count, located.len(), "{query}");
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        assert_eq!(
+            query("$.config[?match(key(), 'ssl_.*')]"),
+            ["$['config']['ssl_cert']", "$['config']['ssl_key']"]
+        );
+        assert_eq!(
+            query("$.list[?key() >= 1]"),
+            ["$['list'][1]", "$['list'][2]"]
+        );
+        assert_eq!(
+            query("$..[?key() == 'a']"),
+            ["$['nested']['a']", "$['nested']['a']['a']"]
+        );
+        // The inner filter sees its own candidates' keys
+        assert_eq!(query("$.*[?@[?key() == 'b']]"), ["$['nested']['a']"]);
+        assert!(query("$.config[?key() == 0]").is_empty());
+    }
+
+    #[test]
+    fn test_parent_of_filter_candidates() {
+        let json = json!({
+            "items": [
+                {"archived": false, "details": {"a": {"id": 1}}},
+                {"archived": true, "details": {"a": {"id": 2}}},
+                {"archived": false, "details": [{"id": 3}, {"id": 4}]}
+            ]
+        });
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &FunctionRegistry::extended()).unwrap();
+            let located = path.query_located(&json);
+            let trace = path.query_trace(&json);
+            assert_eq!(trace.result_count, located.len(), "{query}");
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        // parent() is the object or array holding the candidate
+        assert_eq!(
+            query("$.items[*].details[?parent().a.id == 1]"),
+            ["$['items'][0]['details']['a']"]
+        );
+        assert_eq!(
+            query("$.items[*].details[?parent()[1].id == 4]"),
+            ["$['items'][2]['details'][0]", "$['items'][2]['details'][1]"]
+        );
+        assert_eq!(
+            query("$.items[?parent()[0].archived == @.archived]"),
+            ["$['items'][0]", "$['items'][2]"]
+        );
+        // Inner filters see their own candidates' parents
+        assert_eq!(
+            query("$.items[?@.details[?parent().a]].archived"),
+            ["$['items'][0]['archived']", "$['items'][1]['archived']"]
+        );
+        // Candidates of a filter on the root have the root as parent
+        assert_eq!(query("$[?parent()]"), ["$['items']"]);
+        assert!(query("$[?parent().items == 1]").is_empty());
+
+        for query in ["$[?parent()]", "$[?parent().a == 1]"] {
+            let err = JsonPath::parse(query).unwrap_err();
+            assert_eq!(
+                err.parse_error().unwrap().kind,
+                ParseErrorKind::UnknownFunction
+            );
+        }
+        let registry = FunctionRegistry::extended();
+        for (query, kind) in [
+            ("$[?parent() == 1]", ParseErrorKind::FunctionResultType),
+            (
+                "$[?parent()..a == 1]",
+                ParseErrorKind::NonSingularComparison,
+            ),
+            // Only parent() can start a query
+            ("$[?key().a == 1]", ParseErrorKind::FunctionResultType),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_type_names() {
+        let json = json!({
+            "payload": {"n": null, "b": true, "i": 1, "f": 1.5, "s": "x", "a": [], "o": {}},
+            "items": [{"id": 1}, {"id": "2"}, {"name": "no id"}]
+        });
+        let registry = FunctionRegistry::extended();
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &registry).unwrap();
+            let located = path.query_located(&json);
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        for (name, members) in [
+            ("null", vec!["n"]),
+            ("boolean", vec!["b"]),
+            ("number", vec!["f", "i"]),
+            ("string", vec!["s"]),
+            ("array", vec!["a"]),
+            ("object", vec!["o"]),
+        ] {
+            let expected: Vec<String> = members
+                .iter()
+                .map(|m| format!("$['payload']['{m}']"))
+                .collect();
+            assert_eq!(query(&format!("$.payload[?type(@) == '{name}']")), expected);
+        }
+        assert_eq!(
+            query("$..[?type(@) == 'string']"),
+            [
+                "$['items'][1]['id']",
+                "$['items'][2]['name']",
+                "$['payload']['s']"
+            ]
+        );
+        // A missing member is Nothing, which differs from every type name
+        assert_eq!(
+            query("$.items[?type(@.id) != 'number']"),
+            ["$['items'][1]", "$['items'][2]"]
+        );
+        assert_eq!(
+            query("$.items[?type(@.id) == @.missing]"),
+            ["$['items'][2]"]
+        );
+
+        for (query, kind) in [
+            ("$[?type(@)]", ParseErrorKind::FunctionResultType),
+            (
+                "$[?type(@.*) == 'number']",
+                ParseErrorKind::FunctionArgumentType,
+            ),
+            ("$[?type() == 'number']", ParseErrorKind::FunctionArity),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+        let err = JsonPath::parse("$[?type(@) == 'null']").unwrap_err();
+        assert_eq!(
+            err.parse_error().unwrap().kind,
+            ParseErrorKind::UnknownFunction
+        );
+    }
+
+    #[test]
+    fn test_contains_compares_elements_deeply() {
+        let json = json!({
+            "allowed_statuses": ["open", "pending"],
+            "allowed_ids": [1, 2.0],
+            "owners": [{"name": "ann", "teams": ["a"]}],
+            "items": [
+                {"status": "open", "id": 1, "tags": ["urgent"], "owner": {"name": "ann", "teams": ["a"]}},
+                {"status": "closed", "id": 2, "tags": "urgent", "owner": {"name": "ann"}},
+                {"status": "pending", "id": 3.0, "tags": []}
+            ]
+        });
+        let registry = FunctionRegistry::extended();
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &registry).unwrap();
+            let located = path.query_located(&json);
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        assert_eq!(
+            query("$.items[?contains($.allowed_statuses, @.status)]"),
+            ["$['items'][0]", "$['items'][2]"]
+        );
+        // Numbers compare by value, objects and arrays member by member
+        assert_eq!(
+            query("$.items[?contains($.allowed_ids, @.id)]"),
+            ["$['items'][0]", "$['items'][1]"]
+        );
+        assert_eq!(
+            query("$.items[?contains($.owners, @.owner)]"),
+            ["$['items'][0]"]
+        );
+        // Non-array haystacks and Nothing contain nothing
+        assert_eq!(
+            query("$.items[?contains(@.tags, 'urgent')]"),
+            ["$['items'][0]"]
+        );
+        assert_eq!(
+            query("$.items[?!contains(@.missing, @.status)]"),
+            ["$['items'][0]", "$['items'][1]", "$['items'][2]"]
+        );
+        assert!(query("$.items[?contains($.allowed_statuses, @.missing)]").is_empty());
+
+        for (query, kind) in [
+            (
+                "$[?contains(@.a, 1) == true]",
+                ParseErrorKind::FunctionResultType,
+            ),
+            ("$[?contains(@.*, 1)]", ParseErrorKind::FunctionArgumentType),
+            ("$[?contains(@.a)]", ParseErrorKind::FunctionArity),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_starts_with_and_ends_with_are_literal() {
+        let json = json!([
+            {"path": "/api/users", "file": "report.tar.gz"},
+            {"path": "/apix", "file": "notes.txt"},
+            {"path": "/api/.*", "file": "日本語.テキスト"},
+            {"path": 1, "file": null},
+            {"path": "é", "file": "e\u{301}"}
+        ]);
+        let registry = FunctionRegistry::extended();
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &registry).unwrap();
+            let located = path.query_located(&json);
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        assert_eq!(query("$[?starts_with(@.path, '/api/')]"), ["$[0]", "$[2]"]);
+        // Patterns are not regular expressions
+        assert_eq!(query("$[?starts_with(@.path, '/api/.*')]"), ["$[2]"]);
+        assert_eq!(query("$[?ends_with(@.file, '.gz')]"), ["$[0]"]);
+        assert_eq!(query("$[?ends_with(@.file, 'テキスト')]"), ["$[2]"]);
+        assert_eq!(query("$[?starts_with(@.file, '日')]"), ["$[2]"]);
+        // Precomposed é does not start or end the decomposed form, nor vice versa
+        assert_eq!(query("$[?starts_with(@.file, 'e')]"), ["$[4]"]);
+        assert!(query("$[?starts_with(@.path, 'e')]").is_empty());
+        assert!(query("$[?ends_with(@.file, 'é')]").is_empty());
+        // An empty affix matches every string
+        assert_eq!(
+            query("$[?ends_with(@.file, '')]"),
+            ["$[0]", "$[1]", "$[2]", "$[4]"]
+        );
+        // Non-strings and Nothing are false
+        assert!(query("$[?starts_with(@.path, 1)]").is_empty());
+        assert!(query("$[?ends_with(@.missing, '')]").is_empty());
+        assert_eq!(
+            query("$[?!starts_with(@.file, @.missing)]"),
+            ["$[0]", "$[1]", "$[2]", "$[3]", "$[4]"]
+        );
+
+        for (query, kind) in [
+            (
+                "$[?starts_with(@.a, 'x') == true]",
+                ParseErrorKind::FunctionResultType,
+            ),
+            (
+                "$[?ends_with(@.a, 'x') != false]",
+                ParseErrorKind::FunctionResultType,
+            ),
+            (
+                "$[?starts_with(@..a, 'x')]",
+                ParseErrorKind::FunctionArgumentType,
+            ),
+            ("$[?ends_with(@.a)]", ParseErrorKind::FunctionArity),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+    }
+
+    #[test]
+    fn test_case_insensitive_regex_functions() {
+        let json = json!([
+            {"msg": "ERROR: disk full", "pattern": "error.*"},
+            {"msg": "error: Timeout", "pattern": "error: T.*"},
+            {"msg": "Straße", "pattern": "STRASSE"},
+            {"msg": "ΣΊΣΥΦΟΣ", "pattern": "σίσυφος"},
+            {"msg": "Warning", "pattern": "["}
+        ]);
+        let registry = FunctionRegistry::extended();
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &registry).unwrap();
+            let located = path.query_located(&json);
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        assert_eq!(query("$[?match(@.msg, 'error.*')]"), ["$[1]"]);
+        assert_eq!(query("$[?imatch(@.msg, 'error.*')]"), ["$[0]", "$[1]"]);
+        assert_eq!(query("$[?isearch(@.msg, 'TIMEOUT')]"), ["$[1]"]);
+        assert!(query("$[?imatch(@.msg, 'timeout')]").is_empty());
+        // Dynamic patterns, cached separately from the case-sensitive compilation
+        assert_eq!(query("$[?match(@.msg, @.pattern)]"), ["$[1]"]);
+        assert_eq!(
+            query("$[?imatch(@.msg, @.pattern)]"),
+            ["$[0]", "$[1]", "$[3]"]
+        );
+        assert_eq!(query("$[?match(@.msg, @.pattern)]"), ["$[1]"]);
+        // Simple case folding only: ß does not fold to SS
+        assert!(query("$[?imatch(@.msg, 'STRASSE')]").is_empty());
+        assert_eq!(query("$[?imatch(@.msg, 'STRAßE')]"), ["$[2]"]);
+        assert_eq!(query("$[?isearch(@.msg, 'ΣΊΣ')]"), ["$[3]"]);
+
+        for query in ["$[?imatch(@.msg, 'a')]", "$[?isearch(@.msg, 'a')]"] {
+            let err = JsonPath::parse(query).unwrap_err();
+            assert_eq!(
+                err.parse_error().unwrap().kind,
+                ParseErrorKind::UnknownFunction
+            );
+        }
+        for (query, kind) in [
+            (
+                "$[?imatch(@.a, 'x') == true]",
+                ParseErrorKind::FunctionResultType,
+            ),
+            ("$[?isearch(@.a)]", ParseErrorKind::FunctionArity),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+        let path = JsonPath::parse_with_registry("$[?imatch(@.a, '[')]", &registry).unwrap();
+        assert_eq!(path.lint()[0].kind, crate::LintKind::InvalidRegex);
+    }
+
+    #[test]
+    fn test_conversions() {
+        for (input, number) in [
+            (json!("42"), Some(json!(42))),
+            (json!(42), Some(json!(42))),
+            (json!("-1.5"), Some(json!(-1.5))),
+            (json!("1e3"), Some(json!(1000.0))),
+            (json!("  7 "), None),
+            (json!("7\n"), None),
+            (json!("+7"), None),
+            (json!("07"), None),
+            (json!("0x10"), None),
+            (json!("NaN"), None),
+            (json!(""), None),
+            (json!(null), None),
+            (json!(true), None),
+            (json!([1]), None),
+        ] {
+            assert_eq!(to_number(&input), number, "{input}");
+        }
+        for (input, string) in [
+            (json!("42"), Some(json!("42"))),
+            (json!(42), Some(json!("42"))),
+            (json!(-1.5), Some(json!("-1.5"))),
+            (json!(1e300), Some(json!("1e+300"))),
+            (json!(false), Some(json!("false"))),
+            (json!(null), None),
+            (json!({"a": 1}), None),
+        ] {
+            assert_eq!(to_string(&input), string, "{input}");
+        }
+    }
+
+    #[test]
+    fn test_conversions_in_queries() {
+        let json = json!([
+            {"port": 8080},
+            {"port": "8080"},
+            {"port": " 8080"},
+            {"port": null},
+            {"port": "8.08e3"},
+            {}
+        ]);
+        let registry = FunctionRegistry::extended();
+        let query = |query: &str| -> Vec<String> {
+            let path = JsonPath::parse_with_registry(query, &registry).unwrap();
+            let located = path.query_located(&json);
+            located.iter().map(|(p, _)| p.to_string()).collect()
+        };
+        assert_eq!(
+            query("$[?to_number(@.port) == 8080]"),
+            ["$[0]", "$[1]", "$[4]"]
+        );
+        assert_eq!(query("$[?to_string(@.port) == '8080']"), ["$[0]", "$[1]"]);
+        // Unconvertible and missing values are Nothing
+        assert_eq!(
+            query("$[?to_number(@.port) == @.missing]"),
+            ["$[2]", "$[3]", "$[5]"]
+        );
+        assert_eq!(
+            query("$[?to_string(@.port) == @.missing]"),
+            ["$[3]", "$[5]"]
+        );
+
+        for (query, kind) in [
+            ("$[?to_number(@.port)]", ParseErrorKind::FunctionResultType),
+            ("$[?to_string(@.port)]", ParseErrorKind::FunctionResultType),
+            (
+                "$[?to_number(@.*) == 1]",
+                ParseErrorKind::FunctionArgumentType,
+            ),
+            (
+                "$[?to_string(@.a, @.b) == '1']",
+                ParseErrorKind::FunctionArity,
+            ),
+            (
+                "$[?to_number(match(@.a, 'x')) == 1]",
+                ParseErrorKind::FunctionArgumentType,
+            ),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "{query}");
+        }
+        let err = JsonPath::parse("$[?to_number(@.port) == 8080]").unwrap_err();
+        assert_eq!(
+            err.parse_error().unwrap().kind,
+            ParseErrorKind::UnknownFunction
+        );
+    }
+}
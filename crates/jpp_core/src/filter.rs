@@ -0,0 +1,132 @@
+//! Standalone filter expressions usable as predicates outside of path traversal
+
+use crate::Error;
+use crate::ast::Expr;
+use crate::eval;
+use crate::parser::Parser;
+use serde_json::Value;
+
+/// A parsed filter expression, e.g. `@.price < 10 && @.stock > 0`
+///
+/// Uses the grammar after `?` in a filter selector, with the same validation rules as filters
+/// inside a query (singular comparison operands, function argument and result types).
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilterExpr {
+    expr: Expr,
+}
+
+impl FilterExpr {
+    /// Parse a filter expression
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::FilterExpr;
+    ///
+    /// let filter = FilterExpr::parse("@.price < 10 && @.stock > 0").unwrap();
+    /// assert!(filter.matches(&json!({"price": 5, "stock": 3})));
+    /// assert!(!filter.matches(&json!({"price": 5, "stock": 0})));
+    /// ```
+    pub fn parse(expr: &str) -> Result<Self, Error> {
+        let expr = Parser::parse_filter(expr)?;
+        Ok(Self { expr })
+    }
+
+    /// The parsed expression
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    /// Test the filter against `current` (`@`), which also serves as the root (`$`)
+    pub fn matches(&self, current: &Value) -> bool {
+        eval::filter_matches(&self.expr, current, current)
+    }
+
+    /// Test the filter against `current` (`@`) with a separate `root` for `$` queries
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::FilterExpr;
+    ///
+    /// let root = json!({"limit": 10});
+    /// let filter = FilterExpr::parse("@.price < $.limit").unwrap();
+    /// assert!(filter.matches_with_root(&json!({"price": 5}), &root));
+    /// assert!(!filter.matches_with_root(&json!({"price": 15}), &root));
+    /// ```
+    pub fn matches_with_root(&self, current: &Value, root: &Value) -> bool {
+        eval::filter_matches(&self.expr, current, root)
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::JsonPath;
+    use serde_json::json;
+
+    #[test]
+    fn test_matches_agrees_with_path_filter() {
+        let items = json!([
+            {"price": 5, "tags": ["a"]},
+            {"price": 15, "tags": []},
+            {"name": "x"},
+            3,
+            null
+        ]);
+        let filters = [
+            "@.price < 10",
+            "@.price",
+            "!@.price",
+            "length(@.tags) > 0",
+            "@.price < 10 || @.name == 'x'",
+            "@ == 3",
+            "@",
+            "match(@.name, 'x')",
+            "count(@.*) == 1",
+        ];
+        for filter in filters {
+            let expr = FilterExpr::parse(filter).unwrap();
+            let path = JsonPath::parse(&format!("$[?{filter}]")).unwrap();
+            let expected = path.query(&items);
+            let actual: Vec<&Value> = items
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|item| expr.matches_with_root(item, &items))
+                .collect();
+            assert_eq!(actual, expected, "filter {filter}");
+        }
+    }
+
+    #[test]
+    fn test_matches_uses_current_as_root() {
+        let filter = FilterExpr::parse("$.a == @.a").unwrap();
+        assert!(filter.matches(&json!({"a": 1})));
+        assert!(!filter.matches_with_root(&json!({"a": 1}), &json!({"a": 2})));
+    }
+
+    #[test]
+    fn test_parse_applies_filter_validation() {
+        for invalid in [
+            "1",
+            "length(@.a)",
+            "@.* == 1",
+            "@..a == 1",
+            "match(@.a, 'x') == true",
+            "unknown(@)",
+            "@.a ==",
+            "@.a == 1 )",
+            "",
+        ] {
+            assert!(FilterExpr::parse(invalid).is_err(), "filter {invalid:?}");
+        }
+    }
+
+    #[test]
+    fn test_parse_allows_surrounding_whitespace() {
+        let filter = FilterExpr::parse("  @.a  ").unwrap();
+        assert!(filter.matches(&json!({"a": false})));
+    }
+}
@@ -0,0 +1,489 @@
+//! Filter function definitions: the RFC 9535 built-ins and user-defined extensions
+
+use crate::error::InvalidFunctionName;
+use crate::lexer::is_function_name;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, LazyLock};
+
+/// Evaluation closure of a user-defined function
+pub(crate) type CustomFn = dyn Fn(&[ExprValue]) -> ExprValue + Send + Sync;
+
+/// Built-in registry, shared by every parse that does not supply its own
+static BUILTINS: LazyLock<FunctionRegistry> = LazyLock::new(|| {
+    let mut registry = FunctionRegistry {
+        functions: HashMap::new(),
+    };
+    for (name, params, result) in [
+        ("length", &[FunctionType::Value][..], FunctionType::Value),
+        ("count", &[FunctionType::Nodes][..], FunctionType::Value),
+        ("value", &[FunctionType::Nodes][..], FunctionType::Value),
+        (
+            "match",
+            &[FunctionType::Value, FunctionType::Value][..],
+            FunctionType::Logical,
+        ),
+        (
+            "search",
+            &[FunctionType::Value, FunctionType::Value][..],
+            FunctionType::Logical,
+        ),
+    ] {
+        registry.insert_native(name, params, result);
+    }
+    registry
+});
+
+/// Built-ins plus the extension functions, see [`FunctionRegistry::extended`]
+#[cfg(feature = "extended-functions")]
+static EXTENDED: LazyLock<FunctionRegistry> = LazyLock::new(|| {
+    let mut registry = BUILTINS.clone();
+    for (name, params, result) in crate::extensions::SIGNATURES {
+        registry.insert_native(name, params, *result);
+    }
+    registry
+});
+
+/// RFC 9535 type of a function parameter or result
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FunctionType {
+    /// A JSON value or Nothing: a literal, a singular query, or a ValueType function
+    Value,
+    /// A nodelist: a query or a NodesType function
+    Nodes,
+    /// True or false: a logical expression, an existence test, or a LogicalType function
+    Logical,
+}
+
+impl fmt::Display for FunctionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FunctionType::Value => "ValueType",
+            FunctionType::Nodes => "NodesType",
+            FunctionType::Logical => "LogicalType",
+        })
+    }
+}
+
+/// Argument or result of a user-defined function
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExprValue {
+    /// A ValueType value
+    Value(Value),
+    /// The ValueType absence of a value
+    Nothing,
+    /// A NodesType nodelist
+    Nodes(Vec<Value>),
+    /// A LogicalType result
+    Logical(bool),
+}
+
+impl ExprValue {
+    /// The value, if this is [`ExprValue::Value`]
+    pub fn as_value(&self) -> Option<&Value> {
+        match self {
+            ExprValue::Value(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A function callable from filter expressions
+#[derive(Clone)]
+pub struct Function {
+    name: String,
+    params: Vec<FunctionType>,
+    result: FunctionType,
+    /// None for the built-ins, which are evaluated natively
+    pub(crate) eval: Option<Arc<CustomFn>>,
+}
+
+impl Function {
+    /// Function name as written in queries
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Declared parameter types
+    pub fn params(&self) -> &[FunctionType] {
+        &self.params
+    }
+
+    /// Declared result type
+    pub fn result(&self) -> FunctionType {
+        self.result
+    }
+}
+
+impl fmt::Debug for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Function")
+            .field("name", &self.name)
+            .field("params", &self.params)
+            .field("result", &self.result)
+            .field("builtin", &self.eval.is_none())
+            .finish()
+    }
+}
+
+// Closures compare by identity
+impl PartialEq for Function {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.params == other.params
+            && self.result == other.result
+            && match (&self.eval, &other.eval) {
+                (None, None) => true,
+                (Some(a), Some(b)) => Arc::ptr_eq(a, b),
+                _ => false,
+            }
+    }
+}
+
+/// Set of functions known to the parser, see [`JsonPath::parse_with_registry`]
+///
+/// The default registry contains the five RFC 9535 functions: `length`, `count`, `value`,
+/// `match` and `search`.
+///
+/// [`JsonPath::parse_with_registry`]: crate::JsonPath::parse_with_registry
+#[derive(Debug, Clone)]
+pub struct FunctionRegistry {
+    functions: HashMap<String, Arc<Function>>,
+}
+
+impl Default for FunctionRegistry {
+    fn default() -> Self {
+        BUILTINS.clone()
+    }
+}
+
+impl FunctionRegistry {
+    /// Registry with the RFC 9535 functions
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registry with the RFC 9535 functions and the non-RFC extension functions
+    ///
+    /// Extensions are evaluated natively like the built-ins, but parse only with this
+    /// registry, so [`JsonPath::parse`](crate::JsonPath::parse) keeps rejecting them:
+    ///
+    /// | Function | Parameters | Result |
+    /// |----------|------------|--------|
+    /// | `min`, `max`, `sum`, `avg` | NodesType | ValueType |
+    /// | `key` | none | ValueType |
+    /// | `parent` | none | NodesType |
+    /// | `type` | ValueType | ValueType |
+    /// | `to_number`, `to_string` | ValueType | ValueType |
+    /// | `contains` | ValueType, ValueType | LogicalType |
+    /// | `starts_with`, `ends_with` | ValueType, ValueType | LogicalType |
+    /// | `imatch`, `isearch` | ValueType, ValueType | LogicalType |
+    ///
+    /// The aggregations consider only the numbers in their nodelist and skip other values.
+    /// Without numbers the result is Nothing. `min` and `max` return the extreme node's
+    /// value; `sum` is an integer while the total fits in `i64`, and `avg` is a float.
+    ///
+    /// `key()` is the member name or array index under which the filter selector reached `@`,
+    /// e.g. `$.config[?match(key(), 'ssl_.*')]`. It is Nothing where `@` was not reached by a
+    /// filter selector, as in a standalone [`FilterExpr`](crate::FilterExpr).
+    ///
+    /// `parent()` is the object or array containing `@` under the same condition, and can start
+    /// a singular query like `@` does: `$..details[?parent().archived == false]`.
+    ///
+    /// `type()` is the JSON type name of its argument: `"null"`, `"boolean"`, `"number"`,
+    /// `"string"`, `"array"` or `"object"`, and Nothing for Nothing.
+    ///
+    /// `to_number()` passes numbers through and parses strings that are exactly a JSON number,
+    /// so `$[?to_number(@.port) == 8080]` matches both `8080` and `"8080"`. `to_string()`
+    /// passes strings through and renders numbers and booleans as JSON text. Other values are
+    /// Nothing.
+    ///
+    /// `contains(a, v)` is true when `a` is an array with an element equal to `v`, compared as
+    /// `==` does: `$.items[?contains($.allowed, @.status)]`. It is false when `a` is not an
+    /// array or either argument is Nothing.
+    ///
+    /// `starts_with(s, p)` and `ends_with(s, p)` test for a literal prefix or suffix, without
+    /// regular expression syntax. They are false unless both arguments are strings.
+    ///
+    /// `imatch()` and `isearch()` are `match()` and `search()` ignoring case, with simple case
+    /// folding: `"Straße"` does not match `"STRASSE"`.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::{FunctionRegistry, JsonPath};
+    ///
+    /// let json = json!({"orders": [{"total": 10}, {"total": 40}, {"total": 25}]});
+    /// let query = "$.orders[?@.total > avg($.orders[*].total)]";
+    /// let path = JsonPath::parse_with_registry(query, &FunctionRegistry::extended()).unwrap();
+    /// assert_eq!(path.query(&json), vec![&json!({"total": 40})]);
+    /// assert!(JsonPath::parse(query).is_err());
+    /// ```
+    #[cfg(feature = "extended-functions")]
+    pub fn extended() -> Self {
+        EXTENDED.clone()
+    }
+
+    /// Shared registry with the RFC 9535 functions
+    pub(crate) fn builtins() -> &'static Self {
+        &BUILTINS
+    }
+
+    /// Register a function, replacing any function of the same name
+    ///
+    /// Calls are checked at parse time against `params` and `result` with the same rules as
+    /// the built-ins. Arguments are passed to `eval` as the declared parameter types (nodelist
+    /// values are cloned), and `eval` should return a value of the declared result type.
+    /// Names must be valid function names: a lowercase letter followed by lowercase letters,
+    /// digits or `_`, other than `true`, `false` and `null`. Any other name fails with
+    /// [`InvalidFunctionName`], since no query could call it.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::{ExprValue, FunctionRegistry, FunctionType, JsonPath};
+    ///
+    /// let mut registry = FunctionRegistry::new();
+    /// registry.register("is_even", &[FunctionType::Value], FunctionType::Logical, |args| {
+    ///     let even = args[0].as_value().and_then(|v| v.as_i64()).is_some_and(|n| n % 2 == 0);
+    ///     ExprValue::Logical(even)
+    /// }).unwrap();
+    /// assert!(registry.register("is even", &[], FunctionType::Logical, |_| ExprValue::Nothing).is_err());
+    ///
+    /// let path = JsonPath::parse_with_registry("$[?is_even(@)]", &registry).unwrap();
+    /// assert_eq!(path.query(&json!([1, 2, 3, 4])), vec![&json!(2), &json!(4)]);
+    /// ```
+    pub fn register<F>(
+        &mut self,
+        name: &str,
+        params: &[FunctionType],
+        result: FunctionType,
+        eval: F,
+    ) -> Result<&mut Self, InvalidFunctionName>
+    where
+        F: Fn(&[ExprValue]) -> ExprValue + Send + Sync + 'static,
+    {
+        if !is_function_name(name) {
+            return Err(InvalidFunctionName(name.to_string()));
+        }
+        self.insert(Function {
+            name: name.to_string(),
+            params: params.to_vec(),
+            result,
+            eval: Some(Arc::new(eval)),
+        });
+        Ok(self)
+    }
+
+    /// Look up a function by name
+    pub fn get(&self, name: &str) -> Option<&Arc<Function>> {
+        self.functions.get(name)
+    }
+
+    /// Names of the registered functions, in no particular order
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> {
+        self.functions.keys().map(String::as_str)
+    }
+
+    /// Add a function evaluated natively by the evaluator
+    fn insert_native(&mut self, name: &str, params: &[FunctionType], result: FunctionType) {
+        self.insert(Function {
+            name: name.to_string(),
+            params: params.to_vec(),
+            result,
+            eval: None,
+        });
+    }
+
+    fn insert(&mut self, function: Function) {
+        self.functions
+            .insert(function.name.clone(), Arc::new(function));
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use crate::{FilterExpr, JsonPath};
+    use serde_json::json;
+
+    fn registry() -> FunctionRegistry {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register(
+                "upper",
+                &[FunctionType::Value],
+                FunctionType::Value,
+                |args| match args[0].as_value() {
+                    Some(Value::String(s)) => ExprValue::Value(Value::String(s.to_uppercase())),
+                    _ => ExprValue::Nothing,
+                },
+            )
+            .unwrap()
+            .register(
+                "evens",
+                &[FunctionType::Nodes],
+                FunctionType::Nodes,
+                |args| match &args[0] {
+                    ExprValue::Nodes(nodes) => ExprValue::Nodes(
+                        nodes
+                            .iter()
+                            .filter(|v| v.as_i64().is_some_and(|n| n % 2 == 0))
+                            .cloned()
+                            .collect(),
+                    ),
+                    _ => ExprValue::Nodes(Vec::new()),
+                },
+            )
+            .unwrap()
+            .register(
+                "either",
+                &[FunctionType::Logical, FunctionType::Logical],
+                FunctionType::Logical,
+                |args| ExprValue::Logical(args.contains(&ExprValue::Logical(true))),
+            )
+            .unwrap();
+        registry
+    }
+
+    fn query(query: &str, json: &Value) -> Vec<Value> {
+        JsonPath::parse_with_registry(query, &registry())
+            .unwrap()
+            .query(json)
+            .into_iter()
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn test_default_registry_has_rfc_functions() {
+        let registry = FunctionRegistry::default();
+        for name in ["length", "count", "value", "match", "search"] {
+            assert!(registry.get(name).unwrap().eval.is_none(), "{name}");
+        }
+        assert_eq!(registry.functions.len(), 5);
+        assert!(JsonPath::parse("$[?upper(@) == 'A']").is_err());
+    }
+
+    #[test]
+    fn test_value_function() {
+        let json = json!(["a", "b", 1]);
+        assert_eq!(query("$[?upper(@) == 'A']", &json), vec![json!("a")]);
+        assert_eq!(query("$[?length(upper(@)) == 1]", &json).len(), 2);
+    }
+
+    #[test]
+    fn test_nodes_function() {
+        let json = json!([{"n": [1, 2, 4]}, {"n": [1, 3]}, {"n": []}]);
+        assert_eq!(
+            query("$[?evens(@.n[*])]", &json),
+            vec![json!({"n": [1, 2, 4]})]
+        );
+        assert_eq!(
+            query("$[?count(evens(@.n[*])) == 2]", &json),
+            vec![json!({"n": [1, 2, 4]})]
+        );
+        assert!(query("$[?value(evens(@.n[*])) == 2]", &json).is_empty());
+    }
+
+    #[test]
+    fn test_logical_function() {
+        let json = json!([{"a": 1}, {"b": 2}, {"c": 3}]);
+        assert_eq!(
+            query("$[?either(@.a, @.b == 2)]", &json),
+            vec![json!({"a": 1}), json!({"b": 2})]
+        );
+    }
+
+    #[test]
+    fn test_registered_functions_are_type_checked() {
+        let registry = registry();
+        for (query, kind) in [
+            ("$[?upper(@)]", crate::ParseErrorKind::FunctionResultType),
+            (
+                "$[?evens(@) == 1]",
+                crate::ParseErrorKind::FunctionResultType,
+            ),
+            (
+                "$[?either(@.a, 1)]",
+                crate::ParseErrorKind::FunctionArgumentType,
+            ),
+            (
+                "$[?upper(@.*) == 'A']",
+                crate::ParseErrorKind::FunctionArgumentType,
+            ),
+            (
+                "$[?evens('a')]",
+                crate::ParseErrorKind::FunctionArgumentType,
+            ),
+            (
+                "$[?upper(@, @) == 'A']",
+                crate::ParseErrorKind::FunctionArity,
+            ),
+            ("$[?unknown(@)]", crate::ParseErrorKind::UnknownFunction),
+        ] {
+            let err = JsonPath::parse_with_registry(query, &registry).unwrap_err();
+            assert_eq!(err.parse_error().unwrap().kind, kind, "query {query}");
+        }
+    }
+
+    #[test]
+    fn test_register_replaces_builtin() {
+        let mut registry = FunctionRegistry::new();
+        registry
+            .register(
+                "length",
+                &[FunctionType::Value],
+                FunctionType::Value,
+                |_| ExprValue::Value(json!(0)),
+            )
+            .unwrap();
+        let path = JsonPath::parse_with_registry("$[?length(@) == 0]", &registry).unwrap();
+        assert_eq!(path.query(&json!(["abc"])), vec![&json!("abc")]);
+    }
+
+    #[test]
+    fn test_register_rejects_uncallable_names() {
+        let mut registry = FunctionRegistry::new();
+        for name in [
+            "",
+            "a b",
+            "Upper",
+            "_x",
+            "1st",
+            "is-even",
+            "null",
+            "tr\u{e8}s",
+        ] {
+            assert_eq!(
+                registry
+                    .register(name, &[], FunctionType::Logical, |_| ExprValue::Nothing)
+                    .err(),
+                Some(InvalidFunctionName(name.to_string())),
+                "{name:?}"
+            );
+            assert!(registry.get(name).is_none());
+        }
+        for name in ["x", "is_even2", "nullable"] {
+            assert!(
+                registry
+                    .register(name, &[], FunctionType::Logical, |_| ExprValue::Nothing)
+                    .is_ok()
+            );
+        }
+    }
+
+    #[test]
+    fn test_display_round_trips_with_registry() {
+        let registry = registry();
+        let path = JsonPath::parse_with_registry("$[?upper(@.a) == 'X']", &registry).unwrap();
+        assert_eq!(path.to_string(), "$[?upper(@['a']) == 'X']");
+        assert_eq!(
+            JsonPath::parse_with_registry(&path.to_string(), &registry).unwrap(),
+            path
+        );
+        assert!(FilterExpr::parse("upper(@) == 'X'").is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! I-Regexp (RFC 9485) validation for `match()` and `search()` patterns
+//!
+//! The regex crate accepts far more than I-Regexp (`\d`, `\w`, lazy quantifiers, anchors, ...).
+//! [`translate`] checks a pattern against the I-Regexp grammar and rewrites it into the regex
+//! crate's syntax with I-Regexp semantics: `.` excludes `\r` as well as `\n`, and `^`/`$` are
+//! ordinary characters.
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// True if `pattern` is valid I-Regexp
+///
+/// # Example
+/// ```
+/// use jpp_core::iregexp;
+///
+/// assert!(iregexp::is_valid(r"[A-Z]\p{Ll}{2,}"));
+/// assert!(!iregexp::is_valid(r"\d+"));
+/// ```
+pub fn is_valid(pattern: &str) -> bool {
+    translate(pattern).is_ok()
+}
+
+/// Validate `pattern` against the I-Regexp grammar and translate it to regex crate syntax
+///
+/// The result is unanchored; `match()` wraps it in `^(?:...)$`. Errors describe the first
+/// construct that isn't I-Regexp.
+pub(crate) fn translate(pattern: &str) -> Result<String, String> {
+    let mut chars = pattern.chars().peekable();
+    let mut out = String::with_capacity(pattern.len() * 2);
+    let mut depth = 0usize;
+    // Whether the previous piece can take a quantifier
+    let mut quantifiable = false;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => {
+                depth += 1;
+                out.push_str("(?:");
+                quantifiable = false;
+                continue;
+            }
+            ')' => {
+                depth = depth.checked_sub(1).ok_or("unmatched ')'")?;
+                out.push(')');
+            }
+            '|' => {
+                out.push('|');
+                quantifiable = false;
+                continue;
+            }
+            '*' | '+' | '?' | '{' => {
+                if !quantifiable {
+                    return Err(format!("'{c}' does not follow a quantifiable atom"));
+                }
+                if c == '{' {
+                    range_quantifier(&mut chars, &mut out)?;
+                } else {
+                    out.push(c);
+                }
+                quantifiable = false;
+                continue;
+            }
+            '.' => out.push_str("[^\\r\\n]"),
+            '[' => char_class(&mut chars, &mut out)?,
+            '\\' => escape(&mut chars, &mut out, false)?,
+            ']' | '}' => return Err(format!("'{c}' must be escaped")),
+            '^' | '$' => {
+                out.push('\\');
+                out.push(c);
+            }
+            _ => out.push(c),
+        }
+        quantifiable = true;
+    }
+
+    if depth > 0 {
+        return Err("unclosed group".to_string());
+    }
+    Ok(out)
+}
+
+/// `{n}`, `{n,}` or `{n,m}`, after the `{`
+fn range_quantifier(chars: &mut Peekable<Chars<'_>>, out: &mut String) -> Result<(), String> {
+    const MALFORMED: &str = "malformed range quantifier";
+    out.push('{');
+    let mut digits = 0;
+    while let Some(d) = chars.next_if(char::is_ascii_digit) {
+        out.push(d);
+        digits += 1;
+    }
+    if digits == 0 {
+        return Err(MALFORMED.to_string());
+    }
+    if chars.next_if_eq(&',').is_some() {
+        out.push(',');
+        while let Some(d) = chars.next_if(char::is_ascii_digit) {
+            out.push(d);
+        }
+    }
+    match chars.next() {
+        Some('}') => {
+            out.push('}');
+            Ok(())
+        }
+        _ => Err(MALFORMED.to_string()),
+    }
+}
+
+/// A bracketed character class, after the `[`
+fn char_class(chars: &mut Peekable<Chars<'_>>, out: &mut String) -> Result<(), String> {
+    out.push('[');
+    if chars.next_if_eq(&'^').is_some() {
+        out.push('^');
+    }
+    if chars.next_if_eq(&'-').is_some() {
+        out.push_str("\\-");
+    } else if chars.peek() == Some(&']') {
+        return Err("empty character class".to_string());
+    }
+
+    loop {
+        let c = chars.next().ok_or("unclosed character class")?;
+        match c {
+            ']' => {
+                out.push(']');
+                return Ok(());
+            }
+            '-' => {
+                if chars.peek() != Some(&']') {
+                    return Err(
+                        "'-' in a character class must come first, last or in a range".to_string(),
+                    );
+                }
+                out.push_str("\\-");
+            }
+            '[' => return Err("'[' must be escaped in a character class".to_string()),
+            '\\' if matches!(chars.peek(), Some('p' | 'P')) => {
+                escape(chars, out, true)?;
+            }
+            _ => {
+                class_char(chars, out, c)?;
+                let mut ahead = chars.clone();
+                if ahead.next() == Some('-') && ahead.next().is_some_and(|c| c != ']') {
+                    chars.next();
+                    out.push('-');
+                    let end = chars.next().ok_or("unclosed character class")?;
+                    if end == '\\' && matches!(chars.peek(), Some('p' | 'P')) {
+                        return Err("a category escape cannot end a range".to_string());
+                    }
+                    class_char(chars, out, end)?;
+                }
+            }
+        }
+    }
+}
+
+/// A single character inside a class: a plain character or a single-character escape
+fn class_char(chars: &mut Peekable<Chars<'_>>, out: &mut String, c: char) -> Result<(), String> {
+    match c {
+        '\\' => escape(chars, out, true),
+        '[' | ']' | '-' => Err(format!("'{c}' must be escaped in a character class")),
+        // Literal in I-Regexp, but set operators or negation in the regex crate
+        '&' | '~' | '^' => {
+            out.push('\\');
+            out.push(c);
+            Ok(())
+        }
+        _ => {
+            out.push(c);
+            Ok(())
+        }
+    }
+}
+
+/// An escape, after the `\`: a single-character escape or `\p{..}`/`\P{..}`
+fn escape(chars: &mut Peekable<Chars<'_>>, out: &mut String, in_class: bool) -> Result<(), String> {
+    match chars.next() {
+        Some(
+            c @ ('(' | ')' | '*' | '+' | '-' | '.' | '?' | '[' | '\\' | ']' | '^' | '{' | '|' | '}'
+            | 'n' | 'r' | 't'),
+        ) => {
+            out.push('\\');
+            out.push(c);
+            Ok(())
+        }
+        Some(c @ ('p' | 'P')) => {
+            if chars.next() != Some('{') {
+                return Err(format!("expected '{{' after '\\{c}'"));
+            }
+            let mut property = String::new();
+            for p in chars.by_ref() {
+                if p == '}' {
+                    if !is_category(&property) {
+                        return Err(format!("'{property}' is not a general category"));
+                    }
+                    out.push_str(&format!("\\{c}{{{property}}}"));
+                    return Ok(());
+                }
+                property.push(p);
+            }
+            Err(format!("unclosed '\\{c}{{'"))
+        }
+        Some(c) => Err(format!(
+            "'\\{c}' is not an I-Regexp escape{}",
+            if in_class {
+                " in a character class"
+            } else {
+                ""
+            }
+        )),
+        None => Err("trailing '\\'".to_string()),
+    }
+}
+
+/// A Unicode general category as allowed by `IsCategory`
+fn is_category(name: &str) -> bool {
+    let mut chars = name.chars();
+    let subcategories = match chars.next() {
+        Some('L') => "lmotu",
+        Some('M') => "cen",
+        Some('N') => "dlo",
+        Some('P') => "cdefios",
+        Some('Z') => "lps",
+        Some('S') => "ckmo",
+        Some('C') => "cfno",
+        _ => return false,
+    };
+    match (chars.next(), chars.next()) {
+        (None, _) => true,
+        (Some(sub), None) => subcategories.contains(sub),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_accepted_and_rejected_patterns() {
+        let cases = [
+            ("abc", true),
+            ("a|b|", true),
+            ("(ab)*c+d?", true),
+            ("()", true),
+            ("a{2}b{1,}c{0,3}", true),
+            (".", true),
+            ("[a-z0-9_]", true),
+            ("[^-a]", true),
+            ("[a-]", true),
+            ("[--]", true),
+            (r"[\]\-\\]", true),
+            (r"\p{L}\p{Lu}\P{Nd}[\p{Zs}x]", true),
+            (r"\.\*\n\t\{\}", true),
+            ("^a$", true),
+            ("a&&b", true),
+            ("日本", true),
+            (r"\d", false),
+            (r"\w+", false),
+            (r"\s", false),
+            (r"\b", false),
+            (r"\1", false),
+            ("a*?", false),
+            ("a++", false),
+            ("*a", false),
+            ("a{,2}", false),
+            ("a{2", false),
+            ("a{x}", false),
+            ("(?:a)", false),
+            ("(?i)a", false),
+            ("(a", false),
+            ("a)", false),
+            ("a]", false),
+            ("a}", false),
+            ("[]", false),
+            ("[^]", false),
+            ("[a", false),
+            ("[a-b-c]", false),
+            ("[[a]]", false),
+            (r"[\d]", false),
+            (r"[\p{L}-z]", false),
+            (r"\p{Latin}", false),
+            (r"\p{Lx}", false),
+            (r"\pL", false),
+            ("a\\", false),
+        ];
+        for (pattern, valid) in cases {
+            assert_eq!(is_valid(pattern), valid, "{pattern:?}");
+        }
+    }
+
+    #[test]
+    fn test_translation_keeps_i_regexp_semantics() {
+        let matches = |pattern: &str, input: &str| {
+            regex::Regex::new(&format!("^(?:{})$", translate(pattern).unwrap()))
+                .unwrap()
+                .is_match(input)
+        };
+        assert!(matches("^a$", "^a$"));
+        assert!(!matches("^a$", "a"));
+        assert!(matches("[a&&b]", "&"));
+        assert!(matches("[a~~b]", "~"));
+        assert!(matches("[b^]", "^"));
+        assert!(!matches("a.c", "a\rc"));
+        assert!(matches("[.]", "."));
+        assert!(matches(r"\p{Lu}+", "ÀB"));
+    }
+}
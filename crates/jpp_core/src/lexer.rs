@@ -1,11 +1,16 @@
 //! Lexer for JSONPath queries
 
+use crate::error::{ParseError, ParseErrorKind};
+use std::borrow::Cow;
 use std::iter::Peekable;
+use std::ops::Range;
 use std::str::Chars;
 
 /// Token types for JSONPath
+///
+/// Names and strings borrow from the input; only string literals with escapes allocate.
 #[derive(Debug, Clone, PartialEq)]
-pub enum TokenKind {
+pub enum TokenKind<'a> {
     /// Root identifier `$`
     Root,
     /// Current node `@`
@@ -48,6 +53,12 @@ pub enum TokenKind {
     Or,
     /// Logical NOT `!`
     Not,
+    /// Plus `+`, for filter arithmetic
+    Plus,
+    /// Minus `-` not starting a number, for filter arithmetic
+    Minus,
+    /// Slash `/`, for filter arithmetic
+    Slash,
     /// Boolean true literal
     True,
     /// Boolean false literal
@@ -55,60 +66,126 @@ pub enum TokenKind {
     /// Null literal
     Null,
     /// Identifier (unquoted key name)
-    Ident(String),
-    /// String literal (single or double quoted)
-    String(String),
-    /// Number (integer or floating-point)
+    Ident(&'a str),
+    /// String literal (single or double quoted), unescaped
+    String(Cow<'a, str>),
+    /// Integer written without a decimal point or exponent, exact within the i64 and u64
+    /// ranges
+    Integer(i128),
+    /// Any other number, rounded to f64: a decimal, an exponent form, `-0`, or an integer
+    /// outside the i64 and u64 ranges.
     /// The bool flag indicates if the number was written with a decimal point or exponent
     Number(f64, bool),
+    /// Malformed input, only produced by [`Lexer::recovering`]
+    Error(Box<ParseError>),
 }
 
 /// Token with position information
 #[derive(Debug, Clone, PartialEq)]
-pub struct Token {
-    pub kind: TokenKind,
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    /// Character offset of the first character
     pub position: usize,
+    /// Character offset just past the last character
+    pub end: usize,
+    /// Byte offset of the first character, for slicing the input
+    pub byte_position: usize,
+    /// Byte offset just past the last character
+    pub byte_end: usize,
 }
 
-/// Lexer error
-#[derive(Debug, Clone, PartialEq)]
-pub struct LexerError {
-    pub message: String,
-    pub position: usize,
-}
+impl Token<'_> {
+    /// Character range of the token
+    pub fn span(&self) -> Range<usize> {
+        self.position..self.end
+    }
 
-impl std::fmt::Display for LexerError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "at position {}: {}", self.position, self.message)
+    /// Byte range of the token in the input
+    pub fn byte_span(&self) -> Range<usize> {
+        self.byte_position..self.byte_end
     }
 }
 
 /// Lexer for tokenizing JSONPath queries
+///
+/// As an iterator it yields tokens until the first error. [`Lexer::recovering`] continues
+/// past errors, and [`Lexer::highlight`] classifies tokens for syntax highlighting.
+///
+/// # Example
+/// ```
+/// use jpp_core::lexer::{Lexer, SemanticKind};
+///
+/// let kinds: Vec<SemanticKind> = Lexer::new("$[?length(@.a) > 1]")
+///     .highlight()
+///     .map(|token| token.kind)
+///     .collect();
+/// assert_eq!(kinds[3], SemanticKind::Function);
+/// assert_eq!(kinds[7], SemanticKind::Identifier);
+/// ```
 pub struct Lexer<'a> {
+    input: &'a str,
     chars: Peekable<Chars<'a>>,
+    /// Character offset, used for all spans
     position: usize,
+    /// Byte offset of the same position
+    byte_position: usize,
+    /// An error was returned, which ends iteration
+    failed: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
+            input,
             chars: input.chars().peekable(),
             position: 0,
+            byte_position: 0,
+            failed: false,
+        }
+    }
+
+    /// Tokens that report errors as [`TokenKind::Error`] and continue after them
+    pub fn recovering(self) -> Recovering<'a> {
+        Recovering { lexer: self }
+    }
+
+    /// Tokens classified for syntax highlighting, continuing past errors
+    pub fn highlight(self) -> Highlights<'a> {
+        Highlights {
+            tokens: self.recovering().peekable(),
+            after_dot: false,
         }
     }
 
     /// Tokenize the entire input
-    pub fn tokenize(mut self) -> Result<Vec<Token>, LexerError> {
+    pub fn tokenize(self) -> Result<Vec<Token<'a>>, ParseError> {
+        self.tokenize_limited(None)
+    }
+
+    /// Tokenize the entire input, failing once more than `max_tokens` tokens are produced
+    pub fn tokenize_limited(
+        mut self,
+        max_tokens: Option<usize>,
+    ) -> Result<Vec<Token<'a>>, ParseError> {
         let mut tokens = Vec::new();
 
         while let Some(token) = self.next_token()? {
+            if let Some(max) = max_tokens
+                && tokens.len() == max
+            {
+                return Err(ParseError::new(
+                    ParseErrorKind::LimitExceeded,
+                    format!("query has more than {max} tokens"),
+                    token.span(),
+                ));
+            }
             tokens.push(token);
         }
 
         Ok(tokens)
     }
 
-    fn next_token(&mut self) -> Result<Option<Token>, LexerError> {
+    fn next_token(&mut self) -> Result<Option<Token<'a>>, ParseError> {
         self.skip_whitespace();
 
         let Some(&ch) = self.chars.peek() else {
@@ -116,6 +193,7 @@ impl<'a> Lexer<'a> {
         };
 
         let start_pos = self.position;
+        let byte_start = self.byte_position;
 
         let kind = match ch {
             '$' => {
@@ -191,10 +269,11 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     TokenKind::Equal
                 } else {
-                    return Err(LexerError {
-                        message: "expected '==' but found single '='".to_string(),
-                        position: start_pos,
-                    });
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnexpectedCharacter,
+                        "expected '==' but found single '='",
+                        start_pos..self.position,
+                    ));
                 }
             }
             '!' => {
@@ -212,10 +291,11 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     TokenKind::And
                 } else {
-                    return Err(LexerError {
-                        message: "expected '&&' but found single '&'".to_string(),
-                        position: start_pos,
-                    });
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnexpectedCharacter,
+                        "expected '&&' but found single '&'",
+                        start_pos..self.position,
+                    ));
                 }
             }
             '|' => {
@@ -224,37 +304,88 @@ impl<'a> Lexer<'a> {
                     self.advance();
                     TokenKind::Or
                 } else {
-                    return Err(LexerError {
-                        message: "expected '||' but found single '|'".to_string(),
-                        position: start_pos,
-                    });
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnexpectedCharacter,
+                        "expected '||' but found single '|'",
+                        start_pos..self.position,
+                    ));
                 }
             }
+            '+' => {
+                self.advance();
+                TokenKind::Plus
+            }
+            '/' => {
+                self.advance();
+                TokenKind::Slash
+            }
+            '-' if !self.next_starts_number() => {
+                self.advance();
+                TokenKind::Minus
+            }
             '\'' | '"' => self.read_string()?,
             '-' | '0'..='9' => self.read_number()?,
             _ if is_ident_start(ch) => self.read_ident_or_keyword(),
             _ => {
-                return Err(LexerError {
-                    message: format!("unexpected character: '{ch}'"),
-                    position: self.position,
-                });
+                return Err(ParseError::new(
+                    ParseErrorKind::UnexpectedCharacter,
+                    format!("unexpected character: '{ch}'"),
+                    self.position..self.position + 1,
+                ));
             }
         };
 
         Ok(Some(Token {
             kind,
             position: start_pos,
+            end: self.position,
+            byte_position: byte_start,
+            byte_end: self.byte_position,
         }))
     }
 
     fn advance(&mut self) -> Option<char> {
         let ch = self.chars.next();
-        if ch.is_some() {
+        if let Some(ch) = ch {
             self.position += 1;
+            self.byte_position += ch.len_utf8();
         }
         ch
     }
 
+    /// Skip the rest of a malformed token starting with `first` at `start`
+    fn resync(&mut self, first: char, start: usize, kind: ParseErrorKind) {
+        if matches!(first, '\'' | '"') {
+            // Resume after the closing quote, if there is one
+            while let Some(ch) = self.advance() {
+                if ch == '\\' {
+                    self.advance();
+                } else if ch == first {
+                    break;
+                }
+            }
+            return;
+        }
+        if self.position == start {
+            self.advance();
+        }
+        if kind == ParseErrorKind::InvalidNumber {
+            while self.chars.peek().is_some_and(char::is_ascii_alphanumeric) {
+                self.advance();
+            }
+        }
+    }
+
+    /// Whether the `-` at the current position is followed by a digit or `.`, so it starts a
+    /// number literal (or a malformed one such as `-.1`) rather than being an operator
+    fn next_starts_number(&self) -> bool {
+        let mut chars = self.chars.clone();
+        chars.next();
+        chars
+            .next()
+            .is_some_and(|ch| ch.is_ascii_digit() || ch == '.')
+    }
+
     fn skip_whitespace(&mut self) {
         while let Some(&ch) = self.chars.peek() {
             if ch.is_whitespace() {
@@ -266,41 +397,62 @@ impl<'a> Lexer<'a> {
     }
 
     /// Read 4 hex digits for \uXXXX escape and return the code point
-    fn read_unicode_escape(&mut self) -> Result<u32, LexerError> {
+    fn read_unicode_escape(&mut self, escape_start: usize) -> Result<u32, ParseError> {
         let mut hex = String::with_capacity(4);
         for _ in 0..4 {
             match self.advance() {
                 Some(ch) if ch.is_ascii_hexdigit() => hex.push(ch),
                 _ => {
-                    return Err(LexerError {
-                        message: "invalid unicode escape: expected 4 hex digits".to_string(),
-                        position: self.position,
-                    });
+                    return Err(ParseError::new(
+                        ParseErrorKind::InvalidEscape,
+                        "invalid unicode escape: expected 4 hex digits",
+                        escape_start..self.position,
+                    ));
                 }
             }
         }
-        u32::from_str_radix(&hex, 16).map_err(|_| LexerError {
-            message: "invalid unicode escape".to_string(),
-            position: self.position,
+        u32::from_str_radix(&hex, 16).map_err(|_| {
+            ParseError::new(
+                ParseErrorKind::InvalidEscape,
+                "invalid unicode escape",
+                escape_start..self.position,
+            )
         })
     }
 
-    fn read_string(&mut self) -> Result<TokenKind, LexerError> {
-        let quote = self.advance().ok_or_else(|| LexerError {
-            message: "unexpected end of input".to_string(),
-            position: self.position,
+    fn read_string(&mut self) -> Result<TokenKind<'a>, ParseError> {
+        let quote = self.advance().ok_or_else(|| {
+            ParseError::new(
+                ParseErrorKind::UnexpectedEndOfInput,
+                "unexpected end of input",
+                self.position..self.position,
+            )
         })?;
 
-        let mut value = String::new();
+        // Allocated at the first escape; until then the content is a slice of the input
+        let mut value: Option<String> = None;
+        let input = self.input;
+        let content_start = self.byte_position;
         let start_pos = self.position;
 
         loop {
             match self.advance() {
                 Some(ch) if ch == quote => break,
                 Some('\\') => {
-                    let escaped = self.advance().ok_or_else(|| LexerError {
-                        message: "unexpected end of input in escape sequence".to_string(),
-                        position: self.position,
+                    let escape_start = self.position - 1;
+                    let value = value.get_or_insert_with(|| {
+                        input[content_start..self.byte_position - 1].to_string()
+                    });
+                    let escaped = self.advance().ok_or_else(|| {
+                        ParseError::new(
+                            ParseErrorKind::UnterminatedString,
+                            format!(
+                                "string opened at position {} was never closed",
+                                start_pos - 1
+                            ),
+                            self.position..self.position,
+                        )
+                        .with_opening(start_pos - 1..start_pos)
                     })?;
                     match escaped {
                         'n' => value.push('\n'),
@@ -315,73 +467,89 @@ impl<'a> Lexer<'a> {
                         'f' => value.push('\x0C'),
                         '/' => value.push('/'),
                         'u' => {
-                            let code = self.read_unicode_escape()?;
+                            let code = self.read_unicode_escape(escape_start)?;
                             // Check for surrogate pair
                             if (0xD800..=0xDBFF).contains(&code) {
                                 // High surrogate - expect \uXXXX low surrogate
                                 if self.advance() != Some('\\') || self.advance() != Some('u') {
-                                    return Err(LexerError {
-                                        message: "invalid surrogate pair".to_string(),
-                                        position: self.position,
-                                    });
+                                    return Err(ParseError::new(
+                                        ParseErrorKind::InvalidEscape,
+                                        "invalid surrogate pair",
+                                        escape_start..self.position,
+                                    ));
                                 }
-                                let low = self.read_unicode_escape()?;
+                                let low = self.read_unicode_escape(escape_start)?;
                                 if !(0xDC00..=0xDFFF).contains(&low) {
-                                    return Err(LexerError {
-                                        message: "invalid low surrogate".to_string(),
-                                        position: self.position,
-                                    });
+                                    return Err(ParseError::new(
+                                        ParseErrorKind::InvalidEscape,
+                                        "invalid low surrogate",
+                                        escape_start..self.position,
+                                    ));
                                 }
                                 // Combine surrogate pair
                                 let combined = 0x10000 + ((code - 0xD800) << 10) + (low - 0xDC00);
                                 if let Some(ch) = char::from_u32(combined) {
                                     value.push(ch);
                                 } else {
-                                    return Err(LexerError {
-                                        message: "invalid unicode code point".to_string(),
-                                        position: self.position,
-                                    });
+                                    return Err(ParseError::new(
+                                        ParseErrorKind::InvalidEscape,
+                                        "invalid unicode code point",
+                                        escape_start..self.position,
+                                    ));
                                 }
                             } else if let Some(ch) = char::from_u32(code) {
                                 value.push(ch);
                             } else {
-                                return Err(LexerError {
-                                    message: "invalid unicode code point".to_string(),
-                                    position: self.position,
-                                });
+                                return Err(ParseError::new(
+                                    ParseErrorKind::InvalidEscape,
+                                    "invalid unicode code point",
+                                    escape_start..self.position,
+                                ));
                             }
                         }
                         _ => {
-                            return Err(LexerError {
-                                message: format!("invalid escape sequence: \\{escaped}"),
-                                position: self.position - 1,
-                            });
+                            return Err(ParseError::new(
+                                ParseErrorKind::InvalidEscape,
+                                format!("invalid escape sequence: \\{escaped}"),
+                                escape_start..self.position,
+                            ));
                         }
                     }
                 }
                 Some(ch) => {
                     // RFC 9535: Control characters (U+0000 to U+001F) must be escaped
                     if (ch as u32) <= 0x1F {
-                        return Err(LexerError {
-                            message: format!("unescaped control character U+{:04X}", ch as u32),
-                            position: self.position - 1,
-                        });
+                        return Err(ParseError::new(
+                            ParseErrorKind::ControlCharacter,
+                            format!("unescaped control character U+{:04X}", ch as u32),
+                            self.position - 1..self.position,
+                        ));
+                    }
+                    if let Some(value) = &mut value {
+                        value.push(ch);
                     }
-                    value.push(ch)
                 }
                 None => {
-                    return Err(LexerError {
-                        message: "unterminated string".to_string(),
-                        position: start_pos,
-                    });
+                    return Err(ParseError::new(
+                        ParseErrorKind::UnterminatedString,
+                        format!(
+                            "string opened at position {} was never closed",
+                            start_pos - 1
+                        ),
+                        self.position..self.position,
+                    )
+                    .with_opening(start_pos - 1..start_pos));
                 }
             }
         }
 
-        Ok(TokenKind::String(value))
+        Ok(TokenKind::String(match value {
+            Some(value) => Cow::Owned(value),
+            None => Cow::Borrowed(&input[content_start..self.byte_position - quote.len_utf8()]),
+        }))
     }
 
-    fn read_number(&mut self) -> Result<TokenKind, LexerError> {
+    fn read_number(&mut self) -> Result<TokenKind<'a>, ParseError> {
         let start_pos = self.position;
         let mut num_str = String::new();
 
@@ -405,20 +573,22 @@ impl<'a> Lexer<'a> {
 
         // RFC 9535: Reject leading zeros (e.g., "01", "007") but allow "0"
         if int_part.len() > 1 && int_part.starts_with('0') {
-            return Err(LexerError {
-                message: "leading zeros not allowed".to_string(),
-                position: start_pos,
-            });
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidNumber,
+                "leading zeros not allowed",
+                start_pos..self.position,
+            ));
         }
 
         let is_negative = num_str.starts_with('-');
 
         // RFC 9535: A negative number must have at least one integer digit (reject "-.1")
         if is_negative && int_part.is_empty() {
-            return Err(LexerError {
-                message: "negative number must have integer digit".to_string(),
-                position: start_pos,
-            });
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidNumber,
+                "negative number must have integer digit",
+                start_pos..self.position,
+            ));
         }
 
         // Track if number has decimal point or exponent (makes it a "float")
@@ -468,44 +638,51 @@ impl<'a> Lexer<'a> {
                 }
             }
             if num_str.len() == exp_start || num_str.ends_with('+') || num_str.ends_with('-') {
-                return Err(LexerError {
-                    message: "invalid exponent in number".to_string(),
-                    position: start_pos,
-                });
+                return Err(ParseError::new(
+                    ParseErrorKind::InvalidNumber,
+                    "invalid exponent in number",
+                    start_pos..self.position,
+                ));
             }
         }
 
         if num_str.is_empty() || num_str == "-" {
-            return Err(LexerError {
-                message: "invalid number".to_string(),
-                position: start_pos,
-            });
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidNumber,
+                "invalid number",
+                start_pos..self.position,
+            ));
         }
 
-        // Note: -0 is valid per RFC 9535 and equals 0
+        // Note: -0 is valid per RFC 9535 and equals 0, but is kept as a float so that index
+        // selectors can reject it
+        let negative_zero = is_negative && int_part.bytes().all(|b| b == b'0');
+        if !(has_decimal_or_exp || negative_zero)
+            && let Ok(n) = num_str.parse::<i128>()
+            && (i128::from(i64::MIN)..=i128::from(u64::MAX)).contains(&n)
+        {
+            return Ok(TokenKind::Integer(n));
+        }
 
-        let value: f64 = num_str.parse().map_err(|_| LexerError {
-            message: "number out of range".to_string(),
-            position: start_pos,
+        let value: f64 = num_str.parse().map_err(|_| {
+            ParseError::new(
+                ParseErrorKind::InvalidNumber,
+                "number out of range",
+                start_pos..self.position,
+            )
         })?;
 
         Ok(TokenKind::Number(value, has_decimal_or_exp))
     }
 
-    fn read_ident_or_keyword(&mut self) -> TokenKind {
-        let mut ident = String::new();
-
-        while let Some(&ch) = self.chars.peek() {
-            if is_ident_char(ch) {
-                if let Some(c) = self.advance() {
-                    ident.push(c);
-                }
-            } else {
-                break;
-            }
+    fn read_ident_or_keyword(&mut self) -> TokenKind<'a> {
+        let start = self.byte_position;
+        while self.chars.peek().is_some_and(|&ch| is_ident_char(ch)) {
+            self.advance();
         }
 
-        match ident.as_str() {
+        let ident = &self.input[start..self.byte_position];
+        match ident {
             "true" => TokenKind::True,
             "false" => TokenKind::False,
             "null" => TokenKind::Null,
@@ -514,9 +691,156 @@ impl<'a> Lexer<'a> {
     }
 }
 
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<Token<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.next_token() {
+            Ok(token) => token.map(Ok),
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Tokens of a query, with errors as [`TokenKind::Error`] tokens, see [`Lexer::recovering`]
+///
+/// An error token spans the malformed input: a whole string literal for errors inside one,
+/// otherwise at least one character, so lexing always makes progress.
+pub struct Recovering<'a> {
+    lexer: Lexer<'a>,
+}
+
+impl<'a> Iterator for Recovering<'a> {
+    type Item = Token<'a>;
+
+    fn next(&mut self) -> Option<Token<'a>> {
+        let lexer = &mut self.lexer;
+        lexer.skip_whitespace();
+        let (start, byte_start) = (lexer.position, lexer.byte_position);
+        let first = *lexer.chars.peek()?;
+        match lexer.next_token() {
+            Ok(token) => token,
+            Err(err) => {
+                lexer.resync(first, start, err.kind);
+                Some(Token {
+                    kind: TokenKind::Error(Box::new(err)),
+                    position: start,
+                    end: lexer.position,
+                    byte_position: byte_start,
+                    byte_end: lexer.byte_position,
+                })
+            }
+        }
+    }
+}
+
+/// Coarse token class for syntax highlighting
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SemanticKind {
+    /// `$` and `@`
+    Node,
+    /// Brackets, parentheses, `.`, `..`, `,`, `:`, `?` and `*`
+    Punctuation,
+    /// Comparison operators, `&&`, `||`, `!`, and the arithmetic `+`, `-` and `/`
+    Operator,
+    /// String literals, including quoted member names
+    String,
+    /// Number literals, indices and slice bounds
+    Number,
+    /// `true`, `false` and `null` as literals
+    Keyword,
+    /// Member names in dot notation
+    Identifier,
+    /// A name followed by `(`
+    Function,
+    /// Malformed input
+    Error,
+}
+
+/// Token classified for syntax highlighting, see [`Lexer::highlight`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub kind: SemanticKind,
+    /// Character range of the token
+    pub span: Range<usize>,
+    /// Byte range of the token
+    pub byte_span: Range<usize>,
+}
+
+/// Iterator returned by [`Lexer::highlight`]
+pub struct Highlights<'a> {
+    tokens: Peekable<Recovering<'a>>,
+    /// The previous token was `.` or `..`, so a keyword is a member name
+    after_dot: bool,
+}
+
+impl Iterator for Highlights<'_> {
+    type Item = Highlight;
+
+    fn next(&mut self) -> Option<Highlight> {
+        use TokenKind as T;
+        let token = self.tokens.next()?;
+        let after_dot = std::mem::replace(
+            &mut self.after_dot,
+            matches!(token.kind, T::Dot | T::DotDot),
+        );
+        let kind = match &token.kind {
+            T::Root | T::At => SemanticKind::Node,
+            T::Dot
+            | T::DotDot
+            | T::BracketOpen
+            | T::BracketClose
+            | T::ParenOpen
+            | T::ParenClose
+            | T::Wildcard
+            | T::Colon
+            | T::Comma
+            | T::Question => SemanticKind::Punctuation,
+            T::LessThan
+            | T::GreaterThan
+            | T::LessEq
+            | T::GreaterEq
+            | T::Equal
+            | T::NotEqual
+            | T::And
+            | T::Or
+            | T::Not
+            | T::Plus
+            | T::Minus
+            | T::Slash => SemanticKind::Operator,
+            T::True | T::False | T::Null if after_dot => SemanticKind::Identifier,
+            T::True | T::False | T::Null => SemanticKind::Keyword,
+            T::Ident(_)
+                if !after_dot
+                    && self
+                        .tokens
+                        .peek()
+                        .is_some_and(|next| next.kind == T::ParenOpen) =>
+            {
+                SemanticKind::Function
+            }
+            T::Ident(_) => SemanticKind::Identifier,
+            T::String(_) => SemanticKind::String,
+            T::Integer(_) | T::Number(..) => SemanticKind::Number,
+            T::Error(_) => SemanticKind::Error,
+        };
+        Some(Highlight {
+            kind,
+            span: token.span(),
+            byte_span: token.byte_span(),
+        })
+    }
+}
+
 /// Check if character is valid as the start of an identifier (RFC 9535 name-first)
 /// name-first = ALPHA / "_" / %x80-D7FF / %xE000-10FFFF
-fn is_ident_start(ch: char) -> bool {
+pub(crate) fn is_ident_start(ch: char) -> bool {
     let code = ch as u32;
     ch.is_ascii_alphabetic()
         || ch == '_'
@@ -524,9 +848,20 @@ fn is_ident_start(ch: char) -> bool {
         || (0xE000..=0x10FFFF).contains(&code)
 }
 
+/// Check if `name` can be called as a function (RFC 9535 function-name) rather than lexed as
+/// a keyword
+/// function-name = LCALPHA *( LCALPHA / "_" / DIGIT )
+pub(crate) fn is_function_name(name: &str) -> bool {
+    name.starts_with(|ch: char| ch.is_ascii_lowercase())
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || ch == '_')
+        && !matches!(name, "true" | "false" | "null")
+}
+
 /// Check if character is valid within an identifier (RFC 9535 name-char)
 /// name-char = name-first / DIGIT
-fn is_ident_char(ch: char) -> bool {
+pub(crate) fn is_ident_char(ch: char) -> bool {
     is_ident_start(ch) || ch.is_ascii_digit()
 }
 
@@ -535,20 +870,162 @@ fn is_ident_char(ch: char) -> bool {
 mod tests {
     use super::*;
 
-    fn kinds(tokens: &[Token]) -> Vec<&TokenKind> {
+    fn kinds<'t, 'a>(tokens: &'t [Token<'a>]) -> Vec<&'t TokenKind<'a>> {
         tokens.iter().map(|t| &t.kind).collect()
     }
 
+    #[test]
+    fn test_iterator_stops_at_first_error() {
+        let items: Vec<_> = Lexer::new("$.a # .b").collect();
+        assert_eq!(items.len(), 4);
+        assert!(items[..3].iter().all(Result::is_ok));
+        let err = items[3].as_ref().unwrap_err();
+        assert_eq!(
+            (err.kind, err.span.clone()),
+            (ParseErrorKind::UnexpectedCharacter, 4..5)
+        );
+    }
+
+    #[test]
+    fn test_recovering_continues_after_errors() {
+        let tokens: Vec<_> = Lexer::new("$[?@.a = 01x && 'b\\qc' == #]")
+            .recovering()
+            .collect();
+        let summary: Vec<_> = tokens
+            .iter()
+            .map(|t| match &t.kind {
+                TokenKind::Error(err) => (format!("error {:?}", err.kind), t.span()),
+                kind => (format!("{kind:?}"), t.span()),
+            })
+            .collect();
+        let expected = [
+            ("Root", 0..1),
+            ("BracketOpen", 1..2),
+            ("Question", 2..3),
+            ("At", 3..4),
+            ("Dot", 4..5),
+            ("Ident(\"a\")", 5..6),
+            ("error UnexpectedCharacter", 7..8),
+            ("error InvalidNumber", 9..12),
+            ("And", 13..15),
+            ("error InvalidEscape", 16..22),
+            ("Equal", 23..25),
+            ("error UnexpectedCharacter", 26..27),
+            ("BracketClose", 27..28),
+        ];
+        let expected: Vec<_> = expected
+            .iter()
+            .map(|(k, s)| (k.to_string(), s.clone()))
+            .collect();
+        assert_eq!(summary, expected);
+
+        // An unterminated string runs to the end of input
+        let tokens: Vec<_> = Lexer::new("$['a").recovering().collect();
+        assert_eq!(tokens.len(), 3);
+        assert!(matches!(tokens[2].kind, TokenKind::Error(_)));
+        assert_eq!(tokens[2].span(), 2..4);
+        assert!(Lexer::new("").recovering().next().is_none());
+    }
+
+    #[test]
+    fn test_highlight() {
+        use SemanticKind as K;
+        let input = "$.true[?@.null == null && match(@.a, 'x') || !count(@.*) > 1.5]";
+        let kinds: Vec<_> = Lexer::new(input).highlight().map(|h| h.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                K::Node,
+                K::Punctuation,
+                K::Identifier,
+                K::Punctuation,
+                K::Punctuation,
+                K::Node,
+                K::Punctuation,
+                K::Identifier,
+                K::Operator,
+                K::Keyword,
+                K::Operator,
+                K::Function,
+                K::Punctuation,
+                K::Node,
+                K::Punctuation,
+                K::Identifier,
+                K::Punctuation,
+                K::String,
+                K::Punctuation,
+                K::Operator,
+                K::Operator,
+                K::Function,
+                K::Punctuation,
+                K::Node,
+                K::Punctuation,
+                K::Punctuation,
+                K::Punctuation,
+                K::Operator,
+                K::Number,
+                K::Punctuation,
+            ]
+        );
+
+        let input = "$.日本 # x";
+        let highlights: Vec<_> = Lexer::new(input).highlight().collect();
+        assert_eq!(highlights[3].kind, K::Error);
+        assert_eq!(highlights[3].span, 5..6);
+        assert_eq!(&input[highlights[3].byte_span.clone()], "#");
+        assert_eq!(highlights[4].kind, K::Identifier);
+    }
+
+    #[test]
+    fn test_names_borrow_from_input() {
+        let input = "$.日本['a\\'b', \"c\", 'd\\u0065']";
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        assert!(matches!(
+            tokens[2].kind,
+            TokenKind::Ident(name) if std::ptr::eq(name, &input[tokens[2].byte_span()])
+        ));
+
+        let strings: Vec<_> = tokens
+            .iter()
+            .filter_map(|t| match &t.kind {
+                TokenKind::String(s) => Some(s),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(strings, vec!["a'b", "c", "de"]);
+        assert!(matches!(strings[0], Cow::Owned(_)));
+        assert!(matches!(strings[1], Cow::Borrowed("c")));
+        assert!(matches!(strings[2], Cow::Owned(_)));
+    }
+
+    #[test]
+    fn test_multibyte_spans() {
+        let input = "$.日本語['é', 1]";
+        let tokens = Lexer::new(input).tokenize().unwrap();
+        let spans: Vec<_> = tokens.iter().map(|t| (t.span(), t.byte_span())).collect();
+        assert_eq!(
+            spans,
+            vec![
+                (0..1, 0..1),
+                (1..2, 1..2),
+                (2..5, 2..11),
+                (5..6, 11..12),
+                (6..9, 12..16),
+                (9..10, 16..17),
+                (11..12, 18..19),
+                (12..13, 19..20),
+            ]
+        );
+        assert_eq!(&input[tokens[2].byte_span()], "日本語");
+        assert_eq!(&input[tokens[4].byte_span()], "'é'");
+    }
+
     #[test]
     fn test_basic_tokens() {
         let tokens = Lexer::new("$.foo").tokenize().unwrap();
         assert_eq!(
             kinds(&tokens),
-            vec![
-                &TokenKind::Root,
-                &TokenKind::Dot,
-                &TokenKind::Ident("foo".to_string())
-            ]
+            vec![&TokenKind::Root, &TokenKind::Dot, &TokenKind::Ident("foo")]
         );
     }
 
@@ -560,7 +1037,7 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::BracketOpen,
-                &TokenKind::String("foo".to_string()),
+                &TokenKind::String("foo".into()),
                 &TokenKind::BracketClose
             ]
         );
@@ -574,7 +1051,7 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::BracketOpen,
-                &TokenKind::Number(0.0, false),
+                &TokenKind::Integer(0),
                 &TokenKind::BracketClose
             ]
         );
@@ -588,7 +1065,7 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::BracketOpen,
-                &TokenKind::Number(-1.0, false),
+                &TokenKind::Integer(-1),
                 &TokenKind::BracketClose
             ]
         );
@@ -616,7 +1093,7 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::DotDot,
-                &TokenKind::Ident("foo".to_string())
+                &TokenKind::Ident("foo")
             ]
         );
     }
@@ -634,11 +1111,7 @@ mod tests {
         let tokens = Lexer::new("@.price").tokenize().unwrap();
         assert_eq!(
             kinds(&tokens),
-            vec![
-                &TokenKind::At,
-                &TokenKind::Dot,
-                &TokenKind::Ident("price".to_string())
-            ]
+            vec![&TokenKind::At, &TokenKind::Dot, &TokenKind::Ident("price")]
         );
     }
 
@@ -653,7 +1126,7 @@ mod tests {
                 &TokenKind::Question,
                 &TokenKind::At,
                 &TokenKind::Dot,
-                &TokenKind::Ident("price".to_string()),
+                &TokenKind::Ident("price"),
                 &TokenKind::BracketClose
             ]
         );
@@ -693,11 +1166,11 @@ mod tests {
                 &TokenKind::ParenOpen,
                 &TokenKind::At,
                 &TokenKind::Dot,
-                &TokenKind::Ident("a".to_string()),
+                &TokenKind::Ident("a"),
                 &TokenKind::And,
                 &TokenKind::At,
                 &TokenKind::Dot,
-                &TokenKind::Ident("b".to_string()),
+                &TokenKind::Ident("b"),
                 &TokenKind::ParenClose
             ]
         );
@@ -723,9 +1196,9 @@ mod tests {
                 &TokenKind::Question,
                 &TokenKind::At,
                 &TokenKind::Dot,
-                &TokenKind::Ident("price".to_string()),
+                &TokenKind::Ident("price"),
                 &TokenKind::LessThan,
-                &TokenKind::Number(10.0, false),
+                &TokenKind::Integer(10),
                 &TokenKind::BracketClose
             ]
         );
@@ -744,13 +1217,13 @@ mod tests {
                 &TokenKind::Question,
                 &TokenKind::At,
                 &TokenKind::Dot,
-                &TokenKind::Ident("price".to_string()),
+                &TokenKind::Ident("price"),
                 &TokenKind::GreaterEq,
-                &TokenKind::Number(10.0, false),
+                &TokenKind::Integer(10),
                 &TokenKind::And,
                 &TokenKind::At,
                 &TokenKind::Dot,
-                &TokenKind::Ident("available".to_string()),
+                &TokenKind::Ident("available"),
                 &TokenKind::Equal,
                 &TokenKind::True,
                 &TokenKind::BracketClose
@@ -831,16 +1304,33 @@ mod tests {
 
     // ========== Unicode Identifier Tests ==========
 
+    #[test]
+    fn test_integers_are_exact() {
+        for (input, kind) in [
+            ("9007199254740993", TokenKind::Integer(9007199254740993)),
+            ("-9223372036854775808", TokenKind::Integer(i64::MIN.into())),
+            ("18446744073709551615", TokenKind::Integer(u64::MAX.into())),
+            (
+                "18446744073709551616",
+                TokenKind::Number(18446744073709551616.0, false),
+            ),
+            ("-0", TokenKind::Number(-0.0, false)),
+            ("0", TokenKind::Integer(0)),
+            ("1e2", TokenKind::Number(100.0, true)),
+        ] {
+            let tokens = Lexer::new(input).tokenize().unwrap();
+            assert_eq!(kinds(&tokens), vec![&kind], "input {input}");
+        }
+        let tokens = Lexer::new("-0").tokenize().unwrap();
+        assert!(matches!(tokens[0].kind, TokenKind::Number(n, false) if n.is_sign_negative()));
+    }
+
     #[test]
     fn test_unicode_emoji_identifier() {
         let tokens = Lexer::new("$.☺").tokenize().unwrap();
         assert_eq!(
             kinds(&tokens),
-            vec![
-                &TokenKind::Root,
-                &TokenKind::Dot,
-                &TokenKind::Ident("☺".to_string())
-            ]
+            vec![&TokenKind::Root, &TokenKind::Dot, &TokenKind::Ident("☺")]
         );
     }
 
@@ -852,7 +1342,7 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::Dot,
-                &TokenKind::Ident("日本語".to_string())
+                &TokenKind::Ident("日本語")
             ]
         );
     }
@@ -865,7 +1355,7 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::Dot,
-                &TokenKind::Ident("émoji".to_string())
+                &TokenKind::Ident("émoji")
             ]
         );
     }
@@ -878,8 +1368,33 @@ mod tests {
             vec![
                 &TokenKind::Root,
                 &TokenKind::Dot,
-                &TokenKind::Ident("hello世界123".to_string())
+                &TokenKind::Ident("hello世界123")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arithmetic_operators() {
+        let tokens = Lexer::new("1+2 - -3*4/5-6 - @").tokenize().unwrap();
+        assert_eq!(
+            kinds(&tokens),
+            vec![
+                &TokenKind::Integer(1),
+                &TokenKind::Plus,
+                &TokenKind::Integer(2),
+                &TokenKind::Minus,
+                &TokenKind::Integer(-3),
+                &TokenKind::Wildcard,
+                &TokenKind::Integer(4),
+                &TokenKind::Slash,
+                &TokenKind::Integer(5),
+                &TokenKind::Integer(-6),
+                &TokenKind::Minus,
+                &TokenKind::At,
             ]
         );
+        // A `-` before `.` still reads as a malformed number
+        let err = Lexer::new("-.1").tokenize().unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidNumber);
     }
 }
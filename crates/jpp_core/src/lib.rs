@@ -19,12 +19,42 @@
 //! ```
 
 pub mod ast;
+pub mod complete;
+pub mod complexity;
+pub mod error;
 pub mod eval;
+#[cfg(feature = "extended-functions")]
+mod extensions;
+pub mod filter;
+pub mod function;
+pub mod iregexp;
 pub mod lexer;
+pub mod lint;
+pub mod matcher;
+mod optimize;
 pub mod parser;
+pub mod path;
+pub mod project;
+pub mod trace;
 
 pub use ast::JsonPath;
+pub use complete::{CompletionContext, Expected, complete};
+pub use complexity::{Complexity, ComplexityLimits};
+pub use error::{
+    BudgetLimit, EvalError, InvalidFunctionName, ParseError, ParseErrorKind, ParseWarning,
+    display_width, render_span,
+};
+pub use eval::{EvalOptions, EvalStats};
+pub use filter::FilterExpr;
+pub use function::{ExprValue, Function, FunctionRegistry, FunctionType};
+pub use lint::{LintKind, LintWarning};
+pub use matcher::MatchAnswer;
+pub use parser::ParseOptions;
+pub use path::{NormalizedPath, PathElement, escape_name, needs_bracket_notation};
+pub use project::{ProjectOptions, project, project_with_options};
 use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+pub use trace::{FilterTrace, QueryTrace, SegmentTrace, SelectorTrace};
 
 impl JsonPath {
     /// Parse a JSONPath query string
@@ -49,6 +79,164 @@ impl JsonPath {
         parser::Parser::parse(jsonpath).map_err(Error::from)
     }
 
+    /// Parse a JSONPath query string whose filters may call the functions in `registry`
+    ///
+    /// Registered functions are checked for arity and argument types like the built-ins,
+    /// and calls to names not in the registry are rejected. See [`FunctionRegistry::register`].
+    pub fn parse_with_registry(jsonpath: &str, registry: &FunctionRegistry) -> Result<Self, Error> {
+        parser::Parser::parse_with_registry(jsonpath, registry).map_err(Error::from)
+    }
+
+    /// Parse a JSONPath query string with non-default [`ParseOptions`]
+    ///
+    /// Returns the parsed query together with a warning for each deviation from RFC 9535
+    /// that the options allowed.
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::{JsonPath, ParseOptions};
+    ///
+    /// let options = ParseOptions {
+    ///     lenient_whitespace: true,
+    ///     ..Default::default()
+    /// };
+    /// let (path, warnings) = JsonPath::parse_with_options(" $. foo ", &options).unwrap();
+    /// assert_eq!(path, JsonPath::parse("$.foo").unwrap());
+    /// assert_eq!(warnings.len(), 3);
+    /// assert!(JsonPath::parse(" $. foo ").is_err());
+    /// ```
+    pub fn parse_with_options(
+        jsonpath: &str,
+        options: &ParseOptions,
+    ) -> Result<(Self, Vec<ParseWarning>), Error> {
+        parser::Parser::parse_with_options(jsonpath, options).map_err(Error::from)
+    }
+
+    /// Find patterns that are valid but probably not what was intended
+    ///
+    /// Flags comparisons that cannot depend on the document (between two literals, or between
+    /// a number-valued function and a non-number literal), `!` applied to a ValueType
+    /// function, selectors repeating an earlier selector of their segment, and regex
+    /// literals that do not compile. `span` refers to the query's `Display` output and
+    /// `source_span` to the string it was parsed from.
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::{JsonPath, LintKind};
+    ///
+    /// let query = "$.items[?length(@.name)>'3']";
+    /// let path = JsonPath::parse(query).unwrap();
+    /// let warnings = path.lint();
+    /// assert_eq!(warnings[0].kind, LintKind::IncomparableTypes);
+    /// let rendered = path.to_string();
+    /// assert_eq!(&rendered[warnings[0].span.clone()], "length(@['name']) > '3'");
+    /// let source = warnings[0].source_span.clone().unwrap();
+    /// assert_eq!(&query[source], "length(@.name)>'3'");
+    /// ```
+    pub fn lint(&self) -> Vec<LintWarning> {
+        lint::lint(self)
+    }
+
+    /// Rewrite the query into a cheaper equivalent form
+    ///
+    /// Applied rewrites, each of which keeps the results, their order and their duplicates:
+    /// - slices selecting at most one fixed index become index selectors: `[2:3]` → `[2]`,
+    ///   `[-1:]` → `[-1]`
+    /// - comparisons with a constant outcome are folded: literal against literal, and a
+    ///   singular query against itself with `==`, `!=`, `<` or `>` (`@.a == @.a` is true even
+    ///   when `a` is missing)
+    /// - constants are propagated through `!`, `&&` and `||`, and `!!x` becomes `x`
+    /// - an always-true filter becomes `*`, and an always-false filter is dropped from a
+    ///   segment with other selectors (a lone one is kept as `?!@`)
+    ///
+    /// Rewrites that change duplicate results are not applied: `..*..a` is not collapsed
+    /// into `..a`, and repeated selectors such as `[0, 0]` are kept.
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::JsonPath;
+    ///
+    /// let path = JsonPath::parse("$.items[0:1][?@.id == @.id && @.ok]").unwrap();
+    /// assert_eq!(path.optimize().to_string(), "$['items'][0][?@['ok']]");
+    /// ```
+    pub fn optimize(self) -> JsonPath {
+        optimize::optimize(self)
+    }
+
+    /// Compute structural complexity metrics of the query
+    ///
+    /// Useful for rejecting pathological queries from untrusted sources before evaluation.
+    /// See [`Complexity::exceeds`].
+    pub fn complexity(&self) -> Complexity {
+        Complexity::of(self)
+    }
+
+    /// Decide whether the query selects the node at `path` without access to the document
+    ///
+    /// Returns [`MatchAnswer::NeedsDocument`] when the answer depends on the document, e.g.
+    /// filters, negative indices, or slices whose bounds are relative to the array length.
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::{JsonPath, MatchAnswer, NormalizedPath, PathElement};
+    ///
+    /// let location = NormalizedPath::new(vec![
+    ///     PathElement::Name("book".to_string()),
+    ///     PathElement::Index(2),
+    /// ]);
+    /// let path = JsonPath::parse("$..book[*]").unwrap();
+    /// assert_eq!(path.matches_path(&location), MatchAnswer::Yes);
+    /// let path = JsonPath::parse("$.book[-1]").unwrap();
+    /// assert_eq!(path.matches_path(&location), MatchAnswer::NeedsDocument);
+    /// ```
+    pub fn matches_path(&self, path: &NormalizedPath) -> MatchAnswer {
+        matcher::matches_path(self, path)
+    }
+
+    /// Split the query into its leading definite segments and the remaining suffix
+    ///
+    /// The prefix is the longest leading run of child segments with a single name or
+    /// non-negative index selector. Resolving the prefix and then evaluating the suffix on
+    /// the resolved node gives the same results as evaluating the whole query, which allows
+    /// the prefix to be pushed down to a storage layer.
+    ///
+    /// Filters containing absolute (`$`) queries need the whole document, so such queries
+    /// return an empty prefix and the query unchanged.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let json = json!({"store": {"book": [{"price": 8}, {"price": 12}]}});
+    /// let path = JsonPath::parse("$.store.book[?@.price < 10]").unwrap();
+    /// let (prefix, suffix) = path.static_prefix();
+    /// assert_eq!(prefix.to_string(), "$['store']['book']");
+    /// let node = prefix.get(&json).unwrap();
+    /// assert_eq!(suffix.query(node), path.query(&json));
+    /// ```
+    pub fn static_prefix(&self) -> (NormalizedPath, JsonPath) {
+        if ast::segments_reference_root(&self.segments) {
+            return (NormalizedPath::root(), self.clone());
+        }
+
+        let mut prefix = NormalizedPath::root();
+        for segment in &self.segments {
+            let element = match segment {
+                ast::Segment::Child(selectors) => match selectors.as_slice() {
+                    [ast::Selector::Name(name)] => PathElement::Name(name.clone()),
+                    [ast::Selector::Index(i)] if *i >= 0 => PathElement::Index(*i as usize),
+                    _ => break,
+                },
+                ast::Segment::Descendant(_) => break,
+            };
+            prefix.push(element);
+        }
+
+        let suffix = JsonPath::new(self.segments[prefix.len()..].to_vec());
+        (prefix, suffix)
+    }
+
     /// Execute the query and return references to matching values
     ///
     /// Returns references to the matched values within the input JSON.
@@ -70,12 +258,304 @@ impl JsonPath {
     pub fn query<'a>(&self, json: &'a Value) -> Vec<&'a Value> {
         eval::evaluate(self, json)
     }
+
+    /// Execute the query and return at most its first `limit` results
+    ///
+    /// Evaluation stops at the `limit`-th match, so peeking at a few results of a large
+    /// document costs only the work needed to find them.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let json = json!({"items": [1, 2, 3]});
+    /// let path = JsonPath::parse("$.items[*]").unwrap();
+    /// assert_eq!(path.query_limit(&json, 2), vec![&json!(1), &json!(2)]);
+    /// ```
+    pub fn query_limit<'a>(&self, json: &'a Value, limit: usize) -> Vec<&'a Value> {
+        eval::evaluate_limit(self, json, limit)
+    }
+
+    /// Number of nodes the query selects, duplicates included
+    ///
+    /// Counts matches as evaluation finds them, without building the result list.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let json = json!({"a": [1, 2], "b": {"a": 3}});
+    /// assert_eq!(JsonPath::parse("$..a").unwrap().count(&json), 2);
+    /// ```
+    pub fn count(&self, json: &Value) -> usize {
+        eval::count(self, json)
+    }
+
+    /// Execute the query within the limits of `options`
+    ///
+    /// Fails with [`EvalError::BudgetExceeded`] once evaluation visits more nodes than
+    /// allowed, selects more results than allowed or runs past the deadline, rather than
+    /// returning partial results. Use this for
+    /// untrusted queries or documents. With the default options it costs the same as
+    /// [`JsonPath::query`].
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::{BudgetLimit, EvalError, EvalOptions, JsonPath};
+    ///
+    /// let json = json!({"a": [1, 2, 3], "b": {"c": [4, 5]}});
+    /// let path = JsonPath::parse("$..*").unwrap();
+    /// let options = EvalOptions {
+    ///     max_nodes: Some(5),
+    ///     ..Default::default()
+    /// };
+    /// let err = path.query_with_options(&json, &options).unwrap_err();
+    /// assert_eq!(err, EvalError::BudgetExceeded { visited: 6, limit: BudgetLimit::MaxNodes });
+    /// ```
+    pub fn query_with_options<'a>(
+        &self,
+        json: &'a Value,
+        options: &EvalOptions,
+    ) -> Result<Vec<&'a Value>, EvalError> {
+        eval::evaluate_with_options(self, json, options)
+    }
+
+    /// Execute the query and report how much work it took
+    ///
+    /// See [`EvalStats`] for what is counted. Counting only happens here; [`JsonPath::query`]
+    /// does not pay for it.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let json = json!({"a": {"b": 1, "c": 2}});
+    /// let (results, stats) = JsonPath::parse("$.a.b").unwrap().query_with_stats(&json);
+    /// assert_eq!(results, vec![&json!(1)]);
+    /// assert_eq!((stats.nodes_visited, stats.nodes_matched), (3, 1));
+    /// ```
+    pub fn query_with_stats<'a>(&self, json: &'a Value) -> (Vec<&'a Value>, EvalStats) {
+        eval::evaluate_with_stats(self, json)
+    }
+
+    /// Execute the query and return each selected node at most once
+    ///
+    /// RFC 9535 keeps duplicates when a node is selected more than once (`$[0, 0]`,
+    /// `$['a', *]`); this method intentionally deviates from that and drops repeated
+    /// selections, keeping first-occurrence order. Nodes are compared by identity, not by
+    /// value, so distinct nodes with equal values are all kept.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let json = json!([1, 1]);
+    /// let path = JsonPath::parse("$[0, 0, 1]").unwrap();
+    /// assert_eq!(path.query(&json).len(), 3);
+    /// assert_eq!(path.query_unique_ref(&json), vec![&json!(1), &json!(1)]);
+    /// ```
+    pub fn query_unique_ref<'a>(&self, json: &'a Value) -> Vec<&'a Value> {
+        let mut seen = HashSet::new();
+        let mut results = eval::evaluate(self, json);
+        results.retain(|value| seen.insert(std::ptr::from_ref::<Value>(value)));
+        results
+    }
+
+    /// Execute the query and record per-segment node counts
+    ///
+    /// Useful for explaining an empty result: [`QueryTrace::first_empty_segment`] points at
+    /// the segment that dropped all nodes. Enable the `serde` feature to serialize the trace.
+    /// Tracing has its own evaluation loop, so [`JsonPath::query`] is unaffected.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let json = json!({"items": [{"price": 5}, {"price": 20}]});
+    /// let path = JsonPath::parse("$.items[?@.price > 100]").unwrap();
+    /// let trace = path.query_trace(&json);
+    /// let (index, segment) = trace.first_empty_segment().unwrap();
+    /// assert_eq!(index, 1);
+    /// assert_eq!(segment.segment, "[?@['price'] > 100]");
+    /// let filter = segment.selectors[0].filter.unwrap();
+    /// assert_eq!((filter.passed, filter.failed), (0, 2));
+    /// ```
+    pub fn query_trace(&self, json: &Value) -> QueryTrace {
+        trace::trace(self, json)
+    }
+
+    /// Execute the query and return each match together with its normalized path
+    ///
+    /// Results are in the same order as [`JsonPath::query`].
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let path = JsonPath::parse("$.items[?@ > 1]").unwrap();
+    /// let json = json!({"items": [1, 2, 3]});
+    /// let results = path.query_located(&json);
+    /// assert_eq!(results[0].0.to_string(), "$['items'][1]");
+    /// assert_eq!(results[0].1, &json!(2));
+    /// assert_eq!(results.len(), 2);
+    /// ```
+    pub fn query_located<'a>(&self, json: &'a Value) -> Vec<(NormalizedPath, &'a Value)> {
+        eval::evaluate_located(self, json)
+    }
+
+    /// [`JsonPath::query_located`] within the limits of `options`, failing like
+    /// [`JsonPath::query_with_options`]
+    pub fn query_located_with_options<'a>(
+        &self,
+        json: &'a Value,
+        options: &EvalOptions,
+    ) -> Result<Vec<(NormalizedPath, &'a Value)>, EvalError> {
+        eval::evaluate_located_with_options(self, json, options)
+    }
+
+    /// Execute the query and group the matches by the normalized path of their parent node
+    ///
+    /// Groups are ordered by their first match, and values within a group keep query order.
+    /// A match of the root itself (the query `$`) has no parent and is grouped under `$`.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let path = JsonPath::parse("$..price").unwrap();
+    /// let json = json!({"a": {"price": 1}, "b": [{"price": 2}, {"price": 3}]});
+    /// let groups = path.query_grouped(&json);
+    /// assert_eq!(groups.len(), 3);
+    /// assert_eq!(groups[0].0.to_string(), "$['a']");
+    /// assert_eq!(groups[0].1, vec![&json!(1)]);
+    /// ```
+    pub fn query_grouped<'a>(&self, json: &'a Value) -> Vec<(NormalizedPath, Vec<&'a Value>)> {
+        let mut groups: Vec<(NormalizedPath, Vec<&'a Value>)> = Vec::new();
+        let mut group_index: HashMap<NormalizedPath, usize> = HashMap::new();
+
+        for (mut location, value) in self.query_located(json) {
+            location.pop();
+            match group_index.get(&location) {
+                Some(&i) => groups[i].1.push(value),
+                None => {
+                    group_index.insert(location.clone(), groups.len());
+                    groups.push((location, vec![value]));
+                }
+            }
+        }
+
+        groups
+    }
+
+    /// Remove every node the query selects from `json` and return how many were removed
+    ///
+    /// Elements are removed from the end of each array first, so the indices of the other
+    /// matches stay valid. A match inside another match goes away with it and is not counted,
+    /// and a match of the root itself is left in place, since it has no parent to remove it
+    /// from.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let mut json = json!({"users": [{"name": "a", "token": 1}, {"name": "b", "token": 2}]});
+    /// let path = JsonPath::parse("$..token").unwrap();
+    /// assert_eq!(path.delete(&mut json), 2);
+    /// assert_eq!(json, json!({"users": [{"name": "a"}, {"name": "b"}]}));
+    /// ```
+    pub fn delete(&self, json: &mut Value) -> usize {
+        // In reverse order, removing an array element only shifts matches already removed
+        let mut removed = 0;
+        for location in self.outermost_locations(json).iter().rev() {
+            if location.remove(json).is_some() {
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Replace every node the query selects in `json` with `value` and return how many were
+    /// replaced
+    ///
+    /// A match inside another match is replaced along with it and is not counted. The query
+    /// `$` replaces the whole document.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::JsonPath;
+    ///
+    /// let mut json = json!({"a": {"token": 1}, "b": [{"token": 2}]});
+    /// let path = JsonPath::parse("$..token").unwrap();
+    /// assert_eq!(path.set(&mut json, &json!("REDACTED")), 2);
+    /// assert_eq!(json, json!({"a": {"token": "REDACTED"}, "b": [{"token": "REDACTED"}]}));
+    /// ```
+    pub fn set(&self, json: &mut Value, value: &Value) -> usize {
+        let mut replaced = 0;
+        for location in self.outermost_locations(json) {
+            if let Some(node) = location.get_mut(json) {
+                *node = value.clone();
+                replaced += 1;
+            }
+        }
+        replaced
+    }
+
+    /// Distinct locations of the matches in path order, leaving out those inside another
+    /// match
+    fn outermost_locations(&self, json: &Value) -> Vec<NormalizedPath> {
+        let mut locations: Vec<NormalizedPath> = self
+            .query_located(json)
+            .into_iter()
+            .map(|(location, _)| location)
+            .collect();
+        locations.sort_unstable();
+        locations.dedup();
+        // Sorting puts an ancestor right before its descendants
+        let mut outermost: Vec<NormalizedPath> = Vec::with_capacity(locations.len());
+        for location in locations {
+            if outermost
+                .last()
+                .is_none_or(|ancestor| !ancestor.is_prefix_of(&location))
+            {
+                outermost.push(location);
+            }
+        }
+        outermost
+    }
 }
 
 /// Error type for JSONPath operations
 #[derive(Debug, Clone, PartialEq)]
 pub struct Error {
     message: String,
+    parse_error: Option<ParseError>,
+}
+
+impl Error {
+    /// The underlying syntax error, with its kind and span, if this is a parse error
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::{JsonPath, ParseErrorKind};
+    ///
+    /// let err = JsonPath::parse("$[?foo(@)]").unwrap_err();
+    /// let parse_error = err.parse_error().unwrap();
+    /// assert_eq!(parse_error.kind, ParseErrorKind::UnknownFunction);
+    /// assert_eq!(parse_error.span, 3..6);
+    /// ```
+    pub fn parse_error(&self) -> Option<&ParseError> {
+        self.parse_error.as_ref()
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -86,10 +566,11 @@ impl std::fmt::Display for Error {
 
 impl std::error::Error for Error {}
 
-impl From<parser::ParseError> for Error {
-    fn from(e: parser::ParseError) -> Self {
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
         Self {
             message: format!("parse error: {e}"),
+            parse_error: Some(e),
         }
     }
 }
@@ -171,6 +652,205 @@ mod tests {
         assert_eq!(path.query(&json2), vec![&json!(2)]);
     }
 
+    #[test]
+    fn test_query_located_matches_query() {
+        let json = json!({"a": [{"b": 1}, {"b": [2, 3]}], "c": {"b": 4}});
+        for q in ["$..b", "$.a[*].b", "$..[0]", "$.a[1:]", "$..*", "$[?@.b]"] {
+            let path = JsonPath::parse(q).unwrap();
+            let located = path.query_located(&json);
+            let values: Vec<&Value> = located.iter().map(|(_, v)| *v).collect();
+            assert_eq!(values, path.query(&json), "query {q}");
+            for (location, value) in &located {
+                assert_eq!(location.get(&json), Some(*value), "query {q}");
+            }
+        }
+    }
+
+    fn bookstore() -> Value {
+        json!({
+            "store": {
+                "book": [
+                    {"category": "reference", "author": "Nigel Rees", "title": "Sayings of the Century", "price": 8.95},
+                    {"category": "fiction", "author": "Evelyn Waugh", "title": "Sword of Honour", "price": 12.99},
+                    {"category": "fiction", "author": "Herman Melville", "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99},
+                    {"category": "fiction", "author": "J. R. R. Tolkien", "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99}
+                ],
+                "bicycle": {"color": "red", "price": 399}
+            }
+        })
+    }
+
+    #[test]
+    fn test_static_prefix_splits() {
+        let cases = [
+            ("$", "$", 0),
+            ("$.store.book[0].title", "$['store']['book'][0]['title']", 0),
+            ("$.store.book[*].author", "$['store']['book']", 2),
+            ("$.store..price", "$['store']", 1),
+            ("$.store.book[-1]", "$['store']['book']", 1),
+            ("$.store.book[0,1]", "$['store']['book']", 1),
+            ("$.store.book[1:]", "$['store']['book']", 1),
+            ("$..book[0]", "$", 2),
+            ("$.store.book[?@.price < 10].title", "$['store']['book']", 2),
+            ("$.store.book[?@.price < $.store.bicycle.price]", "$", 3),
+        ];
+        for (query, prefix, suffix_len) in cases {
+            let (p, s) = JsonPath::parse(query).unwrap().static_prefix();
+            assert_eq!(p.to_string(), prefix, "query {query}");
+            assert_eq!(s.segments.len(), suffix_len, "query {query}");
+        }
+    }
+
+    #[test]
+    fn test_static_prefix_equivalent_to_query() {
+        let json = bookstore();
+        let queries = [
+            "$",
+            "$.store",
+            "$.store.book[0].title",
+            "$.store.book[*].author",
+            "$.store.book[-1].title",
+            "$.store.book[1:3]",
+            "$.store.book[::-1].price",
+            "$.store..price",
+            "$..author",
+            "$.store.book[?@.isbn].title",
+            "$.store.book[?@.price < 10 && @.category == 'fiction']",
+            "$.store.book[?@.price < $.store.bicycle.price].title",
+            "$.store.book[?length(@.title) > 10]",
+            "$.store.bicycle.color",
+            "$.store.missing.price",
+            "$.store.book[7].title",
+        ];
+        for query in queries {
+            let path = JsonPath::parse(query).unwrap();
+            let (prefix, suffix) = path.static_prefix();
+            let split = prefix
+                .get(&json)
+                .map(|node| suffix.query(node))
+                .unwrap_or_default();
+            assert_eq!(split, path.query(&json), "query {query}");
+        }
+    }
+
+    #[test]
+    fn test_query_unique_ref_drops_repeated_nodes() {
+        let json = json!({"a": 1, "b": 1, "c": [2]});
+        let path = JsonPath::parse("$['a', *, 'a']").unwrap();
+        assert_eq!(path.query(&json).len(), 5);
+        let unique = path.query_unique_ref(&json);
+        assert_eq!(unique, vec![&json!(1), &json!(1), &json!([2])]);
+        assert!(std::ptr::eq(unique[0], &json["a"]));
+        assert!(std::ptr::eq(unique[1], &json["b"]));
+    }
+
+    #[test]
+    fn test_query_unique_ref_descendant_overlap() {
+        let json = json!({"x": {"x": {"y": 1}}});
+        let path = JsonPath::parse("$..x..y").unwrap();
+        assert_eq!(path.query(&json).len(), 2);
+        assert_eq!(path.query_unique_ref(&json), vec![&json!(1)]);
+    }
+
+    fn grouped_paths(query: &str, json: &Value) -> Vec<(String, Vec<Value>)> {
+        JsonPath::parse(query)
+            .unwrap()
+            .query_grouped(json)
+            .into_iter()
+            .map(|(parent, values)| (parent.to_string(), values.into_iter().cloned().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_query_grouped_array_parents() {
+        let json = json!({
+            "shelves": [
+                {"book": [{"price": 1}, {"price": 2}]},
+                {"book": [{"price": 3}]}
+            ]
+        });
+        assert_eq!(
+            grouped_paths("$..book[*]", &json),
+            vec![
+                (
+                    "$['shelves'][0]['book']".to_string(),
+                    vec![json!({"price": 1}), json!({"price": 2})]
+                ),
+                (
+                    "$['shelves'][1]['book']".to_string(),
+                    vec![json!({"price": 3})]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_grouped_object_parents() {
+        let json = json!({"a": {"x": 1, "y": 2}, "b": {"x": 3}});
+        assert_eq!(
+            grouped_paths("$.*.*", &json),
+            vec![
+                ("$['a']".to_string(), vec![json!(1), json!(2)]),
+                ("$['b']".to_string(), vec![json!(3)]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_grouped_duplicates_and_root() {
+        let json = json!([10, 20]);
+        assert_eq!(
+            grouped_paths("$[1, 0, 1]", &json),
+            vec![("$".to_string(), vec![json!(20), json!(10), json!(20)])]
+        );
+        assert_eq!(
+            grouped_paths("$", &json),
+            vec![("$".to_string(), vec![json!([10, 20])])]
+        );
+        assert!(grouped_paths("$.missing", &json).is_empty());
+    }
+
+    #[test]
+    fn test_delete() {
+        let delete = |query: &str, mut json: Value| {
+            let removed = JsonPath::parse(query).unwrap().delete(&mut json);
+            (removed, json)
+        };
+        // Array indices shift as elements go, whatever the order of the matches
+        assert_eq!(
+            delete("$[3, 0, 1]", json!([0, 1, 2, 3, 4])),
+            (3, json!([2, 4]))
+        );
+        assert_eq!(delete("$[?@ > 1]", json!([3, 1, 2, 0])), (2, json!([1, 0])));
+        assert_eq!(delete("$[0, 0]", json!([1, 2])), (1, json!([2])));
+        // Nested matches go with their ancestor
+        assert_eq!(
+            delete("$..a", json!({"a": {"a": 1}, "b": [{"a": 2}, {"c": 3}]})),
+            (2, json!({"b": [{}, {"c": 3}]}))
+        );
+        assert_eq!(
+            delete("$..*", json!({"a": [1, {"b": 2}], "c": 3})),
+            (2, json!({}))
+        );
+        assert_eq!(delete("$.missing", json!({"a": 1})), (0, json!({"a": 1})));
+        assert_eq!(delete("$", json!({"a": 1})), (0, json!({"a": 1})));
+    }
+
+    #[test]
+    fn test_set() {
+        let set = |query: &str, mut json: Value| {
+            let replaced = JsonPath::parse(query).unwrap().set(&mut json, &json!(0));
+            (replaced, json)
+        };
+        assert_eq!(set("$[1, 1, -1]", json!([1, 2, 3])), (2, json!([1, 0, 0])));
+        assert_eq!(
+            set("$..a", json!({"a": {"a": 1}, "b": [{"a": 2}, {"c": 3}]})),
+            (2, json!({"a": 0, "b": [{"a": 0}, {"c": 3}]}))
+        );
+        assert_eq!(set("$", json!({"a": 1})), (1, json!(0)));
+        assert_eq!(set("$.missing", json!({"a": 1})), (0, json!({"a": 1})));
+    }
+
     #[test]
     fn test_jsonpath_parse_invalid() {
         let result = JsonPath::parse("invalid");
@@ -0,0 +1,310 @@
+//! Static checks for filters and selectors that are valid but almost certainly mistakes
+
+use crate::ast::visit::{Visitor, walk, walk_segment};
+use crate::ast::{Expr, JsonPath, RenderedNode, Segment, Selector, rendered_spans, source_spans};
+use crate::eval::{compare_json_values, regex_compiles};
+use crate::function::FunctionType;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Category of a [`LintWarning`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum LintKind {
+    /// A comparison between a number-valued function such as `length()` and a non-number
+    /// literal, which can never be true (or, for `!=`, never false)
+    IncomparableTypes,
+    /// A comparison between two literals, whose result does not depend on the document
+    ConstantComparison,
+    /// `!` applied to a ValueType function such as `!length(@)`, which RFC 9535 requires to
+    /// be compared instead
+    NegatedValueFunction,
+    /// A selector that selects nodes already selected by an earlier selector in the same
+    /// segment, producing duplicate results
+    DuplicateSelector,
+    /// A `match()` or `search()` pattern literal that is not a valid regular expression, so
+    /// the call is always false
+    InvalidRegex,
+}
+
+/// A statically detected issue, see [`JsonPath::lint`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LintWarning {
+    pub kind: LintKind,
+    pub message: String,
+    /// Character offsets into the query's `Display` output (`path.to_string()`)
+    pub span: Range<usize>,
+    /// Character offsets into the query string the path was parsed from, `None` for a path
+    /// built with [`JsonPath::new`], rewritten by [`JsonPath::optimize`] or whose segments
+    /// were changed after parsing
+    pub source_span: Option<Range<usize>>,
+}
+
+/// Collect the warnings for `path`, ordered by position
+pub(crate) fn lint(path: &JsonPath) -> Vec<LintWarning> {
+    let spans = rendered_spans(path);
+    let sources = source_spans(path, &spans);
+    let source_span = |i: usize| sources.as_ref().map(|sources| sources[i].clone());
+    let mut warnings = Vec::new();
+
+    for (i, (node, span)) in spans.iter().enumerate() {
+        if let RenderedNode::Expr(expr) = node
+            && let Some((kind, message)) = check_expr(expr)
+        {
+            warnings.push(LintWarning {
+                kind,
+                message,
+                span: span.clone(),
+                source_span: source_span(i),
+            });
+        }
+    }
+
+    let selector_spans: HashMap<*const Selector, (Range<usize>, Option<Range<usize>>)> = spans
+        .into_iter()
+        .enumerate()
+        .filter_map(|(i, (node, span))| match node {
+            RenderedNode::Selector(selector) => {
+                Some((selector as *const Selector, (span, source_span(i))))
+            }
+            RenderedNode::Expr(_) => None,
+        })
+        .collect();
+    let mut duplicates = DuplicateSelectors {
+        spans: &selector_spans,
+        warnings: &mut warnings,
+    };
+    walk(path, &mut duplicates);
+
+    warnings.sort_by_key(|warning| warning.span.start);
+    warnings
+}
+
+/// Name of a built-in function returning a number (`length`, `count`)
+fn numeric_function(expr: &Expr) -> Option<&str> {
+    match expr {
+        Expr::FunctionCall { function, .. }
+            if function.eval.is_none() && matches!(function.name(), "length" | "count") =>
+        {
+            Some(function.name())
+        }
+        _ => None,
+    }
+}
+
+fn check_expr(expr: &Expr) -> Option<(LintKind, String)> {
+    match expr {
+        Expr::Comparison { left, op, right } => {
+            if let (Expr::Literal(l), Expr::Literal(r)) = (left.as_ref(), right.as_ref()) {
+                let result = compare_json_values(&l.cached_value, *op, &r.cached_value);
+                return Some((
+                    LintKind::ConstantComparison,
+                    format!("comparison of two literals is always {result}"),
+                ));
+            }
+            for (side, other) in [(left, right), (right, left)] {
+                if let Some(name) = numeric_function(side)
+                    && let Expr::Literal(literal) = other.as_ref()
+                    && !matches!(literal.cached_value, Value::Number(_))
+                {
+                    // A number never equals or orders against another type
+                    let result = compare_json_values(&Value::from(0), *op, &literal.cached_value);
+                    return Some((
+                        LintKind::IncomparableTypes,
+                        format!(
+                            "'{name}' returns a number, so comparing it with {} is always {result}",
+                            literal.literal
+                        ),
+                    ));
+                }
+            }
+            None
+        }
+        Expr::Not(inner) => match inner.as_ref() {
+            Expr::FunctionCall { function, .. } if function.result() == FunctionType::Value => {
+                Some((
+                    LintKind::NegatedValueFunction,
+                    format!(
+                        "'!' applied to '{}', whose result must be compared; this tests the \
+                         truthiness of the value",
+                        function.name()
+                    ),
+                ))
+            }
+            _ => None,
+        },
+        Expr::FunctionCall { function, args, .. }
+            if function.eval.is_none()
+                && matches!(function.name(), "match" | "search" | "imatch" | "isearch") =>
+        {
+            match args.get(1) {
+                Some(Expr::Literal(literal)) => match &literal.cached_value {
+                    Value::String(pattern) if !regex_compiles(pattern) => Some((
+                        LintKind::InvalidRegex,
+                        format!(
+                            "{} is not a valid regular expression, so '{}' is always false",
+                            literal.literal,
+                            function.name()
+                        ),
+                    )),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Flags selectors repeating an earlier selector of the same segment, or following `*`
+struct DuplicateSelectors<'a> {
+    spans: &'a HashMap<*const Selector, (Range<usize>, Option<Range<usize>>)>,
+    warnings: &'a mut Vec<LintWarning>,
+}
+
+impl Visitor for DuplicateSelectors<'_> {
+    fn visit_segment(&mut self, segment: &Segment) {
+        let selectors = segment.selectors();
+        for (i, selector) in selectors.iter().enumerate() {
+            let earlier = &selectors[..i];
+            let message = if earlier.contains(&Selector::Wildcard) {
+                format!("selector {selector} follows '*', which already selects every child")
+            } else if earlier.contains(selector) {
+                format!("selector {selector} repeats an earlier selector of the segment")
+            } else {
+                continue;
+            };
+            if let Some((span, source_span)) = self.spans.get(&(selector as *const Selector)) {
+                self.warnings.push(LintWarning {
+                    kind: LintKind::DuplicateSelector,
+                    message,
+                    span: span.clone(),
+                    source_span: source_span.clone(),
+                });
+            }
+        }
+        walk_segment(self, segment);
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+
+    fn lints(query: &str) -> Vec<(LintKind, String)> {
+        let path = JsonPath::parse(query).unwrap();
+        let rendered: Vec<char> = path.to_string().chars().collect();
+        path.lint()
+            .into_iter()
+            .map(|w| (w.kind, rendered[w.span].iter().collect()))
+            .collect()
+    }
+
+    #[test]
+    fn test_incomparable_types() {
+        assert_eq!(
+            lints("$[?length(@.name) > '3']"),
+            [(
+                LintKind::IncomparableTypes,
+                "length(@['name']) > '3'".to_string()
+            )]
+        );
+        assert_eq!(
+            lints("$[?true == count(@.*)]"),
+            [(
+                LintKind::IncomparableTypes,
+                "true == count(@[*])".to_string()
+            )]
+        );
+        assert!(lints("$[?length(@.name) > 3]").is_empty());
+        // Nothing is known about the type of a query
+        assert!(lints("$[?@.price == '10']").is_empty());
+    }
+
+    #[test]
+    fn test_messages() {
+        let path = JsonPath::parse("$[?length(@) != 'a' && 1 < 2]").unwrap();
+        let messages: Vec<String> = path.lint().into_iter().map(|w| w.message).collect();
+        assert_eq!(
+            messages,
+            [
+                "'length' returns a number, so comparing it with 'a' is always true",
+                "comparison of two literals is always true",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_constant_comparison() {
+        assert_eq!(
+            lints("$[?@.a && 'x' == \"x\"]"),
+            [(LintKind::ConstantComparison, "'x' == 'x'".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_negated_value_function() {
+        assert_eq!(
+            lints("$[?!length(@.a)]"),
+            [(
+                LintKind::NegatedValueFunction,
+                "!length(@['a'])".to_string()
+            )]
+        );
+        assert!(lints("$[?!match(@.a, 'x')]").is_empty());
+    }
+
+    #[test]
+    fn test_duplicate_selectors() {
+        assert_eq!(
+            lints("$['a', 'b', 'a'][*, 0]..[1, 1]"),
+            [
+                (LintKind::DuplicateSelector, "'a'".to_string()),
+                (LintKind::DuplicateSelector, "0".to_string()),
+                (LintKind::DuplicateSelector, "1".to_string()),
+            ]
+        );
+        assert_eq!(
+            lints("$[?@[0, 0]]"),
+            [(LintKind::DuplicateSelector, "0".to_string())]
+        );
+        assert!(lints("$['a', 0, 0:1, *]").is_empty());
+    }
+
+    #[test]
+    fn test_invalid_regex() {
+        assert_eq!(
+            lints("$[?match(@.a, 'a(') || search(@.b, '[x')]"),
+            [
+                (LintKind::InvalidRegex, "match(@['a'], 'a(')".to_string()),
+                (LintKind::InvalidRegex, "search(@['b'], '[x')".to_string()),
+            ]
+        );
+        assert!(lints("$[?match(@.a, 'a.*')]").is_empty());
+    }
+
+    #[test]
+    fn test_source_spans() {
+        let query = "$.a[?length(@.name)>\"3\"]..['b', \"b\"]";
+        let path = JsonPath::parse(query).unwrap();
+        let chars: Vec<char> = query.chars().collect();
+        let texts: Vec<String> = path
+            .lint()
+            .into_iter()
+            .map(|w| chars[w.source_span.unwrap()].iter().collect())
+            .collect();
+        assert_eq!(texts, ["length(@.name)>\"3\"", "\"b\""]);
+
+        let built = JsonPath::new(path.segments);
+        assert_eq!(built.lint().len(), 2);
+        assert!(built.lint().iter().all(|w| w.source_span.is_none()));
+    }
+
+    #[test]
+    fn test_clean_query() {
+        assert!(lints("$.store.book[?@.price < 10 && @.isbn].title").is_empty());
+    }
+}
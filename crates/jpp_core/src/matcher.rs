@@ -0,0 +1,290 @@
+//! Structural matching of a query against a single location, without the document
+
+use crate::ast::{JsonPath, Segment, Selector};
+use crate::eval::slice_selects;
+use crate::path::{NormalizedPath, PathElement};
+
+/// Longest array-length range scanned when a slice depends on the unknown array length.
+/// Larger ranges are reported as [`MatchAnswer::NeedsDocument`].
+const MAX_SLICE_LENGTH_SCAN: u64 = 4096;
+
+/// Answer of [`JsonPath::matches_path`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchAnswer {
+    /// The query selects the location in every document that contains it
+    Yes,
+    /// The query never selects the location
+    No,
+    /// The answer depends on the document (filters, negative indices, slices relative to the
+    /// array length)
+    NeedsDocument,
+}
+
+impl MatchAnswer {
+    fn and(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::No, _) | (_, Self::No) => Self::No,
+            (Self::Yes, Self::Yes) => Self::Yes,
+            _ => Self::NeedsDocument,
+        }
+    }
+
+    fn or(self, other: Self) -> Self {
+        match (self, other) {
+            (Self::Yes, _) | (_, Self::Yes) => Self::Yes,
+            (Self::No, Self::No) => Self::No,
+            _ => Self::NeedsDocument,
+        }
+    }
+}
+
+/// Decide whether `path` selects the node at `location`, assuming the node exists
+pub fn matches_path(path: &JsonPath, location: &NormalizedPath) -> MatchAnswer {
+    let segments = &path.segments;
+    let elements = location.elements();
+
+    // table[i][k]: answer for segments[i..] against elements[k..]
+    let mut table = vec![vec![MatchAnswer::No; elements.len() + 1]; segments.len() + 1];
+    table[segments.len()][elements.len()] = MatchAnswer::Yes;
+
+    for i in (0..segments.len()).rev() {
+        for k in (0..elements.len()).rev() {
+            table[i][k] = match &segments[i] {
+                Segment::Child(selectors) => {
+                    selectors_match(selectors, &elements[k]).and(table[i + 1][k + 1])
+                }
+                Segment::Descendant(selectors) => {
+                    // The segment skips zero or more ancestors, then selects elements[j]
+                    (k..elements.len()).fold(MatchAnswer::No, |answer, j| {
+                        answer.or(selectors_match(selectors, &elements[j]).and(table[i + 1][j + 1]))
+                    })
+                }
+            };
+        }
+    }
+
+    table[0][0]
+}
+
+fn selectors_match(selectors: &[Selector], element: &PathElement) -> MatchAnswer {
+    selectors.iter().fold(MatchAnswer::No, |answer, selector| {
+        answer.or(selector_matches(selector, element))
+    })
+}
+
+fn selector_matches(selector: &Selector, element: &PathElement) -> MatchAnswer {
+    match (selector, element) {
+        (Selector::Wildcard, _) => MatchAnswer::Yes,
+        // Filters apply to both object members and array elements
+        (Selector::Filter(_), _) => MatchAnswer::NeedsDocument,
+        (Selector::Name(name), PathElement::Name(key)) => answer(name == key),
+        (Selector::Index(i), PathElement::Index(index)) => {
+            if *i >= 0 {
+                answer(*i as u64 == *index as u64)
+            } else {
+                // Some array length makes any negative index land on `index`
+                MatchAnswer::NeedsDocument
+            }
+        }
+        (Selector::Slice { start, end, step }, PathElement::Index(index)) => {
+            slice_matches(*start, *end, *step, *index)
+        }
+        _ => MatchAnswer::No,
+    }
+}
+
+/// Decide whether a slice selects `index` for every, no, or only some array lengths
+/// greater than `index`
+fn slice_matches(
+    start: Option<i64>,
+    end: Option<i64>,
+    step: Option<i64>,
+    index: usize,
+) -> MatchAnswer {
+    let step_value = step.unwrap_or(1);
+    let min_len = index + 1;
+
+    // Non-negative bounds with a positive step do not depend on the array length
+    if step_value > 0 && start.unwrap_or(0) >= 0 && end.unwrap_or(0) >= 0 {
+        return answer(slice_selects(min_len, start, end, step, index));
+    }
+
+    // Past this many extra elements the normalized bounds no longer clamp, so the
+    // answer only cycles with the step
+    let span = [start, end, Some(step_value)]
+        .iter()
+        .flatten()
+        .fold(0u64, |acc, bound| acc.saturating_add(bound.unsigned_abs()))
+        .saturating_add(1);
+    if span > MAX_SLICE_LENGTH_SCAN {
+        return MatchAnswer::NeedsDocument;
+    }
+
+    let mut selected = false;
+    let mut skipped = false;
+    for extra in 0..=span as usize {
+        if slice_selects(min_len + extra, start, end, step, index) {
+            selected = true;
+        } else {
+            skipped = true;
+        }
+        if selected && skipped {
+            return MatchAnswer::NeedsDocument;
+        }
+    }
+    answer(selected)
+}
+
+fn answer(matched: bool) -> MatchAnswer {
+    if matched {
+        MatchAnswer::Yes
+    } else {
+        MatchAnswer::No
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn check(query: &str, location: &[PathElement]) -> MatchAnswer {
+        let path = JsonPath::parse(query).unwrap();
+        path.matches_path(&NormalizedPath::new(location.to_vec()))
+    }
+
+    fn name(s: &str) -> PathElement {
+        PathElement::Name(s.to_string())
+    }
+
+    fn index(i: usize) -> PathElement {
+        PathElement::Index(i)
+    }
+
+    #[test]
+    fn test_names_and_indices() {
+        let loc = [name("store"), name("book"), index(0)];
+        assert_eq!(check("$.store.book[0]", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$.store.book[1]", &loc), MatchAnswer::No);
+        assert_eq!(check("$.store.bicycle[0]", &loc), MatchAnswer::No);
+        assert_eq!(check("$.store.book", &loc), MatchAnswer::No);
+        assert_eq!(check("$.store.book[0].title", &loc), MatchAnswer::No);
+        assert_eq!(check("$", &[]), MatchAnswer::Yes);
+        assert_eq!(check("$.a", &[]), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_selector_kind_must_match_element_kind() {
+        assert_eq!(check("$['0']", &[index(0)]), MatchAnswer::No);
+        assert_eq!(check("$[0]", &[name("0")]), MatchAnswer::No);
+        assert_eq!(check("$[0:1]", &[name("0")]), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_wildcard_and_union() {
+        let loc = [name("a"), index(3)];
+        assert_eq!(check("$.*[*]", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$['x','a'][1,3]", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$['x','y'][*]", &loc), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_descendant() {
+        let loc = [name("store"), name("book"), index(0), name("price")];
+        assert_eq!(check("$..price", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$..book..price", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$..book[0]..price", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$..store.price", &loc), MatchAnswer::No);
+        assert_eq!(check("$..title", &loc), MatchAnswer::No);
+        assert_eq!(check("$..*", &loc), MatchAnswer::Yes);
+        assert_eq!(check("$..*", &[]), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_filters_need_document() {
+        let loc = [name("book"), index(1)];
+        assert_eq!(
+            check("$.book[?@.price < 10]", &loc),
+            MatchAnswer::NeedsDocument
+        );
+        assert_eq!(
+            check("$[?@.x].book[1]", &[name("book"), index(1)]),
+            MatchAnswer::No
+        );
+        // A definite mismatch elsewhere wins over an undecidable filter
+        assert_eq!(check("$.other[?@.price < 10]", &loc), MatchAnswer::No);
+        // A definite match in a union wins over an undecidable filter
+        assert_eq!(check("$.book[?@.x, 1]", &loc), MatchAnswer::Yes);
+    }
+
+    #[test]
+    fn test_negative_index_is_ambiguous() {
+        assert_eq!(check("$[-1]", &[index(0)]), MatchAnswer::NeedsDocument);
+        assert_eq!(check("$[-1]", &[index(7)]), MatchAnswer::NeedsDocument);
+        assert_eq!(check("$[-1]", &[name("a")]), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_slice_with_non_negative_bounds() {
+        assert_eq!(check("$[1:3]", &[index(1)]), MatchAnswer::Yes);
+        assert_eq!(check("$[1:3]", &[index(3)]), MatchAnswer::No);
+        assert_eq!(check("$[::2]", &[index(4)]), MatchAnswer::Yes);
+        assert_eq!(check("$[::2]", &[index(5)]), MatchAnswer::No);
+        assert_eq!(check("$[5:]", &[index(2)]), MatchAnswer::No);
+        assert_eq!(check("$[::0]", &[index(0)]), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_slice_relative_to_length() {
+        // Last element: depends on the length
+        assert_eq!(check("$[-1:]", &[index(2)]), MatchAnswer::NeedsDocument);
+        // All but the last element: depends on the length
+        assert_eq!(check("$[:-1]", &[index(0)]), MatchAnswer::NeedsDocument);
+        // Starts at index 5 at the earliest, so index 2 is never selected
+        assert_eq!(check("$[5:-1]", &[index(2)]), MatchAnswer::No);
+        // Reverse slices select every index for every length
+        assert_eq!(check("$[::-1]", &[index(3)]), MatchAnswer::Yes);
+        // Reverse slice with step 2 starts at the last element: depends on parity of the length
+        assert_eq!(check("$[::-2]", &[index(3)]), MatchAnswer::NeedsDocument);
+        // Reverse slice from a fixed start
+        assert_eq!(check("$[4::-2]", &[index(2)]), MatchAnswer::NeedsDocument);
+        assert_eq!(check("$[2::-2]", &[index(0)]), MatchAnswer::NeedsDocument);
+        assert_eq!(check("$[1::-1]", &[index(0)]), MatchAnswer::Yes);
+        assert_eq!(check("$[2::-1]", &[index(3)]), MatchAnswer::No);
+    }
+
+    #[test]
+    fn test_slice_answers_agree_with_evaluation() {
+        let slices = [
+            "$[-1:]",
+            "$[:-1]",
+            "$[-3::2]",
+            "$[1:-1:2]",
+            "$[::-2]",
+            "$[-2::-1]",
+            "$[:-4:-1]",
+            "$[3:1:-1]",
+            "$[-5:5]",
+        ];
+        for query in slices {
+            let path = JsonPath::parse(query).unwrap();
+            for idx in 0..6 {
+                let location = NormalizedPath::new(vec![index(idx)]);
+                let answer = path.matches_path(&location);
+                let mut seen = [false, false];
+                for len in idx + 1..idx + 12 {
+                    let doc = json!((0..len).collect::<Vec<_>>());
+                    let selected = path.query_located(&doc).iter().any(|(l, _)| *l == location);
+                    seen[usize::from(selected)] = true;
+                }
+                let expected = match seen {
+                    [true, true] => MatchAnswer::NeedsDocument,
+                    [false, true] => MatchAnswer::Yes,
+                    _ => MatchAnswer::No,
+                };
+                assert_eq!(answer, expected, "{query} at index {idx}");
+            }
+        }
+    }
+}
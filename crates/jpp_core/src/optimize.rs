@@ -0,0 +1,217 @@
+//! Semantics-preserving query rewrites, see [`JsonPath::optimize`]
+
+use crate::ast::visit::{VisitorMut, walk_expr_mut, walk_mut, walk_segment_mut, walk_selector_mut};
+use crate::ast::{CompOp, Expr, JsonPath, LogicalOp, Segment, Selector};
+use crate::eval::compare_json_values;
+
+/// Apply the rewrites documented on [`JsonPath::optimize`]
+pub(crate) fn optimize(mut path: JsonPath) -> JsonPath {
+    walk_mut(&mut path, &mut Optimizer);
+    // The rewritten nodes no longer match what the query wrote
+    path.source_spans.clear();
+    path
+}
+
+/// Always-true expression in a logical context: `@` always selects the current node
+fn always_true() -> Expr {
+    Expr::CurrentNode
+}
+
+/// Always-false expression in a logical context: `!@`
+fn always_false() -> Expr {
+    Expr::Not(Box::new(Expr::CurrentNode))
+}
+
+/// Truth value of `expr` in a logical context, if it does not depend on the document
+fn constant(expr: &Expr) -> Option<bool> {
+    match expr {
+        Expr::CurrentNode | Expr::RootNode => Some(true),
+        Expr::Not(inner) => constant(inner).map(|b| !b),
+        _ => None,
+    }
+}
+
+/// Result of comparing two operands, if it does not depend on the document
+fn constant_comparison(left: &Expr, op: CompOp, right: &Expr) -> Option<bool> {
+    match (left, right) {
+        (Expr::Literal(l), Expr::Literal(r)) => {
+            Some(compare_json_values(&l.cached_value, op, &r.cached_value))
+        }
+        // A singular query equals itself, whether it selects a value or Nothing
+        (Expr::CurrentNode | Expr::RootNode | Expr::Path { .. }, _) if left == right => match op {
+            CompOp::Eq | CompOp::Le | CompOp::Ge => Some(true),
+            CompOp::Ne | CompOp::Lt | CompOp::Gt => Some(false),
+        },
+        _ => None,
+    }
+}
+
+/// Slice equivalent to a single index selector: `[i:i+1]` with step 1, or `[-1:]`
+fn slice_as_index(start: Option<i64>, end: Option<i64>, step: Option<i64>) -> Option<i64> {
+    if !matches!(step, None | Some(1)) {
+        return None;
+    }
+    match (start?, end) {
+        (-1, None) => Some(-1),
+        // For -1 the end would be 0, which selects nothing
+        (i, Some(end)) if i != -1 && end == i.checked_add(1)? => Some(i),
+        _ => None,
+    }
+}
+
+struct Optimizer;
+
+impl VisitorMut for Optimizer {
+    fn visit_segment_mut(&mut self, segment: &mut Segment) {
+        walk_segment_mut(self, segment);
+        // An always-false filter contributes nothing to a union
+        let (Segment::Child(selectors) | Segment::Descendant(selectors)) = segment;
+        if selectors.len() > 1 {
+            let never =
+                |s: &Selector| matches!(s, Selector::Filter(e) if constant(e) == Some(false));
+            if !selectors.iter().all(never) {
+                selectors.retain(|s| !never(s));
+            }
+        }
+    }
+
+    fn visit_selector_mut(&mut self, selector: &mut Selector) {
+        walk_selector_mut(self, selector);
+        match selector {
+            Selector::Slice { start, end, step } => {
+                if let Some(index) = slice_as_index(*start, *end, *step) {
+                    *selector = Selector::Index(index);
+                }
+            }
+            // Filters and wildcards visit the same children in the same order
+            Selector::Filter(expr) if constant(expr) == Some(true) => {
+                *selector = Selector::Wildcard;
+            }
+            _ => {}
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        walk_expr_mut(self, expr);
+        let replacement = match expr {
+            Expr::Comparison { left, op, right } => constant_comparison(left, *op, right)
+                .map(|b| if b { always_true() } else { always_false() }),
+            Expr::Not(inner) => match inner.as_mut() {
+                // `!!x` has the truth value of `x`
+                Expr::Not(x) => Some(std::mem::replace(x.as_mut(), Expr::CurrentNode)),
+                _ => None,
+            },
+            Expr::Logical { left, op, right } => {
+                let (absorbing, identity) = match op {
+                    LogicalOp::And => (false, true),
+                    LogicalOp::Or => (true, false),
+                };
+                let (l, r) = (constant(left), constant(right));
+                if l == Some(absorbing) || r == Some(absorbing) {
+                    Some(if absorbing {
+                        always_true()
+                    } else {
+                        always_false()
+                    })
+                } else if l == Some(identity) {
+                    Some(std::mem::replace(right.as_mut(), Expr::CurrentNode))
+                } else if r == Some(identity) {
+                    Some(std::mem::replace(left.as_mut(), Expr::CurrentNode))
+                } else {
+                    None
+                }
+            }
+            _ => None,
+        };
+        if let Some(replacement) = replacement {
+            *expr = replacement;
+        }
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+
+    fn documents() -> Vec<Value> {
+        vec![
+            json!({
+                "store": {
+                    "book": [
+                        {"title": "A", "price": 8, "tags": ["x"]},
+                        {"title": "B", "price": 12},
+                        {"title": "C", "price": 9, "isbn": null}
+                    ],
+                    "bicycle": {"price": 19}
+                }
+            }),
+            json!([1, [2, 3], {"a": [4, {"a": 5}]}, "s", null, false, []]),
+            json!({"a": {"a": {"a": 1}}, "b": [[], {}]}),
+            json!([]),
+            json!(3),
+        ]
+    }
+
+    fn assert_same_results(query: &str, optimized: &str) {
+        let path = JsonPath::parse(query).unwrap();
+        let optimized_path = path.clone().optimize();
+        assert_eq!(optimized_path.to_string(), optimized, "query {query}");
+        // The optimized form must stay a valid query
+        assert_eq!(JsonPath::parse(optimized).unwrap(), optimized_path);
+        for document in documents() {
+            assert_eq!(
+                optimized_path.query_located(&document),
+                path.query_located(&document),
+                "query {query} on {document}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_slice_to_index() {
+        assert_same_results("$..[0:1]", "$..[0]");
+        assert_same_results("$..[2:3:1]", "$..[2]");
+        assert_same_results("$..[-1:]", "$..[-1]");
+        assert_same_results("$..[-3:-2]", "$..[-3]");
+        assert_same_results("$..[-1:0]", "$..[-1:0]");
+        assert_same_results("$..[0:1:-1]", "$..[0:1:-1]");
+        assert_same_results("$..[0:2]", "$..[0:2]");
+    }
+
+    #[test]
+    fn test_self_comparisons() {
+        assert_same_results("$..[?@.price == @.price]", "$..[*]");
+        assert_same_results("$..[?@ == @]", "$..[*]");
+        assert_same_results("$..[?@.a != @.a, 0]", "$..[0]");
+        assert_same_results("$..[?@.a < @.a]", "$..[?!@]");
+        assert_same_results("$..[?@.a <= @.a]", "$..[*]");
+        assert_same_results("$..[?@.a >= @.a]", "$..[*]");
+        assert_same_results("$..[?@.a == @.b]", "$..[?@['a'] == @['b']]");
+    }
+
+    #[test]
+    fn test_literal_comparisons() {
+        assert_same_results("$..[?1 < 2 && @.price]", "$..[?@['price']]");
+        assert_same_results("$..[?'a' == 1 || @.price]", "$..[?@['price']]");
+        assert_same_results("$..[?null == null || @.price]", "$..[*]");
+        assert_same_results("$[?(1 == 2 && @.a) || @[0]]", "$[?@[0]]");
+    }
+
+    #[test]
+    fn test_logical_folding() {
+        assert_same_results("$..[?!!@.price]", "$..[?@['price']]");
+        assert_same_results("$..[?!(@.a == @.a)]", "$..[?!@]");
+        assert_same_results("$..[?@.a || !(@.b != @.b)]", "$..[*]");
+        assert_same_results("$..[?@[?@ == @]]", "$..[?@[*]]");
+    }
+
+    #[test]
+    fn test_duplicate_semantics_preserved() {
+        // Neither duplicates nor double descendants are collapsed
+        assert_same_results("$..*..a", "$..[*]..['a']");
+        assert_same_results("$..[0, 0:1, ?@ == @]", "$..[0, 0, *]");
+        assert_same_results("$[?1 == 2, ?!@]", "$[?!@, ?!@]");
+    }
+}
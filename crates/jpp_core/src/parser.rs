@@ -1,81 +1,217 @@
 //! Parser for JSONPath queries
 
-use crate::ast::{CachedLiteral, CompOp, Expr, JsonPath, Literal, LogicalOp, Segment, Selector};
-use crate::lexer::{Lexer, LexerError, Token, TokenKind};
-
-/// RFC 9535: Functions that return LogicalType (cannot be used in comparisons)
-const LOGICAL_TYPE_FUNCTIONS: &[&str] = &["match", "search"];
-
-/// RFC 9535: Functions that return ComparisonType (must be compared, cannot be existence test)
-const COMPARISON_TYPE_FUNCTIONS: &[&str] = &["count", "length", "value"];
-
-/// Parser error
-#[derive(Debug, Clone, PartialEq)]
-pub struct ParseError {
-    pub message: String,
-    pub position: usize,
-}
-
-impl std::fmt::Display for ParseError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "at position {}, {}", self.position, self.message)
-    }
+use crate::ast::{
+    ArithOp, CachedLiteral, CompOp, CompiledRegex, Expr, JsonPath, Literal, LogicalOp, NodeKind,
+    Segment, Selector,
+};
+pub use crate::error::{ParseError, ParseErrorKind, ParseWarning};
+use crate::eval::compile_iregexp;
+use crate::function::{FunctionRegistry, FunctionType};
+use crate::iregexp;
+use crate::lexer::{Lexer, Token, TokenKind};
+use serde_json::Value;
+use std::cmp::Reverse;
+use std::mem;
+use std::ops::Range;
+
+/// Options for [`Parser::parse_with`]
+///
+/// The limits guard against hostile input and fail with [`ParseErrorKind::LimitExceeded`].
+/// `None` means unlimited. The defaults accept any realistic query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Accept leading and trailing whitespace, whitespace after `.` and `..`, and whitespace
+    /// between a function name and `(`, recording a [`ParseWarning`] for each instead of
+    /// failing. Off by default, which keeps parsing strictly RFC 9535 compliant.
+    pub lenient_whitespace: bool,
+    /// Fail with [`ParseErrorKind::InvalidRegex`] when a string literal pattern of `match()`
+    /// or `search()` doesn't compile. Off by default: RFC 9535 makes such calls false.
+    pub reject_invalid_regex: bool,
+    /// Accept only I-Regexp (RFC 9485) patterns in `match()` and `search()`: string literals
+    /// outside the grammar fail with [`ParseErrorKind::InvalidRegex`], and such patterns read
+    /// from the document make the call false. Off by default, which passes patterns to the
+    /// regex crate with only `.` adjusted.
+    pub strict_iregexp: bool,
+    /// Accept `+`, `-`, `*` and `/` between the operands of a comparison, binding tighter
+    /// than the comparison with `*` and `/` before `+` and `-`: `[?@.price * @.qty > 100]`.
+    /// Off by default, which keeps parsing strictly RFC 9535 compliant.
+    #[cfg(feature = "arithmetic")]
+    pub arithmetic: bool,
+    /// Maximum query length in characters
+    pub max_input_length: Option<usize>,
+    /// Maximum number of tokens
+    pub max_tokens: Option<usize>,
+    /// Maximum nesting of filter expressions, parenthesized groups, `!` and function arguments
+    pub max_nesting_depth: Option<usize>,
+    /// Maximum number of selectors in one bracketed segment
+    pub max_selectors_per_segment: Option<usize>,
 }
 
-impl From<LexerError> for ParseError {
-    fn from(e: LexerError) -> Self {
+impl Default for ParseOptions {
+    fn default() -> Self {
         Self {
-            message: e.message,
-            position: e.position,
+            lenient_whitespace: false,
+            reject_invalid_regex: false,
+            strict_iregexp: false,
+            #[cfg(feature = "arithmetic")]
+            arithmetic: false,
+            max_input_length: Some(1 << 20),
+            max_tokens: Some(1 << 17),
+            // Bounds parser recursion; far beyond hand-written queries
+            max_nesting_depth: Some(128),
+            max_selectors_per_segment: Some(1 << 14),
         }
     }
 }
 
 /// Parser for JSONPath queries
-pub struct Parser {
-    tokens: Vec<Token>,
+pub struct Parser<'r> {
+    tokens: Vec<Token<'r>>,
     index: usize,
+    functions: &'r FunctionRegistry,
+    options: ParseOptions,
+    warnings: Vec<ParseWarning>,
+    /// Current nesting depth, see [`ParseOptions::max_nesting_depth`]
+    depth: usize,
+    /// Source span of each selector and expression parsed so far, in the order they completed
+    nodes: Vec<(NodeKind, Range<usize>)>,
 }
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, index: 0 }
-    }
-
+impl Parser<'static> {
     /// Parse a JSONPath query string
     pub fn parse(input: &str) -> Result<JsonPath, ParseError> {
+        Self::parse_with_registry(input, FunctionRegistry::builtins())
+    }
+
+    /// Parse a JSONPath query string with `options`, returning any warnings
+    pub fn parse_with_options(
+        input: &str,
+        options: &ParseOptions,
+    ) -> Result<(JsonPath, Vec<ParseWarning>), ParseError> {
+        Self::parse_with(input, FunctionRegistry::builtins(), options)
+    }
+
+    /// Parse a standalone filter expression (the grammar after `?` in a filter selector)
+    pub fn parse_filter(input: &str) -> Result<Expr, ParseError> {
+        let tokens = Self::tokenize(input, &ParseOptions::default())?;
+        let mut parser = Parser::new(tokens);
+        let expr = parser.parse_filter_expression()?;
+        if let Some(kind) = parser.current_kind() {
+            return Err(parser.error_at_current(
+                ParseErrorKind::UnexpectedToken,
+                format!("unexpected token after filter expression: {kind:?}"),
+            ));
+        }
+        Ok(expr)
+    }
+}
+
+impl<'r> Parser<'r> {
+    /// Parser that knows the RFC 9535 functions
+    pub fn new(tokens: Vec<Token<'r>>) -> Self {
+        Self::with_registry(tokens, FunctionRegistry::builtins())
+    }
+
+    /// Parser that resolves function calls against `functions`
+    pub fn with_registry(tokens: Vec<Token<'r>>, functions: &'r FunctionRegistry) -> Self {
+        Self {
+            tokens,
+            index: 0,
+            functions,
+            options: ParseOptions::default(),
+            warnings: Vec::new(),
+            depth: 0,
+            nodes: Vec::new(),
+        }
+    }
+
+    /// Parse a JSONPath query string, resolving function calls against `functions`
+    pub fn parse_with_registry(
+        input: &str,
+        functions: &'r FunctionRegistry,
+    ) -> Result<JsonPath, ParseError> {
+        Self::parse_with(input, functions, &ParseOptions::default()).map(|(path, _)| path)
+    }
+
+    /// Parse a JSONPath query string with `functions` and `options`, returning any warnings
+    pub fn parse_with(
+        input: &str,
+        functions: &'r FunctionRegistry,
+        options: &ParseOptions,
+    ) -> Result<(JsonPath, Vec<ParseWarning>), ParseError> {
+        let mut warnings = Vec::new();
+
         // RFC 9535: JSONPath must start with '$', no leading whitespace allowed
-        if let Some(first_char) = input.chars().next()
-            && first_char.is_whitespace()
-        {
-            return Err(ParseError {
-                message: "leading whitespace is not allowed".to_string(),
-                position: 0,
+        let leading = input.chars().take_while(|c| c.is_whitespace()).count();
+        if leading > 0 {
+            if !options.lenient_whitespace {
+                return Err(ParseError::new(
+                    ParseErrorKind::InvalidWhitespace,
+                    "leading whitespace is not allowed",
+                    0..leading,
+                ));
+            }
+            warnings.push(ParseWarning {
+                message: "ignored leading whitespace".to_string(),
+                span: 0..leading,
             });
         }
 
         // RFC 9535: No trailing whitespace allowed
-        if let Some(last_char) = input.chars().last()
-            && last_char.is_whitespace()
-        {
-            return Err(ParseError {
-                message: "trailing whitespace is not allowed".to_string(),
-                position: input.len() - 1,
+        let trailing = input
+            .chars()
+            .rev()
+            .take_while(|c| c.is_whitespace())
+            .count();
+        // An all-whitespace input is reported once, as leading whitespace
+        if trailing > 0 && leading < input.chars().count() {
+            let len = input.chars().count();
+            if !options.lenient_whitespace {
+                return Err(ParseError::new(
+                    ParseErrorKind::InvalidWhitespace,
+                    "trailing whitespace is not allowed",
+                    len - trailing..len,
+                ));
+            }
+            warnings.push(ParseWarning {
+                message: "ignored trailing whitespace".to_string(),
+                span: len - trailing..len,
             });
         }
 
-        let tokens = Lexer::new(input).tokenize()?;
-        let mut parser = Self::new(tokens);
-        parser.parse_jsonpath()
+        let tokens = Self::tokenize(input, options)?;
+        // The tokens borrow `input`, so the parser lives no longer than this call
+        let mut parser = Parser::with_registry(tokens, functions);
+        parser.options = *options;
+        parser.warnings = warnings;
+        let path = parser.parse_jsonpath()?;
+        Ok((path, parser.warnings))
+    }
+
+    /// Tokenize `input`, enforcing the input length and token limits
+    fn tokenize<'i>(input: &'i str, options: &ParseOptions) -> Result<Vec<Token<'i>>, ParseError> {
+        // Byte length bounds the character count, so most inputs skip counting
+        if let Some(max) = options.max_input_length
+            && input.len() > max
+        {
+            let len = input.chars().count();
+            if len > max {
+                return Err(ParseError::new(
+                    ParseErrorKind::LimitExceeded,
+                    format!("query is longer than {max} characters"),
+                    max..len,
+                ));
+            }
+        }
+        Lexer::new(input).tokenize_limited(options.max_tokens)
     }
 
     fn parse_jsonpath(&mut self) -> Result<JsonPath, ParseError> {
         // Expect root identifier
         if self.current_kind() != Some(&TokenKind::Root) {
-            return Err(ParseError {
-                message: "JSONPath must start with '$'".to_string(),
-                position: 0,
-            });
+            return Err(
+                self.error_at_current(ParseErrorKind::MissingRoot, "JSONPath must start with '$'")
+            );
         }
         self.advance();
 
@@ -86,7 +222,13 @@ impl Parser {
             segments.push(segment);
         }
 
-        Ok(JsonPath::new(segments))
+        let mut path = JsonPath::new(segments);
+        // Enclosing nodes complete after the nodes inside them; ordering by start, outermost
+        // first, gives the pre-order of `rendered_spans`
+        self.nodes
+            .sort_by_key(|(_, span)| (span.start, Reverse(span.end)));
+        path.source_spans = mem::take(&mut self.nodes);
+        Ok(path)
     }
 
     fn parse_segment(&mut self) -> Result<Segment, ParseError> {
@@ -95,12 +237,7 @@ impl Parser {
                 let dot_pos = self.current_position();
                 self.advance();
                 // RFC 9535: No whitespace allowed after '..'
-                if self.current_position() != dot_pos + 2 {
-                    return Err(ParseError {
-                        message: "whitespace not allowed after '..'".to_string(),
-                        position: dot_pos + 2,
-                    });
-                }
+                self.check_whitespace(dot_pos + 2..self.current_position(), "after '..'")?;
                 let selectors = self.parse_selectors_after_dot()?;
                 Ok(Segment::Descendant(selectors))
             }
@@ -108,12 +245,7 @@ impl Parser {
                 let dot_pos = self.current_position();
                 self.advance();
                 // RFC 9535: No whitespace allowed after '.'
-                if self.current_position() != dot_pos + 1 {
-                    return Err(ParseError {
-                        message: "whitespace not allowed after '.'".to_string(),
-                        position: dot_pos + 1,
-                    });
-                }
+                self.check_whitespace(dot_pos + 1..self.current_position(), "after '.'")?;
                 let selectors = self.parse_selectors_after_dot()?;
                 Ok(Segment::Child(selectors))
             }
@@ -121,58 +253,60 @@ impl Parser {
                 let selectors = self.parse_bracket_selectors()?;
                 Ok(Segment::Child(selectors))
             }
-            Some(kind) => Err(ParseError {
-                message: format!("unexpected token: {kind:?}"),
-                position: self.current_position(),
-            }),
-            None => Err(ParseError {
-                message: "unexpected end of input".to_string(),
-                position: self.current_position(),
-            }),
+            Some(kind) => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedToken,
+                format!("unexpected token: {kind:?}"),
+            )),
+            None => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedEndOfInput,
+                "unexpected end of input",
+            )),
         }
     }
 
     fn parse_selectors_after_dot(&mut self) -> Result<Vec<Selector>, ParseError> {
         // RFC 9535: Keywords are valid as property names in dot notation
         if let Some(name) = self.current_kind().and_then(Self::keyword_to_property_name) {
-            self.advance();
-            return Ok(vec![Selector::Name(name.to_string())]);
+            return Ok(vec![self.parse_shorthand(Selector::Name(name.to_string()))]);
         }
         match self.current_kind().cloned() {
             Some(TokenKind::Ident(name)) => {
-                self.advance();
-                Ok(vec![Selector::Name(name)])
-            }
-            Some(TokenKind::Wildcard) => {
-                self.advance();
-                Ok(vec![Selector::Wildcard])
+                Ok(vec![self.parse_shorthand(Selector::Name(name.to_string()))])
             }
+            Some(TokenKind::Wildcard) => Ok(vec![self.parse_shorthand(Selector::Wildcard)]),
             Some(TokenKind::BracketOpen) => self.parse_bracket_selectors(),
-            Some(kind) => Err(ParseError {
-                message: format!("expected identifier or wildcard after '.', got {kind:?}"),
-                position: self.current_position(),
-            }),
-            None => Err(ParseError {
-                message: "expected identifier or wildcard after '.'".to_string(),
-                position: self.current_position(),
-            }),
+            Some(kind) => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedToken,
+                format!("expected identifier or wildcard after '.', got {kind:?}"),
+            )),
+            None => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedEndOfInput,
+                "expected identifier or wildcard after '.'",
+            )),
         }
     }
 
+    /// Consume the current token as `selector`, written in dot notation
+    fn parse_shorthand(&mut self, selector: Selector) -> Selector {
+        self.record(NodeKind::selector(&selector), self.current_span());
+        self.advance();
+        selector
+    }
+
     fn parse_bracket_selectors(&mut self) -> Result<Vec<Selector>, ParseError> {
         if self.current_kind() != Some(&TokenKind::BracketOpen) {
-            return Err(ParseError {
-                message: "expected '['".to_string(),
-                position: self.current_position(),
-            });
+            return Err(self.error_at_current(ParseErrorKind::UnexpectedToken, "expected '['"));
         }
+        let open = self.current_position();
         self.advance();
 
         let mut selectors = Vec::new();
 
         loop {
+            let start = self.current_position();
             let selector = self.parse_selector()?;
-            selectors.push(selector);
+            self.record(NodeKind::selector(&selector), self.span_from(start));
+            self.push_selector(&mut selectors, selector, start)?;
 
             match self.current_kind() {
                 Some(TokenKind::Comma) => {
@@ -184,16 +318,13 @@ impl Parser {
                     break;
                 }
                 Some(kind) => {
-                    return Err(ParseError {
-                        message: format!("expected ',' or ']', got {kind:?}"),
-                        position: self.current_position(),
-                    });
+                    return Err(self.error_at_current(
+                        ParseErrorKind::UnexpectedToken,
+                        format!("expected ',' or ']', got {kind:?}"),
+                    ));
                 }
                 None => {
-                    return Err(ParseError {
-                        message: "unclosed bracket".to_string(),
-                        position: self.current_position(),
-                    });
+                    return Err(self.unclosed(ParseErrorKind::UnclosedBracket, open));
                 }
             }
         }
@@ -209,42 +340,24 @@ impl Parser {
             }
             Some(TokenKind::String(s)) => {
                 self.advance();
-                Ok(Selector::Name(s))
+                Ok(Selector::Name(s.into_owned()))
+            }
+            Some(TokenKind::Integer(_) | TokenKind::Number(_, _) | TokenKind::Colon) => {
+                self.parse_index_or_slice()
             }
-            Some(TokenKind::Number(_, _)) | Some(TokenKind::Colon) => self.parse_index_or_slice(),
             Some(TokenKind::Question) => {
                 self.advance();
-                let expr = self.parse_expression()?;
-                // RFC 9535: Literal alone is not allowed as filter expression
-                if matches!(expr, Expr::Literal(_)) {
-                    return Err(ParseError {
-                        message: "filter expression cannot be a literal alone".to_string(),
-                        position: self.current_position(),
-                    });
-                }
-                // RFC 9535: ComparisonType functions (count, length, value) must be compared
-                // They cannot be used as standalone existence tests
-                if let Expr::FunctionCall { name, .. } = &expr
-                    && COMPARISON_TYPE_FUNCTIONS.contains(&name.as_str())
-                {
-                    return Err(ParseError {
-                        message: format!(
-                            "function '{}' returns a value that must be compared",
-                            name
-                        ),
-                        position: self.current_position(),
-                    });
-                }
+                let expr = self.parse_filter_expression()?;
                 Ok(Selector::Filter(Box::new(expr)))
             }
-            Some(kind) => Err(ParseError {
-                message: format!("unexpected token in selector: {kind:?}"),
-                position: self.current_position(),
-            }),
-            None => Err(ParseError {
-                message: "unexpected end of input in selector".to_string(),
-                position: self.current_position(),
-            }),
+            Some(kind) => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedToken,
+                format!("unexpected token in selector: {kind:?}"),
+            )),
+            None => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedEndOfInput,
+                "unexpected end of input in selector",
+            )),
         }
     }
 
@@ -256,10 +369,9 @@ impl Parser {
             // Just an index
             return match start {
                 Some(n) => Ok(Selector::Index(n)),
-                None => Err(ParseError {
-                    message: "expected number".to_string(),
-                    position: self.current_position(),
-                }),
+                None => {
+                    Err(self.error_at_current(ParseErrorKind::UnexpectedToken, "expected number"))
+                }
             };
         }
 
@@ -285,34 +397,45 @@ impl Parser {
     /// Try to parse a number for index/slice selector
     /// Returns Ok(Some(n)) if valid integer, Ok(None) if no number token, Err if invalid
     fn try_parse_index_number(&mut self) -> Result<Option<i64>, ParseError> {
-        if let Some(TokenKind::Number(n, has_decimal_or_exp)) = self.current_kind() {
+        if let Some(TokenKind::Integer(n)) = self.current_kind() {
+            // Check RFC 9535 exact integer range
+            let n = i64::try_from(*n)
+                .ok()
+                .filter(|n| (Self::RFC9535_MIN_INT..=Self::RFC9535_MAX_INT).contains(n))
+                .ok_or_else(|| {
+                    self.error_at_current(
+                        ParseErrorKind::IndexOutOfRange,
+                        "index out of range (must be between -(2^53-1) and 2^53-1)",
+                    )
+                })?;
+            self.advance();
+            Ok(Some(n))
+        } else if let Some(TokenKind::Number(n, has_decimal_or_exp)) = self.current_kind() {
             let n = *n;
             let has_decimal_or_exp = *has_decimal_or_exp;
-            let pos = self.current_position();
 
             // RFC 9535: -0 is not valid for index/slice selectors
             if n == 0.0 && n.is_sign_negative() {
-                return Err(ParseError {
-                    message: "-0 is not valid for index selector".to_string(),
-                    position: pos,
-                });
+                return Err(self.error_at_current(
+                    ParseErrorKind::InvalidIndex,
+                    "-0 is not valid for index selector",
+                ));
             }
 
             // RFC 9535: Index must be written as integer (no decimal point or exponent)
             if has_decimal_or_exp {
-                return Err(ParseError {
-                    message: "index must be an integer, not a decimal".to_string(),
-                    position: pos,
-                });
+                return Err(self.error_at_current(
+                    ParseErrorKind::InvalidIndex,
+                    "index must be an integer, not a decimal",
+                ));
             }
 
             // Check RFC 9535 exact integer range
             if n < Self::RFC9535_MIN_INT as f64 || n > Self::RFC9535_MAX_INT as f64 {
-                return Err(ParseError {
-                    message: "index out of range (must be between -(2^53-1) and 2^53-1)"
-                        .to_string(),
-                    position: pos,
-                });
+                return Err(self.error_at_current(
+                    ParseErrorKind::IndexOutOfRange,
+                    "index out of range (must be between -(2^53-1) and 2^53-1)",
+                ));
             }
 
             self.advance();
@@ -322,21 +445,120 @@ impl Parser {
         }
     }
 
-    fn current(&self) -> Option<&Token> {
+    fn current(&self) -> Option<&Token<'r>> {
         self.tokens.get(self.index)
     }
 
-    fn current_kind(&self) -> Option<&TokenKind> {
+    fn current_kind(&self) -> Option<&TokenKind<'r>> {
         self.current().map(|t| &t.kind)
     }
 
     fn current_position(&self) -> usize {
         self.current().map(|t| t.position).unwrap_or(
             // If past the end, use position after last token
-            self.tokens.last().map(|t| t.position + 1).unwrap_or(0),
+            self.tokens.last().map(|t| t.end).unwrap_or(0),
         )
     }
 
+    /// Span of the current token, or an empty span at the end of input
+    fn current_span(&self) -> Range<usize> {
+        match self.current() {
+            Some(token) => token.span(),
+            None => {
+                let end = self.current_position();
+                end..end
+            }
+        }
+    }
+
+    /// Span from `start` to the end of the last consumed token
+    fn span_from(&self, start: usize) -> Range<usize> {
+        let end = self
+            .index
+            .checked_sub(1)
+            .and_then(|i| self.tokens.get(i))
+            .map_or(start, |t| t.end);
+        start..end.max(start)
+    }
+
+    /// Record that the node of `kind` just parsed was written at `span`
+    fn record(&mut self, kind: NodeKind, span: Range<usize>) {
+        self.nodes.push((kind, span));
+    }
+
+    /// Add a selector starting at `start` to a bracketed segment, enforcing
+    /// [`ParseOptions::max_selectors_per_segment`]
+    fn push_selector(
+        &self,
+        selectors: &mut Vec<Selector>,
+        selector: Selector,
+        start: usize,
+    ) -> Result<(), ParseError> {
+        if let Some(max) = self.options.max_selectors_per_segment
+            && selectors.len() == max
+        {
+            return Err(ParseError::new(
+                ParseErrorKind::LimitExceeded,
+                format!("segment has more than {max} selectors"),
+                self.span_from(start),
+            ));
+        }
+        selectors.push(selector);
+        Ok(())
+    }
+
+    /// Enter a nested expression, enforcing [`ParseOptions::max_nesting_depth`]
+    /// Callers decrement `depth` when the nested parse succeeds.
+    fn enter_nested(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        if let Some(max) = self.options.max_nesting_depth
+            && self.depth > max
+        {
+            return Err(self.error_at_current(
+                ParseErrorKind::LimitExceeded,
+                format!("expression nesting exceeds {max} levels"),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reject whitespace in `span` (if any), or record a warning in lenient mode
+    fn check_whitespace(&mut self, span: Range<usize>, place: &str) -> Result<(), ParseError> {
+        if span.is_empty() {
+            return Ok(());
+        }
+        if !self.options.lenient_whitespace {
+            return Err(ParseError::new(
+                ParseErrorKind::InvalidWhitespace,
+                format!("whitespace not allowed {place}"),
+                span,
+            ));
+        }
+        self.warnings.push(ParseWarning {
+            message: format!("ignored whitespace {place}"),
+            span,
+        });
+        Ok(())
+    }
+
+    fn error_at_current(&self, kind: ParseErrorKind, message: impl Into<String>) -> ParseError {
+        ParseError::new(kind, message, self.current_span())
+    }
+
+    /// Error for a missing `]` or `)`, pointing back at the delimiter opened at `open`
+    fn unclosed(&self, kind: ParseErrorKind, open: usize) -> ParseError {
+        let (delimiter, close) = match kind {
+            ParseErrorKind::UnclosedParen => ("parenthesis", ')'),
+            _ => ("bracket", ']'),
+        };
+        let message = match self.current() {
+            Some(_) => format!("expected '{close}' to close {delimiter} opened at position {open}"),
+            None => format!("{delimiter} opened at position {open} was never closed"),
+        };
+        self.error_at_current(kind, message)
+            .with_opening(open..open + 1)
+    }
+
     fn advance(&mut self) {
         self.index += 1;
     }
@@ -354,29 +576,72 @@ impl Parser {
 
     // ========== Expression Parsing ==========
 
+    /// Parse the expression of a filter selector and check it is a valid LogicalType
+    fn parse_filter_expression(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current_position();
+        let expr = self.parse_expression()?;
+        if let Some((kind, message)) = Self::non_logical_filter(&expr) {
+            return Err(ParseError::new(kind, message, self.span_from(start)));
+        }
+        Ok(expr)
+    }
+
+    /// Why `expr` can't stand alone as a filter expression, if it can't. Kept out of
+    /// `parse_filter_expression`, which recurses once per nested filter.
+    fn non_logical_filter(expr: &Expr) -> Option<(ParseErrorKind, String)> {
+        match expr {
+            // RFC 9535: Literal alone is not allowed as filter expression
+            Expr::Literal(_) => Some((
+                ParseErrorKind::LiteralOnlyFilter,
+                "filter expression cannot be a literal alone".to_string(),
+            )),
+            Expr::Arithmetic { .. } => Some((
+                ParseErrorKind::ArithmeticType,
+                "arithmetic expression must be compared".to_string(),
+            )),
+            // RFC 9535: ValueType functions (count, length, value) must be compared
+            // They cannot be used as standalone existence tests
+            Expr::FunctionCall { function, .. } if function.result() == FunctionType::Value => {
+                Some((
+                    ParseErrorKind::FunctionResultType,
+                    format!(
+                        "function '{}' returns a value that must be compared",
+                        function.name()
+                    ),
+                ))
+            }
+            _ => None,
+        }
+    }
+
     /// Parse an expression (entry point) - handles logical OR (lowest precedence)
     fn parse_expression(&mut self) -> Result<Expr, ParseError> {
-        self.parse_or_expression()
+        self.enter_nested()?;
+        let expr = self.parse_or_expression()?;
+        self.depth -= 1;
+        Ok(expr)
     }
 
     /// Parse logical OR expression: expr || expr
     fn parse_or_expression(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current_position();
         let mut left = self.parse_and_expression()?;
 
         while self.current_kind() == Some(&TokenKind::Or) {
-            let op_pos = self.current_position();
+            let op_span = self.current_span();
             self.advance();
             let right = self.parse_and_expression()?;
 
             // RFC 9535: Logical operators require LogicalType operands (not bare literals)
-            Self::validate_logical_operand(&left, op_pos)?;
-            Self::validate_logical_operand(&right, op_pos)?;
+            Self::validate_logical_operand(&left, &op_span)?;
+            Self::validate_logical_operand(&right, &op_span)?;
 
             left = Expr::Logical {
                 left: Box::new(left),
                 op: LogicalOp::Or,
                 right: Box::new(right),
             };
+            self.record(NodeKind::expr(&left), self.span_from(start));
         }
 
         Ok(left)
@@ -384,22 +649,24 @@ impl Parser {
 
     /// Parse logical AND expression: expr && expr
     fn parse_and_expression(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current_position();
         let mut left = self.parse_comparison_expression()?;
 
         while self.current_kind() == Some(&TokenKind::And) {
-            let op_pos = self.current_position();
+            let op_span = self.current_span();
             self.advance();
             let right = self.parse_comparison_expression()?;
 
             // RFC 9535: Logical operators require LogicalType operands (not bare literals)
-            Self::validate_logical_operand(&left, op_pos)?;
-            Self::validate_logical_operand(&right, op_pos)?;
+            Self::validate_logical_operand(&left, &op_span)?;
+            Self::validate_logical_operand(&right, &op_span)?;
 
             left = Expr::Logical {
                 left: Box::new(left),
                 op: LogicalOp::And,
                 right: Box::new(right),
             };
+            self.record(NodeKind::expr(&left), self.span_from(start));
         }
 
         Ok(left)
@@ -407,12 +674,13 @@ impl Parser {
 
     /// Validate that an expression is a valid LogicalType operand for && or ||
     /// RFC 9535: Bare literals are not allowed as operands of logical operators
-    fn validate_logical_operand(expr: &Expr, pos: usize) -> Result<(), ParseError> {
+    fn validate_logical_operand(expr: &Expr, op_span: &Range<usize>) -> Result<(), ParseError> {
         if matches!(expr, Expr::Literal(_)) {
-            return Err(ParseError {
-                message: "literal cannot be used as operand of logical operator".to_string(),
-                position: pos,
-            });
+            return Err(ParseError::new(
+                ParseErrorKind::LiteralLogicalOperand,
+                "literal cannot be used as operand of logical operator",
+                op_span.clone(),
+            ));
         }
         Ok(())
     }
@@ -431,24 +699,28 @@ impl Parser {
             Expr::CurrentNode | Expr::RootNode => true,
             Expr::Literal(_) => true,
             Expr::FunctionCall { .. } => true,
+            Expr::Arithmetic { .. } => true,
             _ => false,
         }
     }
 
-    /// Check if an expression is a LogicalType function (match, search)
-    /// Returns the function name if it is, None otherwise
-    fn get_logical_type_function_name(expr: &Expr) -> Option<&str> {
-        if let Expr::FunctionCall { name, .. } = expr
-            && LOGICAL_TYPE_FUNCTIONS.contains(&name.as_str())
+    /// Check if an expression is a function whose result cannot be compared
+    /// (LogicalType such as match and search, or NodesType)
+    /// Returns the function name and result type if it is, None otherwise
+    fn get_non_value_function(expr: &Expr) -> Option<(&str, FunctionType)> {
+        if let Expr::FunctionCall { function, .. } = expr
+            && function.result() != FunctionType::Value
         {
-            return Some(name.as_str());
+            return Some((function.name(), function.result()));
         }
         None
     }
 
     /// Parse comparison expression: expr op expr
     fn parse_comparison_expression(&mut self) -> Result<Expr, ParseError> {
-        let left = self.parse_unary_expression()?;
+        let left_start = self.current_position();
+        let left = self.parse_comparison_operand()?;
+        let left_span = self.span_from(left_start);
 
         let op = match self.current_kind() {
             Some(TokenKind::Equal) => Some(CompOp::Eq),
@@ -461,53 +733,173 @@ impl Parser {
         };
 
         if let Some(op) = op {
-            let op_pos = self.current_position();
             self.advance(); // consume operator
-            let right = self.parse_unary_expression()?;
-
-            // RFC 9535: Both sides of comparison must be singular queries
-            if !Self::is_singular_query(&left) {
-                return Err(ParseError {
-                    message: "non-singular query not allowed in comparison".to_string(),
-                    position: op_pos,
-                });
-            }
-            if !Self::is_singular_query(&right) {
-                return Err(ParseError {
-                    message: "non-singular query not allowed in comparison".to_string(),
-                    position: op_pos,
-                });
+            let right_start = self.current_position();
+            let right = self.parse_comparison_operand()?;
+            let right_span = self.span_from(right_start);
+
+            for (expr, span) in [(&left, &left_span), (&right, &right_span)] {
+                // RFC 9535: Both sides of comparison must be singular queries
+                if !Self::is_singular_query(expr) {
+                    return Err(ParseError::new(
+                        ParseErrorKind::NonSingularComparison,
+                        "non-singular query not allowed in comparison",
+                        span.clone(),
+                    ));
+                }
             }
 
             // RFC 9535: LogicalType functions (match, search) cannot be compared
-            for expr in [&left, &right] {
-                if let Some(name) = Self::get_logical_type_function_name(expr) {
-                    return Err(ParseError {
-                        message: format!(
-                            "function '{}' returns LogicalType and cannot be compared",
-                            name
+            for (expr, span) in [(&left, &left_span), (&right, &right_span)] {
+                if let Some((name, result)) = Self::get_non_value_function(expr) {
+                    return Err(ParseError::new(
+                        ParseErrorKind::FunctionResultType,
+                        format!(
+                            "function '{}' returns {} and cannot be compared",
+                            name, result
                         ),
-                        position: op_pos,
-                    });
+                        span.clone(),
+                    ));
                 }
             }
 
-            Ok(Expr::Comparison {
+            let comparison = Expr::Comparison {
                 left: Box::new(left),
                 op,
                 right: Box::new(right),
-            })
+            };
+            self.record(NodeKind::expr(&comparison), self.span_from(left_start));
+            Ok(comparison)
         } else {
             Ok(left)
         }
     }
 
+    /// Parse an operand of a comparison, which is an arithmetic expression when
+    /// [`ParseOptions::arithmetic`] is on
+    fn parse_comparison_operand(&mut self) -> Result<Expr, ParseError> {
+        if self.arithmetic_enabled() {
+            self.parse_arithmetic_expression()
+        } else {
+            self.parse_unary_expression()
+        }
+    }
+
+    /// Whether [`ParseOptions::arithmetic`] is on
+    fn arithmetic_enabled(&self) -> bool {
+        #[cfg(feature = "arithmetic")]
+        return self.options.arithmetic;
+        #[cfg(not(feature = "arithmetic"))]
+        false
+    }
+
+    /// Parse arithmetic expression: operands joined by `+ - * /`, with `*` and `/` binding
+    /// tighter and each level left-associative. Parsed in a loop rather than a function per
+    /// level, so enabling arithmetic adds a single stack frame per nesting level.
+    fn parse_arithmetic_expression(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current_position();
+        // Completed `+`/`-` chain and the operator applied to the term being built
+        let mut sum: Option<(Expr, ArithOp)> = None;
+        let mut term_start = start;
+        let mut term = self.parse_arithmetic_operand()?;
+        loop {
+            let term_end = self.span_from(term_start).end;
+            let Some(op) = self.take_arithmetic_operator() else {
+                break;
+            };
+            let operand_start = self.current_position();
+            let operand = self.parse_arithmetic_operand()?;
+            if matches!(op, ArithOp::Mul | ArithOp::Div) {
+                term = Expr::Arithmetic {
+                    left: Box::new(term),
+                    op,
+                    right: Box::new(operand),
+                };
+                self.record(NodeKind::expr(&term), self.span_from(term_start));
+            } else {
+                let left = match sum.take() {
+                    Some((left, left_op)) => {
+                        let left = Expr::Arithmetic {
+                            left: Box::new(left),
+                            op: left_op,
+                            right: Box::new(term),
+                        };
+                        self.record(NodeKind::expr(&left), start..term_end);
+                        left
+                    }
+                    None => term,
+                };
+                sum = Some((left, op));
+                term = operand;
+                term_start = operand_start;
+            }
+        }
+        Ok(match sum {
+            Some((left, op)) => {
+                let sum = Expr::Arithmetic {
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(term),
+                };
+                self.record(NodeKind::expr(&sum), self.span_from(start));
+                sum
+            }
+            None => term,
+        })
+    }
+
+    /// Consume the arithmetic operator at the current token, if any. The lexer reads `-1` as
+    /// a negative number, so after an operand such a literal is turned into `-` followed by
+    /// its magnitude, which is left as the current token.
+    fn take_arithmetic_operator(&mut self) -> Option<ArithOp> {
+        let token = self.tokens.get_mut(self.index)?;
+        let op = match &mut token.kind {
+            TokenKind::Plus => ArithOp::Add,
+            TokenKind::Minus => ArithOp::Sub,
+            TokenKind::Wildcard => ArithOp::Mul,
+            TokenKind::Slash => ArithOp::Div,
+            TokenKind::Integer(n) if *n < 0 => {
+                *n = -*n;
+                token.position += 1;
+                token.byte_position += 1;
+                return Some(ArithOp::Sub);
+            }
+            TokenKind::Number(n, _) if n.is_sign_negative() => {
+                *n = -*n;
+                token.position += 1;
+                token.byte_position += 1;
+                return Some(ArithOp::Sub);
+            }
+            _ => return None,
+        };
+        self.advance();
+        Some(op)
+    }
+
+    /// Parse an operand of `+ - * /`, which must be a singular value
+    fn parse_arithmetic_operand(&mut self) -> Result<Expr, ParseError> {
+        let start = self.current_position();
+        let operand = self.parse_unary_expression()?;
+        if !Self::is_value_type(&operand) {
+            return Err(ParseError::new(
+                ParseErrorKind::ArithmeticType,
+                "arithmetic operand must be a singular query, a literal or a value function",
+                self.span_from(start),
+            ));
+        }
+        Ok(operand)
+    }
+
     /// Parse unary expression: !expr or atom
     fn parse_unary_expression(&mut self) -> Result<Expr, ParseError> {
         if self.current_kind() == Some(&TokenKind::Not) {
+            let start = self.current_position();
             self.advance();
-            let expr = self.parse_unary_expression()?;
-            Ok(Expr::Not(Box::new(expr)))
+            self.enter_nested()?;
+            let expr = Expr::Not(Box::new(self.parse_unary_expression()?));
+            self.depth -= 1;
+            self.record(NodeKind::expr(&expr), self.span_from(start));
+            Ok(expr)
         } else {
             self.parse_atom()
         }
@@ -516,81 +908,80 @@ impl Parser {
     /// Parse atom: @, $, literal, function call, or parenthesized expression
     fn parse_atom(&mut self) -> Result<Expr, ParseError> {
         match self.current_kind().cloned() {
-            Some(TokenKind::At) => {
-                self.advance();
-                self.parse_path_or_node(Expr::CurrentNode)
-            }
-            Some(TokenKind::Root) => {
-                self.advance();
-                self.parse_path_or_node(Expr::RootNode)
-            }
-            Some(TokenKind::True) => {
-                self.advance();
-                Ok(Expr::Literal(CachedLiteral::new(Literal::Bool(true))))
-            }
-            Some(TokenKind::False) => {
-                self.advance();
-                Ok(Expr::Literal(CachedLiteral::new(Literal::Bool(false))))
-            }
-            Some(TokenKind::Null) => {
-                self.advance();
-                Ok(Expr::Literal(CachedLiteral::new(Literal::Null)))
-            }
-            Some(TokenKind::Number(n, _)) => {
-                self.advance();
-                Ok(Expr::Literal(CachedLiteral::new(Literal::Number(n))))
-            }
-            Some(TokenKind::String(s)) => {
+            Some(kind @ (TokenKind::At | TokenKind::Root)) => {
+                let node = match kind {
+                    TokenKind::At => Expr::CurrentNode,
+                    _ => Expr::RootNode,
+                };
+                let mark = self.nodes.len();
+                let position = self.current_position();
+                self.record(NodeKind::expr(&node), self.current_span());
                 self.advance();
-                Ok(Expr::Literal(CachedLiteral::new(Literal::String(s))))
+                self.parse_path_or_node(node, position, mark)
             }
+            Some(TokenKind::True) => Ok(self.parse_literal(Literal::Bool(true))),
+            Some(TokenKind::False) => Ok(self.parse_literal(Literal::Bool(false))),
+            Some(TokenKind::Null) => Ok(self.parse_literal(Literal::Null)),
+            Some(TokenKind::Integer(n)) => Ok(self.parse_literal(Literal::Integer(n))),
+            Some(TokenKind::Number(n, _)) => Ok(self.parse_literal(Literal::Number(n))),
+            Some(TokenKind::String(s)) => Ok(self.parse_literal(Literal::String(s.into_owned()))),
             Some(TokenKind::Ident(name)) => {
-                let ident_pos = self.current_position();
-                let ident_len = name.len();
+                let ident_span = self.current_span();
                 self.advance();
                 // Check if this is a function call
                 if self.current_kind() == Some(&TokenKind::ParenOpen) {
                     // RFC 9535: No whitespace allowed between function name and '('
-                    if self.current_position() != ident_pos + ident_len {
-                        return Err(ParseError {
-                            message: "whitespace not allowed between function name and '('"
-                                .to_string(),
-                            position: ident_pos + ident_len,
-                        });
-                    }
-                    self.parse_function_call(name)
+                    self.check_whitespace(
+                        ident_span.end..self.current_position(),
+                        "between function name and '('",
+                    )?;
+                    self.parse_function_call_or_query(name, ident_span)
                 } else {
-                    Err(ParseError {
-                        message: format!("unexpected identifier '{name}' in expression"),
-                        position: self.current_position(),
-                    })
+                    Err(ParseError::new(
+                        ParseErrorKind::UnexpectedToken,
+                        format!("unexpected identifier '{name}' in expression"),
+                        ident_span,
+                    ))
                 }
             }
             Some(TokenKind::ParenOpen) => {
+                let open = self.current_position();
                 self.advance();
                 let expr = self.parse_expression()?;
                 if self.current_kind() != Some(&TokenKind::ParenClose) {
-                    return Err(ParseError {
-                        message: "expected ')' after expression".to_string(),
-                        position: self.current_position(),
-                    });
+                    return Err(self.unclosed(ParseErrorKind::UnclosedParen, open));
                 }
                 self.advance();
                 Ok(expr)
             }
-            Some(kind) => Err(ParseError {
-                message: format!("unexpected token in expression: {kind:?}"),
-                position: self.current_position(),
-            }),
-            None => Err(ParseError {
-                message: "unexpected end of input in expression".to_string(),
-                position: self.current_position(),
-            }),
+            Some(kind) => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedToken,
+                format!("unexpected token in expression: {kind:?}"),
+            )),
+            None => Err(self.error_at_current(
+                ParseErrorKind::UnexpectedEndOfInput,
+                "unexpected end of input in expression",
+            )),
         }
     }
 
-    /// Parse path segments after @ or $, or return the node itself
-    fn parse_path_or_node(&mut self, start: Expr) -> Result<Expr, ParseError> {
+    /// Consume the current token as `literal`
+    fn parse_literal(&mut self, literal: Literal) -> Expr {
+        let expr = Expr::Literal(CachedLiteral::new(literal));
+        self.record(NodeKind::expr(&expr), self.current_span());
+        self.advance();
+        expr
+    }
+
+    /// Parse path segments after @ or $, or return the node itself. `start` was written from
+    /// `position` and its nodes were recorded from `mark` on, which a path replaces with its
+    /// own as `Display` renders only its segments.
+    fn parse_path_or_node(
+        &mut self,
+        start: Expr,
+        position: usize,
+        mark: usize,
+    ) -> Result<Expr, ParseError> {
         // Check if followed by path segments
         if !matches!(
             self.current_kind(),
@@ -599,211 +990,146 @@ impl Parser {
             return Ok(start);
         }
 
+        self.nodes.truncate(mark);
         let mut segments = Vec::new();
 
         while matches!(
             self.current_kind(),
             Some(TokenKind::Dot) | Some(TokenKind::DotDot) | Some(TokenKind::BracketOpen)
         ) {
-            let segment = self.parse_filter_path_segment()?;
+            // Same rules as top-level segments, including nested filters
+            let segment = self.parse_segment()?;
             segments.push(segment);
         }
 
-        Ok(Expr::Path {
+        let path = Expr::Path {
             start: Box::new(start),
             segments,
-        })
-    }
-
-    /// Parse a path segment within a filter expression (simpler than full segment parsing)
-    fn parse_filter_path_segment(&mut self) -> Result<Segment, ParseError> {
-        match self.current_kind() {
-            Some(TokenKind::DotDot) => {
-                let dot_pos = self.current_position();
-                self.advance();
-                // RFC 9535: No whitespace allowed after '..'
-                if self.current_position() != dot_pos + 2 {
-                    return Err(ParseError {
-                        message: "whitespace not allowed after '..'".to_string(),
-                        position: dot_pos + 2,
-                    });
-                }
-                let selectors = self.parse_filter_selectors_after_dot()?;
-                Ok(Segment::Descendant(selectors))
-            }
-            Some(TokenKind::Dot) => {
-                let dot_pos = self.current_position();
-                self.advance();
-                // RFC 9535: No whitespace allowed after '.'
-                if self.current_position() != dot_pos + 1 {
-                    return Err(ParseError {
-                        message: "whitespace not allowed after '.'".to_string(),
-                        position: dot_pos + 1,
-                    });
-                }
-                let selectors = self.parse_filter_selectors_after_dot()?;
-                Ok(Segment::Child(selectors))
-            }
-            Some(TokenKind::BracketOpen) => {
-                self.advance();
-                let mut selectors = Vec::new();
-                loop {
-                    let selector = self.parse_filter_bracket_selector()?;
-                    selectors.push(selector);
-                    match self.current_kind() {
-                        Some(TokenKind::Comma) => {
-                            self.advance();
-                            continue;
-                        }
-                        Some(TokenKind::BracketClose) => {
-                            self.advance();
-                            break;
-                        }
-                        _ => {
-                            return Err(ParseError {
-                                message: "expected ',' or ']'".to_string(),
-                                position: self.current_position(),
-                            });
-                        }
-                    }
-                }
-                Ok(Segment::Child(selectors))
-            }
-            _ => Err(ParseError {
-                message: "expected path segment".to_string(),
-                position: self.current_position(),
-            }),
-        }
-    }
-
-    /// Parse selectors after '.' or '..' in filter path
-    fn parse_filter_selectors_after_dot(&mut self) -> Result<Vec<Selector>, ParseError> {
-        // RFC 9535: Keywords are valid as property names in dot notation
-        if let Some(name) = self.current_kind().and_then(Self::keyword_to_property_name) {
-            self.advance();
-            return Ok(vec![Selector::Name(name.to_string())]);
-        }
-        match self.current_kind().cloned() {
-            Some(TokenKind::Ident(name)) => {
-                self.advance();
-                Ok(vec![Selector::Name(name)])
-            }
-            Some(TokenKind::Wildcard) => {
-                self.advance();
-                Ok(vec![Selector::Wildcard])
-            }
-            // RFC 9535: Bracket selectors can follow '.' or '..' (e.g., $..['key'])
-            Some(TokenKind::BracketOpen) => {
-                self.advance();
-                let mut selectors = Vec::new();
-                loop {
-                    let selector = self.parse_filter_bracket_selector()?;
-                    selectors.push(selector);
-                    match self.current_kind() {
-                        Some(TokenKind::Comma) => {
-                            self.advance();
-                            continue;
-                        }
-                        Some(TokenKind::BracketClose) => {
-                            self.advance();
-                            break;
-                        }
-                        _ => {
-                            return Err(ParseError {
-                                message: "expected ',' or ']'".to_string(),
-                                position: self.current_position(),
-                            });
-                        }
-                    }
-                }
-                Ok(selectors)
-            }
-            Some(kind) => Err(ParseError {
-                message: format!("expected identifier or wildcard after '.', got {kind:?}"),
-                position: self.current_position(),
-            }),
-            None => Err(ParseError {
-                message: "expected identifier or wildcard after '.'".to_string(),
-                position: self.current_position(),
-            }),
-        }
+        };
+        self.record(NodeKind::expr(&path), self.span_from(position));
+        Ok(path)
     }
 
-    /// Parse a bracket selector within filter path (name, index, wildcard, slice, or nested filter)
-    fn parse_filter_bracket_selector(&mut self) -> Result<Selector, ParseError> {
-        match self.current_kind().cloned() {
-            Some(TokenKind::Wildcard) => {
-                self.advance();
-                Ok(Selector::Wildcard)
-            }
-            Some(TokenKind::String(s)) => {
-                self.advance();
-                Ok(Selector::Name(s))
-            }
-            Some(TokenKind::Number(_, _)) | Some(TokenKind::Colon) => self.parse_index_or_slice(),
-            Some(TokenKind::Question) => {
-                self.advance();
-                let expr = self.parse_expression()?;
-                // RFC 9535: Literal alone is not allowed as filter expression
-                if matches!(expr, Expr::Literal(_)) {
-                    return Err(ParseError {
-                        message: "filter expression cannot be a literal alone".to_string(),
-                        position: self.current_position(),
-                    });
-                }
-                Ok(Selector::Filter(Box::new(expr)))
+    /// Parse a function call, followed by path segments when the call is the extension
+    /// `parent()`, which is a node and so can start a query like `@`
+    fn parse_function_call_or_query(
+        &mut self,
+        name: &str,
+        name_span: Range<usize>,
+    ) -> Result<Expr, ParseError> {
+        let mark = self.nodes.len();
+        let position = name_span.start;
+        let call = self.parse_function_call(name, name_span)?;
+        match &call {
+            Expr::FunctionCall { function, .. }
+                if function.eval.is_none() && function.name() == "parent" =>
+            {
+                self.parse_path_or_node(call, position, mark)
             }
-            Some(kind) => Err(ParseError {
-                message: format!("unexpected token in bracket selector: {kind:?}"),
-                position: self.current_position(),
-            }),
-            None => Err(ParseError {
-                message: "unexpected end of input in bracket selector".to_string(),
-                position: self.current_position(),
-            }),
+            _ => Ok(call),
         }
     }
 
     /// Parse a function call: name(args...)
-    fn parse_function_call(&mut self, name: String) -> Result<Expr, ParseError> {
-        let func_pos = self.current_position();
-
+    fn parse_function_call(
+        &mut self,
+        name: &str,
+        name_span: Range<usize>,
+    ) -> Result<Expr, ParseError> {
         if self.current_kind() != Some(&TokenKind::ParenOpen) {
-            return Err(ParseError {
-                message: "expected '(' after function name".to_string(),
-                position: self.current_position(),
-            });
+            return Err(self.error_at_current(
+                ParseErrorKind::UnexpectedToken,
+                "expected '(' after function name",
+            ));
         }
+        let open = self.current_position();
         self.advance();
 
         let mut args = Vec::new();
+        let mut arg_spans = Vec::new();
 
         if self.current_kind() != Some(&TokenKind::ParenClose) {
-            args.push(self.parse_expression()?);
-
-            while self.current_kind() == Some(&TokenKind::Comma) {
-                self.advance();
+            loop {
+                let start = self.current_position();
                 args.push(self.parse_expression()?);
+                arg_spans.push(self.span_from(start));
+                if self.current_kind() != Some(&TokenKind::Comma) {
+                    break;
+                }
+                self.advance();
             }
         }
 
         if self.current_kind() != Some(&TokenKind::ParenClose) {
-            return Err(ParseError {
-                message: "expected ')' after function arguments".to_string(),
-                position: self.current_position(),
-            });
+            return Err(self.unclosed(ParseErrorKind::UnclosedParen, open));
         }
         self.advance();
 
         // Validate function parameters per RFC 9535
-        self.validate_function_params(&name, &args, func_pos)?;
+        let call = FunctionCallSpans {
+            name: name_span.clone(),
+            call: self.span_from(name_span.start),
+            args: arg_spans,
+        };
+        let Some(function) = self.functions.get(name) else {
+            // RFC 9535: Only registered functions are allowed
+            return Err(ParseError::new(
+                ParseErrorKind::UnknownFunction,
+                format!("unknown function '{}'", name),
+                call.name,
+            ));
+        };
+        Self::validate_function_params(name, function.params(), &args, &call)?;
+
+        let is_regex =
+            function.eval.is_none() && matches!(name, "match" | "search" | "imatch" | "isearch");
+        let strict_regex = is_regex && self.options.strict_iregexp;
+        let regex = match args.get(1) {
+            Some(Expr::Literal(literal)) if is_regex => match &literal.cached_value {
+                Value::String(pattern) => {
+                    let invalid = |message| {
+                        ParseError::new(ParseErrorKind::InvalidRegex, message, call.args[1].clone())
+                    };
+                    if strict_regex && let Err(reason) = iregexp::translate(pattern) {
+                        return Err(invalid(format!(
+                            "{} is not a valid I-Regexp: {reason}",
+                            literal.literal
+                        )));
+                    }
+                    let full_match = matches!(name, "match" | "imatch");
+                    let case_insensitive = name.starts_with('i');
+                    let regex =
+                        compile_iregexp(pattern, full_match, strict_regex, case_insensitive);
+                    if regex.is_none() && self.options.reject_invalid_regex {
+                        return Err(invalid(format!(
+                            "{} is not a valid regular expression",
+                            literal.literal
+                        )));
+                    }
+                    regex.map(CompiledRegex)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
 
-        Ok(Expr::FunctionCall { name, args })
+        let expr = Expr::FunctionCall {
+            function: function.clone(),
+            args,
+            regex,
+            strict_regex,
+        };
+        self.record(NodeKind::expr(&expr), call.call);
+        Ok(expr)
     }
 
     /// Check if an expression is a query (NodesType) - @ or $ based path
     fn is_nodes_type(expr: &Expr) -> bool {
-        matches!(expr, Expr::CurrentNode | Expr::RootNode | Expr::Path { .. })
+        match expr {
+            Expr::CurrentNode | Expr::RootNode | Expr::Path { .. } => true,
+            Expr::FunctionCall { function, .. } => function.result() == FunctionType::Nodes,
+            _ => false,
+        }
     }
 
     /// Check if an expression is ValueType (singular query or literal)
@@ -822,148 +1148,93 @@ impl Parser {
                     Segment::Descendant(_) => false,
                 })
             }
-            // FunctionCalls that return ValueType are allowed (ComparisonType functions)
-            Expr::FunctionCall { name, .. } => COMPARISON_TYPE_FUNCTIONS.contains(&name.as_str()),
+            // FunctionCalls that return ValueType are allowed (count, length, value)
+            Expr::FunctionCall { function, .. } => function.result() == FunctionType::Value,
+            Expr::Arithmetic { .. } => true,
             _ => false,
         }
     }
 
-    /// Validate function parameter count and types per RFC 9535
+    /// Check if an expression is LogicalType (logical expression or existence test)
+    fn is_logical_type(expr: &Expr) -> bool {
+        match expr {
+            Expr::Literal(_) => false,
+            // NodesType results convert to LogicalType by existence
+            Expr::FunctionCall { function, .. } => function.result() != FunctionType::Value,
+            _ => true,
+        }
+    }
+
+    /// Validate function argument count and types against the declared parameters (RFC 9535)
     fn validate_function_params(
-        &self,
         name: &str,
+        params: &[FunctionType],
         args: &[Expr],
-        pos: usize,
+        spans: &FunctionCallSpans,
     ) -> Result<(), ParseError> {
-        match name {
-            // count(NodesType) - exactly 1 argument, must be a query (not literal)
-            "count" => {
-                if args.len() != 1 {
-                    return Err(ParseError {
-                        message: format!(
-                            "function 'count' requires exactly 1 argument, got {}",
-                            args.len()
-                        ),
-                        position: pos,
-                    });
-                }
-                if !Self::is_nodes_type(&args[0]) {
-                    return Err(ParseError {
-                        message: "function 'count' requires a query argument (NodesType)"
-                            .to_string(),
-                        position: pos,
-                    });
-                }
-            }
-            // length(ValueType) - exactly 1 argument, must be singular query or literal
-            "length" => {
-                if args.len() != 1 {
-                    return Err(ParseError {
-                        message: format!(
-                            "function 'length' requires exactly 1 argument, got {}",
-                            args.len()
-                        ),
-                        position: pos,
-                    });
-                }
-                // RFC 9535: length() argument must be ValueType (singular query or literal)
-                if !Self::is_value_type(&args[0]) {
-                    return Err(ParseError {
-                        message: "function 'length' requires a singular query or literal argument"
-                            .to_string(),
-                        position: pos,
-                    });
-                }
-            }
-            // match(ValueType, ValueType) - exactly 2 arguments, both must be ValueType
-            "match" => {
-                if args.len() != 2 {
-                    return Err(ParseError {
-                        message: format!(
-                            "function 'match' requires exactly 2 arguments, got {}",
-                            args.len()
-                        ),
-                        position: pos,
-                    });
-                }
-                // RFC 9535: Both arguments must be ValueType (singular query or literal)
-                if !Self::is_value_type(&args[0]) {
-                    return Err(ParseError {
-                        message:
-                            "function 'match' first argument must be a singular query or literal"
-                                .to_string(),
-                        position: pos,
-                    });
-                }
-                if !Self::is_value_type(&args[1]) {
-                    return Err(ParseError {
-                        message:
-                            "function 'match' second argument must be a singular query or literal"
-                                .to_string(),
-                        position: pos,
-                    });
-                }
-            }
-            // search(ValueType, ValueType) - exactly 2 arguments, both must be ValueType
-            "search" => {
-                if args.len() != 2 {
-                    return Err(ParseError {
-                        message: format!(
-                            "function 'search' requires exactly 2 arguments, got {}",
-                            args.len()
-                        ),
-                        position: pos,
-                    });
-                }
-                // RFC 9535: Both arguments must be ValueType (singular query or literal)
-                if !Self::is_value_type(&args[0]) {
-                    return Err(ParseError {
-                        message:
-                            "function 'search' first argument must be a singular query or literal"
-                                .to_string(),
-                        position: pos,
-                    });
-                }
-                if !Self::is_value_type(&args[1]) {
-                    return Err(ParseError {
-                        message:
-                            "function 'search' second argument must be a singular query or literal"
-                                .to_string(),
-                        position: pos,
-                    });
-                }
-            }
-            // value(NodesType) - exactly 1 argument, must be a query (not literal)
-            "value" => {
-                if args.len() != 1 {
-                    return Err(ParseError {
-                        message: format!(
-                            "function 'value' requires exactly 1 argument, got {}",
-                            args.len()
-                        ),
-                        position: pos,
-                    });
-                }
-                if !Self::is_nodes_type(&args[0]) {
-                    return Err(ParseError {
-                        message: "function 'value' requires a query argument (NodesType)"
-                            .to_string(),
-                        position: pos,
-                    });
-                }
-            }
-            // RFC 9535: Only the 5 defined functions are allowed
-            _ => {
-                return Err(ParseError {
-                    message: format!("unknown function '{}'", name),
-                    position: pos,
-                });
+        if args.len() != params.len() {
+            let plural = if params.len() == 1 { "" } else { "s" };
+            return Err(ParseError::new(
+                ParseErrorKind::FunctionArity,
+                format!(
+                    "function '{name}' requires exactly {} argument{plural}, got {}",
+                    params.len(),
+                    args.len()
+                ),
+                spans.call.clone(),
+            ));
+        }
+
+        for (index, (param, arg)) in params.iter().zip(args).enumerate() {
+            let valid = match param {
+                FunctionType::Value => Self::is_value_type(arg),
+                FunctionType::Nodes => Self::is_nodes_type(arg),
+                FunctionType::Logical => Self::is_logical_type(arg),
+            };
+            if valid {
+                continue;
             }
+            let message = if params.len() == 1 {
+                let expected = match param {
+                    FunctionType::Value => "a singular query or literal argument",
+                    FunctionType::Nodes => "a query argument (NodesType)",
+                    FunctionType::Logical => "a logical expression argument (LogicalType)",
+                };
+                format!("function '{name}' requires {expected}")
+            } else {
+                let expected = match param {
+                    FunctionType::Value => "a singular query or literal",
+                    FunctionType::Nodes => "a query (NodesType)",
+                    FunctionType::Logical => "a logical expression (LogicalType)",
+                };
+                let ordinal = match index {
+                    0 => "first".to_string(),
+                    1 => "second".to_string(),
+                    2 => "third".to_string(),
+                    n => format!("{}th", n + 1),
+                };
+                format!("function '{name}' {ordinal} argument must be {expected}")
+            };
+            return Err(ParseError::new(
+                ParseErrorKind::FunctionArgumentType,
+                message,
+                spans.args[index].clone(),
+            ));
         }
         Ok(())
     }
 }
 
+/// Source spans of a parsed function call, for error reporting
+struct FunctionCallSpans {
+    /// The function name
+    name: Range<usize>,
+    /// The whole call, from the name to the closing parenthesis
+    call: Range<usize>,
+    /// Each argument expression
+    args: Vec<Range<usize>>,
+}
+
 #[cfg(test)]
 #[allow(clippy::unwrap_used, clippy::panic)]
 mod tests {
@@ -1101,7 +1372,7 @@ mod tests {
                             // right should be 10
                             assert_eq!(
                                 **right,
-                                Expr::Literal(CachedLiteral::new(Literal::Number(10.0)))
+                                Expr::Literal(CachedLiteral::new(Literal::Integer(10)))
                             );
                         }
                         _ => panic!("expected Comparison expression"),
@@ -1180,15 +1451,15 @@ mod tests {
                         assert_eq!(*op, CompOp::Gt);
                         // left should be function call
                         match left.as_ref() {
-                            Expr::FunctionCall { name, args } => {
-                                assert_eq!(name, "length");
+                            Expr::FunctionCall { function, args, .. } => {
+                                assert_eq!(function.name(), "length");
                                 assert_eq!(args.len(), 1);
                             }
                             _ => panic!("expected FunctionCall on left"),
                         }
                         assert_eq!(
                             **right,
-                            Expr::Literal(CachedLiteral::new(Literal::Number(0.0)))
+                            Expr::Literal(CachedLiteral::new(Literal::Integer(0)))
                         );
                     }
                     _ => panic!("expected Comparison expression"),
@@ -1275,6 +1546,271 @@ mod tests {
         }
     }
 
+    // ========== Error Kind and Span Tests ==========
+
+    #[test]
+    fn test_error_kinds_and_spans() {
+        let cases = [
+            (" $", ParseErrorKind::InvalidWhitespace, 0..1),
+            ("$.a  ", ParseErrorKind::InvalidWhitespace, 3..5),
+            ("$. a", ParseErrorKind::InvalidWhitespace, 2..3),
+            ("a", ParseErrorKind::MissingRoot, 0..1),
+            ("$#", ParseErrorKind::UnexpectedCharacter, 1..2),
+            ("$[?@.a = 1]", ParseErrorKind::UnexpectedCharacter, 7..8),
+            ("$['a'", ParseErrorKind::UnclosedBracket, 5..5),
+            ("$['a' 'b']", ParseErrorKind::UnexpectedToken, 6..9),
+            ("$[?(@.a]", ParseErrorKind::UnclosedParen, 7..8),
+            ("$['a\\q']", ParseErrorKind::InvalidEscape, 4..6),
+            ("$[01]", ParseErrorKind::InvalidNumber, 2..4),
+            ("$[1.5]", ParseErrorKind::InvalidIndex, 2..5),
+            ("$[-0]", ParseErrorKind::InvalidIndex, 2..4),
+            (
+                "$[9007199254740992]",
+                ParseErrorKind::IndexOutOfRange,
+                2..18,
+            ),
+            ("$[?'x']", ParseErrorKind::LiteralOnlyFilter, 3..6),
+            (
+                "$[?@.a && true]",
+                ParseErrorKind::LiteralLogicalOperand,
+                7..9,
+            ),
+            ("$[?@.* == 1]", ParseErrorKind::NonSingularComparison, 3..6),
+            (
+                "$[?1 == $..a]",
+                ParseErrorKind::NonSingularComparison,
+                8..12,
+            ),
+            ("$[?length(@.a)]", ParseErrorKind::FunctionResultType, 3..14),
+            (
+                "$[?match(@.a, 'x') == true]",
+                ParseErrorKind::FunctionResultType,
+                3..18,
+            ),
+            ("$[?count(@, @) > 1]", ParseErrorKind::FunctionArity, 3..14),
+            (
+                "$[?count(1) > 1]",
+                ParseErrorKind::FunctionArgumentType,
+                9..10,
+            ),
+            (
+                "$[?search(@.a, @.*)]",
+                ParseErrorKind::FunctionArgumentType,
+                15..18,
+            ),
+            ("$[?foo(@)]", ParseErrorKind::UnknownFunction, 3..6),
+        ];
+        for (query, kind, span) in cases {
+            let err = Parser::parse(query).unwrap_err();
+            assert_eq!(
+                (err.kind, err.span.clone()),
+                (kind, span),
+                "query {query}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_error_display_uses_span_start() {
+        let err = Parser::parse("$[?foo(@)]").unwrap_err();
+        assert_eq!(err.to_string(), "at position 3, unknown function 'foo'");
+    }
+
+    #[test]
+    fn test_integer_literals_are_exact() {
+        let path = Parser::parse("$[?@.id == 9007199254740993]").unwrap();
+        assert_eq!(path.to_string(), "$[?@['id'] == 9007199254740993]");
+        for query in [
+            "$[9007199254740992]",
+            "$[-9007199254740992]",
+            "$[18446744073709551615]",
+        ] {
+            let err = Parser::parse(query).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::IndexOutOfRange, "query {query}");
+        }
+        assert!(Parser::parse("$[9007199254740991]").is_ok());
+        assert!(Parser::parse("$[-9007199254740991:]").is_ok());
+        assert_eq!(
+            Parser::parse("$[-0]").unwrap_err().kind,
+            ParseErrorKind::InvalidIndex
+        );
+    }
+
+    #[test]
+    fn test_multibyte_error_positions() {
+        let input = "$.日本語.x[";
+        let err = Parser::parse(input).unwrap_err();
+        assert_eq!(
+            (err.kind, err.span.clone()),
+            (ParseErrorKind::UnexpectedEndOfInput, 8..8)
+        );
+        assert_eq!(err.byte_span(input), 14..14);
+        assert_eq!(&input[..err.byte_span(input).start], input);
+
+        let input = "$[?@.名前 == 'ü' && @.x = 1]";
+        let err = Parser::parse(input).unwrap_err();
+        assert_eq!(err.span, 22..23);
+        assert_eq!(&input[err.byte_span(input)], "=");
+
+        let input = "$['ß'] ";
+        let err = Parser::parse(input).unwrap_err();
+        assert_eq!(err.span, 6..7);
+        assert_eq!(err.byte_span(input), 7..8);
+    }
+
+    #[test]
+    fn test_multibyte_whitespace_adjacency() {
+        for query in [
+            "$.日本語",
+            "$..日本語",
+            "$.日本語.é",
+            "$.日本語 .x",
+            "$[?@.日本語]",
+            "$[?length(@.日本語) > 1]",
+            "$[?@.日本語 == 1 && length(@.é) > 1]",
+        ] {
+            assert!(Parser::parse(query).is_ok(), "query {query}");
+        }
+        for (query, span) in [
+            ("$. 日本語", 2..3),
+            ("$.日本語. é", 6..7),
+            ("$.日本語.. é", 7..8),
+            ("$[?@.日本語 && length (@.é) > 1]", 18..19),
+        ] {
+            let err = Parser::parse(query).unwrap_err();
+            assert_eq!(
+                (err.kind, err.span),
+                (ParseErrorKind::InvalidWhitespace, span),
+                "query {query}"
+            );
+        }
+
+        let input = "  $.日本語 ";
+        let (_, warnings) = Parser::parse_with_options(
+            input,
+            &ParseOptions {
+                lenient_whitespace: true,
+                ..ParseOptions::default()
+            },
+        )
+        .unwrap();
+        let spans: Vec<_> = warnings.iter().map(|w| w.byte_span(input)).collect();
+        assert_eq!(spans, vec![0..2, 13..14]);
+    }
+
+    #[test]
+    fn test_nested_filters_follow_top_level_rules() {
+        let invalid = [
+            ("count(@.x)", ParseErrorKind::FunctionResultType),
+            ("length(@)", ParseErrorKind::FunctionResultType),
+            ("(value(@..x))", ParseErrorKind::FunctionResultType),
+            ("1", ParseErrorKind::LiteralOnlyFilter),
+            ("'x'", ParseErrorKind::LiteralOnlyFilter),
+            ("@.a && true", ParseErrorKind::LiteralLogicalOperand),
+            ("@.* == 1", ParseErrorKind::NonSingularComparison),
+            ("match(@, 'a') == true", ParseErrorKind::FunctionResultType),
+            ("foo(@)", ParseErrorKind::UnknownFunction),
+        ];
+        for (filter, kind) in invalid {
+            for query in [
+                format!("$[?{filter}]"),
+                format!("$[?@[?{filter}]]"),
+                format!("$[?@.a[?{filter}]]"),
+                format!("$[?@..[?{filter}]]"),
+                format!("$[?$[?@[?{filter}]]]"),
+            ] {
+                let err = Parser::parse(&query).unwrap_err();
+                assert_eq!(err.kind, kind, "query {query}: {err}");
+            }
+        }
+
+        for filter in [
+            "count(@.x) > 0",
+            "length(@) == 1",
+            "@.a",
+            "!@.a",
+            "match(@, 'a')",
+        ] {
+            for query in [format!("$[?{filter}]"), format!("$[?@[?{filter}]]")] {
+                assert!(Parser::parse(&query).is_ok(), "query {query}");
+            }
+        }
+
+        for query in ["$[?@. a]", "$[?@[?@.. a]]", "$[?@[?length (@) == 1]]"] {
+            let err = Parser::parse(query).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::InvalidWhitespace, "query {query}");
+        }
+    }
+
+    #[test]
+    fn test_unclosed_delimiter_reports_opening() {
+        let cases = [
+            ("$['a', 'b'", ParseErrorKind::UnclosedBracket, 10..10, 1..2),
+            (
+                "$[?(@.a == 1)",
+                ParseErrorKind::UnclosedBracket,
+                13..13,
+                1..2,
+            ),
+            ("$[?(@.a == 1]", ParseErrorKind::UnclosedParen, 12..13, 3..4),
+            (
+                "$[?(@.a == $.b[1]",
+                ParseErrorKind::UnclosedParen,
+                17..17,
+                3..4,
+            ),
+            ("$[?@[0, 1", ParseErrorKind::UnclosedBracket, 9..9, 4..5),
+            ("$[?@.a[?@.b", ParseErrorKind::UnclosedBracket, 11..11, 6..7),
+            (
+                "$[?length(@.a == 1]",
+                ParseErrorKind::UnclosedParen,
+                18..19,
+                9..10,
+            ),
+            (
+                "$[?((@.a) && (@.b)",
+                ParseErrorKind::UnclosedParen,
+                18..18,
+                3..4,
+            ),
+            ("$['ab", ParseErrorKind::UnterminatedString, 5..5, 2..3),
+            (
+                "$[?@.a == 'x]",
+                ParseErrorKind::UnterminatedString,
+                13..13,
+                10..11,
+            ),
+            ("$[\"a\\", ParseErrorKind::UnterminatedString, 5..5, 2..3),
+        ];
+        for (query, kind, span, opening) in cases {
+            let err = Parser::parse(query).unwrap_err();
+            assert_eq!(
+                (err.kind, err.span.clone(), err.opening.clone()),
+                (kind, span, Some(opening)),
+                "query {query}: {err}"
+            );
+        }
+
+        let err = Parser::parse("$[?(@.a == $.b[1]").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at position 17, parenthesis opened at position 3 was never closed"
+        );
+        let err = Parser::parse("$[?(@.a == 1]").unwrap_err();
+        assert_eq!(
+            err.message,
+            "expected ')' to close parenthesis opened at position 3"
+        );
+
+        // An array is not a literal, so the `[` is reported rather than the open `(`
+        let err = Parser::parse("$[?(@.a == [1,2]").unwrap_err();
+        assert_eq!(
+            (err.kind, err.span),
+            (ParseErrorKind::UnexpectedToken, 11..12)
+        );
+        assert_eq!(err.opening, None);
+    }
+
     // ========== Whitespace Validation Tests ==========
 
     #[test]
@@ -1283,7 +1819,9 @@ mod tests {
         assert!(result.is_err());
         let err = result.unwrap_err();
         assert!(err.message.contains("leading whitespace"));
-        assert_eq!(err.position, 0);
+        assert_eq!(err.position(), 0);
+        assert_eq!(err.kind, ParseErrorKind::InvalidWhitespace);
+        assert_eq!(err.span, 0..1);
     }
 
     #[test]
@@ -1294,6 +1832,244 @@ mod tests {
         assert!(err.message.contains("trailing whitespace"));
     }
 
+    #[test]
+    fn test_lenient_whitespace() {
+        let options = ParseOptions {
+            lenient_whitespace: true,
+            ..Default::default()
+        };
+        let (path, warnings) =
+            Parser::parse_with_options("  $ .. a[?length (@. b) > 1]\n", &options).unwrap();
+        assert_eq!(path, Parser::parse("$..a[?length(@.b) > 1]").unwrap());
+        let relaxed: Vec<(&str, Range<usize>)> = warnings
+            .iter()
+            .map(|w| (w.message.as_str(), w.span.clone()))
+            .collect();
+        assert_eq!(
+            relaxed,
+            vec![
+                ("ignored leading whitespace", 0..2),
+                ("ignored trailing whitespace", 28..29),
+                ("ignored whitespace after '..'", 6..7),
+                ("ignored whitespace between function name and '('", 16..17),
+                ("ignored whitespace after '.'", 20..21),
+            ]
+        );
+
+        let (_, warnings) = Parser::parse_with_options("$.a[?@.b]", &options).unwrap();
+        assert!(warnings.is_empty());
+        // Only whitespace is relaxed
+        assert!(Parser::parse_with_options(" $.a[", &options).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let limited = |options: ParseOptions, query: &str| {
+            let err = Parser::parse_with_options(query, &options).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::LimitExceeded, "query {query}");
+            err
+        };
+        let unlimited = ParseOptions {
+            max_input_length: None,
+            max_tokens: None,
+            max_nesting_depth: None,
+            max_selectors_per_segment: None,
+            ..Default::default()
+        };
+
+        let options = ParseOptions {
+            max_input_length: Some(4),
+            ..unlimited
+        };
+        assert!(Parser::parse_with_options("$.ab", &options).is_ok());
+        assert_eq!(limited(options, "$.abc").span, 4..5);
+        // Multibyte characters count once
+        assert!(Parser::parse_with_options("$.éé", &options).is_ok());
+
+        let options = ParseOptions {
+            max_tokens: Some(3),
+            ..unlimited
+        };
+        assert!(Parser::parse_with_options("$.a", &options).is_ok());
+        assert_eq!(limited(options, "$.a.b").span, 3..4);
+
+        let options = ParseOptions {
+            max_selectors_per_segment: Some(2),
+            ..unlimited
+        };
+        assert!(Parser::parse_with_options("$[0,1]", &options).is_ok());
+        assert_eq!(limited(options, "$[0,1,'x']").span, 6..9);
+        limited(options, "$[?@.a[0,1,2]]");
+        limited(options, "$[?@..[0,1,2]]");
+
+        let options = ParseOptions {
+            max_nesting_depth: Some(3),
+            ..unlimited
+        };
+        assert!(Parser::parse_with_options("$[?!(@.a)]", &options).is_ok());
+        assert!(Parser::parse_with_options("$[?(@.a) && (@.b)]", &options).is_ok());
+        limited(options, "$[?!!(@.a)]");
+        assert!(Parser::parse_with_options("$[?@[?@[?@.a]]]", &options).is_ok());
+        limited(options, "$[?@[?@[?@[?@.a]]]]");
+        limited(options, "$[?count(@[?count(@[?@.a]) > 0]) > 0]");
+    }
+
+    #[cfg(feature = "arithmetic")]
+    #[test]
+    fn test_arithmetic_precedence() {
+        let options = ParseOptions {
+            arithmetic: true,
+            ..Default::default()
+        };
+        let parse = |query: &str| Parser::parse_with_options(query, &options).map(|(p, _)| p);
+        for (query, rendered) in [
+            ("$[?@.a + @.b * 2 > 1]", "$[?@['a'] + @['b'] * 2 > 1]"),
+            ("$[?(@.a + @.b) * 2 > 1]", "$[?(@['a'] + @['b']) * 2 > 1]"),
+            ("$[?(@.a - @.b) - 1 == 0]", "$[?@['a'] - @['b'] - 1 == 0]"),
+            ("$[?@.a - (@.b - 1) == 0]", "$[?@['a'] - (@['b'] - 1) == 0]"),
+            ("$[?@.a / (2 * 3) == 0]", "$[?@['a'] / (2 * 3) == 0]"),
+            ("$[?@.a / 2 * 3 == 0]", "$[?@['a'] / 2 * 3 == 0]"),
+            ("$[?@.a-1 == -1.5*-2]", "$[?@['a'] - 1 == -1.5 * -2]"),
+            (
+                "$[?@.a == 1 && $.b+1 < 2]",
+                "$[?@['a'] == 1 && $['b'] + 1 < 2]",
+            ),
+            ("$[?length(@.a * 2) == 2]", "$[?length(@['a'] * 2) == 2]"),
+        ] {
+            let path = parse(query).unwrap();
+            assert_eq!(path.to_string(), rendered, "{query}");
+            assert_eq!(parse(rendered).unwrap(), path, "{rendered}");
+        }
+
+        let path = parse("$[?1 - 2 + 3 * 4 == 0]").unwrap();
+        let Selector::Filter(expr) = &path.segments[0].selectors()[0] else {
+            unreachable!()
+        };
+        let Expr::Comparison { left, .. } = expr.as_ref() else {
+            unreachable!()
+        };
+        let Expr::Arithmetic { left, op, right } = left.as_ref() else {
+            unreachable!()
+        };
+        assert_eq!(*op, ArithOp::Add);
+        assert_eq!(left.to_string(), "1 - 2");
+        assert_eq!(right.to_string(), "3 * 4");
+
+        for query in [
+            "$[?@.a + 1]",
+            "$[?@.* + 1 == 2]",
+            "$[?match(@.a, 'x') * 2 == 2]",
+            "$[?1 + (@.a == 1) == 2]",
+        ] {
+            assert_eq!(
+                parse(query).unwrap_err().kind,
+                ParseErrorKind::ArithmeticType,
+                "{query}"
+            );
+        }
+        // Off by default
+        for query in ["$[?@.a + 1 == 2]", "$[?@.a -1 == 2]", "$[?@.a * 2 == 2]"] {
+            assert!(Parser::parse(query).is_err(), "{query}");
+        }
+        let deep = format!(
+            "$[?{}@.a{} == 1]",
+            "(".repeat(100_000),
+            "+1)".repeat(100_000)
+        );
+        assert_eq!(
+            parse(&deep).unwrap_err().kind,
+            ParseErrorKind::LimitExceeded
+        );
+    }
+
+    #[test]
+    fn test_default_limits_stop_deep_nesting() {
+        for query in [
+            format!("$[?{}@.a{}]", "!(".repeat(100_000), ")".repeat(100_000)),
+            format!("$[?{}@.a{}]", "(".repeat(100_000), ")".repeat(100_000)),
+            format!("$[?{}@.a{}]", "@[?".repeat(10_000), "]".repeat(10_000)),
+        ] {
+            let err = Parser::parse(&query).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::LimitExceeded);
+        }
+        let nested = format!("$[?{}@.a{}]", "!(".repeat(60), ")".repeat(60));
+        assert!(Parser::parse(&nested).is_ok());
+    }
+
+    #[test]
+    fn test_strict_options_match_parse() {
+        for query in [" $", "$. a", "$[?length (@) > 1]", "$.a ", "$.a"] {
+            assert_eq!(
+                Parser::parse_with_options(query, &ParseOptions::default()).map(|(p, _)| p),
+                Parser::parse(query),
+                "query {query:?}"
+            );
+        }
+    }
+
+    /// The compiled pattern of the filter's function call, if any
+    fn compiled_regex(query: &str) -> Option<String> {
+        let path = Parser::parse(query).unwrap();
+        match &path.segments[0] {
+            Segment::Child(selectors) => match &selectors[0] {
+                Selector::Filter(expr) => match expr.as_ref() {
+                    Expr::FunctionCall { regex, .. } => regex.as_ref().map(|r| r.as_str().into()),
+                    _ => panic!("expected FunctionCall"),
+                },
+                _ => panic!("expected Filter selector"),
+            },
+            _ => panic!("expected Child segment"),
+        }
+    }
+
+    #[test]
+    fn test_regex_literals_compiled_at_parse_time() {
+        assert_eq!(
+            compiled_regex("$[?match(@, 'a.c')]").as_deref(),
+            Some("^(?:a[^\\r\\n]c)$")
+        );
+        assert_eq!(
+            compiled_regex("$[?search(@, 'a.c')]").as_deref(),
+            Some("a[^\\r\\n]c")
+        );
+        assert_eq!(compiled_regex("$[?match(@, @.p)]"), None);
+        assert_eq!(compiled_regex("$[?search(@, 'a(')]"), None);
+
+        let options = ParseOptions {
+            reject_invalid_regex: true,
+            ..ParseOptions::default()
+        };
+        assert!(Parser::parse_with_options("$[?match(@, 'a')]", &options).is_ok());
+        assert!(Parser::parse_with_options("$[?match(@, @.p)]", &options).is_ok());
+        let err = Parser::parse_with_options("$[?search(@.a, 'a(')]", &options).unwrap_err();
+        assert_eq!(err.kind, ParseErrorKind::InvalidRegex);
+        assert_eq!(err.span, 15..19);
+    }
+
+    #[test]
+    fn test_strict_iregexp_rejects_non_iregexp_literals() {
+        let strict = ParseOptions {
+            strict_iregexp: true,
+            ..ParseOptions::default()
+        };
+        for query in ["$[?match(@, '\\\\d+')]", "$[?search(@, 'a*?')]"] {
+            assert!(Parser::parse(query).is_ok(), "{query}");
+            let err = Parser::parse_with_options(query, &strict).unwrap_err();
+            assert_eq!(err.kind, ParseErrorKind::InvalidRegex, "{query}");
+            assert!(err.message.contains("I-Regexp"), "{}", err.message);
+        }
+        // Only literal patterns of the built-ins are checked at parse time
+        assert!(Parser::parse_with_options("$[?match(@, @.p)]", &strict).is_ok());
+        let (path, _) = Parser::parse_with_options("$[?match(@, '^a')]", &strict).unwrap();
+        assert_eq!(path.to_string(), "$[?match(@, '^a')]");
+    }
+
+    #[test]
+    fn test_parsed_query_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+        assert_send_sync(&Parser::parse("$[?match(@, 'a')]").unwrap());
+    }
+
     #[test]
     fn test_reject_both_leading_and_trailing_whitespace() {
         let result = Parser::parse(" $ ");
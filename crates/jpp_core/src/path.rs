@@ -0,0 +1,394 @@
+//! Normalized paths (RFC 9535 Section 2.7)
+//!
+//! A normalized path identifies exactly one node in a JSON value, e.g. `$['store']['book'][0]`.
+
+use crate::lexer::{is_ident_char, is_ident_start};
+use serde_json::Value;
+use std::fmt;
+
+/// A single step of a normalized path
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum PathElement {
+    /// Object member name: `['name']`
+    Name(String),
+    /// Array index: `[0]`
+    Index(usize),
+}
+
+/// Location of a single node within a JSON value
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NormalizedPath {
+    elements: Vec<PathElement>,
+}
+
+impl NormalizedPath {
+    /// The path of the root node (`$`)
+    pub fn root() -> Self {
+        Self::default()
+    }
+
+    /// Create a path from its elements (outermost first)
+    pub fn new(elements: Vec<PathElement>) -> Self {
+        Self { elements }
+    }
+
+    /// Elements of the path, outermost first
+    pub fn elements(&self) -> &[PathElement] {
+        &self.elements
+    }
+
+    /// Number of elements (0 for the root)
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    /// True if this is the root path `$`
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Append an element to the end of the path
+    pub fn push(&mut self, element: PathElement) {
+        self.elements.push(element);
+    }
+
+    /// Remove and return the last element
+    pub fn pop(&mut self) -> Option<PathElement> {
+        self.elements.pop()
+    }
+
+    /// Return a new path with `element` appended
+    pub fn child(&self, element: PathElement) -> Self {
+        let mut elements = Vec::with_capacity(self.elements.len() + 1);
+        elements.extend_from_slice(&self.elements);
+        elements.push(element);
+        Self { elements }
+    }
+
+    /// Path of the parent node, or None for the root
+    pub fn parent(&self) -> Option<Self> {
+        let (_, rest) = self.elements.split_last()?;
+        Some(Self {
+            elements: rest.to_vec(),
+        })
+    }
+
+    /// Last element of the path, or None for the root
+    pub fn last(&self) -> Option<&PathElement> {
+        self.elements.last()
+    }
+
+    /// True if `self` is `other` or one of its ancestors
+    pub fn is_prefix_of(&self, other: &NormalizedPath) -> bool {
+        other.elements.starts_with(&self.elements)
+    }
+
+    /// Resolve the path against a JSON value
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::{NormalizedPath, PathElement};
+    ///
+    /// let json = json!({"a": [10, 20]});
+    /// let path = NormalizedPath::new(vec![PathElement::Name("a".to_string()), PathElement::Index(1)]);
+    /// assert_eq!(path.get(&json), Some(&json!(20)));
+    /// ```
+    pub fn get<'a>(&self, json: &'a Value) -> Option<&'a Value> {
+        self.elements
+            .iter()
+            .try_fold(json, |node, element| match (element, node) {
+                (PathElement::Name(name), Value::Object(map)) => map.get(name),
+                (PathElement::Index(i), Value::Array(arr)) => arr.get(*i),
+                _ => None,
+            })
+    }
+
+    /// The same location as a JSON Pointer (RFC 6901): `~` and `/` in member names are
+    /// escaped as `~0` and `~1`, and the root is the empty string
+    ///
+    /// # Example
+    /// ```
+    /// use jpp_core::{NormalizedPath, PathElement};
+    ///
+    /// let path = NormalizedPath::new(vec![PathElement::Name("a/b~".to_string()), PathElement::Index(0)]);
+    /// assert_eq!(path.to_json_pointer(), "/a~1b~0/0");
+    /// assert_eq!(NormalizedPath::root().to_json_pointer(), "");
+    /// ```
+    pub fn to_json_pointer(&self) -> String {
+        let mut pointer = String::new();
+        for element in &self.elements {
+            pointer.push('/');
+            match element {
+                PathElement::Name(name) => {
+                    pointer.push_str(&name.replace('~', "~0").replace('/', "~1"));
+                }
+                PathElement::Index(i) => pointer.push_str(&i.to_string()),
+            }
+        }
+        pointer
+    }
+}
+
+impl NormalizedPath {
+    /// Resolve the path against a JSON value for modification
+    pub fn get_mut<'a>(&self, json: &'a mut Value) -> Option<&'a mut Value> {
+        self.elements
+            .iter()
+            .try_fold(json, |node, element| match (element, node) {
+                (PathElement::Name(name), Value::Object(map)) => map.get_mut(name),
+                (PathElement::Index(i), Value::Array(arr)) => arr.get_mut(*i),
+                _ => None,
+            })
+    }
+
+    /// Remove the node at this path from its parent and return it
+    ///
+    /// Later elements of an array shift down by one. Returns None for the root, which has no
+    /// parent, and for paths that do not resolve.
+    ///
+    /// # Example
+    /// ```
+    /// use serde_json::json;
+    /// use jpp_core::{NormalizedPath, PathElement};
+    ///
+    /// let mut json = json!({"a": [10, 20, 30]});
+    /// let path = NormalizedPath::new(vec![PathElement::Name("a".to_string()), PathElement::Index(1)]);
+    /// assert_eq!(path.remove(&mut json), Some(json!(20)));
+    /// assert_eq!(json, json!({"a": [10, 30]}));
+    /// ```
+    pub fn remove(&self, json: &mut Value) -> Option<Value> {
+        let (last, parent) = self.elements.split_last()?;
+        let parent = NormalizedPath::new(parent.to_vec()).get_mut(json)?;
+        match (last, parent) {
+            (PathElement::Name(name), Value::Object(map)) => map.remove(name),
+            (PathElement::Index(i), Value::Array(arr)) if *i < arr.len() => Some(arr.remove(*i)),
+            _ => None,
+        }
+    }
+}
+
+impl From<Vec<PathElement>> for NormalizedPath {
+    fn from(elements: Vec<PathElement>) -> Self {
+        Self { elements }
+    }
+}
+
+impl fmt::Display for NormalizedPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("$")?;
+        for element in &self.elements {
+            match element {
+                PathElement::Name(name) => {
+                    f.write_str("['")?;
+                    write_escaped_name(f, name)?;
+                    f.write_str("']")?;
+                }
+                PathElement::Index(i) => write!(f, "[{i}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Quote a member name for use in a bracket selector, e.g. `it's` → `'it\'s'`
+///
+/// Uses the escaping of normalized paths, so any name round-trips through the parser.
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use jpp_core::{JsonPath, escape_name};
+///
+/// let field = "it's \"quoted\"";
+/// assert_eq!(escape_name(field), r#"'it\'s "quoted"'"#);
+/// let path = JsonPath::parse(&format!("$[{}]", escape_name(field))).unwrap();
+/// assert_eq!(path.query(&json!({field: 1})), vec![&json!(1)]);
+/// ```
+pub fn escape_name(name: &str) -> String {
+    struct Quoted<'a>(&'a str);
+
+    impl fmt::Display for Quoted<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("'")?;
+            write_escaped_name(f, self.0)?;
+            f.write_str("'")
+        }
+    }
+
+    Quoted(name).to_string()
+}
+
+/// True if `name` cannot be written in dot notation (`.name`) and needs `[...]` with
+/// [`escape_name`]
+///
+/// Dot notation requires a member-name shorthand: a letter, `_` or non-ASCII character,
+/// followed by those or digits. Names with Unicode whitespace such as U+2028 also get
+/// brackets, since the parser would take it for whitespace around the query.
+pub fn needs_bracket_notation(name: &str) -> bool {
+    let mut chars = name.chars();
+    !(chars.next().is_some_and(is_ident_start) && chars.all(is_ident_char))
+        || name.chars().any(char::is_whitespace)
+}
+
+/// Write a member name using normalized path escaping (RFC 9535 Section 2.7)
+pub(crate) fn write_escaped_name(f: &mut fmt::Formatter<'_>, name: &str) -> fmt::Result {
+    for ch in name.chars() {
+        match ch {
+            '\'' => f.write_str("\\'")?,
+            '\\' => f.write_str("\\\\")?,
+            '\x08' => f.write_str("\\b")?,
+            '\x0C' => f.write_str("\\f")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            // Remaining control characters use lowercase \u00xx
+            c if (c as u32) <= 0x1F => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nasty_names() -> Vec<String> {
+        let mut names: Vec<String> = [
+            "", "a", "it's", "\"", "\\", "\\'", "'\\", "a b", "0", "1a", "a1", "_", "$", "@", "..",
+            "[*]", "true", "null", "😀", "ключ", "a\u{7f}b", "\u{2028}", "\u{FFFF}",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        names.extend((0..=0x1F).map(|c| format!("x{}y", char::from(c))));
+        names
+    }
+
+    #[test]
+    fn test_escape_name_round_trips() {
+        use crate::JsonPath;
+
+        for name in nasty_names() {
+            let mut object = serde_json::Map::new();
+            object.insert(name.clone(), json!("hit"));
+            object.insert(format!("{name}_"), json!("miss"));
+            let json = Value::Object(object);
+
+            let query = format!("$[{}]", escape_name(&name));
+            let path = JsonPath::parse(&query).unwrap();
+            assert_eq!(path.query(&json), vec![&json!("hit")], "name {name:?}");
+
+            if !needs_bracket_notation(&name) {
+                let path = JsonPath::parse(&format!("$.{name}")).unwrap();
+                assert_eq!(path.query(&json), vec![&json!("hit")], "name {name:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_needs_bracket_notation() {
+        for name in ["a", "_a1", "true", "😀", "ключ", "a_b"] {
+            assert!(!needs_bracket_notation(name), "{name:?}");
+        }
+        for name in [
+            "", "1a", "a b", "a-b", "it's", "$", "a.b", "\u{1}", "\u{2028}",
+        ] {
+            assert!(needs_bracket_notation(name), "{name:?}");
+        }
+    }
+
+    #[test]
+    fn test_escape_name_control_characters() {
+        assert_eq!(escape_name("\u{0}\n\u{1f}"), "'\\u0000\\n\\u001f'");
+    }
+
+    fn name(s: &str) -> PathElement {
+        PathElement::Name(s.to_string())
+    }
+
+    #[test]
+    fn test_display_root() {
+        assert_eq!(NormalizedPath::root().to_string(), "$");
+    }
+
+    #[test]
+    fn test_display_names_and_indices() {
+        let path = NormalizedPath::new(vec![name("store"), name("book"), PathElement::Index(0)]);
+        assert_eq!(path.to_string(), "$['store']['book'][0]");
+    }
+
+    #[test]
+    fn test_display_escapes() {
+        let path = NormalizedPath::new(vec![name("it's"), name("a\\b"), name("\n\t\u{1}")]);
+        assert_eq!(path.to_string(), r"$['it\'s']['a\\b']['\n\t\u0001']");
+    }
+
+    #[test]
+    fn test_display_is_parseable() {
+        let json = json!({"it's": {"\u{1}": [1, 2]}});
+        let path = NormalizedPath::new(vec![name("it's"), name("\u{1}"), PathElement::Index(1)]);
+        let parsed = crate::JsonPath::parse(&path.to_string()).unwrap();
+        assert_eq!(parsed.query(&json), vec![&json!(2)]);
+    }
+
+    #[test]
+    fn test_parent_and_prefix() {
+        let path = NormalizedPath::new(vec![name("a"), PathElement::Index(3)]);
+        let parent = path.parent().unwrap();
+        assert_eq!(parent.to_string(), "$['a']");
+        assert!(parent.is_prefix_of(&path));
+        assert!(!path.is_prefix_of(&parent));
+        assert!(NormalizedPath::root().is_prefix_of(&path));
+        assert_eq!(NormalizedPath::root().parent(), None);
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        let json = json!({"a/b": {"~1": [0, {"": true}]}});
+        let path = NormalizedPath::new(vec![
+            name("a/b"),
+            name("~1"),
+            PathElement::Index(1),
+            name(""),
+        ]);
+        let pointer = path.to_json_pointer();
+        assert_eq!(pointer, "/a~1b/~01/1/");
+        assert_eq!(json.pointer(&pointer), Some(&json!(true)));
+        assert_eq!(NormalizedPath::root().to_json_pointer(), "");
+    }
+
+    #[test]
+    fn test_get() {
+        let json = json!({"a": [{"b": true}]});
+        let path = NormalizedPath::new(vec![name("a"), PathElement::Index(0), name("b")]);
+        assert_eq!(path.get(&json), Some(&json!(true)));
+        let missing = NormalizedPath::new(vec![name("a"), PathElement::Index(5)]);
+        assert_eq!(missing.get(&json), None);
+    }
+
+    #[test]
+    fn test_get_mut_and_remove() {
+        let mut json = json!({"a": [{"b": true}, 2], "c": 3});
+        let b = NormalizedPath::new(vec![name("a"), PathElement::Index(0), name("b")]);
+        *b.get_mut(&mut json).unwrap() = json!(false);
+        assert_eq!(json["a"][0]["b"], json!(false));
+
+        assert_eq!(b.remove(&mut json), Some(json!(false)));
+        assert_eq!(b.remove(&mut json), None);
+        let first = NormalizedPath::new(vec![name("a"), PathElement::Index(0)]);
+        assert_eq!(first.remove(&mut json), Some(json!({})));
+        let past_end = NormalizedPath::new(vec![name("a"), PathElement::Index(1)]);
+        assert_eq!(past_end.remove(&mut json), None);
+        // Name elements do not index arrays, nor index elements objects
+        assert_eq!(
+            NormalizedPath::new(vec![PathElement::Index(0)]).remove(&mut json),
+            None
+        );
+        assert_eq!(NormalizedPath::root().remove(&mut json), None);
+        assert_eq!(json, json!({"a": [2], "c": 3}));
+    }
+}
@@ -0,0 +1,222 @@
+//! Document projection: prune a JSON value down to the subtrees selected by queries
+
+use crate::ast::JsonPath;
+use crate::eval;
+use crate::path::{NormalizedPath, PathElement};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Options for [`project_with_options`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProjectOptions {
+    /// Keep array elements at their original index, filling removed siblings with `null`.
+    /// By default arrays are compacted so only the kept elements remain.
+    pub preserve_indices: bool,
+}
+
+/// Tree of kept locations built from the matched normalized paths
+#[derive(Debug, Default)]
+struct KeepTree {
+    /// The node itself was matched, so its whole subtree is kept
+    whole: bool,
+    names: HashMap<String, KeepTree>,
+    indices: HashMap<usize, KeepTree>,
+}
+
+impl KeepTree {
+    fn insert(&mut self, path: &NormalizedPath) {
+        let mut node = self;
+        for element in path.elements() {
+            if node.whole {
+                // An ancestor is already kept in full
+                return;
+            }
+            node = match element {
+                PathElement::Name(name) => node.names.entry(name.clone()).or_default(),
+                PathElement::Index(i) => node.indices.entry(*i).or_default(),
+            };
+        }
+        node.whole = true;
+        node.names.clear();
+        node.indices.clear();
+    }
+
+    fn build(&self, value: &Value, options: &ProjectOptions) -> Value {
+        if self.whole {
+            return value.clone();
+        }
+        match value {
+            Value::Object(map) => {
+                let mut out = Map::new();
+                // Iterate the source to keep its member order
+                for (key, child) in map {
+                    if let Some(tree) = self.names.get(key) {
+                        out.insert(key.clone(), tree.build(child, options));
+                    }
+                }
+                Value::Object(out)
+            }
+            Value::Array(arr) => {
+                let mut out = Vec::new();
+                for (i, child) in arr.iter().enumerate() {
+                    if let Some(tree) = self.indices.get(&i) {
+                        if options.preserve_indices {
+                            out.resize(i, Value::Null);
+                        }
+                        out.push(tree.build(child, options));
+                    }
+                }
+                Value::Array(out)
+            }
+            _ => Value::Null,
+        }
+    }
+}
+
+/// Project a document onto the nodes selected by `paths`
+///
+/// The result has the same shape as `json` but contains only the matched nodes and the
+/// ancestors needed to reach them. A matched node is kept in full, including any matches
+/// nested inside it. Arrays are compacted; see [`project_with_options`] to preserve indices.
+///
+/// If nothing matches, the result is an empty object or array (matching the root's type),
+/// or `null` for a scalar root.
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use jpp_core::{JsonPath, project};
+///
+/// let json = json!({"store": {"book": [{"title": "A", "price": 1}, {"title": "B", "price": 2}]}});
+/// let path = JsonPath::parse("$.store.book[*].title").unwrap();
+/// assert_eq!(
+///     project(&json, &[path]),
+///     json!({"store": {"book": [{"title": "A"}, {"title": "B"}]}})
+/// );
+/// ```
+pub fn project(json: &Value, paths: &[JsonPath]) -> Value {
+    project_with_options(json, paths, &ProjectOptions::default())
+}
+
+/// Project a document onto the nodes selected by `paths` with the given options
+///
+/// # Example
+/// ```
+/// use serde_json::json;
+/// use jpp_core::{JsonPath, ProjectOptions, project_with_options};
+///
+/// let json = json!([1, 2, 3]);
+/// let path = JsonPath::parse("$[2]").unwrap();
+/// let options = ProjectOptions { preserve_indices: true };
+/// assert_eq!(project_with_options(&json, &[path], &options), json!([null, null, 3]));
+/// ```
+pub fn project_with_options(json: &Value, paths: &[JsonPath], options: &ProjectOptions) -> Value {
+    let mut tree = KeepTree::default();
+    for path in paths {
+        for (location, _) in eval::evaluate_located(path, json) {
+            tree.insert(&location);
+        }
+    }
+    tree.build(json, options)
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bookstore() -> Value {
+        json!({
+            "store": {
+                "book": [
+                    {"category": "reference", "title": "Sayings of the Century", "price": 8.95},
+                    {"category": "fiction", "title": "Sword of Honour", "price": 12.99}
+                ],
+                "bicycle": {"color": "red", "price": 19.95}
+            }
+        })
+    }
+
+    fn paths(queries: &[&str]) -> Vec<JsonPath> {
+        queries
+            .iter()
+            .map(|q| JsonPath::parse(q).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_project_titles() {
+        let result = project(&bookstore(), &paths(&["$.store.book[*].title"]));
+        assert_eq!(
+            result,
+            json!({"store": {"book": [
+                {"title": "Sayings of the Century"},
+                {"title": "Sword of Honour"}
+            ]}})
+        );
+    }
+
+    #[test]
+    fn test_project_multiple_queries() {
+        let result = project(
+            &bookstore(),
+            &paths(&["$.store.bicycle.color", "$.store.book[1].price"]),
+        );
+        assert_eq!(
+            result,
+            json!({"store": {"book": [{"price": 12.99}], "bicycle": {"color": "red"}}})
+        );
+    }
+
+    #[test]
+    fn test_project_compacts_arrays() {
+        let json = json!({"a": [1, 2, 3, 4]});
+        let result = project(&json, &paths(&["$.a[1]", "$.a[3]"]));
+        assert_eq!(result, json!({"a": [2, 4]}));
+    }
+
+    #[test]
+    fn test_project_preserve_indices() {
+        let json = json!({"a": [1, 2, 3, 4]});
+        let options = ProjectOptions {
+            preserve_indices: true,
+        };
+        let result = project_with_options(&json, &paths(&["$.a[1]", "$.a[3]"]), &options);
+        assert_eq!(result, json!({"a": [null, 2, null, 4]}));
+    }
+
+    #[test]
+    fn test_project_ancestor_match_keeps_subtree() {
+        // $.store.bicycle is an ancestor of $..color, so the whole bicycle is kept
+        let result = project(&bookstore(), &paths(&["$..color", "$.store.bicycle"]));
+        assert_eq!(
+            result,
+            json!({"store": {"bicycle": {"color": "red", "price": 19.95}}})
+        );
+    }
+
+    #[test]
+    fn test_project_root_match() {
+        let json = bookstore();
+        assert_eq!(project(&json, &paths(&["$"])), json);
+    }
+
+    #[test]
+    fn test_project_no_match() {
+        assert_eq!(project(&bookstore(), &paths(&["$.missing"])), json!({}));
+        assert_eq!(project(&json!([1]), &paths(&["$[5]"])), json!([]));
+        assert_eq!(project(&json!([1]), &[]), json!([]));
+    }
+
+    #[test]
+    fn test_project_filter() {
+        let result = project(&bookstore(), &paths(&["$.store.book[?@.price > 10]"]));
+        assert_eq!(
+            result,
+            json!({"store": {"book": [
+                {"category": "fiction", "title": "Sword of Honour", "price": 12.99}
+            ]}})
+        );
+    }
+}
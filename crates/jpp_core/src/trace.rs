@@ -0,0 +1,267 @@
+//! Evaluation trace: per-segment node counts for explaining query results
+//!
+//! Tracing uses its own evaluation loop so the regular evaluator carries no overhead.
+
+use crate::ast::{JsonPath, Segment, Selector};
+use crate::eval::{Children, RootQueries, candidate_matches, evaluate_selector, walk_descendants};
+use serde_json::Value;
+use std::ops::ControlFlow;
+
+/// Trace of a query evaluation, see [`JsonPath::query_trace`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct QueryTrace {
+    /// One entry per segment, in query order
+    pub segments: Vec<SegmentTrace>,
+    /// Number of nodes in the final result
+    pub result_count: usize,
+}
+
+/// Trace of a single segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SegmentTrace {
+    /// The segment in bracket notation, e.g. `['book']` or `..[*]`
+    pub segment: String,
+    /// Number of nodes the segment was applied to
+    pub input_count: usize,
+    /// Number of nodes the segment produced
+    pub output_count: usize,
+    /// Number of nodes the selectors were applied to: the input nodes for a child segment,
+    /// the input nodes and all their descendants for a descendant segment
+    pub visited_count: usize,
+    /// One entry per selector, in segment order
+    pub selectors: Vec<SelectorTrace>,
+}
+
+/// Trace of a single selector within a segment
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct SelectorTrace {
+    /// The selector, e.g. `'book'`, `0`, `1:3` or `?@['price'] < 10`
+    pub selector: String,
+    /// Number of nodes the selector contributed to the segment output
+    pub match_count: usize,
+    /// Candidate counts, for filter selectors only
+    pub filter: Option<FilterTrace>,
+}
+
+/// Candidate counts of a filter selector
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FilterTrace {
+    /// Candidates for which the filter expression was true
+    pub passed: usize,
+    /// Candidates for which the filter expression was false
+    pub failed: usize,
+}
+
+impl QueryTrace {
+    /// The first segment that produced no nodes from a non-empty input, if any
+    pub fn first_empty_segment(&self) -> Option<(usize, &SegmentTrace)> {
+        self.segments
+            .iter()
+            .enumerate()
+            .find(|(_, segment)| segment.input_count > 0 && segment.output_count == 0)
+    }
+}
+
+/// Evaluate a query while recording a [`QueryTrace`]
+pub fn trace(path: &JsonPath, root: &Value) -> QueryTrace {
+    let mut current = vec![root];
+    let mut segments = Vec::with_capacity(path.segments.len());
+
+    for segment in &path.segments {
+        let selectors = segment.selectors();
+        let mut trace = SegmentTrace {
+            segment: segment.to_string(),
+            input_count: current.len(),
+            output_count: 0,
+            visited_count: 0,
+            selectors: selectors
+                .iter()
+                .map(|selector| SelectorTrace {
+                    selector: selector.to_string(),
+                    match_count: 0,
+                    filter: matches!(selector, Selector::Filter(_)).then(FilterTrace::default),
+                })
+                .collect(),
+        };
+
+        let mut next = Vec::new();
+        for node in &current {
+            match segment {
+                Segment::Child(_) => trace_node(selectors, node, root, &mut trace, &mut next),
+                Segment::Descendant(_) => {
+                    trace_descendants(selectors, node, root, &mut trace, &mut next)
+                }
+            }
+        }
+        trace.output_count = next.len();
+        segments.push(trace);
+        current = next;
+    }
+
+    QueryTrace {
+        segments,
+        result_count: current.len(),
+    }
+}
+
+/// Apply the selectors of a segment to one node, recording counts
+fn trace_node<'a>(
+    selectors: &[Selector],
+    node: &'a Value,
+    root: &'a Value,
+    trace: &mut SegmentTrace,
+    results: &mut Vec<&'a Value>,
+) {
+    trace.visited_count += 1;
+    for (selector, selector_trace) in selectors.iter().zip(&mut trace.selectors) {
+        let before = results.len();
+        if let (Selector::Filter(expr), Some(filter)) = (selector, &mut selector_trace.filter) {
+            let roots = RootQueries::of(expr);
+            for (key, candidate) in Children::of(node).into_iter().flatten() {
+                if candidate_matches(expr, node, key, candidate, root, &roots) {
+                    filter.passed += 1;
+                    results.push(candidate);
+                } else {
+                    filter.failed += 1;
+                }
+            }
+        } else {
+            results.extend(evaluate_selector(selector, node, root));
+        }
+        selector_trace.match_count += results.len() - before;
+    }
+}
+
+/// Apply the selectors of a descendant segment to a node and all its descendants
+fn trace_descendants<'a>(
+    selectors: &[Selector],
+    node: &'a Value,
+    root: &'a Value,
+    trace: &mut SegmentTrace,
+    results: &mut Vec<&'a Value>,
+) {
+    let _ = walk_descendants(node, &mut (), false, &mut |_, node| {
+        trace_node(selectors, node, root, trace, results);
+        ControlFlow::Continue(())
+    });
+}
+
+#[cfg(test)]
+#[allow(clippy::unwrap_used)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn bookstore() -> Value {
+        json!({
+            "store": {
+                "book": [
+                    {"title": "A", "price": 8},
+                    {"title": "B", "price": 12},
+                    {"title": "C", "price": 9, "isbn": "x"}
+                ],
+                "bicycle": {"price": 19}
+            }
+        })
+    }
+
+    #[test]
+    fn test_trace_counts_per_segment() {
+        let path = JsonPath::parse("$.store.book[?@.price < 10].title").unwrap();
+        let trace = path.query_trace(&bookstore());
+
+        let counts: Vec<(usize, usize)> = trace
+            .segments
+            .iter()
+            .map(|s| (s.input_count, s.output_count))
+            .collect();
+        assert_eq!(counts, vec![(1, 1), (1, 1), (1, 2), (2, 2)]);
+        assert_eq!(trace.result_count, 2);
+
+        let filter = &trace.segments[2].selectors[0];
+        assert_eq!(filter.selector, "?@['price'] < 10");
+        assert_eq!(filter.match_count, 2);
+        assert_eq!(
+            filter.filter,
+            Some(FilterTrace {
+                passed: 2,
+                failed: 1
+            })
+        );
+        assert_eq!(trace.segments[3].selectors[0].filter, None);
+    }
+
+    #[test]
+    fn test_trace_selector_contributions() {
+        let path = JsonPath::parse("$.store.book[0, 5, -1, 1:]").unwrap();
+        let trace = path.query_trace(&bookstore());
+        let matches: Vec<(&str, usize)> = trace.segments[2]
+            .selectors
+            .iter()
+            .map(|s| (s.selector.as_str(), s.match_count))
+            .collect();
+        assert_eq!(matches, vec![("0", 1), ("5", 0), ("-1", 1), ("1:", 2)]);
+        assert_eq!(trace.segments[2].output_count, 4);
+    }
+
+    #[test]
+    fn test_trace_descendant() {
+        let json = bookstore();
+        let path = JsonPath::parse("$..price").unwrap();
+        let trace = path.query_trace(&json);
+        let segment = &trace.segments[0];
+        assert_eq!(segment.segment, "..['price']");
+        assert_eq!(segment.input_count, 1);
+        assert_eq!(segment.output_count, 4);
+        // root, store, book, 3 books and their 7 members, bicycle and its price
+        assert_eq!(segment.visited_count, 15);
+        assert_eq!(trace.result_count, path.query(&json).len());
+    }
+
+    #[test]
+    fn test_first_empty_segment() {
+        let path = JsonPath::parse("$.store.books[*].title").unwrap();
+        let trace = path.query_trace(&bookstore());
+        let (index, segment) = trace.first_empty_segment().unwrap();
+        assert_eq!(index, 1);
+        assert_eq!(segment.segment, "['books']");
+        assert_eq!(trace.segments[2].input_count, 0);
+        assert_eq!(trace.result_count, 0);
+
+        let path = JsonPath::parse("$.store.book[*]").unwrap();
+        assert!(
+            path.query_trace(&bookstore())
+                .first_empty_segment()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_trace_matches_query() {
+        let json = bookstore();
+        for query in ["$", "$..*", "$..[?@.price > 8]", "$.store[*][0, 'price']"] {
+            let path = JsonPath::parse(query).unwrap();
+            assert_eq!(
+                path.query_trace(&json).result_count,
+                path.query(&json).len(),
+                "query {query}"
+            );
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_trace_serializes() {
+        let path = JsonPath::parse("$.store.book[?@.isbn]").unwrap();
+        let value = serde_json::to_value(path.query_trace(&bookstore())).unwrap();
+        assert_eq!(value["result_count"], json!(1));
+        assert_eq!(
+            value["segments"][2]["selectors"][0]["filter"],
+            json!({"passed": 1, "failed": 2})
+        );
+    }
+}
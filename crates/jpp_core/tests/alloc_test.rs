@@ -0,0 +1,74 @@
+//! Counts heap allocations during evaluation, so segments keep streaming nodes to each other
+//! instead of collecting them into intermediate lists
+
+#![allow(clippy::unwrap_used)]
+
+use jpp_core::JsonPath;
+use serde_json::{Value, json};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+/// System allocator that counts allocations made by the current thread
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+// SAFETY: defers to the system allocator; the counter is a const-initialized thread local,
+// which neither allocates nor needs a destructor
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        // SAFETY: same contract as this method
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        // SAFETY: same contract as this method
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Allocations made by `f` on this thread
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, T) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (ALLOCATIONS.with(Cell::get) - before, result)
+}
+
+fn document() -> Value {
+    let items: Vec<Value> = (0..1000)
+        .map(|i| json!({"name": format!("item {i}"), "price": i % 20, "tags": ["a", "b", "c"]}))
+        .collect();
+    json!({"store": {"items": items}})
+}
+
+#[test]
+fn test_evaluation_allocates_only_the_result() {
+    let json = document();
+    let queries = [
+        ("$.store.items[*].tags[*]", 3000),
+        ("$.store.items[?@.price < 10].name", 500),
+        ("$..items[1:]..[0]", 999),
+        ("$..tags[?@ == 'b']", 1000),
+        ("$.store.items[?@.tags[*]].name", 1000),
+        ("$.store.items[?count(@.tags[*]) == 3].price", 1000),
+        ("$.store.items[?@..[?@ == 'c']]", 1000),
+        ("$.store.items[*].price", 1000),
+    ];
+    for (query, count) in queries {
+        let path = JsonPath::parse(query).unwrap();
+        let (allocated, results) = allocations(|| path.query(&json));
+        assert_eq!(results.len(), count, "{query}");
+        // Growing the result vector doubles its capacity each time
+        let growth = usize::BITS - count.leading_zeros();
+        assert!(
+            allocated <= growth as usize + 1,
+            "{query}: {allocated} allocations for {count} results"
+        );
+    }
+}
@@ -5,7 +5,7 @@
 
 #![allow(clippy::expect_used)]
 
-use jpp_core::query;
+use jpp_core::{JsonPath, ParseOptions};
 use serde::Deserialize;
 use serde_json::Value;
 use std::fs;
@@ -35,7 +35,10 @@ struct CtsFile {
 }
 
 /// Run a single CTS test and return (passed, skip_reason)
-fn run_cts_test(test: &CtsTest) -> (bool, Option<String>) {
+fn run_cts_test(test: &CtsTest, options: &ParseOptions) -> (bool, Option<String>) {
+    let query = |selector: &str, document| {
+        JsonPath::parse_with_options(selector, options).map(|(path, _)| path.query(document))
+    };
     // If test expects invalid selector
     if test.invalid_selector {
         match query(&test.selector, &test.document) {
@@ -80,6 +83,19 @@ fn run_cts_test(test: &CtsTest) -> (bool, Option<String>) {
 
 #[test]
 fn run_cts_tests() {
+    run_cts_suite(&ParseOptions::default());
+}
+
+/// Strict I-Regexp mode must pass the same suite: every CTS pattern is I-Regexp
+#[test]
+fn run_cts_strict_iregexp_tests() {
+    run_cts_suite(&ParseOptions {
+        strict_iregexp: true,
+        ..ParseOptions::default()
+    });
+}
+
+fn run_cts_suite(options: &ParseOptions) {
     // Load CTS file
     let cts_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/cts/cts.json");
     let cts_content =
@@ -93,7 +109,7 @@ fn run_cts_tests() {
     let mut failed_tests: Vec<(String, String)> = vec![];
 
     for test in &cts.tests {
-        let (success, reason) = run_cts_test(test);
+        let (success, reason) = run_cts_test(test, options);
         if success {
             passed += 1;
         } else {
@@ -141,6 +157,34 @@ fn run_cts_tests() {
     );
 }
 
+/// Test that `JsonPath::optimize` keeps the results of every valid CTS query
+#[test]
+fn run_cts_optimize_tests() {
+    let cts_path = concat!(env!("CARGO_MANIFEST_DIR"), "/../../tests/cts/cts.json");
+    let cts_content = fs::read_to_string(cts_path).expect("Failed to read CTS file");
+    let cts: CtsFile = serde_json::from_str(&cts_content).expect("Failed to parse CTS JSON");
+
+    for test in cts.tests.iter().filter(|t| !t.invalid_selector) {
+        let path = JsonPath::parse(&test.selector).expect("Failed to parse CTS selector");
+        let optimized = path.clone().optimize();
+        assert_eq!(
+            optimized.query_located(&test.document),
+            path.query_located(&test.document),
+            "{} ({} optimized to {})",
+            test.name,
+            test.selector,
+            optimized
+        );
+        // The optimized query must remain valid
+        assert_eq!(
+            JsonPath::parse(&optimized.to_string()).expect("Optimized query must parse"),
+            optimized,
+            "{}",
+            test.name
+        );
+    }
+}
+
 /// Test that CTS file loads correctly
 #[test]
 fn test_cts_file_loads() {
@@ -0,0 +1,69 @@
+//! Checks that `JsonPath::optimize` keeps the results of queries on the benchmark fixtures
+
+#![allow(clippy::expect_used)]
+
+use jpp_core::JsonPath;
+use serde_json::Value;
+use std::fs;
+
+/// Queries with and without applicable rewrites
+const QUERIES: &[&str] = &[
+    "$",
+    "$..*",
+    "$..[0:1]",
+    "$..[-1:]",
+    "$.store.book[0:1].title",
+    "$.store.book[?@.price == @.price]",
+    "$.store.book[?@.price < 10 && @.category == @.category]",
+    "$.store.book[?@.isbn != @.isbn, -1:]",
+    "$..[?!!@.price]",
+    "$..[?1 == 1 || @.isbn]",
+    "$.items[?@.in_stock && !(@.id > @.id)].name",
+    "$.items[?@.tags[?@ == @]]",
+    "$.items[0:1, 0, ?@.id == @.id][*]",
+    "$..a..value",
+    "$..[?@.value == @.value, 0:1]",
+];
+
+fn check_fixture(name: &str) {
+    let path = format!("{}/../jpp_bench/data/{name}", env!("CARGO_MANIFEST_DIR"));
+    let content = fs::read_to_string(&path).expect("Failed to read fixture");
+    let json: Value = serde_json::from_str(&content).expect("Failed to parse fixture");
+
+    for query in QUERIES {
+        let path = JsonPath::parse(query).expect("Failed to parse query");
+        let optimized = path.clone().optimize();
+        // Compare node identities, so order and duplicates must match exactly
+        let nodes = |path: &JsonPath| -> Vec<*const Value> {
+            path.query(&json)
+                .into_iter()
+                .map(|v| v as *const Value)
+                .collect()
+        };
+        assert_eq!(
+            nodes(&optimized),
+            nodes(&path),
+            "{query} (optimized: {optimized}) on {name}"
+        );
+    }
+}
+
+#[test]
+fn optimize_preserves_results_small() {
+    check_fixture("small.json");
+}
+
+#[test]
+fn optimize_preserves_results_medium() {
+    check_fixture("medium.json");
+}
+
+#[test]
+fn optimize_preserves_results_deep() {
+    check_fixture("deep.json");
+}
+
+#[test]
+fn optimize_preserves_results_large() {
+    check_fixture("large.json");
+}